@@ -0,0 +1,299 @@
+//! The debugger side of a session: a [`DebugHook`] that decides where to stop
+//! and, while stopped, answers inspection requests from the adapter thread.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use fusabi_vm::{DebugHook, Vm, VmError};
+use serde_json::{json, Value as Json};
+
+use crate::variables::Variables;
+
+/// Why execution stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    Entry,
+    Breakpoint,
+    Step,
+    Pause,
+    Exception(String),
+}
+
+impl StopReason {
+    /// The DAP `reason` of the `stopped` event
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopReason::Entry => "entry",
+            StopReason::Breakpoint => "breakpoint",
+            StopReason::Step => "step",
+            StopReason::Pause => "pause",
+            StopReason::Exception(_) => "exception",
+        }
+    }
+}
+
+/// How to continue from a stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    StepIn,
+    StepOver,
+    StepOut,
+}
+
+/// A question about the paused VM
+#[derive(Debug, Clone)]
+pub enum Inspect {
+    StackTrace,
+    Scopes {
+        frame_id: usize,
+    },
+    Variables {
+        reference: i64,
+    },
+    Evaluate {
+        frame_id: Option<usize>,
+        expression: String,
+    },
+}
+
+/// Messages from the adapter to the paused VM thread
+#[derive(Debug)]
+pub enum Command {
+    Resume(Resume),
+    Inspect(Inspect, Sender<Result<Json, String>>),
+    Terminate,
+}
+
+/// Messages from the VM thread to the adapter
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugEvent {
+    Stopped(StopReason),
+    Output(String),
+    /// Execution finished, with the result value or the error message
+    Exited(Result<String, String>),
+}
+
+/// State shared between the adapter and the debugger hook
+#[derive(Debug, Clone, Default)]
+pub struct Controls {
+    /// Lines with breakpoints in the debugged program
+    pub breakpoints: Arc<Mutex<BTreeSet<u32>>>,
+    /// Set by the adapter to stop at the next line
+    pub pause: Arc<AtomicBool>,
+    /// Set by the adapter to end the session
+    pub terminate: Arc<AtomicBool>,
+    /// Set by the hook when it stops; cleared by the adapter when it resumes.
+    /// Inspection requests are only sent while this is set.
+    pub stopped: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Entry,
+    Run,
+    StepIn { depth: usize, line: u32 },
+    StepOver { depth: usize, line: u32 },
+    StepOut { depth: usize },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    depth: usize,
+    line: u32,
+    ip: usize,
+}
+
+/// Debug hook driving one session
+pub struct Debugger {
+    controls: Controls,
+    // Behind mutexes only so the hook is `Sync`; the VM thread alone uses them
+    commands: Mutex<Receiver<Command>>,
+    events: Mutex<Sender<DebugEvent>>,
+    source_path: String,
+    mode: Mode,
+    last: Option<Location>,
+    error_reported: bool,
+    terminated: bool,
+    variables: Variables,
+}
+
+impl Debugger {
+    pub fn new(
+        controls: Controls,
+        commands: Receiver<Command>,
+        events: Sender<DebugEvent>,
+        source_path: impl Into<String>,
+        stop_on_entry: bool,
+    ) -> Self {
+        Debugger {
+            controls,
+            commands: Mutex::new(commands),
+            events: Mutex::new(events),
+            source_path: source_path.into(),
+            mode: if stop_on_entry {
+                Mode::Entry
+            } else {
+                Mode::Run
+            },
+            last: None,
+            error_reported: false,
+            terminated: false,
+            variables: Variables::new(),
+        }
+    }
+
+    fn should_stop(&mut self, location: Location) -> Option<StopReason> {
+        if self.controls.pause.swap(false, Ordering::SeqCst) {
+            return Some(StopReason::Pause);
+        }
+        if self.mode == Mode::Entry {
+            return Some(StopReason::Entry);
+        }
+        if self
+            .controls
+            .breakpoints
+            .lock()
+            .unwrap()
+            .contains(&location.line)
+        {
+            return Some(StopReason::Breakpoint);
+        }
+        let step = match self.mode {
+            Mode::StepIn { depth, line } => location.depth != depth || location.line != line,
+            Mode::StepOver { depth, line } => {
+                location.depth < depth || (location.depth == depth && location.line != line)
+            }
+            Mode::StepOut { depth } => location.depth < depth,
+            Mode::Entry | Mode::Run => false,
+        };
+        step.then_some(StopReason::Step)
+    }
+
+    /// Report the stop and serve the adapter until it resumes
+    fn stop(&mut self, vm: &Vm, reason: StopReason) -> Result<(), VmError> {
+        self.variables
+            .set_faulted(matches!(reason, StopReason::Exception(_)));
+        self.controls.stopped.store(true, Ordering::SeqCst);
+        if self
+            .events
+            .lock()
+            .unwrap()
+            .send(DebugEvent::Stopped(reason))
+            .is_err()
+        {
+            self.terminated = true;
+            return Err(terminated());
+        }
+
+        loop {
+            let command = self.commands.lock().unwrap().recv();
+            match command {
+                Ok(Command::Inspect(request, reply)) => {
+                    let _ = reply.send(self.inspect(vm, request));
+                }
+                Ok(Command::Resume(resume)) => {
+                    self.variables.clear();
+                    self.mode = self.mode_for(vm, resume);
+                    return Ok(());
+                }
+                Ok(Command::Terminate) | Err(_) => {
+                    self.terminated = true;
+                    return Err(terminated());
+                }
+            }
+        }
+    }
+
+    fn mode_for(&self, vm: &Vm, resume: Resume) -> Mode {
+        let depth = vm.frames().len();
+        let line = self.last.map_or(0, |l| l.line);
+        match resume {
+            Resume::Continue => Mode::Run,
+            Resume::StepIn => Mode::StepIn { depth, line },
+            Resume::StepOver => Mode::StepOver { depth, line },
+            Resume::StepOut => Mode::StepOut { depth },
+        }
+    }
+
+    fn inspect(&mut self, vm: &Vm, request: Inspect) -> Result<Json, String> {
+        match request {
+            Inspect::StackTrace => Ok(self.variables.stack_trace(vm, &self.source_path)),
+            Inspect::Scopes { frame_id } => self
+                .variables
+                .scopes(vm, frame_id)
+                .map(|scopes| json!({ "scopes": scopes }))
+                .ok_or_else(|| format!("Unknown frame {}", frame_id)),
+            Inspect::Variables { reference } => self
+                .variables
+                .variables(vm, reference)
+                .map(|variables| json!({ "variables": variables }))
+                .ok_or_else(|| format!("Unknown variable reference {}", reference)),
+            Inspect::Evaluate {
+                frame_id,
+                expression,
+            } => {
+                let frame_id = frame_id.unwrap_or(vm.frames().len().saturating_sub(1));
+                self.variables
+                    .evaluate(vm, frame_id, expression.trim())
+                    .ok_or_else(|| format!("Cannot evaluate '{}'", expression))
+            }
+        }
+    }
+}
+
+impl DebugHook for Debugger {
+    fn on_instruction(&mut self, vm: &Vm) -> Result<(), VmError> {
+        self.error_reported = false;
+        if self.controls.terminate.load(Ordering::SeqCst) {
+            self.terminated = true;
+            return Err(terminated());
+        }
+        let Some(frame) = vm.frames().last() else {
+            return Ok(());
+        };
+        // Instructions without a source line are never stopping points
+        let Some(span) = frame.closure.chunk.span_at(frame.ip) else {
+            return Ok(());
+        };
+        let location = Location {
+            depth: vm.frames().len(),
+            line: span.line,
+            ip: frame.ip,
+        };
+
+        // Only stop when arriving at a line: from another line, another
+        // frame, or by jumping back (a loop iteration on one line).
+        let arrived = match self.last {
+            Some(last) => {
+                last.depth != location.depth || last.line != location.line || location.ip < last.ip
+            }
+            None => true,
+        };
+        self.last = Some(location);
+        if !arrived {
+            return Ok(());
+        }
+
+        match self.should_stop(location) {
+            Some(reason) => self.stop(vm, reason),
+            None => Ok(()),
+        }
+    }
+
+    fn on_error(&mut self, vm: &Vm, error: &VmError) {
+        // A failing nested call (e.g. a closure run by List.map) is reported
+        // by each enclosing `run`; stop only once.
+        if self.error_reported || self.terminated {
+            return;
+        }
+        self.error_reported = true;
+        let _ = self.stop(vm, StopReason::Exception(error.to_string()));
+    }
+}
+
+fn terminated() -> VmError {
+    VmError::Runtime("Debug session terminated".to_string())
+}
@@ -0,0 +1,17 @@
+//! Fusabi Debug Adapter Protocol Implementation
+//!
+//! Lets editors debug Fusabi scripts: line breakpoints, stepping in, over and
+//! out of functions, stack traces, and inspection of locals, upvalues and
+//! globals as expandable value trees. Runtime errors pause the program before
+//! it exits.
+//!
+//! Scripts are compiled with source spans (see `Parser::with_spans`) and run
+//! under a [`fusabi_vm::DebugHook`].
+
+pub mod debugger;
+pub mod protocol;
+pub mod server;
+pub mod session;
+pub mod variables;
+
+pub use server::Server;
@@ -0,0 +1,9 @@
+use std::io;
+
+use fusabi_dap::Server;
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    Server::new(io::stdout()).serve(&mut input)
+}
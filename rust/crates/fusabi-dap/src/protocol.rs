@@ -0,0 +1,114 @@
+//! Debug Adapter Protocol wire format
+//!
+//! DAP messages are JSON objects framed by a `Content-Length` header, the same
+//! base protocol LSP uses.
+
+use std::io::{self, BufRead, Write};
+
+use serde::Deserialize;
+use serde_json::{json, Value as Json};
+
+/// A request from the client
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    pub seq: i64,
+    pub command: String,
+    #[serde(default)]
+    pub arguments: Json,
+}
+
+/// Read one framed message, returning `None` at end of input
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let length = value.trim().parse::<usize>().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length")
+                })?;
+                content_length = Some(length);
+            }
+        }
+    }
+
+    let length = content_length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Write one framed message
+pub fn write_message(writer: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Build a successful response to `request`
+pub fn response(seq: i64, request: &Request, body: Json) -> Json {
+    json!({
+        "seq": seq,
+        "type": "response",
+        "request_seq": request.seq,
+        "success": true,
+        "command": request.command,
+        "body": body,
+    })
+}
+
+/// Build a failed response to `request`
+pub fn error_response(seq: i64, request: &Request, message: &str) -> Json {
+    json!({
+        "seq": seq,
+        "type": "response",
+        "request_seq": request.seq,
+        "success": false,
+        "command": request.command,
+        "message": message,
+    })
+}
+
+/// Build an event
+pub fn event(seq: i64, name: &str, body: Json) -> Json {
+    json!({
+        "seq": seq,
+        "type": "event",
+        "event": name,
+        "body": body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut buffer = Vec::new();
+        let message = json!({"seq": 1, "type": "request", "command": "threads"});
+        write_message(&mut buffer, &message).unwrap();
+        write_message(&mut buffer, &message).unwrap();
+
+        let mut reader = io::BufReader::new(&buffer[..]);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message.clone()));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_missing_content_length() {
+        let mut reader = io::BufReader::new(&b"Content-Type: json\r\n\r\n{}"[..]);
+        assert!(read_message(&mut reader).is_err());
+    }
+}
@@ -0,0 +1,559 @@
+//! Request handling
+//!
+//! The adapter thread reads requests and answers them; the program runs on its
+//! own thread (see [`crate::session`]), and a forwarding thread turns its
+//! [`DebugEvent`]s into DAP events. Inspection requests are passed to the
+//! program thread, which answers them while it is stopped.
+
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde::Deserialize;
+use serde_json::{json, Value as Json};
+
+use crate::debugger::{Command, Controls, DebugEvent, Inspect, Resume, StopReason};
+use crate::protocol::{error_response, event, read_message, response, write_message, Request};
+use crate::session::{self, Program};
+
+/// The only thread the adapter reports
+const THREAD_ID: i64 = 1;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LaunchArguments {
+    program: String,
+    #[serde(default)]
+    stop_on_entry: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetBreakpointsArguments {
+    source: Source,
+    #[serde(default)]
+    breakpoints: Vec<SourceBreakpoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Source {
+    path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SourceBreakpoint {
+    line: u32,
+}
+
+/// A debug adapter writing to `W`
+pub struct Server<W: Write + Send + 'static> {
+    output: Arc<Mutex<W>>,
+    seq: Arc<AtomicI64>,
+    controls: Controls,
+    program: Option<Program>,
+    stop_on_entry: bool,
+    commands: Option<Sender<Command>>,
+}
+
+impl<W: Write + Send + 'static> Server<W> {
+    pub fn new(output: W) -> Self {
+        Server {
+            output: Arc::new(Mutex::new(output)),
+            seq: Arc::new(AtomicI64::new(1)),
+            controls: Controls::default(),
+            program: None,
+            stop_on_entry: false,
+            commands: None,
+        }
+    }
+
+    /// Handle requests until the client disconnects or the input ends
+    pub fn serve(&mut self, input: &mut impl BufRead) -> io::Result<()> {
+        while let Some(message) = read_message(input)? {
+            let Ok(request) = serde_json::from_value::<Request>(message) else {
+                continue;
+            };
+            if !self.handle(&request)? {
+                break;
+            }
+        }
+        self.terminate();
+        Ok(())
+    }
+
+    /// Handle one request; returns false once the session is over
+    fn handle(&mut self, request: &Request) -> io::Result<bool> {
+        let args = &request.arguments;
+        let result = match request.command.as_str() {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsTerminateRequest": true,
+                "supportsEvaluateForHovers": true,
+            })),
+            "launch" => {
+                let result = self.launch(args);
+                let launched = result.is_ok();
+                self.reply(request, result)?;
+                if launched {
+                    self.send_event("initialized", json!({}))?;
+                }
+                return Ok(true);
+            }
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "configurationDone" => {
+                self.reply(request, Ok(json!({})))?;
+                self.start();
+                return Ok(true);
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => self.inspect(Inspect::StackTrace),
+            "scopes" => self.inspect(Inspect::Scopes {
+                frame_id: args["frameId"].as_u64().unwrap_or(0) as usize,
+            }),
+            "variables" => self.inspect(Inspect::Variables {
+                reference: args["variablesReference"].as_i64().unwrap_or(0),
+            }),
+            "evaluate" => self.inspect(Inspect::Evaluate {
+                frame_id: args["frameId"].as_u64().map(|id| id as usize),
+                expression: args["expression"].as_str().unwrap_or_default().to_string(),
+            }),
+            "continue" | "next" | "stepIn" | "stepOut" => {
+                let resume = match request.command.as_str() {
+                    "continue" => Resume::Continue,
+                    "next" => Resume::StepOver,
+                    "stepIn" => Resume::StepIn,
+                    _ => Resume::StepOut,
+                };
+                // Answer before resuming so the response precedes the next stop
+                self.reply(request, Ok(json!({ "allThreadsContinued": true })))?;
+                self.resume(resume);
+                return Ok(true);
+            }
+            "pause" => {
+                self.controls.pause.store(true, Ordering::SeqCst);
+                Ok(json!({}))
+            }
+            "terminate" => {
+                self.terminate();
+                Ok(json!({}))
+            }
+            "disconnect" => {
+                self.terminate();
+                self.reply(request, Ok(json!({})))?;
+                return Ok(false);
+            }
+            other => Err(format!("Unsupported request '{}'", other)),
+        };
+        self.reply(request, result)?;
+        Ok(true)
+    }
+
+    fn launch(&mut self, args: &Json) -> Result<Json, String> {
+        let args: LaunchArguments =
+            serde_json::from_value(args.clone()).map_err(|e| e.to_string())?;
+        self.program = Some(Program::load(&args.program)?);
+        self.stop_on_entry = args.stop_on_entry;
+        Ok(json!({}))
+    }
+
+    fn set_breakpoints(&mut self, args: &Json) -> Result<Json, String> {
+        let args: SetBreakpointsArguments =
+            serde_json::from_value(args.clone()).map_err(|e| e.to_string())?;
+        let path = args.source.path.unwrap_or_default();
+
+        let Some(program) = self.program.as_ref().filter(|p| p.is_source(&path)) else {
+            let breakpoints: Vec<Json> = args
+                .breakpoints
+                .iter()
+                .map(|bp| {
+                    json!({
+                        "verified": false,
+                        "line": bp.line,
+                        "message": "Not part of the program being debugged",
+                    })
+                })
+                .collect();
+            return Ok(json!({ "breakpoints": breakpoints }));
+        };
+
+        let mut lines = self.controls.breakpoints.lock().unwrap();
+        lines.clear();
+        let breakpoints: Vec<Json> = args
+            .breakpoints
+            .iter()
+            .map(|bp| match program.breakpoint_line(bp.line) {
+                Some(line) => {
+                    lines.insert(line);
+                    json!({ "verified": true, "line": line })
+                }
+                None => json!({
+                    "verified": false,
+                    "line": bp.line,
+                    "message": "No code at or after this line",
+                }),
+            })
+            .collect();
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    /// Run the launched program
+    fn start(&mut self) {
+        let Some(program) = self.program.clone() else {
+            return;
+        };
+        let session = session::spawn(program, self.controls.clone(), self.stop_on_entry);
+        self.commands = Some(session.commands);
+        let output = self.output.clone();
+        let seq = self.seq.clone();
+        thread::spawn(move || forward_events(session.events, output, seq));
+    }
+
+    fn inspect(&self, request: Inspect) -> Result<Json, String> {
+        let commands = self.commands.as_ref().ok_or("Program is not running")?;
+        if !self.controls.stopped.load(Ordering::SeqCst) {
+            return Err("Program is not paused".to_string());
+        }
+        let (reply_tx, reply_rx) = mpsc::channel();
+        commands
+            .send(Command::Inspect(request, reply_tx))
+            .map_err(|_| "Program has exited")?;
+        reply_rx.recv().map_err(|_| "Program has exited")?
+    }
+
+    fn resume(&mut self, resume: Resume) {
+        if let Some(commands) = &self.commands {
+            if self.controls.stopped.swap(false, Ordering::SeqCst) {
+                let _ = commands.send(Command::Resume(resume));
+            }
+        }
+    }
+
+    fn terminate(&mut self) {
+        self.controls.terminate.store(true, Ordering::SeqCst);
+        if let Some(commands) = self.commands.take() {
+            self.controls.stopped.store(false, Ordering::SeqCst);
+            let _ = commands.send(Command::Terminate);
+        }
+    }
+
+    fn reply(&self, request: &Request, result: Result<Json, String>) -> io::Result<()> {
+        let seq = self.seq.fetch_add(1, Ordering::SeqCst);
+        let message = match result {
+            Ok(body) => response(seq, request, body),
+            Err(message) => error_response(seq, request, &message),
+        };
+        write_message(&mut *self.output.lock().unwrap(), &message)
+    }
+
+    fn send_event(&self, name: &str, body: Json) -> io::Result<()> {
+        send_event(&self.output, &self.seq, name, body)
+    }
+}
+
+fn send_event<W: Write>(
+    output: &Mutex<W>,
+    seq: &AtomicI64,
+    name: &str,
+    body: Json,
+) -> io::Result<()> {
+    let seq = seq.fetch_add(1, Ordering::SeqCst);
+    write_message(&mut *output.lock().unwrap(), &event(seq, name, body))
+}
+
+/// Report what the program thread does until it exits
+fn forward_events<W: Write>(
+    events: Receiver<DebugEvent>,
+    output: Arc<Mutex<W>>,
+    seq: Arc<AtomicI64>,
+) {
+    for debug_event in events {
+        let sent = match debug_event {
+            DebugEvent::Stopped(reason) => {
+                let mut body = json!({
+                    "reason": reason.as_str(),
+                    "threadId": THREAD_ID,
+                    "allThreadsStopped": true,
+                });
+                if let StopReason::Exception(message) = &reason {
+                    body["description"] = json!(message);
+                    body["text"] = json!(message);
+                }
+                send_event(&output, &seq, "stopped", body)
+            }
+            DebugEvent::Output(text) => send_event(
+                &output,
+                &seq,
+                "output",
+                json!({ "category": "stdout", "output": text }),
+            ),
+            DebugEvent::Exited(result) => {
+                let exit_code = match result {
+                    Ok(_) => 0,
+                    Err(message) => {
+                        let _ = send_event(
+                            &output,
+                            &seq,
+                            "output",
+                            json!({ "category": "stderr", "output": format!("{}\n", message) }),
+                        );
+                        1
+                    }
+                };
+                let _ = send_event(&output, &seq, "exited", json!({ "exitCode": exit_code }));
+                let _ = send_event(&output, &seq, "terminated", json!({}));
+                return;
+            }
+        };
+        if sent.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Read};
+    use std::time::Duration;
+
+    struct ChannelReader {
+        rx: Receiver<Vec<u8>>,
+        buffer: Vec<u8>,
+    }
+
+    impl Read for ChannelReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.buffer.is_empty() {
+                match self.rx.recv_timeout(Duration::from_secs(10)) {
+                    Ok(bytes) => self.buffer = bytes,
+                    Err(_) => return Ok(0),
+                }
+            }
+            let n = buf.len().min(self.buffer.len());
+            buf[..n].copy_from_slice(&self.buffer[..n]);
+            self.buffer.drain(..n);
+            Ok(n)
+        }
+    }
+
+    struct ChannelWriter(Sender<Vec<u8>>);
+
+    impl Write for ChannelWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let _ = self.0.send(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn channel_reader(rx: Receiver<Vec<u8>>) -> BufReader<ChannelReader> {
+        BufReader::new(ChannelReader {
+            rx,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// A client talking to a server on another thread
+    struct Client {
+        requests: ChannelWriter,
+        messages: BufReader<ChannelReader>,
+        events: Vec<Json>,
+        seq: i64,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let (request_tx, request_rx) = mpsc::channel();
+            let (message_tx, message_rx) = mpsc::channel();
+            thread::spawn(move || {
+                let mut input = channel_reader(request_rx);
+                Server::new(ChannelWriter(message_tx)).serve(&mut input)
+            });
+            Client {
+                requests: ChannelWriter(request_tx),
+                messages: channel_reader(message_rx),
+                events: Vec::new(),
+                seq: 0,
+            }
+        }
+
+        fn next_message(&mut self) -> Json {
+            read_message(&mut self.messages)
+                .unwrap()
+                .expect("server closed the connection")
+        }
+
+        fn request(&mut self, command: &str, arguments: Json) -> Json {
+            self.seq += 1;
+            let message = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            });
+            write_message(&mut self.requests, &message).unwrap();
+            loop {
+                let message = self.next_message();
+                if message["type"] == "response" && message["request_seq"] == self.seq {
+                    return message;
+                }
+                self.events.push(message);
+            }
+        }
+
+        fn event(&mut self, name: &str) -> Json {
+            if let Some(i) = self.events.iter().position(|e| e["event"] == name) {
+                return self.events.remove(i);
+            }
+            loop {
+                let message = self.next_message();
+                if message["event"] == name {
+                    return message;
+                }
+                self.events.push(message);
+            }
+        }
+
+        fn launch(&mut self, source: &str, breakpoints: &[u32]) -> tempfile::TempDir {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("main.fsx");
+            std::fs::write(&path, source).unwrap();
+            let path = path.to_str().unwrap();
+
+            assert_eq!(self.request("initialize", json!({}))["success"], true);
+            let launch = self.request("launch", json!({ "program": path }));
+            assert_eq!(launch["success"], true, "{}", launch);
+            self.event("initialized");
+            let lines: Vec<Json> = breakpoints.iter().map(|l| json!({ "line": l })).collect();
+            let response = self.request(
+                "setBreakpoints",
+                json!({ "source": { "path": path }, "breakpoints": lines }),
+            );
+            for breakpoint in response["body"]["breakpoints"].as_array().unwrap() {
+                assert_eq!(breakpoint["verified"], true, "{}", response);
+            }
+            self.request("configurationDone", json!({}));
+            dir
+        }
+
+        fn variables(&mut self, reference: &Json) -> Vec<(String, String)> {
+            let response = self.request("variables", json!({ "variablesReference": reference }));
+            response["body"]["variables"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| {
+                    (
+                        v["name"].as_str().unwrap().to_string(),
+                        v["value"].as_str().unwrap().to_string(),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    const SQUARE: &str = "let square x =
+    let y = x * x in
+    y
+let a = 3
+let b = square a
+printfn b
+b + 1
+";
+
+    #[test]
+    fn test_breakpoint_stack_and_locals() {
+        let mut client = Client::start();
+        let _dir = client.launch(SQUARE, &[3]);
+
+        let stopped = client.event("stopped");
+        assert_eq!(stopped["body"]["reason"], "breakpoint");
+
+        let trace = client.request("stackTrace", json!({ "threadId": THREAD_ID }));
+        let frames = trace["body"]["stackFrames"].as_array().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0]["name"], "square");
+        assert_eq!(frames[0]["line"], 3);
+        assert_eq!(frames[1]["name"], "<main>");
+        assert_eq!(frames[1]["line"], 5);
+
+        let scopes = client.request("scopes", json!({ "frameId": frames[0]["id"] }));
+        let locals = &scopes["body"]["scopes"][0];
+        assert_eq!(locals["name"], "Locals");
+        let variables = client.variables(&locals["variablesReference"]);
+        assert!(variables.contains(&("x".to_string(), "3".to_string())));
+        assert!(variables.contains(&("y".to_string(), "9".to_string())));
+
+        client.request("continue", json!({ "threadId": THREAD_ID }));
+        assert_eq!(client.event("output")["body"]["output"], "9\n");
+        assert_eq!(client.event("exited")["body"]["exitCode"], 0);
+        client.event("terminated");
+    }
+
+    #[test]
+    fn test_step_over_and_out() {
+        let mut client = Client::start();
+        let _dir = client.launch(SQUARE, &[2]);
+        client.event("stopped");
+
+        client.request("next", json!({ "threadId": THREAD_ID }));
+        assert_eq!(client.event("stopped")["body"]["reason"], "step");
+        let trace = client.request("stackTrace", json!({}));
+        assert_eq!(trace["body"]["stackFrames"][0]["line"], 3);
+
+        client.request("stepOut", json!({ "threadId": THREAD_ID }));
+        client.event("stopped");
+        let trace = client.request("stackTrace", json!({}));
+        assert_eq!(trace["body"]["stackFrames"][0]["name"], "<main>");
+
+        client.request("disconnect", json!({}));
+    }
+
+    #[test]
+    fn test_step_in() {
+        let mut client = Client::start();
+        let _dir = client.launch(SQUARE, &[5]);
+        client.event("stopped");
+
+        client.request("stepIn", json!({ "threadId": THREAD_ID }));
+        client.event("stopped");
+        let trace = client.request("stackTrace", json!({}));
+        assert_eq!(trace["body"]["stackFrames"][0]["name"], "square");
+        assert_eq!(trace["body"]["stackFrames"][0]["line"], 2);
+
+        client.request("disconnect", json!({}));
+    }
+
+    #[test]
+    fn test_pauses_on_runtime_error() {
+        let mut client = Client::start();
+        let _dir = client.launch("let xs = [1; 2]\nlet n = List.head xs\nn + \"one\"\n", &[]);
+
+        let stopped = client.event("stopped");
+        assert_eq!(stopped["body"]["reason"], "exception");
+        let response = client.request("evaluate", json!({ "expression": "xs" }));
+        assert_eq!(response["body"]["result"], "[1; 2]");
+        assert!(response["body"]["variablesReference"].as_i64().unwrap() > 0);
+
+        client.request("continue", json!({ "threadId": THREAD_ID }));
+        assert_eq!(client.event("exited")["body"]["exitCode"], 1);
+    }
+
+    #[test]
+    fn test_breakpoint_outside_program_unverified() {
+        let mut client = Client::start();
+        client.request("initialize", json!({}));
+        let response = client.request(
+            "setBreakpoints",
+            json!({ "source": { "path": "/elsewhere.fsx" }, "breakpoints": [{ "line": 1 }] }),
+        );
+        assert_eq!(response["body"]["breakpoints"][0]["verified"], false);
+        let response = client.request("stackTrace", json!({}));
+        assert_eq!(response["success"], false);
+    }
+}
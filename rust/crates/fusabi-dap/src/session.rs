@@ -0,0 +1,155 @@
+//! Compiling the debugged program and running it on its own thread
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use fusabi_frontend::provider_resolver::resolve_source_paths;
+use fusabi_frontend::{Compiler, Lexer, Parser};
+use fusabi_vm::{Chunk, Value, Vm, VmError};
+
+use crate::debugger::{Command, Controls, DebugEvent, Debugger};
+
+/// A script compiled with debug info
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub path: String,
+    pub chunk: Chunk,
+    /// Lines that have code, in any function
+    pub lines: BTreeSet<u32>,
+}
+
+impl Program {
+    /// Compile the script at `path`, recording source spans
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source =
+            fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        Self::compile(path, &source)
+    }
+
+    /// Compile `source`, reported as coming from `path`
+    pub fn compile(path: &str, source: &str) -> Result<Self, String> {
+        let tokens = Lexer::new(source).tokenize().map_err(|e| e.to_string())?;
+        let mut program = Parser::new(tokens)
+            .with_spans()
+            .parse_program()
+            .map_err(|e| e.to_string())?;
+        if let Some(dir) = Path::new(path).parent() {
+            resolve_source_paths(&mut program, dir);
+        }
+        let mut chunk = Compiler::compile_program(&program).map_err(|e| e.to_string())?;
+        chunk.set_source(source);
+        chunk.set_source_file(path);
+
+        let mut lines = BTreeSet::new();
+        collect_lines(&chunk, &mut lines);
+        Ok(Program {
+            path: path.to_string(),
+            chunk,
+            lines,
+        })
+    }
+
+    /// Whether `path` names this program's source file
+    pub fn is_source(&self, path: &str) -> bool {
+        path == self.path
+            || matches!(
+                (Path::new(path).canonicalize(), Path::new(&self.path).canonicalize()),
+                (Ok(a), Ok(b)) if a == b
+            )
+    }
+
+    /// The line a breakpoint requested at `line` binds to: the first line at
+    /// or after it that has code
+    pub fn breakpoint_line(&self, line: u32) -> Option<u32> {
+        self.lines.range(line..).next().copied()
+    }
+}
+
+fn collect_lines(chunk: &Chunk, lines: &mut BTreeSet<u32>) {
+    lines.extend(
+        (0..chunk.instructions.len()).filter_map(|offset| chunk.span_at(offset).map(|s| s.line)),
+    );
+    for constant in &chunk.constants {
+        if let Value::Closure(closure) = constant {
+            collect_lines(&closure.chunk, lines);
+        }
+    }
+}
+
+/// A program running under the debugger
+pub struct Session {
+    pub commands: Sender<Command>,
+    pub events: Receiver<DebugEvent>,
+    pub thread: JoinHandle<()>,
+}
+
+/// Start running `program` on a new thread
+pub fn spawn(program: Program, controls: Controls, stop_on_entry: bool) -> Session {
+    let (command_tx, command_rx) = mpsc::channel();
+    let (event_tx, event_rx) = mpsc::channel();
+
+    let thread = thread::spawn(move || {
+        let mut vm = Vm::new();
+        fusabi_vm::stdlib::register_stdlib(&mut vm);
+        redirect_output(&mut vm, event_tx.clone());
+        vm.set_debug_hook(Box::new(Debugger::new(
+            controls,
+            command_rx,
+            event_tx.clone(),
+            program.path.clone(),
+            stop_on_entry,
+        )));
+
+        let result = vm
+            .execute(program.chunk)
+            .map(|value| value.to_string())
+            .map_err(|e| e.to_string());
+        let _ = event_tx.send(DebugEvent::Exited(result));
+    });
+
+    Session {
+        commands: command_tx,
+        events: event_rx,
+        thread,
+    }
+}
+
+/// Send the script's console output to the client as `output` events;
+/// stdout carries the protocol itself
+fn redirect_output(vm: &mut Vm, events: Sender<DebugEvent>) {
+    let events = Arc::new(Mutex::new(events));
+    let mut registry = vm.host_registry.lock().unwrap();
+    for (name, newline, strings_only) in [
+        ("print", false, false),
+        ("printfn", true, false),
+        ("Console.write", false, true),
+        ("Console.writeLine", true, true),
+    ] {
+        let events = events.clone();
+        registry.register(name, move |_vm, args| {
+            let text = match args {
+                [Value::Str(s)] => s.clone(),
+                [other] if !strings_only => other.to_string(),
+                [other] => {
+                    return Err(VmError::TypeMismatch {
+                        expected: "string",
+                        got: other.type_name(),
+                    })
+                }
+                _ => {
+                    return Err(VmError::Runtime(format!(
+                        "Expected 1 argument, got {}",
+                        args.len()
+                    )))
+                }
+            };
+            let text = if newline { text + "\n" } else { text };
+            let _ = events.lock().unwrap().send(DebugEvent::Output(text));
+            Ok(Value::Unit)
+        });
+    }
+}
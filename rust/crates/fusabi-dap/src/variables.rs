@@ -0,0 +1,316 @@
+//! Stack and variable inspection
+//!
+//! Turns the paused VM's frames into DAP stack frames and its locals, upvalues,
+//! globals and compound values into expandable variable trees.
+
+use fusabi_vm::{Value, Vm};
+use serde_json::{json, Value as Json};
+
+/// Longest value summary shown inline; expand the variable to see the rest
+const MAX_SUMMARY_LEN: usize = 200;
+
+/// Most children listed for one value
+const MAX_CHILDREN: usize = 1000;
+
+/// Something the client can expand with a `variables` request
+#[derive(Debug, Clone)]
+enum Container {
+    Locals(usize),
+    Upvalues(usize),
+    Globals,
+    Value(Value),
+}
+
+/// Variable references handed out while the VM is paused
+///
+/// References are only valid until execution resumes, as DAP specifies.
+#[derive(Debug, Default)]
+pub struct Variables {
+    containers: Vec<Container>,
+    faulted: bool,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the innermost frame stopped on a failed instruction
+    pub fn set_faulted(&mut self, faulted: bool) {
+        self.faulted = faulted;
+    }
+
+    /// Stack trace of the paused VM
+    pub fn stack_trace(&self, vm: &Vm, source_path: &str) -> Json {
+        stack_trace(vm, source_path, self.faulted)
+    }
+
+    /// Forget all references (call on resume)
+    pub fn clear(&mut self) {
+        self.containers.clear();
+    }
+
+    fn reference(&mut self, container: Container) -> i64 {
+        self.containers.push(container);
+        self.containers.len() as i64
+    }
+
+    /// Scopes of the frame at `frame_index`
+    pub fn scopes(&mut self, vm: &Vm, frame_index: usize) -> Option<Json> {
+        let frame = vm.frames().get(frame_index)?;
+        let mut scopes = vec![json!({
+            "name": "Locals",
+            "presentationHint": "locals",
+            "variablesReference": self.reference(Container::Locals(frame_index)),
+            "expensive": false,
+        })];
+        if frame.closure.upvalue_count() > 0 {
+            scopes.push(json!({
+                "name": "Upvalues",
+                "variablesReference": self.reference(Container::Upvalues(frame_index)),
+                "expensive": false,
+            }));
+        }
+        scopes.push(json!({
+            "name": "Globals",
+            "variablesReference": self.reference(Container::Globals),
+            "expensive": true,
+        }));
+        Some(Json::Array(scopes))
+    }
+
+    /// Children of the container behind `reference`
+    pub fn variables(&mut self, vm: &Vm, reference: i64) -> Option<Json> {
+        let index = usize::try_from(reference).ok()?.checked_sub(1)?;
+        let container = self.containers.get(index)?.clone();
+        let children: Vec<(String, Value)> = match container {
+            Container::Locals(frame_index) => {
+                let frame = vm.frames().get(frame_index)?;
+                let ip = location_ip(vm, frame_index, self.faulted);
+                frame
+                    .closure
+                    .chunk
+                    .locals_at(ip)
+                    .into_iter()
+                    .filter_map(|var| {
+                        let value = vm.frame_local(frame_index, var.slot)?;
+                        Some((var.name.clone(), value.clone()))
+                    })
+                    .collect()
+            }
+            Container::Upvalues(frame_index) => {
+                let count = vm.frames().get(frame_index)?.closure.upvalue_count();
+                (0..count)
+                    .filter_map(|i| {
+                        Some((format!("upvalue {}", i), vm.frame_upvalue(frame_index, i)?))
+                    })
+                    .collect()
+            }
+            Container::Globals => {
+                let mut globals: Vec<_> = vm
+                    .globals
+                    .iter()
+                    .filter(|(_, value)| !matches!(value, Value::NativeFn { .. }))
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect();
+                globals.sort_by(|a, b| a.0.cmp(&b.0));
+                globals
+            }
+            Container::Value(value) => children(&value),
+        };
+
+        let variables = children
+            .into_iter()
+            .map(|(name, value)| self.variable(name, value))
+            .collect();
+        Some(Json::Array(variables))
+    }
+
+    /// Look up a variable, optionally followed by `.field` accesses
+    pub fn evaluate(&mut self, vm: &Vm, frame_index: usize, expression: &str) -> Option<Json> {
+        let mut path = expression.split('.');
+        let name = path.next()?;
+        let mut value = lookup(vm, frame_index, name, self.faulted)?;
+        for field in path {
+            value = children(&value)
+                .into_iter()
+                .find(|(child, _)| child == field)?
+                .1;
+        }
+        let variable = self.variable(expression.to_string(), value);
+        Some(json!({
+            "result": variable["value"],
+            "type": variable["type"],
+            "variablesReference": variable["variablesReference"],
+        }))
+    }
+
+    fn variable(&mut self, name: String, value: Value) -> Json {
+        let reference = if children(&value).is_empty() {
+            0
+        } else {
+            self.reference(Container::Value(value.clone()))
+        };
+        json!({
+            "name": name,
+            "value": summary(&value),
+            "type": value.type_name(),
+            "variablesReference": reference,
+        })
+    }
+}
+
+/// Instruction the frame at `frame_index` is stopped at
+///
+/// The innermost frame is about to execute `ip`, unless the instruction
+/// before it just failed; callers have already advanced past their call
+/// instruction.
+pub fn location_ip(vm: &Vm, frame_index: usize, faulted: bool) -> usize {
+    let frame = &vm.frames()[frame_index];
+    if frame_index + 1 == vm.frames().len() && !faulted {
+        frame.ip
+    } else {
+        frame.ip.saturating_sub(1)
+    }
+}
+
+/// Value of the innermost local named `name` in the frame, else of the global
+fn lookup(vm: &Vm, frame_index: usize, name: &str, faulted: bool) -> Option<Value> {
+    let frame = vm.frames().get(frame_index)?;
+    let local = frame
+        .closure
+        .chunk
+        .locals_at(location_ip(vm, frame_index, faulted))
+        .into_iter()
+        .rev()
+        .find(|var| var.name == name)
+        .and_then(|var| vm.frame_local(frame_index, var.slot).cloned());
+    local.or_else(|| vm.globals.get(name).cloned())
+}
+
+/// Name shown for the frame at `frame_index`
+pub fn frame_name(vm: &Vm, frame_index: usize) -> String {
+    let closure = &vm.frames()[frame_index].closure;
+    closure
+        .name
+        .clone()
+        .or_else(|| closure.chunk.name.clone())
+        .unwrap_or_else(|| {
+            if frame_index == 0 {
+                "<main>".to_string()
+            } else {
+                "<lambda>".to_string()
+            }
+        })
+}
+
+/// DAP stack frames, innermost first; frame ids are frame indices
+fn stack_trace(vm: &Vm, source_path: &str, faulted: bool) -> Json {
+    let frames: Vec<Json> = (0..vm.frames().len())
+        .rev()
+        .map(|index| {
+            let chunk = &vm.frames()[index].closure.chunk;
+            let span = chunk.span_at(location_ip(vm, index, faulted));
+            json!({
+                "id": index,
+                "name": frame_name(vm, index),
+                "source": { "path": source_path },
+                "line": span.map_or(0, |s| s.line),
+                "column": span.map_or(0, |s| s.column),
+            })
+        })
+        .collect();
+    json!({ "stackFrames": frames, "totalFrames": frames.len() })
+}
+
+/// One-line rendering of a value
+fn summary(value: &Value) -> String {
+    let text = match value {
+        Value::Str(s) => format!("{:?}", s),
+        other => other.to_string(),
+    };
+    if text.chars().count() > MAX_SUMMARY_LEN {
+        let truncated: String = text.chars().take(MAX_SUMMARY_LEN).collect();
+        format!("{}...", truncated)
+    } else {
+        text
+    }
+}
+
+/// Named children of a compound value
+fn children(value: &Value) -> Vec<(String, Value)> {
+    let indexed = |items: &mut dyn Iterator<Item = Value>| {
+        items
+            .take(MAX_CHILDREN)
+            .enumerate()
+            .map(|(i, v)| (format!("[{}]", i), v))
+            .collect()
+    };
+    match value {
+        Value::Tuple(items) => indexed(&mut items.iter().cloned()),
+        Value::Variant { fields, .. } => indexed(&mut fields.iter().cloned()),
+        Value::Array(items) => indexed(&mut items.lock().unwrap().clone().into_iter()),
+        Value::Cons { .. } => {
+            let mut items = Vec::new();
+            let mut current = value;
+            while let Value::Cons { head, tail } = current {
+                if items.len() == MAX_CHILDREN {
+                    break;
+                }
+                items.push((**head).clone());
+                current = tail;
+            }
+            indexed(&mut items.into_iter())
+        }
+        Value::Record(fields) | Value::Map(fields) => {
+            let mut fields: Vec<_> = fields
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            fields.truncate(MAX_CHILDREN);
+            fields
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_children() {
+        let list = Value::vec_to_cons(vec![Value::Int(1), Value::Int(2)]);
+        let kids = children(&list);
+        assert_eq!(kids.len(), 2);
+        assert_eq!(kids[1], ("[1]".to_string(), Value::Int(2)));
+        assert!(children(&Value::Int(1)).is_empty());
+    }
+
+    #[test]
+    fn test_nested_values_expand() {
+        let mut variables = Variables::new();
+        let vm = Vm::new();
+        let value = Value::Tuple(vec![Value::Int(1), Value::Tuple(vec![Value::Bool(true)])]);
+        let variable = variables.variable("t".to_string(), value);
+        let reference = variable["variablesReference"].as_i64().unwrap();
+        assert!(reference > 0);
+
+        let kids = variables.variables(&vm, reference).unwrap();
+        assert_eq!(kids[0]["variablesReference"], 0);
+        assert!(kids[1]["variablesReference"].as_i64().unwrap() > 0);
+
+        variables.clear();
+        assert!(variables.variables(&vm, reference).is_none());
+    }
+
+    #[test]
+    fn test_long_summary_truncated() {
+        let text = summary(&Value::Str("x".repeat(500)));
+        assert!(text.ends_with("..."));
+        assert!(text.len() < 300);
+    }
+}
@@ -34,6 +34,8 @@
 //! };
 //! ```
 
+use crate::span::Span;
+use std::collections::HashMap;
 use std::fmt;
 
 /// Literal values in the AST.
@@ -746,6 +748,27 @@ pub enum Expr {
     /// Continue statement (skips to next iteration)
     Continue,
 
+    /// Type annotation (e.g., `let x : int = 42`, `let f x : string = ...`)
+    Annotated {
+        /// Annotated expression
+        expr: Box<Expr>,
+        /// Declared type
+        ty: TypeExpr,
+    },
+
+    /// Expression tagged with its source location (only produced when the
+    /// parser records spans, see `Parser::with_spans`)
+    Spanned {
+        /// Located expression
+        expr: Box<Expr>,
+        /// Where the expression starts (and the token after it)
+        span: Span,
+    },
+
+    /// Placeholder for source that failed to parse (only produced by
+    /// `Parser::parse_program_recovering`)
+    Error,
+
     /// Computation expression: async { ... }, seq { ... }, etc.
     ComputationExpr {
         /// Builder name (e.g., "async", "seq", "option", "result")
@@ -884,6 +907,19 @@ impl Expr {
         matches!(self, Expr::Continue)
     }
 
+    /// Returns true if this expression carries a type annotation.
+    pub fn is_annotated(&self) -> bool {
+        matches!(self, Expr::Annotated { .. })
+    }
+
+    /// The expression without any source location wrappers.
+    pub fn unspanned(&self) -> &Expr {
+        match self {
+            Expr::Spanned { expr, .. } => expr.unspanned(),
+            other => other,
+        }
+    }
+
     /// Returns true if this expression is a computation expression.
     pub fn is_computation_expr(&self) -> bool {
         matches!(self, Expr::ComputationExpr { .. })
@@ -1118,7 +1154,10 @@ impl fmt::Display for Expr {
                 write!(f, "(while {} do {})", cond, body)
             }
             Expr::Break => write!(f, "break"),
+            Expr::Error => write!(f, "<error>"),
             Expr::Continue => write!(f, "continue"),
+            Expr::Annotated { expr, ty } => write!(f, "({} : {})", expr, ty),
+            Expr::Spanned { expr, .. } => write!(f, "{}", expr),
             Expr::ComputationExpr { builder, body } => {
                 write!(f, "{} {{ ... ({} statements) }}", builder, body.len())
             }
@@ -1243,6 +1282,9 @@ pub struct Program {
     pub items: Vec<ModuleItem>,
     /// Main expression to evaluate (if any)
     pub main_expr: Option<Expr>,
+    /// `///` documentation of declarations, by name: `add` for a top-level
+    /// binding or type, `Math` for a module and `Math.add` for its members
+    pub docs: HashMap<String, String>,
 }
 
 impl fmt::Display for Program {
@@ -42,12 +42,13 @@ use crate::ast::{BinOp, Expr, Import, Literal, MatchArm, ModuleDef, ModuleItem,
 use crate::modules::ModuleRegistry;
 use crate::provider_resolver::ProviderResolver;
 use crate::types::{Type, TypeEnv};
-use fusabi_vm::chunk::Chunk;
+use fusabi_vm::chunk::{Chunk, LocalVarInfo, SourceSpan};
 use fusabi_vm::closure::Closure;
 use fusabi_vm::instruction::Instruction;
 use fusabi_vm::value::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::rc::Rc;
 use std::sync::Arc;
 
 /// Compilation errors
@@ -143,7 +144,7 @@ pub struct CompileOptions {
     pub strict_mode: bool,
     /// Allow type warnings (only relevant if enable_type_checking is true)
     pub allow_warnings: bool,
-    /// Optional provider resolver for type provider declarations
+    /// Resolver for type provider declarations (`ProviderResolver::from_env()` if unset)
     pub provider_resolver: Option<ProviderResolver>,
 }
 
@@ -174,6 +175,10 @@ impl Default for CompileOptions {
 struct Local {
     name: String,
     depth: usize,
+    /// Index of the variable's debug info in `chunk.local_vars`
+    debug_index: usize,
+    /// Global the binding is also stored in (recursive bindings in top-level code)
+    global: Option<String>,
 }
 
 /// Loop state for tracking break/continue targets
@@ -197,11 +202,28 @@ pub struct Compiler {
     type_env: Option<TypeEnv>,
 
     // Module support
-    module_registry: Option<ModuleRegistry>,
+    module_registry: Option<Rc<ModuleRegistry>>,
     imported_bindings: HashMap<String, Expr>,
 
+    // Closure support
+    /// Whether this compiler compiles a function body rather than top-level code
+    in_function: bool,
+    /// Locals of the enclosing functions and top-level code, which this
+    /// function captures, with the global of those stored in one
+    enclosing: HashMap<String, Option<String>>,
+    /// Names captured from `enclosing`, in upvalue order
+    upvalues: Vec<String>,
+    /// Globals defined for recursive bindings so far
+    global_names: HashSet<String>,
+
     // Loop support
     loop_stack: Vec<LoopState>,
+
+    // Debug info
+    /// Source location attached to emitted instructions
+    current_span: SourceSpan,
+    /// Name for the closure compiled next (the function a `let` defines)
+    closure_name: Option<String>,
 }
 
 impl Compiler {
@@ -215,7 +237,13 @@ impl Compiler {
             type_env: None,
             module_registry: None,
             imported_bindings: HashMap::new(),
+            in_function: false,
+            enclosing: HashMap::new(),
+            upvalues: Vec::new(),
+            global_names: HashSet::new(),
             loop_stack: Vec::new(),
+            current_span: SourceSpan::unknown(),
+            closure_name: None,
         }
     }
 
@@ -229,7 +257,13 @@ impl Compiler {
             type_env: None,
             module_registry: None,
             imported_bindings: HashMap::new(),
+            in_function: false,
+            enclosing: HashMap::new(),
+            upvalues: Vec::new(),
+            global_names: HashSet::new(),
             loop_stack: Vec::new(),
+            current_span: SourceSpan::unknown(),
+            closure_name: None,
         }
     }
 
@@ -341,6 +375,9 @@ impl Compiler {
             Expr::While { cond, body } => {
                 Self::expr_references_var(cond, name) || Self::expr_references_var(body, name)
             }
+            Expr::Annotated { expr, .. } | Expr::Spanned { expr, .. } => {
+                Self::expr_references_var(expr, name)
+            }
             Expr::ComputationExpr { body, .. } => {
                 // Check if any statement in the CE body references the variable
                 body.iter().any(|stmt| {
@@ -358,7 +395,7 @@ impl Compiler {
                 })
             }
             // Literals and control flow don't reference variables
-            Expr::Lit(_) | Expr::Break | Expr::Continue => false,
+            Expr::Lit(_) | Expr::Break | Expr::Continue | Expr::Error => false,
         }
     }
 
@@ -423,29 +460,53 @@ impl Compiler {
         let mut registry = ModuleRegistry::with_stdlib();
 
         // Phase 1: Resolve type providers in top-level items
-        // This collects all type provider declarations and resolves them
-        if let Some(ref resolver) = compiler.options.provider_resolver {
-            for item in &program.items {
-                if let ModuleItem::TypeDef(crate::ast::TypeDefinition::Provider(decl)) = item {
-                    match resolver.resolve(decl) {
-                        Ok(resolved) => {
-                            // Inject resolved types into type environment
-                            if let Some(ref mut env) = compiler.type_env {
-                                resolver.inject_into_env(&resolved, env);
-                            } else {
-                                // Create new environment if none exists
-                                let mut env = TypeEnv::new();
-                                resolver.inject_into_env(&resolved, &mut env);
-                                compiler.type_env = Some(env);
-                            }
+        // This collects all type provider declarations and resolves them,
+        // with the built-in providers (cached per the environment) unless a
+        // resolver is given
+        let decls: Vec<_> = program
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ModuleItem::TypeDef(crate::ast::TypeDefinition::Provider(decl)) => Some(decl),
+                _ => None,
+            })
+            .collect();
+        if !decls.is_empty() {
+            let builtin;
+            let resolver = match compiler.options.provider_resolver {
+                Some(ref resolver) => resolver,
+                None => {
+                    builtin = ProviderResolver::from_env();
+                    &builtin
+                }
+            };
+            for decl in decls {
+                match resolver.resolve(decl) {
+                    Ok(resolved) => {
+                        // Inject resolved types into type environment
+                        if let Some(ref mut env) = compiler.type_env {
+                            resolver.inject_into_env(&resolved, env);
+                        } else {
+                            // Create new environment if none exists
+                            let mut env = TypeEnv::new();
+                            resolver.inject_into_env(&resolved, &mut env);
+                            compiler.type_env = Some(env);
                         }
-                        Err(e) => {
-                            return Err(CompileError::ProviderError(format!(
-                                "Resolution failed for {}: {}",
-                                decl.name, e
-                            )));
+                        // Provided members resolve as `Alias.member`
+                        if !resolved.members.is_empty() {
+                            registry.register_module(
+                                resolved.alias,
+                                resolved.members,
+                                HashMap::new(),
+                            );
                         }
                     }
+                    Err(e) => {
+                        return Err(CompileError::ProviderError(format!(
+                            "Resolution failed for {}: {}",
+                            decl.name, e
+                        )));
+                    }
                 }
             }
         }
@@ -456,7 +517,7 @@ impl Compiler {
         }
 
         // Store registry for qualified name lookups
-        compiler.module_registry = Some(registry);
+        compiler.module_registry = Some(Rc::new(registry));
 
         // Phase 3: Apply imports to environment
         for import in &program.imports {
@@ -474,62 +535,12 @@ impl Compiler {
     ///
     /// This processes all items in a module definition and registers them
     /// in the module registry for later lookup.
-    #[allow(clippy::only_used_in_recursion)]
     fn register_module(
         &mut self,
         registry: &mut ModuleRegistry,
         module: &ModuleDef,
     ) -> CompileResult<()> {
-        let mut bindings = HashMap::new();
-        let mut types = HashMap::new();
-
-        for item in &module.items {
-            match item {
-                ModuleItem::Let(name, expr) => {
-                    // Store binding for later compilation (skip discard bindings)
-                    if let Some(name) = name {
-                        bindings.insert(name.clone(), expr.clone());
-                    }
-                }
-                ModuleItem::LetRec(rec_bindings) => {
-                    // Handle recursive bindings
-                    for (name, expr) in rec_bindings {
-                        bindings.insert(name.clone(), expr.clone());
-                    }
-                }
-                ModuleItem::TypeDef(type_def) => {
-                    // Convert AST TypeDefinition to modules TypeDefinition
-                    let module_type_def = match type_def {
-                        crate::ast::TypeDefinition::Record(r) => {
-                            crate::modules::TypeDefinition::Record(r.clone())
-                        }
-                        crate::ast::TypeDefinition::Du(du) => {
-                            crate::modules::TypeDefinition::Du(du.clone())
-                        }
-                        crate::ast::TypeDefinition::Provider(p) => {
-                            crate::modules::TypeDefinition::Provider(p.clone())
-                        }
-                    };
-
-                    // Extract type name based on definition
-                    let type_name = match type_def {
-                        crate::ast::TypeDefinition::Record(r) => r.name.clone(),
-                        crate::ast::TypeDefinition::Du(du) => du.name.clone(),
-                        crate::ast::TypeDefinition::Provider(p) => p.name.clone(),
-                    };
-
-                    types.insert(type_name, module_type_def);
-                }
-                ModuleItem::Module(nested) => {
-                    // Recursively register nested module
-                    self.register_module(registry, nested)?;
-                }
-            }
-        }
-
-        // Register module in registry
-        registry.register_module(module.name.clone(), bindings, types);
-
+        registry.register_module_def(module);
         Ok(())
     }
 
@@ -595,7 +606,7 @@ impl Compiler {
             Expr::Let { name, value, body } => self.compile_let(name, value, body),
             Expr::LetRec { name, value, body } => self.compile_let_rec(name, value, body),
             Expr::LetRecMutual { bindings, body } => self.compile_let_rec_mutual(bindings, body),
-            Expr::Lambda { param, body } => self.compile_lambda(param, body),
+            Expr::Lambda { param, body } => self.compile_lambda(param, body).map(|_| ()),
             Expr::App { func, arg } => self.compile_app(func, arg),
             Expr::If {
                 cond,
@@ -630,6 +641,23 @@ impl Compiler {
             Expr::While { cond, body } => self.compile_while(cond, body),
             Expr::Break => self.compile_break(),
             Expr::Continue => self.compile_continue(),
+            Expr::Error => Err(CompileError::CodeGenError(
+                "cannot compile source that failed to parse".to_string(),
+            )),
+            // Annotations only matter to the type checker
+            Expr::Annotated { expr, .. } => self.compile_expr(expr),
+            Expr::Spanned { expr, span } => {
+                let outer = self.current_span;
+                self.current_span = SourceSpan::new(
+                    span.start.line as u32,
+                    span.start.column as u32,
+                    span.start.offset as u32,
+                    span.len() as u32,
+                );
+                let result = self.compile_expr(expr);
+                self.current_span = outer;
+                result
+            }
             Expr::ComputationExpr { builder, body } => self.compile_computation_expr(builder, body),
         }
     }
@@ -669,6 +697,32 @@ impl Compiler {
             }
         }
 
+        // Then the enclosing scopes, whose locals are captured by value
+        if let Some(index) = self.upvalues.iter().position(|upvalue| upvalue == name) {
+            self.emit(Instruction::LoadUpvalue(index as u8));
+            return Ok(());
+        }
+        match self.enclosing.get(name).cloned() {
+            // Recursive bindings are not defined yet when they would be
+            // captured, so they are loaded from their global when used
+            Some(Some(global)) => {
+                let idx = self.add_constant(Value::Str(global))?;
+                self.emit(Instruction::LoadGlobal(idx));
+                return Ok(());
+            }
+            Some(None) => {
+                if self.upvalues.len() >= u8::MAX as usize {
+                    return Err(CompileError::CodeGenError(
+                        "Too many captured variables (max 255)".to_string(),
+                    ));
+                }
+                self.upvalues.push(name.to_string());
+                self.emit(Instruction::LoadUpvalue((self.upvalues.len() - 1) as u8));
+                return Ok(());
+            }
+            None => {}
+        }
+
         // Check imported bindings
         if let Some(expr) = self.imported_bindings.get(name) {
             // For imported bindings, check if the expression is a simple variable reference
@@ -679,8 +733,16 @@ impl Compiler {
                 self.emit(Instruction::LoadGlobal(idx));
                 return Ok(());
             }
-            // For other expressions (e.g., user-defined module bindings), compile normally
-            return self.compile_expr(&expr.clone());
+            // For other expressions (e.g., user-defined module bindings), compile normally.
+            // The binding's own name in its expression refers to something else.
+            let expr = expr.clone();
+            let binding = self.imported_bindings.remove(name);
+            self.name_closure(name, &expr);
+            let result = self.compile_expr(&expr);
+            if let Some(binding) = binding {
+                self.imported_bindings.insert(name.to_string(), binding);
+            }
+            return result;
         }
 
         // If not found locally or imported, assume it's a global variable
@@ -713,7 +775,9 @@ impl Compiler {
                 }
 
                 // For user-defined modules, compile the expression normally
-                return self.compile_expr(&expr.clone());
+                let expr = expr.clone();
+                self.name_closure(&format!("{}.{}", module_name, name), &expr);
+                return self.compile_expr(&expr);
             }
         }
 
@@ -875,6 +939,9 @@ impl Compiler {
             match first {
                 ModuleItem::Let(name, value) => {
                     // Compile value
+                    if let Some(name) = name {
+                        self.name_closure(name, value);
+                    }
                     self.compile_expr(value)?;
 
                     if let Some(name) = name {
@@ -892,7 +959,7 @@ impl Compiler {
                         // Clean up scope
                         let locals_to_remove = self.end_scope_count();
                         for _ in 0..locals_to_remove {
-                            self.locals.pop();
+                            self.pop_local();
                         }
                         self.scope_depth -= 1;
                     } else {
@@ -909,18 +976,18 @@ impl Compiler {
                         self.emit(Instruction::LoadConst(placeholder_idx));
 
                         self.begin_scope();
-                        self.add_local(name.to_string())?;
-                        let local_idx = (self.locals.len() - 1) as u8;
+                        let local_idx = self.add_recursive_local(name)?;
                         self.emit(Instruction::StoreLocal(local_idx));
 
+                        self.name_closure(name, value);
                         self.compile_expr(value)?;
-                        self.emit(Instruction::StoreLocal(local_idx));
+                        self.store_binding(local_idx)?;
 
                         self.compile_top_level_items(rest, main_expr)?;
 
                         let locals_to_remove = self.end_scope_count();
                         for _ in 0..locals_to_remove {
-                            self.locals.pop();
+                            self.pop_local();
                         }
                         self.scope_depth -= 1;
                     } else {
@@ -932,21 +999,22 @@ impl Compiler {
 
                         for (name, _) in bindings {
                             self.emit(Instruction::LoadConst(placeholder_idx));
-                            self.add_local(name.to_string())?;
-                            local_indices.push((self.locals.len() - 1) as u8);
+                            local_indices.push(self.add_recursive_local(name)?);
                             self.emit(Instruction::StoreLocal(*local_indices.last().unwrap()));
                         }
 
-                        for ((_, value), local_idx) in bindings.iter().zip(local_indices.iter()) {
+                        for ((name, value), local_idx) in bindings.iter().zip(local_indices.iter())
+                        {
+                            self.name_closure(name, value);
                             self.compile_expr(value)?;
-                            self.emit(Instruction::StoreLocal(*local_idx));
+                            self.store_binding(*local_idx)?;
                         }
 
                         self.compile_top_level_items(rest, main_expr)?;
 
                         let locals_to_remove = self.end_scope_count();
                         for _ in 0..locals_to_remove {
-                            self.locals.pop();
+                            self.pop_local();
                         }
                         self.scope_depth -= 1;
                     }
@@ -978,6 +1046,7 @@ impl Compiler {
         } else {
             // Use standard let compilation
             // Compile the value expression
+            self.name_closure(name, value);
             self.compile_expr(value)?;
 
             // Enter new scope
@@ -1001,7 +1070,7 @@ impl Compiler {
             // For Phase 1 MVP, we simplify by not emitting POPs
             // The locals stay in their stack slots until function return
             for _ in 0..locals_to_remove {
-                self.locals.pop();
+                self.pop_local();
             }
             self.scope_depth -= 1;
 
@@ -1009,10 +1078,24 @@ impl Compiler {
         }
     }
 
-    /// Compile a lambda function
-    fn compile_lambda(&mut self, param: &str, body: &Expr) -> CompileResult<()> {
+    /// Compile a lambda function, returning the names it captures in
+    /// upvalue order
+    fn compile_lambda(&mut self, param: &str, body: &Expr) -> CompileResult<Vec<String>> {
         // Create a nested chunk for the lambda body
         let mut lambda_compiler = Compiler::new();
+        lambda_compiler.current_span = self.current_span;
+        // Module names resolve the same way inside the body
+        lambda_compiler.module_registry = self.module_registry.clone();
+        lambda_compiler.imported_bindings = self.imported_bindings.clone();
+        // Everything in scope here can be captured
+        lambda_compiler.in_function = true;
+        lambda_compiler.enclosing = self.enclosing.clone();
+        lambda_compiler.enclosing.extend(
+            self.locals
+                .iter()
+                .map(|local| (local.name.clone(), local.global.clone())),
+        );
+        let name = self.closure_name.take();
 
         // Lambda parameter becomes local 0
         lambda_compiler.begin_scope();
@@ -1027,17 +1110,63 @@ impl Compiler {
         lambda_compiler.scope_depth -= 1;
 
         // Create a closure prototype (chunk + arity)
-        // For now, we don't support upvalue capture in the compiler (Phase 2 extension)
-        // We assume no upvalues.
-        let closure = Closure::with_arity(lambda_compiler.chunk, 1);
+        let closure = match name {
+            Some(name) => Closure::with_arity_and_name(lambda_compiler.chunk, 1, name),
+            None => Closure::with_arity(lambda_compiler.chunk, 1),
+        };
         let closure_val = Value::Closure(Arc::new(closure));
 
         // Store prototype in constants
         let const_idx = self.add_constant(closure_val)?;
 
-        // Emit MakeClosure instruction (0 upvalues for now)
-        self.emit(Instruction::MakeClosure(const_idx, 0));
-        Ok(())
+        // Push the captured values, then make the closure from them
+        for upvalue in &lambda_compiler.upvalues {
+            self.compile_var(upvalue)?;
+        }
+        self.emit(Instruction::MakeClosure(
+            const_idx,
+            lambda_compiler.upvalues.len() as u8,
+        ));
+        Ok(lambda_compiler.upvalues)
+    }
+
+    /// Compile the value of a recursive binding, returning the names the
+    /// closure it makes captures, if it is a lambda
+    fn compile_recursive_value(&mut self, value: &Expr) -> CompileResult<Vec<String>> {
+        match value {
+            Expr::Lambda { param, body } => self.compile_lambda(param, body),
+            Expr::Spanned { expr, span } => {
+                let outer = self.current_span;
+                self.current_span = SourceSpan::new(
+                    span.start.line as u32,
+                    span.start.column as u32,
+                    span.start.offset as u32,
+                    span.len() as u32,
+                );
+                let result = self.compile_recursive_value(expr);
+                self.current_span = outer;
+                result
+            }
+            _ => self.compile_expr(value).map(|()| Vec::new()),
+        }
+    }
+
+    /// Point the closures of recursive bindings in a function at each
+    /// other. They capture the bindings before they are defined, so each
+    /// captured binding of the group is set once all are.
+    fn link_recursive_closures(&mut self, group: &[(u8, Vec<String>)]) {
+        for (slot, captured) in group {
+            for (index, name) in captured.iter().enumerate() {
+                let target = group
+                    .iter()
+                    .find(|(other, _)| self.locals[*other as usize].name == *name);
+                if let Some((target, _)) = target {
+                    self.emit(Instruction::LoadLocal(*slot));
+                    self.emit(Instruction::LoadLocal(*target));
+                    self.emit(Instruction::SetClosureUpvalue(index as u8));
+                }
+            }
+        }
     }
 
     /// Compile a recursive let-binding using placeholder strategy
@@ -1051,18 +1180,20 @@ impl Compiler {
 
         // 2. Enter scope and add local for the recursive binding
         self.begin_scope();
-        self.add_local(name.to_string())?;
-        let local_idx = (self.locals.len() - 1) as u8;
+        let local_idx = self.add_recursive_local(name)?;
 
         // 3. Store placeholder in local slot
         self.emit(Instruction::StoreLocal(local_idx));
 
         // 4. Compile the value (usually a lambda) with name in scope
         // The value can now reference itself via the local
-        self.compile_expr(value)?;
+        self.name_closure(name, value);
+        let captured = self.compile_recursive_value(value)?;
 
-        // 5. Update the local slot with the actual value
-        self.emit(Instruction::StoreLocal(local_idx));
+        // 5. Update the local slot with the actual value, which a closure
+        // captured as the placeholder
+        self.store_binding(local_idx)?;
+        self.link_recursive_closures(&[(local_idx, captured)]);
 
         // 6. Compile body (the local is still in scope)
         self.compile_expr(body)?;
@@ -1070,7 +1201,7 @@ impl Compiler {
         // 7. Clean up scope
         let locals_to_remove = self.end_scope_count();
         for _ in 0..locals_to_remove {
-            self.locals.pop();
+            self.pop_local();
         }
         self.scope_depth -= 1;
 
@@ -1097,8 +1228,7 @@ impl Compiler {
             self.emit(Instruction::LoadConst(placeholder_idx));
 
             // Add local
-            self.add_local(name.clone())?;
-            let local_idx = (self.locals.len() - 1) as u8;
+            let local_idx = self.add_recursive_local(name)?;
             local_indices.push(local_idx);
 
             // Store placeholder
@@ -1106,10 +1236,14 @@ impl Compiler {
         }
 
         // 3. Compile each value (with all names in scope)
-        for (i, (_name, value)) in bindings.iter().enumerate() {
-            self.compile_expr(value)?;
-            self.emit(Instruction::StoreLocal(local_indices[i]));
+        let mut group = Vec::new();
+        for (i, (name, value)) in bindings.iter().enumerate() {
+            self.name_closure(name, value);
+            let captured = self.compile_recursive_value(value)?;
+            self.store_binding(local_indices[i])?;
+            group.push((local_indices[i], captured));
         }
+        self.link_recursive_closures(&group);
 
         // 4. Compile body
         self.compile_expr(body)?;
@@ -1117,7 +1251,7 @@ impl Compiler {
         // 5. Clean up scope
         let locals_to_remove = self.end_scope_count();
         for _ in 0..locals_to_remove {
-            self.locals.pop();
+            self.pop_local();
         }
         self.scope_depth -= 1;
 
@@ -1473,11 +1607,6 @@ impl Compiler {
         for (i, arm) in arms.iter().enumerate() {
             let is_last_arm = i == arms.len() - 1;
 
-            // Duplicate scrutinee for pattern test (except we'll clean it up)
-            if !is_last_arm {
-                self.emit(Instruction::Dup);
-            }
-
             // Compile pattern test - this will push a boolean result
             let _next_arm_offset = if !is_last_arm {
                 self.chunk.current_offset()
@@ -1497,12 +1626,8 @@ impl Compiler {
                 0
             };
 
-            // Pattern matched - now bind variables from the pattern
-            // We still have the scrutinee on the stack
-            if !is_last_arm {
-                self.emit(Instruction::Dup); // Keep scrutinee for binding
-            }
-
+            // Pattern matched - now bind variables from the pattern,
+            // consuming the scrutinee
             // Enter a new scope for pattern bindings
             self.begin_scope();
 
@@ -1515,7 +1640,7 @@ impl Compiler {
             // Exit scope for pattern bindings
             let locals_to_remove = self.end_scope_count();
             for _ in 0..locals_to_remove {
-                self.locals.pop();
+                self.pop_local();
             }
             self.scope_depth -= 1;
 
@@ -1523,11 +1648,10 @@ impl Compiler {
             let jump_to_end = self.emit_jump(Instruction::Jump(0));
             end_jumps.push(jump_to_end);
 
-            // Patch jump to next arm (if not last)
+            // Patch jump to next arm (if not last), which tests the
+            // scrutinee still on the stack
             if !is_last_arm {
                 self.patch_jump(jump_to_next)?;
-                // Pop the scrutinee since this arm didn't match
-                self.emit(Instruction::Pop);
             }
         }
 
@@ -1681,7 +1805,7 @@ impl Compiler {
     }
 
     fn emit(&mut self, instruction: Instruction) {
-        self.chunk.emit(instruction);
+        self.chunk.emit_with_span(instruction, self.current_span);
     }
 
     /// Emit a jump instruction and return its index for later patching
@@ -1743,13 +1867,66 @@ impl Compiler {
             return Err(CompileError::TooManyLocals);
         }
 
+        self.chunk.local_vars.push(LocalVarInfo {
+            name: name.clone(),
+            slot: self.locals.len() as u8,
+            start: self.chunk.current_offset(),
+            end: usize::MAX,
+        });
         self.locals.push(Local {
             name,
             depth: self.scope_depth,
+            debug_index: self.chunk.local_vars.len() - 1,
+            global: None,
         });
 
         Ok(())
     }
+
+    /// Add the local of a recursive binding, returning its slot. In top-level
+    /// code the binding is also stored in a global, which the functions it
+    /// defines call it through.
+    fn add_recursive_local(&mut self, name: &str) -> CompileResult<u8> {
+        self.add_local(name.to_string())?;
+        let slot = (self.locals.len() - 1) as u8;
+        if !self.in_function {
+            // A name bound again gets a global of its own
+            let global = if self.global_names.contains(name) {
+                format!("{}#{}", name, self.global_names.len())
+            } else {
+                name.to_string()
+            };
+            self.global_names.insert(global.clone());
+            self.locals[slot as usize].global = Some(global);
+        }
+        Ok(slot)
+    }
+
+    /// Pop the value on top of the stack into the local at `slot`, and into
+    /// its global, if it has one
+    fn store_binding(&mut self, slot: u8) -> CompileResult<()> {
+        if let Some(global) = self.locals[slot as usize].global.clone() {
+            self.emit(Instruction::Dup);
+            let idx = self.add_constant(Value::Str(global))?;
+            self.emit(Instruction::StoreGlobal(idx));
+        }
+        self.emit(Instruction::StoreLocal(slot));
+        Ok(())
+    }
+
+    /// Remove the innermost local variable, ending its debug info range
+    fn pop_local(&mut self) {
+        if let Some(local) = self.locals.pop() {
+            self.chunk.local_vars[local.debug_index].end = self.chunk.current_offset();
+        }
+    }
+
+    /// Name the closure `value` compiles to after the binding it defines
+    fn name_closure(&mut self, name: &str, value: &Expr) {
+        if matches!(value.unspanned(), Expr::Lambda { .. }) {
+            self.closure_name = Some(name.to_string());
+        }
+    }
     /// Compile a record literal expression
     /// Stack effect: pushes a record value
     fn compile_record_literal(&mut self, fields: &[(String, Box<Expr>)]) -> CompileResult<()> {
@@ -1779,6 +1956,21 @@ impl Compiler {
     /// Compile a record field access expression
     /// Stack effect: pushes the field value
     fn compile_record_access(&mut self, record: &Expr, field: &str) -> CompileResult<()> {
+        // `Module.binding` parses as a field access; bindings the registry
+        // compiles inline (user modules, provided members) resolve here,
+        // while stdlib modules stay runtime records
+        if let Expr::Var(module) = record {
+            let is_local = self.locals.iter().any(|local| &local.name == module);
+            let inline = self
+                .module_registry
+                .as_ref()
+                .and_then(|registry| registry.resolve_qualified(module, field))
+                .is_some_and(|expr| !matches!(expr, Expr::Var(_)));
+            if !is_local && inline {
+                return self.compile_qualified_var(std::slice::from_ref(module), field);
+            }
+        }
+
         // Compile the record expression
         self.compile_expr(record)?;
 
@@ -1966,4 +2158,164 @@ mod tests {
         let result = parse_qualified_name("add");
         assert!(result.is_none());
     }
+
+    fn closure_constant(chunk: &Chunk) -> Arc<Closure> {
+        chunk
+            .constants
+            .iter()
+            .find_map(|c| match c {
+                Value::Closure(closure) => Some(closure.clone()),
+                _ => None,
+            })
+            .expect("expected a closure constant")
+    }
+
+    #[test]
+    fn test_closure_captures_enclosing_local() {
+        // fun x -> fun y -> x + y
+        let expr = Expr::Lambda {
+            param: "x".to_string(),
+            body: Box::new(Expr::Lambda {
+                param: "y".to_string(),
+                body: Box::new(Expr::BinOp {
+                    op: BinOp::Add,
+                    left: Box::new(Expr::Var("x".to_string())),
+                    right: Box::new(Expr::Var("y".to_string())),
+                }),
+            }),
+        };
+        let chunk = Compiler::compile(&expr).unwrap();
+
+        let outer = closure_constant(&chunk);
+        assert!(outer.chunk.instructions.windows(2).any(|w| matches!(
+            w,
+            [Instruction::LoadLocal(0), Instruction::MakeClosure(_, 1)]
+        )));
+        let inner = closure_constant(&outer.chunk);
+        assert!(inner
+            .chunk
+            .instructions
+            .contains(&Instruction::LoadUpvalue(0)));
+        assert!(inner
+            .chunk
+            .instructions
+            .contains(&Instruction::LoadLocal(0)));
+    }
+
+    #[test]
+    fn test_recursive_top_level_binding_is_global() {
+        use crate::{Lexer, Parser};
+
+        let source = "let rec f n = f n\nlet rec f n = f n\nf";
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let chunk = Compiler::compile_program(&program).unwrap();
+
+        // Each binding has a global of its own, which its function calls
+        let globals: Vec<&Value> = chunk
+            .instructions
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::StoreGlobal(idx) => Some(&chunk.constants[*idx as usize]),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            globals,
+            vec![&Value::Str("f".to_string()), &Value::Str("f#1".to_string())]
+        );
+        let function = closure_constant(&chunk);
+        assert!(function
+            .chunk
+            .instructions
+            .iter()
+            .any(|i| matches!(i, Instruction::LoadGlobal(idx)
+                if function.chunk.constants[*idx as usize] == Value::Str("f".to_string()))));
+    }
+
+    #[test]
+    fn test_debug_info_from_spanned_program() {
+        use crate::{Lexer, Parser};
+
+        let source = "let square x =\n    let y = x * x in\n    y\nsquare 3\n";
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let program = Parser::new(tokens).with_spans().parse_program().unwrap();
+        let chunk = Compiler::compile_program(&program).unwrap();
+
+        let closure = chunk
+            .constants
+            .iter()
+            .find_map(|c| match c {
+                Value::Closure(closure) => Some(closure.clone()),
+                _ => None,
+            })
+            .expect("square should be compiled to a closure constant");
+        assert_eq!(closure.name.as_deref(), Some("square"));
+
+        let lines: Vec<u32> = (0..closure.chunk.instructions.len())
+            .filter_map(|i| closure.chunk.span_at(i).map(|s| s.line))
+            .collect();
+        assert!(lines.contains(&2));
+        assert!(lines.contains(&3));
+
+        // `y` is live while the body on line 3 runs
+        let body = (0..closure.chunk.instructions.len())
+            .find(|&i| closure.chunk.span_at(i).map(|s| s.line) == Some(3))
+            .unwrap();
+        let names: Vec<&str> = closure
+            .chunk
+            .locals_at(body)
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["x", "y"]);
+    }
+
+    #[test]
+    fn test_builtin_type_providers() {
+        use crate::{Lexer, Parser};
+
+        let compile = |source: &str| {
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            let program = Parser::new(tokens).parse_program().unwrap();
+            Compiler::compile_program(&program)
+        };
+
+        let path = std::env::temp_dir().join(format!("fusabi-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "name = \"app\"\n[server]\nport = 8080\n").unwrap();
+        let source = format!("type Config = TomlProvider<\"{}\">\n42", path.display());
+        let compiled = compile(&source);
+        std::fs::remove_file(&path).unwrap();
+        assert!(compiled.is_ok(), "{:?}", compiled.err());
+
+        let missing = compile("type Config = TomlProvider<\"missing.toml\">\n42");
+        assert!(matches!(missing, Err(CompileError::ProviderError(_))));
+        let unknown = compile("type Api = NoSuchProvider<\"api.json\">\n42");
+        assert!(matches!(unknown, Err(CompileError::ProviderError(_))));
+    }
+
+    #[test]
+    fn test_parsed_qualified_module_access() {
+        use crate::{Lexer, Parser};
+
+        // `Math.value` parses as a field access on `Math`
+        let source = "module Math =\n    let value = 100\n\n(fun x -> Math.value) 0";
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let chunk = Compiler::compile_program(&program).unwrap();
+
+        // The binding is compiled inline inside the lambda, not looked up at runtime
+        let Some(Value::Closure(closure)) = chunk
+            .constants
+            .iter()
+            .find(|c| matches!(c, Value::Closure(_)))
+        else {
+            panic!("expected a closure constant");
+        };
+        assert!(closure.chunk.constants.contains(&Value::Int(100)));
+        assert!(!closure
+            .chunk
+            .instructions
+            .contains(&Instruction::GetRecordField));
+    }
 }
@@ -37,6 +37,18 @@ pub struct TypeError {
     pub context: Vec<String>,
 }
 
+/// A mechanical fix for a type error: use `to` in place of the identifier `from`.
+///
+/// Produced by [`TypeError::suggested_replacements`] for tools (such as the
+/// language server) that can apply fixes rather than just display them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacement {
+    /// Identifier as written
+    pub from: String,
+    /// Suggested identifier
+    pub to: String,
+}
+
 /// Different kinds of type errors.
 #[derive(Debug, Clone)]
 pub enum TypeErrorKind {
@@ -222,7 +234,7 @@ impl TypeError {
             output.push_str(&format!("  --> {}\n", span.format_location()));
 
             // Show source line with highlight
-            if let Some(highlight) = self.format_source_highlight(source, span) {
+            if let Some(highlight) = format_source_highlight(source, span) {
                 output.push_str(&highlight);
             }
         }
@@ -243,39 +255,35 @@ impl TypeError {
         output
     }
 
-    /// Format source code with error highlighting.
-    fn format_source_highlight(&self, source: &str, span: &Span) -> Option<String> {
-        let lines: Vec<&str> = source.lines().collect();
-        if span.start.line == 0 || span.start.line > lines.len() {
-            return None;
-        }
-
-        let line_idx = span.start.line - 1;
-        let line = lines[line_idx];
-
-        let mut output = String::new();
-        output.push_str("   |\n");
-        output.push_str(&format!("{:3} | {}\n", span.start.line, line));
-        output.push_str("   | ");
-
-        // Highlight the error span
-        let start_col = span.start.column.saturating_sub(1);
-        let end_col = if span.is_single_line() {
-            span.end.column.saturating_sub(1)
-        } else {
-            line.len()
-        };
-
-        for i in 0..line.len() {
-            if i >= start_col && i < end_col {
-                output.push('^');
-            } else {
-                output.push(' ');
+    /// Identifier replacements that would fix this error, closest first.
+    ///
+    /// Type errors do not know which names were visible where they occurred, so
+    /// callers pass `in_scope` for unbound variables to be matched against.
+    pub fn suggested_replacements(&self, in_scope: &[&str]) -> Vec<Replacement> {
+        let (from, candidates) = match &self.kind {
+            TypeErrorKind::UnboundVariable { name } => {
+                (name, similar_names(name, in_scope.iter().copied()))
             }
-        }
-        output.push('\n');
-
-        Some(output)
+            TypeErrorKind::ExtraField {
+                field, suggestions, ..
+            } => (field, suggestions.clone()),
+            TypeErrorKind::FieldNotFound {
+                record_type: Type::Record(fields),
+                field,
+            } => (
+                field,
+                similar_names(field, fields.keys().map(String::as_str)),
+            ),
+            _ => return Vec::new(),
+        };
+        candidates
+            .into_iter()
+            .filter(|to| to != from)
+            .map(|to| Replacement {
+                from: from.clone(),
+                to,
+            })
+            .collect()
     }
 
     /// Suggest a fix for common error patterns.
@@ -537,6 +545,104 @@ impl fmt::Display for TypeError {
 
 impl std::error::Error for TypeError {}
 
+/// Names from `candidates` that look like typos of `name`, closest first.
+///
+/// Keeps at most three candidates within an edit distance of 3, skipping
+/// candidates that differ from `name` in more than half their characters.
+pub fn similar_names<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let name_len = name.chars().count();
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|candidate| (levenshtein_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| {
+            *distance <= 3 && *distance * 2 <= name_len.max(candidate.chars().count())
+        })
+        .collect();
+    scored.sort();
+    scored.dedup();
+    scored
+        .into_iter()
+        .take(3)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Compute Levenshtein distance between two strings.
+pub(crate) fn levenshtein_distance(s1: &str, s2: &str) -> usize {
+    let s1_chars: Vec<char> = s1.chars().collect();
+    let s2_chars: Vec<char> = s2.chars().collect();
+    let len1 = s1_chars.len();
+    let len2 = s2_chars.len();
+
+    if len1 == 0 {
+        return len2;
+    }
+    if len2 == 0 {
+        return len1;
+    }
+
+    let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
+
+    for (i, row) in matrix.iter_mut().enumerate().take(len1 + 1) {
+        row[0] = i;
+    }
+    for (j, cell) in matrix[0].iter_mut().enumerate().take(len2 + 1) {
+        *cell = j;
+    }
+
+    for i in 1..=len1 {
+        for j in 1..=len2 {
+            let cost = if s1_chars[i - 1] == s2_chars[j - 1] {
+                0
+            } else {
+                1
+            };
+            matrix[i][j] = std::cmp::min(
+                std::cmp::min(matrix[i - 1][j] + 1, matrix[i][j - 1] + 1),
+                matrix[i - 1][j - 1] + cost,
+            );
+        }
+    }
+
+    matrix[len1][len2]
+}
+
+/// Format the source line of `span` with the span underlined, as in
+/// [`TypeError::format`].
+pub fn format_source_highlight(source: &str, span: &Span) -> Option<String> {
+    let lines: Vec<&str> = source.lines().collect();
+    if span.start.line == 0 || span.start.line > lines.len() {
+        return None;
+    }
+
+    let line_idx = span.start.line - 1;
+    let line = lines[line_idx];
+
+    let mut output = String::new();
+    output.push_str("   |\n");
+    output.push_str(&format!("{:3} | {}\n", span.start.line, line));
+    output.push_str("   | ");
+
+    // Highlight the error span
+    let start_col = span.start.column.saturating_sub(1);
+    let end_col = if span.is_single_line() {
+        span.end.column.saturating_sub(1)
+    } else {
+        line.len()
+    };
+
+    for i in 0..line.len() {
+        if i >= start_col && i < end_col {
+            output.push('^');
+        } else {
+            output.push(' ');
+        }
+    }
+    output.push('\n');
+
+    Some(output)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -756,6 +862,59 @@ mod tests {
         assert!(suggestion.is_some());
     }
 
+    #[test]
+    fn test_suggested_replacements_unbound_variable() {
+        let err = TypeError::new(TypeErrorKind::UnboundVariable {
+            name: "nmae".to_string(),
+        });
+        let replacements = err.suggested_replacements(&["name", "age", "x"]);
+        assert_eq!(
+            replacements,
+            vec![Replacement {
+                from: "nmae".to_string(),
+                to: "name".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_suggested_replacements_extra_field() {
+        let err = TypeError::new(TypeErrorKind::ExtraField {
+            type_name: "Person".to_string(),
+            field: "agee".to_string(),
+            suggestions: vec!["age".to_string()],
+        });
+        let replacements = err.suggested_replacements(&[]);
+        assert_eq!(replacements.len(), 1);
+        assert_eq!(replacements[0].to, "age");
+    }
+
+    #[test]
+    fn test_suggested_replacements_none_for_mismatch() {
+        let err = TypeError::new(TypeErrorKind::Mismatch {
+            expected: Type::Int,
+            got: Type::String,
+        });
+        assert!(err.suggested_replacements(&["x"]).is_empty());
+    }
+
+    #[test]
+    fn test_similar_names_skips_unrelated() {
+        assert!(similar_names("x", ["y", "z"]).is_empty());
+        assert!(similar_names("nmae", ["File"]).is_empty());
+        assert_eq!(similar_names("lenght", ["length", "width"]), vec!["length"]);
+    }
+
+    #[test]
+    fn test_levenshtein_distance() {
+        assert_eq!(levenshtein_distance("", ""), 0);
+        assert_eq!(levenshtein_distance("hello", "hello"), 0);
+        assert_eq!(levenshtein_distance("hello", "hallo"), 1);
+        assert_eq!(levenshtein_distance("nam", "name"), 1);
+        assert_eq!(levenshtein_distance("age", "aeg"), 2); // swap is 2 edits
+        assert_eq!(levenshtein_distance("kitten", "sitting"), 3);
+    }
+
     // ========================================================================
     // Source Formatting Tests
     // ========================================================================
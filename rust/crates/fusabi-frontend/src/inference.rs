@@ -36,9 +36,10 @@
 //! - **Helpful errors**: Detailed error messages with suggestions
 //! - **Auto-recursive detection**: Automatically detects recursive lambdas (issue #126)
 
-use crate::ast::{BinOp, Expr, Literal, MatchArm, Pattern};
+use crate::ast::{BinOp, DuTypeDef, Expr, Literal, MatchArm, Pattern};
 use crate::error::{TypeError, TypeErrorKind};
 use crate::modules::ModuleRegistry;
+use crate::span::Span;
 use crate::types::{Substitution, Type, TypeEnv, TypeScheme, TypeVar};
 use std::collections::HashMap;
 
@@ -50,6 +51,8 @@ use std::collections::HashMap;
 pub enum Constraint {
     /// Two types must be equal
     Equal(Type, Type),
+    /// A record type must have the named field, of the given type
+    HasField(Type, String, Type),
 }
 
 /// Type inference engine implementing Algorithm W.
@@ -60,8 +63,14 @@ pub struct TypeInference {
     next_var_id: usize,
     /// Accumulated type constraints
     constraints: Vec<Constraint>,
+    /// Where each constraint arose, if the expression had a span
+    constraint_spans: Vec<Option<Span>>,
+    /// Span of the innermost spanned expression being inferred
+    current_span: Option<Span>,
     /// Optional module registry for type definition lookups
     module_registry: Option<ModuleRegistry>,
+    /// The union each registered constructor builds
+    unions: HashMap<String, String>,
 }
 
 #[allow(clippy::result_large_err)]
@@ -71,7 +80,10 @@ impl TypeInference {
         TypeInference {
             next_var_id: 0,
             constraints: Vec::new(),
+            constraint_spans: Vec::new(),
+            current_span: None,
             module_registry: None,
+            unions: HashMap::new(),
         }
     }
 
@@ -80,7 +92,10 @@ impl TypeInference {
         TypeInference {
             next_var_id: 0,
             constraints: Vec::new(),
+            constraint_spans: Vec::new(),
+            current_span: None,
             module_registry: Some(module_registry),
+            unions: HashMap::new(),
         }
     }
 
@@ -89,6 +104,14 @@ impl TypeInference {
         self.module_registry = Some(registry);
     }
 
+    /// Type the constructors of `union` as building values of that type,
+    /// rather than by their own names, so that they can be matched together.
+    pub fn register_union(&mut self, union: &DuTypeDef) {
+        for variant in &union.variants {
+            self.unions.insert(variant.name.clone(), union.name.clone());
+        }
+    }
+
     /// Generate a fresh type variable.
     ///
     /// Each call produces a unique type variable that hasn't been used before.
@@ -101,6 +124,7 @@ impl TypeInference {
     /// Add a constraint to the constraint set.
     fn add_constraint(&mut self, constraint: Constraint) {
         self.constraints.push(constraint);
+        self.constraint_spans.push(self.current_span);
     }
 
     /// Check if an expression references a variable (for auto-recursion detection).
@@ -209,6 +233,9 @@ impl TypeInference {
             Expr::While { cond, body } => {
                 Self::expr_references_var(cond, name) || Self::expr_references_var(body, name)
             }
+            Expr::Annotated { expr, .. } | Expr::Spanned { expr, .. } => {
+                Self::expr_references_var(expr, name)
+            }
             Expr::ComputationExpr { body, .. } => {
                 // Check if any statement in the CE body references the variable
                 body.iter().any(|stmt| {
@@ -226,7 +253,7 @@ impl TypeInference {
                 })
             }
             // Literals and control flow don't reference variables
-            Expr::Lit(_) | Expr::Break | Expr::Continue => false,
+            Expr::Lit(_) | Expr::Break | Expr::Continue | Expr::Error => false,
         }
     }
 
@@ -357,6 +384,10 @@ impl TypeInference {
                 Ok(Type::Unit)
             }
 
+            // Source that failed to parse can have any type, so that it causes
+            // no further errors
+            Expr::Error => Ok(Type::Var(self.fresh_var())),
+
             // Break statement
             Expr::Break => {
                 // Break has unit type but can only appear in loops
@@ -371,6 +402,29 @@ impl TypeInference {
                 Ok(Type::Unit)
             }
 
+            // Errors in the expression are located at its span, unless a
+            // more specific one is known
+            Expr::Spanned { expr, span } => {
+                let outer = self.current_span.replace(*span);
+                let result = self.infer(expr, env);
+                self.current_span = outer;
+                result.map_err(|err| match err.span {
+                    Some(_) => err,
+                    None => TypeError {
+                        span: Some(*span),
+                        ..err
+                    },
+                })
+            }
+
+            // Type annotation: the expression must have the declared type
+            Expr::Annotated { expr, ty } => {
+                let inferred = self.infer(expr, env)?;
+                let declared = self.annotation_type(ty);
+                self.add_constraint(Constraint::Equal(inferred.clone(), declared));
+                Ok(inferred)
+            }
+
             // Computation expression (stub implementation)
             Expr::ComputationExpr {
                 builder: _,
@@ -383,6 +437,51 @@ impl TypeInference {
         }
     }
 
+    /// Convert a type annotation into a type.
+    ///
+    /// Builtin types (and lists, arrays, tuples and functions of them) are
+    /// checked; user-defined type names are not yet tracked nominally, so they
+    /// become fresh type variables.
+    fn annotation_type(&mut self, ty: &crate::ast::TypeExpr) -> Type {
+        use crate::ast::TypeExpr;
+        match ty {
+            TypeExpr::Named(name) => {
+                let mut words = name.split_whitespace();
+                let base = words.next().unwrap_or_default();
+                let (base, arrays) = match base.find("[]") {
+                    Some(i) => (&base[..i], base[i..].matches("[]").count()),
+                    None => (base, 0),
+                };
+                let mut result = match base {
+                    "int" => Type::Int,
+                    "bool" => Type::Bool,
+                    "string" => Type::String,
+                    "unit" => Type::Unit,
+                    "float" => Type::Float,
+                    _ => return Type::Var(self.fresh_var()),
+                };
+                for _ in 0..arrays {
+                    result = Type::Array(Box::new(result));
+                }
+                for applied in words {
+                    result = match applied {
+                        "list" => Type::List(Box::new(result)),
+                        "array" => Type::Array(Box::new(result)),
+                        _ => return Type::Var(self.fresh_var()),
+                    };
+                }
+                result
+            }
+            TypeExpr::Tuple(types) => {
+                Type::Tuple(types.iter().map(|t| self.annotation_type(t)).collect())
+            }
+            TypeExpr::Function(param, ret) => Type::Function(
+                Box::new(self.annotation_type(param)),
+                Box::new(self.annotation_type(ret)),
+            ),
+        }
+    }
+
     /// Infer the type of a literal value.
     fn infer_literal(&self, lit: &Literal) -> Type {
         match lit {
@@ -570,6 +669,11 @@ impl TypeInference {
             self.add_constraint(Constraint::Equal(left_type, Type::Bool));
             self.add_constraint(Constraint::Equal(right_type, Type::Bool));
             Ok(Type::Bool)
+        } else if matches!(op, BinOp::Concat) {
+            // String concatenation: both operands and the result are strings
+            self.add_constraint(Constraint::Equal(left_type, Type::String));
+            self.add_constraint(Constraint::Equal(right_type, Type::String));
+            Ok(Type::String)
         } else {
             unreachable!("Unknown binary operator")
         }
@@ -851,63 +955,7 @@ impl TypeInference {
 
     /// Compute field name suggestions based on Levenshtein distance.
     fn compute_field_suggestions(field: &str, expected_fields: &[String]) -> Vec<String> {
-        let mut suggestions: Vec<(String, usize)> = expected_fields
-            .iter()
-            .map(|expected| {
-                let distance = Self::levenshtein_distance(field, expected);
-                (expected.clone(), distance)
-            })
-            .collect();
-
-        // Sort by distance and keep only close matches (distance <= 3)
-        suggestions.sort_by_key(|(_, dist)| *dist);
-        suggestions
-            .into_iter()
-            .filter(|(_, dist)| *dist <= 3)
-            .take(3) // Limit to top 3 suggestions
-            .map(|(name, _)| name)
-            .collect()
-    }
-
-    /// Compute Levenshtein distance between two strings.
-    fn levenshtein_distance(s1: &str, s2: &str) -> usize {
-        let len1 = s1.len();
-        let len2 = s2.len();
-
-        if len1 == 0 {
-            return len2;
-        }
-        if len2 == 0 {
-            return len1;
-        }
-
-        let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
-
-        for (i, row) in matrix.iter_mut().enumerate().take(len1 + 1) {
-            row[0] = i;
-        }
-        for (j, cell) in matrix[0].iter_mut().enumerate().take(len2 + 1) {
-            *cell = j;
-        }
-
-        let s1_chars: Vec<char> = s1.chars().collect();
-        let s2_chars: Vec<char> = s2.chars().collect();
-
-        for i in 1..=len1 {
-            for j in 1..=len2 {
-                let cost = if s1_chars[i - 1] == s2_chars[j - 1] {
-                    0
-                } else {
-                    1
-                };
-                matrix[i][j] = std::cmp::min(
-                    std::cmp::min(matrix[i - 1][j] + 1, matrix[i][j - 1] + 1),
-                    matrix[i - 1][j - 1] + cost,
-                );
-            }
-        }
-
-        matrix[len1][len2]
+        crate::error::similar_names(field, expected_fields.iter().map(String::as_str))
     }
 
     /// Infer the type of record field access.
@@ -919,17 +967,24 @@ impl TypeInference {
     ) -> Result<Type, TypeError> {
         let record_type = self.infer(record, env)?;
 
-        // Create a fresh type variable for the field
-        let field_type = Type::Var(self.fresh_var());
-
-        // Create a record type with at least this field
-        let mut expected_fields = HashMap::new();
-        expected_fields.insert(field.to_string(), field_type.clone());
-        let expected_record = Type::Record(expected_fields);
+        // A record whose fields are already known has the field's type
+        if let Type::Record(fields) = &record_type {
+            return match fields.get(field) {
+                Some(field_type) => Ok(field_type.clone()),
+                None => Err(TypeError::new(TypeErrorKind::FieldNotFound {
+                    record_type,
+                    field: field.to_string(),
+                })),
+            };
+        }
 
-        // Note: This is a simplified version. A full implementation would use
-        // row polymorphism or structural typing for better record handling.
-        self.add_constraint(Constraint::Equal(record_type, expected_record));
+        // Otherwise the record must have the field once its type is known
+        let field_type = Type::Var(self.fresh_var());
+        self.add_constraint(Constraint::HasField(
+            record_type,
+            field.to_string(),
+            field_type.clone(),
+        ));
 
         Ok(field_type)
     }
@@ -970,7 +1025,32 @@ impl TypeInference {
         }
 
         // Create variant type
-        Ok(Type::Variant(variant.to_string(), field_types))
+        Ok(self.variant_type(variant, field_types))
+    }
+
+    /// The type of a value built by the constructor `variant` from fields of
+    /// the given types.
+    ///
+    /// The constructors of `Option` and `Result` produce their parent type, so
+    /// that `Some 1` and `None` unify, as do those of registered unions, whose
+    /// fields are not checked. Other constructors are typed by name.
+    fn variant_type(&mut self, variant: &str, mut field_types: Vec<Type>) -> Type {
+        if let Some(union) = self.unions.get(variant) {
+            return Type::Variant(union.clone(), Vec::new());
+        }
+        match (variant, field_types.len()) {
+            ("Some", 1) => Type::Variant("Option".to_string(), field_types),
+            ("None", 0) => Type::Variant("Option".to_string(), vec![Type::Var(self.fresh_var())]),
+            ("Ok", 1) => {
+                field_types.push(Type::Var(self.fresh_var()));
+                Type::Variant("Result".to_string(), field_types)
+            }
+            ("Error", 1) => {
+                field_types.insert(0, Type::Var(self.fresh_var()));
+                Type::Variant("Result".to_string(), field_types)
+            }
+            _ => Type::Variant(variant.to_string(), field_types),
+        }
     }
 
     /// Infer the type of a match expression.
@@ -1080,7 +1160,7 @@ impl TypeInference {
                     field_types.push(Type::Var(self.fresh_var()));
                 }
 
-                let expected_variant = Type::Variant(variant.clone(), field_types.clone());
+                let expected_variant = self.variant_type(variant, field_types.clone());
                 self.add_constraint(Constraint::Equal(scrutinee_ty.clone(), expected_variant));
 
                 // Process field patterns
@@ -1097,26 +1177,80 @@ impl TypeInference {
     /// Solve all accumulated constraints using unification.
     ///
     /// Returns a substitution that satisfies all constraints.
+    ///
+    /// A field access on a record whose type is not yet known waits for the
+    /// other constraints to tell it. If none does, the record is taken to
+    /// have just that field.
     pub fn solve_constraints(&mut self) -> Result<Substitution, TypeError> {
         let mut subst = Substitution::empty();
+        let mut pending = Vec::new();
 
-        for constraint in &self.constraints {
-            match constraint {
-                Constraint::Equal(t1, t2) => {
-                    // Apply current substitution to both sides
-                    let t1_subst = t1.apply(&subst);
-                    let t2_subst = t2.apply(&subst);
-
-                    // Unify and compose substitutions
-                    let new_subst = self.unify(&t1_subst, &t2_subst)?;
-                    subst = Substitution::compose(&new_subst, &subst);
+        for i in 0..self.constraints.len() {
+            if !self.solve_constraint(i, &mut subst, false)? {
+                pending.push(i);
+            }
+        }
+        while !pending.is_empty() {
+            let waiting = pending.len();
+            let mut still_pending = Vec::new();
+            for &i in &pending {
+                if !self.solve_constraint(i, &mut subst, false)? {
+                    still_pending.push(i);
                 }
             }
+            if still_pending.len() == waiting {
+                self.solve_constraint(still_pending.remove(0), &mut subst, true)?;
+            }
+            pending = still_pending;
         }
 
         Ok(subst)
     }
 
+    /// Solve constraint `i`, extending `subst`. Returns false, leaving
+    /// `subst` alone, for a field access on a record not yet known, unless
+    /// `force` is set.
+    fn solve_constraint(
+        &self,
+        i: usize,
+        subst: &mut Substitution,
+        force: bool,
+    ) -> Result<bool, TypeError> {
+        // Errors are located where the constraint arose
+        let locate = |err: TypeError| match (err.span, self.constraint_spans[i]) {
+            (None, Some(span)) => TypeError {
+                span: Some(span),
+                ..err
+            },
+            _ => err,
+        };
+
+        // Apply current substitution to both sides
+        let (t1, t2) = match &self.constraints[i] {
+            Constraint::Equal(t1, t2) => (t1.apply(subst), t2.apply(subst)),
+            Constraint::HasField(record, field, field_type) => match record.apply(subst) {
+                Type::Record(fields) => match fields.get(field) {
+                    Some(ty) => (ty.apply(subst), field_type.apply(subst)),
+                    None => {
+                        return Err(locate(TypeError::new(TypeErrorKind::FieldNotFound {
+                            record_type: Type::Record(fields),
+                            field: field.clone(),
+                        })))
+                    }
+                },
+                Type::Var(_) if !force => return Ok(false),
+                record => {
+                    let expected = HashMap::from([(field.clone(), field_type.apply(subst))]);
+                    (record, Type::Record(expected))
+                }
+            },
+        };
+
+        let new_subst = self.unify(&t1, &t2).map_err(locate)?;
+        *subst = Substitution::compose(&new_subst, subst);
+        Ok(true)
+    }
+
     /// Unify two types using Robinson's unification algorithm.
     ///
     /// Returns a substitution that makes the types equal, or an error if unification fails.
@@ -1262,6 +1396,7 @@ impl TypeInference {
     pub fn infer_and_solve(&mut self, expr: &Expr, env: &TypeEnv) -> Result<Type, TypeError> {
         // Clear any previous constraints
         self.constraints.clear();
+        self.constraint_spans.clear();
 
         // Infer the type (generating constraints)
         let ty = self.infer(expr, env)?;
@@ -1684,13 +1819,46 @@ mod tests {
     }
 
     #[test]
-    fn test_levenshtein_distance() {
-        assert_eq!(TypeInference::levenshtein_distance("", ""), 0);
-        assert_eq!(TypeInference::levenshtein_distance("hello", "hello"), 0);
-        assert_eq!(TypeInference::levenshtein_distance("hello", "hallo"), 1);
-        assert_eq!(TypeInference::levenshtein_distance("nam", "name"), 1);
-        assert_eq!(TypeInference::levenshtein_distance("age", "aeg"), 2); // swap is 2 edits
-        assert_eq!(TypeInference::levenshtein_distance("kitten", "sitting"), 3);
+    fn test_infer_string_concat() {
+        let mut inf = TypeInference::new();
+        let env = TypeEnv::new();
+        let concat = |left: Expr, right: Expr| Expr::BinOp {
+            op: BinOp::Concat,
+            left: Box::new(left),
+            right: Box::new(right),
+        };
+        let str_lit = |s: &str| Expr::Lit(Literal::Str(s.to_string()));
+
+        let expr = concat(str_lit("a"), str_lit("b"));
+        assert_eq!(inf.infer_and_solve(&expr, &env).unwrap(), Type::String);
+
+        let expr = concat(str_lit("a"), lit_int(1));
+        assert!(inf.infer_and_solve(&expr, &env).is_err());
+    }
+
+    #[test]
+    fn test_infer_annotation() {
+        use crate::ast::TypeExpr;
+        let mut inf = TypeInference::new();
+        let env = TypeEnv::new();
+
+        let annotated = |expr: Expr, ty: &str| Expr::Annotated {
+            expr: Box::new(expr),
+            ty: TypeExpr::Named(ty.to_string()),
+        };
+
+        let ok = annotated(Expr::List(vec![lit_int(1)]), "int list");
+        assert_eq!(
+            inf.infer_and_solve(&ok, &env).unwrap(),
+            Type::List(Box::new(Type::Int))
+        );
+
+        let mismatch = annotated(lit_int(1), "string");
+        assert!(inf.infer_and_solve(&mismatch, &env).is_err());
+
+        // User-defined type names are not checked
+        let unchecked = annotated(lit_int(1), "Person");
+        assert_eq!(inf.infer_and_solve(&unchecked, &env).unwrap(), Type::Int);
     }
 
     #[test]
@@ -1715,4 +1883,60 @@ mod tests {
         let suggestions = TypeInference::compute_field_suggestions("xyz", &expected);
         assert!(suggestions.is_empty() || suggestions.len() <= 3);
     }
+
+    #[allow(clippy::result_large_err)]
+    fn infer_source(source: &str) -> Result<Type, TypeError> {
+        let tokens = crate::lexer::Lexer::new(source).tokenize().unwrap();
+        let expr = crate::parser::Parser::new(tokens)
+            .with_spans()
+            .parse()
+            .unwrap();
+        TypeInference::new().infer_and_solve(&expr, &TypeEnv::new())
+    }
+
+    #[test]
+    fn test_type_errors_have_spans() {
+        let error = infer_source("let x = 1 in\nlet y = if x then 1 else 2 in\ny").unwrap_err();
+        let span = error.span.expect("type error without a span");
+        assert_eq!((span.start.line, span.start.column), (2, 9));
+
+        let error = infer_source("let y = 1 in\nif true then y else \"a\"").unwrap_err();
+        assert_eq!(error.span.map(|span| span.start.line), Some(2));
+    }
+
+    #[test]
+    fn test_infer_option_and_result_constructors() {
+        let ty = infer_source("match Some 1 with\n| Some(x) -> x\n| None -> 0").unwrap();
+        assert_eq!(ty, Type::Int);
+
+        let ty = infer_source("if true then None else Some \"a\"").unwrap();
+        assert_eq!(ty, Type::Variant("Option".to_string(), vec![Type::String]));
+
+        let ty = infer_source("match Ok 1 with\n| Ok(x) -> x\n| Error(e) -> e").unwrap();
+        assert_eq!(ty, Type::Int);
+        assert!(infer_source("if true then Some 1 else Ok 1").is_err());
+    }
+
+    #[test]
+    fn test_field_access_checked_once_record_known() {
+        // The record's type is known only after the match is solved
+        let source = "match Some { name = \"a\"; age = 1 } with\n| Some(p) -> p.age\n| None -> 0";
+        assert_eq!(infer_source(source).unwrap(), Type::Int);
+
+        let source = "match Some { name = \"a\" } with\n| Some(p) -> p.nope\n| None -> 0";
+        let error = infer_source(source).unwrap_err();
+        assert!(
+            matches!(&error.kind, TypeErrorKind::FieldNotFound { field, .. } if field == "nope"),
+            "{}",
+            error
+        );
+        assert_eq!(error.span.map(|span| span.start.line), Some(2));
+
+        let error = infer_source("let p = { name = \"a\" } in p.nope").unwrap_err();
+        assert!(matches!(error.kind, TypeErrorKind::FieldNotFound { .. }));
+
+        // A record never known has the fields accessed
+        let ty = infer_source("fun r -> r.x + 1").unwrap();
+        assert!(matches!(ty, Type::Function(param, _) if matches!(*param, Type::Record(_))));
+    }
 }
@@ -12,6 +12,9 @@
 //! - Array syntax: [|, |], <-, .
 //! - Anonymous record syntax: {|, |}
 //! - Comments: single-line (//) and multi-line ((* *)) with nesting support
+//! - Documentation comments: `///` lines, kept as `DocComment` tokens
+//! - Directives: `#load "path"`, and `#if FEATURE` / `#else` / `#endif` for
+//!   conditional compilation
 //! - Position tracking for error reporting
 //!
 //! # Example
@@ -26,6 +29,7 @@
 //! ```
 
 pub use crate::span::{Position, Span};
+use std::collections::HashSet;
 use std::fmt;
 
 /// Token types in Mini-F# source code.
@@ -173,6 +177,9 @@ pub enum Token {
     /// #load directive (e.g., #load "path.fsx")
     LoadDirective(String),
 
+    /// `///` documentation comment, with the text after it (less one space)
+    DocComment(String),
+
     // Special
     /// End of file marker
     Eof,
@@ -246,6 +253,7 @@ impl fmt::Display for Token {
             Token::ReturnBang => write!(f, "return!"),
             Token::YieldBang => write!(f, "yield!"),
             Token::LoadDirective(path) => write!(f, "#load \"{}\"", path),
+            Token::DocComment(text) => write!(f, "/// {}", text),
             Token::Eof => write!(f, "EOF"),
         }
     }
@@ -258,12 +266,24 @@ pub struct TokenWithPos {
     pub token: Token,
     /// Position in source
     pub pos: Position,
+    /// Position just past the token, or `pos` if unknown
+    pub end: Position,
 }
 
 impl TokenWithPos {
     /// Create a new token with position.
     pub fn new(token: Token, pos: Position) -> Self {
-        TokenWithPos { token, pos }
+        TokenWithPos {
+            token,
+            pos,
+            end: pos,
+        }
+    }
+
+    /// Set where the token ends.
+    pub fn with_end(mut self, end: Position) -> Self {
+        self.end = end;
+        self
     }
 }
 
@@ -296,6 +316,12 @@ pub enum LexError {
     UnterminatedComment(Position),
     /// Unknown directive
     UnknownDirective(String, Position),
+    /// `#else` or `#endif` without a matching `#if`
+    UnmatchedDirective(String, Position),
+    /// `#if` without a matching `#endif`
+    UnterminatedConditional(Position),
+    /// An `#if`, `#else` or `#endif` line that is not well formed
+    MalformedDirective(String, Position),
 }
 
 impl fmt::Display for LexError {
@@ -316,15 +342,75 @@ impl fmt::Display for LexError {
             LexError::UnknownDirective(name, pos) => {
                 write!(f, "Unknown directive '{}' at {}", name, pos)
             }
+            LexError::UnmatchedDirective(name, pos) => {
+                write!(f, "'#{}' without a matching '#if' at {}", name, pos)
+            }
+            LexError::UnterminatedConditional(pos) => {
+                write!(f, "'#if' without a matching '#endif' at {}", pos)
+            }
+            LexError::MalformedDirective(message, pos) => {
+                write!(f, "{} at {}", message, pos)
+            }
+        }
+    }
+}
+
+impl LexError {
+    /// Where the error occurred.
+    pub fn position(&self) -> Position {
+        match self {
+            LexError::UnexpectedChar(_, pos)
+            | LexError::UnterminatedString(pos)
+            | LexError::InvalidNumber(_, pos)
+            | LexError::UnterminatedComment(pos)
+            | LexError::UnknownDirective(_, pos)
+            | LexError::UnmatchedDirective(_, pos)
+            | LexError::UnterminatedConditional(pos)
+            | LexError::MalformedDirective(_, pos) => *pos,
         }
     }
 }
 
 impl std::error::Error for LexError {}
 
+/// Error for an `#if` not followed by just a feature name
+fn if_expects_feature(pos: Position) -> LexError {
+    LexError::MalformedDirective(
+        "'#if' expects a single feature name ('!', '&&' and '||' are not supported; \
+         use '#else' for the opposite)"
+            .to_string(),
+        pos,
+    )
+}
+
+/// An open `#if` block.
+#[derive(Debug, Clone, Copy)]
+struct Conditional {
+    /// Position of the `#if`
+    start: Position,
+    /// Whether the enclosing code is being compiled
+    parent_active: bool,
+    /// Whether the feature named by the `#if` is enabled
+    condition: bool,
+    /// Whether the `#else` branch has been reached
+    in_else: bool,
+}
+
+impl Conditional {
+    /// Whether the code in the current branch is being compiled.
+    fn is_active(&self) -> bool {
+        self.parent_active && self.condition != self.in_else
+    }
+}
+
 /// Lexer for Mini-F# source code.
 ///
 /// The lexer converts source text into a stream of tokens with position information.
+///
+/// Code between `#if FEATURE` and `#else` or `#endif` is only lexed when
+/// `FEATURE` is enabled (see [`Lexer::with_features`]), and code between
+/// `#else` and `#endif` only when it is not. `#if` takes a single feature
+/// name: there are no `!`, `&&` or `||` operators.
 pub struct Lexer {
     /// Input characters
     input: Vec<char>,
@@ -334,6 +420,10 @@ pub struct Lexer {
     line: usize,
     /// Current column number (1-indexed)
     column: usize,
+    /// Features enabled for `#if` directives
+    features: HashSet<String>,
+    /// Open `#if` blocks, innermost last
+    conditionals: Vec<Conditional>,
 }
 
 impl Lexer {
@@ -344,9 +434,21 @@ impl Lexer {
             pos: 0,
             line: 1,
             column: 1,
+            features: HashSet::new(),
+            conditionals: Vec::new(),
         }
     }
 
+    /// Enable features for `#if` directives.
+    pub fn with_features<I, S>(mut self, features: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.features.extend(features.into_iter().map(Into::into));
+        self
+    }
+
     /// Tokenize the entire input, returning a vector of tokens with positions.
     pub fn tokenize(&mut self) -> Result<Vec<TokenWithPos>, LexError> {
         let mut tokens = Vec::new();
@@ -359,8 +461,9 @@ impl Lexer {
 
             let start_pos = self.current_position();
             let token = self.next_token()?;
-            tokens.push(TokenWithPos::new(token, start_pos));
+            tokens.push(TokenWithPos::new(token, start_pos).with_end(self.current_position()));
         }
+        self.check_conditionals_closed()?;
 
         tokens.push(TokenWithPos::new(Token::Eof, self.current_position()));
 
@@ -383,6 +486,7 @@ impl Lexer {
             let span = Span::new(start_pos, end_pos);
             tokens.push(TokenWithSpan::new(token, span));
         }
+        self.check_conditionals_closed()?;
 
         let eof_pos = self.current_position();
         tokens.push(TokenWithSpan::new(Token::Eof, Span::point(eof_pos)));
@@ -415,6 +519,7 @@ impl Lexer {
                 self.advance();
                 Ok(Token::Star)
             }
+            '/' if self.at_doc_comment() => Ok(self.lex_doc_comment()),
             '/' => {
                 self.advance();
                 Ok(Token::Slash)
@@ -586,7 +691,12 @@ impl Lexer {
                     'r' => '\r',
                     '\\' => '\\',
                     '"' => '"',
-                    c => c, // Unknown escape, just include the character
+                    c => {
+                        // Unknown escapes are kept as written, so regex
+                        // patterns like "\d+" need no doubling
+                        s.push('\\');
+                        c
+                    }
                 };
                 s.push(escaped);
                 self.advance();
@@ -777,6 +887,7 @@ impl Lexer {
                     self.column = 0; // Will be incremented to 1 by advance()
                     self.advance();
                 }
+                '/' if self.at_doc_comment() => break,
                 '/' if !self.is_at_end_or(1) && self.peek_char() == '/' => {
                     // Single-line comment
                     self.skip_single_line_comment();
@@ -785,12 +896,158 @@ impl Lexer {
                     // Multi-line comment
                     self.skip_multiline_comment()?;
                 }
+                '#' if self.peek_conditional_directive().is_some() => {
+                    self.lex_conditional_directive()?;
+                }
                 _ => break,
             }
         }
         Ok(())
     }
 
+    /// Peek at the name of the `#if`, `#else` or `#endif` directive at the
+    /// current position, if there is one.
+    fn peek_conditional_directive(&self) -> Option<&'static str> {
+        let rest = &self.input[self.pos + 1..];
+        ["if", "else", "endif"].into_iter().find(|name| {
+            rest.len() >= name.len()
+                && rest.iter().zip(name.chars()).all(|(a, b)| *a == b)
+                && rest
+                    .get(name.len())
+                    .map_or(true, |ch| !ch.is_alphanumeric() && *ch != '_')
+        })
+    }
+
+    /// Lex an `#if FEATURE`, `#else` or `#endif` directive, then skip the
+    /// lines of any branch that is not compiled.
+    fn lex_conditional_directive(&mut self) -> Result<(), LexError> {
+        let start_pos = self.current_position();
+        let directive = self.peek_conditional_directive().unwrap_or_default();
+        for _ in 0..=directive.len() {
+            self.advance(); // consume '#' and the directive name
+        }
+        while !self.is_at_end() && matches!(self.current_char(), ' ' | '\t') {
+            self.advance();
+        }
+
+        match directive {
+            "if" => {
+                let name_start = self.pos;
+                while !self.is_at_end()
+                    && (self.current_char().is_alphanumeric()
+                        || matches!(self.current_char(), '_' | '-'))
+                {
+                    self.advance();
+                }
+                let feature: String = self.input[name_start..self.pos].iter().collect();
+                if feature.is_empty() {
+                    return Err(if_expects_feature(start_pos));
+                }
+                self.conditionals.push(Conditional {
+                    start: start_pos,
+                    parent_active: self.is_active(),
+                    condition: self.features.contains(&feature),
+                    in_else: false,
+                });
+            }
+            _ => match self.conditionals.last_mut() {
+                Some(conditional) if directive == "else" && !conditional.in_else => {
+                    conditional.in_else = true;
+                }
+                Some(_) if directive == "endif" => {
+                    self.conditionals.pop();
+                }
+                _ => {
+                    return Err(LexError::UnmatchedDirective(
+                        directive.to_string(),
+                        start_pos,
+                    ))
+                }
+            },
+        }
+
+        // Nothing but a comment may follow the directive on its line
+        while !self.is_at_end() && matches!(self.current_char(), ' ' | '\t' | '\r') {
+            self.advance();
+        }
+        if !self.is_at_end() && self.current_char() == '/' && self.peek_char() == '/' {
+            self.skip_single_line_comment();
+        }
+        if !self.is_at_end() && self.current_char() != '\n' {
+            return Err(match directive {
+                "if" => if_expects_feature(start_pos),
+                _ => LexError::MalformedDirective(
+                    format!("'#{}' must be alone on its line", directive),
+                    start_pos,
+                ),
+            });
+        }
+
+        if !self.is_active() {
+            self.skip_inactive_lines();
+        }
+        Ok(())
+    }
+
+    /// Whether the code at the current position is being compiled.
+    fn is_active(&self) -> bool {
+        self.conditionals
+            .last()
+            .map_or(true, Conditional::is_active)
+    }
+
+    /// Skip whole lines up to the next conditional directive.
+    fn skip_inactive_lines(&mut self) {
+        loop {
+            self.skip_single_line_comment();
+            if self.is_at_end() {
+                return;
+            }
+            self.line += 1;
+            self.column = 0; // Will be incremented to 1 by advance()
+            self.advance(); // consume '\n'
+            while !self.is_at_end() && matches!(self.current_char(), ' ' | '\t' | '\r') {
+                self.advance();
+            }
+            if !self.is_at_end()
+                && self.current_char() == '#'
+                && self.peek_conditional_directive().is_some()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Check that every `#if` has been closed with `#endif`.
+    fn check_conditionals_closed(&self) -> Result<(), LexError> {
+        match self.conditionals.last() {
+            Some(conditional) => Err(LexError::UnterminatedConditional(conditional.start)),
+            None => Ok(()),
+        }
+    }
+
+    /// Check if a `///` documentation comment, not a `////` comment, starts here.
+    fn at_doc_comment(&self) -> bool {
+        let rest = &self.input[self.pos..];
+        rest.starts_with(&['/', '/', '/']) && rest.get(3) != Some(&'/')
+    }
+
+    /// Lex a `///` documentation comment up to the end of the line.
+    fn lex_doc_comment(&mut self) -> Token {
+        for _ in 0..3 {
+            self.advance();
+        }
+        if !self.is_at_end() && self.current_char() == ' ' {
+            self.advance();
+        }
+        let mut text = String::new();
+        while !self.is_at_end() && self.current_char() != '\n' {
+            text.push(self.current_char());
+            self.advance();
+        }
+        Token::DocComment(text.trim_end().to_string())
+    }
+
     /// Skip a single-line comment.
     fn skip_single_line_comment(&mut self) {
         while !self.is_at_end() && self.current_char() != '\n' {
@@ -1042,6 +1299,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_doc_comments() {
+        let source =
+            "/// Adds one.\n///\n///   Indented\n//// not docs\nlet inc x = x + 1 /// trailing";
+        let mut lexer = Lexer::new(source);
+        let tokens: Vec<Token> = lexer
+            .tokenize()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.token)
+            .collect();
+        assert_eq!(tokens[0], Token::DocComment("Adds one.".to_string()));
+        assert_eq!(tokens[1], Token::DocComment(String::new()));
+        assert_eq!(tokens[2], Token::DocComment("  Indented".to_string()));
+        assert_eq!(tokens[3], Token::Let);
+        assert_eq!(
+            tokens[tokens.len() - 2],
+            Token::DocComment("trailing".to_string())
+        );
+        // Division is unaffected
+        let tokens = Lexer::new("a / b").tokenize().unwrap();
+        assert_eq!(tokens[1].token, Token::Slash);
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let source = r#""a\tb\\c\"d" "\d+\.\w""#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens[0].token, Token::String("a\tb\\c\"d".to_string()));
+        // Unknown escapes keep their backslash
+        assert_eq!(tokens[1].token, Token::String(r"\d+\.\w".to_string()));
+    }
+
     #[test]
     fn test_empty_multiline_comment() {
         let source = "(**) let x = 1";
@@ -1209,4 +1500,89 @@ mod tests {
         assert_eq!(tokens[3].token, Token::YieldBang);
         assert_eq!(tokens[4].token, Token::Ident("y".to_string()));
     }
+
+    fn idents(tokens: &[TokenWithPos]) -> Vec<String> {
+        tokens
+            .iter()
+            .filter_map(|t| match &t.token {
+                Token::Ident(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn idents_index(tokens: &[TokenWithPos], name: &str) -> usize {
+        tokens
+            .iter()
+            .position(|t| t.token == Token::Ident(name.to_string()))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_conditional_directives() {
+        let source = "#if terminal\nlet a = 1\n#else\nlet b = \"#endif\"\n#endif\nlet c = 3";
+
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        assert_eq!(idents(&tokens), vec!["b", "c"]);
+        assert_eq!(tokens.last().unwrap().token, Token::Eof);
+
+        let tokens = Lexer::new(source)
+            .with_features(["terminal"])
+            .tokenize()
+            .unwrap();
+        assert_eq!(idents(&tokens), vec!["a", "c"]);
+        // Line numbers count the skipped lines
+        assert_eq!(tokens[idents_index(&tokens, "c")].pos.line, 6);
+    }
+
+    #[test]
+    fn test_nested_conditional_directives() {
+        let source = "#if a // host\n  #if b\nx\n  #else\ny\n  #endif\n#else\nz\n#endif";
+        let lex = |features: &[&str]| {
+            idents(
+                &Lexer::new(source)
+                    .with_features(features.iter().copied())
+                    .tokenize()
+                    .unwrap(),
+            )
+        };
+        assert_eq!(lex(&["a", "b"]), vec!["x"]);
+        assert_eq!(lex(&["a"]), vec!["y"]);
+        assert_eq!(lex(&["b"]), vec!["z"]);
+        assert_eq!(lex(&[]), vec!["z"]);
+    }
+
+    #[test]
+    fn test_conditional_directive_errors() {
+        assert!(matches!(
+            Lexer::new("let x = 1\n#if debug\nx").tokenize(),
+            Err(LexError::UnterminatedConditional(pos)) if pos.line == 2
+        ));
+        assert!(matches!(
+            Lexer::new("x\n#endif").tokenize(),
+            Err(LexError::UnmatchedDirective(name, _)) if name == "endif"
+        ));
+        assert!(matches!(
+            Lexer::new("#if a\n#else\n#else\n#endif").tokenize(),
+            Err(LexError::UnmatchedDirective(name, _)) if name == "else"
+        ));
+        assert!(matches!(
+            Lexer::new("#if a x\n#endif").tokenize(),
+            Err(LexError::MalformedDirective(_, _))
+        ));
+        assert!(matches!(
+            Lexer::new("#if a\n#else x\n#endif").tokenize(),
+            Err(LexError::MalformedDirective(_, _))
+        ));
+        let error = Lexer::new("#if !fancy\nx\n#endif").tokenize().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "'#if' expects a single feature name ('!', '&&' and '||' are not supported; \
+             use '#else' for the opposite) at 1:1"
+        );
+        assert!(matches!(
+            Lexer::new("#iffy").tokenize(),
+            Err(LexError::UnknownDirective(name, _)) if name == "iffy"
+        ));
+    }
 }
@@ -69,7 +69,7 @@ pub mod types;
 // Re-export commonly used types for convenience
 pub use ast::{BinOp, Expr, Literal, LoadDirective, ModuleDef, ModuleItem, Pattern, Program};
 pub use compiler::{CompileError, CompileOptions, Compiler};
-pub use error::{TypeError, TypeErrorKind};
+pub use error::{format_source_highlight, Replacement, TypeError, TypeErrorKind};
 pub use inference::TypeInference;
 pub use lexer::{LexError, Lexer, Position, Token, TokenWithPos};
 pub use loader::{FileLoader, LoadError, LoadedFile};
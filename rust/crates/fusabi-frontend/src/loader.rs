@@ -5,6 +5,7 @@
 //! - Path resolution (relative and absolute paths)
 //! - Circular dependency detection
 //! - Caching of loaded files to avoid recompilation
+//! - In-memory sources that take precedence over the disk (e.g. editor buffers)
 //!
 //! # Example
 //!
@@ -20,6 +21,7 @@
 use crate::ast::Program;
 use crate::lexer::{LexError, Lexer};
 use crate::parser::{ParseError, Parser};
+use crate::provider_resolver::resolve_source_paths;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::path::{Path, PathBuf};
@@ -71,6 +73,8 @@ pub struct LoadedFile {
     pub path: PathBuf,
     /// Parsed program AST
     pub program: Program,
+    /// Canonical paths of the files this file loads, in directive order
+    pub dependencies: Vec<PathBuf>,
 }
 
 /// File loader with caching and circular dependency detection
@@ -81,6 +85,8 @@ pub struct FileLoader {
     loading: HashSet<PathBuf>,
     /// Base directory for relative path resolution
     base_dir: PathBuf,
+    /// Sources that override the file contents on disk
+    sources: HashMap<PathBuf, String>,
 }
 
 impl FileLoader {
@@ -90,6 +96,7 @@ impl FileLoader {
             cache: HashMap::new(),
             loading: HashSet::new(),
             base_dir,
+            sources: HashMap::new(),
         }
     }
 
@@ -118,37 +125,55 @@ impl FileLoader {
 
         // Mark as loading
         self.loading.insert(resolved.clone());
+        let result = self.parse_with_dependencies(&resolved);
+        self.loading.remove(&resolved);
+
+        let (program, dependencies) = result?;
 
-        // Read and parse file
-        let source = std::fs::read_to_string(&resolved)
-            .map_err(|e| LoadError::IoError(format!("{}: {}", resolved.display(), e)))?;
+        // Create loaded file
+        let loaded = LoadedFile {
+            path: resolved.clone(),
+            program,
+            dependencies,
+        };
+
+        self.cache.insert(resolved.clone(), loaded);
+
+        Ok(self.cache.get(&resolved).unwrap())
+    }
+
+    /// Read and parse a file, then load everything it loads
+    fn parse_with_dependencies(
+        &mut self,
+        resolved: &Path,
+    ) -> Result<(Program, Vec<PathBuf>), LoadError> {
+        let source = match self.sources.get(resolved) {
+            Some(source) => source.clone(),
+            None => std::fs::read_to_string(resolved)
+                .map_err(|e| LoadError::IoError(format!("{}: {}", resolved.display(), e)))?,
+        };
 
         let mut lexer = Lexer::new(&source);
         let tokens = lexer
             .tokenize()
-            .map_err(|e| LoadError::LexError(resolved.clone(), e))?;
+            .map_err(|e| LoadError::LexError(resolved.to_path_buf(), e))?;
 
         let mut parser = Parser::new(tokens);
-        let program = parser
+        let mut program = parser
             .parse_program()
-            .map_err(|e| LoadError::ParseError(resolved.clone(), e))?;
+            .map_err(|e| LoadError::ParseError(resolved.to_path_buf(), e))?;
+        if let Some(dir) = resolved.parent() {
+            resolve_source_paths(&mut program, dir);
+        }
 
         // Recursively load dependencies
+        let mut dependencies = Vec::new();
         for directive in &program.directives {
-            self.load(&directive.path, &resolved)?;
+            let dependency = self.load(&directive.path, resolved)?.path.clone();
+            dependencies.push(dependency);
         }
 
-        // Create loaded file
-        let loaded = LoadedFile {
-            path: resolved.clone(),
-            program,
-        };
-
-        // Remove from loading, add to cache
-        self.loading.remove(&resolved);
-        self.cache.insert(resolved.clone(), loaded);
-
-        Ok(self.cache.get(&resolved).unwrap())
+        Ok((program, dependencies))
     }
 
     /// Resolve a path relative to a source file
@@ -161,10 +186,13 @@ impl FileLoader {
             from_file.parent().unwrap_or(&self.base_dir).join(path)
         };
 
-        // Canonicalize to get absolute path and resolve symlinks
-        resolved
-            .canonicalize()
-            .map_err(|_| LoadError::FileNotFound(resolved.clone()))
+        // Canonicalize to get absolute path and resolve symlinks. A file that
+        // only exists as an in-memory source is used as given.
+        match resolved.canonicalize() {
+            Ok(canonical) => Ok(canonical),
+            Err(_) if self.sources.contains_key(&resolved) => Ok(resolved),
+            Err(_) => Err(LoadError::FileNotFound(resolved)),
+        }
     }
 
     /// Get a loaded file from cache (if it exists)
@@ -181,6 +209,39 @@ impl FileLoader {
     pub fn invalidate(&mut self, path: &Path) {
         self.cache.remove(path);
     }
+
+    /// Use `source` as the contents of `path` instead of reading the disk
+    ///
+    /// `path` should be canonical (or absolute, for a file not yet on disk).
+    /// The cached copy of the file is invalidated.
+    pub fn set_source(&mut self, path: PathBuf, source: String) {
+        self.cache.remove(&path);
+        self.sources.insert(path, source);
+    }
+
+    /// Go back to reading `path` from disk
+    pub fn remove_source(&mut self, path: &Path) {
+        if self.sources.remove(path).is_some() {
+            self.cache.remove(path);
+        }
+    }
+
+    /// Cached files that load `path`, directly or through other files
+    pub fn dependents(&self, path: &Path) -> Vec<PathBuf> {
+        let mut found: Vec<PathBuf> = Vec::new();
+        let mut pending = vec![path.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            for file in self.cache.values() {
+                if file.dependencies.contains(&current) && !found.contains(&file.path) {
+                    found.push(file.path.clone());
+                    pending.push(file.path.clone());
+                }
+            }
+        }
+        found.retain(|p| p != path);
+        found.sort();
+        found
+    }
 }
 
 #[cfg(test)]
@@ -260,4 +321,74 @@ mod tests {
 
         assert!(matches!(result, Err(LoadError::FileNotFound(_))));
     }
+
+    #[test]
+    fn test_failed_load_can_be_retried() {
+        let temp_dir = TempDir::new().unwrap();
+        let main_file = temp_dir.path().join("main.fsx");
+        fs::write(&main_file, "#load \"missing.fsx\"\nlet x = 1").unwrap();
+
+        let mut loader = FileLoader::new(temp_dir.path().to_path_buf());
+        assert!(matches!(
+            loader.load("main.fsx", &main_file),
+            Err(LoadError::FileNotFound(_))
+        ));
+
+        // A failed load must not leave the file marked as loading
+        fs::write(temp_dir.path().join("missing.fsx"), "let y = 2").unwrap();
+        assert!(loader.load("main.fsx", &main_file).is_ok());
+    }
+
+    #[test]
+    fn test_source_overrides_disk() {
+        let temp_dir = TempDir::new().unwrap();
+        let utils_file = temp_dir.path().join("utils.fsx");
+        let main_file = temp_dir.path().join("main.fsx");
+        fs::write(&utils_file, "let a = 1").unwrap();
+        fs::write(&main_file, "#load \"utils.fsx\"\nlet b = a").unwrap();
+
+        let mut loader = FileLoader::new(temp_dir.path().to_path_buf());
+        let canonical = utils_file.canonicalize().unwrap();
+        loader.load("main.fsx", &main_file).unwrap();
+        assert_eq!(
+            loader.get_cached(&canonical).unwrap().program.items.len(),
+            1
+        );
+
+        loader.set_source(canonical.clone(), "let a = 1\nlet c = 3".to_string());
+        assert!(loader.get_cached(&canonical).is_none());
+        let loaded = loader.load("utils.fsx", &main_file).unwrap();
+        assert_eq!(loaded.program.items.len(), 2);
+
+        loader.remove_source(&canonical);
+        let loaded = loader.load("utils.fsx", &main_file).unwrap();
+        assert_eq!(loaded.program.items.len(), 1);
+    }
+
+    #[test]
+    fn test_dependents() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("utils.fsx"), "let a = 1").unwrap();
+        fs::write(
+            temp_dir.path().join("math.fsx"),
+            "#load \"utils.fsx\"\nlet b = 2",
+        )
+        .unwrap();
+        let main_file = temp_dir.path().join("main.fsx");
+        fs::write(&main_file, "#load \"math.fsx\"\nlet c = 3").unwrap();
+
+        let mut loader = FileLoader::new(temp_dir.path().to_path_buf());
+        let main = loader.load("main.fsx", &main_file).unwrap().path.clone();
+        let math = main.with_file_name("math.fsx");
+        let utils = main.with_file_name("utils.fsx");
+
+        assert_eq!(
+            loader.get_cached(&main).unwrap().dependencies,
+            vec![math.clone()]
+        );
+        let mut expected = vec![main.clone(), math];
+        expected.sort();
+        assert_eq!(loader.dependents(&utils), expected);
+        assert!(loader.dependents(&main).is_empty());
+    }
 }
@@ -24,7 +24,7 @@
 //! let result2 = Math.multiply 3 4  // Qualified access
 //! ```
 
-use crate::ast::{DuTypeDef, Expr, RecordTypeDef, TypeProviderDecl};
+use crate::ast::{DuTypeDef, Expr, ModuleDef, ModuleItem, RecordTypeDef, TypeProviderDecl};
 use crate::types::TypeEnv;
 use std::collections::HashMap;
 
@@ -208,6 +208,50 @@ impl ModuleRegistry {
         self.modules.insert(name, module);
     }
 
+    /// Register a parsed module definition and, recursively, its nested modules
+    ///
+    /// Let bindings become the module's value bindings (discard bindings are
+    /// skipped) and type definitions become its exported types. Nested modules
+    /// are registered under their own name.
+    pub fn register_module_def(&mut self, module: &ModuleDef) {
+        let mut bindings = HashMap::new();
+        let mut types = HashMap::new();
+
+        for item in &module.items {
+            match item {
+                ModuleItem::Let(name, expr) => {
+                    if let Some(name) = name {
+                        bindings.insert(name.clone(), expr.clone());
+                    }
+                }
+                ModuleItem::LetRec(rec_bindings) => {
+                    for (name, expr) in rec_bindings {
+                        bindings.insert(name.clone(), expr.clone());
+                    }
+                }
+                ModuleItem::TypeDef(type_def) => {
+                    let (type_name, module_type_def) = match type_def {
+                        crate::ast::TypeDefinition::Record(r) => {
+                            (r.name.clone(), TypeDefinition::Record(r.clone()))
+                        }
+                        crate::ast::TypeDefinition::Du(du) => {
+                            (du.name.clone(), TypeDefinition::Du(du.clone()))
+                        }
+                        crate::ast::TypeDefinition::Provider(p) => {
+                            (p.name.clone(), TypeDefinition::Provider(p.clone()))
+                        }
+                    };
+                    types.insert(type_name, module_type_def);
+                }
+                ModuleItem::Module(nested) => {
+                    self.register_module_def(nested);
+                }
+            }
+        }
+
+        self.register_module(module.name.clone(), bindings, types);
+    }
+
     /// Resolve a qualified name (e.g., "Math.add")
     ///
     /// # Returns
@@ -371,6 +415,32 @@ mod tests {
         assert_eq!(module_bindings.unwrap().len(), 2);
     }
 
+    #[test]
+    fn test_register_module_def_with_nested() {
+        let mut registry = ModuleRegistry::new();
+
+        let module = ModuleDef {
+            name: "Geometry".to_string(),
+            items: vec![
+                ModuleItem::Let(Some("origin".to_string()), Expr::Lit(Literal::Int(0))),
+                ModuleItem::Let(None, Expr::Lit(Literal::Unit)),
+                ModuleItem::Module(Box::new(ModuleDef {
+                    name: "Point".to_string(),
+                    items: vec![ModuleItem::Let(
+                        Some("x".to_string()),
+                        Expr::Lit(Literal::Int(1)),
+                    )],
+                })),
+            ],
+        };
+
+        registry.register_module_def(&module);
+
+        assert_eq!(registry.get_module_bindings("Geometry").unwrap().len(), 1);
+        assert!(registry.resolve_qualified("Geometry", "origin").is_some());
+        assert!(registry.resolve_qualified("Point", "x").is_some());
+    }
+
     #[test]
     fn test_resolve_nonexistent_module() {
         let registry = ModuleRegistry::new();
@@ -76,9 +76,12 @@
 //! ```
 use crate::ast::{
     BinOp, CEStatement, DuTypeDef, Expr, Import, Literal, LoadDirective, MatchArm, ModuleDef,
-    ModuleItem, Pattern, Program, TypeDefinition, TypeExpr, TypeProviderDecl, VariantDef,
+    ModuleItem, Pattern, Program, RecordTypeDef, TypeDefinition, TypeExpr, TypeProviderDecl,
+    VariantDef,
 };
 use crate::lexer::{Position, Token, TokenWithPos};
+use crate::span::Span;
+use std::collections::HashMap;
 use std::fmt;
 
 /// Parse errors with position information.
@@ -124,6 +127,18 @@ impl fmt::Display for ParseError {
     }
 }
 
+impl ParseError {
+    /// Where the error occurred, unless it is at the end of input.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            ParseError::UnexpectedToken { pos, .. } | ParseError::InvalidExpr { pos, .. } => {
+                Some(*pos)
+            }
+            ParseError::UnexpectedEof { .. } => None,
+        }
+    }
+}
+
 impl std::error::Error for ParseError {}
 
 type Result<T> = std::result::Result<T, ParseError>;
@@ -138,12 +153,64 @@ enum LetResult {
 pub struct Parser {
     tokens: Vec<TokenWithPos>,
     pos: usize,
+    /// Whether to wrap statement-level expressions in `Expr::Spanned`
+    record_spans: bool,
+    /// Whether to record errors and keep parsing (see `parse_program_recovering`)
+    recovering: bool,
+    /// Errors recorded while recovering
+    errors: Vec<ParseError>,
+    /// Column of the declaration being parsed; a declaration keyword starting
+    /// a line at or before it ends the declaration when recovering
+    decl_column: usize,
+    /// `///` comments, by the index of the token they precede
+    doc_comments: HashMap<usize, String>,
+    /// Documentation of the declarations parsed so far (see `Program::docs`)
+    docs: HashMap<String, String>,
 }
 
 impl Parser {
     /// Create a new parser from a token stream.
+    ///
+    /// Documentation comments are taken out of the stream and attached to
+    /// the declarations they precede.
     pub fn new(tokens: Vec<TokenWithPos>) -> Self {
-        Parser { tokens, pos: 0 }
+        let mut doc_comments: HashMap<usize, String> = HashMap::new();
+        let mut doc: Option<String> = None;
+        let mut stripped = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            if let Token::DocComment(text) = token.token {
+                doc = Some(match doc {
+                    Some(doc) => doc + "\n" + &text,
+                    None => text,
+                });
+            } else {
+                if let Some(doc) = doc.take() {
+                    doc_comments.insert(stripped.len(), doc);
+                }
+                stripped.push(token);
+            }
+        }
+        Parser {
+            tokens: stripped,
+            pos: 0,
+            record_spans: false,
+            recovering: false,
+            errors: vec![],
+            decl_column: 1,
+            doc_comments,
+            docs: HashMap::new(),
+        }
+    }
+
+    /// Record source locations in the AST.
+    ///
+    /// Let-bound values and bodies, branches, match arms, lambda and loop
+    /// bodies and top-level expressions are wrapped in `Expr::Spanned`, which
+    /// the compiler turns into the chunk's `SourceSpan` table (used by
+    /// debuggers to map instructions back to source lines).
+    pub fn with_spans(mut self) -> Self {
+        self.record_spans = true;
+        self
     }
 
     /// Parse the token stream into an expression AST.
@@ -151,7 +218,7 @@ impl Parser {
     /// This is the main entry point for parsing. It delegates to `parse_expr` for
     /// expression parsing. For backward compatibility with existing code.
     pub fn parse(&mut self) -> Result<Expr> {
-        let expr = self.parse_expr()?;
+        let expr = self.parse_located_expr()?;
 
         // Ensure we've consumed all tokens (except EOF)
         if !self.is_at_end() {
@@ -183,25 +250,31 @@ impl Parser {
 
         // Parse imports
         while self.peek() == Some(&Token::Open) {
-            imports.push(self.parse_import()?);
+            let start = self.start_declaration("");
+            let import = self.parse_import();
+            imports.extend(self.recover_declaration(import, start)?);
         }
 
         // Parse module definitions
         while self.peek() == Some(&Token::Module) {
-            modules.push(self.parse_module()?);
+            let start = self.start_declaration("");
+            let module = self.parse_module();
+            modules.extend(self.recover_declaration(module, start)?);
         }
 
         // Parse top-level items and main expression
         while !self.is_at_end() {
+            let start = self.start_declaration("");
             let tok = self.current_token();
             match tok.token {
                 Token::Let => {
                     // Check if it's a top-level binding or a let expression
                     // We parse the binding part first
-                    let binding = self.parse_let_binding_or_expr()?;
-                    match binding {
-                        LetResult::Item(item) => items.push(item),
-                        LetResult::Expr(expr) => {
+                    let name = self.binding_name();
+                    let binding = self.parse_let_binding_or_expr();
+                    match self.recover_declaration(binding, start)? {
+                        Some(LetResult::Item(item)) => items.push(item),
+                        Some(LetResult::Expr(expr)) => {
                             main_expr = Some(expr);
                             // If we found a main expression (e.g., let ... in ...),
                             // we shouldn't expect more top-level items unless they are part of that expression
@@ -211,23 +284,47 @@ impl Parser {
                             // F# doesn't really allow `let ... in ...; let ...` at top level.
                             break;
                         }
+                        // Keep the name bound, so that uses of it are not errors too
+                        None => items.push(ModuleItem::Let(name, Expr::Error)),
                     }
                 }
                 Token::Do => {
                     // do expr is syntax sugar for let _ = expr
                     self.advance();
-                    let expr = self.parse_expr()?;
-                    items.push(ModuleItem::Let(None, expr));
+                    let expr = self.parse_located_expr();
+                    let expr = self.recover_declaration(expr, start)?;
+                    items.push(ModuleItem::Let(None, expr.unwrap_or(Expr::Error)));
                 }
                 Token::Type => {
-                    let type_def = self.parse_type_def()?;
-                    items.push(ModuleItem::TypeDef(type_def));
+                    let type_def = self.parse_type_def();
+                    if let Some(type_def) = self.recover_declaration(type_def, start)? {
+                        items.push(ModuleItem::TypeDef(type_def));
+                    }
+                }
+                Token::Open | Token::Module if self.recovering => {
+                    // Out of place, but parsed so that the code after it can
+                    // use what it declares
+                    let tok = self.current_token();
+                    self.errors.push(ParseError::UnexpectedToken {
+                        expected: "expression".to_string(),
+                        found: tok.token.clone(),
+                        pos: tok.pos,
+                    });
+                    if self.peek() == Some(&Token::Open) {
+                        let import = self.parse_import();
+                        imports.extend(self.recover_declaration(import, start)?);
+                    } else {
+                        let module = self.parse_module();
+                        modules.extend(self.recover_declaration(module, start)?);
+                    }
                 }
                 _ => {
                     // Assume main expression
-                    let expr = self.parse_expr()?;
-                    main_expr = Some(expr);
-                    break;
+                    let expr = self.parse_located_expr();
+                    if let Some(expr) = self.recover_declaration(expr, start)? {
+                        main_expr = Some(expr);
+                        break;
+                    }
                 }
             }
         }
@@ -238,22 +335,68 @@ impl Parser {
             imports,
             items,
             main_expr,
+            docs: std::mem::take(&mut self.docs),
         })
     }
 
+    /// Parse a complete program, recovering from errors instead of stopping
+    /// at the first one.
+    ///
+    /// A declaration, `let` binding, match arm, list or array element or
+    /// record field that fails to parse is recorded as an error and skipped:
+    /// the parser resumes at the next separator of the enclosing construct or
+    /// the next declaration keyword (`let`, `type`, `module`, `open`, `do`)
+    /// that starts a line no further right than the current declaration.
+    /// Failed parts become `Expr::Error` nodes (or are left out), so the
+    /// returned program can still be analysed. All errors are returned, in
+    /// source order; the program is complete if there are none.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use fusabi_frontend::lexer::Lexer;
+    /// use fusabi_frontend::parser::Parser;
+    ///
+    /// let source = "let a = 1 +\nlet b = [1; 2 *; 3]\nlet c = 3";
+    /// let tokens = Lexer::new(source).tokenize().unwrap();
+    /// let (program, errors) = Parser::new(tokens).parse_program_recovering();
+    ///
+    /// assert_eq!(errors.len(), 2);
+    /// assert_eq!(program.items.len(), 3);
+    /// ```
+    pub fn parse_program_recovering(&mut self) -> (Program, Vec<ParseError>) {
+        self.recovering = true;
+        let result = self.parse_program();
+        self.recovering = false;
+        let mut errors = std::mem::take(&mut self.errors);
+        let program = result.unwrap_or_else(|error| {
+            // Errors are only returned outside any declaration
+            errors.push(error);
+            Program {
+                directives: vec![],
+                modules: vec![],
+                imports: vec![],
+                items: vec![],
+                main_expr: None,
+                docs: HashMap::new(),
+            }
+        });
+        (program, errors)
+    }
+
     /// Parse a module definition: module Math = <items>
     fn parse_module(&mut self) -> Result<ModuleDef> {
         self.expect_token(Token::Module)?;
         let name = self.expect_ident()?;
         self.expect_token(Token::Eq)?;
 
-        let items = self.parse_module_items()?;
+        let items = self.parse_module_items(&name)?;
 
         Ok(ModuleDef { name, items })
     }
 
     /// Parse module items (let bindings, types, nested modules)
-    fn parse_module_items(&mut self) -> Result<Vec<ModuleItem>> {
+    fn parse_module_items(&mut self, module: &str) -> Result<Vec<ModuleItem>> {
         let mut items = vec![];
 
         // Continue until we hit EOF or another module keyword
@@ -271,27 +414,35 @@ impl Parser {
                     // For modules, we expect declarations.
                     // However, parse_let_binding_parts is what we used before.
                     // Let's use parse_let_binding_or_expr and ensure it returns an Item.
-                    let result = self.parse_let_binding_or_expr()?;
-                    match result {
-                        LetResult::Item(item) => items.push(item),
-                        LetResult::Expr(_) => {
-                            return Err(ParseError::UnexpectedToken {
+                    let start = self.start_declaration(module);
+                    let name = self.binding_name();
+                    let result = self
+                        .parse_let_binding_or_expr()
+                        .and_then(|result| match result {
+                            LetResult::Item(item) => Ok(item),
+                            LetResult::Expr(_) => Err(ParseError::UnexpectedToken {
                                 expected: "module item".to_string(),
                                 found: Token::In,
                                 pos: self.current_token().pos,
-                            });
-                        }
-                    }
+                            }),
+                        });
+                    let item = self.recover_declaration(result, start)?;
+                    items.push(item.unwrap_or(ModuleItem::Let(name, Expr::Error)));
                 }
                 Token::Do => {
                     // do expr is syntax sugar for let _ = expr
+                    let start = self.start_declaration(module);
                     self.advance();
-                    let expr = self.parse_expr()?;
-                    items.push(ModuleItem::Let(None, expr));
+                    let expr = self.parse_located_expr();
+                    let expr = self.recover_declaration(expr, start)?;
+                    items.push(ModuleItem::Let(None, expr.unwrap_or(Expr::Error)));
                 }
                 Token::Type => {
-                    let type_def = self.parse_type_def()?;
-                    items.push(ModuleItem::TypeDef(type_def));
+                    let start = self.start_declaration(module);
+                    let type_def = self.parse_type_def();
+                    if let Some(type_def) = self.recover_declaration(type_def, start)? {
+                        items.push(ModuleItem::TypeDef(type_def));
+                    }
                 }
                 _ => break,
             }
@@ -310,13 +461,11 @@ impl Parser {
         if is_rec {
             // Recursive binding(s)
             let first_name = self.expect_ident()?;
-            let mut params = vec![];
-            while let Token::Ident(_) = &self.current_token().token {
-                params.push(self.expect_ident()?);
-            }
+            let params = self.parse_params()?;
 
+            let annotation = self.parse_annotation()?;
             self.expect_token(Token::Eq)?;
-            let mut first_value = self.parse_expr()?;
+            let mut first_value = Self::annotate(self.parse_located_expr()?, annotation);
 
             // Desugar params
             if !params.is_empty() {
@@ -336,12 +485,10 @@ impl Parser {
 
                 loop {
                     let name = self.expect_ident()?;
-                    let mut params = vec![];
-                    while let Token::Ident(_) = &self.current_token().token {
-                        params.push(self.expect_ident()?);
-                    }
+                    let params = self.parse_params()?;
+                    let annotation = self.parse_annotation()?;
                     self.expect_token(Token::Eq)?;
-                    let mut value = self.parse_expr()?;
+                    let mut value = Self::annotate(self.parse_located_expr()?, annotation);
 
                     if !params.is_empty() {
                         value = params
@@ -361,7 +508,7 @@ impl Parser {
 
                 // Now check for 'in'
                 if self.match_token(&Token::In) {
-                    let body = self.parse_expr()?;
+                    let body = self.parse_located_expr()?;
                     Ok(LetResult::Expr(Expr::LetRecMutual {
                         bindings,
                         body: Box::new(body),
@@ -377,7 +524,7 @@ impl Parser {
                 // Expect 'in' - wait, for top-level item, 'in' is NOT expected.
                 // If 'in' is present, it is an expression.
                 if self.match_token(&Token::In) {
-                    let body = self.parse_expr()?;
+                    let body = self.parse_located_expr()?;
                     Ok(LetResult::Expr(Expr::LetRec {
                         name: first_name,
                         value: Box::new(first_value),
@@ -405,13 +552,11 @@ impl Parser {
                 _ => Some(self.expect_ident()?),
             };
 
-            let mut params = vec![];
-            while let Token::Ident(_) = &self.current_token().token {
-                params.push(self.expect_ident()?);
-            }
+            let params = self.parse_params()?;
 
+            let annotation = self.parse_annotation()?;
             self.expect_token(Token::Eq)?;
-            let mut value = self.parse_expr()?;
+            let mut value = Self::annotate(self.parse_located_expr()?, annotation);
 
             if !params.is_empty() {
                 value = params
@@ -424,7 +569,7 @@ impl Parser {
             }
 
             if self.match_token(&Token::In) {
-                let body = self.parse_expr()?;
+                let body = self.parse_located_expr()?;
                 // Discard variables not allowed in let...in expressions
                 if name.is_none() {
                     return Err(ParseError::UnexpectedToken {
@@ -529,6 +674,13 @@ impl Parser {
             }
         }
 
+        // Record type: type Person = { name: string; age: int }
+        if self.check(&Token::LBrace) {
+            return self
+                .parse_record_type_body(type_name)
+                .map(TypeDefinition::Record);
+        }
+
         // Fall back to parsing as a discriminated union
         // We've already consumed `type TypeName =`, so we need to parse variants directly
         let mut variants = vec![];
@@ -585,6 +737,25 @@ impl Parser {
     // Expression Parsing
     // ========================================================================
 
+    /// Parse an expression, tagged with its location when recording spans
+    fn parse_located_expr(&mut self) -> Result<Expr> {
+        if !self.record_spans {
+            return self.parse_expr();
+        }
+        let start = self.current_token().pos;
+        let expr = self.parse_expr()?;
+        // The end of the last token of the expression, not the start of the
+        // next one, which may be on a later line
+        let end = match self.pos.checked_sub(1).and_then(|i| self.tokens.get(i)) {
+            Some(last) if last.end.offset > start.offset => last.end,
+            _ => start,
+        };
+        Ok(Expr::Spanned {
+            expr: Box::new(expr),
+            span: Span::new(start, end),
+        })
+    }
+
     /// Parse an expression
     fn parse_expr(&mut self) -> Result<Expr> {
         // Try let, if, lambda, match, while first, then fall through to parse_pipeline_expr
@@ -620,13 +791,13 @@ impl Parser {
 
         // Parse optional parameter list (for multi-parameter functions)
         // Example: let f x y = ...
-        let mut params = vec![];
-        while let Token::Ident(_) = &self.current_token().token {
-            params.push(self.expect_ident()?);
-        }
+        let params = self.parse_params()?;
 
+        let annotation = self.parse_annotation()?;
         self.expect_token(Token::Eq)?;
-        let mut value = self.parse_expr()?;
+        let value = self.parse_located_expr();
+        let value = self.recover(value, &[Token::In])?.unwrap_or(Expr::Error);
+        let mut value = Self::annotate(value, annotation);
 
         // If we have params, desugar into nested lambdas
         // let f x y = body  =>  let f = fun x -> fun y -> body
@@ -640,8 +811,10 @@ impl Parser {
                 });
         }
 
-        self.expect_token(Token::In)?;
-        let body = self.parse_expr()?;
+        let body = self
+            .expect_token(Token::In)
+            .and_then(|_| self.parse_located_expr());
+        let body = self.recover(body, &[])?.unwrap_or(Expr::Error);
 
         Ok(Expr::Let {
             name,
@@ -656,13 +829,11 @@ impl Parser {
         let first_name = self.expect_ident()?;
 
         // Parse parameters
-        let mut params = vec![];
-        while let Token::Ident(_) = &self.current_token().token {
-            params.push(self.expect_ident()?);
-        }
+        let params = self.parse_params()?;
 
+        let annotation = self.parse_annotation()?;
         self.expect_token(Token::Eq)?;
-        let mut first_value = self.parse_expr()?;
+        let mut first_value = Self::annotate(self.parse_located_expr()?, annotation);
 
         // Desugar params into nested lambdas
         if !params.is_empty() {
@@ -681,12 +852,10 @@ impl Parser {
 
             loop {
                 let name = self.expect_ident()?;
-                let mut params = vec![];
-                while let Token::Ident(_) = &self.current_token().token {
-                    params.push(self.expect_ident()?);
-                }
+                let params = self.parse_params()?;
+                let annotation = self.parse_annotation()?;
                 self.expect_token(Token::Eq)?;
-                let mut value = self.parse_expr()?;
+                let mut value = Self::annotate(self.parse_located_expr()?, annotation);
 
                 if !params.is_empty() {
                     value = params
@@ -705,7 +874,7 @@ impl Parser {
             }
 
             self.expect_token(Token::In)?;
-            let body = self.parse_expr()?;
+            let body = self.parse_located_expr()?;
 
             Ok(Expr::LetRecMutual {
                 bindings,
@@ -714,7 +883,7 @@ impl Parser {
         } else {
             // Single recursive function
             self.expect_token(Token::In)?;
-            let body = self.parse_expr()?;
+            let body = self.parse_located_expr()?;
 
             Ok(Expr::LetRec {
                 name: first_name,
@@ -731,10 +900,10 @@ impl Parser {
         let cond = self.parse_expr()?;
 
         self.expect_token(Token::Then)?;
-        let then_branch = self.parse_expr()?;
+        let then_branch = self.parse_located_expr()?;
 
         self.expect_token(Token::Else)?;
-        let else_branch = self.parse_expr()?;
+        let else_branch = self.parse_located_expr()?;
 
         Ok(Expr::If {
             cond: Box::new(cond),
@@ -748,10 +917,7 @@ impl Parser {
         self.expect_token(Token::Fun)?;
 
         // Parse parameter list
-        let mut params = vec![];
-        while let Token::Ident(_) = &self.current_token().token {
-            params.push(self.expect_ident()?);
-        }
+        let params = self.parse_params()?;
 
         if params.is_empty() {
             let tok = self.current_token();
@@ -763,7 +929,7 @@ impl Parser {
         }
 
         self.expect_token(Token::Arrow)?;
-        let body = self.parse_expr()?;
+        let body = self.parse_located_expr()?;
 
         // Desugar multi-param lambda into nested lambdas
         // fun x y -> body  =>  fun x -> fun y -> body
@@ -791,13 +957,11 @@ impl Parser {
             // Optional leading pipe
             self.match_token(&Token::Pipe);
 
-            let pattern = self.parse_pattern()?;
-
-            self.expect_token(Token::Arrow)?;
-
-            let body = Box::new(self.parse_expr()?);
-
-            arms.push(MatchArm { pattern, body });
+            let arm = self.parse_match_arm();
+            arms.push(self.recover(arm, &[Token::Pipe])?.unwrap_or(MatchArm {
+                pattern: Pattern::Wildcard,
+                body: Box::new(Expr::Error),
+            }));
 
             // Check if there's another arm (starts with |)
             if !self.check(&Token::Pipe) {
@@ -808,6 +972,17 @@ impl Parser {
         Ok(Expr::Match { scrutinee, arms })
     }
 
+    /// Parse a match arm after its `|`: pattern -> expr
+    fn parse_match_arm(&mut self) -> Result<MatchArm> {
+        let pattern = self.parse_pattern()?;
+
+        self.expect_token(Token::Arrow)?;
+
+        let body = Box::new(self.parse_located_expr()?);
+
+        Ok(MatchArm { pattern, body })
+    }
+
     /// Parse while loop: while cond do body
     fn parse_while(&mut self) -> Result<Expr> {
         self.expect_token(Token::While)?;
@@ -816,7 +991,7 @@ impl Parser {
 
         self.expect_token(Token::Do)?;
 
-        let body = Box::new(self.parse_expr()?);
+        let body = Box::new(self.parse_located_expr()?);
 
         Ok(Expr::While { cond, body })
     }
@@ -1306,10 +1481,8 @@ impl Parser {
                 let mut fields = vec![];
 
                 loop {
-                    let field_name = self.expect_ident()?;
-                    self.expect_token(Token::Eq)?;
-                    let value = self.parse_expr()?;
-                    fields.push((field_name, Box::new(value)));
+                    let field = self.parse_record_field();
+                    fields.extend(self.recover(field, &[Token::Semicolon, Token::RBrace])?);
 
                     // Check for semicolon or closing brace
                     if self.match_token(&Token::Semicolon) {
@@ -1335,10 +1508,8 @@ impl Parser {
         let mut fields = vec![];
 
         loop {
-            let field_name = self.expect_ident()?;
-            self.expect_token(Token::Eq)?;
-            let value = self.parse_expr()?;
-            fields.push((field_name, Box::new(value)));
+            let field = self.parse_record_field();
+            fields.extend(self.recover(field, &[Token::Semicolon, Token::RBrace])?);
 
             // Check for semicolon or closing brace
             if self.match_token(&Token::Semicolon) {
@@ -1359,6 +1530,14 @@ impl Parser {
         })
     }
 
+    /// Parse a field of a record literal or update: name = expr
+    fn parse_record_field(&mut self) -> Result<(String, Box<Expr>)> {
+        let field_name = self.expect_ident()?;
+        self.expect_token(Token::Eq)?;
+        let value = self.parse_expr()?;
+        Ok((field_name, Box::new(value)))
+    }
+
     /// Parse anonymous record literal: {| name = "John"; age = 30 |}
     /// Anonymous records use the {| ... |} syntax and work exactly like regular records
     /// but without requiring a type declaration.
@@ -1378,10 +1557,8 @@ impl Parser {
         let mut fields = vec![];
 
         loop {
-            let field_name = self.expect_ident()?;
-            self.expect_token(Token::Eq)?;
-            let value = self.parse_expr()?;
-            fields.push((field_name, Box::new(value)));
+            let field = self.parse_record_field();
+            fields.extend(self.recover(field, &[Token::Semicolon, Token::PipeRBrace])?);
 
             // Check for semicolon or closing brace
             if self.match_token(&Token::Semicolon) {
@@ -1504,9 +1681,7 @@ impl Parser {
                 // Check if this could be a variant constructor
                 // Use uppercase heuristic: if identifier starts with uppercase, it's likely a variant
                 // Exception: if followed by '.', it's likely a module access (e.g. String.length)
-                if (Self::is_uppercase_ident(&val) && !self.check(&Token::Dot))
-                    || matches!(self.current_token().token, Token::LParen)
-                {
+                if Self::is_uppercase_ident(&val) && !self.check(&Token::Dot) {
                     // This looks like a variant constructor
                     self.parse_variant_construct(val)
                 } else {
@@ -1588,7 +1763,9 @@ impl Parser {
         let mut elements = vec![];
 
         loop {
-            elements.push(self.parse_expr()?);
+            let element = self.parse_expr();
+            let sync = [Token::Comma, Token::Semicolon, Token::RBracket];
+            elements.push(self.recover(element, &sync)?.unwrap_or(Expr::Error));
 
             // Check for comma or semicolon separator
             if self.match_token(&Token::Comma) || self.match_token(&Token::Semicolon) {
@@ -1620,7 +1797,9 @@ impl Parser {
         let mut elements = vec![];
 
         loop {
-            elements.push(self.parse_expr()?);
+            let element = self.parse_expr();
+            let sync = [Token::Semicolon, Token::PipeRBracket];
+            elements.push(self.recover(element, &sync)?.unwrap_or(Expr::Error));
 
             // Check for semicolon separator
             if self.match_token(&Token::Semicolon) {
@@ -1639,6 +1818,41 @@ impl Parser {
         Ok(Expr::Array(elements))
     }
 
+    /// Parse the field list of a record type: { name: string; tags: string list }
+    ///
+    /// Fields are separated by `;` or simply placed on separate lines.
+    fn parse_record_type_body(&mut self, name: String) -> Result<RecordTypeDef> {
+        self.expect_token(Token::LBrace)?;
+
+        let mut fields = vec![];
+        while !self.check(&Token::RBrace) && !self.is_at_end() {
+            let field_name = self.expect_ident()?;
+            self.expect_token(Token::Colon)?;
+            let mut field_type = self.parse_type_expr()?;
+
+            // Postfix type application (string list, int option), as long as the
+            // identifier does not start the next field
+            while let Token::Ident(applied) = &self.current_token().token {
+                let starts_field = matches!(
+                    self.tokens.get(self.pos + 1).map(|t| &t.token),
+                    Some(Token::Colon)
+                );
+                if starts_field {
+                    break;
+                }
+                field_type = TypeExpr::Named(format!("{} {}", field_type, applied));
+                self.advance();
+            }
+
+            fields.push((field_name, field_type));
+            self.match_token(&Token::Semicolon);
+        }
+
+        self.expect_token(Token::RBrace)?;
+
+        Ok(RecordTypeDef { name, fields })
+    }
+
     /// Parse DU type definition: type Option = Some of int | None
     pub fn parse_du_type_def(&mut self) -> Result<DuTypeDef> {
         self.expect_token(Token::Type)?;
@@ -1725,6 +1939,85 @@ impl Parser {
         Ok(TypeExpr::Named(name))
     }
 
+    /// Parse an optional type annotation on a let binding: `: int list`
+    fn parse_annotation(&mut self) -> Result<Option<TypeExpr>> {
+        if self.match_token(&Token::Colon) {
+            Ok(Some(self.parse_annotation_type()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Wrap a bound value in its type annotation, if any.
+    fn annotate(value: Expr, annotation: Option<TypeExpr>) -> Expr {
+        match annotation {
+            Some(ty) => Expr::Annotated {
+                expr: Box::new(value),
+                ty,
+            },
+            None => value,
+        }
+    }
+
+    /// Parse an annotation type: `int`, `string list`, `int[]`,
+    /// `int * string`, `(int -> int) -> int`
+    fn parse_annotation_type(&mut self) -> Result<TypeExpr> {
+        let left = self.parse_annotation_tuple()?;
+        if self.match_token(&Token::Arrow) {
+            let right = self.parse_annotation_type()?;
+            return Ok(TypeExpr::Function(Box::new(left), Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn parse_annotation_tuple(&mut self) -> Result<TypeExpr> {
+        let first = self.parse_annotation_postfix()?;
+        if !self.check(&Token::Star) {
+            return Ok(first);
+        }
+        let mut types = vec![first];
+        while self.match_token(&Token::Star) {
+            types.push(self.parse_annotation_postfix()?);
+        }
+        Ok(TypeExpr::Tuple(types))
+    }
+
+    /// Parse a type with postfix applications (`int list option`, `int[]`).
+    fn parse_annotation_postfix(&mut self) -> Result<TypeExpr> {
+        let mut ty = if self.match_token(&Token::LParen) {
+            let inner = self.parse_annotation_type()?;
+            self.expect_token(Token::RParen)?;
+            inner
+        } else {
+            self.parse_simple_type()?
+        };
+
+        loop {
+            let applied = match &self.current_token().token {
+                Token::Ident(name) => name.clone(),
+                Token::LBracket
+                    if matches!(
+                        self.tokens.get(self.pos + 1).map(|t| &t.token),
+                        Some(Token::RBracket)
+                    ) =>
+                {
+                    self.advance();
+                    "[]".to_string()
+                }
+                _ => break,
+            };
+            self.advance();
+            ty = match ty {
+                TypeExpr::Named(name) if applied == "[]" => TypeExpr::Named(name + "[]"),
+                TypeExpr::Named(name) => TypeExpr::Named(format!("{} {}", name, applied)),
+                other if applied == "[]" => TypeExpr::Named(format!("({})[]", other)),
+                other => TypeExpr::Named(format!("({}) {}", other, applied)),
+            };
+        }
+
+        Ok(ty)
+    }
+
     // ========================================================================
     // Computation Expression Parsing
     // ========================================================================
@@ -1839,6 +2132,122 @@ impl Parser {
         }
     }
 
+    // ========================================================================
+    // Error Recovery
+    // ========================================================================
+
+    /// Note the start of a declaration in `module` (empty at the top level),
+    /// recording its documentation, and return its token index.
+    fn start_declaration(&mut self, module: &str) -> usize {
+        self.decl_column = self.current_token().pos.column;
+        if let Some(doc) = self.doc_comments.get(&self.pos) {
+            let name = match self.peek() {
+                Some(Token::Let) => self.binding_name(),
+                Some(Token::Type | Token::Module) => match self.tokens.get(self.pos + 1) {
+                    Some(TokenWithPos {
+                        token: Token::Ident(name),
+                        ..
+                    }) => Some(name.clone()),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(name) = name {
+                let name = if module.is_empty() {
+                    name
+                } else {
+                    format!("{}.{}", module, name)
+                };
+                self.docs.insert(name, doc.clone());
+            }
+        }
+        self.pos
+    }
+
+    /// The name bound by the `let` at the current token, if any
+    fn binding_name(&self) -> Option<String> {
+        let mut pos = self.pos + 1;
+        if matches!(self.tokens.get(pos).map(|t| &t.token), Some(Token::Rec)) {
+            pos += 1;
+        }
+        match self.tokens.get(pos).map(|t| &t.token) {
+            Some(Token::Ident(name)) if name != "_" => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    /// When recovering, record the error of a failed parse and skip to one of
+    /// the `sync` tokens (outside brackets) or the end of the declaration,
+    /// returning `None`. Otherwise return the error.
+    fn recover<T>(&mut self, result: Result<T>, sync: &[Token]) -> Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error) if self.recovering => {
+                // Report one error per position: an enclosing construct often
+                // fails at the same token
+                if self.errors.last().map(ParseError::position) != Some(error.position()) {
+                    self.errors.push(error);
+                }
+                self.synchronize(sync);
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Recover from a failed declaration that started at token `start`,
+    /// skipping at least one token so that parsing moves on.
+    fn recover_declaration<T>(&mut self, result: Result<T>, start: usize) -> Result<Option<T>> {
+        if result.is_err() && self.recovering && self.pos == start {
+            self.advance();
+        }
+        self.recover(result, &[])
+    }
+
+    /// Skip tokens up to one of `sync` outside brackets, a declaration
+    /// boundary, or the end of input.
+    fn synchronize(&mut self, sync: &[Token]) {
+        let mut depth = 0usize;
+        while !self.is_at_end() && !self.at_declaration_boundary() {
+            let token = &self.current_token().token;
+            if depth == 0 && sync.contains(token) {
+                return;
+            }
+            match token {
+                Token::LParen
+                | Token::LBracket
+                | Token::LBracketPipe
+                | Token::LBrace
+                | Token::LBracePipe => depth += 1,
+                Token::RParen
+                | Token::RBracket
+                | Token::PipeRBracket
+                | Token::RBrace
+                | Token::PipeRBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Whether the current token is a declaration keyword that starts a line
+    /// no further right than the declaration being parsed
+    fn at_declaration_boundary(&self) -> bool {
+        let tok = self.current_token();
+        let starts_line = self.pos == 0 || self.tokens[self.pos - 1].pos.line < tok.pos.line;
+        starts_line
+            && tok.pos.column <= self.decl_column
+            && matches!(
+                tok.token,
+                Token::Let
+                    | Token::Type
+                    | Token::Module
+                    | Token::Open
+                    | Token::Do
+                    | Token::LoadDirective(_)
+            )
+    }
+
     // ========================================================================
     // Helper Methods
     // ========================================================================
@@ -1848,15 +2257,33 @@ impl Parser {
         if self.is_at_end() {
             // Return a special EOF token
             static EOF_TOKEN: std::sync::OnceLock<TokenWithPos> = std::sync::OnceLock::new();
-            EOF_TOKEN.get_or_init(|| TokenWithPos {
-                token: Token::Eof,
-                pos: Position::new(0, 0, 0),
-            })
+            EOF_TOKEN.get_or_init(|| TokenWithPos::new(Token::Eof, Position::new(0, 0, 0)))
         } else {
             &self.tokens[self.pos]
         }
     }
 
+    /// Parse the parameters of a `let` or `fun`: identifiers, with `()` for a unit parameter
+    fn parse_params(&mut self) -> Result<Vec<String>> {
+        let mut params = vec![];
+        loop {
+            match &self.current_token().token {
+                Token::Ident(_) => params.push(self.expect_ident()?),
+                Token::LParen
+                    if matches!(
+                        self.tokens.get(self.pos + 1).map(|t| &t.token),
+                        Some(Token::RParen)
+                    ) =>
+                {
+                    self.advance();
+                    self.advance();
+                    params.push("_".to_string());
+                }
+                _ => return Ok(params),
+            }
+        }
+    }
+
     /// Peek at the current token without consuming it
     fn peek(&self) -> Option<&Token> {
         if self.is_at_end() {
@@ -2030,12 +2457,46 @@ mod tests {
         assert!(expr.is_app());
     }
 
+    #[test]
+    fn test_parse_application_to_parenthesized_argument() {
+        match parse_str("g (f 2)").unwrap() {
+            Expr::App { func, arg } => {
+                assert_eq!(*func, Expr::Var("g".to_string()));
+                assert!(arg.is_app());
+            }
+            other => panic!("Expected application, got {:?}", other),
+        }
+
+        // Uppercase names are still variant constructors
+        assert!(matches!(
+            parse_str("Some (x)").unwrap(),
+            Expr::VariantConstruct { .. }
+        ));
+    }
+
     #[test]
     fn test_parse_lambda() {
         let expr = parse_str("fun x -> x").unwrap();
         assert!(expr.is_lambda());
     }
 
+    #[test]
+    fn test_parse_unit_param() {
+        let expr = parse_str("fun () -> 1").unwrap();
+        assert!(expr.is_lambda());
+
+        match parse_str("let f () x = x in f").unwrap() {
+            Expr::Let { value, .. } => match *value {
+                Expr::Lambda { param, body } => {
+                    assert_eq!(param, "_");
+                    assert!(body.is_lambda());
+                }
+                other => panic!("Expected lambda, got {:?}", other),
+            },
+            other => panic!("Expected let, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_parse_string_concat() {
         let expr = parse_str(r#""hello" ++ "world""#).unwrap();
@@ -2460,6 +2921,73 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_record_type_def() {
+        let program =
+            parse_program_str("type Person = { name: string; tags: string list\n age: int }")
+                .unwrap();
+        assert_eq!(program.items.len(), 1);
+
+        match &program.items[0] {
+            ModuleItem::TypeDef(TypeDefinition::Record(r)) => {
+                assert_eq!(r.name, "Person");
+                assert_eq!(
+                    r.fields,
+                    vec![
+                        ("name".to_string(), TypeExpr::Named("string".to_string())),
+                        (
+                            "tags".to_string(),
+                            TypeExpr::Named("string list".to_string())
+                        ),
+                        ("age".to_string(), TypeExpr::Named("int".to_string())),
+                    ]
+                );
+            }
+            _ => panic!("Expected record type definition"),
+        }
+    }
+
+    #[test]
+    fn test_parse_let_annotations() {
+        let program =
+            parse_program_str("let x : int list = [1]\nlet f a b : int * string = (a, b)").unwrap();
+
+        match &program.items[0] {
+            ModuleItem::Let(Some(name), Expr::Annotated { ty, .. }) => {
+                assert_eq!(name, "x");
+                assert_eq!(ty, &TypeExpr::Named("int list".to_string()));
+            }
+            other => panic!("Expected annotated binding, got {:?}", other),
+        }
+
+        // On a function the annotation applies to the body (the return type)
+        match &program.items[1] {
+            ModuleItem::Let(Some(_), Expr::Lambda { body, .. }) => match body.as_ref() {
+                Expr::Lambda { body, .. } => assert!(body.is_annotated()),
+                other => panic!("Expected inner lambda, got {:?}", other),
+            },
+            other => panic!("Expected function binding, got {:?}", other),
+        }
+
+        let expr = parse_str("let g : (int -> int) -> int[] = h in g").unwrap();
+        match expr {
+            Expr::Let { value, .. } => match *value {
+                Expr::Annotated { ty, .. } => assert_eq!(
+                    ty,
+                    TypeExpr::Function(
+                        Box::new(TypeExpr::Function(
+                            Box::new(TypeExpr::Named("int".to_string())),
+                            Box::new(TypeExpr::Named("int".to_string())),
+                        )),
+                        Box::new(TypeExpr::Named("int[]".to_string())),
+                    )
+                ),
+                other => panic!("Expected annotation, got {:?}", other),
+            },
+            other => panic!("Expected let, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_parse_du_still_works() {
         // Ensure regular DU parsing still works
@@ -2503,4 +3031,182 @@ mod tests {
             _ => panic!("Expected type provider"),
         }
     }
+
+    #[test]
+    fn test_with_spans_records_positions() {
+        let tokens = Lexer::new("let x = 1 in\nx + 2").tokenize().unwrap();
+        let expr = Parser::new(tokens).with_spans().parse().unwrap();
+        let Expr::Spanned { expr, span } = &expr else {
+            panic!("expected a spanned expression, got {:?}", expr);
+        };
+        assert_eq!(span.start.line, 1);
+        let Expr::Let { body, .. } = expr.as_ref() else {
+            panic!("expected let, got {:?}", expr);
+        };
+        assert!(matches!(body.as_ref(), Expr::Spanned { span, .. } if span.start.line == 2));
+
+        // A span ends at its last token, not at the next line
+        let tokens = Lexer::new("let a = 1 + \"x\"\n").tokenize().unwrap();
+        let program = Parser::new(tokens).with_spans().parse_program().unwrap();
+        let ModuleItem::Let(_, Expr::Spanned { span, .. }) = &program.items[0] else {
+            panic!("expected a spanned binding, got {:?}", program.items);
+        };
+        assert_eq!((span.start.line, span.start.column), (1, 9));
+        assert_eq!((span.end.line, span.end.column), (1, 16));
+
+        // Without span recording the tree is unchanged
+        assert!(matches!(
+            parse_str("let x = 1 in\nx + 2").unwrap(),
+            Expr::Let { .. }
+        ));
+    }
+
+    fn parse_recovering(input: &str) -> (Program, Vec<ParseError>) {
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        Parser::new(tokens).parse_program_recovering()
+    }
+
+    fn error_lines(errors: &[ParseError]) -> Vec<usize> {
+        errors
+            .iter()
+            .map(|e| e.position().map_or(0, |pos| pos.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_recover_at_declarations() {
+        let source = "let a = 1 +\n\
+                      let f x =\n    let y = x\n    let z = y\n    z\n\
+                      type T = { name: }\n\
+                      let ok = 3\n";
+        let (program, errors) = parse_recovering(source);
+        assert_eq!(error_lines(&errors), [2, 4, 6]);
+
+        let names: Vec<_> = program
+            .items
+            .iter()
+            .map(|item| match item {
+                ModuleItem::Let(name, _) => name.clone().unwrap(),
+                ModuleItem::TypeDef(_) => "type".to_string(),
+                other => panic!("unexpected item {:?}", other),
+            })
+            .collect();
+        assert_eq!(names, ["a", "f", "ok"]);
+        assert!(matches!(&program.items[0], ModuleItem::Let(_, Expr::Error)));
+        assert!(matches!(
+            &program.items[2],
+            ModuleItem::Let(_, Expr::Lit(Literal::Int(3)))
+        ));
+
+        // The first error is the one parse_program reports
+        let first = parse_program_str(source).unwrap_err();
+        assert_eq!(errors[0], first);
+    }
+
+    #[test]
+    fn test_recover_in_expressions() {
+        let source = "let xs = [1; 2 *; 3]\n\
+                      let r = { a = 1; b = ; c = 3 }\n\
+                      let m = match xs with\n    | [] -> 0 +\n    | _ -> 1\n\
+                      let v = let y = ) in y\n";
+        let (program, errors) = parse_recovering(source);
+        assert_eq!(error_lines(&errors), [1, 2, 4, 6]);
+
+        let values: Vec<_> = program
+            .items
+            .iter()
+            .map(|item| match item {
+                ModuleItem::Let(_, value) => value,
+                other => panic!("unexpected item {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            *values[0],
+            Expr::List(vec![
+                Expr::Lit(Literal::Int(1)),
+                Expr::Error,
+                Expr::Lit(Literal::Int(3))
+            ])
+        );
+        let Expr::RecordLiteral { fields, .. } = values[1] else {
+            panic!("expected a record, got {:?}", values[1]);
+        };
+        let fields: Vec<_> = fields.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(fields, ["a", "c"]);
+        let Expr::Match { arms, .. } = values[2] else {
+            panic!("expected a match, got {:?}", values[2]);
+        };
+        assert_eq!(arms.len(), 2);
+        assert_eq!(*arms[0].body, Expr::Error);
+        assert!(matches!(values[3], Expr::Let { value, .. } if **value == Expr::Error));
+    }
+
+    #[test]
+    fn test_recover_without_errors() {
+        let source = "module M =\n    let x = 1\n\nlet y = 2\ny";
+        let (program, errors) = parse_recovering(source);
+        assert!(errors.is_empty());
+        assert_eq!(program, parse_program_str(source).unwrap());
+
+        let (program, errors) = parse_recovering("let x = (1 +");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            program.items,
+            [ModuleItem::Let(Some("x".into()), Expr::Error)]
+        );
+    }
+
+    #[test]
+    fn test_parse_doc_comments() {
+        fn docs(program: &Program) -> Vec<(&str, &str)> {
+            let mut docs: Vec<_> = program
+                .docs
+                .iter()
+                .map(|(name, doc)| (name.as_str(), doc.as_str()))
+                .collect();
+            docs.sort();
+            docs
+        }
+
+        let source = "/// Adds two numbers.\n\
+                      /// Example: `add 1 2`\n\
+                      let add x y = x + y\n\
+                      \n\
+                      /// Shapes.\n\
+                      type Shape = Circle of float | Square of float\n\
+                      let undocumented = 1\n";
+        let program = parse_program_str(source).unwrap();
+        assert_eq!(
+            docs(&program),
+            [
+                ("Shape", "Shapes."),
+                ("add", "Adds two numbers.\nExample: `add 1 2`")
+            ]
+        );
+
+        // Documentation does not change the program
+        let undocumented: String = source
+            .lines()
+            .filter(|line| !line.starts_with("///"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let mut expected = parse_program_str(&undocumented).unwrap();
+        expected.docs = program.docs.clone();
+        assert_eq!(program, expected);
+
+        let source = "/// Math helpers.\n\
+                      module Math =\n\
+                      \x20   /// Squares a number.\n\
+                      \x20   let square x =\n\
+                      \x20       /// Not a declaration\n\
+                      \x20       x * x\n";
+        let program = parse_program_str(source).unwrap();
+        assert_eq!(
+            docs(&program),
+            [
+                ("Math", "Math helpers."),
+                ("Math.square", "Squares a number.")
+            ]
+        );
+    }
 }
@@ -11,17 +11,24 @@
 //! 3. Call `resolve_schema` and `generate_types` on the provider
 //! 4. Convert generated types to frontend `Type` and `TypeDefinition`
 //! 5. Inject resolved types into the `TypeEnv` for type inference
+//! 6. Erase provided members (`Alias.tryMatch`) to applications of their
+//!    stdlib target, registered as bindings of a module named after the alias
+//!
+//! Sources naming a file are read relative to the working directory; scripts
+//! read from a file first make theirs relative to the file's directory with
+//! [`resolve_source_paths`].
 
 use crate::ast::{
-    DuTypeDef, RecordTypeDef, TypeDefinition as AstTypeDef, TypeExpr as AstTypeExpr,
-    TypeProviderDecl, VariantDef as AstVariantDef,
+    DuTypeDef, Expr, Literal, ModuleItem, Program, RecordTypeDef, TypeDefinition as AstTypeDef,
+    TypeExpr as AstTypeExpr, TypeProviderDecl, VariantDef as AstVariantDef,
 };
-use crate::types::{Type, TypeEnv, TypeScheme};
+use crate::types::{Type, TypeEnv, TypeScheme, TypeVar};
 use fusabi_type_providers::{
-    GeneratedTypes, ProviderParams, ProviderRegistry, TypeDefinition as ProviderTypeDef,
-    TypeExpr as ProviderTypeExpr, TypeProvider,
+    GeneratedTypes, ProvidedMember, ProviderCache, ProviderError, ProviderParams, ProviderRegistry,
+    TypeDefinition as ProviderTypeDef, TypeExpr as ProviderTypeExpr, TypeProvider,
 };
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 /// Error type for provider resolution failures
@@ -64,6 +71,103 @@ pub struct ResolvedTypes {
     pub types: Vec<AstTypeDef>,
     /// Type schemes for type environment injection
     pub type_schemes: HashMap<String, TypeScheme>,
+    /// Provided members by name, erased to the expressions implementing them
+    pub members: HashMap<String, Expr>,
+}
+
+impl ResolvedTypes {
+    /// The type of the alias as a value: a record of its provided members,
+    /// as `Alias.member` reads. The generated records the members mention
+    /// are spelled out field by field so that field access can be checked;
+    /// records that contain themselves may be any type.
+    pub fn members_scheme(&self) -> TypeScheme {
+        let mut records = HashMap::new();
+        for type_def in &self.types {
+            if let AstTypeDef::Record(record) = type_def {
+                if let Some(TypeScheme {
+                    ty: Type::Record(fields),
+                    ..
+                }) = self.type_schemes.get(&record.name)
+                {
+                    records.insert(record.name.as_str(), fields);
+                }
+            }
+        }
+
+        let mut expansion = Expansion {
+            records: &records,
+            expanding: Vec::new(),
+            open: Vec::new(),
+        };
+        let prefix = format!("{}.", self.alias);
+        let mut members = HashMap::new();
+        for (name, scheme) in &self.type_schemes {
+            if let Some(member) = name.strip_prefix(&prefix) {
+                members.insert(member.to_string(), expansion.expand(&scheme.ty));
+            }
+        }
+        let vars = expansion.open.into_iter().map(|(_, var)| var).collect();
+        TypeScheme::poly(vars, Type::Record(members))
+    }
+}
+
+/// Spells out the generated types named in a member's type
+struct Expansion<'a> {
+    records: &'a HashMap<&'a str, &'a HashMap<String, Type>>,
+    /// The records being spelled out, innermost last
+    expanding: Vec<String>,
+    /// The type standing for each record that contains itself, by name
+    open: Vec<(String, TypeVar)>,
+}
+
+impl Expansion<'_> {
+    fn expand(&mut self, ty: &Type) -> Type {
+        match ty {
+            Type::Variant(name, args) if args.is_empty() => {
+                let name = name.as_str();
+                match self.records.get(name) {
+                    Some(fields) if !self.expanding.iter().any(|n| n == name) => {
+                        self.expanding.push(name.to_string());
+                        let fields = fields
+                            .iter()
+                            .map(|(field, ty)| (field.clone(), self.expand(ty)))
+                            .collect();
+                        self.expanding.pop();
+                        Type::Record(fields)
+                    }
+                    Some(_) => self.open(name),
+                    None => ty.clone(),
+                }
+            }
+            Type::Variant(name, args) => {
+                Type::Variant(name.clone(), args.iter().map(|t| self.expand(t)).collect())
+            }
+            Type::Tuple(types) => Type::Tuple(types.iter().map(|t| self.expand(t)).collect()),
+            Type::List(elem) => Type::List(Box::new(self.expand(elem))),
+            Type::Array(elem) => Type::Array(Box::new(self.expand(elem))),
+            Type::Function(param, ret) => {
+                Type::Function(Box::new(self.expand(param)), Box::new(self.expand(ret)))
+            }
+            Type::Record(fields) => Type::Record(
+                fields
+                    .iter()
+                    .map(|(field, ty)| (field.clone(), self.expand(ty)))
+                    .collect(),
+            ),
+            _ => ty.clone(),
+        }
+    }
+
+    /// The type variable standing for the record `name`
+    fn open(&mut self, name: &str) -> Type {
+        if let Some((_, var)) = self.open.iter().find(|(n, _)| n == name) {
+            return Type::Var(var.clone());
+        }
+        let id = self.open.len();
+        let var = TypeVar::new(id, char::from(b'a' + (id % 26) as u8).to_string());
+        self.open.push((name.to_string(), var.clone()));
+        Type::Var(var)
+    }
 }
 
 /// Resolves type provider declarations to concrete types
@@ -74,13 +178,25 @@ pub struct ProviderResolver {
 }
 
 impl ProviderResolver {
-    /// Create a new provider resolver with an empty registry
+    /// Create a new provider resolver with the built-in providers
     pub fn new() -> Self {
         Self {
             registry: ProviderRegistry::new(),
         }
     }
 
+    /// Create a resolver with the built-in providers, caching resolutions as
+    /// configured by the environment (see `ProviderCache::from_env`)
+    pub fn from_env() -> Self {
+        let registry = ProviderRegistry::new();
+        Self {
+            registry: match ProviderCache::from_env() {
+                Some(cache) => registry.with_cache(cache),
+                None => registry,
+            },
+        }
+    }
+
     /// Create a resolver with a pre-configured registry
     pub fn with_registry(registry: ProviderRegistry) -> Self {
         Self { registry }
@@ -98,12 +214,6 @@ impl ProviderResolver {
 
     /// Resolve a type provider declaration to concrete types
     pub fn resolve(&self, decl: &TypeProviderDecl) -> ResolverResult<ResolvedTypes> {
-        // Get provider from registry
-        let provider = self
-            .registry
-            .get(&decl.provider)
-            .ok_or_else(|| ResolverError::ProviderNotFound(decl.provider.clone()))?;
-
         // Build provider params
         let params = ProviderParams {
             cache_ttl_secs: None,
@@ -114,26 +224,55 @@ impl ProviderResolver {
                 .collect::<HashMap<String, String>>(),
         };
 
-        // Resolve schema from source
-        let schema = provider
-            .resolve_schema(&decl.source, &params)
-            .map_err(|e| ResolverError::SchemaError(e.to_string()))?;
-
-        // Generate types from schema
-        let generated = provider
-            .generate_types(&schema, &decl.name)
-            .map_err(|e| ResolverError::GenerationError(e.to_string()))?;
+        // Resolve the schema and generate types, through the cache if any
+        let generated = self
+            .registry
+            .resolve(&decl.provider, &decl.source, &decl.name, &params)
+            .map_err(|e| match e {
+                ProviderError::UnknownProvider(name) => ResolverError::ProviderNotFound(name),
+                ProviderError::GenerationError(_) => ResolverError::GenerationError(e.to_string()),
+                _ => ResolverError::SchemaError(e.to_string()),
+            })?;
 
         // Convert to AST types
         let types = self.convert_generated_types(&generated)?;
 
         // Generate type schemes for TypeEnv
-        let type_schemes = self.generate_type_schemes(&types);
+        let mut type_schemes = self.generate_type_schemes(&types);
+
+        // Members are typed under their qualified name
+        let mut members = HashMap::new();
+        for member in &generated.members {
+            let ty = self.ast_type_to_type(&self.convert_type_expr(&member.member_type));
+            type_schemes.insert(
+                format!("{}.{}", decl.name, member.name),
+                TypeScheme::mono(ty),
+            );
+            members.insert(member.name.clone(), Self::erase_member(member));
+        }
 
         Ok(ResolvedTypes {
             alias: decl.name.clone(),
             types,
             type_schemes,
+            members,
+        })
+    }
+
+    /// The expression a provided member stands for: its target applied to
+    /// the baked-in arguments
+    fn erase_member(member: &ProvidedMember) -> Expr {
+        // Stdlib functions are fields of their module's global record
+        let target = match member.target.rsplit_once('.') {
+            Some((module, name)) => Expr::RecordAccess {
+                record: Box::new(Expr::Var(module.to_string())),
+                field: name.to_string(),
+            },
+            None => Expr::Var(member.target.clone()),
+        };
+        member.args.iter().fold(target, |func, arg| Expr::App {
+            func: Box::new(func),
+            arg: Box::new(Expr::Lit(Literal::Str(arg.clone()))),
         })
     }
 
@@ -216,7 +355,13 @@ impl ProviderResolver {
     fn generate_type_schemes(&self, types: &[AstTypeDef]) -> HashMap<String, TypeScheme> {
         let mut schemes = HashMap::new();
 
-        for type_def in types {
+        // Records first, so a variant constructor sharing a record's name
+        // (`Circle of Circle`) takes precedence
+        let (records, others): (Vec<&AstTypeDef>, Vec<&AstTypeDef>) = types
+            .iter()
+            .partition(|type_def| matches!(type_def, AstTypeDef::Record(_)));
+
+        for type_def in records.into_iter().chain(others) {
             match type_def {
                 AstTypeDef::Record(record) => {
                     // Record type is represented as a HashMap of field types
@@ -267,15 +412,39 @@ impl ProviderResolver {
     /// Convert AST type expression to inference Type
     fn ast_type_to_type(&self, ty: &AstTypeExpr) -> Type {
         match ty {
-            AstTypeExpr::Named(name) => match name.as_str() {
-                "int" => Type::Int,
-                "float" => Type::Float,
-                "bool" => Type::Bool,
-                "string" => Type::String,
-                "unit" => Type::Unit,
-                // Custom types are represented as Variant with empty type params
-                _ => Type::Variant(name.clone(), vec![]),
-            },
+            AstTypeExpr::Named(name) => {
+                // Types with several arguments are written `Result<Config, string>`
+                if let Some(args) = name.strip_suffix('>') {
+                    if let Some((base, args)) = args.split_once('<') {
+                        let args = split_type_args(args)
+                            .into_iter()
+                            .map(|arg| self.ast_type_to_type(&AstTypeExpr::Named(arg.to_string())))
+                            .collect();
+                        return Type::Variant(base.trim().to_string(), args);
+                    }
+                }
+                // Applied types are written postfix: `int list`, `Person option`
+                let mut words = name.split_whitespace();
+                let mut result = match words.next().unwrap_or_default() {
+                    "int" => Type::Int,
+                    "float" => Type::Float,
+                    "bool" => Type::Bool,
+                    "string" => Type::String,
+                    "unit" => Type::Unit,
+                    // Custom types are represented as Variant with empty type params
+                    base => Type::Variant(base.to_string(), vec![]),
+                };
+                for applied in words {
+                    result = match applied {
+                        "list" => Type::List(Box::new(result)),
+                        "array" => Type::Array(Box::new(result)),
+                        // Named as the inference names the option constructors' type
+                        "option" => Type::Variant("Option".to_string(), vec![result]),
+                        other => Type::Variant(other.to_string(), vec![result]),
+                    };
+                }
+                result
+            }
             AstTypeExpr::Tuple(types) => {
                 Type::Tuple(types.iter().map(|t| self.ast_type_to_type(t)).collect())
             }
@@ -294,6 +463,50 @@ impl ProviderResolver {
     }
 }
 
+/// The file `source` names relative to `dir`, made absolute, if `source` is
+/// a path and such a file exists
+pub fn source_file(dir: &Path, source: &str) -> Option<PathBuf> {
+    if source.contains('\n') || source.contains("://") {
+        return None;
+    }
+    let path = dir.join(source);
+    path.is_file().then(|| path.canonicalize().unwrap_or(path))
+}
+
+/// Read the sources of the provider declarations of `program` relative to
+/// `dir`, the directory of the script, as `#load` paths are. Inline
+/// sources, URLs and paths naming no file in `dir` are left alone.
+pub fn resolve_source_paths(program: &mut Program, dir: &Path) {
+    for item in &mut program.items {
+        if let ModuleItem::TypeDef(AstTypeDef::Provider(decl)) = item {
+            if let Some(path) = source_file(dir, &decl.source) {
+                decl.source = path.to_string_lossy().into_owned();
+            }
+        }
+    }
+}
+
+/// The comma-separated arguments of a generic type, which may be generic
+/// themselves
+fn split_type_args(args: &str) -> Vec<&str> {
+    let mut depth = 0;
+    let mut start = 0;
+    let mut parts = Vec::new();
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(args[start..].trim());
+    parts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -301,7 +514,189 @@ mod tests {
     #[test]
     fn test_resolver_creation() {
         let resolver = ProviderResolver::new();
-        assert!(resolver.list_providers().is_empty());
+        for provider in [
+            "JsonSchemaProvider",
+            "TomlProvider",
+            "SqlProvider",
+            "ProtobufProvider",
+            "GraphqlProvider",
+            "RegexProvider",
+        ] {
+            assert!(resolver.list_providers().contains(&provider));
+        }
+
+        let empty = ProviderResolver::with_registry(ProviderRegistry::empty());
+        assert!(empty.list_providers().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_toml() {
+        let resolver = ProviderResolver::new();
+        let decl = TypeProviderDecl::new(
+            "Config".to_string(),
+            "TomlProvider".to_string(),
+            "name = \"app\"\nports = [80]\n[database]\nhost = \"db\"".to_string(),
+        );
+        let resolved = resolver.resolve(&decl).unwrap();
+
+        let config = resolved
+            .types
+            .iter()
+            .find_map(|ty| match ty {
+                AstTypeDef::Record(record) if record.name == "Config" => Some(record),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            config.fields,
+            vec![
+                (
+                    "database".to_string(),
+                    AstTypeExpr::Named("Database".to_string())
+                ),
+                ("name".to_string(), AstTypeExpr::Named("string".to_string())),
+                (
+                    "ports".to_string(),
+                    AstTypeExpr::Named("int list".to_string())
+                ),
+            ]
+        );
+        assert!(resolved.type_schemes.contains_key("Database"));
+    }
+
+    #[test]
+    fn test_resolve_graphql_union() {
+        let resolver = ProviderResolver::new();
+        let decl = TypeProviderDecl::new(
+            "Api".to_string(),
+            "GraphqlProvider".to_string(),
+            "type User { name: String! }\nunion Result = User".to_string(),
+        );
+        let resolved = resolver.resolve(&decl).unwrap();
+        assert_eq!(resolved.types.len(), 2);
+
+        // The `User` constructor of `Result` shadows the `User` record
+        let user = &resolved.type_schemes["User"];
+        assert!(matches!(
+            &user.ty,
+            Type::Function(_, ret) if **ret == Type::Variant("Result".to_string(), vec![])
+        ));
+    }
+
+    #[test]
+    fn test_resolve_regex_members() {
+        let resolver = ProviderResolver::new();
+        let decl = TypeProviderDecl::new(
+            "LogEntry".to_string(),
+            "RegexProvider".to_string(),
+            r"(?P<ts>\d+) (?P<msg>.*)".to_string(),
+        );
+        let resolved = resolver.resolve(&decl).unwrap();
+
+        assert_eq!(
+            resolved.members["tryMatch"],
+            Expr::App {
+                func: Box::new(Expr::RecordAccess {
+                    record: Box::new(Expr::Var("Regex".to_string())),
+                    field: "namedCaptures".to_string(),
+                }),
+                arg: Box::new(Expr::Lit(Literal::Str(
+                    r"(?P<ts>\d+) (?P<msg>.*)".to_string()
+                ))),
+            }
+        );
+        assert_eq!(
+            resolved.type_schemes["LogEntry.tryMatch"].ty,
+            Type::Function(
+                Box::new(Type::String),
+                Box::new(Type::Variant(
+                    "Option".to_string(),
+                    vec![Type::Variant("LogEntry".to_string(), vec![])]
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn test_resolve_toml_members() {
+        let resolver = ProviderResolver::new();
+        let decl = TypeProviderDecl::new(
+            "Config".to_string(),
+            "TomlProvider".to_string(),
+            "name = \"app\"".to_string(),
+        );
+        let resolved = resolver.resolve(&decl).unwrap();
+
+        let config = Type::Variant("Config".to_string(), vec![]);
+        assert_eq!(
+            resolved.type_schemes["Config.parse"].ty,
+            Type::Function(
+                Box::new(Type::String),
+                Box::new(Type::Variant(
+                    "Result".to_string(),
+                    vec![config.clone(), Type::String]
+                ))
+            )
+        );
+        assert_eq!(
+            resolved.type_schemes["Config.serialize"].ty,
+            Type::Function(Box::new(config), Box::new(Type::String))
+        );
+        assert!(matches!(
+            &resolved.members["parse"],
+            Expr::App { func, .. } if **func == Expr::RecordAccess {
+                record: Box::new(Expr::Var("Toml".to_string())),
+                field: "parseAs".to_string(),
+            }
+        ));
+    }
+
+    #[test]
+    fn test_resolve_missing_source() {
+        let resolver = ProviderResolver::new();
+        let decl = TypeProviderDecl::new(
+            "Config".to_string(),
+            "TomlProvider".to_string(),
+            "no-such-config.toml".to_string(),
+        );
+        assert!(matches!(
+            resolver.resolve(&decl),
+            Err(ResolverError::SchemaError(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_source_paths() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("config.toml"), "name = \"app\"").unwrap();
+        let source = "type Config = TomlProvider<\"config.toml\">\n\
+                      type Missing = TomlProvider<\"missing.toml\">\n\
+                      type Line = RegexProvider<\"(?P<n>\\d+)\">\n\
+                      42";
+        let tokens = crate::Lexer::new(source).tokenize().unwrap();
+        let mut program = crate::Parser::new(tokens).parse_program().unwrap();
+        resolve_source_paths(&mut program, dir.path());
+
+        let sources: Vec<_> = program
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ModuleItem::TypeDef(AstTypeDef::Provider(decl)) => Some(decl.source.clone()),
+                _ => None,
+            })
+            .collect();
+        let config = dir.path().join("config.toml").canonicalize().unwrap();
+        assert_eq!(sources[0], config.to_string_lossy());
+        assert_eq!(sources[1], "missing.toml");
+        assert_eq!(sources[2], r"(?P<n>\d+)");
+
+        // The file is found from any working directory
+        let decl = TypeProviderDecl::new(
+            "Config".to_string(),
+            "TomlProvider".to_string(),
+            sources[0].clone(),
+        );
+        assert!(ProviderResolver::new().resolve(&decl).is_ok());
     }
 
     #[test]
@@ -359,5 +754,14 @@ mod tests {
         // Custom types become Variant types
         let ty = resolver.ast_type_to_type(&AstTypeExpr::Named("Person".to_string()));
         assert!(matches!(ty, Type::Variant(name, params) if name == "Person" && params.is_empty()));
+
+        let ty = resolver.ast_type_to_type(&AstTypeExpr::Named("int option list".to_string()));
+        assert_eq!(
+            ty,
+            Type::List(Box::new(Type::Variant(
+                "Option".to_string(),
+                vec![Type::Int]
+            )))
+        );
     }
 }
@@ -33,6 +33,7 @@ fn test_compile_empty_program() {
         imports: vec![],
         items: vec![],
         main_expr: None,
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -53,6 +54,7 @@ fn test_compile_program_with_simple_module_constant() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Lit(Literal::Int(42))),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -77,6 +79,7 @@ fn test_compile_program_with_import() {
         imports: vec![make_import("Constants")],
         items: vec![],
         main_expr: Some(Expr::Lit(Literal::Int(42))),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -97,6 +100,7 @@ fn test_compile_qualified_name() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Var("Math.value".to_string())),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -120,6 +124,7 @@ fn test_compile_imported_binding() {
         imports: vec![make_import("Constants")],
         items: vec![],
         main_expr: Some(Expr::Var("answer".to_string())),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -145,6 +150,7 @@ fn test_compile_multiple_modules() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Lit(Literal::Int(42))),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -171,6 +177,7 @@ fn test_compile_multiple_imports() {
         imports: vec![make_import("Math"), make_import("Physics")],
         items: vec![],
         main_expr: Some(Expr::Lit(Literal::Int(42))),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -198,6 +205,7 @@ fn test_compile_module_with_multiple_bindings() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Lit(Literal::Int(42))),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -224,6 +232,7 @@ fn test_compile_nested_modules() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Lit(Literal::Int(42))),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -251,6 +260,7 @@ fn test_compile_module_with_constant() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Var("Constants.pi".to_string())),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -274,6 +284,7 @@ fn test_compile_program_using_imported_constant() {
         imports: vec![make_import("Constants")],
         items: vec![],
         main_expr: Some(Expr::Var("value".to_string())),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -290,6 +301,7 @@ fn test_compile_error_undefined_module() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Var("NonExistent.func".to_string())),
+        docs: Default::default(),
     };
 
     let result = Compiler::compile_program(&program);
@@ -316,6 +328,7 @@ fn test_compile_error_undefined_binding_in_module() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Var("Math.nonexistent".to_string())),
+        docs: Default::default(),
     };
 
     let result = Compiler::compile_program(&program);
@@ -352,6 +365,7 @@ fn test_compile_module_with_expression() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Var("Math.sum".to_string())),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -378,6 +392,7 @@ fn test_compile_program_with_expression_using_module() {
             left: Box::new(Expr::Var("Math.value".to_string())),
             right: Box::new(Expr::Lit(Literal::Int(5))),
         }),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -409,6 +424,7 @@ fn test_compile_imported_expression() {
         imports: vec![make_import("Math")],
         items: vec![],
         main_expr: Some(Expr::Var("doubled".to_string())),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -430,6 +446,7 @@ fn test_compile_module_with_bool() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Var("Flags.enabled".to_string())),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
@@ -457,6 +474,7 @@ fn test_compile_module_with_string() {
         imports: vec![],
         items: vec![],
         main_expr: Some(Expr::Var("Messages.greeting".to_string())),
+        docs: Default::default(),
     };
 
     let chunk = Compiler::compile_program(&program).unwrap();
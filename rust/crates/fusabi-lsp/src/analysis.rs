@@ -0,0 +1,624 @@
+//! Document analysis shared by the IDE features.
+//!
+//! Editors ask for completions while the document is mid-edit, so analysis is
+//! lenient: if the full text does not parse, the line under the cursor is
+//! blanked out and parsing is retried. Scope information comes from the token
+//! stream (which carries positions), while type definitions, modules and
+//! inferred types come from the parsed `Program` when one is available.
+//!
+//! Host functions are invisible to the type checker, so inference treats them
+//! (and the modules they live in) as having any type. The same goes for the
+//! top-level bindings of files pulled in with `#load`.
+
+use std::collections::HashMap;
+
+use fusabi_frontend::ast::{DuTypeDef, RecordTypeDef, TypeDefinition, TypeExpr, VariantDef};
+use fusabi_frontend::{
+    Expr, Lexer, ModuleItem, ModuleRegistry, Parser, Program, Token, TokenWithPos, Type, TypeEnv,
+    TypeError, TypeErrorKind, TypeInference, TypeScheme, TypeVar,
+};
+
+use crate::signatures::HostFunction;
+
+/// How many lines above the cursor to search for a parseable prefix.
+const MAX_PREFIX_RETRIES: usize = 20;
+
+/// Kind of a name bound in the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+    /// `let x = ...`
+    Value,
+    /// `let f x y = ...`
+    Function,
+    /// Function, lambda or pattern parameter
+    Parameter,
+}
+
+/// A name bound somewhere before the cursor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    pub kind: BindingKind,
+    /// Byte offset of the binding occurrence
+    pub offset: usize,
+}
+
+/// Lenient analysis of a single document.
+pub struct DocumentAnalysis {
+    /// Parsed program, if the (possibly patched) text parses
+    pub program: Option<Program>,
+    /// Tokens of the (possibly patched) text
+    pub tokens: Vec<TokenWithPos>,
+    /// Standard library modules plus the modules defined in the document
+    pub registry: ModuleRegistry,
+    /// Record types declared in the document (including inside modules)
+    pub records: Vec<RecordTypeDef>,
+    /// Discriminated unions declared in the document (including inside modules)
+    pub unions: Vec<DuTypeDef>,
+    /// Inferred types of top-level bindings, where inference succeeded
+    pub types: HashMap<String, Type>,
+    /// Type errors of top-level bindings and the main expression
+    pub errors: Vec<TypeError>,
+    /// Names visible everywhere without a binding in the document: host
+    /// functions, module names and members of opened modules
+    pub globals: Vec<String>,
+}
+
+impl DocumentAnalysis {
+    /// Analyze `text`. `cursor_line` (0-indexed) is blanked out on a retry if
+    /// the text as a whole fails to lex or parse.
+    pub fn new(text: &str, cursor_line: Option<u32>, host_functions: &[HostFunction]) -> Self {
+        Self::with_loaded(text, cursor_line, host_functions, &[])
+    }
+
+    /// Analyze `text` with the programs of the files it `#load`s in scope.
+    pub fn with_loaded(
+        text: &str,
+        cursor_line: Option<u32>,
+        host_functions: &[HostFunction],
+        loaded: &[Program],
+    ) -> Self {
+        let mut tokens = tokenize(text);
+        let mut program = tokens.as_ref().and_then(|t| parse(t.clone()));
+
+        if program.is_none() {
+            if let Some(line) = cursor_line {
+                let patched = blank_line(text, line as usize);
+                let patched_tokens = tokenize(&patched);
+                if tokens.is_none() {
+                    tokens = patched_tokens.clone();
+                }
+                program = patched_tokens.and_then(parse);
+
+                // The construct being edited may span several lines (e.g. a
+                // `match` still missing its arms): fall back to the longest
+                // prefix before the cursor line that parses.
+                let lines: Vec<&str> = text.split('\n').collect();
+                let mut end = (line as usize).min(lines.len());
+                while program.is_none() && end > 0 && (line as usize) - end < MAX_PREFIX_RETRIES {
+                    end -= 1;
+                    program = tokenize(&lines[..end].join("\n")).and_then(parse);
+                }
+            }
+        }
+        if program.is_none() {
+            // Otherwise use what the parser recovers around the errors
+            program = tokens
+                .as_ref()
+                .map(|t| Parser::new(t.clone()).parse_program_recovering().0);
+        }
+
+        let mut analysis = DocumentAnalysis {
+            program: None,
+            tokens: tokens.unwrap_or_default(),
+            registry: ModuleRegistry::with_stdlib(),
+            records: Vec::new(),
+            unions: Vec::new(),
+            types: HashMap::new(),
+            errors: Vec::new(),
+            globals: Vec::new(),
+        };
+
+        if let Some(program) = program {
+            for module in loaded.iter().chain(Some(&program)).flat_map(|p| &p.modules) {
+                analysis.registry.register_module_def(module);
+                analysis.collect_types(&module.items);
+            }
+            for other in loaded {
+                analysis.collect_types(&other.items);
+            }
+            analysis.collect_types(&program.items);
+            analysis.collect_globals(&program, host_functions, loaded);
+            analysis.infer_top_level(&program);
+            analysis.program = Some(program);
+        }
+
+        analysis
+    }
+
+    fn collect_types(&mut self, items: &[ModuleItem]) {
+        for item in items {
+            match item {
+                ModuleItem::TypeDef(TypeDefinition::Record(record)) => {
+                    self.records.push(record.clone())
+                }
+                ModuleItem::TypeDef(TypeDefinition::Du(du)) => self.unions.push(du.clone()),
+                ModuleItem::Module(nested) => self.collect_types(&nested.items),
+                _ => {}
+            }
+        }
+    }
+
+    fn collect_globals(
+        &mut self,
+        program: &Program,
+        host_functions: &[HostFunction],
+        loaded: &[Program],
+    ) {
+        let mut globals: Vec<String> = Vec::new();
+        for other in loaded {
+            for item in &other.items {
+                match item {
+                    ModuleItem::Let(Some(name), _) => globals.push(name.clone()),
+                    ModuleItem::LetRec(bindings) => {
+                        globals.extend(bindings.iter().map(|(name, _)| name.clone()))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for f in host_functions {
+            match f.module() {
+                Some(module) => globals.push(module.to_string()),
+                None => globals.push(f.name.clone()),
+            }
+        }
+        globals.extend(self.registry.module_names().into_iter().map(str::to_string));
+
+        for import in &program.imports {
+            let module = import.module_path.join(".");
+            if let Some(bindings) = self.registry.get_module_bindings(&module) {
+                globals.extend(bindings.keys().cloned());
+            }
+            globals.extend(
+                host_functions
+                    .iter()
+                    .filter(|f| f.module() == Some(module.as_str()))
+                    .map(|f| f.member().to_string()),
+            );
+        }
+
+        globals.sort();
+        globals.dedup();
+        self.globals = globals;
+    }
+
+    /// Best-effort inference of top-level bindings, in order.
+    ///
+    /// A binding that fails to type check is recorded in `errors` and left out
+    /// of the environment, so later bindings using it may be skipped as well.
+    fn infer_top_level(&mut self, program: &Program) {
+        let mut inference = TypeInference::with_module_registry(self.registry.clone());
+        let mut env = TypeEnv::new();
+
+        // Globals can be used at any type
+        let any = TypeVar::new(0, "a");
+        for name in &self.globals {
+            env.insert(
+                name.clone(),
+                TypeScheme::poly(vec![any.clone()], Type::Var(any.clone())),
+            );
+        }
+
+        for item in &program.items {
+            // Recursive bindings are checked as `let rec ... in name` so that
+            // they can refer to themselves (and each other)
+            let bindings: Vec<(Option<&String>, Expr)> = match item {
+                ModuleItem::Let(name, value) => vec![(name.as_ref(), value.clone())],
+                ModuleItem::LetRec(bindings) if bindings.len() == 1 => {
+                    let (name, value) = &bindings[0];
+                    vec![(
+                        Some(name),
+                        Expr::LetRec {
+                            name: name.clone(),
+                            value: Box::new(value.clone()),
+                            body: Box::new(Expr::Var(name.clone())),
+                        },
+                    )]
+                }
+                ModuleItem::LetRec(bindings) => bindings
+                    .iter()
+                    .map(|(name, _)| {
+                        (
+                            Some(name),
+                            Expr::LetRecMutual {
+                                bindings: bindings.clone(),
+                                body: Box::new(Expr::Var(name.clone())),
+                            },
+                        )
+                    })
+                    .collect(),
+                _ => continue,
+            };
+            for (name, value) in bindings {
+                match inference.infer_and_solve(&value, &env) {
+                    Ok(ty) => {
+                        if let Some(name) = name {
+                            let scheme = env.generalize(&ty);
+                            env.insert(name.clone(), scheme);
+                            self.types.insert(name.clone(), ty);
+                        }
+                    }
+                    Err(err) => {
+                        self.errors.push(err);
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(expr) = &program.main_expr {
+            if let Err(err) = inference.infer_and_solve(expr, &env) {
+                self.errors.push(err);
+            }
+        }
+    }
+
+    /// Names bound before `offset`, most recent binding of each name last.
+    ///
+    /// This is a token-level approximation of scope: every `let`, `fun` and
+    /// match-arm binding that starts before the cursor is considered visible.
+    pub fn bindings_before(&self, offset: usize) -> Vec<Binding> {
+        let tokens = &self.tokens;
+        let mut found: Vec<Binding> = Vec::new();
+        let mut push = |name: &str, kind: BindingKind, offset: usize| {
+            if name == "_" {
+                return;
+            }
+            found.retain(|b| b.name != name);
+            found.push(Binding {
+                name: name.to_string(),
+                kind,
+                offset,
+            });
+        };
+
+        let mut i = 0;
+        while i < tokens.len() && tokens[i].pos.offset < offset {
+            match &tokens[i].token {
+                Token::Let | Token::LetBang | Token::AndKeyword => {
+                    let mut j = i + 1;
+                    if matches!(tokens.get(j).map(|t| &t.token), Some(Token::Rec)) {
+                        j += 1;
+                    }
+                    if let Some(TokenWithPos {
+                        token: Token::Ident(name),
+                        pos,
+                        ..
+                    }) = tokens.get(j)
+                    {
+                        let params = collect_params(tokens, j + 1, offset);
+                        let kind = if params.is_empty() {
+                            BindingKind::Value
+                        } else {
+                            BindingKind::Function
+                        };
+                        push(name, kind, pos.offset);
+                        for (param, param_offset) in params {
+                            push(&param, BindingKind::Parameter, param_offset);
+                        }
+                    }
+                }
+                Token::Fun => {
+                    for (param, param_offset) in collect_params(tokens, i + 1, offset) {
+                        push(&param, BindingKind::Parameter, param_offset);
+                    }
+                }
+                Token::Pipe => {
+                    let mut j = i + 1;
+                    while let Some(tok) = tokens.get(j) {
+                        if tok.pos.offset >= offset || matches!(tok.token, Token::Arrow) {
+                            break;
+                        }
+                        if let Token::Ident(name) = &tok.token {
+                            let after_dot = j > 0 && matches!(tokens[j - 1].token, Token::Dot);
+                            if !after_dot && !is_uppercase(name) {
+                                push(name, BindingKind::Parameter, tok.pos.offset);
+                            }
+                        }
+                        j += 1;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        found
+    }
+
+    /// Fields of the record bound to `name`, with their types where known.
+    pub fn record_fields_of(&self, name: &str) -> Option<Vec<(String, Option<String>)>> {
+        if let Some(Type::Record(fields)) = self.types.get(name) {
+            let mut fields: Vec<(String, Option<String>)> = fields
+                .iter()
+                .map(|(field, ty)| (field.clone(), Some(ty.to_string())))
+                .collect();
+            fields.sort();
+            return Some(fields);
+        }
+
+        let program = self.program.as_ref()?;
+        let mut literal_fields = None;
+        for item in &program.items {
+            match item {
+                ModuleItem::Let(Some(bound), value) if bound == name => {
+                    literal_fields = record_literal_fields(value).or(literal_fields);
+                }
+                ModuleItem::Let(_, value) => {
+                    literal_fields = find_let_record(value, name).or(literal_fields);
+                }
+                _ => {}
+            }
+        }
+        if let Some(expr) = &program.main_expr {
+            literal_fields = find_let_record(expr, name).or(literal_fields);
+        }
+
+        literal_fields.map(|names| {
+            let declared = self.record_matching(&names);
+            names
+                .into_iter()
+                .map(|field| {
+                    let ty = declared.and_then(|r| {
+                        r.fields
+                            .iter()
+                            .find(|(f, _)| *f == field)
+                            .map(|(_, ty)| ty.to_string())
+                    });
+                    (field, ty)
+                })
+                .collect()
+        })
+    }
+
+    /// Declared record type whose field names are exactly `names`.
+    pub fn record_matching(&self, names: &[String]) -> Option<&RecordTypeDef> {
+        self.records.iter().find(|record| {
+            record.fields.len() == names.len()
+                && record.fields.iter().all(|(field, _)| names.contains(field))
+        })
+    }
+
+    /// References to names that are not bound anywhere visible, with their
+    /// byte offsets.
+    ///
+    /// Inference reports unbound variables without a location, so every use of
+    /// such a name that has no binding before it is reported.
+    pub fn unbound_references(&self) -> Vec<(String, usize)> {
+        let mut names: Vec<&str> = self
+            .errors
+            .iter()
+            .filter_map(|err| match &err.kind {
+                TypeErrorKind::UnboundVariable { name } => Some(name.as_str()),
+                _ => None,
+            })
+            .filter(|name| !self.globals.iter().any(|g| g == name))
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        if names.is_empty() {
+            return Vec::new();
+        }
+
+        let mut found = Vec::new();
+        for (i, tok) in self.tokens.iter().enumerate() {
+            let Token::Ident(name) = &tok.token else {
+                continue;
+            };
+            let after_dot = i > 0 && matches!(self.tokens[i - 1].token, Token::Dot);
+            // Module qualifiers may come from `#load`ed files, which are not analyzed
+            let qualifier = matches!(self.tokens.get(i + 1).map(|t| &t.token), Some(Token::Dot));
+            if after_dot || qualifier || !names.contains(&name.as_str()) {
+                continue;
+            }
+            let bound = self
+                .bindings_before(tok.pos.offset + 1)
+                .iter()
+                .any(|b| &b.name == name);
+            if !bound {
+                found.push((name.clone(), tok.pos.offset));
+            }
+        }
+        found
+    }
+
+    /// All DUs visible in the document, including the built-in option and result.
+    pub fn known_unions(&self) -> Vec<DuTypeDef> {
+        let mut unions = self.unions.clone();
+        unions.push(DuTypeDef {
+            name: "Option".to_string(),
+            variants: vec![
+                VariantDef::new("Some".to_string(), vec![TypeExpr::Named("'a".to_string())]),
+                VariantDef::new_simple("None".to_string()),
+            ],
+        });
+        unions.push(DuTypeDef {
+            name: "Result".to_string(),
+            variants: vec![
+                VariantDef::new("Ok".to_string(), vec![TypeExpr::Named("'a".to_string())]),
+                VariantDef::new("Error".to_string(), vec![TypeExpr::Named("'e".to_string())]),
+            ],
+        });
+        unions
+    }
+
+    /// Discriminated union that declares `variant`.
+    pub fn union_with_variant(&self, variant: &str) -> Option<&DuTypeDef> {
+        self.unions
+            .iter()
+            .find(|du| du.find_variant(variant).is_some())
+    }
+
+    /// Discriminated union of the value bound to `name`, where it can be determined.
+    pub fn union_of(&self, name: &str) -> Option<&DuTypeDef> {
+        if let Some(Type::Variant(type_name, _)) = self.types.get(name) {
+            if let Some(du) = self.unions.iter().find(|du| &du.name == type_name) {
+                return Some(du);
+            }
+        }
+
+        let program = self.program.as_ref()?;
+        program.items.iter().find_map(|item| match item {
+            ModuleItem::Let(Some(bound), Expr::VariantConstruct { variant, .. })
+                if bound == name =>
+            {
+                self.union_with_variant(variant)
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Field names of a record literal or record update expression.
+fn record_literal_fields(expr: &Expr) -> Option<Vec<String>> {
+    match expr {
+        Expr::RecordLiteral { fields, .. } => {
+            Some(fields.iter().map(|(name, _)| name.clone()).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Search nested `let ... in` chains for a record literal bound to `name`.
+fn find_let_record(expr: &Expr, name: &str) -> Option<Vec<String>> {
+    match expr {
+        Expr::Let {
+            name: bound,
+            value,
+            body,
+        }
+        | Expr::LetRec {
+            name: bound,
+            value,
+            body,
+        } => {
+            let here = if bound == name {
+                record_literal_fields(value)
+            } else {
+                None
+            };
+            find_let_record(body, name)
+                .or(here)
+                .or_else(|| find_let_record(value, name))
+        }
+        Expr::Lambda { body, .. } => find_let_record(body, name),
+        Expr::If {
+            then_branch,
+            else_branch,
+            ..
+        } => find_let_record(then_branch, name).or_else(|| find_let_record(else_branch, name)),
+        Expr::Match { arms, .. } => arms.iter().find_map(|arm| find_let_record(&arm.body, name)),
+        _ => None,
+    }
+}
+
+/// Parameter names following a binding name, up to `=`, `:` or `->`.
+///
+/// Accepts plain identifiers and parenthesized, optionally annotated,
+/// parameters such as `(x: int)`.
+pub(crate) fn collect_params(
+    tokens: &[TokenWithPos],
+    start: usize,
+    limit: usize,
+) -> Vec<(String, usize)> {
+    let mut params = Vec::new();
+    let mut j = start;
+    while let Some(tok) = tokens.get(j) {
+        if tok.pos.offset >= limit {
+            break;
+        }
+        match &tok.token {
+            Token::Ident(name) => params.push((name.clone(), tok.pos.offset)),
+            Token::LParen => {
+                if let Some(TokenWithPos {
+                    token: Token::Ident(name),
+                    pos,
+                    ..
+                }) = tokens.get(j + 1)
+                {
+                    params.push((name.clone(), pos.offset));
+                }
+                while let Some(inner) = tokens.get(j) {
+                    if matches!(inner.token, Token::RParen) {
+                        break;
+                    }
+                    j += 1;
+                }
+            }
+            Token::Underscore => {}
+            _ => break,
+        }
+        j += 1;
+    }
+    params
+}
+
+fn tokenize(text: &str) -> Option<Vec<TokenWithPos>> {
+    Lexer::new(text).tokenize().ok()
+}
+
+fn parse(tokens: Vec<TokenWithPos>) -> Option<Program> {
+    Parser::new(tokens).parse_program().ok()
+}
+
+/// Replace the contents of `line` with spaces, preserving byte offsets.
+fn blank_line(text: &str, line: usize) -> String {
+    text.split('\n')
+        .enumerate()
+        .map(|(i, l)| {
+            if i == line {
+                " ".repeat(l.len())
+            } else {
+                l.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub(crate) fn is_uppercase(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_uppercase())
+}
+
+/// LSP position (line, UTF-16 character) of a byte offset in `text`.
+pub fn position_at(text: &str, offset: usize) -> tower_lsp::lsp_types::Position {
+    let offset = offset.min(text.len());
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    tower_lsp::lsp_types::Position {
+        line: line as u32,
+        character: character as u32,
+    }
+}
+
+/// Byte offset of an LSP position (line, UTF-16 character) in `text`.
+pub fn offset_at(text: &str, line: u32, character: u32) -> usize {
+    let mut offset = 0;
+    for (i, l) in text.split('\n').enumerate() {
+        if i == line as usize {
+            let mut units = 0;
+            for (byte, ch) in l.char_indices() {
+                if units >= character as usize {
+                    return offset + byte;
+                }
+                units += ch.len_utf16();
+            }
+            return offset + l.len();
+        }
+        offset += l.len() + 1;
+    }
+    text.len()
+}
@@ -0,0 +1,813 @@
+//! Code actions: quick fixes for type errors and small refactorings.
+//!
+//! Type errors carry no source location, so fixes are matched to the
+//! identifier under the cursor by name. Everything else works on the token
+//! stream of the document, like completion does.
+
+use std::collections::HashSet;
+
+use fusabi_frontend::ast::VariantDef;
+use fusabi_frontend::{Lexer, Parser, Token, TokenWithPos, Type, TypeErrorKind};
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, Range, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use crate::analysis::{self, DocumentAnalysis};
+use crate::signatures::HostFunction;
+
+/// Name given to bindings introduced by "extract to let".
+const EXTRACTED_NAME: &str = "extracted";
+
+/// Code actions available for `range` in `text`.
+///
+/// `diagnostics` are the diagnostics the client sent along with the request;
+/// quick fixes are linked to the ones they resolve.
+pub fn code_actions(
+    text: &str,
+    uri: &Url,
+    range: Range,
+    diagnostics: &[Diagnostic],
+    host_functions: &[HostFunction],
+) -> Vec<CodeActionOrCommand> {
+    let analysis = DocumentAnalysis::new(text, None, host_functions);
+    let ctx = ActionContext {
+        text,
+        uri,
+        analysis: &analysis,
+        diagnostics,
+    };
+    let start = analysis::offset_at(text, range.start.line, range.start.character);
+    let end = analysis::offset_at(text, range.end.line, range.end.character);
+
+    let mut actions = Vec::new();
+    if let Some(index) = ident_at(&analysis.tokens, start) {
+        actions.extend(replacement_fixes(&ctx, index));
+        actions.extend(unbound_name_fixes(&ctx, index, host_functions));
+        actions.extend(annotation_action(&ctx, index));
+    }
+    actions.extend(missing_cases_action(&ctx, start));
+    if start < end {
+        actions.extend(extract_action(&ctx, start, end));
+    }
+    actions
+}
+
+struct ActionContext<'a> {
+    text: &'a str,
+    uri: &'a Url,
+    analysis: &'a DocumentAnalysis,
+    diagnostics: &'a [Diagnostic],
+}
+
+impl ActionContext<'_> {
+    fn range(&self, start: usize, end: usize) -> Range {
+        Range {
+            start: analysis::position_at(self.text, start),
+            end: analysis::position_at(self.text, end),
+        }
+    }
+
+    fn edit(&self, start: usize, end: usize, new_text: impl Into<String>) -> TextEdit {
+        TextEdit {
+            range: self.range(start, end),
+            new_text: new_text.into(),
+        }
+    }
+
+    /// Client diagnostics covering `offset` that mention `name`.
+    fn diagnostics_for(&self, offset: usize, name: &str) -> Option<Vec<Diagnostic>> {
+        let position = analysis::position_at(self.text, offset);
+        let matching: Vec<Diagnostic> = self
+            .diagnostics
+            .iter()
+            .filter(|d| d.range.start <= position && position <= d.range.end)
+            .filter(|d| d.message.contains(name))
+            .cloned()
+            .collect();
+        (!matching.is_empty()).then_some(matching)
+    }
+
+    fn action(
+        &self,
+        title: String,
+        kind: CodeActionKind,
+        edits: Vec<TextEdit>,
+        diagnostics: Option<Vec<Diagnostic>>,
+    ) -> CodeActionOrCommand {
+        let mut changes = std::collections::HashMap::new();
+        changes.insert(self.uri.clone(), edits);
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title,
+            kind: Some(kind),
+            diagnostics,
+            edit: Some(WorkspaceEdit {
+                changes: Some(changes),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+/// Apply the replacements suggested by type errors (e.g. typos in variable
+/// or field names) to the identifier under the cursor.
+fn replacement_fixes(ctx: &ActionContext, index: usize) -> Vec<CodeActionOrCommand> {
+    let tok = &ctx.analysis.tokens[index];
+    let Token::Ident(name) = &tok.token else {
+        return Vec::new();
+    };
+    let offset = tok.pos.offset;
+
+    let mut in_scope: Vec<String> = ctx
+        .analysis
+        .bindings_before(offset)
+        .into_iter()
+        .map(|b| b.name)
+        .collect();
+    in_scope.extend(ctx.analysis.globals.iter().cloned());
+    // A value is not replaced by a module or constructor name, nor vice versa
+    let in_scope: Vec<&str> = in_scope
+        .iter()
+        .map(String::as_str)
+        .filter(|candidate| analysis::is_uppercase(candidate) == analysis::is_uppercase(name))
+        .collect();
+
+    let is_unbound = ctx
+        .analysis
+        .unbound_references()
+        .contains(&(name.clone(), offset));
+
+    let mut seen = HashSet::new();
+    let mut actions = Vec::new();
+    for err in &ctx.analysis.errors {
+        if matches!(err.kind, TypeErrorKind::UnboundVariable { .. }) && !is_unbound {
+            continue;
+        }
+        for replacement in err.suggested_replacements(&in_scope) {
+            if &replacement.from != name || !seen.insert(replacement.to.clone()) {
+                continue;
+            }
+            let edit = ctx.edit(offset, offset + name.len(), replacement.to.clone());
+            let mut action = ctx.action(
+                format!("Replace '{}' with '{}'", name, replacement.to),
+                CodeActionKind::QUICKFIX,
+                vec![edit],
+                ctx.diagnostics_for(offset, name),
+            );
+            if actions.is_empty() {
+                if let CodeActionOrCommand::CodeAction(a) = &mut action {
+                    a.is_preferred = Some(true);
+                }
+            }
+            actions.push(action);
+        }
+    }
+    actions
+}
+
+/// For an unbound name that is a member of a known module, offer to open the
+/// module or to qualify the name.
+fn unbound_name_fixes(
+    ctx: &ActionContext,
+    index: usize,
+    host_functions: &[HostFunction],
+) -> Vec<CodeActionOrCommand> {
+    let tok = &ctx.analysis.tokens[index];
+    let Token::Ident(name) = &tok.token else {
+        return Vec::new();
+    };
+    let offset = tok.pos.offset;
+    if !ctx
+        .analysis
+        .unbound_references()
+        .contains(&(name.clone(), offset))
+    {
+        return Vec::new();
+    }
+
+    let registry = &ctx.analysis.registry;
+    let mut openable: Vec<String> = registry
+        .module_names()
+        .into_iter()
+        .filter(|module| {
+            registry
+                .get_module_bindings(module)
+                .is_some_and(|bindings| bindings.contains_key(name))
+        })
+        .map(str::to_string)
+        .collect();
+    openable.sort();
+
+    let mut qualifiers = openable.clone();
+    for f in host_functions {
+        if let Some(module) = f.module() {
+            if f.member() == name && !qualifiers.iter().any(|q| q == module) {
+                qualifiers.push(module.to_string());
+            }
+        }
+    }
+
+    let diagnostics = ctx.diagnostics_for(offset, name);
+    let mut actions = Vec::new();
+    for module in &openable {
+        let (at, line) = open_insertion(ctx.text, &ctx.analysis.tokens);
+        let new_text = if line {
+            format!("open {}\n", module)
+        } else {
+            format!("\nopen {}", module)
+        };
+        actions.push(ctx.action(
+            format!("Add 'open {}'", module),
+            CodeActionKind::QUICKFIX,
+            vec![ctx.edit(at, at, new_text)],
+            diagnostics.clone(),
+        ));
+    }
+    for module in &qualifiers {
+        let qualified = format!("{}.{}", module, name);
+        actions.push(ctx.action(
+            format!("Use '{}'", qualified),
+            CodeActionKind::QUICKFIX,
+            vec![ctx.edit(offset, offset + name.len(), qualified)],
+            diagnostics.clone(),
+        ));
+    }
+    actions
+}
+
+/// Where a new `open` goes: after the last `open` (or `#load`), else at the
+/// top. Returns the offset and whether it is the start of a line.
+fn open_insertion(text: &str, tokens: &[TokenWithPos]) -> (usize, bool) {
+    let anchor = tokens
+        .iter()
+        .rev()
+        .find(|t| matches!(t.token, Token::Open))
+        .or_else(|| {
+            tokens
+                .iter()
+                .rev()
+                .find(|t| matches!(t.token, Token::LoadDirective(_)))
+        });
+    match anchor {
+        Some(tok) => match text[tok.pos.offset..].find('\n') {
+            Some(i) => (tok.pos.offset + i + 1, true),
+            None => (text.len(), false),
+        },
+        None => (0, true),
+    }
+}
+
+/// Annotate a top-level binding with its inferred type.
+fn annotation_action(ctx: &ActionContext, index: usize) -> Option<CodeActionOrCommand> {
+    let tokens = &ctx.analysis.tokens;
+    let Token::Ident(name) = &tokens[index].token else {
+        return None;
+    };
+
+    // `let name`, `let rec name` or `and name`, starting a top-level item
+    let keyword = match index.checked_sub(1).map(|i| &tokens[i].token) {
+        Some(Token::Rec) => index.checked_sub(2)?,
+        Some(Token::Let) | Some(Token::AndKeyword) => index - 1,
+        _ => return None,
+    };
+    if !matches!(tokens[keyword].token, Token::Let | Token::AndKeyword)
+        || tokens[keyword].pos.column != 1
+    {
+        return None;
+    }
+
+    let params = analysis::collect_params(tokens, index + 1, usize::MAX);
+    let eq = tokens[index + 1..]
+        .iter()
+        .find(|t| matches!(t.token, Token::Eq | Token::Colon))?;
+    if !matches!(eq.token, Token::Eq) {
+        return None; // already annotated
+    }
+
+    let mut ty = ctx.analysis.types.get(name)?;
+    for _ in &params {
+        match ty {
+            Type::Function(_, ret) => ty = ret,
+            _ => return None,
+        }
+    }
+    let annotation = annotation_text(ty, ctx.analysis)?;
+
+    let at = eq.pos.offset;
+    let spaced = ctx.text[..at].ends_with(char::is_whitespace);
+    let new_text = if spaced {
+        format!(": {} ", annotation)
+    } else {
+        format!(" : {} ", annotation)
+    };
+    Some(ctx.action(
+        format!("Add type annotation ': {}'", annotation),
+        CodeActionKind::REFACTOR_REWRITE,
+        vec![ctx.edit(at, at, new_text)],
+        None,
+    ))
+}
+
+/// Render a type in annotation syntax, if it can be written down.
+fn annotation_text(ty: &Type, analysis: &DocumentAnalysis) -> Option<String> {
+    let text = match ty {
+        Type::Int | Type::Bool | Type::String | Type::Unit | Type::Float => ty.to_string(),
+        Type::List(elem) => format!("{} list", annotation_atom(elem, analysis)?),
+        Type::Array(elem) => format!("{}[]", annotation_atom(elem, analysis)?),
+        Type::Tuple(elems) => elems
+            .iter()
+            .map(|elem| annotation_atom(elem, analysis))
+            .collect::<Option<Vec<_>>>()?
+            .join(" * "),
+        Type::Function(param, ret) => {
+            let param_text = match param.as_ref() {
+                Type::Function(..) => format!("({})", annotation_text(param, analysis)?),
+                _ => annotation_text(param, analysis)?,
+            };
+            format!("{} -> {}", param_text, annotation_text(ret, analysis)?)
+        }
+        Type::Record(fields) => {
+            let names: Vec<String> = fields.keys().cloned().collect();
+            analysis.record_matching(&names)?.name.clone()
+        }
+        Type::Var(_) | Type::Variant(..) => return None,
+    };
+    Some(text)
+}
+
+/// Like [`annotation_text`], parenthesized where needed as an operand.
+fn annotation_atom(ty: &Type, analysis: &DocumentAnalysis) -> Option<String> {
+    let text = annotation_text(ty, analysis)?;
+    Some(match ty {
+        Type::Tuple(_) | Type::Function(..) => format!("({})", text),
+        _ => text,
+    })
+}
+
+/// Arms of a `match` expression, found from its tokens.
+struct MatchArms {
+    /// Scrutinee, when it is a plain identifier
+    scrutinee: Option<String>,
+    /// Index of the first token of each arm's pattern
+    patterns: Vec<usize>,
+    /// Index of the last token of the match
+    last: usize,
+    /// Index of the token that ends the match on the same line, if any
+    closer: Option<usize>,
+    /// Whether arms start on their own lines
+    multiline: bool,
+}
+
+/// Add the cases missing from the `match` around `offset`.
+fn missing_cases_action(ctx: &ActionContext, offset: usize) -> Option<CodeActionOrCommand> {
+    let tokens = &ctx.analysis.tokens;
+    let arms = (0..tokens.len())
+        .rev()
+        .filter(|&i| matches!(tokens[i].token, Token::Match) && tokens[i].pos.offset <= offset)
+        .filter_map(|i| match_arms(tokens, i))
+        .find(|arms| offset <= line_end(ctx.text, tokens[arms.last].pos.offset))?;
+
+    let mut covered = Vec::new();
+    for &p in &arms.patterns {
+        match &tokens.get(p)?.token {
+            Token::Ident(name) if analysis::is_uppercase(name) => covered.push(name.as_str()),
+            Token::Ident(_) | Token::Underscore => return None, // catch-all arm
+            _ => {}
+        }
+    }
+
+    let unions = ctx.analysis.known_unions();
+    let du = match covered.first() {
+        Some(variant) => unions
+            .iter()
+            .find(|du| du.find_variant(variant).is_some())?
+            .clone(),
+        None => ctx.analysis.union_of(arms.scrutinee.as_deref()?)?.clone(),
+    };
+    let missing: Vec<&VariantDef> = du
+        .variants
+        .iter()
+        .filter(|v| !covered.contains(&v.name.as_str()))
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+
+    let first_pipe = tokens[arms.patterns[0] - 1].pos.offset;
+    let indent = &ctx.text[line_start(ctx.text, first_pipe)..first_pipe];
+    let (at, new_text) = match arms.closer {
+        Some(closer) if !arms.multiline => {
+            let arms_text: Vec<String> = missing
+                .iter()
+                .map(|v| format!("| {} -> () ", variant_pattern(v)))
+                .collect();
+            (tokens[closer].pos.offset, arms_text.concat())
+        }
+        _ => {
+            let separator = if arms.multiline {
+                format!("\n{}", indent)
+            } else {
+                " ".to_string()
+            };
+            let arms_text: Vec<String> = missing
+                .iter()
+                .map(|v| format!("{}| {} -> ()", separator, variant_pattern(v)))
+                .collect();
+            (
+                line_end(ctx.text, tokens[arms.last].pos.offset),
+                arms_text.concat(),
+            )
+        }
+    };
+
+    let title = match missing.as_slice() {
+        [single] => format!("Add missing case '{}'", single.name),
+        _ => format!("Add {} missing {} cases", missing.len(), du.name),
+    };
+    Some(ctx.action(
+        title,
+        CodeActionKind::QUICKFIX,
+        vec![ctx.edit(at, at, new_text)],
+        None,
+    ))
+}
+
+/// Find the arms of the `match` at token index `m`.
+fn match_arms(tokens: &[TokenWithPos], m: usize) -> Option<MatchArms> {
+    let mut depth = 0i32;
+    let mut with = m + 1;
+    loop {
+        match tokens.get(with)?.token {
+            Token::With if depth == 0 => break,
+            Token::LParen | Token::LBracket | Token::LBracketPipe | Token::LBrace => depth += 1,
+            Token::RParen | Token::RBracket | Token::PipeRBracket | Token::RBrace => depth -= 1,
+            Token::Eof => return None,
+            _ => {}
+        }
+        with += 1;
+    }
+    let scrutinee = match (&tokens[m + 1].token, with == m + 2) {
+        (Token::Ident(name), true) => Some(name.clone()),
+        _ => None,
+    };
+
+    let mut patterns = Vec::new();
+    if !matches!(tokens.get(with + 1)?.token, Token::Pipe) {
+        patterns.push(with + 1);
+    }
+    let mut pipe_column = None;
+    let mut multiline = false;
+    let mut last = with;
+    let mut closer = None;
+    let mut depth = 0i32;
+
+    for (i, tok) in tokens.iter().enumerate().skip(with + 1) {
+        let starts_line = tokens[i - 1].pos.line != tok.pos.line;
+        if depth == 0 {
+            if let Some(column) = pipe_column {
+                if starts_line && tok.pos.column <= column && !matches!(tok.token, Token::Pipe) {
+                    break;
+                }
+            }
+            match tok.token {
+                Token::Eof => break,
+                // Arms of a nested, unparenthesized match cannot be told apart
+                Token::Match => return None,
+                Token::In
+                | Token::Then
+                | Token::Else
+                | Token::AndKeyword
+                | Token::RParen
+                | Token::RBracket
+                | Token::PipeRBracket
+                | Token::RBrace => {
+                    if tok.pos.line == tokens[last].pos.line {
+                        closer = Some(i);
+                    }
+                    break;
+                }
+                Token::Pipe => {
+                    patterns.push(i + 1);
+                    if pipe_column.is_none() {
+                        pipe_column = Some(tok.pos.column);
+                        multiline = starts_line;
+                    }
+                }
+                _ => {}
+            }
+        }
+        match tok.token {
+            Token::LParen | Token::LBracket | Token::LBracketPipe | Token::LBrace => depth += 1,
+            Token::RParen | Token::RBracket | Token::PipeRBracket | Token::RBrace => depth -= 1,
+            _ => {}
+        }
+        last = i;
+    }
+
+    if patterns.is_empty() || last == with {
+        return None;
+    }
+    Some(MatchArms {
+        scrutinee,
+        patterns,
+        last,
+        closer,
+        multiline,
+    })
+}
+
+/// Pattern matching any value of `variant`.
+fn variant_pattern(variant: &VariantDef) -> String {
+    if variant.is_simple() {
+        return variant.name.clone();
+    }
+    let holes = vec!["_"; variant.fields.len()];
+    format!("{}({})", variant.name, holes.join(", "))
+}
+
+/// Extract the selected expression into a new binding before the enclosing
+/// item.
+///
+/// Only offered when the expression does not use names bound inside that item,
+/// so moving it out cannot change what it refers to.
+fn extract_action(ctx: &ActionContext, start: usize, end: usize) -> Option<CodeActionOrCommand> {
+    let text = ctx.text;
+    let selected = text.get(start..end)?;
+    let expr_text = selected.trim();
+    if expr_text.is_empty() {
+        return None;
+    }
+
+    // The selection must not cut an identifier in half
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    if text[..start].ends_with(is_ident) && selected.starts_with(is_ident) {
+        return None;
+    }
+    if text[end..].starts_with(is_ident) && selected.ends_with(is_ident) {
+        return None;
+    }
+
+    let expr_tokens = Lexer::new(expr_text).tokenize().ok()?;
+    Parser::new(expr_tokens.clone()).parse().ok()?;
+
+    let tokens = &ctx.analysis.tokens;
+    let item = enclosing_item(tokens, start)?;
+    let item_offset = tokens[item].pos.offset;
+
+    let locals: Vec<String> = ctx
+        .analysis
+        .bindings_before(start)
+        .into_iter()
+        .filter(|b| b.offset >= item_offset)
+        .map(|b| b.name)
+        .collect();
+    let uses_local = expr_tokens
+        .iter()
+        .enumerate()
+        .any(|(i, tok)| match &tok.token {
+            Token::Ident(name) => {
+                let after_dot = i > 0 && matches!(expr_tokens[i - 1].token, Token::Dot);
+                !after_dot && locals.contains(name)
+            }
+            _ => false,
+        });
+    if uses_local {
+        return None;
+    }
+
+    let taken: HashSet<&str> = tokens
+        .iter()
+        .filter_map(|t| match &t.token {
+            Token::Ident(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let name = (1..)
+        .map(|n| match n {
+            1 => EXTRACTED_NAME.to_string(),
+            _ => format!("{}{}", EXTRACTED_NAME, n),
+        })
+        .find(|candidate| !taken.contains(candidate.as_str()))?;
+
+    let item_line = line_start(text, item_offset);
+    let indent = &text[item_line..item_offset];
+    let edits = vec![
+        ctx.edit(
+            item_line,
+            item_line,
+            format!("{}let {} = {}\n", indent, name, expr_text),
+        ),
+        ctx.edit(start, end, name.clone()),
+    ];
+    Some(ctx.action(
+        format!("Extract to let binding '{}'", name),
+        CodeActionKind::REFACTOR_EXTRACT,
+        edits,
+        None,
+    ))
+}
+
+/// The outermost `let` that starts a line and encloses `offset`: a top-level
+/// or module-level binding.
+fn enclosing_item(tokens: &[TokenWithPos], offset: usize) -> Option<usize> {
+    let mut item: Option<usize> = None;
+    for i in (0..tokens.len()).rev() {
+        let tok = &tokens[i];
+        let starts_line = i == 0 || tokens[i - 1].pos.line != tok.pos.line;
+        if tok.pos.offset >= offset || !starts_line {
+            continue;
+        }
+        let column = tok.pos.column;
+        let item_column = item.map(|c| tokens[c].pos.column);
+        match tok.token {
+            Token::Let => match item_column {
+                None => item = Some(i),
+                Some(c) if column < c => item = Some(i),
+                Some(c) if column == c => break, // a sibling binding
+                _ => {}
+            },
+            Token::Module | Token::Type | Token::Open | Token::Do
+                if item_column.map_or(true, |c| column <= c) =>
+            {
+                break
+            }
+            _ if item_column.is_some_and(|c| column < c) => break,
+            _ => {}
+        }
+    }
+    item
+}
+
+/// Index of the identifier token at (or ending at) `offset`.
+fn ident_at(tokens: &[TokenWithPos], offset: usize) -> Option<usize> {
+    tokens.iter().position(|tok| match &tok.token {
+        Token::Ident(name) => tok.pos.offset <= offset && offset <= tok.pos.offset + name.len(),
+        _ => false,
+    })
+}
+
+fn line_start(text: &str, offset: usize) -> usize {
+    text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0)
+}
+
+fn line_end(text: &str, offset: usize) -> usize {
+    text[offset..]
+        .find('\n')
+        .map(|i| offset + i)
+        .unwrap_or(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signatures::stdlib_functions;
+    use tower_lsp::lsp_types::Position;
+
+    /// Titles of the actions for a range, each with the text after applying it.
+    fn actions(text: &str, start: (u32, u32), end: (u32, u32)) -> Vec<(String, String)> {
+        let uri = Url::parse("file:///test.fsx").unwrap();
+        let range = Range {
+            start: Position::new(start.0, start.1),
+            end: Position::new(end.0, end.1),
+        };
+        code_actions(text, &uri, range, &[], &stdlib_functions())
+            .into_iter()
+            .map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => {
+                    let mut edits = action.edit.unwrap().changes.unwrap().remove(&uri).unwrap();
+                    edits.sort_by_key(|e| std::cmp::Reverse(e.range.start));
+                    let mut result = text.to_string();
+                    for edit in edits {
+                        let s = analysis::offset_at(
+                            &result,
+                            edit.range.start.line,
+                            edit.range.start.character,
+                        );
+                        let e = analysis::offset_at(
+                            &result,
+                            edit.range.end.line,
+                            edit.range.end.character,
+                        );
+                        result.replace_range(s..e, &edit.new_text);
+                    }
+                    (action.title, result)
+                }
+                CodeActionOrCommand::Command(c) => (c.title, text.to_string()),
+            })
+            .collect()
+    }
+
+    fn at(text: &str, line: u32, character: u32) -> Vec<(String, String)> {
+        actions(text, (line, character), (line, character))
+    }
+
+    fn titles(actions: &[(String, String)]) -> Vec<&str> {
+        actions.iter().map(|(title, _)| title.as_str()).collect()
+    }
+
+    fn applied<'a>(actions: &'a [(String, String)], title: &str) -> &'a str {
+        &actions
+            .iter()
+            .find(|(t, _)| t == title)
+            .unwrap_or_else(|| panic!("no action '{}' in {:?}", title, titles(actions)))
+            .1
+    }
+
+    #[test]
+    fn test_replace_misspelled_variable() {
+        let text = "let name = \"x\"\nlet greeting = nmae ++ \"!\"\n";
+        let result = at(text, 1, 16);
+        assert_eq!(titles(&result), vec!["Replace 'nmae' with 'name'"]);
+        assert_eq!(
+            result[0].1,
+            "let name = \"x\"\nlet greeting = name ++ \"!\"\n"
+        );
+    }
+
+    #[test]
+    fn test_open_or_qualify_module_member() {
+        let text = "let inc x = x + 1\nlet ys = map inc [1; 2]\n";
+        let result = at(text, 1, 10);
+        assert_eq!(
+            applied(&result, "Add 'open List'"),
+            "open List\nlet inc x = x + 1\nlet ys = map inc [1; 2]\n"
+        );
+        assert_eq!(
+            applied(&result, "Use 'List.map'"),
+            "let inc x = x + 1\nlet ys = List.map inc [1; 2]\n"
+        );
+        assert!(titles(&result).contains(&"Use 'Option.map'"));
+    }
+
+    #[test]
+    fn test_open_goes_after_existing_opens() {
+        let text = "open String\nlet ys = map (fun x -> x) [1]\nlet zs = map id ys\n";
+        let result = at(text, 2, 10);
+        assert_eq!(
+            applied(&result, "Add 'open List'"),
+            "open String\nopen List\nlet ys = map (fun x -> x) [1]\nlet zs = map id ys\n"
+        );
+    }
+
+    #[test]
+    fn test_add_type_annotation() {
+        let text = "let total = 1 + 2\nlet label n = \"#\" ++ n\nlet xs = [(1, \"a\")]\n";
+        assert_eq!(
+            applied(&at(text, 0, 5), "Add type annotation ': int'"),
+            "let total : int = 1 + 2\nlet label n = \"#\" ++ n\nlet xs = [(1, \"a\")]\n"
+        );
+        assert_eq!(
+            applied(&at(text, 1, 5), "Add type annotation ': string'"),
+            "let total = 1 + 2\nlet label n : string = \"#\" ++ n\nlet xs = [(1, \"a\")]\n"
+        );
+        assert_eq!(
+            titles(&at(text, 2, 4)),
+            vec!["Add type annotation ': (int * string) list'"]
+        );
+    }
+
+    #[test]
+    fn test_no_annotation_when_annotated_or_generic() {
+        let text = "let total : int = 1 + 2\nlet id x = x\n";
+        assert!(at(text, 0, 5).is_empty());
+        assert!(at(text, 1, 4).is_empty());
+    }
+
+    #[test]
+    fn test_add_missing_match_cases() {
+        let text = "type Shape =\n    | Circle of float\n    | Rect of float * float\n    | Dot\n\nlet area s =\n    match s with\n    | Circle(r) -> r\n    | Dot -> 0.0\n\nlet z = 1\n";
+        let result = at(text, 7, 8);
+        assert_eq!(
+            applied(&result, "Add missing case 'Rect'"),
+            "type Shape =\n    | Circle of float\n    | Rect of float * float\n    | Dot\n\nlet area s =\n    match s with\n    | Circle(r) -> r\n    | Dot -> 0.0\n    | Rect(_, _) -> ()\n\nlet z = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_add_missing_option_case() {
+        let text = "let get o =\n    match o with\n    | Some(x) -> x\n";
+        assert_eq!(
+            applied(&at(text, 1, 6), "Add missing case 'None'"),
+            "let get o =\n    match o with\n    | Some(x) -> x\n    | None -> ()\n"
+        );
+        // A wildcard arm covers everything
+        let text = "let get o =\n    match o with\n    | Some(x) -> x\n    | _ -> 0\n";
+        assert!(at(text, 1, 6).is_empty());
+    }
+
+    #[test]
+    fn test_extract_to_let() {
+        let text = "let a = 1\nlet f x =\n    let y = a + 2 in\n    y * x\n";
+        let result = actions(text, (2, 12), (2, 17));
+        assert_eq!(
+            applied(&result, "Extract to let binding 'extracted'"),
+            "let a = 1\nlet extracted = a + 2\nlet f x =\n    let y = extracted in\n    y * x\n"
+        );
+
+        // `y * x` uses names bound inside `f`
+        assert!(actions(text, (3, 4), (3, 9)).is_empty());
+        // Not a whole expression
+        assert!(actions(text, (2, 12), (2, 15)).is_empty());
+    }
+}
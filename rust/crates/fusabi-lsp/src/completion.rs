@@ -0,0 +1,627 @@
+//! Context-aware completion.
+//!
+//! The text before the cursor decides what is offered:
+//!
+//! - after `Name.`: members of a module (stdlib, host or user-defined) or the
+//!   fields of a record value
+//! - at the start of a match arm (`| ...`): DU constructors for patterns
+//! - right after `match x with`: snippets covering every case of a known DU
+//! - anywhere else: keywords, in-scope locals, modules, top-level host
+//!   functions, DU constructors and type providers
+
+use std::collections::BTreeMap;
+
+use fusabi_frontend::ast::VariantDef;
+use fusabi_frontend::Program;
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, InsertTextFormat, Position};
+
+use crate::analysis::{self, BindingKind, DocumentAnalysis};
+use crate::signatures::HostFunction;
+
+/// What the cursor is positioned on.
+#[derive(Debug, Clone, PartialEq)]
+enum CompletionContext {
+    /// After `qualifier.`
+    Member { qualifier: String },
+    /// In the pattern of a match arm
+    Pattern,
+    /// Directly after `match <scrutinee> with`
+    MatchArms {
+        scrutinee: Option<String>,
+        indent: String,
+        same_line: bool,
+    },
+    /// Expression position
+    General,
+}
+
+/// Compute completion items for `position` in `text`, with the programs of
+/// the files it `#load`s in scope.
+pub fn completions(
+    text: &str,
+    position: Position,
+    host_functions: &[HostFunction],
+    loaded: &[Program],
+) -> Vec<CompletionItem> {
+    let analysis = DocumentAnalysis::with_loaded(text, Some(position.line), host_functions, loaded);
+    let offset = analysis::offset_at(text, position.line, position.character);
+
+    match detect_context(text, position) {
+        CompletionContext::Member { qualifier } => {
+            member_completions(&analysis, &qualifier, host_functions)
+        }
+        CompletionContext::Pattern => pattern_completions(&analysis),
+        CompletionContext::MatchArms {
+            scrutinee,
+            indent,
+            same_line,
+        } => match_arm_snippets(&analysis, scrutinee.as_deref(), &indent, same_line),
+        CompletionContext::General => general_completions(&analysis, offset, host_functions),
+    }
+}
+
+fn detect_context(text: &str, position: Position) -> CompletionContext {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let line = lines.get(position.line as usize).copied().unwrap_or("");
+    // `character` counts UTF-16 code units, as in `offset_at`
+    let prefix = &line[..analysis::offset_at(line, 0, position.character)];
+
+    // Strip the identifier being typed
+    let before_word = prefix.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_');
+
+    if let Some(before_dot) = before_word.strip_suffix('.') {
+        let qualifier: String = before_dot
+            .chars()
+            .rev()
+            .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        let qualifier = qualifier.trim_matches('.').to_string();
+        if !qualifier.is_empty() && !qualifier.starts_with(|c: char| c.is_ascii_digit()) {
+            return CompletionContext::Member { qualifier };
+        }
+    }
+
+    if let Some(context) = match_with_context(prefix.trim_end(), line, true) {
+        return context;
+    }
+    let trimmed = before_word.trim_end();
+    if trimmed.trim().is_empty() {
+        let previous = lines[..position.line as usize]
+            .iter()
+            .rev()
+            .find(|l| !l.trim().is_empty());
+        if let Some(previous) = previous {
+            if let Some(context) = match_with_context(previous.trim_end(), previous, false) {
+                return context;
+            }
+        }
+    }
+
+    if let Some(last_pipe) = trimmed.rfind('|') {
+        let starts_arm = trimmed.trim_start().starts_with('|');
+        if starts_arm && !trimmed[last_pipe..].contains("->") {
+            return CompletionContext::Pattern;
+        }
+    }
+
+    CompletionContext::General
+}
+
+/// Recognize `... match <expr> with` at the end of `text`.
+fn match_with_context(text: &str, line: &str, same_line: bool) -> Option<CompletionContext> {
+    let before_with = text.strip_suffix("with")?;
+    if !before_with.ends_with(char::is_whitespace) {
+        return None;
+    }
+    let match_idx = before_with.rfind("match ")?;
+    let scrutinee = before_with[match_idx + "match ".len()..].trim();
+    let scrutinee = scrutinee
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_')
+        .then(|| scrutinee.to_string())
+        .filter(|s| !s.is_empty());
+    let indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+    Some(CompletionContext::MatchArms {
+        scrutinee,
+        indent,
+        same_line,
+    })
+}
+
+fn member_completions(
+    analysis: &DocumentAnalysis,
+    qualifier: &str,
+    host_functions: &[HostFunction],
+) -> Vec<CompletionItem> {
+    let last = qualifier.rsplit('.').next().unwrap_or(qualifier);
+
+    let module_name = [qualifier, last]
+        .into_iter()
+        .find(|name| analysis.registry.has_module(name))
+        .map(str::to_string)
+        .or_else(|| {
+            host_functions
+                .iter()
+                .any(|f| f.module() == Some(qualifier))
+                .then(|| qualifier.to_string())
+        });
+
+    if let Some(module_name) = module_name {
+        let mut members: BTreeMap<String, CompletionItem> = BTreeMap::new();
+
+        for f in host_functions
+            .iter()
+            .filter(|f| f.module() == Some(module_name.as_str()))
+        {
+            members.insert(f.member().to_string(), host_function_item(f, f.member()));
+        }
+
+        if let Some(bindings) = analysis.registry.get_module_bindings(&module_name) {
+            for (name, expr) in bindings {
+                if members.contains_key(name) {
+                    continue;
+                }
+                let kind = if expr.is_lambda() {
+                    CompletionItemKind::FUNCTION
+                } else {
+                    CompletionItemKind::VARIABLE
+                };
+                members.insert(
+                    name.clone(),
+                    CompletionItem {
+                        label: name.clone(),
+                        kind: Some(kind),
+                        detail: Some(format!("{}.{}", module_name, name)),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+
+        if let Some(types) = analysis.registry.get_module_types(&module_name) {
+            for name in types.keys() {
+                members
+                    .entry(name.clone())
+                    .or_insert_with(|| CompletionItem {
+                        label: name.clone(),
+                        kind: Some(CompletionItemKind::STRUCT),
+                        detail: Some(format!("type {}.{}", module_name, name)),
+                        ..Default::default()
+                    });
+            }
+        }
+
+        return members.into_values().collect();
+    }
+
+    if let Some(fields) = analysis.record_fields_of(last) {
+        return fields
+            .into_iter()
+            .map(|(field, ty)| field_item(&field, ty.as_deref()))
+            .collect();
+    }
+
+    // Unknown receiver: offer every field declared in the document
+    let mut fields: BTreeMap<String, CompletionItem> = BTreeMap::new();
+    for record in &analysis.records {
+        for (field, ty) in &record.fields {
+            fields
+                .entry(field.clone())
+                .or_insert_with(|| field_item(field, Some(&ty.to_string())));
+        }
+    }
+    fields.into_values().collect()
+}
+
+fn field_item(field: &str, ty: Option<&str>) -> CompletionItem {
+    CompletionItem {
+        label: field.to_string(),
+        kind: Some(CompletionItemKind::FIELD),
+        detail: ty.map(|ty| format!("{}: {}", field, ty)),
+        ..Default::default()
+    }
+}
+
+fn host_function_item(f: &HostFunction, label: &str) -> CompletionItem {
+    let detail = match &f.signature {
+        Some(sig) => format!("{} : {}", f.name, sig),
+        None => f.name.clone(),
+    };
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(CompletionItemKind::FUNCTION),
+        detail: Some(detail),
+        ..Default::default()
+    }
+}
+
+/// Pattern for a variant, with one snippet placeholder per field starting at `next`.
+fn variant_pattern_snippet(variant: &VariantDef, next: &mut usize) -> String {
+    if variant.is_simple() {
+        return variant.name.clone();
+    }
+    let holes: Vec<String> = variant
+        .fields
+        .iter()
+        .map(|_| {
+            let hole = format!("${{{}:_}}", next);
+            *next += 1;
+            hole
+        })
+        .collect();
+    format!("{}({})", variant.name, holes.join(", "))
+}
+
+fn pattern_completions(analysis: &DocumentAnalysis) -> Vec<CompletionItem> {
+    let mut items: Vec<CompletionItem> = analysis
+        .known_unions()
+        .iter()
+        .flat_map(|du| {
+            du.variants.iter().map(move |variant| {
+                let mut next = 1;
+                CompletionItem {
+                    label: variant.name.clone(),
+                    kind: Some(CompletionItemKind::ENUM_MEMBER),
+                    detail: Some(format!("{}.{}", du.name, variant)),
+                    insert_text: Some(variant_pattern_snippet(variant, &mut next)),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                }
+            })
+        })
+        .collect();
+    items.push(CompletionItem {
+        label: "_".to_string(),
+        kind: Some(CompletionItemKind::KEYWORD),
+        detail: Some("Wildcard pattern".to_string()),
+        ..Default::default()
+    });
+    items
+}
+
+fn match_arm_snippets(
+    analysis: &DocumentAnalysis,
+    scrutinee: Option<&str>,
+    indent: &str,
+    same_line: bool,
+) -> Vec<CompletionItem> {
+    let target = scrutinee.and_then(|name| analysis.union_of(name).map(|du| du.name.clone()));
+
+    analysis
+        .known_unions()
+        .iter()
+        .map(|du| {
+            let mut next = 1;
+            let arms: Vec<String> = du
+                .variants
+                .iter()
+                .map(|variant| {
+                    let pattern = variant_pattern_snippet(variant, &mut next);
+                    let arm = format!("| {} -> ${{{}:()}}", pattern, next);
+                    next += 1;
+                    arm
+                })
+                .collect();
+            let separator = format!("\n{}", indent);
+            let body = arms.join(&separator);
+            let insert_text = if same_line {
+                format!("{}{}", separator, body)
+            } else {
+                body
+            };
+            let is_target = target.as_deref() == Some(du.name.as_str());
+            CompletionItem {
+                label: format!("match {} cases", du.name),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: Some(format!("Arms for every case of {}", du.name)),
+                insert_text: Some(insert_text),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                preselect: is_target.then_some(true),
+                sort_text: Some(format!("{}{}", if is_target { "0" } else { "1" }, du.name)),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+fn general_completions(
+    analysis: &DocumentAnalysis,
+    offset: usize,
+    host_functions: &[HostFunction],
+) -> Vec<CompletionItem> {
+    let mut items = static_completions();
+
+    for binding in analysis.bindings_before(offset).into_iter().rev() {
+        let kind = match binding.kind {
+            BindingKind::Function => CompletionItemKind::FUNCTION,
+            BindingKind::Value | BindingKind::Parameter => CompletionItemKind::VARIABLE,
+        };
+        let detail = analysis
+            .types
+            .get(&binding.name)
+            .map(|ty| format!("{} : {}", binding.name, ty));
+        items.push(CompletionItem {
+            label: binding.name,
+            kind: Some(kind),
+            detail,
+            sort_text: Some("0".to_string()),
+            ..Default::default()
+        });
+    }
+
+    let mut modules: Vec<String> = analysis
+        .registry
+        .module_names()
+        .into_iter()
+        .filter(|name| !name.contains('.'))
+        .map(str::to_string)
+        .collect();
+    for module in host_functions.iter().filter_map(|f| f.module()) {
+        if !module.contains('.') && !modules.iter().any(|m| m == module) {
+            modules.push(module.to_string());
+        }
+    }
+    modules.sort();
+    items.extend(modules.into_iter().map(|name| CompletionItem {
+        detail: Some(format!("module {}", name)),
+        label: name,
+        kind: Some(CompletionItemKind::MODULE),
+        ..Default::default()
+    }));
+
+    items.extend(
+        host_functions
+            .iter()
+            .filter(|f| f.module().is_none())
+            .map(|f| host_function_item(f, &f.name)),
+    );
+
+    for du in &analysis.unions {
+        for variant in &du.variants {
+            items.push(CompletionItem {
+                label: variant.name.clone(),
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some(format!("{}.{}", du.name, variant)),
+                ..Default::default()
+            });
+        }
+    }
+
+    items
+}
+
+/// Keywords and type providers, offered in every expression position.
+fn static_completions() -> Vec<CompletionItem> {
+    let keywords = vec![
+        ("let", "Bind a value", CompletionItemKind::KEYWORD),
+        ("let rec", "Recursive binding", CompletionItemKind::KEYWORD),
+        ("in", "Let body", CompletionItemKind::KEYWORD),
+        ("if", "Conditional", CompletionItemKind::KEYWORD),
+        ("then", "If true branch", CompletionItemKind::KEYWORD),
+        ("else", "If false branch", CompletionItemKind::KEYWORD),
+        ("fun", "Lambda function", CompletionItemKind::KEYWORD),
+        ("match", "Pattern matching", CompletionItemKind::KEYWORD),
+        (
+            "with",
+            "Match arms / record update",
+            CompletionItemKind::KEYWORD,
+        ),
+        ("type", "Type definition", CompletionItemKind::KEYWORD),
+        ("module", "Module definition", CompletionItemKind::KEYWORD),
+        ("open", "Import module", CompletionItemKind::KEYWORD),
+        ("async", "Async block", CompletionItemKind::KEYWORD),
+        ("return", "Return value", CompletionItemKind::KEYWORD),
+        ("yield", "Yield value", CompletionItemKind::KEYWORD),
+        ("do", "Side effect", CompletionItemKind::KEYWORD),
+        ("while", "While loop", CompletionItemKind::KEYWORD),
+        ("break", "Exit loop", CompletionItemKind::KEYWORD),
+        ("continue", "Next iteration", CompletionItemKind::KEYWORD),
+        ("true", "Boolean true", CompletionItemKind::CONSTANT),
+        ("false", "Boolean false", CompletionItemKind::CONSTANT),
+    ];
+
+    // Type providers for compile-time type generation
+    let type_providers = vec![
+        (
+            "SqlProvider",
+            "Generate types from SQL DDL schema",
+            CompletionItemKind::CLASS,
+        ),
+        (
+            "ProtobufProvider",
+            "Generate types from Protocol Buffer definitions",
+            CompletionItemKind::CLASS,
+        ),
+        (
+            "JsonSchemaProvider",
+            "Generate types from JSON Schema",
+            CompletionItemKind::CLASS,
+        ),
+        (
+            "KubernetesProvider",
+            "Generate types from Kubernetes OpenAPI specs",
+            CompletionItemKind::CLASS,
+        ),
+        (
+            "TomlProvider",
+            "Generate types from TOML configuration",
+            CompletionItemKind::CLASS,
+        ),
+        (
+            "RegexProvider",
+            "Generate record types from regex capture groups",
+            CompletionItemKind::CLASS,
+        ),
+        (
+            "McpProvider",
+            "Generate types from MCP tool/resource schemas",
+            CompletionItemKind::CLASS,
+        ),
+        (
+            "GraphqlProvider",
+            "Generate types from GraphQL schemas",
+            CompletionItemKind::CLASS,
+        ),
+        (
+            "ObiProvider",
+            "Generate types from OBI/eBPF event schemas",
+            CompletionItemKind::CLASS,
+        ),
+        (
+            "HibanaSourcesProvider",
+            "Generate types for Hibana data sources",
+            CompletionItemKind::CLASS,
+        ),
+        (
+            "HibanaSinksProvider",
+            "Generate types for Hibana data sinks",
+            CompletionItemKind::CLASS,
+        ),
+    ];
+
+    keywords
+        .into_iter()
+        .chain(type_providers)
+        .map(|(label, detail, kind)| CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            detail: Some(detail.to_string()),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signatures::stdlib_functions;
+
+    fn labels_at(text: &str, line: u32, character: u32) -> Vec<String> {
+        completions(text, Position { line, character }, &stdlib_functions(), &[])
+            .into_iter()
+            .map(|item| item.label)
+            .collect()
+    }
+
+    fn end_of(text: &str) -> (u32, u32) {
+        let lines: Vec<&str> = text.split('\n').collect();
+        let last = lines.last().unwrap();
+        ((lines.len() - 1) as u32, last.encode_utf16().count() as u32)
+    }
+
+    #[test]
+    fn test_context_counts_utf16_units() {
+        // The emoji take two UTF-16 code units each
+        let text = "let e = \"😀😀😀\" in List. + 1";
+        let col = "let e = \"😀😀😀\" in List.".encode_utf16().count() as u32;
+        let labels = labels_at(text, 0, col);
+        assert!(labels.contains(&"map".to_string()), "{:?}", labels);
+    }
+
+    #[test]
+    fn test_stdlib_module_members() {
+        let text = "let xs = [1; 2; 3]\nList.";
+        let (line, col) = end_of(text);
+        let labels = labels_at(text, line, col);
+        assert!(labels.contains(&"map".to_string()));
+        assert!(labels.contains(&"filter".to_string()));
+        assert!(!labels.contains(&"let".to_string()));
+    }
+
+    #[test]
+    fn test_member_detail_has_signature() {
+        let text = "Map.";
+        let items = completions(text, Position::new(0, 4), &stdlib_functions(), &[]);
+        let add = items.iter().find(|i| i.label == "add").unwrap();
+        assert!(add.detail.as_deref().unwrap().contains("Map<'k, 'v>"));
+    }
+
+    #[test]
+    fn test_user_module_members() {
+        let text = "module Geometry =\n    let area w h = w * h\n    let unit = 1\n\nGeometry.";
+        let (line, col) = end_of(text);
+        let labels = labels_at(text, line, col);
+        assert_eq!(labels, vec!["area".to_string(), "unit".to_string()]);
+    }
+
+    #[test]
+    fn test_loaded_module_members() {
+        let utils = "module Utils =\n    let square x = x * x";
+        let tokens = fusabi_frontend::Lexer::new(utils).tokenize().unwrap();
+        let loaded = fusabi_frontend::Parser::new(tokens)
+            .parse_program()
+            .unwrap();
+
+        let text = "#load \"utils.fsx\"\nlet y = Utils.";
+        let (line, col) = end_of(text);
+        let items = completions(
+            text,
+            Position::new(line, col),
+            &stdlib_functions(),
+            &[loaded],
+        );
+        let labels: Vec<&str> = items.iter().map(|i| i.label.as_str()).collect();
+        assert_eq!(labels, vec!["square"]);
+    }
+
+    #[test]
+    fn test_record_fields() {
+        let text =
+            "type Person = { name: string; age: int }\nlet p = { name = \"Ada\"; age = 36 }\np.";
+        let (line, col) = end_of(text);
+        let items = completions(text, Position::new(line, col), &stdlib_functions(), &[]);
+        let labels: Vec<&str> = items.iter().map(|i| i.label.as_str()).collect();
+        assert_eq!(labels, vec!["age", "name"]);
+        assert!(items
+            .iter()
+            .all(|i| i.kind == Some(CompletionItemKind::FIELD)));
+    }
+
+    #[test]
+    fn test_locals_in_scope() {
+        let text = "let total = 10\nlet add a b = a + b\n";
+        let labels = labels_at(text, 2, 0);
+        assert!(labels.contains(&"total".to_string()));
+        assert!(labels.contains(&"add".to_string()));
+        assert!(labels.contains(&"printfn".to_string()));
+        assert!(labels.contains(&"List".to_string()));
+    }
+
+    #[test]
+    fn test_locals_after_cursor_not_offered() {
+        let text = "let a = 1\n\nlet later = 2";
+        let labels = labels_at(text, 1, 0);
+        assert!(labels.contains(&"a".to_string()));
+        assert!(!labels.contains(&"later".to_string()));
+    }
+
+    #[test]
+    fn test_du_constructors_in_pattern() {
+        let text = "type Shape = Circle of float | Rect of float * float\nlet s = Circle(1.0)\nmatch s with\n| ";
+        let (line, col) = end_of(text);
+        let items = completions(text, Position::new(line, col), &stdlib_functions(), &[]);
+        let rect = items.iter().find(|i| i.label == "Rect").unwrap();
+        assert_eq!(rect.insert_text.as_deref(), Some("Rect(${1:_}, ${2:_})"));
+        assert!(items.iter().any(|i| i.label == "None"));
+        assert!(!items.iter().any(|i| i.label == "let"));
+    }
+
+    #[test]
+    fn test_match_arm_snippet_for_known_du() {
+        let text = "type Shape = Circle of float | Square of float\nlet s = Circle(1.0)\nlet r = match s with";
+        let (line, col) = end_of(text);
+        let items = completions(text, Position::new(line, col), &stdlib_functions(), &[]);
+        let snippet = items
+            .iter()
+            .find(|i| i.label == "match Shape cases")
+            .unwrap();
+        assert_eq!(snippet.preselect, Some(true));
+        assert_eq!(
+            snippet.insert_text.as_deref(),
+            Some("\n| Circle(${1:_}) -> ${2:()}\n| Square(${3:_}) -> ${4:()}")
+        );
+    }
+}
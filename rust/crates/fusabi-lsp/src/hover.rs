@@ -0,0 +1,127 @@
+//! Hover documentation for declarations.
+//!
+//! Hovering a name declared with `///` comments, in the document or in a file
+//! it `#load`s, shows the comments under the name and its inferred type.
+//! `Module.name` is looked up qualified, and an unqualified name also in the
+//! modules the document opens.
+
+use fusabi_frontend::Program;
+use tower_lsp::lsp_types::Position;
+
+use crate::analysis::DocumentAnalysis;
+use crate::signatures::HostFunction;
+
+/// Markdown for the documented declaration at `position` in `text`, with the
+/// programs of the files it `#load`s in scope.
+pub fn declaration_hover(
+    text: &str,
+    position: Position,
+    host_functions: &[HostFunction],
+    loaded: &[Program],
+) -> Option<String> {
+    let line = text.lines().nth(position.line as usize)?;
+    let (qualifier, word) = name_at(line, position.character as usize)?;
+
+    let analysis = DocumentAnalysis::with_loaded(text, None, host_functions, loaded);
+    let opened: Vec<String> = analysis
+        .program
+        .iter()
+        .flat_map(|program| {
+            program
+                .imports
+                .iter()
+                .filter_map(|import| Some(format!("{}.{}", import.module_path.first()?, word)))
+        })
+        .collect();
+    let candidates: Vec<String> = match qualifier {
+        Some(qualifier) => vec![format!("{}.{}", qualifier, word)],
+        None => std::iter::once(word.to_string()).chain(opened).collect(),
+    };
+
+    let programs = analysis.program.iter().chain(loaded);
+    let (name, doc) = programs
+        .flat_map(|program| {
+            candidates
+                .iter()
+                .filter_map(|name| Some((name, program.docs.get(name)?)))
+        })
+        .next()?;
+
+    let signature = match analysis.types.get(name) {
+        Some(ty) => format!("{} : {}", name, ty),
+        None => name.clone(),
+    };
+    Some(format!("```fusabi\n{}\n```\n\n{}", signature, doc))
+}
+
+/// The identifier at byte `index` of `line`, and the module qualifying it
+/// (`Math` in `Math.square`), if any.
+fn name_at(line: &str, index: usize) -> Option<(Option<&str>, &str)> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    if index >= line.len() || !line.is_char_boundary(index) {
+        return None;
+    }
+    let start = line[..index]
+        .rfind(|c: char| !is_ident(c))
+        .map_or(0, |i| i + 1);
+    let end = line[index..]
+        .find(|c: char| !is_ident(c))
+        .map_or(line.len(), |i| i + index);
+    let word = &line[start..end];
+    if word.is_empty() {
+        return None;
+    }
+
+    let qualifier = line[..start].strip_suffix('.').and_then(|before| {
+        let qualifier_start = before.rfind(|c: char| !is_ident(c)).map_or(0, |i| i + 1);
+        let qualifier = &before[qualifier_start..];
+        (!qualifier.is_empty()).then_some(qualifier)
+    });
+    Some((qualifier, word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signatures::stdlib_functions;
+    use fusabi_frontend::{Lexer, Parser};
+
+    fn hover(text: &str, line: u32, character: u32, loaded: &[Program]) -> Option<String> {
+        declaration_hover(
+            text,
+            Position::new(line, character),
+            &stdlib_functions(),
+            loaded,
+        )
+    }
+
+    #[test]
+    fn test_hover_shows_docs_and_type() {
+        let text = "/// Adds one.\n/// Works on ints.\nlet inc x = x + 1\nlet y = inc 2";
+        let info = hover(text, 3, 9, &[]).unwrap();
+        assert_eq!(
+            info,
+            "```fusabi\ninc : int -> int\n```\n\nAdds one.\nWorks on ints."
+        );
+        // Undocumented names have no hover
+        assert_eq!(hover(text, 3, 4, &[]), None);
+    }
+
+    #[test]
+    fn test_hover_qualified_and_loaded() {
+        let utils =
+            "/// Helpers.\nmodule Utils =\n    /// Squares a number.\n    let square x = x * x";
+        let tokens = Lexer::new(utils).tokenize().unwrap();
+        let loaded = Parser::new(tokens).parse_program().unwrap();
+
+        let text = "#load \"utils.fsx\"\nopen Utils\nlet a = Utils.square 2\nlet b = square 3";
+        let loaded = [loaded];
+        let qualified = hover(text, 2, 15, &loaded).unwrap();
+        assert!(qualified.contains("Utils.square"));
+        assert!(qualified.ends_with("Squares a number."));
+        let opened = hover(text, 3, 9, &loaded).unwrap();
+        assert!(opened.ends_with("Squares a number."));
+        let module = hover(text, 2, 9, &loaded).unwrap();
+        assert!(module.ends_with("Helpers."));
+    }
+}
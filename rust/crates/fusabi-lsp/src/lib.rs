@@ -1,130 +1,137 @@
 //! Fusabi Language Server Protocol Implementation
 //!
-//! Provides IDE features for Fusabi: diagnostics, hover, and completion.
+//! Provides IDE features for Fusabi: diagnostics, hover, completion and code actions.
+//! Hover shows keyword documentation and the `///` comments of declarations.
+//!
+//! Documents are synced incrementally and analyzed in the background. Each
+//! edit cancels the pending analysis of the document (and of the open
+//! documents that `#load` it), so only the latest text is ever reported.
+//!
+//! Type provider declarations are resolved during analysis, so a schema that
+//! is missing or invalid is reported at its declaration. Editing a schema file
+//! re-analyzes the open documents whose providers read it.
+
+pub mod analysis;
+pub mod code_actions;
+pub mod completion;
+pub mod hover;
+pub mod signatures;
+pub mod workspace;
 
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use fusabi_frontend::{Lexer, Parser};
+use fusabi_frontend::{Lexer, Parser, Program, ProviderResolver};
+use tokio::task::JoinHandle;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
+use crate::analysis::DocumentAnalysis;
+use crate::signatures::HostFunction;
+use crate::workspace::{LoadedFiles, ProviderSource, Workspace, MANIFEST_FILE};
+
+/// How long a document must stay unchanged before it is analyzed.
+const ANALYSIS_DELAY: Duration = Duration::from_millis(150);
+
+/// Schema files read by the built-in type providers
+const SCHEMA_GLOB: &str = "**/*.{json,toml,sql,proto,graphql,gql}";
+
 pub struct FusabiLanguageServer {
     client: Client,
-    documents: RwLock<HashMap<Url, String>>,
+    workspace: Arc<Mutex<Workspace>>,
+    host_functions: Arc<Vec<HostFunction>>,
+    resolver: Arc<ProviderResolver>,
+    /// Analyses waiting to publish, by document
+    pending: Mutex<HashMap<Url, JoinHandle<()>>>,
 }
 
 impl FusabiLanguageServer {
     pub fn new(client: Client) -> Self {
+        Self::with_host_functions(client, Vec::new())
+    }
+
+    /// Create a server that also completes functions registered by an
+    /// embedding host, in addition to the standard library.
+    pub fn with_host_functions(client: Client, host_functions: Vec<HostFunction>) -> Self {
+        let mut functions = signatures::stdlib_functions();
+        for f in host_functions {
+            if !functions.iter().any(|existing| existing.name == f.name) {
+                functions.push(f);
+            }
+        }
         Self {
             client,
-            documents: RwLock::new(HashMap::new()),
+            workspace: Arc::new(Mutex::new(Workspace::new())),
+            host_functions: Arc::new(functions),
+            resolver: Arc::new(ProviderResolver::from_env()),
+            pending: Mutex::new(HashMap::new()),
         }
     }
 
-    async fn publish_diagnostics(&self, uri: Url, text: &str) {
-        let diagnostics = self.analyze(text);
-        self.client
-            .publish_diagnostics(uri, diagnostics, None)
-            .await;
-    }
-
-    fn analyze(&self, text: &str) -> Vec<Diagnostic> {
-        let mut diagnostics = Vec::new();
-
-        let mut lexer = Lexer::new(text);
-        let tokens = match lexer.tokenize() {
-            Ok(tokens) => tokens,
-            Err(e) => {
-                let (line, col, msg) = match &e {
-                    fusabi_frontend::LexError::UnexpectedChar(ch, pos) => (
-                        pos.line,
-                        pos.column,
-                        format!("Unexpected character: '{}'", ch),
-                    ),
-                    fusabi_frontend::LexError::UnterminatedString(pos) => (
-                        pos.line,
-                        pos.column,
-                        "Unterminated string literal".to_string(),
-                    ),
-                    fusabi_frontend::LexError::InvalidNumber(s, pos) => {
-                        (pos.line, pos.column, format!("Invalid number: '{}'", s))
-                    }
-                    fusabi_frontend::LexError::UnterminatedComment(pos) => {
-                        (pos.line, pos.column, "Unterminated comment".to_string())
-                    }
-                    fusabi_frontend::LexError::UnknownDirective(name, pos) => (
-                        pos.line,
-                        pos.column,
-                        format!("Unknown directive: '{}'", name),
-                    ),
+    /// Schedule analysis of `uri`, cancelling any analysis still pending for it.
+    fn schedule_check(&self, uri: Url) {
+        let client = self.client.clone();
+        let workspace = Arc::clone(&self.workspace);
+        let host_functions = Arc::clone(&self.host_functions);
+        let resolver = Arc::clone(&self.resolver);
+
+        let task = tokio::spawn({
+            let uri = uri.clone();
+            async move {
+                tokio::time::sleep(ANALYSIS_DELAY).await;
+
+                let snapshot = {
+                    let mut workspace = workspace.lock().unwrap();
+                    let document = workspace.document(&uri).cloned();
+                    document.map(|doc| {
+                        let loaded = workspace.load_dependencies(&uri);
+                        (doc, loaded, workspace.provider_sources(&uri))
+                    })
+                };
+                let Some((document, loaded, providers)) = snapshot else {
+                    return;
                 };
-                diagnostics.push(Diagnostic {
-                    range: Range {
-                        start: Position {
-                            line: (line.saturating_sub(1)) as u32,
-                            character: (col.saturating_sub(1)) as u32,
-                        },
-                        end: Position {
-                            line: (line.saturating_sub(1)) as u32,
-                            character: col as u32,
-                        },
-                    },
-                    severity: Some(DiagnosticSeverity::ERROR),
-                    source: Some("fusabi".to_string()),
-                    message: msg,
-                    ..Default::default()
-                });
-                return diagnostics;
-            }
-        };
 
-        let mut parser = Parser::new(tokens);
-        if let Err(e) = parser.parse_program() {
-            let (line, col, msg) = match &e {
-                fusabi_frontend::ParseError::UnexpectedToken {
-                    expected,
-                    found,
-                    pos,
-                } => (
-                    pos.line,
-                    pos.column,
-                    format!("Expected {}, found {}", expected, found),
-                ),
-                fusabi_frontend::ParseError::UnexpectedEof { expected } => {
-                    let lines: Vec<&str> = text.lines().collect();
-                    let last_line = lines.len().max(1);
-                    let last_col = lines.last().map(|l| l.len()).unwrap_or(0);
-                    (
-                        last_line,
-                        last_col,
-                        format!("Unexpected end of file, expected {}", expected),
-                    )
+                let diagnostics = tokio::task::spawn_blocking(move || {
+                    let mut diagnostics = analyze(&document.text, &loaded, &host_functions);
+                    diagnostics.extend(provider_diagnostics(&document.text, &providers, &resolver));
+                    diagnostics
+                })
+                .await;
+                if let Ok(diagnostics) = diagnostics {
+                    client
+                        .publish_diagnostics(uri, diagnostics, Some(document.version))
+                        .await;
                 }
-                fusabi_frontend::ParseError::InvalidExpr { message, pos } => {
-                    (pos.line, pos.column, message.clone())
-                }
-            };
-            diagnostics.push(Diagnostic {
-                range: Range {
-                    start: Position {
-                        line: (line.saturating_sub(1)) as u32,
-                        character: (col.saturating_sub(1)) as u32,
-                    },
-                    end: Position {
-                        line: (line.saturating_sub(1)) as u32,
-                        character: col as u32,
-                    },
-                },
-                severity: Some(DiagnosticSeverity::ERROR),
-                source: Some("fusabi".to_string()),
-                message: msg,
-                ..Default::default()
-            });
+            }
+        });
+
+        if let Some(stale) = self.pending.lock().unwrap().insert(uri, task) {
+            stale.abort();
+        }
+    }
+
+    /// Schedule analysis of `uri` and of the open documents that load it.
+    fn schedule_with_dependents(&self, uri: Url) {
+        let dependents = self.workspace.lock().unwrap().dependents(&uri);
+        self.schedule_check(uri);
+        for dependent in dependents {
+            self.schedule_check(dependent);
         }
+    }
 
-        diagnostics
+    fn document_text(&self, uri: &Url) -> Option<String> {
+        let workspace = self.workspace.lock().unwrap();
+        workspace.document(uri).map(|doc| doc.text.clone())
+    }
+
+    /// Text of `uri` and the programs it loads, for the request handlers.
+    fn document_with_loaded(&self, uri: &Url) -> Option<(String, Vec<Program>)> {
+        let mut workspace = self.workspace.lock().unwrap();
+        let text = workspace.document(uri)?.text.clone();
+        Some((text, workspace.load_dependencies(uri).programs))
     }
 
     fn get_hover_info(&self, text: &str, position: Position) -> Option<String> {
@@ -184,10 +191,10 @@ impl FusabiLanguageServer {
             // Type providers
             "SqlProvider" => "**SqlProvider** - SQL Schema Type Provider\n\nGenerates record types from SQL DDL schema definitions.\n\n```fusabi\ntype DbSchema = SqlProvider<\"schema.sql\">\ntype Users = DbSchema.users  // Record with columns as fields\n```\n\nSource: file path, inline SQL, or \"embedded\"",
             "ProtobufProvider" => "**ProtobufProvider** - Protocol Buffer Type Provider\n\nGenerates types from .proto file definitions.\n\n```fusabi\ntype Proto = ProtobufProvider<\"messages.proto\">\ntype User = Proto.User  // Generated message type\n```",
-            "JsonSchemaProvider" => "**JsonSchemaProvider** - JSON Schema Type Provider\n\nGenerates types from JSON Schema definitions.\n\n```fusabi\ntype Schema = JsonSchemaProvider<\"schema.json\">\n```\n\nSupports $ref resolution, oneOf → DU conversion.",
+            "JsonSchemaProvider" => "**JsonSchemaProvider** - JSON Schema Type Provider\n\nGenerates types from JSON Schema definitions.\n\n```fusabi\ntype Schema = JsonSchemaProvider<\"schema.json\">\nlet parsed = Schema.parse text  // Result<Schema, string>\n```\n\nSupports $ref resolution, oneOf → DU conversion.",
             "KubernetesProvider" => "**KubernetesProvider** - Kubernetes OpenAPI Type Provider\n\nGenerates types from Kubernetes API schemas.\n\n```fusabi\ntype K8s = KubernetesProvider<\"https://api.k8s.io/openapi/v2\">\ntype Pod = K8s.Core.V1.Pod\n```",
-            "TomlProvider" => "**TomlProvider** - TOML Configuration Type Provider\n\nGenerates types from TOML configuration files.\n\n```fusabi\ntype Config = TomlProvider<\"config.toml\">\nlet settings = Config.parse \"config.toml\"\n```",
-            "RegexProvider" => "**RegexProvider** - Regex Capture Group Type Provider\n\nGenerates record types from named regex capture groups.\n\n```fusabi\ntype LogEntry = RegexProvider<\"(?P<timestamp>\\d+) (?P<level>\\w+) (?P<msg>.*)\">\n```",
+            "TomlProvider" => "**TomlProvider** - TOML Configuration Type Provider\n\nGenerates types from TOML configuration files.\n\n```fusabi\ntype Config = TomlProvider<\"config.toml\">\nlet settings = Config.parse text  // Result<Config, string>\n```",
+            "RegexProvider" => "**RegexProvider** - Regex Capture Group Type Provider\n\nGenerates record types from named regex capture groups.\n\n```fusabi\ntype LogEntry = RegexProvider<\"(?P<timestamp>\\d+) (?P<level>\\w+) (?P<msg>.*)\">\nlet entry = LogEntry.tryMatch line  // LogEntry option\n```",
             "McpProvider" => "**McpProvider** - MCP Schema Type Provider\n\nGenerates types from Model Context Protocol tool/resource schemas.\n\n```fusabi\ntype Mcp = McpProvider<\"server.json\">\ntype Tool = Mcp.tools.search\n```",
             "GraphqlProvider" => "**GraphqlProvider** - GraphQL Schema Type Provider\n\nGenerates types from GraphQL schema definitions.\n\n```fusabi\ntype Api = GraphqlProvider<\"schema.graphql\">\ntype User = Api.User\n```",
             "ObiProvider" => "**ObiProvider** - OBI/eBPF Event Type Provider\n\nGenerates types from Observability Binary Interface schemas.\n\n```fusabi\ntype Events = ObiProvider<\"events.obi\">\ntype SyscallEvent = Events.syscall\n```",
@@ -197,144 +204,31 @@ impl FusabiLanguageServer {
         };
         Some(docs.to_string())
     }
-
-    fn get_completions(&self) -> Vec<CompletionItem> {
-        let keywords = vec![
-            ("let", "Bind a value", CompletionItemKind::KEYWORD),
-            ("let rec", "Recursive binding", CompletionItemKind::KEYWORD),
-            ("in", "Let body", CompletionItemKind::KEYWORD),
-            ("if", "Conditional", CompletionItemKind::KEYWORD),
-            ("then", "If true branch", CompletionItemKind::KEYWORD),
-            ("else", "If false branch", CompletionItemKind::KEYWORD),
-            ("fun", "Lambda function", CompletionItemKind::KEYWORD),
-            ("match", "Pattern matching", CompletionItemKind::KEYWORD),
-            (
-                "with",
-                "Match arms / record update",
-                CompletionItemKind::KEYWORD,
-            ),
-            ("type", "Type definition", CompletionItemKind::KEYWORD),
-            ("module", "Module definition", CompletionItemKind::KEYWORD),
-            ("open", "Import module", CompletionItemKind::KEYWORD),
-            ("async", "Async block", CompletionItemKind::KEYWORD),
-            ("return", "Return value", CompletionItemKind::KEYWORD),
-            ("yield", "Yield value", CompletionItemKind::KEYWORD),
-            ("do", "Side effect", CompletionItemKind::KEYWORD),
-            ("while", "While loop", CompletionItemKind::KEYWORD),
-            ("break", "Exit loop", CompletionItemKind::KEYWORD),
-            ("continue", "Next iteration", CompletionItemKind::KEYWORD),
-            ("true", "Boolean true", CompletionItemKind::CONSTANT),
-            ("false", "Boolean false", CompletionItemKind::CONSTANT),
-        ];
-
-        let builtins = vec![
-            (
-                "printfn",
-                "Print formatted line",
-                CompletionItemKind::FUNCTION,
-            ),
-            ("printf", "Print formatted", CompletionItemKind::FUNCTION),
-            ("List.map", "Map over list", CompletionItemKind::FUNCTION),
-            ("List.filter", "Filter list", CompletionItemKind::FUNCTION),
-            ("List.fold", "Fold list", CompletionItemKind::FUNCTION),
-            ("List.head", "First element", CompletionItemKind::FUNCTION),
-            ("List.tail", "Rest of list", CompletionItemKind::FUNCTION),
-            ("List.length", "List length", CompletionItemKind::FUNCTION),
-            ("Array.length", "Array length", CompletionItemKind::FUNCTION),
-            (
-                "Array.get",
-                "Get array element",
-                CompletionItemKind::FUNCTION,
-            ),
-            (
-                "Array.set",
-                "Set array element",
-                CompletionItemKind::FUNCTION,
-            ),
-        ];
-
-        // Type providers for compile-time type generation
-        let type_providers = vec![
-            (
-                "SqlProvider",
-                "Generate types from SQL DDL schema",
-                CompletionItemKind::CLASS,
-            ),
-            (
-                "ProtobufProvider",
-                "Generate types from Protocol Buffer definitions",
-                CompletionItemKind::CLASS,
-            ),
-            (
-                "JsonSchemaProvider",
-                "Generate types from JSON Schema",
-                CompletionItemKind::CLASS,
-            ),
-            (
-                "KubernetesProvider",
-                "Generate types from Kubernetes OpenAPI specs",
-                CompletionItemKind::CLASS,
-            ),
-            (
-                "TomlProvider",
-                "Generate types from TOML configuration",
-                CompletionItemKind::CLASS,
-            ),
-            (
-                "RegexProvider",
-                "Generate record types from regex capture groups",
-                CompletionItemKind::CLASS,
-            ),
-            (
-                "McpProvider",
-                "Generate types from MCP tool/resource schemas",
-                CompletionItemKind::CLASS,
-            ),
-            (
-                "GraphqlProvider",
-                "Generate types from GraphQL schemas",
-                CompletionItemKind::CLASS,
-            ),
-            (
-                "ObiProvider",
-                "Generate types from OBI/eBPF event schemas",
-                CompletionItemKind::CLASS,
-            ),
-            (
-                "HibanaSourcesProvider",
-                "Generate types for Hibana data sources",
-                CompletionItemKind::CLASS,
-            ),
-            (
-                "HibanaSinksProvider",
-                "Generate types for Hibana data sinks",
-                CompletionItemKind::CLASS,
-            ),
-        ];
-
-        keywords
-            .into_iter()
-            .chain(builtins)
-            .chain(type_providers)
-            .map(|(label, detail, kind)| CompletionItem {
-                label: label.to_string(),
-                kind: Some(kind),
-                detail: Some(detail.to_string()),
-                ..Default::default()
-            })
-            .collect()
-    }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for FusabiLanguageServer {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        {
+            let mut workspace = self.workspace.lock().unwrap();
+            let folders = params.workspace_folders.unwrap_or_default();
+            let roots = folders
+                .into_iter()
+                .map(|folder| folder.uri)
+                .chain(params.root_uri);
+            for root in roots {
+                if let Ok(path) = root.to_file_path() {
+                    workspace.add_root(&path);
+                }
+            }
+        }
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Options(
                     TextDocumentSyncOptions {
                         open_close: Some(true),
-                        change: Some(TextDocumentSyncKind::FULL),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
                         ..Default::default()
                     },
                 )),
@@ -343,6 +237,16 @@ impl LanguageServer for FusabiLanguageServer {
                     trigger_characters: Some(vec![".".to_string()]),
                     ..Default::default()
                 }),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR_EXTRACT,
+                            CodeActionKind::REFACTOR_REWRITE,
+                        ]),
+                        ..Default::default()
+                    },
+                )),
                 ..Default::default()
             },
             server_info: Some(ServerInfo {
@@ -356,55 +260,110 @@ impl LanguageServer for FusabiLanguageServer {
         self.client
             .log_message(MessageType::INFO, "Fusabi LSP initialized")
             .await;
+
+        // Files loaded with `#load`, manifests and provider schemas are
+        // usually not open in the editor, so ask to be told when they change
+        let watchers = [
+            "**/*.fsx".to_string(),
+            format!("**/{}", MANIFEST_FILE),
+            SCHEMA_GLOB.to_string(),
+        ]
+        .into_iter()
+        .map(|glob| FileSystemWatcher {
+            glob_pattern: GlobPattern::String(glob),
+            kind: None,
+        })
+        .collect();
+        let options = DidChangeWatchedFilesRegistrationOptions { watchers };
+        let registration = Registration {
+            id: "fusabi-watched-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Could not watch workspace files: {}", e),
+                )
+                .await;
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
+        for (_, task) in self.pending.lock().unwrap().drain() {
+            task.abort();
+        }
         Ok(())
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        let uri = params.text_document.uri;
-        let text = params.text_document.text;
-
-        {
-            let mut docs = self.documents.write().unwrap();
-            docs.insert(uri.clone(), text.clone());
-        }
-
-        self.publish_diagnostics(uri, &text).await;
+        let document = params.text_document;
+        self.workspace
+            .lock()
+            .unwrap()
+            .open(document.uri.clone(), document.text, document.version);
+        self.schedule_with_dependents(document.uri);
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri;
-        if let Some(change) = params.content_changes.into_iter().last() {
-            let text = change.text;
+        let changed = self.workspace.lock().unwrap().change(
+            &uri,
+            params.text_document.version,
+            &params.content_changes,
+        );
+        if changed {
+            self.schedule_with_dependents(uri);
+        }
+    }
 
-            {
-                let mut docs = self.documents.write().unwrap();
-                docs.insert(uri.clone(), text.clone());
-            }
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        if let Some(task) = self.pending.lock().unwrap().remove(&uri) {
+            task.abort();
+        }
 
-            self.publish_diagnostics(uri, &text).await;
+        // Dependents go back to seeing the file as saved on disk
+        let dependents = {
+            let mut workspace = self.workspace.lock().unwrap();
+            let dependents = workspace.dependents(&uri);
+            workspace.close(&uri);
+            dependents
+        };
+        for dependent in dependents {
+            self.schedule_check(dependent);
         }
     }
 
-    async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        let mut docs = self.documents.write().unwrap();
-        docs.remove(&params.text_document.uri);
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let mut affected: Vec<Url> = Vec::new();
+        {
+            let mut workspace = self.workspace.lock().unwrap();
+            for change in params.changes {
+                if let Ok(path) = change.uri.to_file_path() {
+                    affected.extend(workspace.file_changed(&path));
+                }
+            }
+        }
+        affected.sort();
+        affected.dedup();
+        for uri in affected {
+            self.schedule_check(uri);
+        }
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         let uri = &params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
-        let docs = self.documents.read().unwrap();
-        let text = match docs.get(uri) {
-            Some(t) => t.clone(),
-            None => return Ok(None),
+        let Some((text, loaded)) = self.document_with_loaded(uri) else {
+            return Ok(None);
         };
-        drop(docs);
 
-        let info = self.get_hover_info(&text, position);
+        let info = self
+            .get_hover_info(&text, position)
+            .or_else(|| hover::declaration_hover(&text, position, &self.host_functions, &loaded));
 
         Ok(info.map(|content| Hover {
             contents: HoverContents::Markup(MarkupContent {
@@ -415,8 +374,224 @@ impl LanguageServer for FusabiLanguageServer {
         }))
     }
 
-    async fn completion(&self, _params: CompletionParams) -> Result<Option<CompletionResponse>> {
-        let items = self.get_completions();
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let Some((text, loaded)) = self.document_with_loaded(uri) else {
+            return Ok(None);
+        };
+
+        let items = completion::completions(&text, position, &self.host_functions, &loaded);
         Ok(Some(CompletionResponse::Array(items)))
     }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = &params.text_document.uri;
+
+        let Some(text) = self.document_text(uri) else {
+            return Ok(None);
+        };
+
+        let actions = code_actions::code_actions(
+            &text,
+            uri,
+            params.range,
+            &params.context.diagnostics,
+            &self.host_functions,
+        );
+        Ok(Some(actions))
+    }
+}
+
+/// Diagnostics for a document: a lexing error or every parsing error, `#load`
+/// directives that could not be loaded, and unbound names.
+fn analyze(text: &str, loaded: &LoadedFiles, host_functions: &[HostFunction]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = loaded
+        .problems
+        .iter()
+        .map(|problem| {
+            let line = problem.line.saturating_sub(1);
+            let width = text
+                .split('\n')
+                .nth(line)
+                .map_or(0, |l| l.encode_utf16().count());
+            Diagnostic {
+                range: Range {
+                    start: Position::new(line as u32, 0),
+                    end: Position::new(line as u32, width as u32),
+                },
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("fusabi".to_string()),
+                message: problem.error.to_string(),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    let mut lexer = Lexer::new(text);
+    let tokens = match lexer.tokenize() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            let (line, col, msg) = match &e {
+                fusabi_frontend::LexError::UnexpectedChar(ch, pos) => (
+                    pos.line,
+                    pos.column,
+                    format!("Unexpected character: '{}'", ch),
+                ),
+                fusabi_frontend::LexError::UnterminatedString(pos) => (
+                    pos.line,
+                    pos.column,
+                    "Unterminated string literal".to_string(),
+                ),
+                fusabi_frontend::LexError::InvalidNumber(s, pos) => {
+                    (pos.line, pos.column, format!("Invalid number: '{}'", s))
+                }
+                fusabi_frontend::LexError::UnterminatedComment(pos) => {
+                    (pos.line, pos.column, "Unterminated comment".to_string())
+                }
+                fusabi_frontend::LexError::UnknownDirective(name, pos) => (
+                    pos.line,
+                    pos.column,
+                    format!("Unknown directive: '{}'", name),
+                ),
+                fusabi_frontend::LexError::UnmatchedDirective(name, pos) => (
+                    pos.line,
+                    pos.column,
+                    format!("'#{}' without a matching '#if'", name),
+                ),
+                fusabi_frontend::LexError::UnterminatedConditional(pos) => (
+                    pos.line,
+                    pos.column,
+                    "'#if' without a matching '#endif'".to_string(),
+                ),
+                fusabi_frontend::LexError::MalformedDirective(message, pos) => {
+                    (pos.line, pos.column, message.clone())
+                }
+            };
+            diagnostics.push(Diagnostic {
+                range: Range {
+                    start: Position {
+                        line: (line.saturating_sub(1)) as u32,
+                        character: (col.saturating_sub(1)) as u32,
+                    },
+                    end: Position {
+                        line: (line.saturating_sub(1)) as u32,
+                        character: col as u32,
+                    },
+                },
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("fusabi".to_string()),
+                message: msg,
+                ..Default::default()
+            });
+            return diagnostics;
+        }
+    };
+
+    // The parser recovers from errors, so every syntax error is reported
+    let (_, errors) = Parser::new(tokens).parse_program_recovering();
+    for e in &errors {
+        let (line, col, msg) = match e {
+            fusabi_frontend::ParseError::UnexpectedToken {
+                expected,
+                found,
+                pos,
+            } => (
+                pos.line,
+                pos.column,
+                format!("Expected {}, found {}", expected, found),
+            ),
+            fusabi_frontend::ParseError::UnexpectedEof { expected } => {
+                let lines: Vec<&str> = text.lines().collect();
+                let last_line = lines.len().max(1);
+                let last_col = lines.last().map(|l| l.len()).unwrap_or(0);
+                (
+                    last_line,
+                    last_col,
+                    format!("Unexpected end of file, expected {}", expected),
+                )
+            }
+            fusabi_frontend::ParseError::InvalidExpr { message, pos } => {
+                (pos.line, pos.column, message.clone())
+            }
+        };
+        diagnostics.push(Diagnostic {
+            range: Range {
+                start: Position {
+                    line: (line.saturating_sub(1)) as u32,
+                    character: (col.saturating_sub(1)) as u32,
+                },
+                end: Position {
+                    line: (line.saturating_sub(1)) as u32,
+                    character: col as u32,
+                },
+            },
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("fusabi".to_string()),
+            message: msg,
+            ..Default::default()
+        });
+    }
+    if errors.is_empty() {
+        diagnostics.extend(type_diagnostics(text, &loaded.programs, host_functions));
+    }
+
+    diagnostics
+}
+
+/// Diagnostics for type provider declarations that fail to resolve, e.g.
+/// because the schema file is missing or invalid.
+fn provider_diagnostics(
+    text: &str,
+    providers: &[ProviderSource],
+    resolver: &ProviderResolver,
+) -> Vec<Diagnostic> {
+    providers
+        .iter()
+        .filter_map(|provider| {
+            let error = resolver.resolve(&provider.decl).err()?;
+            let line = provider.line.saturating_sub(1);
+            let width = text
+                .split('\n')
+                .nth(line)
+                .map_or(0, |l| l.encode_utf16().count());
+            Some(Diagnostic {
+                range: Range {
+                    start: Position::new(line as u32, 0),
+                    end: Position::new(line as u32, width as u32),
+                },
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("fusabi".to_string()),
+                message: error.to_string(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Diagnostics from type checking a document that parses.
+///
+/// Inference is best-effort in the editor, so only unbound names are
+/// reported.
+fn type_diagnostics(
+    text: &str,
+    loaded: &[Program],
+    host_functions: &[HostFunction],
+) -> Vec<Diagnostic> {
+    let analysis = DocumentAnalysis::with_loaded(text, None, host_functions, loaded);
+    analysis
+        .unbound_references()
+        .into_iter()
+        .map(|(name, offset)| Diagnostic {
+            range: Range {
+                start: analysis::position_at(text, offset),
+                end: analysis::position_at(text, offset + name.len()),
+            },
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("fusabi".to_string()),
+            message: format!("Unbound variable: {}", name),
+            ..Default::default()
+        })
+        .collect()
 }
@@ -0,0 +1,253 @@
+//! Signatures of VM-provided functions.
+//!
+//! The VM registers its standard library as untyped host functions, so the
+//! signatures shown by the editor are kept here, keyed by qualified name.
+
+use fusabi_vm::{Value, Vm};
+
+/// Known signatures for standard library functions.
+const STDLIB_SIGNATURES: &[(&str, &str)] = &[
+    // Printing
+    ("print", "'a -> unit"),
+    ("printfn", "string -> unit"),
+    ("sprintf", "string -> 'a list -> string"),
+    // Option / Result constructors
+    ("Some", "'a -> 'a option"),
+    ("None", "'a option"),
+    ("Ok", "'a -> Result<'a, 'e>"),
+    ("Error", "'e -> Result<'a, 'e>"),
+    // List
+    ("List.length", "'a list -> int"),
+    ("List.head", "'a list -> 'a"),
+    ("List.tail", "'a list -> 'a list"),
+    ("List.reverse", "'a list -> 'a list"),
+    ("List.isEmpty", "'a list -> bool"),
+    ("List.append", "'a list -> 'a list -> 'a list"),
+    ("List.concat", "'a list list -> 'a list"),
+    ("List.map", "('a -> 'b) -> 'a list -> 'b list"),
+    ("List.mapi", "(int -> 'a -> 'b) -> 'a list -> 'b list"),
+    ("List.iter", "('a -> unit) -> 'a list -> unit"),
+    ("List.filter", "('a -> bool) -> 'a list -> 'a list"),
+    ("List.fold", "('s -> 'a -> 's) -> 's -> 'a list -> 's"),
+    ("List.exists", "('a -> bool) -> 'a list -> bool"),
+    ("List.find", "('a -> bool) -> 'a list -> 'a"),
+    ("List.tryFind", "('a -> bool) -> 'a list -> 'a option"),
+    ("List.nth", "'a list -> int -> 'a"),
+    // Array
+    ("Array.length", "'a[] -> int"),
+    ("Array.isEmpty", "'a[] -> bool"),
+    ("Array.get", "int -> 'a[] -> 'a"),
+    ("Array.set", "int -> 'a -> 'a[] -> 'a[]"),
+    ("Array.ofList", "'a list -> 'a[]"),
+    ("Array.toList", "'a[] -> 'a list"),
+    ("Array.init", "int -> (int -> 'a) -> 'a[]"),
+    ("Array.create", "int -> 'a -> 'a[]"),
+    // String
+    ("String.length", "string -> int"),
+    ("String.trim", "string -> string"),
+    ("String.toLower", "string -> string"),
+    ("String.toUpper", "string -> string"),
+    ("String.split", "string -> string -> string list"),
+    ("String.concat", "string list -> string"),
+    ("String.contains", "string -> string -> bool"),
+    ("String.startsWith", "string -> string -> bool"),
+    ("String.endsWith", "string -> string -> bool"),
+    ("String.format", "string -> 'a list -> string"),
+    // Map
+    ("Map.empty", "Map<'k, 'v>"),
+    ("Map.add", "'k -> 'v -> Map<'k, 'v> -> Map<'k, 'v>"),
+    ("Map.remove", "'k -> Map<'k, 'v> -> Map<'k, 'v>"),
+    ("Map.find", "'k -> Map<'k, 'v> -> 'v"),
+    ("Map.tryFind", "'k -> Map<'k, 'v> -> 'v option"),
+    ("Map.containsKey", "'k -> Map<'k, 'v> -> bool"),
+    ("Map.isEmpty", "Map<'k, 'v> -> bool"),
+    ("Map.count", "Map<'k, 'v> -> int"),
+    ("Map.ofList", "('k * 'v) list -> Map<'k, 'v>"),
+    ("Map.toList", "Map<'k, 'v> -> ('k * 'v) list"),
+    ("Map.map", "('v -> 'u) -> Map<'k, 'v> -> Map<'k, 'u>"),
+    ("Map.iter", "('k -> 'v -> unit) -> Map<'k, 'v> -> unit"),
+    // Option
+    ("Option.isSome", "'a option -> bool"),
+    ("Option.isNone", "'a option -> bool"),
+    ("Option.defaultValue", "'a -> 'a option -> 'a"),
+    ("Option.defaultWith", "(unit -> 'a) -> 'a option -> 'a"),
+    ("Option.map", "('a -> 'b) -> 'a option -> 'b option"),
+    ("Option.bind", "('a -> 'b option) -> 'a option -> 'b option"),
+    ("Option.iter", "('a -> unit) -> 'a option -> unit"),
+    ("Option.orElse", "'a option -> 'a option -> 'a option"),
+    // Result
+    ("Result.isOk", "Result<'a, 'e> -> bool"),
+    ("Result.isError", "Result<'a, 'e> -> bool"),
+    ("Result.defaultValue", "'a -> Result<'a, 'e> -> 'a"),
+    ("Result.defaultWith", "('e -> 'a) -> Result<'a, 'e> -> 'a"),
+    (
+        "Result.map",
+        "('a -> 'b) -> Result<'a, 'e> -> Result<'b, 'e>",
+    ),
+    (
+        "Result.mapError",
+        "('e -> 'f) -> Result<'a, 'e> -> Result<'a, 'f>",
+    ),
+    (
+        "Result.bind",
+        "('a -> Result<'b, 'e>) -> Result<'a, 'e> -> Result<'b, 'e>",
+    ),
+    ("Result.iter", "('a -> unit) -> Result<'a, 'e> -> unit"),
+    // Math
+    ("Math.pi", "unit -> float"),
+    ("Math.e", "unit -> float"),
+    ("Math.abs", "float -> float"),
+    ("Math.sqrt", "float -> float"),
+    ("Math.pow", "float -> float -> float"),
+    ("Math.max", "float -> float -> float"),
+    ("Math.min", "float -> float -> float"),
+    ("Math.floor", "float -> float"),
+    ("Math.ceil", "float -> float"),
+    ("Math.round", "float -> float"),
+    ("Math.truncate", "float -> float"),
+    // Regex
+    ("Regex.isMatch", "string -> string -> bool"),
+    ("Regex.find", "string -> string -> string option"),
+    ("Regex.findAll", "string -> string -> string list"),
+    ("Regex.replace", "string -> string -> string -> string"),
+    ("Regex.split", "string -> string -> string list"),
+    ("Regex.captures", "string -> string -> string list option"),
+    ("Regex.namedCaptures", "string -> string -> record option"),
+    // Json
+    ("Json.parse", "string -> 'a"),
+    ("Json.stringify", "'a -> string"),
+    ("Json.stringifyPretty", "'a -> string"),
+    ("Json.parseAs", "string -> string -> Result<'a, string>"),
+    ("Json.serializeAs", "string -> 'a -> string"),
+    // Toml
+    ("Toml.parse", "string -> 'a"),
+    ("Toml.stringify", "'a -> string"),
+    ("Toml.parseAs", "string -> string -> Result<'a, string>"),
+    ("Toml.serializeAs", "string -> 'a -> string"),
+    // Process
+    ("Process.run", "string -> string list -> ProcessResult"),
+    ("Process.runShell", "string -> ProcessResult"),
+    ("Process.env", "string -> string option"),
+    ("Process.setEnv", "string -> string -> unit"),
+    ("Process.args", "unit -> string list"),
+    ("Process.cwd", "unit -> string"),
+    // Mcp
+    ("Mcp.connect", "string -> string list -> int"),
+    ("Mcp.listTools", "int -> ToolInfo list"),
+    ("Mcp.callTool", "int -> string -> 'a -> ToolResult"),
+    ("Mcp.readResource", "int -> string -> ResourceContents list"),
+    ("Mcp.close", "int -> unit"),
+    // Test
+    ("Test.equal", "'a -> 'a -> unit"),
+    ("Test.notEqual", "'a -> 'a -> unit"),
+    ("Test.isTrue", "bool -> unit"),
+    ("Test.fail", "string -> 'a"),
+    ("Test.throws", "(unit -> 'a) -> string"),
+    ("Test.forAll", "'a gen -> ('a -> bool) -> unit"),
+    // Gen
+    ("Gen.int", "int gen"),
+    ("Gen.float", "float gen"),
+    ("Gen.bool", "bool gen"),
+    ("Gen.string", "string gen"),
+    ("Gen.range", "int -> int -> int gen"),
+    ("Gen.list", "'a gen -> 'a list gen"),
+    ("Gen.oneOf", "'a list -> 'a gen"),
+    ("Gen.pair", "'a gen -> 'b gen -> ('a * 'b) gen"),
+    ("Gen.map", "('a -> 'b) -> 'a gen -> 'b gen"),
+    // Events
+    ("Events.on", "string -> ('a -> unit) -> int"),
+    ("Events.off", "string -> int -> bool"),
+    ("Events.emit", "string -> 'a -> unit"),
+    ("Events.once", "string -> ('a -> unit) -> int"),
+    // Files and console
+    ("File.readLines", "string -> string list"),
+    ("File.writeLines", "string -> string list -> unit"),
+    ("File.appendLine", "string -> string -> unit"),
+    ("Console.readLine", "unit -> string"),
+    ("Console.write", "string -> unit"),
+    ("Console.writeLine", "string -> unit"),
+    // Time
+    ("Time.now", "unit -> int"),
+    ("Time.nowSeconds", "unit -> float"),
+    ("Time.format", "string -> int -> string"),
+    ("Time.parse", "string -> string -> int option"),
+];
+
+/// A function provided by the VM or the embedding host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostFunction {
+    /// Qualified name as registered (e.g. "List.map", "printfn")
+    pub name: String,
+    /// Signature, when known
+    pub signature: Option<String>,
+}
+
+impl HostFunction {
+    /// Create a host function entry, looking up its stdlib signature.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        let signature = lookup_signature(&name).map(str::to_string);
+        HostFunction { name, signature }
+    }
+
+    /// Module part of the name (e.g. "List" for "List.map"), if qualified.
+    pub fn module(&self) -> Option<&str> {
+        self.name.rsplit_once('.').map(|(module, _)| module)
+    }
+
+    /// Unqualified member name (e.g. "map" for "List.map").
+    pub fn member(&self) -> &str {
+        self.name
+            .rsplit_once('.')
+            .map(|(_, member)| member)
+            .unwrap_or(&self.name)
+    }
+}
+
+/// Look up the signature of a standard library function.
+pub fn lookup_signature(name: &str) -> Option<&'static str> {
+    STDLIB_SIGNATURES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, sig)| *sig)
+}
+
+/// Collect every function the standard library registers in a fresh VM,
+/// along with the values its modules hold (e.g. "Gen.int").
+pub fn stdlib_functions() -> Vec<HostFunction> {
+    let mut vm = Vm::new();
+    fusabi_vm::stdlib::register_stdlib(&mut vm);
+
+    let mut names = vm.host_registry.lock().unwrap().function_names();
+    for (module, value) in vm.globals.iter() {
+        if let Value::Record(fields) = value {
+            let fields = fields.lock().unwrap();
+            names.extend(fields.keys().map(|field| format!("{}.{}", module, field)));
+        }
+    }
+    names.sort();
+    names.dedup();
+    names.into_iter().map(HostFunction::new).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stdlib_functions_include_module_values() {
+        let functions = stdlib_functions();
+        let int = functions.iter().find(|f| f.name == "Gen.int").unwrap();
+        assert_eq!(int.signature.as_deref(), Some("int gen"));
+    }
+
+    #[test]
+    fn test_stdlib_modules_have_signatures() {
+        let modules = ["Regex", "Json", "Toml", "Process", "Mcp", "Test", "Gen"];
+        for function in stdlib_functions() {
+            if function.module().is_some_and(|m| modules.contains(&m)) {
+                assert!(function.signature.is_some(), "{}", function.name);
+            }
+        }
+    }
+}
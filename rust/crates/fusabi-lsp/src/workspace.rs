@@ -0,0 +1,636 @@
+//! Workspace model: open documents, packages and `#load` graphs.
+//!
+//! Open documents are kept up to date from incremental edits and handed to a
+//! shared `FileLoader` as in-memory sources, so a file that loads an unsaved
+//! buffer sees the buffer rather than the disk. The loader caches every
+//! loaded file; after an edit or a change on disk only the changed file is
+//! invalidated, and the open documents that load it are reported so that they
+//! can be checked again.
+//!
+//! A directory with a `fusabi.toml` is a package. Packages are discovered by
+//! walking up from an open document (and from the workspace roots), and are
+//! reloaded when their manifest changes.
+//!
+//! Schema files named by type provider declarations are tracked the same
+//! way as loaded files, so editing a schema re-checks the scripts using it.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use fusabi_frontend::ast::TypeProviderDecl;
+use fusabi_frontend::provider_resolver::source_file;
+use fusabi_frontend::{FileLoader, Lexer, LoadError, Program, Token};
+use tower_lsp::lsp_types::{TextDocumentContentChangeEvent, Url};
+
+use crate::analysis;
+
+/// Name of the package manifest.
+pub const MANIFEST_FILE: &str = "fusabi.toml";
+
+/// An open text document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub text: String,
+    /// Version reported by the client
+    pub version: i32,
+}
+
+impl Document {
+    pub fn new(text: String, version: i32) -> Self {
+        Document { text, version }
+    }
+
+    /// Apply one content change: a range edit, or the full text if the change
+    /// has no range.
+    pub fn apply_change(&mut self, change: &TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => {
+                let start =
+                    analysis::offset_at(&self.text, range.start.line, range.start.character);
+                let end = analysis::offset_at(&self.text, range.end.line, range.end.character);
+                self.text
+                    .replace_range(start.min(end)..end.max(start), &change.text);
+            }
+            None => self.text = change.text.clone(),
+        }
+    }
+}
+
+/// A package described by a `fusabi.toml` manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Package {
+    pub name: String,
+    pub version: Option<String>,
+    /// Directory containing the manifest
+    pub root: PathBuf,
+    /// Entry point (`src/main.fsx`)
+    pub entry: PathBuf,
+    /// Names of the declared dependencies
+    pub dependencies: Vec<String>,
+}
+
+impl Package {
+    /// Read the manifest in `root`, if there is a valid one.
+    pub fn load(root: &Path) -> Option<Package> {
+        let source = std::fs::read_to_string(root.join(MANIFEST_FILE)).ok()?;
+        Self::parse(&source, root)
+    }
+
+    /// Parse manifest `source` for a package rooted at `root`.
+    pub fn parse(source: &str, root: &Path) -> Option<Package> {
+        let manifest: toml::Table = source.parse().ok()?;
+        let package = manifest.get("package")?.as_table()?;
+        let name = package.get("name")?.as_str()?.to_string();
+        let version = package
+            .get("version")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+
+        let mut dependencies: Vec<String> = manifest
+            .get("dependencies")
+            .and_then(|d| d.as_table())
+            .map(|d| d.keys().cloned().collect())
+            .unwrap_or_default();
+        dependencies.sort();
+
+        Some(Package {
+            name,
+            version,
+            root: root.to_path_buf(),
+            entry: root.join("src").join("main.fsx"),
+            dependencies,
+        })
+    }
+
+    /// Whether `path` lies inside the package.
+    pub fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+}
+
+/// A `#load` directive that could not be loaded.
+#[derive(Debug, Clone)]
+pub struct LoadProblem {
+    /// 1-based line of the directive
+    pub line: usize,
+    pub error: LoadError,
+}
+
+/// Files pulled into a document by its `#load` directives.
+#[derive(Debug, Clone, Default)]
+pub struct LoadedFiles {
+    /// Programs of every loaded file, dependencies first
+    pub programs: Vec<Program>,
+    pub problems: Vec<LoadProblem>,
+}
+
+/// The documents and packages the server knows about.
+pub struct Workspace {
+    documents: HashMap<Url, Document>,
+    packages: Vec<Package>,
+    loader: FileLoader,
+    /// Files each open document loads, directly or through other files
+    loads: HashMap<Url, Vec<PathBuf>>,
+    /// Schema files named by each open document's type providers
+    schemas: HashMap<Url, Vec<PathBuf>>,
+}
+
+impl Default for Workspace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Workspace {
+            documents: HashMap::new(),
+            packages: Vec::new(),
+            loader: FileLoader::new(PathBuf::from(".")),
+            loads: HashMap::new(),
+            schemas: HashMap::new(),
+        }
+    }
+
+    /// Register a workspace folder, picking up the package it contains.
+    pub fn add_root(&mut self, root: &Path) {
+        self.discover_package(root);
+    }
+
+    pub fn packages(&self) -> &[Package] {
+        &self.packages
+    }
+
+    /// The innermost package containing `path`.
+    pub fn package_for(&self, path: &Path) -> Option<&Package> {
+        self.packages
+            .iter()
+            .filter(|p| p.contains(path))
+            .max_by_key(|p| p.root.components().count())
+    }
+
+    pub fn document(&self, uri: &Url) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    pub fn open(&mut self, uri: Url, text: String, version: i32) {
+        if let Some(path) = file_path(&uri) {
+            if let Some(dir) = path.parent() {
+                self.discover_package(dir);
+            }
+            self.loader.set_source(path, text.clone());
+        }
+        self.documents.insert(uri, Document::new(text, version));
+    }
+
+    /// Apply edits to an open document. Returns false for an unknown document.
+    pub fn change(
+        &mut self,
+        uri: &Url,
+        version: i32,
+        changes: &[TextDocumentContentChangeEvent],
+    ) -> bool {
+        let Some(document) = self.documents.get_mut(uri) else {
+            return false;
+        };
+        for change in changes {
+            document.apply_change(change);
+        }
+        document.version = version;
+
+        if let Some(path) = file_path(uri) {
+            self.loader.set_source(path, document.text.clone());
+        }
+        true
+    }
+
+    pub fn close(&mut self, uri: &Url) {
+        self.documents.remove(uri);
+        self.loads.remove(uri);
+        self.schemas.remove(uri);
+        if let Some(path) = file_path(uri) {
+            self.loader.remove_source(&path);
+        }
+    }
+
+    /// Load the files `uri` pulls in with `#load`.
+    ///
+    /// Cached files are reused; the set of loaded files is remembered so that
+    /// the document can be found by [`Workspace::dependents`] later.
+    pub fn load_dependencies(&mut self, uri: &Url) -> LoadedFiles {
+        let mut loaded = LoadedFiles::default();
+        let (Some(document), Some(path)) = (self.documents.get(uri), file_path(uri)) else {
+            return loaded;
+        };
+
+        let directives = load_directives(&document.text);
+        let mut visited: Vec<PathBuf> = Vec::new();
+        for (target, line) in directives {
+            match self.loader.load(&target, &path) {
+                Ok(file) => {
+                    let file_path = file.path.clone();
+                    self.collect(&file_path, &mut visited, &mut loaded);
+                }
+                Err(error) => loaded.problems.push(LoadProblem { line, error }),
+            }
+        }
+
+        visited.retain(|p| *p != path);
+        self.loads.insert(uri.clone(), visited);
+        loaded
+    }
+
+    /// Add `path` and everything it loads to `loaded`, dependencies first.
+    fn collect(&mut self, path: &Path, visited: &mut Vec<PathBuf>, loaded: &mut LoadedFiles) {
+        if visited.iter().any(|p| p == path) {
+            return;
+        }
+        visited.push(path.to_path_buf());
+
+        // An edit may have evicted the file since its dependent was cached
+        let file = match self.loader.load(&path.to_string_lossy(), path) {
+            Ok(file) => file.clone(),
+            Err(_) => return,
+        };
+        for dependency in &file.dependencies {
+            self.collect(dependency, visited, loaded);
+        }
+        loaded.programs.push(file.program);
+    }
+
+    /// The type provider declarations of `uri`.
+    ///
+    /// Sources naming a file next to the document are made absolute, and the
+    /// files are remembered so that [`Workspace::file_changed`] reports the
+    /// document when one of them changes.
+    pub fn provider_sources(&mut self, uri: &Url) -> Vec<ProviderSource> {
+        let Some(document) = self.documents.get(uri) else {
+            return Vec::new();
+        };
+        let dir = file_path(uri).and_then(|p| p.parent().map(Path::to_path_buf));
+
+        let mut sources = provider_declarations(&document.text);
+        for source in &mut sources {
+            let Some(dir) = &dir else { continue };
+            if let Some(path) = source_file(dir, &source.decl.source) {
+                source.decl.source = path.to_string_lossy().into_owned();
+                source.file = Some(path);
+            }
+        }
+
+        let files = sources.iter().filter_map(|s| s.file.clone()).collect();
+        self.schemas.insert(uri.clone(), files);
+        sources
+    }
+
+    /// Open documents that load `uri`, directly or through other files.
+    pub fn dependents(&self, uri: &Url) -> Vec<Url> {
+        match file_path(uri) {
+            Some(path) => self.documents_loading(&path),
+            None => Vec::new(),
+        }
+    }
+
+    /// Record that `path` changed on disk. Returns the open documents whose
+    /// diagnostics may have changed as a result.
+    pub fn file_changed(&mut self, path: &Path) -> Vec<Url> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        if path.file_name().is_some_and(|name| name == MANIFEST_FILE) {
+            let root = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+            self.packages.retain(|p| p.root != root);
+            self.discover_package(&root);
+            self.loader.clear_cache();
+            let mut affected: Vec<Url> = self
+                .documents
+                .keys()
+                .filter(|uri| file_path(uri).is_some_and(|p| p.starts_with(&root)))
+                .cloned()
+                .collect();
+            affected.sort();
+            return affected;
+        }
+
+        self.loader.invalidate(&path);
+        let mut affected = self.documents_loading(&path);
+        affected.extend(
+            self.schemas
+                .iter()
+                .filter(|(_, files)| files.contains(&path))
+                .map(|(uri, _)| uri.clone()),
+        );
+        affected.sort();
+        affected.dedup();
+        affected
+    }
+
+    fn documents_loading(&self, path: &Path) -> Vec<Url> {
+        let mut uris: Vec<Url> = self
+            .loads
+            .iter()
+            .filter(|(_, files)| files.iter().any(|f| f == path))
+            .map(|(uri, _)| uri.clone())
+            .collect();
+        uris.sort();
+        uris
+    }
+
+    /// Find the package `dir` belongs to by walking up to the nearest manifest.
+    fn discover_package(&mut self, dir: &Path) {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        for ancestor in dir.ancestors() {
+            if self.packages.iter().any(|p| p.root == ancestor) {
+                return;
+            }
+            if ancestor.join(MANIFEST_FILE).is_file() {
+                if let Some(package) = Package::load(ancestor) {
+                    self.packages.push(package);
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Canonical file system path of a `file://` URI.
+fn file_path(uri: &Url) -> Option<PathBuf> {
+    let path = uri.to_file_path().ok()?;
+    Some(path.canonicalize().unwrap_or(path))
+}
+
+/// A type provider declaration found in a document.
+#[derive(Debug, Clone)]
+pub struct ProviderSource {
+    pub decl: TypeProviderDecl,
+    /// 1-based line of the declaration
+    pub line: usize,
+    /// The schema file the source names, if it names one
+    pub file: Option<PathBuf>,
+}
+
+/// The `type Alias = Provider<"source">` declarations of `text`.
+fn provider_declarations(text: &str) -> Vec<ProviderSource> {
+    let Ok(tokens) = Lexer::new(text).tokenize() else {
+        return Vec::new();
+    };
+    tokens
+        .windows(7)
+        .filter_map(|window| {
+            let [ty, name, eq, provider, lt, source, gt] = window else {
+                return None;
+            };
+            match (
+                &ty.token,
+                &name.token,
+                &eq.token,
+                &provider.token,
+                &lt.token,
+                &source.token,
+                &gt.token,
+            ) {
+                (
+                    Token::Type,
+                    Token::Ident(name),
+                    Token::Eq,
+                    Token::Ident(provider),
+                    Token::Lt,
+                    Token::String(source),
+                    Token::Gt,
+                ) => Some(ProviderSource {
+                    decl: TypeProviderDecl::new(name.clone(), provider.clone(), source.clone()),
+                    line: ty.pos.line,
+                    file: None,
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// The `#load` targets of `text`, with the 1-based line of each directive.
+fn load_directives(text: &str) -> Vec<(String, usize)> {
+    let Ok(tokens) = Lexer::new(text).tokenize() else {
+        return Vec::new();
+    };
+    tokens
+        .into_iter()
+        .filter_map(|t| match t.token {
+            Token::LoadDirective(path) => Some((path, t.pos.line)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+    use tower_lsp::lsp_types::{Position, Range};
+
+    fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: Position::new(start.0, start.1),
+                end: Position::new(end.0, end.1),
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    fn uri(path: &Path) -> Url {
+        Url::from_file_path(path.canonicalize().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_apply_incremental_changes() {
+        let mut doc = Document::new("let x = 1\nlet y = x\n".to_string(), 1);
+        doc.apply_change(&edit((0, 8), (0, 9), "42"));
+        doc.apply_change(&edit((1, 4), (1, 5), "total"));
+        doc.apply_change(&edit((2, 0), (2, 0), "let z = 3\n"));
+        assert_eq!(doc.text, "let x = 42\nlet total = x\nlet z = 3\n");
+
+        // Deleting across lines
+        doc.apply_change(&edit((0, 10), (1, 14), ""));
+        assert_eq!(doc.text, "let x = 42\nlet z = 3\n");
+    }
+
+    #[test]
+    fn test_apply_change_counts_utf16_units() {
+        let mut doc = Document::new("let s = \"😀\" ++ t".to_string(), 1);
+        // The emoji is two UTF-16 code units
+        doc.apply_change(&edit((0, 16), (0, 17), "u"));
+        assert_eq!(doc.text, "let s = \"😀\" ++ u");
+    }
+
+    #[test]
+    fn test_full_change_replaces_text() {
+        let mut doc = Document::new("let x = 1".to_string(), 1);
+        doc.apply_change(&TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "let y = 2".to_string(),
+        });
+        assert_eq!(doc.text, "let y = 2");
+    }
+
+    #[test]
+    fn test_parse_package_manifest() {
+        let manifest = r#"
+[package]
+name = "config"
+version = "0.2.0"
+
+[dependencies]
+utils = "1.0"
+http = { path = "../http" }
+"#;
+        let package = Package::parse(manifest, Path::new("/work/config")).unwrap();
+        assert_eq!(package.name, "config");
+        assert_eq!(package.version.as_deref(), Some("0.2.0"));
+        assert_eq!(package.entry, Path::new("/work/config/src/main.fsx"));
+        assert_eq!(package.dependencies, vec!["http", "utils"]);
+
+        assert!(Package::parse("[dependencies]\nx = \"1\"", Path::new("/")).is_none());
+    }
+
+    #[test]
+    fn test_open_discovers_package() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(MANIFEST_FILE), "[package]\nname = \"demo\"").unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        let main = dir.path().join("src").join("main.fsx");
+        fs::write(&main, "let x = 1").unwrap();
+
+        let mut workspace = Workspace::new();
+        workspace.open(uri(&main), "let x = 1".to_string(), 1);
+
+        let package = workspace
+            .package_for(&main.canonicalize().unwrap())
+            .unwrap();
+        assert_eq!(package.name, "demo");
+        assert_eq!(workspace.packages().len(), 1);
+    }
+
+    #[test]
+    fn test_load_dependencies_uses_open_buffers() {
+        let dir = TempDir::new().unwrap();
+        let utils = dir.path().join("utils.fsx");
+        let main = dir.path().join("main.fsx");
+        fs::write(&utils, "let helper x = x + 1").unwrap();
+        fs::write(&main, "").unwrap();
+
+        let mut workspace = Workspace::new();
+        let main_uri = uri(&main);
+        workspace.open(
+            main_uri.clone(),
+            "#load \"utils.fsx\"\nlet y = helper 1".to_string(),
+            1,
+        );
+
+        let loaded = workspace.load_dependencies(&main_uri);
+        assert!(loaded.problems.is_empty());
+        assert_eq!(loaded.programs.len(), 1);
+        assert_eq!(loaded.programs[0].items.len(), 1);
+
+        // Editing the loaded file (unsaved) is seen by the document loading it
+        let utils_uri = uri(&utils);
+        workspace.open(utils_uri.clone(), "let helper x = x + 1".to_string(), 1);
+        workspace.change(&utils_uri, 2, &[edit((0, 20), (0, 20), "\nlet other = 2")]);
+        assert_eq!(workspace.dependents(&utils_uri), vec![main_uri.clone()]);
+
+        let loaded = workspace.load_dependencies(&main_uri);
+        assert_eq!(loaded.programs[0].items.len(), 2);
+    }
+
+    #[test]
+    fn test_transitive_dependents_of_changed_file() {
+        let dir = TempDir::new().unwrap();
+        let utils = dir.path().join("utils.fsx");
+        fs::write(&utils, "module Utils =\n    let square x = x * x").unwrap();
+        fs::write(
+            dir.path().join("math.fsx"),
+            "#load \"utils.fsx\"\nlet two = 2",
+        )
+        .unwrap();
+        let main = dir.path().join("main.fsx");
+        fs::write(&main, "").unwrap();
+
+        let mut workspace = Workspace::new();
+        let main_uri = uri(&main);
+        workspace.open(
+            main_uri.clone(),
+            "#load \"math.fsx\"\nlet r = two".to_string(),
+            1,
+        );
+
+        let loaded = workspace.load_dependencies(&main_uri);
+        // utils.fsx comes before math.fsx, which loads it
+        assert_eq!(loaded.programs.len(), 2);
+        assert_eq!(loaded.programs[0].modules[0].name, "Utils");
+
+        fs::write(&utils, "module Utils =\n    let cube x = x * x * x").unwrap();
+        assert_eq!(workspace.file_changed(&utils), vec![main_uri.clone()]);
+        let loaded = workspace.load_dependencies(&main_uri);
+        assert_eq!(loaded.programs.len(), 2);
+
+        workspace.close(&main_uri);
+        assert!(workspace.file_changed(&utils).is_empty());
+    }
+
+    #[test]
+    fn test_schema_files_have_dependents() {
+        let dir = TempDir::new().unwrap();
+        let schema = dir.path().join("config.toml");
+        fs::write(&schema, "name = \"app\"").unwrap();
+        let main = dir.path().join("main.fsx");
+        fs::write(&main, "").unwrap();
+
+        let mut workspace = Workspace::new();
+        let main_uri = uri(&main);
+        let text = "let x = 1\ntype Config = TomlProvider<\"config.toml\">\n\
+                    type Log = RegexProvider<\"(?P<n>\\d+)\">";
+        workspace.open(main_uri.clone(), text.to_string(), 1);
+
+        let sources = workspace.provider_sources(&main_uri);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].line, 2);
+        let schema = schema.canonicalize().unwrap();
+        assert_eq!(sources[0].file.as_deref(), Some(schema.as_path()));
+        assert_eq!(sources[0].decl.source, schema.to_string_lossy());
+        // Inline sources are left alone
+        assert_eq!(sources[1].file, None);
+        assert_eq!(sources[1].decl.source, r"(?P<n>\d+)");
+
+        assert_eq!(workspace.file_changed(&schema), vec![main_uri.clone()]);
+        workspace.close(&main_uri);
+        assert!(workspace.file_changed(&schema).is_empty());
+    }
+
+    #[test]
+    fn test_load_problems_are_reported() {
+        let dir = TempDir::new().unwrap();
+        let main = dir.path().join("main.fsx");
+        fs::write(&main, "").unwrap();
+        fs::write(dir.path().join("broken.fsx"), "let = 1").unwrap();
+
+        let mut workspace = Workspace::new();
+        let main_uri = uri(&main);
+        let text = "#load \"missing.fsx\"\n#load \"broken.fsx\"\nlet x = 1";
+        workspace.open(main_uri.clone(), text.to_string(), 1);
+
+        let loaded = workspace.load_dependencies(&main_uri);
+        assert_eq!(loaded.problems.len(), 2);
+        assert_eq!(loaded.problems[0].line, 1);
+        assert!(matches!(
+            loaded.problems[0].error,
+            LoadError::FileNotFound(_)
+        ));
+        assert_eq!(loaded.problems[1].line, 2);
+        assert!(matches!(
+            loaded.problems[1].error,
+            LoadError::ParseError(..)
+        ));
+    }
+}
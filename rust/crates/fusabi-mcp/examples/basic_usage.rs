@@ -14,7 +14,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Start the MCP server process
     let mut child = Command::new("cargo")
-        .args(&["run", "--bin", "fusabi-mcp"])
+        .args(["run", "--bin", "fusabi-mcp"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -25,19 +25,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut reader = BufReader::new(stdout);
 
     // Helper function to send request and receive response
-    let mut send_request = |request: serde_json::Value| -> Result<String, Box<dyn std::error::Error>> {
-        let request_str = serde_json::to_string(&request)?;
-        eprintln!("→ Sending: {}", request_str);
+    let mut send_request =
+        |request: serde_json::Value| -> Result<String, Box<dyn std::error::Error>> {
+            let request_str = serde_json::to_string(&request)?;
+            eprintln!("→ Sending: {}", request_str);
 
-        writeln!(stdin, "{}", request_str)?;
-        stdin.flush()?;
+            writeln!(stdin, "{}", request_str)?;
+            stdin.flush()?;
 
-        let mut response = String::new();
-        reader.read_line(&mut response)?;
-        eprintln!("← Received: {}\n", response.trim());
+            let mut response = String::new();
+            reader.read_line(&mut response)?;
+            eprintln!("← Received: {}\n", response.trim());
 
-        Ok(response)
-    };
+            Ok(response)
+        };
 
     // 1. Initialize the server
     println!("1. Initializing server...");
@@ -59,7 +60,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
     let tools_response = send_request(list_request)?;
     let tools: serde_json::Value = serde_json::from_str(&tools_response)?;
-    println!("   Available tools: {}\n",
+    println!(
+        "   Available tools: {}\n",
         tools["result"]["tools"]
             .as_array()
             .map(|arr| arr.len())
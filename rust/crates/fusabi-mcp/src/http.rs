@@ -0,0 +1,501 @@
+//! Streamable HTTP transport
+//!
+//! Serves MCP at a single endpoint, [`ENDPOINT`]:
+//!
+//! - `POST` a JSON-RPC message or batch. Requests are answered with JSON (a
+//!   batch with an array); a body of only notifications gets `202 Accepted`.
+//! - `GET` with `Accept: text/event-stream` opens a Server-Sent Events stream
+//!   carrying the session's notifications, such as resource updates.
+//! - `DELETE` ends the session.
+//!
+//! An `initialize` request starts a session: the response carries an
+//! `Mcp-Session-Id` header, which every later request must send back. Each
+//! session has its own [`McpServer`], so clients never share eval sessions,
+//! subscriptions or script state, and sessions are served concurrently.
+//!
+//! Sessions are bounded by [`HttpLimits`]: a session without requests for
+//! the idle timeout is ended as if deleted, and `initialize` is refused with
+//! `503 Service Unavailable` while the maximum number of sessions are open.
+//!
+//! `notifications/cancelled` is handled as soon as it is posted, even while
+//! the cancelled request still holds its session.
+//!
+//! Requests with an `Origin` header are only accepted from localhost, to
+//! guard local servers against DNS rebinding.
+
+use crate::{cancel, cancelled_request, JsonRpcError, JsonRpcRequest, JsonRpcResponse, McpServer};
+use anyhow::{Context, Result};
+use hyper::body::Bytes;
+use hyper::header::{HeaderValue, ACCEPT, CONTENT_TYPE, ORIGIN};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::json;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+/// Path of the MCP endpoint
+pub const ENDPOINT: &str = "/mcp";
+
+/// Header carrying the session id
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Sessions open at once, by default
+pub const MAX_SESSIONS: usize = 64;
+
+/// Time without requests after which a session ends, by default (30 minutes)
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Bounds on the sessions a server keeps, each of which has its own
+/// [`McpServer`] and worker threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpLimits {
+    /// Sessions open at once
+    pub max_sessions: usize,
+    /// Time without requests after which a session ends
+    pub idle_timeout: Duration,
+}
+
+impl Default for HttpLimits {
+    fn default() -> Self {
+        HttpLimits {
+            max_sessions: MAX_SESSIONS,
+            idle_timeout: SESSION_IDLE_TIMEOUT,
+        }
+    }
+}
+
+/// Creates the server for each new session
+type Factory = dyn Fn() -> Result<McpServer> + Send + Sync;
+
+/// One client's session
+struct ClientSession {
+    server: tokio::sync::Mutex<McpServer>,
+    /// The server's in-flight evaluations, reachable without its lock
+    in_flight: Arc<Mutex<HashMap<String, fusabi::Interrupt>>>,
+    /// Open SSE streams
+    streams: Mutex<Vec<mpsc::UnboundedSender<String>>>,
+    /// When a request last used the session
+    last_used: Mutex<Instant>,
+}
+
+impl ClientSession {
+    fn new(server: McpServer) -> Self {
+        ClientSession {
+            in_flight: server.in_flight.clone(),
+            server: tokio::sync::Mutex::new(server),
+            streams: Mutex::new(Vec::new()),
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn touch(&self) {
+        *self.last_used.lock().unwrap() = Instant::now();
+    }
+
+    /// Whether the session has gone `timeout` without requests, and is not
+    /// handling one
+    fn is_idle(&self, timeout: Duration) -> bool {
+        self.last_used.lock().unwrap().elapsed() >= timeout && self.server.try_lock().is_ok()
+    }
+
+    /// Interrupt the session's evaluations and close its streams
+    fn close(&self) {
+        for interrupt in self.in_flight.lock().unwrap().values() {
+            interrupt.interrupt();
+        }
+        self.streams.lock().unwrap().clear();
+    }
+
+    /// Send a message to every open SSE stream
+    fn broadcast(&self, message: &str) {
+        let mut streams = self.streams.lock().unwrap();
+        streams.retain(|stream| stream.send(message.to_string()).is_ok());
+    }
+}
+
+/// Sessions by id
+struct Transport {
+    factory: Box<Factory>,
+    limits: HttpLimits,
+    sessions: Mutex<HashMap<String, Arc<ClientSession>>>,
+}
+
+/// Serve MCP over HTTP on `listener`, with a server from `factory` per session
+/// and the default [`HttpLimits`]
+///
+/// Runs until accepting a connection fails.
+pub async fn serve(
+    listener: TcpListener,
+    factory: impl Fn() -> Result<McpServer> + Send + Sync + 'static,
+) -> Result<()> {
+    serve_with_limits(listener, HttpLimits::default(), factory).await
+}
+
+/// Serve MCP over HTTP on `listener`, with a server from `factory` per session,
+/// keeping sessions within `limits`
+///
+/// Runs until accepting a connection fails.
+pub async fn serve_with_limits(
+    listener: TcpListener,
+    limits: HttpLimits,
+    factory: impl Fn() -> Result<McpServer> + Send + Sync + 'static,
+) -> Result<()> {
+    let transport = Arc::new(Transport {
+        factory: Box::new(factory),
+        limits,
+        sessions: Mutex::new(HashMap::new()),
+    });
+
+    // Idle sessions are ended within a minute of timing out
+    let expiry = {
+        let transport = transport.clone();
+        let period = limits
+            .idle_timeout
+            .clamp(Duration::from_millis(10), Duration::from_secs(60));
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(period);
+            loop {
+                ticks.tick().await;
+                transport.end_idle_sessions();
+            }
+        })
+    };
+    let result = accept(listener, transport).await;
+    expiry.abort();
+    result
+}
+
+/// Serve the connections `listener` accepts until accepting one fails
+async fn accept(listener: TcpListener, transport: Arc<Transport>) -> Result<()> {
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .context("Failed to accept connection")?;
+        let transport = transport.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let transport = transport.clone();
+                async move { Ok::<_, Infallible>(transport.handle(request).await) }
+            });
+            if let Err(e) = Http::new().serve_connection(stream, service).await {
+                eprintln!("HTTP connection error: {}", e);
+            }
+        });
+    }
+}
+
+impl Transport {
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        if request.uri().path() != ENDPOINT {
+            return status(StatusCode::NOT_FOUND);
+        }
+        if !origin_allowed(request.headers().get(ORIGIN)) {
+            return status(StatusCode::FORBIDDEN);
+        }
+
+        match *request.method() {
+            Method::POST => self.post(request).await,
+            Method::GET => self.stream(&request),
+            Method::DELETE => self.delete(&request),
+            _ => status(StatusCode::METHOD_NOT_ALLOWED),
+        }
+    }
+
+    /// The session named by the request's session header, or the error response
+    fn session(&self, request: &Request<Body>) -> Result<Arc<ClientSession>, StatusCode> {
+        let id = request
+            .headers()
+            .get(SESSION_HEADER)
+            .ok_or(StatusCode::BAD_REQUEST)?;
+        let id = id.to_str().unwrap_or_default();
+        let session = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or(StatusCode::NOT_FOUND)?;
+        session.touch();
+        Ok(session)
+    }
+
+    /// End the sessions that have gone the idle timeout without requests
+    fn end_idle_sessions(&self) {
+        let mut ended = Vec::new();
+        self.sessions.lock().unwrap().retain(|_, session| {
+            let idle = session.is_idle(self.limits.idle_timeout);
+            if idle {
+                ended.push(session.clone());
+            }
+            !idle
+        });
+        for session in ended {
+            session.close();
+        }
+    }
+
+    async fn post(&self, request: Request<Body>) -> Response<Body> {
+        let (parts, body) = request.into_parts();
+        let request = Request::from_parts(parts, Body::empty());
+        let message: serde_json::Value = match hyper::body::to_bytes(body)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|body| serde_json::from_slice(&body).map_err(|e| e.to_string()))
+        {
+            Ok(message) => message,
+            Err(e) => {
+                return rpc_error(
+                    StatusCode::BAD_REQUEST,
+                    JsonRpcError::parse_error(format!("Invalid JSON: {}", e)),
+                )
+            }
+        };
+
+        let (batch, messages) = match message {
+            serde_json::Value::Array(messages) => (true, messages),
+            message => (false, vec![message]),
+        };
+        if messages.is_empty() {
+            return rpc_error(
+                StatusCode::BAD_REQUEST,
+                JsonRpcError::invalid_request("Empty batch"),
+            );
+        }
+
+        let initialize = messages
+            .iter()
+            .any(|message| message.get("method") == Some(&json!("initialize")));
+        let (new_session, session) = if initialize {
+            if messages.len() > 1 {
+                return rpc_error(
+                    StatusCode::BAD_REQUEST,
+                    JsonRpcError::invalid_request("initialize must not be batched"),
+                );
+            }
+            self.end_idle_sessions();
+            if self.sessions.lock().unwrap().len() >= self.limits.max_sessions {
+                return rpc_error(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    JsonRpcError::internal_error(format!(
+                        "Too many sessions (at most {} may be open)",
+                        self.limits.max_sessions
+                    )),
+                );
+            }
+            match (self.factory)().and_then(|server| Ok((session_id()?, server))) {
+                Ok((id, server)) => {
+                    let session = Arc::new(ClientSession::new(server));
+                    self.sessions
+                        .lock()
+                        .unwrap()
+                        .insert(id.clone(), session.clone());
+                    (Some(id), session)
+                }
+                Err(e) => {
+                    return rpc_error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        JsonRpcError::internal_error(format!("{:#}", e)),
+                    )
+                }
+            }
+        } else {
+            match self.session(&request) {
+                Ok(session) => (None, session),
+                Err(code) => return session_error(code),
+            }
+        };
+
+        // Cancel first: the cancelled request may be holding the session
+        let mut pending = Vec::new();
+        for message in messages {
+            if let Some(id) = cancelled_request(&message) {
+                cancel(&session.in_flight, &id);
+            } else if message.get("method").is_some() {
+                // Anything else without a method is a response to the server
+                pending.push(message);
+            }
+        }
+
+        let mut responses = Vec::new();
+        if !pending.is_empty() {
+            let mut server = session.server.lock().await;
+            for message in pending {
+                let is_request = message.get("id").is_some();
+                let response = match serde_json::from_value::<JsonRpcRequest>(message) {
+                    Ok(request) => server.handle_request(request).await,
+                    Err(e) => JsonRpcResponse::failure(
+                        None,
+                        JsonRpcError::invalid_request(format!("Invalid request: {}", e)),
+                    ),
+                };
+                if is_request {
+                    responses.push(response);
+                }
+            }
+            for notification in server.take_notifications() {
+                if let Ok(notification) = serde_json::to_string(&notification) {
+                    session.broadcast(&notification);
+                }
+            }
+            // Idle time counts from the end of the last request
+            session.touch();
+        }
+
+        let mut response = if responses.is_empty() {
+            status(StatusCode::ACCEPTED)
+        } else if batch {
+            json_response(StatusCode::OK, &json!(responses))
+        } else {
+            json_response(StatusCode::OK, &json!(responses[0]))
+        };
+        if let Some(id) = new_session.and_then(|id| HeaderValue::from_str(&id).ok()) {
+            response.headers_mut().insert(SESSION_HEADER, id);
+        }
+        response
+    }
+
+    /// Open an SSE stream for the session's notifications
+    fn stream(&self, request: &Request<Body>) -> Response<Body> {
+        let accepts_sse = request
+            .headers()
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/event-stream"));
+        if !accepts_sse {
+            return status(StatusCode::METHOD_NOT_ALLOWED);
+        }
+        let session = match self.session(request) {
+            Ok(session) => session,
+            Err(code) => return session_error(code),
+        };
+
+        let (messages, mut queue) = mpsc::unbounded_channel::<String>();
+        session.streams.lock().unwrap().push(messages);
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            // An initial comment so clients see the stream open at once
+            if sender.send_data(Bytes::from(": open\n\n")).await.is_err() {
+                return;
+            }
+            while let Some(message) = queue.recv().await {
+                let event = format!("event: message\ndata: {}\n\n", message);
+                if sender.send_data(Bytes::from(event)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut response = Response::new(body);
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        headers.insert("cache-control", HeaderValue::from_static("no-cache"));
+        response
+    }
+
+    /// End a session, interrupting its evaluations and closing its streams
+    fn delete(&self, request: &Request<Body>) -> Response<Body> {
+        let session = match self.session(request) {
+            Ok(session) => session,
+            Err(code) => return session_error(code),
+        };
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, other| !Arc::ptr_eq(other, &session));
+        session.close();
+        status(StatusCode::OK)
+    }
+}
+
+/// An unguessable session id: 128 bits from the operating system's secure
+/// random number generator, in hex
+fn session_id() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to generate a session id: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Whether a request from `origin` may be served: no origin (not a browser) or
+/// a localhost one
+fn origin_allowed(origin: Option<&HeaderValue>) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    let Some(host) = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, rest)| rest)
+    else {
+        return false;
+    };
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+fn session_error(code: StatusCode) -> Response<Body> {
+    if code == StatusCode::BAD_REQUEST {
+        rpc_error(
+            code,
+            JsonRpcError::invalid_request("Missing Mcp-Session-Id header"),
+        )
+    } else {
+        status(code)
+    }
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+}
+
+fn json_response(code: StatusCode, body: &serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = code;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+fn rpc_error(code: StatusCode, error: JsonRpcError) -> Response<Body> {
+    json_response(code, &json!(JsonRpcResponse::failure(None, error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_allowed() {
+        let origin = |s: &'static str| HeaderValue::from_static(s);
+        assert!(origin_allowed(None));
+        assert!(origin_allowed(Some(&origin("http://localhost:3000"))));
+        assert!(origin_allowed(Some(&origin("http://127.0.0.1"))));
+        assert!(origin_allowed(Some(&origin("http://[::1]:8080"))));
+        assert!(!origin_allowed(Some(&origin("https://example.com"))));
+        assert!(!origin_allowed(Some(&origin(
+            "http://localhost.example.com"
+        ))));
+        assert!(!origin_allowed(Some(&origin("null"))));
+    }
+
+    #[test]
+    fn test_session_ids_are_unique() {
+        let a = session_id().unwrap();
+        assert_eq!(a.len(), 32);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, session_id().unwrap());
+    }
+}
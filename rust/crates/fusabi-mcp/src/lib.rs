@@ -8,8 +8,12 @@
 //!
 //! - Execute F# scripts via `eval_fusabi` tool
 //! - Query runtime context via `get_context` tool
-//! - Timeout protection for long-running scripts
-//! - JSON-RPC based message handling
+//! - Serve a script's tools, resources and prompts (see [`Script`])
+//! - Enforced timeouts and cancellation: every evaluation runs on a worker
+//!   thread (see [`sessions`])
+//! - Named sessions whose bindings persist across `eval_fusabi` calls
+//! - JSON-RPC based message handling over stdio, or Streamable HTTP with SSE
+//!   notifications (see [`http`])
 //!
 //! # Example
 //!
@@ -23,20 +27,46 @@
 //! }
 //! ```
 
+pub mod http;
+pub mod prompts;
+pub mod resources;
+pub mod script;
+pub mod script_tools;
+pub mod sessions;
+
+pub use prompts::ScriptPrompt;
+pub use resources::{ScriptResource, UriTemplate};
+pub use script::Script;
+pub use script_tools::{InvalidArguments, ScriptTool};
+pub use sessions::{Session, SessionLimits};
+
 use anyhow::{anyhow, Context, Result};
-use fusabi::{Engine, Value};
+use fusabi::{Engine, Interrupt, Value};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
+use sessions::WorkerPool;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
 
-/// MCP protocol version
-pub const MCP_VERSION: &str = "2024-11-05";
+/// MCP protocol version, the latest of [`SUPPORTED_VERSIONS`]
+pub const MCP_VERSION: &str = "2025-03-26";
+
+/// MCP protocol versions the server speaks, newest first
+///
+/// `initialize` answers with the version the client asked for when it is one
+/// of these, and with [`MCP_VERSION`] otherwise.
+pub const SUPPORTED_VERSIONS: &[&str] = &[MCP_VERSION, "2024-11-05"];
 
 /// Maximum execution time for scripts (5 seconds)
 pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// Worker threads evaluating scripts outside sessions
+pub const MAX_EVAL_WORKERS: usize = 4;
+
 /// JSON-RPC request message
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct JsonRpcRequest {
@@ -58,6 +88,34 @@ pub struct JsonRpcResponse {
     pub error: Option<JsonRpcError>,
 }
 
+impl JsonRpcResponse {
+    fn success(id: Option<serde_json::Value>, result: serde_json::Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn failure(id: Option<serde_json::Value>, error: JsonRpcError) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// JSON-RPC notification (a message without an id)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JsonRpcNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: serde_json::Value,
+}
+
 /// JSON-RPC error
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct JsonRpcError {
@@ -107,21 +165,39 @@ impl JsonRpcError {
             data: None,
         }
     }
+
+    /// MCP error for `resources/read` of an unknown URI
+    pub fn resource_not_found(uri: &str) -> Self {
+        Self {
+            code: -32002,
+            message: "Resource not found".to_string(),
+            data: Some(json!({ "uri": uri })),
+        }
+    }
 }
 
 /// MCP Server state
 pub struct McpServer {
     engine: Engine,
     timeout_duration: Duration,
+    /// When set, its tools replace the built-in ones
+    script: Option<Script>,
+    /// Resource URIs the client subscribed to
+    subscriptions: HashSet<String>,
+    /// Named sessions created with the `create_session` tool
+    sessions: BTreeMap<String, Session>,
+    /// Id of the request being handled
+    current_request: Option<String>,
+    /// Evaluations in progress, by request id, for `notifications/cancelled`
+    in_flight: Arc<Mutex<HashMap<String, Interrupt>>>,
+    /// Workers for `eval_fusabi` calls outside sessions
+    workers: WorkerPool,
 }
 
 impl McpServer {
     /// Create a new MCP server with default settings
     pub fn new() -> Self {
-        Self {
-            engine: Engine::new(),
-            timeout_duration: DEFAULT_TIMEOUT,
-        }
+        Self::with_timeout(DEFAULT_TIMEOUT)
     }
 
     /// Create a new MCP server with custom timeout
@@ -129,32 +205,67 @@ impl McpServer {
         Self {
             engine: Engine::new(),
             timeout_duration,
+            script: None,
+            subscriptions: HashSet::new(),
+            sessions: BTreeMap::new(),
+            current_request: None,
+            in_flight: Arc::default(),
+            workers: WorkerPool::new(
+                SessionLimits::with_timeout(timeout_duration),
+                MAX_EVAL_WORKERS,
+            ),
         }
     }
 
+    /// Serve the tools, resources and prompts of a `.fsx` script; its tools
+    /// replace the built-in ones
+    pub fn load_script(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.script = Some(Script::load(path)?);
+        Ok(())
+    }
+
+    /// Serve an already loaded script; its tools replace the built-in ones
+    pub fn with_script(mut self, script: Script) -> Self {
+        self.script = Some(script);
+        self
+    }
+
+    /// Notifications to send after the last response: updates to subscribed resources
+    pub fn take_notifications(&mut self) -> Vec<JsonRpcNotification> {
+        let Some(script) = &self.script else {
+            return Vec::new();
+        };
+        let mut uris = script.take_updated();
+        uris.retain(|uri| self.subscriptions.contains(uri));
+        uris.dedup();
+        uris.into_iter()
+            .map(|uri| JsonRpcNotification {
+                jsonrpc: "2.0".to_string(),
+                method: "notifications/resources/updated".to_string(),
+                params: json!({ "uri": uri }),
+            })
+            .collect()
+    }
+
     /// Run the MCP server, reading from stdin and writing to stdout
+    ///
+    /// Stdin is read on its own task so `notifications/cancelled` can
+    /// interrupt an evaluation while its request is being handled.
     pub async fn run(&mut self) -> Result<()> {
-        let stdin = tokio::io::stdin();
         let mut stdout = tokio::io::stdout();
-        let mut reader = BufReader::new(stdin);
-        let mut line = String::new();
+        let mut lines = read_requests(self.in_flight.clone());
 
         eprintln!("Fusabi MCP Server starting...");
         eprintln!("Protocol version: {}", MCP_VERSION);
         eprintln!("Ready to accept requests");
 
         loop {
-            line.clear();
-            let bytes_read = reader
-                .read_line(&mut line)
-                .await
-                .context("Failed to read from stdin")?;
-
-            if bytes_read == 0 {
+            let Some(line) = lines.recv().await else {
                 // EOF reached
                 eprintln!("EOF received, shutting down");
                 break;
-            }
+            };
+            let line = line?;
 
             let trimmed = line.trim();
             if trimmed.is_empty() {
@@ -169,26 +280,32 @@ impl McpServer {
                     jsonrpc: "2.0".to_string(),
                     id: None,
                     result: None,
-                    error: Some(JsonRpcError::parse_error(format!(
-                        "Invalid JSON: {}",
-                        e
-                    ))),
+                    error: Some(JsonRpcError::parse_error(format!("Invalid JSON: {}", e))),
                 },
             };
 
-            let response_json = serde_json::to_string(&response)
-                .context("Failed to serialize response")?;
+            let response_json =
+                serde_json::to_string(&response).context("Failed to serialize response")?;
 
             eprintln!("Sending response: {}", response_json);
 
-            stdout
-                .write_all(response_json.as_bytes())
-                .await
-                .context("Failed to write response")?;
-            stdout
-                .write_all(b"\n")
-                .await
-                .context("Failed to write newline")?;
+            let mut messages = vec![response_json];
+            for notification in self.take_notifications() {
+                messages.push(
+                    serde_json::to_string(&notification)
+                        .context("Failed to serialize notification")?,
+                );
+            }
+            for message in messages {
+                stdout
+                    .write_all(message.as_bytes())
+                    .await
+                    .context("Failed to write response")?;
+                stdout
+                    .write_all(b"\n")
+                    .await
+                    .context("Failed to write newline")?;
+            }
             stdout.flush().await.context("Failed to flush stdout")?;
         }
 
@@ -198,12 +315,25 @@ impl McpServer {
     /// Handle a JSON-RPC request
     async fn handle_request(&mut self, request: JsonRpcRequest) -> JsonRpcResponse {
         let id = request.id.clone();
+        self.current_request = id.as_ref().map(|id| id.to_string());
 
         match request.method.as_str() {
             "initialize" => self.handle_initialize(id, request.params),
             "initialized" => self.handle_initialized(id),
             "tools/list" => self.handle_tools_list(id),
             "tools/call" => self.handle_tools_call(id, request.params).await,
+            "resources/list" if self.has_resources() => self.handle_resources_list(id, false),
+            "resources/templates/list" if self.has_resources() => {
+                self.handle_resources_list(id, true)
+            }
+            "resources/read" if self.has_resources() => {
+                self.handle_resources_read(id, request.params)
+            }
+            "resources/subscribe" | "resources/unsubscribe" if self.has_resources() => {
+                self.handle_resources_subscribe(id, &request.method, request.params)
+            }
+            "prompts/list" if self.has_prompts() => self.handle_prompts_list(id),
+            "prompts/get" if self.has_prompts() => self.handle_prompts_get(id, request.params),
             "ping" => self.handle_ping(id),
             _ => JsonRpcResponse {
                 jsonrpc: "2.0".to_string(),
@@ -221,16 +351,26 @@ impl McpServer {
     fn handle_initialize(
         &self,
         id: Option<serde_json::Value>,
-        _params: serde_json::Value,
+        params: serde_json::Value,
     ) -> JsonRpcResponse {
+        let version = params["protocolVersion"]
+            .as_str()
+            .filter(|version| SUPPORTED_VERSIONS.contains(version))
+            .unwrap_or(MCP_VERSION);
+        let mut capabilities = json!({ "tools": {} });
+        if self.has_resources() {
+            capabilities["resources"] = json!({ "subscribe": true });
+        }
+        if self.has_prompts() {
+            capabilities["prompts"] = json!({});
+        }
+
         JsonRpcResponse {
             jsonrpc: "2.0".to_string(),
             id,
             result: Some(json!({
-                "protocolVersion": MCP_VERSION,
-                "capabilities": {
-                    "tools": {}
-                },
+                "protocolVersion": version,
+                "capabilities": capabilities,
                 "serverInfo": {
                     "name": "fusabi-mcp",
                     "version": env!("CARGO_PKG_VERSION")
@@ -252,6 +392,16 @@ impl McpServer {
 
     /// Handle tools/list request
     fn handle_tools_list(&self, id: Option<serde_json::Value>) -> JsonRpcResponse {
+        if let Some(script) = &self.script {
+            let definitions: Vec<_> = script.tools().iter().map(ScriptTool::definition).collect();
+            return JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                id,
+                result: Some(json!({ "tools": definitions })),
+                error: None,
+            };
+        }
+
         JsonRpcResponse {
             jsonrpc: "2.0".to_string(),
             id,
@@ -266,6 +416,10 @@ impl McpServer {
                                 "script": {
                                     "type": "string",
                                     "description": "The Fusabi script to execute. Example: 'let x = 42 in x * 2'"
+                                },
+                                "session": {
+                                    "type": "string",
+                                    "description": "Session to run in; its earlier top-level bindings are in scope and new ones are kept. Without it the script runs in a fresh engine."
                                 }
                             },
                             "required": ["script"]
@@ -273,7 +427,56 @@ impl McpServer {
                     },
                     {
                         "name": "get_context",
-                        "description": "Get the current Fusabi runtime context including all registered host functions and global variables.",
+                        "description": "Get the current Fusabi runtime context including all registered host functions and the state of each session.",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {}
+                        }
+                    },
+                    {
+                        "name": "create_session",
+                        "description": "Create a named session whose top-level bindings persist across eval_fusabi calls, with optional resource limits.",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "timeout_ms": {
+                                    "type": "integer",
+                                    "description": "Wall time each evaluation may take (defaults to the server timeout)"
+                                },
+                                "max_instructions": {
+                                    "type": "integer",
+                                    "description": "VM instructions each evaluation may execute"
+                                },
+                                "max_call_depth": {
+                                    "type": "integer",
+                                    "description": "Nested calls an evaluation may make"
+                                }
+                            },
+                            "required": ["name"]
+                        }
+                    },
+                    {
+                        "name": "reset_session",
+                        "description": "Drop all bindings of a session, keeping its limits.",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": { "name": { "type": "string" } },
+                            "required": ["name"]
+                        }
+                    },
+                    {
+                        "name": "close_session",
+                        "description": "Delete a session.",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": { "name": { "type": "string" } },
+                            "required": ["name"]
+                        }
+                    },
+                    {
+                        "name": "list_sessions",
+                        "description": "List sessions with their bindings, evaluation counts and limits.",
                         "inputSchema": {
                             "type": "object",
                             "properties": {}
@@ -294,6 +497,7 @@ impl McpServer {
         #[derive(Deserialize)]
         struct ToolCallParams {
             name: String,
+            #[serde(default)]
             arguments: serde_json::Value,
         }
 
@@ -312,10 +516,15 @@ impl McpServer {
             }
         };
 
-        let result = match tool_params.name.as_str() {
-            "eval_fusabi" => self.eval_fusabi(tool_params.arguments).await,
-            "get_context" => self.get_context(),
-            _ => Err(anyhow!("Unknown tool: {}", tool_params.name)),
+        let result = match (&mut self.script, tool_params.name.as_str()) {
+            (Some(script), name) => script.call_tool(name, &tool_params.arguments),
+            (None, "eval_fusabi") => self.eval_fusabi(tool_params.arguments).await,
+            (None, "get_context") => self.get_context(),
+            (None, "create_session") => self.create_session(tool_params.arguments),
+            (None, "reset_session") => self.reset_session(tool_params.arguments),
+            (None, "close_session") => self.close_session(tool_params.arguments),
+            (None, "list_sessions") => Ok(self.list_sessions()),
+            (None, name) => Err(InvalidArguments(format!("Unknown tool: {}", name)).into()),
         };
 
         match result {
@@ -332,15 +541,120 @@ impl McpServer {
                 })),
                 error: None,
             },
-            Err(e) => JsonRpcResponse {
-                jsonrpc: "2.0".to_string(),
-                id,
-                result: None,
-                error: Some(JsonRpcError::internal_error(format!("Tool execution failed: {}", e))),
+            Err(e) => match e.downcast_ref::<InvalidArguments>() {
+                Some(invalid) => {
+                    JsonRpcResponse::failure(id, JsonRpcError::invalid_params(invalid.to_string()))
+                }
+                None => JsonRpcResponse::failure(
+                    id,
+                    JsonRpcError::internal_error(format!("Tool execution failed: {}", e)),
+                ),
             },
         }
     }
 
+    fn has_resources(&self) -> bool {
+        self.script
+            .as_ref()
+            .is_some_and(|script| !script.resources().is_empty())
+    }
+
+    fn has_prompts(&self) -> bool {
+        self.script
+            .as_ref()
+            .is_some_and(|script| !script.prompts().is_empty())
+    }
+
+    /// Handle resources/list, or resources/templates/list when `templates` is set
+    fn handle_resources_list(
+        &self,
+        id: Option<serde_json::Value>,
+        templates: bool,
+    ) -> JsonRpcResponse {
+        let resources = self.script.as_ref().map_or(&[][..], Script::resources);
+        let definitions: Vec<_> = resources
+            .iter()
+            .filter(|r| r.uri.is_template() == templates)
+            .map(ScriptResource::definition)
+            .collect();
+        let key = if templates {
+            "resourceTemplates"
+        } else {
+            "resources"
+        };
+        JsonRpcResponse::success(id, json!({ key: definitions }))
+    }
+
+    /// Handle resources/read request
+    fn handle_resources_read(
+        &mut self,
+        id: Option<serde_json::Value>,
+        params: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let Some(uri) = params.get("uri").and_then(|uri| uri.as_str()) else {
+            return JsonRpcResponse::failure(id, JsonRpcError::invalid_params("Missing 'uri'"));
+        };
+        match self.script.as_mut().and_then(|s| s.read_resource(uri)) {
+            Some(Ok(contents)) => JsonRpcResponse::success(id, json!({ "contents": [contents] })),
+            Some(Err(e)) => JsonRpcResponse::failure(
+                id,
+                JsonRpcError::internal_error(format!("Failed to read resource: {}", e)),
+            ),
+            None => JsonRpcResponse::failure(id, JsonRpcError::resource_not_found(uri)),
+        }
+    }
+
+    /// Handle resources/subscribe and resources/unsubscribe requests
+    fn handle_resources_subscribe(
+        &mut self,
+        id: Option<serde_json::Value>,
+        method: &str,
+        params: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let Some(uri) = params.get("uri").and_then(|uri| uri.as_str()) else {
+            return JsonRpcResponse::failure(id, JsonRpcError::invalid_params("Missing 'uri'"));
+        };
+        if method == "resources/subscribe" {
+            self.subscriptions.insert(uri.to_string());
+        } else {
+            self.subscriptions.remove(uri);
+        }
+        JsonRpcResponse::success(id, json!({}))
+    }
+
+    /// Handle prompts/list request
+    fn handle_prompts_list(&self, id: Option<serde_json::Value>) -> JsonRpcResponse {
+        let prompts = self.script.as_ref().map_or(&[][..], Script::prompts);
+        let definitions: Vec<_> = prompts.iter().map(ScriptPrompt::definition).collect();
+        JsonRpcResponse::success(id, json!({ "prompts": definitions }))
+    }
+
+    /// Handle prompts/get request
+    fn handle_prompts_get(
+        &mut self,
+        id: Option<serde_json::Value>,
+        params: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let Some(name) = params.get("name").and_then(|name| name.as_str()) else {
+            return JsonRpcResponse::failure(id, JsonRpcError::invalid_params("Missing 'name'"));
+        };
+        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+        match self
+            .script
+            .as_mut()
+            .and_then(|s| s.get_prompt(name, &arguments))
+        {
+            Some(Ok(prompt)) => JsonRpcResponse::success(id, prompt),
+            Some(Err(e)) => {
+                JsonRpcResponse::failure(id, JsonRpcError::invalid_params(e.to_string()))
+            }
+            None => JsonRpcResponse::failure(
+                id,
+                JsonRpcError::invalid_params(format!("Unknown prompt: {}", name)),
+            ),
+        }
+    }
+
     /// Handle ping request
     fn handle_ping(&self, id: Option<serde_json::Value>) -> JsonRpcResponse {
         JsonRpcResponse {
@@ -351,35 +665,112 @@ impl McpServer {
         }
     }
 
-    /// Execute a Fusabi script
+    /// Execute a Fusabi script, in a session or in a fresh engine
     ///
-    /// Note: Timeout protection is limited because Fusabi's Value type uses Rc which is not Send.
-    /// A full timeout implementation would require the Fusabi crate to use Arc instead.
+    /// Either way the script runs on a worker thread and is interrupted once
+    /// it exceeds the timeout or its request is cancelled.
     async fn eval_fusabi(&mut self, arguments: serde_json::Value) -> Result<serde_json::Value> {
         #[derive(Deserialize)]
         struct EvalArgs {
             script: String,
+            #[serde(default)]
+            session: Option<String>,
         }
 
         let args: EvalArgs = serde_json::from_value(arguments)
-            .context("Missing or invalid 'script' parameter")?;
+            .map_err(|_| InvalidArguments("Missing or invalid 'script' parameter".to_string()))?;
 
         eprintln!("Executing script: {}", args.script);
 
-        // Create a fresh engine for each execution to ensure clean state
-        // Note: Since Value contains Rc, we cannot move it to another thread
-        // This means we cannot use tokio::spawn_blocking for true timeout protection
-        let mut engine = Engine::new();
-        match engine.eval(&args.script) {
-            Ok(value) => {
-                eprintln!("Execution succeeded: {:?}", value);
-                Ok(value_to_json(&value))
+        let result = match &args.session {
+            Some(name) => {
+                let session = self
+                    .sessions
+                    .get_mut(name)
+                    .ok_or_else(|| anyhow!("Unknown session: {}", name))?;
+                let _running = InFlight::track(
+                    &self.in_flight,
+                    self.current_request.clone(),
+                    session.interrupt_handle(),
+                );
+                session.eval(&args.script).await
             }
-            Err(e) => {
-                eprintln!("Execution failed: {}", e);
-                Err(anyhow!("Execution error: {}", e))
+            None => {
+                let worker = self.workers.worker()?;
+                let interrupt = Interrupt::default();
+                let _running = InFlight::track(
+                    &self.in_flight,
+                    self.current_request.clone(),
+                    interrupt.clone(),
+                );
+                worker.eval(&args.script, interrupt).await.map(|e| e.value)
             }
+        };
+
+        match &result {
+            Ok(value) => eprintln!("Execution succeeded: {}", value),
+            Err(e) => eprintln!("Execution failed: {}", e),
+        }
+        result
+    }
+
+    /// Create a named session
+    fn create_session(&mut self, arguments: serde_json::Value) -> Result<serde_json::Value> {
+        #[derive(Deserialize)]
+        struct CreateArgs {
+            name: String,
+            timeout_ms: Option<u64>,
+            max_instructions: Option<u64>,
+            max_call_depth: Option<usize>,
+        }
+
+        let args: CreateArgs = serde_json::from_value(arguments)
+            .map_err(|_| InvalidArguments("Missing or invalid 'name' parameter".to_string()))?;
+        if self.sessions.contains_key(&args.name) {
+            return Err(anyhow!("Session '{}' already exists", args.name));
         }
+        let limits = SessionLimits {
+            timeout: args
+                .timeout_ms
+                .map(Duration::from_millis)
+                .unwrap_or(self.timeout_duration),
+            max_instructions: args.max_instructions,
+            max_call_depth: args.max_call_depth,
+        };
+        let session = Session::new(&args.name, limits)?;
+        let state = session.state();
+        self.sessions.insert(args.name, session);
+        Ok(state)
+    }
+
+    /// Drop a session's bindings
+    fn reset_session(&mut self, arguments: serde_json::Value) -> Result<serde_json::Value> {
+        let name = session_name(arguments)?;
+        let session = self
+            .sessions
+            .get_mut(&name)
+            .ok_or_else(|| anyhow!("Unknown session: {}", name))?;
+        session.reset()?;
+        Ok(session.state())
+    }
+
+    /// Delete a session, interrupting any evaluation still running in it
+    fn close_session(&mut self, arguments: serde_json::Value) -> Result<serde_json::Value> {
+        let name = session_name(arguments)?;
+        let session = self
+            .sessions
+            .remove(&name)
+            .ok_or_else(|| anyhow!("Unknown session: {}", name))?;
+        session.interrupt();
+        Ok(json!({ "closed": name }))
+    }
+
+    fn list_sessions(&self) -> serde_json::Value {
+        json!(self
+            .sessions
+            .values()
+            .map(Session::state)
+            .collect::<Vec<_>>())
     }
 
     /// Get the current runtime context
@@ -389,21 +780,111 @@ impl McpServer {
         Ok(json!({
             "host_functions": host_functions,
             "timeout_seconds": self.timeout_duration.as_secs(),
+            "sessions": self.list_sessions(),
             "fusabi_version": env!("CARGO_PKG_VERSION")
         }))
     }
 }
 
+fn session_name(arguments: serde_json::Value) -> Result<String> {
+    #[derive(Deserialize)]
+    struct SessionArgs {
+        name: String,
+    }
+
+    let args: SessionArgs = serde_json::from_value(arguments)
+        .map_err(|_| InvalidArguments("Missing or invalid 'name' parameter".to_string()))?;
+    Ok(args.name)
+}
+
+/// Registers an evaluation for cancellation while it runs
+struct InFlight<'a> {
+    in_flight: &'a Mutex<HashMap<String, Interrupt>>,
+    request: Option<String>,
+}
+
+impl<'a> InFlight<'a> {
+    fn track(
+        in_flight: &'a Mutex<HashMap<String, Interrupt>>,
+        request: Option<String>,
+        interrupt: Interrupt,
+    ) -> Self {
+        if let Some(request) = &request {
+            in_flight.lock().unwrap().insert(request.clone(), interrupt);
+        }
+        InFlight { in_flight, request }
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Some(request) = &self.request {
+            self.in_flight.lock().unwrap().remove(request);
+        }
+    }
+}
+
 impl Default for McpServer {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Forward lines from stdin, handling `notifications/cancelled` as they arrive
+fn read_requests(
+    in_flight: Arc<Mutex<HashMap<String, Interrupt>>>,
+) -> mpsc::UnboundedReceiver<Result<String>> {
+    let (lines, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(tokio::io::stdin());
+        loop {
+            let mut line = String::new();
+            match reader
+                .read_line(&mut line)
+                .await
+                .context("Failed to read from stdin")
+            {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    let _ = lines.send(Err(e));
+                    break;
+                }
+            }
+            let message = serde_json::from_str(line.trim()).ok();
+            if let Some(id) = message.as_ref().and_then(cancelled_request) {
+                cancel(&in_flight, &id);
+                continue;
+            }
+            if lines.send(Ok(line)).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Id of the request a `notifications/cancelled` message cancels
+fn cancelled_request(message: &serde_json::Value) -> Option<String> {
+    if message.get("method")? != "notifications/cancelled" {
+        return None;
+    }
+    Some(message.get("params")?.get("requestId")?.to_string())
+}
+
+/// Interrupt the evaluation running for request `id`, if any
+fn cancel(in_flight: &Mutex<HashMap<String, Interrupt>>, id: &str) {
+    if let Some(interrupt) = in_flight.lock().unwrap().get(id) {
+        eprintln!("Cancelling request {}", id);
+        interrupt.interrupt();
+    }
+}
+
 /// Convert a Fusabi Value to JSON
 pub fn value_to_json(value: &Value) -> serde_json::Value {
     match value {
         Value::Int(n) => json!(n),
+        Value::Float(f) => json!(f),
         Value::Bool(b) => json!(b),
         Value::Str(s) => json!(s),
         Value::Unit => json!(null),
@@ -422,11 +903,11 @@ pub fn value_to_json(value: &Value) -> serde_json::Value {
             json!(result)
         }
         Value::Array(arr) => {
-            let arr = arr.borrow();
+            let arr = arr.lock().unwrap();
             json!(arr.iter().map(value_to_json).collect::<Vec<_>>())
         }
-        Value::Record(rec) => {
-            let rec = rec.borrow();
+        Value::Record(rec) | Value::Map(rec) => {
+            let rec = rec.lock().unwrap();
             let map: HashMap<String, serde_json::Value> = rec
                 .iter()
                 .map(|(k, v)| (k.clone(), value_to_json(v)))
@@ -436,7 +917,11 @@ pub fn value_to_json(value: &Value) -> serde_json::Value {
         Value::Closure { .. } => json!("<closure>"),
         Value::HostData(_) => json!("<host_data>"),
         Value::NativeFn { name, .. } => json!(format!("<native fn: {}>", name)),
-        Value::Variant { variant_name, fields, .. } => json!({
+        Value::Variant {
+            variant_name,
+            fields,
+            ..
+        } => json!({
             "variant": variant_name,
             "fields": fields.iter().map(value_to_json).collect::<Vec<_>>()
         }),
@@ -457,7 +942,11 @@ mod tests {
 
     #[test]
     fn test_value_to_json_tuple() {
-        let tuple = Value::Tuple(vec![Value::Int(1), Value::Bool(true), Value::Str("test".into())]);
+        let tuple = Value::Tuple(vec![
+            Value::Int(1),
+            Value::Bool(true),
+            Value::Str("test".into()),
+        ]);
         assert_eq!(value_to_json(&tuple), json!([1, true, "test"]));
     }
 
@@ -505,7 +994,8 @@ mod tests {
     async fn test_eval_fusabi_function() {
         let mut server = McpServer::new();
         // Test with recursive function
-        let script = "let rec factorial n = if n <= 1 then 1 else n * factorial (n - 1) in factorial 5";
+        let script =
+            "let rec factorial n = if n <= 1 then 1 else n * factorial (n - 1) in factorial 5";
         let args = json!({ "script": script });
         let result = server.eval_fusabi(args).await.unwrap();
         assert_eq!(result, json!(120));
@@ -540,7 +1030,273 @@ mod tests {
         assert!(!functions.is_empty()); // Should have stdlib functions
     }
 
-    // Note: Timeout protection test removed due to Fusabi's Value type using Rc
-    // which cannot be sent between threads. This is a limitation of the current
-    // Fusabi architecture.
+    #[tokio::test]
+    async fn test_script_tools_replace_builtins() {
+        let script = Script::from_source("let square n : int = n * n").unwrap();
+        let mut server = McpServer::new().with_script(script);
+
+        let list = server.handle_tools_list(Some(json!(1)));
+        let listed = &list.result.unwrap()["tools"];
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+        assert_eq!(listed[0]["name"], json!("square"));
+
+        let call = server
+            .handle_tools_call(
+                Some(json!(2)),
+                json!({ "name": "square", "arguments": { "n": 9 } }),
+            )
+            .await;
+        assert_eq!(call.result.unwrap()["content"][0]["text"], json!("81"));
+
+        let call = server
+            .handle_tools_call(
+                Some(json!(3)),
+                json!({ "name": "eval_fusabi", "arguments": {} }),
+            )
+            .await;
+        assert_eq!(call.error.unwrap().code, -32602);
+
+        let call = server
+            .handle_tools_call(
+                Some(json!(4)),
+                json!({ "name": "square", "arguments": { "n": "nine" } }),
+            )
+            .await;
+        let error = call.error.unwrap();
+        assert_eq!(error.code, -32602);
+        assert!(
+            error.message.contains("expected an integer"),
+            "{}",
+            error.message
+        );
+    }
+
+    #[tokio::test]
+    async fn test_script_resources_and_prompts() {
+        let source = r#"
+module Tools =
+    let save id = Events.emit "resources/updated" ("notes://" ++ id)
+
+module Resources =
+    let note = { uri = "notes://{id}"; read = fun p -> "Note " ++ p.id }
+
+module Prompts =
+    let review note = "Review " ++ note
+"#;
+        let mut server = McpServer::new().with_script(Script::from_source(source).unwrap());
+        let request = |id: i64, method: &str, params: serde_json::Value| JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(id)),
+            method: method.to_string(),
+            params,
+        };
+
+        let init = server
+            .handle_request(request(1, "initialize", json!({})))
+            .await;
+        let init = init.result.unwrap();
+        assert_eq!(init["protocolVersion"], json!(MCP_VERSION));
+        let capabilities = &init["capabilities"];
+        assert_eq!(capabilities["resources"]["subscribe"], json!(true));
+        assert!(capabilities.get("prompts").is_some());
+
+        let templates = server
+            .handle_request(request(2, "resources/templates/list", json!({})))
+            .await;
+        assert_eq!(
+            templates.result.unwrap()["resourceTemplates"][0]["uriTemplate"],
+            json!("notes://{id}")
+        );
+
+        let read = server
+            .handle_request(request(3, "resources/read", json!({ "uri": "notes://1" })))
+            .await;
+        assert_eq!(read.result.unwrap()["contents"][0]["text"], json!("Note 1"));
+        let missing = server
+            .handle_request(request(4, "resources/read", json!({ "uri": "x://1" })))
+            .await;
+        assert_eq!(missing.error.unwrap().code, -32002);
+
+        // Only subscribed URIs are notified
+        server
+            .handle_request(request(
+                5,
+                "resources/subscribe",
+                json!({ "uri": "notes://1" }),
+            ))
+            .await;
+        for id in ["1", "2"] {
+            let params = json!({ "name": "save", "arguments": { "id": id } });
+            server
+                .handle_request(request(6, "tools/call", params))
+                .await;
+        }
+        let notifications = server.take_notifications();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].method, "notifications/resources/updated");
+        assert_eq!(notifications[0].params, json!({ "uri": "notes://1" }));
+
+        let prompt = server
+            .handle_request(request(
+                7,
+                "prompts/get",
+                json!({ "name": "review", "arguments": { "note": "this" } }),
+            ))
+            .await;
+        assert_eq!(
+            prompt.result.unwrap()["messages"][0]["content"]["text"],
+            json!("Review this")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resources_require_a_script() {
+        let mut server = McpServer::new();
+        let response = server
+            .handle_request(JsonRpcRequest {
+                jsonrpc: "2.0".to_string(),
+                id: Some(json!(1)),
+                method: "resources/list".to_string(),
+                params: json!({}),
+            })
+            .await;
+        assert_eq!(response.error.unwrap().code, -32601);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_negotiates_version() {
+        let mut server = McpServer::new();
+        for (requested, answered) in [
+            (json!("2024-11-05"), "2024-11-05"),
+            (json!("2025-03-26"), "2025-03-26"),
+            (json!("1999-01-01"), MCP_VERSION),
+            (json!(null), MCP_VERSION),
+        ] {
+            let response = server
+                .handle_request(JsonRpcRequest {
+                    jsonrpc: "2.0".to_string(),
+                    id: Some(json!(1)),
+                    method: "initialize".to_string(),
+                    params: json!({ "protocolVersion": requested }),
+                })
+                .await;
+            assert_eq!(response.result.unwrap()["protocolVersion"], json!(answered));
+        }
+    }
+
+    async fn call_tool(
+        server: &mut McpServer,
+        name: &str,
+        arguments: serde_json::Value,
+    ) -> JsonRpcResponse {
+        server
+            .handle_request(JsonRpcRequest {
+                jsonrpc: "2.0".to_string(),
+                id: Some(json!(1)),
+                method: "tools/call".to_string(),
+                params: json!({ "name": name, "arguments": arguments }),
+            })
+            .await
+    }
+
+    fn tool_result(response: &JsonRpcResponse) -> serde_json::Value {
+        let text = response.result.as_ref().unwrap()["content"][0]["text"]
+            .as_str()
+            .unwrap();
+        serde_json::from_str(text).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_eval_fusabi_timeout() {
+        let mut server = McpServer::with_timeout(Duration::from_millis(200));
+        let error = server
+            .eval_fusabi(json!({ "script": "while true do ()" }))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("time"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let mut server = McpServer::new();
+        let created = call_tool(
+            &mut server,
+            "create_session",
+            json!({ "name": "work", "max_instructions": 100000 }),
+        )
+        .await;
+        assert_eq!(
+            tool_result(&created)["limits"]["max_instructions"],
+            json!(100000)
+        );
+        let duplicate = call_tool(&mut server, "create_session", json!({ "name": "work" })).await;
+        assert!(duplicate.error.is_some());
+
+        server
+            .eval_fusabi(json!({ "script": "let x = 21", "session": "work" }))
+            .await
+            .unwrap();
+        let result = server
+            .eval_fusabi(json!({ "script": "x * 2", "session": "work" }))
+            .await
+            .unwrap();
+        assert_eq!(result, json!(42));
+
+        // Sessions are isolated from each other and from one-off evaluations
+        assert!(server.eval_fusabi(json!({ "script": "x" })).await.is_err());
+        assert!(server
+            .eval_fusabi(json!({ "script": "x", "session": "missing" }))
+            .await
+            .is_err());
+
+        let context = server.get_context().unwrap();
+        assert_eq!(context["sessions"][0]["name"], json!("work"));
+        assert_eq!(context["sessions"][0]["bindings"], json!(["x"]));
+        assert_eq!(context["sessions"][0]["evaluations"], json!(2));
+
+        call_tool(&mut server, "reset_session", json!({ "name": "work" })).await;
+        assert!(server
+            .eval_fusabi(json!({ "script": "x", "session": "work" }))
+            .await
+            .is_err());
+
+        call_tool(&mut server, "close_session", json!({ "name": "work" })).await;
+        let listed = call_tool(&mut server, "list_sessions", json!({})).await;
+        assert_eq!(tool_result(&listed), json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_evaluation() {
+        let mut server = McpServer::new();
+        server.current_request = Some("7".to_string());
+        let in_flight = server.in_flight.clone();
+        // As when a `notifications/cancelled` arrives mid-evaluation
+        let cancel = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                in_flight.lock().unwrap()["7"].interrupt();
+            }
+        });
+        let error = server
+            .eval_fusabi(json!({ "script": "while true do ()" }))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("interrupted"), "{}", error);
+        cancel.abort();
+        assert!(server.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cancelled_request() {
+        let cancelled = json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": { "requestId": 7 }
+        });
+        assert_eq!(cancelled_request(&cancelled), Some("7".to_string()));
+        assert_eq!(
+            cancelled_request(&json!({ "method": "ping", "id": 7 })),
+            None
+        );
+    }
 }
@@ -6,20 +6,100 @@
 //! # Usage
 //!
 //! ```bash
-//! fusabi-mcp
+//! fusabi-mcp                      # eval_fusabi and get_context tools
+//! fusabi-mcp --script tools.fsx   # the script's tools, resources and prompts
+//! fusabi-mcp --http 127.0.0.1:8080  # Streamable HTTP instead of stdio
 //! ```
 //!
-//! The server reads JSON-RPC requests from stdin and writes responses to stdout.
-//! All logging and diagnostics are sent to stderr.
+//! By default the server reads JSON-RPC requests from stdin and writes
+//! responses to stdout. All logging and diagnostics are sent to stderr.
 
-use fusabi_mcp::McpServer;
+use fusabi_mcp::{http, McpServer, Script};
+use std::env;
 use std::process;
+use tokio::net::TcpListener;
+
+const USAGE: &str = "Usage: fusabi-mcp [--script <FILE>] [--http <ADDR>]";
 
 #[tokio::main]
 async fn main() {
-    let mut server = McpServer::new();
+    let mut script = None;
+    let mut address = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--script" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--script requires a file\n{}", USAGE);
+                    process::exit(2);
+                };
+                script = Some(path);
+            }
+            "--http" => {
+                let Some(addr) = args.next() else {
+                    eprintln!("--http requires an address, e.g. 127.0.0.1:8080\n{}", USAGE);
+                    process::exit(2);
+                };
+                address = Some(addr);
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}\n{}", arg, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    let Some(address) = address else {
+        let mut server = McpServer::new();
+        if let Some(path) = &script {
+            if let Err(e) = server.load_script(path) {
+                eprintln!("Error: {:#}", e);
+                process::exit(1);
+            }
+        }
+        if let Err(e) = server.run().await {
+            eprintln!("Server error: {}", e);
+            process::exit(1);
+        }
+        return;
+    };
+
+    // Each HTTP session loads the script afresh; check it loads before listening
+    if let Some(path) = &script {
+        if let Err(e) = Script::load(path) {
+            eprintln!("Error: {:#}", e);
+            process::exit(1);
+        }
+    }
+    let listener = match TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Error: failed to listen on {}: {}", address, e);
+            process::exit(1);
+        }
+    };
+    if let Ok(local) = listener.local_addr() {
+        eprintln!(
+            "Fusabi MCP Server listening on http://{}{}",
+            local,
+            http::ENDPOINT
+        );
+    }
 
-    if let Err(e) = server.run().await {
+    let result = http::serve(listener, move || {
+        let mut server = McpServer::new();
+        if let Some(path) = &script {
+            server.load_script(path)?;
+        }
+        Ok(server)
+    })
+    .await;
+    if let Err(e) = result {
         eprintln!("Server error: {}", e);
         process::exit(1);
     }
@@ -0,0 +1,133 @@
+//! Script-defined MCP prompts
+//!
+//! Each binding in a script's `module Prompts` is a prompt template:
+//!
+//! ```fsharp
+//! module Prompts =
+//!     let summarize = "Summarize the conversation so far."
+//!     let review code = "Please review this code:\n" ++ code
+//! ```
+//!
+//! A function's parameters are the prompt's (string) arguments. The result
+//! is a string, sent as one user message, or a list of messages: strings
+//! (user messages) or `(role, text)` tuples.
+
+use crate::script_tools::{apply_curried, describe, lambda_params};
+use anyhow::{anyhow, bail, Result};
+use fusabi::{Engine, Value};
+use fusabi_frontend::{Expr, Type};
+use serde_json::json;
+
+/// Name of the module whose bindings are published as prompts
+pub const PROMPTS_MODULE: &str = "Prompts";
+
+/// A prompt template published by a script
+#[derive(Debug, Clone)]
+pub struct ScriptPrompt {
+    pub name: String,
+    pub description: String,
+    /// Argument names, in order
+    pub arguments: Vec<String>,
+    value: Value,
+}
+
+impl ScriptPrompt {
+    pub(crate) fn new(name: &str, expr: &Expr, value: Value, ty: Option<&Type>) -> Self {
+        let (arguments, _) = lambda_params(expr);
+        ScriptPrompt {
+            name: name.to_string(),
+            description: describe(name, ty),
+            arguments,
+            value,
+        }
+    }
+
+    /// Entry for the `prompts/list` response
+    pub fn definition(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "arguments": self
+                .arguments
+                .iter()
+                .map(|name| json!({ "name": name, "required": true }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// Render the prompt for a `prompts/get` request
+    pub(crate) fn get(
+        &self,
+        engine: &mut Engine,
+        arguments: &serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let args = self
+            .arguments
+            .iter()
+            .map(|name| match arguments.get(name) {
+                Some(serde_json::Value::String(s)) => Ok(Value::Str(s.clone())),
+                Some(other) => Ok(Value::Str(other.to_string())),
+                None => Err(anyhow!("Missing argument '{}'", name)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let result = apply_curried(engine, &self.value, args)?;
+        Ok(json!({
+            "description": self.description,
+            "messages": messages(&result)?,
+        }))
+    }
+}
+
+fn messages(result: &Value) -> Result<Vec<serde_json::Value>> {
+    let message = |role: &str, text: &str| {
+        json!({
+            "role": role,
+            "content": { "type": "text", "text": text },
+        })
+    };
+
+    if let Value::Str(text) = result {
+        return Ok(vec![message("user", text)]);
+    }
+    let items = result
+        .list_to_vec()
+        .ok_or_else(|| anyhow!("Prompt must return a string or a list of messages"))?;
+    items
+        .iter()
+        .map(|item| match item {
+            Value::Str(text) => Ok(message("user", text)),
+            Value::Tuple(pair) => match pair.as_slice() {
+                [Value::Str(role), Value::Str(text)] if role == "user" || role == "assistant" => {
+                    Ok(message(role, text))
+                }
+                _ => bail!("Prompt messages must be (\"user\" | \"assistant\", text) tuples"),
+            },
+            other => bail!("Unexpected prompt message: {}", other),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let single = messages(&Value::Str("hi".to_string())).unwrap();
+        assert_eq!(single[0]["role"], json!("user"));
+        assert_eq!(single[0]["content"]["text"], json!("hi"));
+
+        let conversation = Value::vec_to_cons(vec![
+            Value::Str("question".to_string()),
+            Value::Tuple(vec![
+                Value::Str("assistant".to_string()),
+                Value::Str("answer".to_string()),
+            ]),
+        ]);
+        let many = messages(&conversation).unwrap();
+        assert_eq!(many.len(), 2);
+        assert_eq!(many[1]["role"], json!("assistant"));
+
+        assert!(messages(&Value::Int(1)).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Script-defined MCP resources
+//!
+//! Each binding in a script's `module Resources` is a record describing one
+//! resource or resource template:
+//!
+//! ```fsharp
+//! module Resources =
+//!     let readme = { uri = "docs://readme"; read = fun u -> "# Readme" }
+//!     let note = { uri = "notes://{id}"; description = "A note"; read = fun p -> loadNote p.id }
+//! ```
+//!
+//! `uri` may contain `{name}` placeholders, which makes the resource a
+//! template. `read` receives a record of the placeholder values (empty for
+//! plain resources) and returns the contents: a string is sent as text, any
+//! other value as JSON. `name`, `description` and `mimeType` are optional.
+
+use crate::script_tools::apply_curried;
+use crate::value_to_json;
+use anyhow::{anyhow, bail, Result};
+use fusabi::{Engine, Value};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Name of the module whose bindings are published as resources
+pub const RESOURCES_MODULE: &str = "Resources";
+
+/// One piece of a URI template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Variable(String),
+}
+
+/// URI with `{name}` placeholders (RFC 6570 simple string expansion)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UriTemplate {
+    template: String,
+    segments: Vec<Segment>,
+}
+
+impl UriTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            let close = rest[open..]
+                .find('}')
+                .map(|i| open + i)
+                .ok_or_else(|| anyhow!("Unclosed '{{' in URI template '{}'", template))?;
+            if open > 0 {
+                segments.push(Segment::Literal(rest[..open].to_string()));
+            }
+            let name = &rest[open + 1..close];
+            if name.is_empty() {
+                bail!("Empty placeholder in URI template '{}'", template);
+            }
+            if matches!(segments.last(), Some(Segment::Variable(_))) {
+                bail!("Adjacent placeholders in URI template '{}'", template);
+            }
+            segments.push(Segment::Variable(name.to_string()));
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        Ok(UriTemplate {
+            template: template.to_string(),
+            segments,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Whether the URI has placeholders
+    pub fn is_template(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|s| matches!(s, Segment::Variable(_)))
+    }
+
+    /// Placeholder values if `uri` matches; a placeholder matches up to the
+    /// next literal part and never spans a `/`
+    pub fn matches(&self, uri: &str) -> Option<HashMap<String, String>> {
+        let mut values = HashMap::new();
+        let mut rest = uri;
+        let mut segments = self.segments.iter().peekable();
+        while let Some(segment) = segments.next() {
+            match segment {
+                Segment::Literal(literal) => rest = rest.strip_prefix(literal.as_str())?,
+                Segment::Variable(name) => {
+                    let end = match segments.peek() {
+                        Some(Segment::Literal(next)) => rest.find(next.as_str())?,
+                        _ => rest.len(),
+                    };
+                    let value = &rest[..end];
+                    if value.is_empty() || value.contains('/') {
+                        return None;
+                    }
+                    values.insert(name.clone(), value.to_string());
+                    rest = &rest[end..];
+                }
+            }
+        }
+        rest.is_empty().then_some(values)
+    }
+}
+
+/// A resource (or resource template) published by a script
+#[derive(Debug, Clone)]
+pub struct ScriptResource {
+    pub name: String,
+    pub uri: UriTemplate,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+    read: Value,
+}
+
+impl ScriptResource {
+    /// Build a resource from the record bound to `name`
+    pub(crate) fn from_value(name: &str, value: &Value) -> Result<Self> {
+        if !value.is_record() {
+            bail!(
+                "Resource '{}' must be a record with `uri` and `read` fields",
+                name
+            );
+        }
+        let string_field = |field: &str| -> Result<Option<String>> {
+            match value.record_get(field) {
+                Ok(Value::Str(s)) => Ok(Some(s)),
+                Ok(other) => bail!(
+                    "Resource '{}': `{}` must be a string, got {}",
+                    name,
+                    field,
+                    other.type_name()
+                ),
+                Err(_) => Ok(None),
+            }
+        };
+
+        let uri = string_field("uri")?
+            .ok_or_else(|| anyhow!("Resource '{}' has no `uri` field", name))?;
+        let read = value
+            .record_get("read")
+            .map_err(|_| anyhow!("Resource '{}' has no `read` field", name))?;
+        Ok(ScriptResource {
+            name: string_field("name")?.unwrap_or_else(|| name.to_string()),
+            uri: UriTemplate::parse(&uri)?,
+            description: string_field("description")?,
+            mime_type: string_field("mimeType")?,
+            read,
+        })
+    }
+
+    /// Entry for `resources/list`, or `resources/templates/list` for templates
+    pub fn definition(&self) -> serde_json::Value {
+        let mut definition = json!({ "name": self.name });
+        let key = if self.uri.is_template() {
+            "uriTemplate"
+        } else {
+            "uri"
+        };
+        definition[key] = json!(self.uri.as_str());
+        if let Some(description) = &self.description {
+            definition["description"] = json!(description);
+        }
+        if let Some(mime_type) = &self.mime_type {
+            definition["mimeType"] = json!(mime_type);
+        }
+        definition
+    }
+
+    /// Read `uri` (which must match this resource) into a `resources/read` content entry
+    pub(crate) fn read(&self, engine: &mut Engine, uri: &str) -> Result<serde_json::Value> {
+        let params = self
+            .uri
+            .matches(uri)
+            .ok_or_else(|| anyhow!("'{}' does not match '{}'", uri, self.uri.as_str()))?;
+        let params: HashMap<String, Value> = params
+            .into_iter()
+            .map(|(k, v)| (k, Value::Str(v)))
+            .collect();
+        let arg = Value::Record(Arc::new(Mutex::new(params)));
+
+        let (text, mime_type) = match apply_curried(engine, &self.read, vec![arg])? {
+            Value::Str(text) => (text, "text/plain"),
+            other => (
+                serde_json::to_string_pretty(&value_to_json(&other))?,
+                "application/json",
+            ),
+        };
+        Ok(json!({
+            "uri": uri,
+            "mimeType": self.mime_type.as_deref().unwrap_or(mime_type),
+            "text": text,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_template_matching() {
+        let template = UriTemplate::parse("notes://{folder}/{id}.md").unwrap();
+        assert!(template.is_template());
+
+        let values = template.matches("notes://work/42.md").unwrap();
+        assert_eq!(values["folder"], "work");
+        assert_eq!(values["id"], "42");
+
+        assert!(template.matches("notes://work/42.txt").is_none());
+        assert!(template.matches("notes://a/b/42.md").is_none());
+        assert!(template.matches("notes:///42.md").is_none());
+    }
+
+    #[test]
+    fn test_plain_uri() {
+        let uri = UriTemplate::parse("docs://readme").unwrap();
+        assert!(!uri.is_template());
+        assert_eq!(uri.matches("docs://readme"), Some(HashMap::new()));
+        assert!(uri.matches("docs://readme2").is_none());
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(UriTemplate::parse("notes://{id").is_err());
+        assert!(UriTemplate::parse("notes://{}").is_err());
+        assert!(UriTemplate::parse("notes://{a}{b}").is_err());
+    }
+}
@@ -0,0 +1,312 @@
+//! MCP servers written in Fusabi
+//!
+//! A script publishes tools (see [`script_tools`](crate::script_tools)),
+//! resources (see [`resources`](crate::resources)) and prompts (see
+//! [`prompts`](crate::prompts)). Its bindings are evaluated once, in one
+//! engine, which then serves every request.
+//!
+//! A script signals that a resource changed with
+//! `Events.emit "resources/updated" uri`; clients subscribed to that URI are
+//! sent `notifications/resources/updated`.
+
+use crate::prompts::{ScriptPrompt, PROMPTS_MODULE};
+use crate::resources::{ScriptResource, RESOURCES_MODULE};
+use crate::script_tools::{find_tools, infer_types, InvalidArguments, ScriptTool, TOOLS_MODULE};
+use anyhow::{anyhow, bail, Context, Result};
+use fusabi::{Engine, RunOptions, Value};
+use fusabi_frontend::{Expr, Lexer, ModuleItem, Parser};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, Once};
+
+/// Event a script emits, with the resource URI, when a resource changes
+pub const RESOURCE_UPDATED_EVENT: &str = "resources/updated";
+
+/// Host function handling [`RESOURCE_UPDATED_EVENT`] in each script's engine
+const RESOURCE_UPDATED_FN: &str = "Mcp.resourceUpdated";
+
+/// Event handlers are process-wide, so the handler is registered once and
+/// dispatches to whichever engine emitted the event
+static RESOURCE_UPDATED_HANDLER: Once = Once::new();
+
+/// A loaded script and the engine serving it
+pub struct Script {
+    engine: Engine,
+    tools: Vec<ScriptTool>,
+    resources: Vec<ScriptResource>,
+    prompts: Vec<ScriptPrompt>,
+    /// URIs of resources updated since the last [`Script::take_updated`]
+    updated: Arc<Mutex<Vec<String>>>,
+}
+
+impl Script {
+    /// Load a `.fsx` file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_source(&source).with_context(|| format!("Failed to load {}", path.display()))
+    }
+
+    /// Load script source
+    ///
+    /// The script's top-level bindings are evaluated once; its main expression,
+    /// if any, is not run.
+    pub fn from_source(source: &str) -> Result<Self> {
+        let tokens = Lexer::new(source)
+            .tokenize()
+            .map_err(|e| anyhow!("Lexer error: {}", e))?;
+        let mut program = Parser::new(tokens)
+            .parse_program()
+            .map_err(|e| anyhow!("Parse error: {}", e))?;
+
+        let tools = find_tools(&program)?;
+        let module_bindings = |module: &str| -> Vec<(String, Expr)> {
+            program
+                .modules
+                .iter()
+                .filter(|m| m.name == module)
+                .flat_map(|m| &m.items)
+                .filter_map(|item| match item {
+                    ModuleItem::Let(Some(name), value) => Some((name.clone(), value.clone())),
+                    _ => None,
+                })
+                .collect()
+        };
+        let resources = module_bindings(RESOURCES_MODULE);
+        let prompts = module_bindings(PROMPTS_MODULE);
+        if tools.is_empty() && resources.is_empty() && prompts.is_empty() {
+            bail!(
+                "No tools, resources or prompts found: annotate a top-level function's return type or define modules {}, {} or {}",
+                TOOLS_MODULE,
+                RESOURCES_MODULE,
+                PROMPTS_MODULE
+            );
+        }
+
+        let mut engine = Engine::new();
+        let updated = Arc::new(Mutex::new(Vec::new()));
+        register_update_handler(&mut engine, updated.clone())?;
+        let types = infer_types(
+            &program,
+            &[TOOLS_MODULE, PROMPTS_MODULE],
+            &engine.host_function_names(),
+        );
+
+        // Evaluate the bindings once, collecting the published values in a list
+        let paths: Vec<String> = tools
+            .iter()
+            .map(|c| c.path.clone())
+            .chain(
+                resources
+                    .iter()
+                    .map(|(name, _)| format!("{}.{}", RESOURCES_MODULE, name)),
+            )
+            .chain(
+                prompts
+                    .iter()
+                    .map(|(name, _)| format!("{}.{}", PROMPTS_MODULE, name)),
+            )
+            .collect();
+        program.main_expr = Some(Expr::List(paths.into_iter().map(Expr::Var).collect()));
+        let mut values = engine
+            .eval_program(&program, RunOptions::default())
+            .map_err(|e| anyhow!("{}", e))?
+            .list_to_vec()
+            .ok_or_else(|| anyhow!("Script did not evaluate to its bindings"))?
+            .into_iter();
+
+        let tools = tools
+            .into_iter()
+            .zip(values.by_ref())
+            .map(|(candidate, function)| {
+                let ty = types.get(&candidate.path);
+                ScriptTool::new(candidate, function, ty)
+            })
+            .collect();
+        let resources = resources
+            .iter()
+            .zip(values.by_ref())
+            .map(|((name, _), value)| ScriptResource::from_value(name, &value))
+            .collect::<Result<Vec<_>>>()?;
+        let prompts = prompts
+            .iter()
+            .zip(values)
+            .map(|((name, expr), value)| {
+                let ty = types.get(&format!("{}.{}", PROMPTS_MODULE, name));
+                ScriptPrompt::new(name, expr, value, ty)
+            })
+            .collect();
+
+        Ok(Script {
+            engine,
+            tools,
+            resources,
+            prompts,
+            updated,
+        })
+    }
+
+    /// Published tools, in definition order
+    pub fn tools(&self) -> &[ScriptTool] {
+        &self.tools
+    }
+
+    /// Look up a tool by name
+    pub fn tool(&self, name: &str) -> Option<&ScriptTool> {
+        self.tools.iter().find(|tool| tool.name == name)
+    }
+
+    /// Call a tool with the `arguments` of a `tools/call` request
+    pub fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: &serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let tool = self
+            .tools
+            .iter()
+            .find(|tool| tool.name == name)
+            .ok_or_else(|| InvalidArguments(format!("Unknown tool: {}", name)))?;
+        tool.call(&mut self.engine, arguments)
+    }
+
+    /// Published resources and resource templates, in definition order
+    pub fn resources(&self) -> &[ScriptResource] {
+        &self.resources
+    }
+
+    /// Read a resource for `resources/read`; `None` if no resource matches `uri`
+    ///
+    /// Plain resources take precedence over templates.
+    pub fn read_resource(&mut self, uri: &str) -> Option<Result<serde_json::Value>> {
+        let resource = self
+            .resources
+            .iter()
+            .find(|r| !r.uri.is_template() && r.uri.as_str() == uri)
+            .or_else(|| {
+                self.resources
+                    .iter()
+                    .find(|r| r.uri.is_template() && r.uri.matches(uri).is_some())
+            })?;
+        Some(resource.read(&mut self.engine, uri))
+    }
+
+    /// Published prompts, in definition order
+    pub fn prompts(&self) -> &[ScriptPrompt] {
+        &self.prompts
+    }
+
+    /// Render a prompt for `prompts/get`; `None` if there is no such prompt
+    pub fn get_prompt(
+        &mut self,
+        name: &str,
+        arguments: &serde_json::Value,
+    ) -> Option<Result<serde_json::Value>> {
+        let prompt = self.prompts.iter().find(|p| p.name == name)?;
+        Some(prompt.get(&mut self.engine, arguments))
+    }
+
+    /// URIs the script reported as updated since the last call
+    pub fn take_updated(&self) -> Vec<String> {
+        std::mem::take(&mut *self.updated.lock().unwrap())
+    }
+}
+
+fn register_update_handler(engine: &mut Engine, updated: Arc<Mutex<Vec<String>>>) -> Result<()> {
+    engine.register(RESOURCE_UPDATED_FN, move |args| {
+        if let [Value::Str(uri)] = args {
+            updated.lock().unwrap().push(uri.clone());
+        }
+        Ok(Value::Unit)
+    });
+
+    let mut result = Ok(());
+    RESOURCE_UPDATED_HANDLER.call_once(|| {
+        let handler = Value::NativeFn {
+            name: RESOURCE_UPDATED_FN.to_string(),
+            arity: 1,
+            args: vec![],
+        };
+        result = engine
+            .call_host(
+                "Events.on",
+                &[Value::Str(RESOURCE_UPDATED_EVENT.to_string()), handler],
+            )
+            .map(|_| ())
+            .map_err(|e| anyhow!("Failed to subscribe to resource updates: {}", e));
+    });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SCRIPT: &str = r##"
+module Tools =
+    let touch id = Events.emit "resources/updated" ("notes://" ++ id)
+
+module Resources =
+    let readme = { uri = "docs://readme"; description = "Read me"; read = fun u -> "# Notes" }
+    let note = { uri = "notes://{id}"; read = fun p -> "Note " ++ p.id }
+    let stats = { uri = "notes://stats"; read = fun u -> [1; 2] }
+
+module Prompts =
+    let summarize = "Summarize the notes."
+    let review note = [("assistant", "Ready."); ("user", "Review " ++ note)]
+"##;
+
+    #[test]
+    fn test_loads_tools_resources_and_prompts() {
+        let script = Script::from_source(SCRIPT).unwrap();
+        assert_eq!(script.tools().len(), 1);
+        let resources: Vec<&str> = script.resources().iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(resources, vec!["readme", "note", "stats"]);
+        let prompts: Vec<&str> = script.prompts().iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(prompts, vec!["summarize", "review"]);
+        assert_eq!(script.prompts()[1].arguments, vec!["note"]);
+    }
+
+    #[test]
+    fn test_read_resources() {
+        let mut script = Script::from_source(SCRIPT).unwrap();
+
+        let readme = script.read_resource("docs://readme").unwrap().unwrap();
+        assert_eq!(readme["text"], json!("# Notes"));
+        assert_eq!(readme["mimeType"], json!("text/plain"));
+
+        let note = script.read_resource("notes://7").unwrap().unwrap();
+        assert_eq!(note["text"], json!("Note 7"));
+
+        // The plain resource wins over the template
+        let stats = script.read_resource("notes://stats").unwrap().unwrap();
+        assert_eq!(stats["mimeType"], json!("application/json"));
+
+        assert!(script.read_resource("other://x").is_none());
+    }
+
+    #[test]
+    fn test_get_prompt() {
+        let mut script = Script::from_source(SCRIPT).unwrap();
+        let review = script
+            .get_prompt("review", &json!({ "note": "this" }))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            review["messages"][1]["content"]["text"],
+            json!("Review this")
+        );
+        assert!(script.get_prompt("review", &json!({})).unwrap().is_err());
+        assert!(script.get_prompt("missing", &json!({})).is_none());
+    }
+
+    #[test]
+    fn test_events_report_updated_resources() {
+        let mut script = Script::from_source(SCRIPT).unwrap();
+        script.call_tool("touch", &json!({ "id": "7" })).unwrap();
+        assert_eq!(script.take_updated(), vec!["notes://7"]);
+        assert!(script.take_updated().is_empty());
+    }
+}
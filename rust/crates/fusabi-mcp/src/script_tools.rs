@@ -0,0 +1,616 @@
+//! Script-defined MCP tools
+//!
+//! A script (see [`Script`](crate::Script)) publishes its functions as MCP tools:
+//!
+//! - every top-level function whose return type is annotated
+//!   (`let greet name : string = ...`), and
+//! - every function in a top-level `module Tools`.
+//!
+//! Each tool's `inputSchema` is derived from the function's inferred parameter
+//! types. A function taking a single record is described by the record's fields;
+//! otherwise each parameter becomes a property named after it. Parameters whose
+//! type could not be inferred accept any JSON value.
+
+use crate::value_to_json;
+use anyhow::{anyhow, bail, Context, Result};
+use fusabi::{Engine, Value};
+use fusabi_frontend::{
+    Expr, ModuleItem, ModuleRegistry, Program, Substitution, Type, TypeEnv, TypeInference,
+    TypeScheme, TypeVar,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Name of the module whose functions are all published as tools
+pub const TOOLS_MODULE: &str = "Tools";
+
+/// How a tool's JSON arguments map onto the function's parameters
+#[derive(Debug, Clone)]
+enum Arguments {
+    /// One property per parameter, in order
+    Named(Vec<(String, Option<Type>)>),
+    /// A single record parameter built from the whole arguments object
+    Record(HashMap<String, Type>),
+}
+
+/// Arguments of a `tools/call` that do not fit the tool's parameters,
+/// answered with an "Invalid params" error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidArguments(pub String);
+
+impl fmt::Display for InvalidArguments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidArguments {}
+
+/// A function published as an MCP tool
+#[derive(Debug, Clone)]
+pub struct ScriptTool {
+    pub name: String,
+    pub description: String,
+    pub input_schema: serde_json::Value,
+    arguments: Arguments,
+    function: Value,
+}
+
+impl ScriptTool {
+    pub(crate) fn new(candidate: Candidate, function: Value, ty: Option<&Type>) -> Self {
+        let arguments = arguments_for(&candidate.params, ty);
+        ScriptTool {
+            description: describe(&candidate.name, ty),
+            input_schema: input_schema(&arguments),
+            name: candidate.name,
+            arguments,
+            function,
+        }
+    }
+
+    /// Call the tool with the `arguments` of a `tools/call` request
+    pub(crate) fn call(
+        &self,
+        engine: &mut Engine,
+        arguments: &serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let args = convert_arguments(&self.arguments, arguments)
+            .map_err(|e| InvalidArguments(format!("{:#}", e)))?;
+        let result = apply_curried(engine, &self.function, args)?;
+        Ok(value_to_json(&result))
+    }
+
+    /// Entry for the `tools/list` response
+    pub fn definition(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "inputSchema": self.input_schema,
+        })
+    }
+}
+
+/// A binding selected as a tool or prompt, before evaluation
+pub(crate) struct Candidate {
+    pub(crate) name: String,
+    /// Name the binding is reachable under from the top level
+    pub(crate) path: String,
+    pub(crate) params: Vec<String>,
+}
+
+/// `name : type`, or just the name when the type is unknown
+pub(crate) fn describe(name: &str, ty: Option<&Type>) -> String {
+    match ty {
+        Some(ty) => format!("{} : {}", name, readable(ty)),
+        None => name.to_string(),
+    }
+}
+
+/// `ty` with its type variables named `'a`, `'b`, ... in order of appearance,
+/// rather than after inference's internal numbering
+fn readable(ty: &Type) -> Type {
+    fn collect(ty: &Type, vars: &mut Vec<TypeVar>) {
+        match ty {
+            Type::Var(var) => {
+                if !vars.contains(var) {
+                    vars.push(var.clone());
+                }
+            }
+            Type::Int | Type::Bool | Type::String | Type::Unit | Type::Float => {}
+            Type::Tuple(types) | Type::Variant(_, types) => {
+                types.iter().for_each(|ty| collect(ty, vars))
+            }
+            Type::List(elem) | Type::Array(elem) => collect(elem, vars),
+            Type::Function(arg, ret) => {
+                collect(arg, vars);
+                collect(ret, vars);
+            }
+            Type::Record(fields) => {
+                let mut names: Vec<&String> = fields.keys().collect();
+                names.sort();
+                names
+                    .into_iter()
+                    .for_each(|name| collect(&fields[name], vars));
+            }
+        }
+    }
+
+    let mut vars = Vec::new();
+    collect(ty, &mut vars);
+    let mut subst = Substitution::empty();
+    for (index, var) in vars.into_iter().enumerate() {
+        let name = match index {
+            0..=25 => char::from(b'a' + index as u8).to_string(),
+            _ => format!("a{}", index),
+        };
+        let renamed = Type::Var(TypeVar::new(var.id, name));
+        subst.insert(var, renamed);
+    }
+    ty.apply(&subst)
+}
+
+/// Apply a curried function to its arguments one at a time
+pub(crate) fn apply_curried(
+    engine: &mut Engine,
+    function: &Value,
+    args: Vec<Value>,
+) -> Result<Value> {
+    let mut result = function.clone();
+    for arg in args {
+        result = engine
+            .apply(result, &[arg])
+            .map_err(|e| anyhow!("Execution error: {}", e))?;
+    }
+    Ok(result)
+}
+
+pub(crate) fn find_tools(program: &Program) -> Result<Vec<Candidate>> {
+    let mut candidates: Vec<Candidate> = Vec::new();
+
+    for item in &program.items {
+        let (name, value) = match item {
+            ModuleItem::Let(Some(name), value) => (name, value),
+            ModuleItem::LetRec(bindings) if bindings.len() == 1 => (&bindings[0].0, &bindings[0].1),
+            _ => continue,
+        };
+        let (params, body) = lambda_params(value);
+        if !params.is_empty() && body.is_annotated() {
+            candidates.push(Candidate {
+                name: name.clone(),
+                path: name.clone(),
+                params,
+            });
+        }
+    }
+
+    for module in program.modules.iter().filter(|m| m.name == TOOLS_MODULE) {
+        for item in &module.items {
+            if let ModuleItem::Let(Some(name), value) = item {
+                let (params, _) = lambda_params(value);
+                if !params.is_empty() {
+                    candidates.push(Candidate {
+                        name: name.clone(),
+                        path: format!("{}.{}", TOOLS_MODULE, name),
+                        params,
+                    });
+                }
+            }
+        }
+    }
+
+    for (i, candidate) in candidates.iter().enumerate() {
+        if candidates[..i].iter().any(|c| c.name == candidate.name) {
+            bail!("Tool '{}' is defined more than once", candidate.name);
+        }
+    }
+    Ok(candidates)
+}
+
+/// Parameter names of a curried function and its innermost body
+pub(crate) fn lambda_params(mut expr: &Expr) -> (Vec<String>, &Expr) {
+    let mut params = Vec::new();
+    loop {
+        match expr {
+            Expr::Lambda { param, body } => {
+                params.push(param.clone());
+                expr = body;
+            }
+            Expr::Spanned { expr: inner, .. } => expr = inner,
+            _ => return (params, expr),
+        }
+    }
+}
+
+/// Best-effort types of the top-level bindings and those of `modules`, keyed by path
+///
+/// Bindings that fail to type check are left out; their tools accept any
+/// arguments.
+pub(crate) fn infer_types(
+    program: &Program,
+    modules: &[&str],
+    host_functions: &[String],
+) -> HashMap<String, Type> {
+    let mut registry = ModuleRegistry::with_stdlib();
+    for module in &program.modules {
+        registry.register_module_def(module);
+    }
+    let mut inference = TypeInference::with_module_registry(registry);
+
+    // Host functions and their modules can be used at any type
+    let any = TypeVar::new(0, "a");
+    let mut env = TypeEnv::new();
+    for name in host_functions {
+        let name = name.split('.').next().unwrap_or(name);
+        env.insert(
+            name.to_string(),
+            TypeScheme::poly(vec![any.clone()], Type::Var(any.clone())),
+        );
+    }
+
+    let mut types = HashMap::new();
+    let mut infer = |prefix: Option<&str>, items: &[ModuleItem], env: &mut TypeEnv| {
+        for item in items {
+            let (name, value) = match item {
+                ModuleItem::Let(Some(name), value) => (name, value.clone()),
+                ModuleItem::LetRec(bindings) if bindings.len() == 1 => {
+                    let (name, value) = &bindings[0];
+                    (
+                        name,
+                        Expr::LetRec {
+                            name: name.clone(),
+                            value: Box::new(value.clone()),
+                            body: Box::new(Expr::Var(name.clone())),
+                        },
+                    )
+                }
+                _ => continue,
+            };
+            if let Ok(ty) = inference.infer_and_solve(&value, env) {
+                env.insert(name.clone(), env.generalize(&ty));
+                let path = match prefix {
+                    Some(prefix) => format!("{}.{}", prefix, name),
+                    None => name.clone(),
+                };
+                types.insert(path, ty);
+            }
+        }
+    };
+
+    for module in program
+        .modules
+        .iter()
+        .filter(|m| modules.contains(&m.name.as_str()))
+    {
+        infer(Some(&module.name), &module.items, &mut env.clone());
+    }
+    infer(None, &program.items, &mut env);
+    types
+}
+
+fn arguments_for(params: &[String], ty: Option<&Type>) -> Arguments {
+    let mut param_types = Vec::new();
+    let mut current = ty;
+    for _ in params {
+        match current {
+            Some(Type::Function(param, result)) => {
+                param_types.push(Some(param.as_ref().clone()));
+                current = Some(result);
+            }
+            _ => {
+                param_types.push(None);
+                current = None;
+            }
+        }
+    }
+
+    if let [Some(Type::Record(fields))] = param_types.as_slice() {
+        return Arguments::Record(fields.clone());
+    }
+    Arguments::Named(params.iter().cloned().zip(param_types).collect())
+}
+
+fn input_schema(arguments: &Arguments) -> serde_json::Value {
+    match arguments {
+        Arguments::Record(fields) => type_schema(&Type::Record(fields.clone())),
+        Arguments::Named(params) => {
+            let mut properties = serde_json::Map::new();
+            let mut required = Vec::new();
+            for (name, ty) in params {
+                match ty {
+                    // Unit parameters (`let f () = ...`) take no argument
+                    Some(Type::Unit) => {}
+                    Some(ty) => {
+                        properties.insert(name.clone(), type_schema(ty));
+                        required.push(name.clone());
+                    }
+                    None => {
+                        properties.insert(name.clone(), json!({}));
+                        required.push(name.clone());
+                    }
+                }
+            }
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            })
+        }
+    }
+}
+
+/// JSON Schema describing values of `ty`
+pub fn type_schema(ty: &Type) -> serde_json::Value {
+    match ty {
+        Type::Int => json!({ "type": "integer" }),
+        Type::Float => json!({ "type": "number" }),
+        Type::Bool => json!({ "type": "boolean" }),
+        Type::String => json!({ "type": "string" }),
+        Type::Unit => json!({ "type": "null" }),
+        Type::List(elem) | Type::Array(elem) => json!({
+            "type": "array",
+            "items": type_schema(elem),
+        }),
+        Type::Tuple(elems) => json!({
+            "type": "array",
+            "prefixItems": elems.iter().map(type_schema).collect::<Vec<_>>(),
+            "minItems": elems.len(),
+            "maxItems": elems.len(),
+        }),
+        Type::Record(fields) => {
+            let mut names: Vec<&String> = fields.keys().collect();
+            names.sort();
+            let properties: serde_json::Map<String, serde_json::Value> = names
+                .iter()
+                .map(|name| (name.to_string(), type_schema(&fields[*name])))
+                .collect();
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": names,
+            })
+        }
+        // Type variables, functions and unions are not constrained
+        Type::Var(_) | Type::Function(_, _) | Type::Variant(_, _) => json!({}),
+    }
+}
+
+fn convert_arguments(arguments: &Arguments, json: &serde_json::Value) -> Result<Vec<Value>> {
+    let object = match json {
+        serde_json::Value::Object(object) => object.clone(),
+        serde_json::Value::Null => serde_json::Map::new(),
+        _ => bail!("Tool arguments must be an object"),
+    };
+
+    match arguments {
+        Arguments::Record(fields) => Ok(vec![json_to_value(
+            &serde_json::Value::Object(object),
+            &Type::Record(fields.clone()),
+        )?]),
+        Arguments::Named(params) => params
+            .iter()
+            .map(|(name, ty)| match (object.get(name), ty) {
+                (None, Some(Type::Unit)) => Ok(Value::Unit),
+                (None, _) => Err(anyhow!("Missing argument '{}'", name)),
+                (Some(value), Some(ty)) => {
+                    json_to_value(value, ty).with_context(|| format!("Invalid argument '{}'", name))
+                }
+                (Some(value), None) => Ok(untyped_json_to_value(value)),
+            })
+            .collect(),
+    }
+}
+
+/// Convert a JSON value to a Fusabi value of type `ty`
+pub fn json_to_value(json: &serde_json::Value, ty: &Type) -> Result<Value> {
+    let expected = |what: &str| anyhow!("expected {}, got {}", what, json);
+    match ty {
+        Type::Int => json
+            .as_i64()
+            .map(Value::Int)
+            .ok_or_else(|| expected("an integer")),
+        Type::Float => json
+            .as_f64()
+            .map(Value::Float)
+            .ok_or_else(|| expected("a number")),
+        Type::Bool => json
+            .as_bool()
+            .map(Value::Bool)
+            .ok_or_else(|| expected("a boolean")),
+        Type::String => json
+            .as_str()
+            .map(|s| Value::Str(s.to_string()))
+            .ok_or_else(|| expected("a string")),
+        Type::Unit => Ok(Value::Unit),
+        Type::List(elem) | Type::Array(elem) => {
+            let items = json
+                .as_array()
+                .ok_or_else(|| expected("an array"))?
+                .iter()
+                .map(|item| json_to_value(item, elem))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(match ty {
+                Type::List(_) => Value::vec_to_cons(items),
+                _ => Value::Array(Arc::new(Mutex::new(items))),
+            })
+        }
+        Type::Tuple(elems) => {
+            let items = json.as_array().ok_or_else(|| expected("an array"))?;
+            if items.len() != elems.len() {
+                return Err(expected(&format!("{} elements", elems.len())));
+            }
+            Ok(Value::Tuple(
+                items
+                    .iter()
+                    .zip(elems)
+                    .map(|(item, elem)| json_to_value(item, elem))
+                    .collect::<Result<Vec<_>>>()?,
+            ))
+        }
+        Type::Record(fields) => {
+            let object = json.as_object().ok_or_else(|| expected("an object"))?;
+            let mut record = HashMap::new();
+            for (name, field_ty) in fields {
+                let value = object
+                    .get(name)
+                    .ok_or_else(|| anyhow!("missing field '{}'", name))?;
+                let value =
+                    json_to_value(value, field_ty).with_context(|| format!("field '{}'", name))?;
+                record.insert(name.clone(), value);
+            }
+            Ok(Value::Record(Arc::new(Mutex::new(record))))
+        }
+        Type::Var(_) => Ok(untyped_json_to_value(json)),
+        Type::Function(_, _) | Type::Variant(_, _) => {
+            bail!("values of type {} cannot be passed as JSON", ty)
+        }
+    }
+}
+
+/// Convert a JSON value by its shape: arrays become lists, objects records
+fn untyped_json_to_value(json: &serde_json::Value) -> Value {
+    match json {
+        serde_json::Value::Null => Value::Unit,
+        serde_json::Value::Bool(b) => Value::Bool(*b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Int(i),
+            None => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => Value::Str(s.clone()),
+        serde_json::Value::Array(items) => {
+            Value::vec_to_cons(items.iter().map(untyped_json_to_value).collect())
+        }
+        serde_json::Value::Object(object) => Value::Record(Arc::new(Mutex::new(
+            object
+                .iter()
+                .map(|(k, v)| (k.clone(), untyped_json_to_value(v)))
+                .collect(),
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Script;
+
+    const SCRIPT: &str = r#"
+let square n : int = n * n
+let greet p : string = "Hello, " ++ p.name
+let helper x = x + 1
+
+square 4
+"#;
+
+    #[test]
+    fn test_discovers_annotated_functions() {
+        let tools = Script::from_source(SCRIPT).unwrap();
+        let names: Vec<&str> = tools.tools().iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["square", "greet"]);
+    }
+
+    #[test]
+    fn test_tools_module() {
+        let source =
+            "module Tools =\n    let shout s = String.toUpper s\n    let double n = n * 2\n";
+        let mut tools = Script::from_source(source).unwrap();
+        let names: Vec<&str> = tools.tools().iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["shout", "double"]);
+        assert_eq!(
+            tools.call_tool("shout", &json!({ "s": "hi" })).unwrap(),
+            json!("HI")
+        );
+        assert_eq!(
+            tools.call_tool("double", &json!({ "n": 21 })).unwrap(),
+            json!(42)
+        );
+    }
+
+    #[test]
+    fn test_input_schema_from_inferred_types() {
+        let tools = Script::from_source(SCRIPT).unwrap();
+        let square = tools.tool("square").unwrap();
+        assert_eq!(
+            square.input_schema,
+            json!({
+                "type": "object",
+                "properties": { "n": { "type": "integer" } },
+                "required": ["n"]
+            })
+        );
+        assert_eq!(square.description, "square : int -> int");
+
+        // A single record parameter is described by its fields
+        let greet = tools.tool("greet").unwrap();
+        assert_eq!(
+            greet.input_schema["properties"]["name"],
+            json!({ "type": "string" })
+        );
+    }
+
+    #[test]
+    fn test_call_tools() {
+        let mut tools = Script::from_source(SCRIPT).unwrap();
+        assert_eq!(
+            tools.call_tool("square", &json!({ "n": 7 })).unwrap(),
+            json!(49)
+        );
+        assert_eq!(
+            tools.call_tool("greet", &json!({ "name": "Ada" })).unwrap(),
+            json!("Hello, Ada")
+        );
+    }
+
+    #[test]
+    fn test_call_rejects_bad_arguments() {
+        let mut tools = Script::from_source(SCRIPT).unwrap();
+        assert!(tools.call_tool("square", &json!({})).is_err());
+        assert!(tools.call_tool("square", &json!({ "n": "seven" })).is_err());
+        assert!(tools.call_tool("helper", &json!({ "x": 1 })).is_err());
+
+        let error = tools
+            .call_tool("square", &json!({ "n": "seven" }))
+            .unwrap_err();
+        let invalid = error.downcast_ref::<InvalidArguments>().unwrap();
+        assert_eq!(
+            invalid.0,
+            "Invalid argument 'n': expected an integer, got \"seven\""
+        );
+    }
+
+    #[test]
+    fn test_descriptions_name_type_variables() {
+        let source = "module Tools =\n    let pair x y = (y, x)\n    let wrap x = [x]\n";
+        let tools = Script::from_source(source).unwrap();
+        assert_eq!(
+            tools.tool("pair").unwrap().description,
+            "pair : 'a -> 'b -> ('b * 'a)"
+        );
+        assert_eq!(
+            tools.tool("wrap").unwrap().description,
+            "wrap : 'a -> 'a list"
+        );
+    }
+
+    #[test]
+    fn test_script_without_tools() {
+        assert!(Script::from_source("let x = 1\nx").is_err());
+    }
+
+    #[test]
+    fn test_json_to_value() {
+        let list = json_to_value(&json!([1, 2]), &Type::List(Box::new(Type::Int))).unwrap();
+        assert_eq!(list.list_to_vec(), Some(vec![Value::Int(1), Value::Int(2)]));
+        let tuple = json_to_value(
+            &json!([1, "a"]),
+            &Type::Tuple(vec![Type::Int, Type::String]),
+        )
+        .unwrap();
+        assert_eq!(
+            tuple,
+            Value::Tuple(vec![Value::Int(1), Value::Str("a".to_string())])
+        );
+        assert!(json_to_value(&json!(1.5), &Type::Int).is_err());
+    }
+}
@@ -0,0 +1,475 @@
+//! Evaluation workers and named sessions
+//!
+//! Every evaluation runs on a worker thread that owns its engine, so the
+//! server can stop waiting once the timeout passes and interrupt the VM. A
+//! [`Session`] keeps its worker between calls: the top-level bindings of each
+//! script it evaluates stay defined for the next one. Evaluations outside
+//! sessions share the workers of a [`WorkerPool`], which start every script
+//! in a fresh engine.
+//!
+//! Each evaluation has an [`Interrupt`] of its own, so interrupting one that
+//! already finished (say, when its timeout fires just as it returns) cannot
+//! stop the worker's next evaluation.
+//!
+//! The VM is interrupted between instructions; a script blocked inside a host
+//! function keeps its worker busy until that function returns.
+
+use crate::value_to_json;
+use anyhow::{anyhow, Result};
+use fusabi::{Engine, ExecutionLimits, Interrupt, RunOptions, Value};
+use fusabi_frontend::{Expr, Lexer, Literal, ModuleItem, Parser};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// Limits applied to every evaluation in a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionLimits {
+    /// Wall time an evaluation may take
+    pub timeout: Duration,
+    /// Instructions an evaluation may execute
+    pub max_instructions: Option<u64>,
+    /// Frames that may be active at once
+    pub max_call_depth: Option<usize>,
+}
+
+impl SessionLimits {
+    /// Only a wall-clock timeout
+    pub fn with_timeout(timeout: Duration) -> Self {
+        SessionLimits {
+            timeout,
+            max_instructions: None,
+            max_call_depth: None,
+        }
+    }
+
+    fn execution_limits(&self) -> ExecutionLimits {
+        ExecutionLimits {
+            max_instructions: self.max_instructions,
+            max_call_depth: self.max_call_depth,
+            timeout: Some(self.timeout),
+        }
+    }
+
+    fn to_json(self) -> serde_json::Value {
+        json!({
+            "timeout_ms": self.timeout.as_millis() as u64,
+            "max_instructions": self.max_instructions,
+            "max_call_depth": self.max_call_depth,
+        })
+    }
+}
+
+/// Result of one evaluation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub value: serde_json::Value,
+    /// Top-level names the script bound
+    pub bindings: Vec<String>,
+}
+
+struct Job {
+    source: String,
+    interrupt: Interrupt,
+    reply: oneshot::Sender<Result<Evaluation, String>>,
+}
+
+/// A thread owning an engine, evaluating scripts one at a time
+pub struct Worker {
+    jobs: mpsc::UnboundedSender<Job>,
+    /// Interrupt of the evaluation in progress
+    running: Arc<Mutex<Option<Interrupt>>>,
+    limits: SessionLimits,
+    /// Jobs sent and not yet finished
+    pending: Arc<AtomicUsize>,
+}
+
+impl Worker {
+    /// Start a worker with a fresh engine, kept for every script
+    pub fn spawn(limits: SessionLimits) -> Result<Self> {
+        Self::start(limits, false)
+    }
+
+    /// Start a worker evaluating each script in a fresh engine
+    pub fn spawn_fresh(limits: SessionLimits) -> Result<Self> {
+        Self::start(limits, true)
+    }
+
+    fn start(limits: SessionLimits, fresh: bool) -> Result<Self> {
+        let mut engine = new_engine(limits);
+        let (jobs, mut queue) = mpsc::unbounded_channel::<Job>();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let running = Arc::new(Mutex::new(None));
+
+        let current = running.clone();
+        let finished = pending.clone();
+        thread::Builder::new()
+            .name("fusabi-eval".to_string())
+            .spawn(move || {
+                let mut used = false;
+                while let Some(job) = queue.blocking_recv() {
+                    // Skip jobs whose caller already gave up waiting
+                    if job.reply.is_closed() {
+                        finished.fetch_sub(1, Ordering::SeqCst);
+                        continue;
+                    }
+                    if fresh && used {
+                        engine = new_engine(limits);
+                    }
+                    used = true;
+                    engine.set_interrupt_handle(job.interrupt.clone());
+                    *current.lock().unwrap() = Some(job.interrupt);
+                    let result = evaluate(&mut engine, &job.source);
+                    *current.lock().unwrap() = None;
+                    // Idle again before the caller hears back
+                    finished.fetch_sub(1, Ordering::SeqCst);
+                    let _ = job.reply.send(result);
+                }
+            })
+            .map_err(|e| anyhow!("Failed to start evaluation worker: {}", e))?;
+
+        Ok(Worker {
+            jobs,
+            running,
+            limits,
+            pending,
+        })
+    }
+
+    /// Whether no evaluation is queued or running
+    pub fn is_idle(&self) -> bool {
+        self.pending.load(Ordering::SeqCst) == 0
+    }
+
+    /// Interrupt the evaluation in progress, if any
+    pub fn interrupt(&self) {
+        if let Some(interrupt) = &*self.running.lock().unwrap() {
+            interrupt.interrupt();
+        }
+    }
+
+    /// Evaluate `source`, interrupting it if it outlives the timeout
+    ///
+    /// `interrupt` stops this evaluation only, whether it is used before the
+    /// evaluation starts or while it runs.
+    pub async fn eval(&self, source: &str, interrupt: Interrupt) -> Result<Evaluation> {
+        let (reply, result) = oneshot::channel();
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        self.jobs
+            .send(Job {
+                source: source.to_string(),
+                interrupt: interrupt.clone(),
+                reply,
+            })
+            .map_err(|_| {
+                self.pending.fetch_sub(1, Ordering::SeqCst);
+                anyhow!("Evaluation worker has stopped")
+            })?;
+
+        match tokio::time::timeout(self.limits.timeout, result).await {
+            Ok(Ok(result)) => result.map_err(|e| anyhow!(e)),
+            Ok(Err(_)) => Err(anyhow!("Evaluation was interrupted")),
+            Err(_) => {
+                interrupt.interrupt();
+                Err(anyhow!(
+                    "Execution timed out after {} ms",
+                    self.limits.timeout.as_millis()
+                ))
+            }
+        }
+    }
+}
+
+/// An engine bounded by `limits`
+fn new_engine(limits: SessionLimits) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_limits(limits.execution_limits());
+    engine
+}
+
+/// Workers for evaluations outside sessions
+///
+/// An idle worker is reused; more are started, up to a bound, only while
+/// earlier evaluations keep theirs busy, such as one blocked in a host
+/// function after its timeout.
+pub struct WorkerPool {
+    limits: SessionLimits,
+    max_workers: usize,
+    workers: Vec<Worker>,
+}
+
+impl WorkerPool {
+    pub fn new(limits: SessionLimits, max_workers: usize) -> Self {
+        WorkerPool {
+            limits,
+            max_workers,
+            workers: Vec::new(),
+        }
+    }
+
+    /// An idle worker, started if there is none and the pool is not full
+    pub fn worker(&mut self) -> Result<&Worker> {
+        if let Some(index) = self.workers.iter().position(Worker::is_idle) {
+            return Ok(&self.workers[index]);
+        }
+        if self.workers.len() >= self.max_workers {
+            return Err(anyhow!(
+                "All {} evaluation workers are busy",
+                self.max_workers
+            ));
+        }
+        self.workers.push(Worker::spawn_fresh(self.limits)?);
+        Ok(&self.workers[self.workers.len() - 1])
+    }
+
+    /// Workers started so far
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+}
+
+/// Evaluate a script, keeping its top-level bindings defined in `engine`
+fn evaluate(engine: &mut Engine, source: &str) -> Result<Evaluation, String> {
+    let tokens = Lexer::new(source)
+        .tokenize()
+        .map_err(|e| format!("Lexer error: {}", e))?;
+    let mut program = Parser::new(tokens)
+        .parse_program()
+        .map_err(|e| format!("Parse error: {}", e))?;
+
+    let bindings: Vec<String> = program
+        .items
+        .iter()
+        .flat_map(|item| match item {
+            ModuleItem::Let(Some(name), _) => vec![name.clone()],
+            ModuleItem::LetRec(bindings) => bindings.iter().map(|(n, _)| n.clone()).collect(),
+            _ => Vec::new(),
+        })
+        .collect();
+
+    // Evaluate to (result, [bound values]) so the bindings can be kept
+    let main = program.main_expr.take().unwrap_or(Expr::Lit(Literal::Unit));
+    program.main_expr = Some(Expr::Tuple(vec![
+        main,
+        Expr::List(bindings.iter().cloned().map(Expr::Var).collect()),
+    ]));
+
+    let result = engine
+        .eval_program(&program, RunOptions::default())
+        .map_err(|e| format!("Execution error: {}", e))?;
+    let Value::Tuple(mut parts) = result else {
+        return Err("Script did not evaluate to its bindings".to_string());
+    };
+    let values = parts.pop().and_then(|list| list.list_to_vec());
+    let (Some(value), Some(values)) = (parts.pop(), values) else {
+        return Err("Script did not evaluate to its bindings".to_string());
+    };
+    for (name, value) in bindings.iter().zip(values) {
+        engine.set_global(name, value);
+    }
+
+    Ok(Evaluation {
+        value: value_to_json(&value),
+        bindings,
+    })
+}
+
+/// A named worker whose bindings persist across evaluations
+pub struct Session {
+    pub name: String,
+    limits: SessionLimits,
+    worker: Worker,
+    /// Interrupt for the next evaluation
+    next: Interrupt,
+    bindings: BTreeSet<String>,
+    evaluations: u64,
+}
+
+impl Session {
+    pub fn new(name: &str, limits: SessionLimits) -> Result<Self> {
+        Ok(Session {
+            name: name.to_string(),
+            limits,
+            worker: Worker::spawn(limits)?,
+            next: Interrupt::default(),
+            bindings: BTreeSet::new(),
+            evaluations: 0,
+        })
+    }
+
+    pub fn limits(&self) -> SessionLimits {
+        self.limits
+    }
+
+    /// Names bound by earlier evaluations
+    pub fn bindings(&self) -> impl Iterator<Item = &str> {
+        self.bindings.iter().map(String::as_str)
+    }
+
+    /// Handle for interrupting the session's next evaluation
+    pub fn interrupt_handle(&self) -> Interrupt {
+        self.next.clone()
+    }
+
+    /// Interrupt the evaluation in progress, if any
+    pub fn interrupt(&self) {
+        self.worker.interrupt();
+    }
+
+    /// Evaluate `source` in the session
+    pub async fn eval(&mut self, source: &str) -> Result<serde_json::Value> {
+        self.evaluations += 1;
+        let interrupt = std::mem::take(&mut self.next);
+        let evaluation = self.worker.eval(source, interrupt).await?;
+        self.bindings.extend(evaluation.bindings);
+        Ok(evaluation.value)
+    }
+
+    /// Drop all bindings by starting over with a fresh engine
+    ///
+    /// An evaluation still running in the old engine is interrupted.
+    pub fn reset(&mut self) -> Result<()> {
+        let worker = Worker::spawn(self.limits)?;
+        std::mem::replace(&mut self.worker, worker).interrupt();
+        self.bindings.clear();
+        self.evaluations = 0;
+        Ok(())
+    }
+
+    /// Summary for `list_sessions` and `get_context`
+    pub fn state(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "bindings": self.bindings,
+            "evaluations": self.evaluations,
+            "limits": self.limits.to_json(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> SessionLimits {
+        SessionLimits::with_timeout(Duration::from_secs(5))
+    }
+
+    #[tokio::test]
+    async fn test_bindings_persist() {
+        let mut session = Session::new("s", limits()).unwrap();
+        assert_eq!(session.eval("let x = 20").await.unwrap(), json!(null));
+        assert_eq!(
+            session.eval("let double n = n * 2").await.unwrap(),
+            json!(null)
+        );
+        assert_eq!(session.eval("double x + 2").await.unwrap(), json!(42));
+        assert_eq!(session.bindings().collect::<Vec<_>>(), vec!["double", "x"]);
+
+        session.reset().unwrap();
+        assert!(session.eval("x").await.is_err());
+        assert_eq!(session.bindings().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_interrupts_evaluation() {
+        let mut session =
+            Session::new("s", SessionLimits::with_timeout(Duration::from_millis(200))).unwrap();
+        let error = session.eval("while true do ()").await.unwrap_err();
+        assert!(error.to_string().contains("time"), "{}", error);
+
+        // The worker is free again
+        assert_eq!(session.eval("1 + 1").await.unwrap(), json!(2));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_before_evaluation() {
+        // As when a `notifications/cancelled` arrives before the worker starts
+        let mut session = Session::new("s", limits()).unwrap();
+        session.interrupt_handle().interrupt();
+        let error = session.eval("1 + 1").await.unwrap_err();
+        assert!(error.to_string().contains("interrupted"), "{}", error);
+
+        assert_eq!(session.eval("1 + 1").await.unwrap(), json!(2));
+    }
+
+    #[tokio::test]
+    async fn test_late_interrupt_spares_next_evaluation() {
+        // As when a timeout fires just as its evaluation returns
+        let mut pool = WorkerPool::new(limits(), 1);
+        let worker = pool.worker().unwrap();
+        let first = Interrupt::default();
+        assert_eq!(
+            worker.eval("1 + 1", first.clone()).await.unwrap().value,
+            json!(2)
+        );
+        first.interrupt();
+        let worker = pool.worker().unwrap();
+        assert_eq!(
+            worker
+                .eval("2 + 2", Interrupt::default())
+                .await
+                .unwrap()
+                .value,
+            json!(4)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_reuses_idle_workers() {
+        let mut pool = WorkerPool::new(limits(), 1);
+        let worker = pool.worker().unwrap();
+        assert_eq!(
+            worker
+                .eval("let x = 20\nx + 1", Interrupt::default())
+                .await
+                .unwrap()
+                .value,
+            json!(21)
+        );
+        // The next script starts in a fresh engine on the same worker
+        let worker = pool.worker().unwrap();
+        assert!(worker.eval("x", Interrupt::default()).await.is_err());
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pool_is_bounded() {
+        let mut pool = WorkerPool::new(limits(), 1);
+        let worker = pool.worker().unwrap();
+        let interrupt = Interrupt::default();
+        // Give up waiting while the script still runs
+        let running = tokio::time::timeout(
+            Duration::from_millis(50),
+            worker.eval("while true do ()", interrupt.clone()),
+        )
+        .await;
+        assert!(running.is_err());
+
+        let error = pool.worker().err().unwrap();
+        assert!(error.to_string().contains("busy"), "{}", error);
+        interrupt.interrupt();
+    }
+
+    #[tokio::test]
+    async fn test_instruction_limit() {
+        let mut session = Session::new(
+            "s",
+            SessionLimits {
+                max_instructions: Some(1_000),
+                ..limits()
+            },
+        )
+        .unwrap();
+        let error = session.eval("while true do ()").await.unwrap_err();
+        assert!(error.to_string().contains("instruction limit"), "{}", error);
+    }
+}
@@ -0,0 +1,269 @@
+//! The Streamable HTTP transport, served on localhost
+
+use fusabi_mcp::http::{self, HttpLimits, ENDPOINT, SESSION_HEADER};
+use fusabi_mcp::{McpServer, Script};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const NOTES: &str = r#"
+module Tools =
+    let touch id = Events.emit "resources/updated" ("notes://" ++ id)
+
+module Resources =
+    let note = { uri = "notes://{id}"; read = fun p -> "Note " ++ p.id }
+"#;
+
+async fn start(factory: impl Fn() -> anyhow::Result<McpServer> + Send + Sync + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}{}", listener.local_addr().unwrap(), ENDPOINT);
+    tokio::spawn(http::serve(listener, factory));
+    url
+}
+
+async fn initialize(client: &Client, url: &str) -> String {
+    let response = client
+        .post(url)
+        .json(&json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    response.headers()[SESSION_HEADER]
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+async fn post(client: &Client, url: &str, session: &str, body: Value) -> (StatusCode, Value) {
+    let response = client
+        .post(url)
+        .header(SESSION_HEADER, session)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    let status = response.status();
+    let text = response.text().await.unwrap();
+    (status, serde_json::from_str(&text).unwrap_or(Value::Null))
+}
+
+fn call(id: i64, name: &str, arguments: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": name, "arguments": arguments }
+    })
+}
+
+fn tool_text(response: &Value) -> &str {
+    response["result"]["content"][0]["text"].as_str().unwrap()
+}
+
+#[tokio::test]
+async fn test_clients_get_separate_sessions() {
+    let url = start(|| Ok(McpServer::new())).await;
+    let client = Client::new();
+    let first = initialize(&client, &url).await;
+    let second = initialize(&client, &url).await;
+    assert_ne!(first, second);
+
+    let (_, created) = post(
+        &client,
+        &url,
+        &first,
+        call(1, "create_session", json!({ "name": "work" })),
+    )
+    .await;
+    assert!(created.get("error").is_none(), "{}", created);
+
+    let (_, listed) = post(&client, &url, &second, call(2, "list_sessions", json!({}))).await;
+    assert_eq!(tool_text(&listed), "[]");
+
+    // A session id is required, and must be known
+    let missing = client
+        .post(&url)
+        .json(&call(3, "list_sessions", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), StatusCode::BAD_REQUEST);
+    let (status, _) = post(&client, &url, "nope", call(4, "list_sessions", json!({}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Deleted sessions are gone
+    let deleted = client
+        .delete(&url)
+        .header(SESSION_HEADER, &first)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(deleted.status(), StatusCode::OK);
+    let (status, _) = post(&client, &url, &first, call(5, "list_sessions", json!({}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_session_limits() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}{}", listener.local_addr().unwrap(), ENDPOINT);
+    let limits = HttpLimits {
+        max_sessions: 2,
+        idle_timeout: Duration::from_millis(500),
+    };
+    tokio::spawn(http::serve_with_limits(listener, limits, || {
+        Ok(McpServer::new())
+    }));
+    let client = Client::new();
+    let first = initialize(&client, &url).await;
+    let _second = initialize(&client, &url).await;
+
+    // No more sessions while both are open
+    let refused = client
+        .post(&url)
+        .json(&json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(refused.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(refused.headers().get(SESSION_HEADER).is_none());
+
+    // Idle sessions end, making room for new ones
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    let (status, _) = post(&client, &url, &first, call(1, "list_sessions", json!({}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    initialize(&client, &url).await;
+}
+
+#[tokio::test]
+async fn test_batches() {
+    let url = start(|| Ok(McpServer::new())).await;
+    let client = Client::new();
+    let session = initialize(&client, &url).await;
+
+    let (status, responses) = post(
+        &client,
+        &url,
+        &session,
+        json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "ping" },
+            { "jsonrpc": "2.0", "method": "notifications/initialized" },
+            call(2, "eval_fusabi", json!({ "script": "6 * 7" })),
+        ]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let responses = responses.as_array().unwrap();
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["id"], json!(1));
+    assert_eq!(tool_text(&responses[1]), "42");
+
+    let (status, _) = post(
+        &client,
+        &url,
+        &session,
+        json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let (status, error) = post(&client, &url, &session, json!([])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"]["code"], json!(-32600));
+}
+
+#[tokio::test]
+async fn test_cancel_running_request() {
+    let url = start(|| Ok(McpServer::new())).await;
+    let client = Client::new();
+    let session = initialize(&client, &url).await;
+
+    let running = tokio::spawn({
+        let (client, url, session) = (client.clone(), url.clone(), session.clone());
+        async move {
+            post(
+                &client,
+                &url,
+                &session,
+                call(7, "eval_fusabi", json!({ "script": "while true do ()" })),
+            )
+            .await
+        }
+    });
+
+    // Keep cancelling until the evaluation has started and stopped
+    let cancel = json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/cancelled",
+        "params": { "requestId": 7 }
+    });
+    while !running.is_finished() {
+        let (status, _) = post(&client, &url, &session, cancel.clone()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    let (_, response) = running.await.unwrap();
+    let message = response["error"]["message"].as_str().unwrap();
+    assert!(message.contains("interrupted"), "{}", message);
+}
+
+#[tokio::test]
+async fn test_notifications_stream_over_sse() {
+    let url = start(|| Ok(McpServer::new().with_script(Script::from_source(NOTES)?))).await;
+    let client = Client::new();
+    let session = initialize(&client, &url).await;
+
+    let (_, subscribed) = post(
+        &client,
+        &url,
+        &session,
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "resources/subscribe",
+            "params": { "uri": "notes://7" }
+        }),
+    )
+    .await;
+    assert!(subscribed.get("error").is_none(), "{}", subscribed);
+
+    let mut stream = client
+        .get(&url)
+        .header(SESSION_HEADER, &session)
+        .header("accept", "text/event-stream")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stream.headers()["content-type"], "text/event-stream");
+
+    post(
+        &client,
+        &url,
+        &session,
+        call(2, "touch", json!({ "id": "7" })),
+    )
+    .await;
+
+    let mut received = String::new();
+    while !received.contains("\n\n") || !received.contains("data:") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), stream.chunk())
+            .await
+            .expect("no notification within 5s")
+            .unwrap()
+            .expect("stream ended");
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    let data = received
+        .lines()
+        .find_map(|line| line.strip_prefix("data: "))
+        .unwrap();
+    let notification: Value = serde_json::from_str(data).unwrap();
+    assert_eq!(
+        notification["method"],
+        json!("notifications/resources/updated")
+    );
+    assert_eq!(notification["params"]["uri"], json!("notes://7"));
+}
@@ -0,0 +1,79 @@
+//! The `Mcp` stdlib client talking to a stub server scripted with the
+//! responses of this crate's server
+#![cfg(unix)]
+
+use fusabi::{Engine, Value};
+
+/// A server answering like `fusabi-mcp`, echoing request ids: `20 + 22`
+/// evaluates to 42, `while true do ()` times out and any other tool fails
+const STUB_SERVER: &str = r##"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9][0-9]*\).*/\1/p')
+  case "$line" in
+    *'"initialize"'*)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"protocolVersion\":\"2024-11-05\",\"capabilities\":{\"tools\":{}},\"serverInfo\":{\"name\":\"fusabi-mcp\",\"version\":\"0.35.0\"}}}" ;;
+    *'"tools/list"'*)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"tools\":[{\"name\":\"eval_fusabi\",\"description\":\"Evaluate Fusabi code\",\"inputSchema\":{\"type\":\"object\"}}]}}" ;;
+    *'"tools/call"'*'"script":"20 + 22"'*)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"content\":[{\"type\":\"text\",\"text\":\"42\"}]}}" ;;
+    *'"tools/call"'*'"script":"while true do ()"'*)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"error\":{\"code\":-32603,\"message\":\"Tool execution failed: Execution timed out after 5000 ms\"}}" ;;
+    *'"tools/call"'*)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"error\":{\"code\":-32603,\"message\":\"Tool execution failed: Unknown tool\"}}" ;;
+    *'"id"'*)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"error\":{\"code\":-32601,\"message\":\"Method not found\"}}" ;;
+  esac
+done
+"##;
+
+/// Evaluate `body` with `server` bound to a client of the stub server
+fn eval_with_server(body: &str) -> Result<Value, fusabi::FusabiError> {
+    let script = format!(
+        "let server = Mcp.connect \"sh\" [\"-c\"; {:?}] in\n{}",
+        STUB_SERVER, body
+    );
+    Engine::new().eval(&script)
+}
+
+#[test]
+fn test_script_calls_fusabi_mcp() {
+    let result = eval_with_server(
+        r#"
+let tools = Mcp.listTools server in
+let result = Mcp.callTool server "eval_fusabi" { script = "20 + 22" } in
+let closed = Mcp.close server in
+(List.map (fun tool -> tool.name) tools, result.isError, result.text)
+"#,
+    )
+    .unwrap();
+    let Value::Tuple(parts) = result else {
+        panic!("expected a tuple, got {}", result);
+    };
+    let tools: Vec<String> = parts[0]
+        .list_to_vec()
+        .unwrap()
+        .iter()
+        .map(|name| name.as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(tools, ["eval_fusabi"]);
+    assert_eq!(parts[1], Value::Bool(false));
+    assert_eq!(parts[2], Value::Str("42".to_string()));
+}
+
+#[test]
+fn test_tool_error_reply() {
+    let error = eval_with_server(r#"Mcp.callTool server "missing" ()"#).unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("MCP error -32603"), "{}", message);
+    assert!(message.contains("Unknown tool"), "{}", message);
+}
+
+#[test]
+fn test_timeout_reply() {
+    let error =
+        eval_with_server(r#"Mcp.callTool server "eval_fusabi" { script = "while true do ()" }"#)
+            .unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("MCP error -32603"), "{}", message);
+    assert!(message.contains("timed out after 5000 ms"), "{}", message);
+}
@@ -1,9 +1,25 @@
 //! Build functionality for the Fusabi Package Manager.
 //!
 //! This module handles compilation of Fusabi packages including dependency resolution.
+//!
+//! A build produces one package artifact, `target/<name>.fzp`: each
+//! dependency's `src/lib.fsx` (or `src/main.fsx`) compiled as a library
+//! module, named after the package in PascalCase (`json-utils` becomes
+//! `JsonUtils`), and the package's `src/main.fsx` as the entry module.
+//! A library package without a `src/main.fsx` is built the same way as a
+//! dependency, from its `src/lib.fsx`, into an artifact without an entry.
+//! The artifact also records a checksum of each dependency's sources.
+//!
+//! Features are unified across the dependency graph: each package is
+//! compiled once, with every feature any of its dependents asks for, and
+//! optional dependencies no enabled feature activates are left out.
 
+use crate::features::{resolve_features, EnabledFeatures, FeatureRequest};
+use crate::lockfile::checksum_dir;
 use crate::manifest::{Dependency, DetailedDependency, Manifest};
-use std::collections::HashSet;
+use crate::workspace::Workspace;
+use fusabi_vm::{ArtifactBuilder, DependencyInfo, ModuleInfo};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
@@ -23,6 +39,9 @@ pub enum BuildError {
     #[error("Manifest error: {0}")]
     ManifestError(#[from] crate::manifest::ManifestError),
 
+    #[error("Workspace error: {0}")]
+    WorkspaceError(#[from] crate::workspace::WorkspaceError),
+
     #[error("Compilation error: {0}")]
     CompileError(String),
 
@@ -34,12 +53,15 @@ pub enum BuildError {
 
     #[error("Dependency resolution failed: {0}")]
     DependencyResolutionFailed(String),
+
+    #[error("Failed to write artifact: {0}")]
+    ArtifactError(#[from] fusabi_vm::ArtifactError),
 }
 
 /// Result of a successful build.
 #[derive(Debug)]
 pub struct BuildResult {
-    /// Path to the output bytecode file.
+    /// Path to the output package artifact.
     pub output_path: PathBuf,
     /// Size of the output in bytes.
     pub output_size: usize,
@@ -54,8 +76,49 @@ pub struct ResolvedDependency {
     pub name: String,
     /// Resolved path to the dependency.
     pub path: PathBuf,
-    /// Version if available.
+    /// Version of the package, or the required version if it has no
+    /// manifest.
     pub version: Option<String>,
+    /// Names of the packages it depends on.
+    pub dependencies: Vec<String>,
+    /// Features it is compiled with.
+    pub features: Vec<String>,
+}
+
+/// Name of the module a package compiles to: its name in PascalCase.
+pub fn module_name(package: &str) -> String {
+    package
+        .split(['-', '_', '.'])
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// State of a dependency resolution.
+struct Resolution {
+    /// Dependencies resolved so far, each after its own dependencies.
+    resolved: Vec<ResolvedDependency>,
+    /// Dependencies being resolved, for cycle detection.
+    visited: HashSet<String>,
+    /// Features asked of each dependency.
+    requests: HashMap<String, FeatureRequest>,
+}
+
+impl Resolution {
+    /// Adds `request` to the features asked of the dependency `name`.
+    fn request(&mut self, name: &str, request: FeatureRequest) {
+        match self.requests.get_mut(name) {
+            Some(existing) => existing.merge(request),
+            None => {
+                self.requests.insert(name.to_string(), request);
+            }
+        }
+    }
 }
 
 /// Builder for Fusabi packages.
@@ -66,6 +129,10 @@ pub struct PackageBuilder {
     packages_dir: PathBuf,
     /// Verbose output flag.
     verbose: bool,
+    /// Workspace the package is a member of.
+    workspace: Option<Workspace>,
+    /// Features to enable in the package.
+    features: FeatureRequest,
 }
 
 impl PackageBuilder {
@@ -76,6 +143,8 @@ impl PackageBuilder {
             project_root,
             packages_dir,
             verbose: false,
+            workspace: None,
+            features: FeatureRequest::default(),
         }
     }
 
@@ -85,6 +154,32 @@ impl PackageBuilder {
         self
     }
 
+    /// Builds the package as a member of `workspace`, using its
+    /// dependencies directory and inherited dependencies.
+    pub fn workspace(mut self, workspace: Workspace) -> Self {
+        self.packages_dir = workspace.root().join("fusabi_packages");
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// Enables features of the package.
+    pub fn features<I, S>(mut self, features: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.features
+            .features
+            .extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets whether the package's default features are enabled.
+    pub fn default_features(mut self, default_features: bool) -> Self {
+        self.features.default_features = default_features;
+        self
+    }
+
     /// Builds the package.
     pub fn build(&self) -> Result<BuildResult, BuildError> {
         let manifest_path = self.project_root.join("fusabi.toml");
@@ -92,14 +187,20 @@ impl PackageBuilder {
             return Err(BuildError::ManifestNotFound(manifest_path));
         }
 
-        let manifest = Manifest::load(&manifest_path)?;
+        let manifest = self.load_manifest(&self.project_root)?;
 
         if self.verbose {
             println!("Building {}...", manifest.package.name);
         }
 
-        // Resolve dependencies
-        let resolved_deps = self.resolve_dependencies(&manifest)?;
+        // Resolve dependencies and features
+        let enabled = resolve_features(&manifest, &self.features)?;
+        let features: Vec<String> = enabled.features.iter().cloned().collect();
+        let resolved_deps = self.resolve_dependencies(&manifest, &enabled)?;
+
+        if self.verbose && !features.is_empty() {
+            println!("Features: {}", features.join(", "));
+        }
 
         if self.verbose && !resolved_deps.is_empty() {
             println!("Resolved {} dependencies:", resolved_deps.len());
@@ -108,17 +209,65 @@ impl PackageBuilder {
             }
         }
 
-        // Find and read main source file
+        // Find and read main source file, or the library source of a
+        // package without one
         let main_path = self.project_root.join("src").join("main.fsx");
-        if !main_path.exists() {
+        let lib_path = self.project_root.join("src").join("lib.fsx");
+        let library = !main_path.exists() && lib_path.exists();
+        if !library && !main_path.exists() {
             return Err(BuildError::MainFileNotFound(main_path));
         }
 
-        let source = fs::read_to_string(&main_path)?;
+        let source = fs::read_to_string(if library { &lib_path } else { &main_path })?;
+
+        // Compile the dependencies, then the package, into one artifact
+        let package = &manifest.package;
+        let mut artifact = ArtifactBuilder::new(&package.name, &package.version);
+        for dep in &resolved_deps {
+            let src_dir = dep.path.join("src");
+            let lib_path = src_dir.join("lib.fsx");
+            let source_path = if lib_path.exists() {
+                lib_path
+            } else {
+                src_dir.join("main.fsx")
+            };
+            if !source_path.exists() {
+                return Err(BuildError::MainFileNotFound(source_path));
+            }
+
+            let source = fs::read_to_string(&source_path)?;
+            let (chunk, exports) = fusabi::compile_module(&source, &dep.features)
+                .map_err(|e| BuildError::CompileError(format!("{}: {}", dep.name, e)))?;
+            let version = dep.version.clone().unwrap_or_default();
+            let module = ModuleInfo::new(module_name(&dep.name), &dep.name, &version)
+                .requires(dep.dependencies.iter().map(|d| module_name(d)).collect())
+                .exports(exports);
+            artifact = artifact.module(module, chunk).dependency(DependencyInfo {
+                name: dep.name.clone(),
+                version,
+                hash: checksum_dir(&dep.path)?,
+            });
+        }
 
-        // Compile to bytecode
-        let bytecode = fusabi::compile_to_bytecode(&source)
-            .map_err(|e| BuildError::CompileError(e.to_string()))?;
+        let mut requires: Vec<String> = manifest
+            .dependencies
+            .iter()
+            .filter(|(name, dep)| enabled.is_active(name, dep))
+            .map(|(name, _)| module_name(name))
+            .collect();
+        requires.sort();
+        let info = ModuleInfo::new(module_name(&package.name), &package.name, &package.version)
+            .requires(requires);
+        let artifact = if library {
+            let (chunk, exports) = fusabi::compile_module(&source, &features)
+                .map_err(|e| BuildError::CompileError(e.to_string()))?;
+            artifact.module(info.exports(exports), chunk)
+        } else {
+            let chunk = fusabi::compile_to_chunk_with_features(&source, &features)
+                .map_err(|e| BuildError::CompileError(e.to_string()))?;
+            artifact.entry(info, chunk)
+        };
+        let bytecode = artifact.to_bytes()?;
 
         // Create target directory
         let target_dir = self.project_root.join("target");
@@ -126,8 +275,8 @@ impl PackageBuilder {
             fs::create_dir_all(&target_dir)?;
         }
 
-        // Write bytecode
-        let output_path = target_dir.join(format!("{}.fzb", manifest.package.name));
+        // Write the artifact
+        let output_path = target_dir.join(format!("{}.fzp", package.name));
         let output_size = bytecode.len();
         fs::write(&output_path, &bytecode)?;
 
@@ -143,19 +292,42 @@ impl PackageBuilder {
         })
     }
 
-    /// Resolves all dependencies for the manifest.
+    /// Loads the manifest in `dir`, taking workspace members' manifests from
+    /// the workspace.
+    fn load_manifest(&self, dir: &Path) -> Result<Manifest, BuildError> {
+        match self.workspace.as_ref().and_then(|ws| ws.member_at(dir)) {
+            Some(member) => Ok(member.manifest.clone()),
+            None => Ok(Manifest::load(dir.join("fusabi.toml"))?),
+        }
+    }
+
+    /// Resolves all active dependencies for the manifest, with `enabled`
+    /// its enabled features.
     fn resolve_dependencies(
         &self,
         manifest: &Manifest,
+        enabled: &EnabledFeatures,
     ) -> Result<Vec<ResolvedDependency>, BuildError> {
-        let mut resolved = Vec::new();
-        let mut visited = HashSet::new();
-
-        for (name, dep) in &manifest.dependencies {
-            self.resolve_dependency(name, dep, &mut resolved, &mut visited)?;
+        // Resolve until no package is asked for a feature it was not
+        // resolved with
+        let mut requests = HashMap::new();
+        loop {
+            let mut resolution = Resolution {
+                resolved: Vec::new(),
+                visited: HashSet::new(),
+                requests: requests.clone(),
+            };
+            for (name, dep) in &manifest.dependencies {
+                if enabled.is_active(name, dep) {
+                    resolution.request(name, enabled.request(name, dep));
+                    self.resolve_dependency(name, dep, &mut resolution)?;
+                }
+            }
+            if resolution.requests == requests {
+                return Ok(resolution.resolved);
+            }
+            requests = resolution.requests;
         }
-
-        Ok(resolved)
     }
 
     /// Resolves a single dependency recursively.
@@ -163,41 +335,54 @@ impl PackageBuilder {
         &self,
         name: &str,
         dependency: &Dependency,
-        resolved: &mut Vec<ResolvedDependency>,
-        visited: &mut HashSet<String>,
+        resolution: &mut Resolution,
     ) -> Result<(), BuildError> {
         // Check for cycles
-        if visited.contains(name) {
+        if resolution.visited.contains(name) {
             return Err(BuildError::CyclicDependency(name.to_string()));
         }
 
         // Check if already resolved
-        if resolved.iter().any(|d| d.name == name) {
+        if resolution.resolved.iter().any(|d| d.name == name) {
             return Ok(());
         }
 
-        visited.insert(name.to_string());
+        resolution.visited.insert(name.to_string());
 
-        let (dep_path, version) = self.locate_dependency(name, dependency)?;
+        let (dep_path, mut version) = self.locate_dependency(name, dependency)?;
+        let mut dependencies = Vec::new();
+        let mut features = Vec::new();
 
         // Check if the dependency has its own manifest with dependencies
         let dep_manifest_path = dep_path.join("fusabi.toml");
         if dep_manifest_path.exists() {
-            let dep_manifest = Manifest::load(&dep_manifest_path)?;
+            let dep_manifest = self.load_manifest(&dep_path)?;
+            let request = resolution.requests.get(name).cloned().unwrap_or_default();
+            let enabled = resolve_features(&dep_manifest, &request)?;
 
-            // Recursively resolve transitive dependencies
+            // Recursively resolve active transitive dependencies
             for (trans_name, trans_dep) in &dep_manifest.dependencies {
-                self.resolve_dependency(trans_name, trans_dep, resolved, visited)?;
+                if !enabled.is_active(trans_name, trans_dep) {
+                    continue;
+                }
+                resolution.request(trans_name, enabled.request(trans_name, trans_dep));
+                self.resolve_dependency(trans_name, trans_dep, resolution)?;
+                dependencies.push(trans_name.clone());
             }
+            dependencies.sort();
+            features = enabled.features.into_iter().collect();
+            version = Some(dep_manifest.package.version);
         }
 
-        resolved.push(ResolvedDependency {
+        resolution.resolved.push(ResolvedDependency {
             name: name.to_string(),
             path: dep_path,
             version,
+            dependencies,
+            features,
         });
 
-        visited.remove(name);
+        resolution.visited.remove(name);
 
         Ok(())
     }
@@ -276,7 +461,7 @@ impl PackageBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::manifest::Package;
+    use crate::manifest::{ManifestError, Package};
     use tempfile::TempDir;
 
     fn create_test_package(temp_dir: &TempDir, name: &str) -> PathBuf {
@@ -352,6 +537,9 @@ mod tests {
                 git: None,
                 rev: None,
                 optional: false,
+                features: Vec::new(),
+                default_features: true,
+                workspace: false,
             }),
         );
         fs::write(main_pkg.join("fusabi.toml"), manifest.to_toml().unwrap()).unwrap();
@@ -362,4 +550,222 @@ mod tests {
         assert_eq!(result.resolved_deps.len(), 1);
         assert_eq!(result.resolved_deps[0].name, "dep-pkg");
     }
+
+    #[test]
+    fn test_module_name() {
+        assert_eq!(module_name("json"), "Json");
+        assert_eq!(module_name("json-utils"), "JsonUtils");
+        assert_eq!(module_name("http_client.v2"), "HttpClientV2");
+    }
+
+    #[test]
+    fn test_build_links_dependency_modules() {
+        let temp_dir = TempDir::new().unwrap();
+        let main_pkg = create_test_package(&temp_dir, "main-pkg");
+        let dep_pkg = create_test_package(&temp_dir, "math-utils");
+        fs::write(
+            dep_pkg.join("src/lib.fsx"),
+            "let double x = x * 2\nlet answer = 21\nlet _scratch = 0\n",
+        )
+        .unwrap();
+        fs::write(
+            main_pkg.join("src/main.fsx"),
+            "MathUtils.double MathUtils.answer",
+        )
+        .unwrap();
+
+        let mut manifest = Manifest::load(main_pkg.join("fusabi.toml")).unwrap();
+        manifest.add_dependency(
+            "math-utils".to_string(),
+            Dependency::Detailed(DetailedDependency {
+                path: Some("../math-utils".to_string()),
+                version: None,
+                git: None,
+                rev: None,
+                optional: false,
+                features: Vec::new(),
+                default_features: true,
+                workspace: false,
+            }),
+        );
+        fs::write(main_pkg.join("fusabi.toml"), manifest.to_toml().unwrap()).unwrap();
+
+        let result = PackageBuilder::new(main_pkg).build().unwrap();
+        assert_eq!(result.output_path.extension().unwrap(), "fzp");
+        let bytes = fs::read(&result.output_path).unwrap();
+
+        let artifact = fusabi_vm::Artifact::from_bytes(&bytes).unwrap();
+        assert_eq!(artifact.entry(), Some("MainPkg"));
+        let module = artifact.module("MathUtils").unwrap();
+        assert_eq!(module.package, "math-utils");
+        assert_eq!(module.version, "0.1.0");
+        let exports: Vec<_> = module.exports.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(exports, ["double", "answer"]);
+        assert_eq!(module.exports[0].arity, 1);
+        assert_eq!(artifact.module("MainPkg").unwrap().requires, ["MathUtils"]);
+        assert_eq!(
+            artifact.dependencies()[0].hash,
+            checksum_dir(&dep_pkg).unwrap()
+        );
+
+        let value = fusabi::execute_artifact(&bytes).unwrap();
+        assert_eq!(value, fusabi_vm::Value::Int(42));
+    }
+
+    #[test]
+    fn test_build_with_features() {
+        let temp_dir = TempDir::new().unwrap();
+        let main_pkg = create_test_package(&temp_dir, "main-pkg");
+        let ui_pkg = create_test_package(&temp_dir, "ui");
+        fs::write(
+            ui_pkg.join("fusabi.toml"),
+            "[package]\nname = \"ui\"\nversion = \"0.1.0\"\n\n[features]\nterminal = []\n",
+        )
+        .unwrap();
+        fs::write(
+            ui_pkg.join("src/lib.fsx"),
+            "#if terminal\nlet width = 80\n#else\nlet width = 0\n#endif\n",
+        )
+        .unwrap();
+        fs::write(
+            main_pkg.join("src/main.fsx"),
+            "#if wide\nUi.width + 1\n#else\nUi.width\n#endif\n",
+        )
+        .unwrap();
+        fs::write(
+            main_pkg.join("fusabi.toml"),
+            r#"
+[package]
+name = "main-pkg"
+version = "0.1.0"
+
+[dependencies]
+ui = { path = "../ui" }
+gpu = { path = "../missing", optional = true }
+
+[features]
+wide = ["ui/terminal"]
+"#,
+        )
+        .unwrap();
+
+        // Neither the feature nor the optional dependency is enabled
+        let result = PackageBuilder::new(main_pkg.clone()).build().unwrap();
+        let names: Vec<_> = result.resolved_deps.iter().map(|d| &d.name).collect();
+        assert_eq!(names, ["ui"]);
+        assert!(result.resolved_deps[0].features.is_empty());
+        let bytes = fs::read(&result.output_path).unwrap();
+        assert_eq!(
+            fusabi::execute_artifact(&bytes).unwrap(),
+            fusabi_vm::Value::Int(0)
+        );
+
+        let result = PackageBuilder::new(main_pkg.clone())
+            .features(["wide"])
+            .build()
+            .unwrap();
+        assert_eq!(result.resolved_deps[0].features, ["terminal"]);
+        let bytes = fs::read(&result.output_path).unwrap();
+        assert_eq!(
+            fusabi::execute_artifact(&bytes).unwrap(),
+            fusabi_vm::Value::Int(81)
+        );
+
+        let result = PackageBuilder::new(main_pkg.clone())
+            .features(["gpu"])
+            .build();
+        assert!(matches!(result, Err(BuildError::DependencyNotFound { .. })));
+        let result = PackageBuilder::new(main_pkg).features(["fast"]).build();
+        assert!(matches!(
+            result,
+            Err(BuildError::ManifestError(
+                ManifestError::UnknownFeature { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_build_workspace_member() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("fusabi.toml"),
+            "[workspace]\nmembers = [\"*-pkg\"]\n",
+        )
+        .unwrap();
+        let main_pkg = create_test_package(&temp_dir, "main-pkg");
+        let dep_pkg = create_test_package(&temp_dir, "dep-pkg");
+
+        // Members depend on each other by name
+        let mut manifest = Manifest::load(main_pkg.join("fusabi.toml")).unwrap();
+        manifest.add_dependency(
+            "dep-pkg".to_string(),
+            Dependency::Detailed(DetailedDependency {
+                path: None,
+                version: None,
+                git: None,
+                rev: None,
+                optional: false,
+                features: Vec::new(),
+                default_features: true,
+                workspace: true,
+            }),
+        );
+        fs::write(main_pkg.join("fusabi.toml"), manifest.to_toml().unwrap()).unwrap();
+
+        let workspace = Workspace::load(temp_dir.path()).unwrap().unwrap();
+        let builder = PackageBuilder::new(main_pkg).workspace(workspace);
+        let result = builder.build().unwrap();
+
+        assert_eq!(result.resolved_deps.len(), 1);
+        assert_eq!(
+            result.resolved_deps[0].path,
+            dep_pkg.canonicalize().unwrap()
+        );
+        assert!(!temp_dir.path().join("fusabi_packages").exists());
+    }
+
+    #[test]
+    fn test_build_workspace_library_member() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("fusabi.toml"),
+            "[workspace]\nmembers = [\"app\", \"core\"]\n",
+        )
+        .unwrap();
+        let app = create_test_package(&temp_dir, "app");
+        let core = create_test_package(&temp_dir, "core");
+        fs::remove_file(core.join("src/main.fsx")).unwrap();
+        fs::write(core.join("src/lib.fsx"), "let answer = 42\n").unwrap();
+        fs::write(app.join("src/main.fsx"), "Core.answer").unwrap();
+        fs::write(
+            app.join("fusabi.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\ncore = { workspace = true }\n",
+        )
+        .unwrap();
+
+        // Build every member, as `fpm build` does at the workspace root
+        let workspace = Workspace::load(temp_dir.path()).unwrap().unwrap();
+        for member in workspace.select(None, temp_dir.path()).unwrap() {
+            PackageBuilder::new(member.path.clone())
+                .workspace(workspace.clone())
+                .build()
+                .unwrap();
+        }
+
+        let bytes = fs::read(core.join("target/core.fzp")).unwrap();
+        let artifact = fusabi_vm::Artifact::from_bytes(&bytes).unwrap();
+        assert_eq!(artifact.entry(), None);
+        let exports: Vec<_> = artifact
+            .module("Core")
+            .unwrap()
+            .exports
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(exports, ["answer"]);
+
+        let bytes = fs::read(app.join("target/app.fzp")).unwrap();
+        let value = fusabi::execute_artifact(&bytes).unwrap();
+        assert_eq!(value, fusabi_vm::Value::Int(42));
+    }
 }
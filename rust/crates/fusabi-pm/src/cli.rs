@@ -1,11 +1,14 @@
 //! Command-line interface for the Fusabi Package Manager (fpm).
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use fusabi::doc::{DocFormat, DocGenerator};
+use fusabi::testing::{TestOutcome, TestReport, TestRunner};
 use fusabi_pm::{
-    install_dependencies, print_publish_instructions, publish_package, Dependency, Manifest,
-    Package, PackageBuilder, Registry,
+    print_publish_instructions, publish_package, Dependency, Installer, Manifest, Package,
+    PackageBuilder, Registry, SourceConfig, Workspace,
 };
 use std::fs;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "fpm")]
@@ -16,6 +19,27 @@ struct Cli {
     command: Commands,
 }
 
+/// Features to build the package with.
+#[derive(Args)]
+struct FeatureArgs {
+    /// Features to enable, separated by commas
+    #[arg(long, value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Do not enable the default feature
+    #[arg(long)]
+    no_default_features: bool,
+}
+
+impl FeatureArgs {
+    /// Applies the features to `builder`.
+    fn apply(&self, builder: PackageBuilder) -> PackageBuilder {
+        builder
+            .features(self.features.iter().cloned())
+            .default_features(!self.no_default_features)
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize a new Fusabi package
@@ -24,10 +48,43 @@ enum Commands {
         #[arg(long)]
         name: Option<String>,
     },
-    /// Build the current package
-    Build,
+    /// Build the current package, or the members of the current workspace
+    Build {
+        /// Build only this workspace member
+        #[arg(short, long)]
+        package: Option<String>,
+
+        #[command(flatten)]
+        features: FeatureArgs,
+    },
     /// Run the current package
-    Run,
+    Run {
+        #[command(flatten)]
+        features: FeatureArgs,
+    },
+    /// Run the tests in tests/*.fsx of the current package or workspace members
+    Test {
+        /// Run only tests whose name (file::test) contains this text
+        filter: Option<String>,
+
+        /// Test only this workspace member
+        #[arg(short, long)]
+        package: Option<String>,
+
+        /// Write the results as JUnit XML to this file
+        #[arg(long)]
+        junit: Option<PathBuf>,
+    },
+    /// Write API documentation of the current package to target/doc
+    Doc {
+        /// Format of the pages: markdown or html
+        #[arg(long, default_value = "markdown", value_parser = ["markdown", "html"])]
+        format: String,
+
+        /// Do not run the examples in documentation comments
+        #[arg(long)]
+        no_doctests: bool,
+    },
     /// Add a dependency to the current package
     Add {
         /// Package name to add
@@ -38,7 +95,25 @@ enum Commands {
         version: Option<String>,
     },
     /// Install dependencies from fusabi.toml
-    Install,
+    Install {
+        /// Install exactly the versions recorded in fusabi.lock
+        #[arg(long)]
+        locked: bool,
+
+        /// Fail instead of fetching from the network
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Copy all resolved dependencies into a directory for offline builds
+    Vendor {
+        /// Directory to copy the dependencies into
+        #[arg(default_value = "vendor")]
+        path: PathBuf,
+
+        /// Vendor exactly the versions recorded in fusabi.lock
+        #[arg(long)]
+        locked: bool,
+    },
     /// Update the registry index
     Update,
     /// Search for packages in the registry
@@ -60,30 +135,59 @@ fn main() {
                 std::process::exit(1);
             }
         }
-        Commands::Build => {
-            if let Err(e) = build_package() {
+        Commands::Build { package, features } => {
+            if let Err(e) = build_package(package.as_deref(), &features) {
                 eprintln!("Error building package: {}", e);
                 std::process::exit(1);
             }
         }
-        Commands::Run => {
-            if let Err(e) = run_package() {
+        Commands::Run { features } => {
+            if let Err(e) = run_package(&features) {
                 eprintln!("Error running package: {}", e);
                 std::process::exit(1);
             }
         }
+        Commands::Test {
+            filter,
+            package,
+            junit,
+        } => match run_tests(filter, package.as_deref(), junit) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("Error running tests: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Doc {
+            format,
+            no_doctests,
+        } => match run_doc(&format, !no_doctests) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("Error documenting package: {}", e);
+                std::process::exit(1);
+            }
+        },
         Commands::Add { package, version } => {
             if let Err(e) = add_package(package, version) {
                 eprintln!("Error adding package: {}", e);
                 std::process::exit(1);
             }
         }
-        Commands::Install => {
-            if let Err(e) = run_install() {
+        Commands::Install { locked, offline } => {
+            if let Err(e) = run_install(locked, offline) {
                 eprintln!("Error installing dependencies: {}", e);
                 std::process::exit(1);
             }
         }
+        Commands::Vendor { path, locked } => {
+            if let Err(e) = run_vendor(&path, locked) {
+                eprintln!("Error vendoring dependencies: {}", e);
+                std::process::exit(1);
+            }
+        }
         Commands::Update => {
             if let Err(e) = run_update() {
                 eprintln!("Error updating registry: {}", e);
@@ -105,10 +209,45 @@ fn main() {
     }
 }
 
-/// Installs dependencies from fusabi.toml.
-fn run_install() -> Result<(), Box<dyn std::error::Error>> {
+/// The root of the workspace containing the current directory, or the
+/// current directory.
+fn project_root() -> Result<PathBuf, Box<dyn std::error::Error>> {
     let current_dir = std::env::current_dir()?;
-    install_dependencies(&current_dir)?;
+    Ok(match Workspace::find(&current_dir)? {
+        Some(workspace) => workspace.root().to_path_buf(),
+        None => current_dir,
+    })
+}
+
+/// Installs dependencies from fusabi.toml, or from fusabi.lock if `locked`.
+/// Inside a workspace, installs for the whole workspace.
+fn run_install(locked: bool, offline: bool) -> Result<(), Box<dyn std::error::Error>> {
+    Installer::new(project_root()?)
+        .locked(locked)
+        .offline(offline)
+        .install()?;
+    Ok(())
+}
+
+/// Copies the resolved dependencies into `path` and explains how to build
+/// from them.
+fn run_vendor(path: &Path, locked: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let root = project_root()?;
+    let vendor_dir = root.join(path);
+    let vendored = Installer::new(root.clone())
+        .locked(locked)
+        .vendor(&vendor_dir)?;
+    println!(
+        "\nVendored {} package(s) into {}",
+        vendored.packages.len(),
+        path.display()
+    );
+
+    if SourceConfig::load(&root)?.vendor.as_deref() != Some(vendor_dir.as_path()) {
+        println!("\nTo install from the vendored packages, add to fusabi.toml:\n");
+        println!("[source]");
+        println!("vendor = \"{}\"", path.display());
+    }
     Ok(())
 }
 
@@ -120,10 +259,15 @@ fn run_publish() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-/// Updates the registry index by fetching the latest from fusabi-community.
+/// The registry configured for the current package.
+fn registry() -> Result<Registry, Box<dyn std::error::Error>> {
+    Ok(SourceConfig::load(&project_root()?)?.registry())
+}
+
+/// Updates the registry index by fetching the latest from fusabi-community,
+/// or checks the configured local registry.
 fn run_update() -> Result<(), Box<dyn std::error::Error>> {
-    let registry = Registry::new();
-    registry.update()?;
+    registry()?.update()?;
     Ok(())
 }
 
@@ -131,7 +275,7 @@ fn run_update() -> Result<(), Box<dyn std::error::Error>> {
 fn run_search(query: &str) -> Result<(), Box<dyn std::error::Error>> {
     println!("Searching for '{}'...\n", query);
 
-    let registry = Registry::new();
+    let registry = registry()?;
 
     match registry.search(query) {
         Ok(matches) => {
@@ -254,24 +398,166 @@ fn add_package(package: String, version: Option<String>) -> Result<(), Box<dyn s
     Ok(())
 }
 
-/// Builds the current Fusabi package.
-fn build_package() -> Result<(), Box<dyn std::error::Error>> {
+/// Builds the current Fusabi package, or the selected workspace members.
+fn build_package(
+    package: Option<&str>,
+    features: &FeatureArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
     let current_dir = std::env::current_dir()?;
 
-    let builder = PackageBuilder::new(current_dir).verbose(true);
-    let result = builder.build()?;
+    let Some(workspace) = Workspace::find(&current_dir)? else {
+        if package.is_some() {
+            return Err("-p can only be used in a workspace".into());
+        }
+        let builder = features.apply(PackageBuilder::new(current_dir).verbose(true));
+        let result = builder.build()?;
+
+        println!(
+            "Output: {} ({} bytes)",
+            result.output_path.display(),
+            result.output_size
+        );
+        return Ok(());
+    };
+
+    for member in workspace.select(package, &current_dir)? {
+        let builder = PackageBuilder::new(member.path.clone())
+            .workspace(workspace.clone())
+            .verbose(true);
+        let result = features.apply(builder).build()?;
+
+        println!(
+            "Output: {} ({} bytes)",
+            result.output_path.display(),
+            result.output_size
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs the tests of the current package, or of the selected workspace
+/// members, returning whether they all passed.
+fn run_tests(
+    filter: Option<String>,
+    package: Option<&str>,
+    junit: Option<PathBuf>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let current_dir = std::env::current_dir()?;
+    let packages = match Workspace::find(&current_dir)? {
+        Some(workspace) => workspace
+            .select(package, &current_dir)?
+            .into_iter()
+            .map(|member| member.path.clone())
+            .collect(),
+        None if package.is_some() => return Err("-p can only be used in a workspace".into()),
+        None => vec![current_dir],
+    };
+
+    let mut runner = TestRunner::new();
+    if let Some(filter) = filter {
+        runner = runner.filter(filter);
+    }
+    let mut report = TestReport::default();
+    for package in packages {
+        let tests = package.join("tests");
+        if tests.is_dir() {
+            report.suites.extend(runner.run(&tests)?.suites);
+        }
+    }
 
+    for suite in &report.suites {
+        for case in &suite.cases {
+            let status = match case.outcome {
+                TestOutcome::Passed => "ok",
+                TestOutcome::Failed(_) => "FAILED",
+                TestOutcome::Errored(_) => "ERROR",
+            };
+            println!("test {}::{} ... {}", suite.name(), case.name, status);
+        }
+    }
+    for suite in &report.suites {
+        for case in &suite.cases {
+            if let Some(failure) = case.failure() {
+                println!("\n---- {}::{} ----", suite.name(), case.name);
+                print!("{}", failure.report);
+            }
+        }
+    }
     println!(
-        "Output: {} ({} bytes)",
-        result.output_path.display(),
-        result.output_size
+        "\ntest result: {}. {} passed; {} failed; {} errors",
+        if report.is_success() { "ok" } else { "FAILED" },
+        report.passed(),
+        report.failures(),
+        report.errors()
     );
 
-    Ok(())
+    if let Some(junit) = junit {
+        fs::write(junit, report.to_junit_xml())?;
+    }
+    Ok(report.is_success())
 }
 
-/// Runs the current Fusabi package.
-fn run_package() -> Result<(), Box<dyn std::error::Error>> {
+/// Documents the sources of the current package, returning whether all
+/// examples passed.
+fn run_doc(format: &str, doctests: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let current_dir = std::env::current_dir()?;
+    let manifest_path = current_dir.join("fusabi.toml");
+    if !manifest_path.exists() {
+        return Err("fusabi.toml not found. Run 'fpm init' first.".into());
+    }
+    let manifest = Manifest::load(&manifest_path)?;
+    let format = match format {
+        "html" => DocFormat::Html,
+        _ => DocFormat::Markdown,
+    };
+
+    let docs = DocGenerator::new()
+        .title(&manifest.package.name)
+        .doctests(doctests)
+        .document(current_dir.join("src"))?;
+    let output = current_dir.join("target").join("doc");
+    docs.write(&output, format)?;
+
+    for doctest in &docs.doctests {
+        let status = if doctest.passed() { "ok" } else { "FAILED" };
+        let path = doctest
+            .path
+            .strip_prefix(&current_dir)
+            .unwrap_or(&doctest.path);
+        println!(
+            "doctest {}::{} ... {}",
+            path.display(),
+            doctest.item,
+            status
+        );
+        if let Some(error) = &doctest.error {
+            print!("{}", doctest.source);
+            println!("Error: {}", error);
+        }
+    }
+    let failed = docs.doctests.iter().filter(|d| !d.passed()).count();
+    println!(
+        "Documented {} of {} in {}; {} passed; {} failed",
+        plural(docs.pages.len(), "file"),
+        manifest.package.name,
+        output.display(),
+        plural(docs.doctests.len() - failed, "doctest"),
+        failed
+    );
+    Ok(failed == 0)
+}
+
+/// `count` followed by `noun`, in the plural unless `count` is 1
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+/// Builds the current Fusabi package and runs its artifact.
+fn run_package(features: &FeatureArgs) -> Result<(), Box<dyn std::error::Error>> {
     let current_dir = std::env::current_dir()?;
     let manifest_path = current_dir.join("fusabi.toml");
 
@@ -284,16 +570,14 @@ fn run_package() -> Result<(), Box<dyn std::error::Error>> {
     let manifest = Manifest::load(&manifest_path)?;
     println!("Running {}...", manifest.package.name);
 
-    // Find main entry point
-    let main_path = current_dir.join("src").join("main.fsx");
-    if !main_path.exists() {
-        return Err("src/main.fsx not found".into());
-    }
-
-    // Read and execute source code
-    let source = fs::read_to_string(&main_path)?;
+    // Build, linking in the dependencies
+    let builder = match Workspace::find(&current_dir)? {
+        Some(workspace) => PackageBuilder::new(current_dir).workspace(workspace),
+        None => PackageBuilder::new(current_dir),
+    };
+    let artifact = fs::read(features.apply(builder).build()?.output_path)?;
 
-    match fusabi::run_source(&source) {
+    match fusabi::execute_artifact(&artifact) {
         Ok(result) => {
             // Print result if not Unit
             if !matches!(result, fusabi_vm::Value::Unit) {
@@ -0,0 +1,237 @@
+//! Package features.
+//!
+//! The `[features]` table of a fusabi.toml names features and what each one
+//! enables:
+//!
+//! ```toml
+//! [dependencies]
+//! terminal = { version = "1.0", optional = true }
+//! json = { version = "2.0", features = ["pretty"], default-features = false }
+//!
+//! [features]
+//! default = ["color"]
+//! color = []
+//! # An optional dependency, and a feature of it
+//! tui = ["dep:terminal", "terminal/unicode"]
+//! # Another feature, and a feature of a dependency
+//! full = ["tui", "json/streaming"]
+//! ```
+//!
+//! An entry may also name an optional dependency without `dep:`. Optional
+//! dependencies are only built when a feature activates them. Enabled
+//! features, and the names of activated optional dependencies, are visible
+//! to `#if` directives in the package's sources.
+
+use crate::manifest::{Dependency, Manifest, ManifestError};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Features requested of a package, by the user or by its dependents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureRequest {
+    /// Features to enable.
+    pub features: BTreeSet<String>,
+    /// Whether to enable the `default` feature.
+    pub default_features: bool,
+}
+
+impl Default for FeatureRequest {
+    fn default() -> Self {
+        Self {
+            features: BTreeSet::new(),
+            default_features: true,
+        }
+    }
+}
+
+impl FeatureRequest {
+    /// Adds the features of `other` to this request.
+    pub fn merge(&mut self, other: FeatureRequest) {
+        self.features.extend(other.features);
+        self.default_features |= other.default_features;
+    }
+}
+
+/// The features enabled in a package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnabledFeatures {
+    /// Enabled features and activated optional dependencies.
+    pub features: BTreeSet<String>,
+    /// Features enabled in each dependency through `dep/feature` entries.
+    pub dependency_features: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl EnabledFeatures {
+    /// Whether the dependency `name` is built.
+    pub fn is_active(&self, name: &str, dependency: &Dependency) -> bool {
+        match dependency {
+            Dependency::Detailed(detailed) if detailed.optional => self.features.contains(name),
+            _ => true,
+        }
+    }
+
+    /// What is requested of the active dependency `name`.
+    pub fn request(&self, name: &str, dependency: &Dependency) -> FeatureRequest {
+        let mut request = match dependency {
+            Dependency::Simple(_) => FeatureRequest::default(),
+            Dependency::Detailed(detailed) => FeatureRequest {
+                features: detailed.features.iter().cloned().collect(),
+                default_features: detailed.default_features,
+            },
+        };
+        if let Some(features) = self.dependency_features.get(name) {
+            request.features.extend(features.iter().cloned());
+        }
+        request
+    }
+}
+
+/// Resolves the features of `manifest` that `request` enables.
+pub fn resolve_features(
+    manifest: &Manifest,
+    request: &FeatureRequest,
+) -> Result<EnabledFeatures, ManifestError> {
+    let mut enabled = EnabledFeatures::default();
+    let mut pending: Vec<String> = request.features.iter().cloned().collect();
+    if request.default_features && manifest.features.contains_key("default") {
+        pending.push("default".to_string());
+    }
+
+    while let Some(feature) = pending.pop() {
+        if let Some(name) = feature.strip_prefix("dep:") {
+            enabled.features.insert(name.to_string());
+        } else if let Some((name, dep_feature)) = feature.split_once('/') {
+            if is_optional(manifest, name) {
+                enabled.features.insert(name.to_string());
+            }
+            enabled
+                .dependency_features
+                .entry(name.to_string())
+                .or_default()
+                .insert(dep_feature.to_string());
+        } else if enabled.features.insert(feature.clone()) {
+            match manifest.features.get(&feature) {
+                Some(entries) => pending.extend(entries.iter().cloned()),
+                None if is_optional(manifest, &feature) => {}
+                None => {
+                    return Err(ManifestError::UnknownFeature {
+                        package: manifest.package.name.clone(),
+                        feature,
+                    })
+                }
+            }
+        }
+    }
+
+    Ok(enabled)
+}
+
+/// Checks that every entry of the `[features]` table names a feature or a
+/// dependency of the package.
+pub(crate) fn validate_features(manifest: &Manifest) -> Result<(), ManifestError> {
+    for (feature, entries) in &manifest.features {
+        for entry in entries {
+            let valid = if let Some(name) = entry.strip_prefix("dep:") {
+                is_optional(manifest, name)
+            } else if let Some((name, _)) = entry.split_once('/') {
+                manifest.dependencies.contains_key(name)
+            } else {
+                manifest.features.contains_key(entry) || is_optional(manifest, entry)
+            };
+            if !valid {
+                return Err(ManifestError::InvalidManifest(format!(
+                    "feature '{}' enables '{}', which is not a feature or optional dependency",
+                    feature, entry
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` is an optional dependency of the package.
+fn is_optional(manifest: &Manifest, name: &str) -> bool {
+    matches!(
+        manifest.dependencies.get(name),
+        Some(Dependency::Detailed(detailed)) if detailed.optional
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+[package]
+name = "widgets"
+version = "0.1.0"
+
+[dependencies]
+terminal = { version = "1.0.0", optional = true }
+json = { version = "2.0.0", features = ["pretty"], default-features = false }
+
+[features]
+default = ["color"]
+color = []
+tui = ["dep:terminal", "terminal/unicode"]
+full = ["tui", "json/streaming"]
+"#;
+
+    fn request(features: &[&str], default_features: bool) -> FeatureRequest {
+        FeatureRequest {
+            features: features.iter().map(|f| f.to_string()).collect(),
+            default_features,
+        }
+    }
+
+    #[test]
+    fn test_resolve_features() {
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+        let terminal = &manifest.dependencies["terminal"];
+        let json = &manifest.dependencies["json"];
+
+        let enabled = resolve_features(&manifest, &FeatureRequest::default()).unwrap();
+        let features: Vec<_> = enabled.features.iter().map(String::as_str).collect();
+        assert_eq!(features, ["color", "default"]);
+        assert!(!enabled.is_active("terminal", terminal));
+        assert!(enabled.is_active("json", json));
+        assert_eq!(enabled.request("json", json), request(&["pretty"], false));
+
+        let enabled = resolve_features(&manifest, &request(&["full"], false)).unwrap();
+        let features: Vec<_> = enabled.features.iter().map(String::as_str).collect();
+        assert_eq!(features, ["full", "terminal", "tui"]);
+        assert!(enabled.is_active("terminal", terminal));
+        assert_eq!(
+            enabled.request("terminal", terminal),
+            request(&["unicode"], true)
+        );
+        assert_eq!(
+            enabled.request("json", json),
+            request(&["pretty", "streaming"], false)
+        );
+
+        assert!(matches!(
+            resolve_features(&manifest, &request(&["gpu"], true)),
+            Err(ManifestError::UnknownFeature { feature, .. }) if feature == "gpu"
+        ));
+    }
+
+    #[test]
+    fn test_validate_features() {
+        let invalid = MANIFEST.replace("color = []", "color = [\"dep:json\"]");
+        assert!(matches!(
+            Manifest::parse(&invalid),
+            Err(ManifestError::InvalidManifest(_))
+        ));
+        let invalid = MANIFEST.replace("color = []", "color = [\"missing/x\"]");
+        assert!(Manifest::parse(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_merge_request() {
+        let mut merged = request(&["a"], false);
+        merged.merge(request(&["a", "b"], false));
+        assert_eq!(merged, request(&["a", "b"], false));
+        merged.merge(request(&[], true));
+        assert_eq!(merged, request(&["a", "b"], true));
+    }
+}
@@ -1,9 +1,38 @@
-//! Install command for cloning git dependencies.
+//! Install command for resolving and fetching dependencies.
+//!
+//! Dependencies are resolved transitively: registry packages through
+//! [`Registry::resolve`], git repositories and local paths as written, and
+//! then the dependencies in each package's own `fusabi.toml`. Registry and
+//! git packages are checked out in `fusabi_packages/<name>`. A package
+//! required twice must satisfy every requirement, or resolution fails with a
+//! conflict.
+//!
+//! The first requirement seen for a registry package selects its newest
+//! matching version. When a later requirement rules that out, resolution
+//! backtracks: older matching versions, of the package and of those
+//! selected before it, are tried before failing with a conflict. The
+//! result is written to `fusabi.lock`. At a workspace root, the
+//! dependencies of all members are resolved together into one lockfile.
+//!
+//! With `--locked`, the packages are instead checked out at the commits the
+//! lockfile records and verified against its checksums.
+//!
+//! The `[source]` section of the root fusabi.toml ([`SourceConfig`]) can
+//! point the registry at a local index, clone git repositories from mirrors,
+//! install from a vendor directory written by [`Installer::vendor`], or
+//! forbid network access altogether.
 
+use crate::lockfile::{
+    checksum_dir, LockedPackage, Lockfile, LockfileError, PackageSource, LOCKFILE_NAME,
+};
 use crate::manifest::{Dependency, Manifest, ManifestError};
-use crate::registry::{Registry, RegistryError};
+use crate::registry::{local_path, Registry, RegistryError, SemVer};
+use crate::source::SourceConfig;
+use crate::vendor::{copy_dir, load_vendored, vendor_packages, verify};
+use crate::workspace::{Member, Workspace, WorkspaceError};
 use git2::Repository;
-use std::path::Path;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -11,6 +40,9 @@ pub enum InstallError {
     #[error("Manifest error: {0}")]
     Manifest(#[from] ManifestError),
 
+    #[error("Lockfile error: {0}")]
+    Lockfile(#[from] LockfileError),
+
     #[error("Git error: {0}")]
     Git(#[from] git2::Error),
 
@@ -20,121 +52,771 @@ pub enum InstallError {
     #[error("Registry error: {0}")]
     Registry(#[from] RegistryError),
 
+    #[error("Workspace error: {0}")]
+    Workspace(#[from] WorkspaceError),
+
+    #[error("Version conflict for '{name}': '{required_by}' requires {required}, but {selected} was selected")]
+    Conflict {
+        name: String,
+        required: String,
+        required_by: String,
+        selected: String,
+    },
+
+    #[error("Checksum mismatch for '{name}': fusabi.lock has {expected}, found {found}")]
+    ChecksumMismatch {
+        name: String,
+        expected: String,
+        found: String,
+    },
+
+    #[error("fusabi.lock is out of date: {0}. Run 'fpm install' without --locked to update it.")]
+    LockfileOutOfDate(String),
+
+    #[error(
+        "'{name}' ({required}) is not vendored. Run 'fpm vendor' to update the vendor directory."
+    )]
+    NotVendored { name: String, required: String },
+
+    #[error("Cannot fetch {0} while offline. Vendor the dependencies or add a local mirror to [source.replace].")]
+    Offline(String),
+
     #[error("{0}")]
     Other(String),
 }
 
+/// Installs the dependencies of the package in `project_dir` and writes
+/// `fusabi.lock`.
 pub fn install_dependencies(project_dir: &Path) -> Result<(), InstallError> {
-    let manifest_path = project_dir.join("fusabi.toml");
-    if !manifest_path.exists() {
-        return Err(InstallError::Other(
-            "fusabi.toml not found. Run 'fpm init' first.".to_string(),
-        ));
-    }
-
-    let manifest = Manifest::load(&manifest_path)?;
-    let packages_dir = project_dir.join("fusabi_packages");
-
-    if manifest.dependencies.is_empty() {
-        println!("No dependencies to install.");
-        return Ok(());
-    }
-
-    std::fs::create_dir_all(&packages_dir)?;
-
-    let registry = Registry::new();
-
-    for (name, dependency) in &manifest.dependencies {
-        match dependency {
-            Dependency::Detailed(detailed) => {
-                if let Some(git_url) = &detailed.git {
-                    install_git_dependency(name, git_url, detailed.rev.as_deref(), &packages_dir)?;
-                } else if detailed.path.is_some() {
-                    println!("Skipping local path dependency '{}'", name);
-                } else if let Some(version) = &detailed.version {
-                    install_registry_dependency(name, version, &packages_dir, &registry)?;
-                } else {
-                    println!(
-                        "Skipping dependency '{}': no git, path, or version specified",
-                        name
-                    );
-                }
+    Installer::new(project_dir.to_path_buf()).install()?;
+    Ok(())
+}
+
+/// Installer for the dependencies of a package.
+pub struct Installer {
+    /// Root directory of the package.
+    project_root: PathBuf,
+    /// Directory registry and git dependencies are checked out in.
+    packages_dir: PathBuf,
+    /// Registry used to resolve version requirements, instead of the one
+    /// the source configuration names.
+    registry: Option<Registry>,
+    /// Install exactly what fusabi.lock records.
+    locked: bool,
+    /// Never fetch from the network.
+    offline: bool,
+}
+
+/// Where one install gets packages from.
+struct Sources {
+    config: SourceConfig,
+    registry: Registry,
+    /// The vendored packages, when installing from the vendor directory.
+    vendored: Option<Vendored>,
+    offline: bool,
+}
+
+/// The contents of a vendor directory.
+struct Vendored {
+    dir: PathBuf,
+    packages: Lockfile,
+}
+
+/// A dependency requirement, as written in a manifest.
+#[derive(Debug, Clone, PartialEq)]
+enum Requirement {
+    Registry(String),
+    Git { url: String, rev: Option<String> },
+    Path(PathBuf),
+}
+
+impl std::fmt::Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Requirement::Registry(constraint) => write!(f, "version '{}'", constraint),
+            Requirement::Git { url, rev: None } => write!(f, "git {}", url),
+            Requirement::Git {
+                url,
+                rev: Some(rev),
+            } => write!(f, "git {} at {}", url, rev),
+            Requirement::Path(path) => write!(f, "path {}", path.display()),
+        }
+    }
+}
+
+/// A requirement waiting to be resolved.
+#[derive(Clone)]
+struct Pending {
+    name: String,
+    requirement: Requirement,
+    required_by: String,
+}
+
+/// The packages selected so far.
+#[derive(Clone, Default)]
+struct Resolution {
+    packages: Vec<LockedPackage>,
+    /// The requirement each package was selected for, and what required it
+    selected_for: HashMap<String, (Requirement, String)>,
+}
+
+/// What resolution reads besides the requirements.
+struct Context<'a> {
+    workspace: Option<&'a Workspace>,
+    previous: Option<&'a Lockfile>,
+    sources: &'a Sources,
+}
+
+impl Installer {
+    /// Creates an installer for the given project root, using the sources
+    /// its fusabi.toml configures.
+    pub fn new(project_root: PathBuf) -> Self {
+        let packages_dir = project_root.join("fusabi_packages");
+        Self {
+            project_root,
+            packages_dir,
+            registry: None,
+            locked: false,
+            offline: false,
+        }
+    }
+
+    /// Resolves registry dependencies with the given registry.
+    pub fn registry(mut self, registry: Registry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Installs exactly the packages fusabi.lock records.
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    /// Fails instead of fetching from the network.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Installs the dependencies and returns the lockfile describing them.
+    pub fn install(&self) -> Result<Lockfile, InstallError> {
+        self.install_from(true)
+    }
+
+    /// Installs the dependencies, fetching them rather than using the
+    /// vendor directory, and copies them into `vendor_dir`. Returns the
+    /// lockfile of the vendored packages.
+    pub fn vendor(&self, vendor_dir: &Path) -> Result<Lockfile, InstallError> {
+        let lockfile = self.install_from(false)?;
+        vendor_packages(&lockfile, &self.packages_dir, vendor_dir)
+    }
+
+    fn sources(&self, use_vendor: bool) -> Result<Sources, InstallError> {
+        let config = SourceConfig::load(&self.project_root)?;
+        let offline = self.offline || config.offline;
+        let registry = self
+            .registry
+            .clone()
+            .unwrap_or_else(|| config.registry())
+            .offline(offline);
+        let vendored = match &config.vendor {
+            Some(dir) if use_vendor => Some(Vendored {
+                dir: dir.clone(),
+                packages: load_vendored(dir)?,
+            }),
+            _ => None,
+        };
+        Ok(Sources {
+            config,
+            registry,
+            vendored,
+            offline,
+        })
+    }
+
+    fn install_from(&self, use_vendor: bool) -> Result<Lockfile, InstallError> {
+        let manifest_path = self.project_root.join("fusabi.toml");
+        if !manifest_path.exists() {
+            return Err(InstallError::Other(
+                "fusabi.toml not found. Run 'fpm init' first.".to_string(),
+            ));
+        }
+
+        // A workspace installs the dependencies of all its members
+        let workspace = Workspace::load(&self.project_root)?;
+        let roots = match &workspace {
+            Some(workspace) => workspace.members().to_vec(),
+            None => {
+                let manifest = Manifest::load(&manifest_path)?;
+                vec![Member {
+                    name: manifest.package.name.clone(),
+                    path: self.project_root.clone(),
+                    manifest,
+                }]
             }
-            Dependency::Simple(version) => {
-                install_registry_dependency(name, version, &packages_dir, &registry)?;
+        };
+        let lock_path = self.project_root.join(LOCKFILE_NAME);
+        let sources = self.sources(use_vendor)?;
+
+        if self.locked {
+            if !lock_path.exists() {
+                return Err(InstallError::Other(
+                    "fusabi.lock not found. Run 'fpm install' to create it.".to_string(),
+                ));
             }
+            let lockfile = Lockfile::load(&lock_path)?;
+            self.install_locked(&roots, &lockfile, &sources)?;
+            return Ok(lockfile);
         }
+
+        let previous = if lock_path.exists() {
+            Some(Lockfile::load(&lock_path)?)
+        } else {
+            None
+        };
+
+        if roots
+            .iter()
+            .all(|root| root.manifest.dependencies.is_empty())
+        {
+            println!("No dependencies to install.");
+        }
+
+        let lockfile = self.resolve(&roots, workspace.as_ref(), previous.as_ref(), &sources)?;
+        lockfile.save(&lock_path)?;
+
+        println!("Install complete.");
+        Ok(lockfile)
     }
 
-    println!("Install complete.");
-    Ok(())
-}
+    /// Resolves and fetches every dependency of `roots`, transitively.
+    fn resolve(
+        &self,
+        roots: &[Member],
+        workspace: Option<&Workspace>,
+        previous: Option<&Lockfile>,
+        sources: &Sources,
+    ) -> Result<Lockfile, InstallError> {
+        let mut queue = VecDeque::new();
+        for root in roots {
+            self.enqueue(&root.name, &root.manifest, &root.path, &mut queue)?;
+        }
+        let context = Context {
+            workspace,
+            previous,
+            sources,
+        };
+        let resolution = self.select(queue, Resolution::default(), &context)?;
+        Ok(Lockfile::new(resolution.packages))
+    }
+
+    /// Selects a package for each requirement in `queue`, and for their
+    /// dependencies, adding to `resolution`. A registry requirement tries
+    /// its matching versions newest first, moving on to the next version
+    /// when the rest cannot be resolved with one. The conflict reported is
+    /// the one met with the newest version.
+    fn select(
+        &self,
+        mut queue: VecDeque<Pending>,
+        mut resolution: Resolution,
+        context: &Context,
+    ) -> Result<Resolution, InstallError> {
+        while let Some(pending) = queue.pop_front() {
+            if let Some(package) = resolution.packages.iter().find(|p| p.name == pending.name) {
+                check_requirement(package, &pending.requirement, &self.project_root).map_err(
+                    |selected| {
+                        let (first, first_by) = &resolution.selected_for[&pending.name];
+                        InstallError::Conflict {
+                            name: pending.name.clone(),
+                            required: pending.requirement.to_string(),
+                            required_by: pending.required_by.clone(),
+                            selected: format!("{} (for {} of '{}')", selected, first, first_by),
+                        }
+                    },
+                )?;
+                continue;
+            }
+
+            let mut choices = self.choices(&pending, context)?;
+            if choices.len() == 1 {
+                self.add(&pending, &choices[0], &mut queue, &mut resolution, context)?;
+                continue;
+            }
 
-fn install_git_dependency(
-    name: &str,
-    git_url: &str,
-    rev: Option<&str>,
-    packages_dir: &Path,
-) -> Result<(), InstallError> {
-    let dep_path = packages_dir.join(name);
+            let mut conflict = None;
+            for choice in choices.drain(..) {
+                let mut queue = queue.clone();
+                let mut resolution = resolution.clone();
+                let selected = self
+                    .add(&pending, &choice, &mut queue, &mut resolution, context)
+                    .and_then(|()| self.select(queue, resolution, context));
+                match selected {
+                    Ok(resolution) => return Ok(resolution),
+                    Err(error @ InstallError::Conflict { .. }) => {
+                        conflict.get_or_insert(error);
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+            return Err(conflict.expect("a registry requirement has several versions"));
+        }
+        Ok(resolution)
+    }
 
-    if dep_path.exists() {
-        println!("Dependency '{}' already exists, skipping.", name);
-        return Ok(());
+    /// The requirements to fetch `pending` by, in the order to try them:
+    /// for a registry package, each matching version, the locked one
+    /// first and then newest first.
+    fn choices(
+        &self,
+        pending: &Pending,
+        context: &Context,
+    ) -> Result<Vec<Requirement>, InstallError> {
+        let constraint = match &pending.requirement {
+            Requirement::Registry(constraint) if context.sources.vendored.is_none() => constraint,
+            requirement => return Ok(vec![requirement.clone()]),
+        };
+        println!("Resolving '{}' ({})...", pending.name, constraint);
+        let mut versions: Vec<String> = context
+            .sources
+            .registry
+            .candidates(&pending.name, constraint)?
+            .into_iter()
+            .map(|resolved| resolved.version)
+            .collect();
+        if versions.is_empty() {
+            return Err(
+                RegistryError::NoMatchingVersion(pending.name.clone(), constraint.clone()).into(),
+            );
+        }
+        let locked = context
+            .previous
+            .and_then(|lock| lock.get(&pending.name))
+            .filter(|p| matches!(p.source, PackageSource::Registry(_)));
+        if let Some(i) = locked.and_then(|p| versions.iter().position(|v| *v == p.version)) {
+            let version = versions.remove(i);
+            versions.insert(0, version);
+        }
+        Ok(versions
+            .into_iter()
+            .map(|version| Requirement::Registry(format!("={}", version)))
+            .collect())
     }
 
-    println!("Cloning '{}'...", name);
+    /// Fetches `pending` by `choice` into `resolution`, queueing its
+    /// dependencies.
+    fn add(
+        &self,
+        pending: &Pending,
+        choice: &Requirement,
+        queue: &mut VecDeque<Pending>,
+        resolution: &mut Resolution,
+        context: &Context,
+    ) -> Result<(), InstallError> {
+        let locked = context.previous.and_then(|lock| lock.get(&pending.name));
+        let (mut package, dir) = self.fetch(&pending.name, choice, locked, context.sources)?;
 
-    let repo = Repository::clone(git_url, &dep_path)?;
+        let dep_manifest_path = dir.join("fusabi.toml");
+        if dep_manifest_path.exists() {
+            // Workspace members are used as they are, without copying
+            let dep_manifest = match context.workspace.and_then(|ws| ws.member_at(&dir)) {
+                Some(member) => member.manifest.clone(),
+                None => Manifest::load(&dep_manifest_path)?,
+            };
+            if !matches!(package.source, PackageSource::Registry(_)) {
+                package.version = dep_manifest.package.version.clone();
+            }
+            package.dependencies = sorted_names(&dep_manifest);
+            self.enqueue(&pending.name, &dep_manifest, &dir, queue)?;
+        }
 
-    if let Some(rev) = rev {
-        checkout_rev(&repo, rev)?;
+        resolution.selected_for.insert(
+            pending.name.clone(),
+            (pending.requirement.clone(), pending.required_by.clone()),
+        );
+        resolution.packages.push(package);
+        Ok(())
     }
 
-    println!("Installed '{}'", name);
-    Ok(())
-}
+    /// Queues the dependencies of `manifest`, which lives in `dir`.
+    fn enqueue(
+        &self,
+        required_by: &str,
+        manifest: &Manifest,
+        dir: &Path,
+        queue: &mut VecDeque<Pending>,
+    ) -> Result<(), InstallError> {
+        for name in sorted_names(manifest) {
+            match requirement(&manifest.dependencies[&name], dir) {
+                Some(requirement) => queue.push_back(Pending {
+                    name,
+                    requirement,
+                    required_by: required_by.to_string(),
+                }),
+                None => println!(
+                    "Skipping dependency '{}': no git, path, or version specified",
+                    name
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetches a package, preferring what the previous lockfile chose when
+    /// it still satisfies the requirement.
+    fn fetch(
+        &self,
+        name: &str,
+        requirement: &Requirement,
+        locked: Option<&LockedPackage>,
+        sources: &Sources,
+    ) -> Result<(LockedPackage, PathBuf), InstallError> {
+        if let (Some(vendored), Requirement::Registry(_) | Requirement::Git { .. }) =
+            (&sources.vendored, requirement)
+        {
+            let package = vendored
+                .packages
+                .get(name)
+                .filter(|p| check_requirement(p, requirement, &self.project_root).is_ok())
+                .ok_or_else(|| InstallError::NotVendored {
+                    name: name.to_string(),
+                    required: requirement.to_string(),
+                })?;
+            self.install_vendored(package, vendored, package.checksum.as_deref())?;
+            return Ok((package.clone(), self.packages_dir.join(name)));
+        }
+
+        let mut package = LockedPackage {
+            name: name.to_string(),
+            version: "0.0.0".to_string(),
+            source: PackageSource::Path(PathBuf::new()),
+            rev: None,
+            commit: None,
+            checksum: None,
+            dependencies: Vec::new(),
+        };
+
+        let dir = match requirement {
+            Requirement::Registry(constraint) => {
+                let locked = locked.filter(|p| {
+                    matches!(p.source, PackageSource::Registry(_))
+                        && check_requirement(p, requirement, &self.project_root).is_ok()
+                });
+                let resolved = match locked {
+                    Some(p) => sources.registry.resolve(name, &format!("={}", p.version))?,
+                    None => sources.registry.resolve(name, constraint)?,
+                };
 
-fn install_registry_dependency(
-    name: &str,
-    version_constraint: &str,
-    packages_dir: &Path,
-    registry: &Registry,
-) -> Result<(), InstallError> {
-    let dep_path = packages_dir.join(name);
+                let repo = self.checkout_repo(name, &resolved.git_url, sources)?;
+                let locked_commit = locked
+                    .filter(|p| p.source == PackageSource::Registry(resolved.git_url.clone()))
+                    .and_then(|p| p.commit.as_deref());
+                match (locked_commit, &resolved.rev) {
+                    (Some(commit), _) => checkout_rev(&repo, commit)?,
+                    (None, Some(rev)) => checkout_rev(&repo, rev).map_err(|e| {
+                        InstallError::Other(format!(
+                            "Cannot check out '{}' v{}: no tag '{}' in {} ({})",
+                            name,
+                            resolved.version,
+                            rev,
+                            resolved.git_url,
+                            e.message()
+                        ))
+                    })?,
+                    (None, None) => {}
+                }
+
+                println!(
+                    "Installed '{}' v{} from {}",
+                    resolved.name, resolved.version, resolved.git_url
+                );
+                package.version = resolved.version;
+                package.source = PackageSource::Registry(resolved.git_url);
+                package.commit = Some(head_commit(&repo)?);
+                self.packages_dir.join(name)
+            }
+            Requirement::Git { url, rev } => {
+                let repo = self.checkout_repo(name, url, sources)?;
+                // Without a rev, stay on the commit locked before
+                let locked_commit = locked
+                    .filter(|p| rev.is_none() && p.source == PackageSource::Git(url.clone()))
+                    .and_then(|p| p.commit.as_deref());
+                if let Some(rev) = rev.as_deref().or(locked_commit) {
+                    checkout_rev(&repo, rev)?;
+                }
 
-    if dep_path.exists() {
-        println!("Dependency '{}' already exists, skipping.", name);
-        return Ok(());
+                println!("Installed '{}' from {}", name, url);
+                package.source = PackageSource::Git(url.clone());
+                package.rev = rev.clone();
+                package.commit = Some(head_commit(&repo)?);
+                self.packages_dir.join(name)
+            }
+            Requirement::Path(path) => {
+                if !path.exists() {
+                    return Err(InstallError::Other(format!(
+                        "Path dependency '{}' not found at {}",
+                        name,
+                        path.display()
+                    )));
+                }
+                let root = self
+                    .project_root
+                    .canonicalize()
+                    .unwrap_or_else(|_| self.project_root.clone());
+                let relative = path
+                    .strip_prefix(&root)
+                    .map(Path::to_path_buf)
+                    .unwrap_or_else(|_| path.clone());
+                package.source = PackageSource::Path(relative);
+                return Ok((package, path.clone()));
+            }
+        };
+
+        package.checksum = Some(checksum_dir(&dir)?);
+        Ok((package, dir))
     }
 
-    println!("Resolving '{}' ({})...", name, version_constraint);
+    /// Opens the checkout of `name` in fusabi_packages, cloning `url`, or
+    /// the mirror replacing it, if there is none or it came from elsewhere.
+    fn checkout_repo(
+        &self,
+        name: &str,
+        url: &str,
+        sources: &Sources,
+    ) -> Result<Repository, InstallError> {
+        let url = sources.config.replace(url);
+        if sources.offline && local_path(url).is_none() {
+            return Err(InstallError::Offline(url.to_string()));
+        }
+        let dep_path = self.packages_dir.join(name);
 
-    let resolved = registry.resolve(name, version_constraint)?;
+        if dep_path.exists() {
+            let repo = Repository::open(&dep_path).map_err(|_| {
+                InstallError::Other(format!(
+                    "{} exists but is not a git checkout",
+                    dep_path.display()
+                ))
+            })?;
+            let same_origin = repo
+                .find_remote("origin")
+                .ok()
+                .is_some_and(|remote| remote.url() == Some(url));
+            if same_origin {
+                return Ok(repo);
+            }
+            drop(repo);
+            std::fs::remove_dir_all(&dep_path)?;
+        }
 
-    println!(
-        "Installing '{}' v{} from {}",
-        resolved.name, resolved.version, resolved.git_url
-    );
+        std::fs::create_dir_all(&self.packages_dir)?;
+        println!("Cloning '{}'...", name);
+        Ok(Repository::clone(url, &dep_path)?)
+    }
 
-    let repo = Repository::clone(&resolved.git_url, &dep_path)?;
+    /// Copies the vendored `package` into fusabi_packages, checking its
+    /// contents against `checksum`.
+    fn install_vendored(
+        &self,
+        package: &LockedPackage,
+        vendored: &Vendored,
+        checksum: Option<&str>,
+    ) -> Result<(), InstallError> {
+        let dep_path = self.packages_dir.join(&package.name);
+        if dep_path.exists() {
+            std::fs::remove_dir_all(&dep_path)?;
+        }
+        copy_dir(&vendored.dir.join(&package.name), &dep_path)?;
+        verify(&package.name, &dep_path, checksum)?;
+        println!(
+            "Installed '{}' v{} from {}",
+            package.name,
+            package.version,
+            vendored.dir.display()
+        );
+        Ok(())
+    }
 
-    if let Some(ref rev) = resolved.rev {
-        if let Err(e) = checkout_rev(&repo, rev) {
+    /// Checks out every package at the commit fusabi.lock records.
+    fn install_locked(
+        &self,
+        roots: &[Member],
+        lockfile: &Lockfile,
+        sources: &Sources,
+    ) -> Result<(), InstallError> {
+        for package in &lockfile.packages {
+            let url = match &package.source {
+                PackageSource::Registry(url) | PackageSource::Git(url) => url,
+                PackageSource::Path(path) => {
+                    let path = self.project_root.join(path);
+                    if !path.exists() {
+                        return Err(InstallError::Other(format!(
+                            "Path dependency '{}' not found at {}",
+                            package.name,
+                            path.display()
+                        )));
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(vendored) = &sources.vendored {
+                let vendored_package = vendored
+                    .packages
+                    .get(&package.name)
+                    .filter(|v| v.source == package.source && v.commit == package.commit)
+                    .ok_or_else(|| InstallError::NotVendored {
+                        name: package.name.clone(),
+                        required: format!("v{} from {}", package.version, package.source),
+                    })?;
+                self.install_vendored(vendored_package, vendored, package.checksum.as_deref())?;
+                continue;
+            }
+
+            let commit = package.commit.as_deref().ok_or_else(|| {
+                InstallError::LockfileOutOfDate(format!("'{}' has no locked commit", package.name))
+            })?;
+            let repo = self.checkout_repo(&package.name, url, sources)?;
+            checkout_rev(&repo, commit)?;
+
+            if let Some(expected) = &package.checksum {
+                let found = checksum_dir(&self.packages_dir.join(&package.name))?;
+                if &found != expected {
+                    return Err(InstallError::ChecksumMismatch {
+                        name: package.name.clone(),
+                        expected: expected.clone(),
+                        found,
+                    });
+                }
+            }
             println!(
-                "Warning: Could not checkout version tag '{}': {}. Using default branch.",
-                rev, e
+                "Installed '{}' v{} at {}",
+                package.name, package.version, commit
             );
         }
+
+        // Checked after checkout, so that git revs naming tags resolve
+        for root in roots {
+            for name in sorted_names(&root.manifest) {
+                let Some(requirement) = requirement(&root.manifest.dependencies[&name], &root.path)
+                else {
+                    continue;
+                };
+                let package = lockfile.get(&name).ok_or_else(|| {
+                    InstallError::LockfileOutOfDate(format!("'{}' is not locked", name))
+                })?;
+                check_requirement(package, &requirement, &self.project_root).map_err(
+                    |selected| {
+                        InstallError::LockfileOutOfDate(format!(
+                            "'{}' requires {} of '{}', but {} is locked",
+                            root.name, requirement, name, selected
+                        ))
+                    },
+                )?;
+            }
+        }
+
+        println!("Install complete.");
+        Ok(())
     }
+}
 
-    println!("Installed '{}' v{}", resolved.name, resolved.version);
-    Ok(())
+/// The requirement a dependency declared in `dir` makes, if it makes one.
+fn requirement(dependency: &Dependency, dir: &Path) -> Option<Requirement> {
+    match dependency {
+        Dependency::Simple(version) => Some(Requirement::Registry(version.clone())),
+        Dependency::Detailed(detailed) => {
+            if let Some(url) = &detailed.git {
+                Some(Requirement::Git {
+                    url: url.clone(),
+                    rev: detailed.rev.clone(),
+                })
+            } else if let Some(path) = &detailed.path {
+                let path = dir.join(path);
+                Some(Requirement::Path(path.canonicalize().unwrap_or(path)))
+            } else {
+                detailed.version.clone().map(Requirement::Registry)
+            }
+        }
+    }
+}
+
+/// Checks that a selected package satisfies `requirement`, describing the
+/// selection if it does not.
+fn check_requirement(
+    package: &LockedPackage,
+    requirement: &Requirement,
+    root: &Path,
+) -> Result<(), String> {
+    let satisfied = match (requirement, &package.source) {
+        (Requirement::Registry(constraint), PackageSource::Registry(_)) => {
+            SemVer::parse(&package.version).is_ok_and(|v| v.satisfies(constraint))
+        }
+        (Requirement::Git { url, rev }, PackageSource::Git(locked_url)) => {
+            url == locked_url
+                && rev.as_deref().map_or(true, |rev| {
+                    package.rev.as_deref() == Some(rev)
+                        || package
+                            .commit
+                            .as_deref()
+                            .is_some_and(|commit| rev_is(root, &package.name, rev, commit))
+                })
+        }
+        (Requirement::Path(path), PackageSource::Path(locked_path)) => {
+            let locked_path = root.join(locked_path);
+            locked_path.canonicalize().unwrap_or(locked_path) == *path
+        }
+        _ => false,
+    };
+    if satisfied {
+        Ok(())
+    } else {
+        Err(format!("v{} from {}", package.version, package.source))
+    }
+}
+
+/// Whether `rev` names `commit` in the checkout of package `name`.
+fn rev_is(root: &Path, name: &str, rev: &str, commit: &str) -> bool {
+    if commit.starts_with(rev) {
+        return true;
+    }
+    Repository::open(root.join("fusabi_packages").join(name))
+        .and_then(|repo| {
+            let id = repo.revparse_single(rev)?.peel_to_commit()?.id();
+            Ok(id.to_string() == commit)
+        })
+        .unwrap_or(false)
+}
+
+fn sorted_names(manifest: &Manifest) -> Vec<String> {
+    let mut names: Vec<String> = manifest.dependencies.keys().cloned().collect();
+    names.sort();
+    names
 }
 
+fn head_commit(repo: &Repository) -> Result<String, git2::Error> {
+    Ok(repo.head()?.peel_to_commit()?.id().to_string())
+}
+
+/// Checks out `rev`, fetching from origin first if it is not known locally.
 fn checkout_rev(repo: &Repository, rev: &str) -> Result<(), git2::Error> {
-    let (object, reference) = repo.revparse_ext(rev)?;
-    repo.checkout_tree(&object, None)?;
+    let (object, reference) = match repo.revparse_ext(rev) {
+        Ok(found) => found,
+        Err(_) => {
+            repo.find_remote("origin")?.fetch(
+                &[
+                    "+refs/heads/*:refs/remotes/origin/*",
+                    "+refs/tags/*:refs/tags/*",
+                ],
+                None,
+                None,
+            )?;
+            repo.revparse_ext(rev)?
+        }
+    };
+    repo.checkout_tree(
+        &object,
+        Some(git2::build::CheckoutBuilder::default().force()),
+    )?;
 
     match reference {
         Some(gref) => repo.set_head(gref.name().unwrap()),
@@ -145,6 +827,7 @@ fn checkout_rev(repo: &Repository, rev: &str) -> Result<(), git2::Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vendor::VENDOR_LOCK_NAME;
     use tempfile::TempDir;
 
     #[test]
@@ -154,6 +837,86 @@ mod tests {
         assert!(result.is_err());
     }
 
+    /// Commits the package `name` at `version` to its repository under
+    /// `root/repos`, tagged `v<version>`, and returns the repository path.
+    fn release(root: &Path, name: &str, version: &str, dependencies: &str) -> String {
+        let dir = root.join("repos").join(name);
+        let repo = Repository::open(&dir)
+            .or_else(|_| Repository::init(&dir))
+            .unwrap();
+        std::fs::write(
+            dir.join("fusabi.toml"),
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"{}\"\n\n[dependencies]\n{}",
+                name, version, dependencies
+            ),
+        )
+        .unwrap();
+
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("fpm", "fpm@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        let id = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                version,
+                &tree,
+                &parents,
+            )
+            .unwrap();
+        repo.tag_lightweight(
+            &format!("v{}", version),
+            &repo.find_object(id, None).unwrap(),
+            false,
+        )
+        .unwrap();
+        dir.to_string_lossy().into_owned()
+    }
+
+    /// Writes a registry index listing `(name, version)` releases under
+    /// `root/repos`.
+    fn registry(root: &Path, releases: &[(&str, &str)]) -> Registry {
+        let cache = root.join("cache");
+        let index_dir = cache.join("fusabi-community").join("registry");
+        std::fs::create_dir_all(&index_dir).unwrap();
+        let index: String = releases
+            .iter()
+            .map(|(name, version)| {
+                format!(
+                    "[[packages]]\nname = \"{}\"\nversion = \"{}\"\nrepository = \"{}\"\n\n",
+                    name,
+                    version,
+                    root.join("repos").join(name).display()
+                )
+            })
+            .collect();
+        std::fs::write(index_dir.join("index.toml"), index).unwrap();
+        Registry::with_cache_dir(cache)
+    }
+
+    /// Creates the project `app` with the given dependencies.
+    fn project(root: &Path, dependencies: &str) -> PathBuf {
+        let dir = root.join("app");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("fusabi.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+                dependencies
+            ),
+        )
+        .unwrap();
+        dir
+    }
+
     #[test]
     fn test_install_empty_dependencies() {
         let temp_dir = TempDir::new().unwrap();
@@ -167,4 +930,305 @@ version = "0.1.0"
         let result = install_dependencies(temp_dir.path());
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_install_resolves_transitively() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        release(root, "strings", "1.0.0", "");
+        release(root, "json", "1.2.0", "strings = \"^1.0\"\n");
+        let app = project(root, "json = \"^1.0\"\nlocal = { path = \"local\" }\n");
+        std::fs::create_dir_all(app.join("local")).unwrap();
+        std::fs::write(
+            app.join("local/fusabi.toml"),
+            "[package]\nname = \"local\"\nversion = \"0.3.0\"\n",
+        )
+        .unwrap();
+
+        let registry = registry(root, &[("json", "1.2.0"), ("strings", "1.0.0")]);
+        let lockfile = Installer::new(app.clone())
+            .registry(registry)
+            .install()
+            .unwrap();
+
+        assert_eq!(Lockfile::load(app.join(LOCKFILE_NAME)).unwrap(), lockfile);
+        let names: Vec<_> = lockfile.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["json", "local", "strings"]);
+
+        let json = lockfile.get("json").unwrap();
+        assert_eq!(json.version, "1.2.0");
+        assert_eq!(json.dependencies, ["strings"]);
+        assert_eq!(json.commit.as_ref().unwrap().len(), 40);
+        assert!(json.checksum.as_ref().unwrap().starts_with("sha256:"));
+        assert!(app.join("fusabi_packages/strings/fusabi.toml").exists());
+
+        let local = lockfile.get("local").unwrap();
+        assert_eq!(local.version, "0.3.0");
+        assert_eq!(local.source, PackageSource::Path(PathBuf::from("local")));
+        assert!(local.commit.is_none());
+    }
+
+    #[test]
+    fn test_install_version_conflict() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        release(root, "strings", "1.0.0", "");
+        release(root, "strings", "2.0.0", "");
+        release(root, "json", "1.0.0", "strings = \"^1.0\"\n");
+        let app = project(root, "json = \"^1.0\"\nstrings = \"^2.0\"\n");
+
+        let registry = registry(
+            root,
+            &[
+                ("json", "1.0.0"),
+                ("strings", "1.0.0"),
+                ("strings", "2.0.0"),
+            ],
+        );
+        let result = Installer::new(app.clone()).registry(registry).install();
+
+        match result {
+            Err(InstallError::Conflict {
+                name,
+                required_by,
+                selected,
+                ..
+            }) => {
+                assert_eq!(name, "strings");
+                assert_eq!(required_by, "json");
+                assert!(selected.contains("v2.0.0"), "{}", selected);
+            }
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+        assert!(!app.join(LOCKFILE_NAME).exists());
+    }
+
+    #[test]
+    fn test_install_backtracks_to_older_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        release(root, "strings", "1.0.0", "");
+        release(root, "strings", "2.0.0", "");
+        release(root, "json", "1.0.0", "strings = \"^1.0\"\n");
+        release(root, "json", "1.1.0", "strings = \"^2.0\"\n");
+        let releases = [
+            ("json", "1.0.0"),
+            ("json", "1.1.0"),
+            ("strings", "1.0.0"),
+            ("strings", "2.0.0"),
+        ];
+
+        // json 1.1.0 needs strings 2, which the app rules out, so json
+        // 1.0.0 is selected instead
+        let app = project(root, "json = \"^1.0\"\nstrings = \"^1.0\"\n");
+        let lockfile = Installer::new(app.clone())
+            .registry(registry(root, &releases))
+            .install()
+            .unwrap();
+        assert_eq!(lockfile.get("json").unwrap().version, "1.0.0");
+        assert_eq!(lockfile.get("strings").unwrap().version, "1.0.0");
+        let manifest = Manifest::load(app.join("fusabi_packages/json/fusabi.toml")).unwrap();
+        assert_eq!(manifest.package.version, "1.0.0");
+    }
+
+    #[test]
+    fn test_install_fails_without_version_tag() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        release(root, "strings", "1.0.0", "");
+        let app = project(root, "strings = \"^1.0\"\n");
+
+        // The index lists a release that was never tagged
+        let releases = [("strings", "1.0.0"), ("strings", "1.1.0")];
+        let result = Installer::new(app.clone())
+            .registry(registry(root, &releases))
+            .install();
+        match result {
+            Err(InstallError::Other(message)) => {
+                assert!(message.contains("no tag 'v1.1.0'"), "{}", message)
+            }
+            other => panic!("expected a checkout error, got {:?}", other),
+        }
+        assert!(!app.join(LOCKFILE_NAME).exists());
+    }
+
+    #[test]
+    fn test_install_locked() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        release(root, "strings", "1.0.0", "");
+        let app = project(root, "strings = \"^1.0\"\n");
+        let lockfile = Installer::new(app.clone())
+            .registry(registry(root, &[("strings", "1.0.0")]))
+            .install()
+            .unwrap();
+
+        // A newer release is not picked up, with or without --locked
+        release(root, "strings", "1.1.0", "");
+        let registry = || registry(root, &[("strings", "1.0.0"), ("strings", "1.1.0")]);
+        let relocked = Installer::new(app.clone())
+            .registry(registry())
+            .install()
+            .unwrap();
+        assert_eq!(relocked, lockfile);
+
+        std::fs::remove_dir_all(app.join("fusabi_packages")).unwrap();
+        let installed = Installer::new(app.clone())
+            .registry(registry())
+            .locked(true)
+            .install()
+            .unwrap();
+        assert_eq!(installed, lockfile);
+        let manifest = Manifest::load(app.join("fusabi_packages/strings/fusabi.toml")).unwrap();
+        assert_eq!(manifest.package.version, "1.0.0");
+
+        // Tampered contents are rejected
+        let mut tampered = lockfile.clone();
+        tampered.packages[0].checksum = Some("sha256:00".to_string());
+        tampered.save(app.join(LOCKFILE_NAME)).unwrap();
+        let result = Installer::new(app.clone()).locked(true).install();
+        assert!(matches!(result, Err(InstallError::ChecksumMismatch { .. })));
+
+        // So is a lockfile that no longer matches fusabi.toml
+        lockfile.save(app.join(LOCKFILE_NAME)).unwrap();
+        project(root, "strings = \"^2.0\"\n");
+        let result = Installer::new(app.clone()).locked(true).install();
+        assert!(matches!(result, Err(InstallError::LockfileOutOfDate(_))));
+    }
+
+    #[test]
+    fn test_vendor_and_install_from_vendor() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        release(root, "strings", "1.0.0", "");
+        release(root, "json", "1.2.0", "strings = \"^1.0\"\n");
+        let tools = release(root, "tools", "0.4.0", "");
+        let dependencies = format!(
+            "json = \"^1.0\"\ntools = {{ git = \"{}\", rev = \"v0.4.0\" }}\n",
+            tools
+        );
+        let app = project(root, &dependencies);
+
+        let registry = registry(root, &[("json", "1.2.0"), ("strings", "1.0.0")]);
+        let vendored = Installer::new(app.clone())
+            .registry(registry)
+            .vendor(&app.join("vendor"))
+            .unwrap();
+        let lockfile = Lockfile::load(app.join(LOCKFILE_NAME)).unwrap();
+        assert_eq!(vendored, lockfile);
+        assert!(app.join("vendor/json/fusabi.toml").exists());
+        assert!(!app.join("vendor/json/.git").exists());
+        assert!(app.join("vendor").join(VENDOR_LOCK_NAME).exists());
+
+        // Without the repositories, packages come from the vendor directory
+        std::fs::rename(root.join("repos"), root.join("unreachable")).unwrap();
+        std::fs::remove_dir_all(app.join("fusabi_packages")).unwrap();
+        std::fs::write(
+            app.join("fusabi.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}\n[source]\nvendor = \"vendor\"\noffline = true\n",
+                dependencies
+            ),
+        )
+        .unwrap();
+        let installed = Installer::new(app.clone()).install().unwrap();
+        assert_eq!(installed, lockfile);
+        assert!(app.join("fusabi_packages/strings/fusabi.toml").exists());
+
+        let installed = Installer::new(app.clone()).locked(true).install().unwrap();
+        assert_eq!(installed, lockfile);
+
+        // Modified vendored packages are rejected
+        std::fs::write(app.join("vendor/json/fusabi.toml"), "tampered").unwrap();
+        let result = Installer::new(app.clone()).locked(true).install();
+        assert!(matches!(result, Err(InstallError::ChecksumMismatch { .. })));
+
+        // So are requirements the vendored packages do not satisfy
+        project(root, "json = \"^2.0\"\n[source]\nvendor = \"vendor\"\n");
+        let result = Installer::new(app.clone()).install();
+        assert!(matches!(result, Err(InstallError::NotVendored { .. })));
+    }
+
+    #[test]
+    fn test_install_offline_with_replaced_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let mirror = release(root, "tools", "0.4.0", "");
+        let url = "https://git.example.com/tools";
+        let app = project(root, &format!("tools = {{ git = \"{}\" }}\n", url));
+
+        let result = Installer::new(app.clone()).offline(true).install();
+        assert!(matches!(result, Err(InstallError::Offline(u)) if u == url));
+
+        let manifest = std::fs::read_to_string(app.join("fusabi.toml")).unwrap();
+        std::fs::write(
+            app.join("fusabi.toml"),
+            format!(
+                "{}\n[source]\noffline = true\n\n[source.replace]\n\"{}\" = \"{}\"\n",
+                manifest, url, mirror
+            ),
+        )
+        .unwrap();
+        let lockfile = Installer::new(app.clone()).install().unwrap();
+
+        // The lockfile keeps the original source
+        let tools = lockfile.get("tools").unwrap();
+        assert_eq!(tools.source, PackageSource::Git(url.to_string()));
+        assert_eq!(tools.version, "0.4.0");
+        assert!(app.join("fusabi_packages/tools/fusabi.toml").exists());
+    }
+
+    #[test]
+    fn test_install_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        release(root, "strings", "1.0.0", "");
+        release(root, "strings", "1.1.0", "");
+        let workspace = root.join("workspace");
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::write(
+            workspace.join("fusabi.toml"),
+            "[workspace]\nmembers = [\"packages/*\"]\n\n[workspace.dependencies]\nstrings = \"~1.0\"\n",
+        )
+        .unwrap();
+        for (name, dependencies) in [
+            (
+                "api",
+                "strings = { workspace = true }\ncore = { workspace = true }\n",
+            ),
+            ("core", "strings = { workspace = true }\n"),
+        ] {
+            let dir = workspace.join("packages").join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(
+                dir.join("fusabi.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.2.0\"\n\n[dependencies]\n{}",
+                    name, dependencies
+                ),
+            )
+            .unwrap();
+        }
+
+        let registry = registry(root, &[("strings", "1.0.0"), ("strings", "1.1.0")]);
+        let lockfile = Installer::new(workspace.clone())
+            .registry(registry)
+            .install()
+            .unwrap();
+
+        // One lockfile at the root, with the shared version requirement
+        assert!(workspace.join(LOCKFILE_NAME).exists());
+        assert!(!workspace.join("packages/api").join(LOCKFILE_NAME).exists());
+        assert_eq!(lockfile.get("strings").unwrap().version, "1.0.0");
+        assert!(workspace.join("fusabi_packages/strings").exists());
+
+        // Members are used in place
+        let core = lockfile.get("core").unwrap();
+        assert_eq!(
+            core.source,
+            PackageSource::Path(PathBuf::from("packages/core"))
+        );
+        assert_eq!(core.dependencies, ["strings"]);
+        assert!(!workspace.join("fusabi_packages/core").exists());
+    }
 }
@@ -3,13 +3,23 @@
 //! A package manager for the Fusabi language.
 
 pub mod build;
+pub mod features;
 pub mod install;
+pub mod lockfile;
 pub mod manifest;
 pub mod publish;
 pub mod registry;
+pub mod source;
+pub mod vendor;
+pub mod workspace;
 
 pub use build::{BuildError, BuildResult, PackageBuilder, ResolvedDependency};
-pub use install::{install_dependencies, InstallError};
-pub use manifest::{Dependencies, Dependency, Manifest, Package};
+pub use features::{EnabledFeatures, FeatureRequest};
+pub use install::{install_dependencies, InstallError, Installer};
+pub use lockfile::{LockedPackage, Lockfile, LockfileError, PackageSource, LOCKFILE_NAME};
+pub use manifest::{Dependencies, Dependency, Features, Manifest, Package};
 pub use publish::{print_publish_instructions, publish_package, PublishError, PublishResult};
 pub use registry::{Registry, RegistryError, RegistryPackage};
+pub use source::SourceConfig;
+pub use vendor::VENDOR_LOCK_NAME;
+pub use workspace::{Member, Workspace, WorkspaceConfig, WorkspaceError};
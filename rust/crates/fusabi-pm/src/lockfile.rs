@@ -0,0 +1,307 @@
+//! Lockfile handling for Fusabi packages.
+//!
+//! `fusabi.lock` records every package `fpm install` resolved, directly or
+//! transitively: its exact version, where it came from, the git commit it
+//! was checked out at and a checksum of its contents. `fpm install --locked`
+//! reinstalls exactly these packages.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Name of the lockfile, next to `fusabi.toml`.
+pub const LOCKFILE_NAME: &str = "fusabi.lock";
+
+/// Format version written to new lockfiles.
+const LOCKFILE_VERSION: u32 = 1;
+
+const HEADER: &str = "# This file is generated by fpm. It is not intended for manual editing.\n";
+
+/// Errors that can occur when working with lockfiles.
+#[derive(Debug, Error)]
+pub enum LockfileError {
+    #[error("Failed to read lockfile: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Failed to parse lockfile: {0}")]
+    ParseError(#[from] toml::de::Error),
+
+    #[error("Failed to serialize lockfile: {0}")]
+    SerializeError(#[from] toml::ser::Error),
+
+    #[error("Unsupported lockfile version {0}")]
+    UnsupportedVersion(u32),
+}
+
+/// Represents a fusabi.lock file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Lockfile {
+    /// Lockfile format version.
+    pub version: u32,
+
+    /// Resolved packages, sorted by name.
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+impl Lockfile {
+    /// Creates a lockfile of the given packages.
+    pub fn new(mut packages: Vec<LockedPackage>) -> Self {
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        Self {
+            version: LOCKFILE_VERSION,
+            packages,
+        }
+    }
+
+    /// Loads a lockfile from a file path.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LockfileError> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents)
+    }
+
+    /// Parses a lockfile from a TOML string.
+    pub fn parse(toml_str: &str) -> Result<Self, LockfileError> {
+        let lockfile: Lockfile = toml::from_str(toml_str)?;
+        if lockfile.version != LOCKFILE_VERSION {
+            return Err(LockfileError::UnsupportedVersion(lockfile.version));
+        }
+        Ok(lockfile)
+    }
+
+    /// Serializes the lockfile to a TOML string.
+    pub fn to_toml(&self) -> Result<String, LockfileError> {
+        Ok(format!("{}\n{}", HEADER, toml::to_string_pretty(self)?))
+    }
+
+    /// Writes the lockfile to a file path.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), LockfileError> {
+        fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+
+    /// Returns the locked package with the given name.
+    pub fn get(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|p| p.name == name)
+    }
+}
+
+/// A package pinned by the lockfile.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LockedPackage {
+    /// The package name.
+    pub name: String,
+
+    /// The exact version that was resolved.
+    pub version: String,
+
+    /// Where the package came from.
+    pub source: PackageSource,
+
+    /// Git revision the manifest asked for, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+
+    /// Git commit SHA the package was checked out at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+
+    /// Checksum of the package contents (`sha256:<hex>`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+
+    /// Names of the packages this package depends on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+}
+
+/// Origin of a locked package, written as `registry+<url>`, `git+<url>` or
+/// `path+<path>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackageSource {
+    /// Resolved through the registry index to a git repository.
+    Registry(String),
+    /// A git repository named in a manifest.
+    Git(String),
+    /// A local directory, relative to the project root when possible.
+    Path(PathBuf),
+}
+
+impl std::fmt::Display for PackageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageSource::Registry(url) => write!(f, "registry+{}", url),
+            PackageSource::Git(url) => write!(f, "git+{}", url),
+            PackageSource::Path(path) => write!(f, "path+{}", path.display()),
+        }
+    }
+}
+
+impl std::str::FromStr for PackageSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('+') {
+            Some(("registry", url)) => Ok(PackageSource::Registry(url.to_string())),
+            Some(("git", url)) => Ok(PackageSource::Git(url.to_string())),
+            Some(("path", path)) => Ok(PackageSource::Path(PathBuf::from(path))),
+            _ => Err(format!("invalid package source '{}'", s)),
+        }
+    }
+}
+
+impl Serialize for PackageSource {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PackageSource {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Computes the checksum of a package directory.
+///
+/// Covers the relative path and contents of the package's sources, the
+/// manifest and every file under `src/`, in a stable order. Anything else,
+/// such as `.git`, build output in `target/` or installed packages, does not
+/// change the checksum.
+pub fn checksum_dir(dir: &Path) -> std::io::Result<String> {
+    let mut files = Vec::new();
+    if dir.join("fusabi.toml").is_file() {
+        files.push("fusabi.toml".to_string());
+    }
+    let src = dir.join("src");
+    if src.is_dir() {
+        collect_files(dir, &src, &mut files)?;
+    }
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for relative in files {
+        let contents = fs::read(dir.join(&relative))?;
+        hasher.update(relative.as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+
+    let digest = hasher.finalize();
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("sha256:{}", hex))
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            // Forward slashes so checksums agree across platforms
+            let parts: Vec<_> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect();
+            files.push(parts.join("/"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn locked(name: &str, source: PackageSource) -> LockedPackage {
+        LockedPackage {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            source,
+            rev: None,
+            commit: None,
+            checksum: None,
+            dependencies: vec![],
+        }
+    }
+
+    #[test]
+    fn test_lockfile_roundtrip() {
+        let mut registry = locked(
+            "json-utils",
+            PackageSource::Registry("https://github.com/test/json-utils".to_string()),
+        );
+        registry.commit = Some("0123abcd".to_string());
+        registry.checksum = Some("sha256:ff".to_string());
+        registry.dependencies = vec!["strings".to_string()];
+        let lockfile = Lockfile::new(vec![
+            locked("strings", PackageSource::Path(PathBuf::from("../strings"))),
+            registry,
+        ]);
+
+        let toml_str = lockfile.to_toml().unwrap();
+        assert!(toml_str.starts_with(HEADER));
+        assert!(toml_str.contains("source = \"registry+https://github.com/test/json-utils\""));
+        assert!(toml_str.contains("source = \"path+../strings\""));
+
+        let parsed = Lockfile::parse(&toml_str).unwrap();
+        assert_eq!(parsed, lockfile);
+        assert_eq!(parsed.packages[0].name, "json-utils");
+        assert_eq!(
+            parsed.get("strings").unwrap().source,
+            PackageSource::Path(PathBuf::from("../strings"))
+        );
+    }
+
+    #[test]
+    fn test_lockfile_rejects_unknown_version() {
+        let result = Lockfile::parse("version = 99\n");
+        assert!(matches!(result, Err(LockfileError::UnsupportedVersion(99))));
+
+        let result = Lockfile::parse(
+            "version = 1\n[[package]]\nname = \"a\"\nversion = \"1.0.0\"\nsource = \"ftp+x\"\n",
+        );
+        assert!(matches!(result, Err(LockfileError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_checksum_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join("fusabi.toml"), "[package]").unwrap();
+        fs::write(dir.join("src/lib.fsx"), "42").unwrap();
+
+        let checksum = checksum_dir(dir).unwrap();
+        assert!(checksum.starts_with("sha256:"));
+        assert_eq!(checksum.len(), "sha256:".len() + 64);
+
+        // Git metadata, build output and installed packages do not count
+        fs::write(dir.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        fs::write(dir.join("target/lib.fzp"), "FZP").unwrap();
+        fs::create_dir_all(dir.join("fusabi_packages/json/src")).unwrap();
+        fs::write(dir.join("fusabi_packages/json/src/lib.fsx"), "1").unwrap();
+        fs::write(dir.join("fusabi.lock"), "version = 1").unwrap();
+        assert_eq!(checksum_dir(dir).unwrap(), checksum);
+
+        fs::create_dir_all(dir.join("src/util")).unwrap();
+        fs::write(dir.join("src/util/strings.fsx"), "1").unwrap();
+        let with_module = checksum_dir(dir).unwrap();
+        assert_ne!(with_module, checksum);
+        fs::write(dir.join("fusabi.toml"), "[package]\nname = \"a\"").unwrap();
+        assert_ne!(checksum_dir(dir).unwrap(), with_module);
+        fs::write(dir.join("fusabi.toml"), "[package]").unwrap();
+        fs::remove_file(dir.join("src/util/strings.fsx")).unwrap();
+
+        fs::write(dir.join("src/lib.fsx"), "43").unwrap();
+        assert_ne!(checksum_dir(dir).unwrap(), checksum);
+    }
+}
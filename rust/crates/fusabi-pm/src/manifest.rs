@@ -11,6 +11,9 @@ use thiserror::Error;
 /// Type alias for package dependencies.
 pub type Dependencies = HashMap<String, Dependency>;
 
+/// Type alias for package features and what each one enables.
+pub type Features = HashMap<String, Vec<String>>;
+
 /// Errors that can occur when working with manifests.
 #[derive(Debug, Error)]
 pub enum ManifestError {
@@ -25,6 +28,9 @@ pub enum ManifestError {
 
     #[error("Invalid manifest: {0}")]
     InvalidManifest(String),
+
+    #[error("Package '{package}' has no feature '{feature}'")]
+    UnknownFeature { package: String, feature: String },
 }
 
 /// Represents a fusabi.toml manifest file.
@@ -40,6 +46,10 @@ pub struct Manifest {
     /// Development dependencies.
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub dev_dependencies: Dependencies,
+
+    /// Package features.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub features: Features,
 }
 
 impl Manifest {
@@ -49,6 +59,7 @@ impl Manifest {
             package,
             dependencies: HashMap::new(),
             dev_dependencies: HashMap::new(),
+            features: HashMap::new(),
         }
     }
 
@@ -84,7 +95,7 @@ impl Manifest {
             ));
         }
 
-        Ok(())
+        crate::features::validate_features(self)
     }
 
     /// Adds a dependency to the manifest.
@@ -171,6 +182,22 @@ pub struct DetailedDependency {
     /// Whether this dependency is optional.
     #[serde(default, skip_serializing_if = "is_false")]
     pub optional: bool,
+
+    /// Features to enable in the dependency.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+
+    /// Whether to enable the dependency's default features.
+    #[serde(
+        default = "default_true",
+        rename = "default-features",
+        skip_serializing_if = "is_true"
+    )]
+    pub default_features: bool,
+
+    /// Whether this dependency is inherited from the workspace.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub workspace: bool,
 }
 
 /// Helper function for serde to skip serializing false values.
@@ -178,6 +205,16 @@ fn is_false(b: &bool) -> bool {
     !b
 }
 
+/// Helper function for serde to skip serializing true values.
+fn is_true(b: &bool) -> bool {
+    *b
+}
+
+/// Helper function for serde to default to true.
+fn default_true() -> bool {
+    true
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2,6 +2,10 @@
 //!
 //! Provides functionality to fetch and parse the registry index, and resolve
 //! package names to git URLs with version constraints.
+//!
+//! The index is normally a clone of fusabi-community. A local registry reads
+//! it from a directory or `file://` URL instead, so it works without network
+//! access; its `repository` entries may be paths relative to that directory.
 
 use git2::Repository;
 use serde::Deserialize;
@@ -31,6 +35,12 @@ pub enum RegistryError {
 
     #[error("Invalid version format: {0}")]
     InvalidVersion(String),
+
+    #[error("Registry index not found at {0}")]
+    IndexNotFound(PathBuf),
+
+    #[error("Cannot fetch the registry index while offline. Run 'fpm update' with network access, or configure a local registry.")]
+    Offline,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -167,8 +177,25 @@ impl Ord for SemVer {
     }
 }
 
+/// The local path a registry or repository location names: a `file://` URL
+/// or a plain path. Returns `None` for network URLs.
+pub fn local_path(location: &str) -> Option<PathBuf> {
+    if let Some(path) = location.strip_prefix("file://") {
+        return Some(PathBuf::from(path));
+    }
+    if location.contains("://") || location.starts_with("git@") {
+        return None;
+    }
+    Some(PathBuf::from(location))
+}
+
+#[derive(Debug, Clone)]
 pub struct Registry {
     cache_dir: PathBuf,
+    /// Directory of a local registry, read in place.
+    local_dir: Option<PathBuf>,
+    /// Never fetch the index from the network.
+    offline: bool,
 }
 
 impl Registry {
@@ -178,16 +205,69 @@ impl Registry {
             .join("fusabi")
             .join("registry");
 
-        Self { cache_dir }
+        Self::with_cache_dir(cache_dir)
     }
 
     pub fn with_cache_dir<P: AsRef<Path>>(cache_dir: P) -> Self {
         Self {
             cache_dir: cache_dir.as_ref().to_path_buf(),
+            local_dir: None,
+            offline: false,
+        }
+    }
+
+    /// Uses the registry in a local directory, laid out like
+    /// fusabi-community (`registry/index.toml`) or holding `index.toml`
+    /// directly.
+    pub fn local<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            local_dir: Some(dir.as_ref().to_path_buf()),
+            ..Self::new()
+        }
+    }
+
+    /// Never fetches the index from the network; only a cached or local
+    /// index is used.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Whether the index is read from a local directory.
+    pub fn is_local(&self) -> bool {
+        self.local_dir.is_some()
+    }
+
+    fn index_path(&self) -> PathBuf {
+        match &self.local_dir {
+            Some(dir) => {
+                let nested = dir.join(REGISTRY_INDEX_PATH);
+                if nested.exists() {
+                    nested
+                } else {
+                    dir.join("index.toml")
+                }
+            }
+            None => self
+                .cache_dir
+                .join("fusabi-community")
+                .join(REGISTRY_INDEX_PATH),
         }
     }
 
     pub fn update(&self) -> Result<(), RegistryError> {
+        if let Some(dir) = &self.local_dir {
+            let index_path = self.index_path();
+            if !index_path.exists() {
+                return Err(RegistryError::IndexNotFound(index_path));
+            }
+            println!("Using local registry at {}", dir.display());
+            return Ok(());
+        }
+        if self.offline {
+            return Err(RegistryError::Offline);
+        }
+
         std::fs::create_dir_all(&self.cache_dir)?;
 
         let repo_path = self.cache_dir.join("fusabi-community");
@@ -219,17 +299,25 @@ impl Registry {
     }
 
     pub fn load_index(&self) -> Result<RegistryIndex, RegistryError> {
-        let index_path = self
-            .cache_dir
-            .join("fusabi-community")
-            .join(REGISTRY_INDEX_PATH);
+        let index_path = self.index_path();
 
         if !index_path.exists() {
             self.update()?;
         }
 
         let contents = std::fs::read_to_string(&index_path)?;
-        let index: RegistryIndex = toml::from_str(&contents)?;
+        let mut index: RegistryIndex = toml::from_str(&contents)?;
+
+        // Repositories of a local registry may be relative to it
+        if let Some(dir) = &self.local_dir {
+            for package in &mut index.packages {
+                if let Some(path) = local_path(&package.repository) {
+                    if path.is_relative() {
+                        package.repository = dir.join(path).to_string_lossy().into_owned();
+                    }
+                }
+            }
+        }
         Ok(index)
     }
 
@@ -238,6 +326,19 @@ impl Registry {
         name: &str,
         version_constraint: &str,
     ) -> Result<ResolvedPackage, RegistryError> {
+        let candidates = self.candidates(name, version_constraint)?;
+        candidates.into_iter().next().ok_or_else(|| {
+            RegistryError::NoMatchingVersion(name.to_string(), version_constraint.to_string())
+        })
+    }
+
+    /// Every release of `name` matching `version_constraint`, newest first.
+    /// Fails if the registry has no package `name`.
+    pub fn candidates(
+        &self,
+        name: &str,
+        version_constraint: &str,
+    ) -> Result<Vec<ResolvedPackage>, RegistryError> {
         let index = self.load_index()?;
 
         let matching_packages: Vec<&RegistryPackage> =
@@ -261,18 +362,15 @@ impl Registry {
 
         candidates.sort_by(|a, b| b.1.cmp(&a.1));
 
-        let (package, _) = candidates.into_iter().next().ok_or_else(|| {
-            RegistryError::NoMatchingVersion(name.to_string(), version_constraint.to_string())
-        })?;
-
-        let rev = Some(format!("v{}", package.version));
-
-        Ok(ResolvedPackage {
-            name: package.name.clone(),
-            version: package.version.clone(),
-            git_url: package.repository.clone(),
-            rev,
-        })
+        Ok(candidates
+            .into_iter()
+            .map(|(package, _)| ResolvedPackage {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                git_url: package.repository.clone(),
+                rev: Some(format!("v{}", package.version)),
+            })
+            .collect())
     }
 
     pub fn search(&self, query: &str) -> Result<Vec<RegistryPackage>, RegistryError> {
@@ -402,4 +500,44 @@ repository = "https://github.com/test/another"
         assert_eq!(index.packages[0].name, "test-pkg");
         assert_eq!(index.packages[1].name, "another-pkg");
     }
+
+    #[test]
+    fn test_local_path() {
+        assert_eq!(
+            local_path("file:///srv/registry"),
+            Some(PathBuf::from("/srv/registry"))
+        );
+        assert_eq!(local_path("../mirror"), Some(PathBuf::from("../mirror")));
+        assert_eq!(local_path("https://github.com/test/test-pkg"), None);
+        assert_eq!(local_path("git@github.com:test/test-pkg.git"), None);
+    }
+
+    #[test]
+    fn test_local_registry() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(
+            dir.join("index.toml"),
+            "[[packages]]\nname = \"json\"\nversion = \"1.0.0\"\nrepository = \"repos/json\"\n",
+        )
+        .unwrap();
+
+        let registry = Registry::local(dir).offline(true);
+        registry.update().unwrap();
+        let resolved = registry.resolve("json", "^1.0").unwrap();
+        assert_eq!(resolved.git_url, dir.join("repos/json").to_string_lossy());
+
+        let missing = Registry::local(dir.join("missing"));
+        assert!(matches!(
+            missing.update(),
+            Err(RegistryError::IndexNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_offline_registry_without_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let registry = Registry::with_cache_dir(temp_dir.path()).offline(true);
+        assert!(matches!(registry.load_index(), Err(RegistryError::Offline)));
+    }
 }
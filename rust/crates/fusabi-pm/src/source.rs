@@ -0,0 +1,170 @@
+//! Source configuration for offline and mirrored installs.
+//!
+//! The `[source]` section of the root fusabi.toml (a package's, or a
+//! workspace's) tells fpm where to get packages instead of the network:
+//!
+//! ```toml
+//! [source]
+//! # Registry index in a local directory or file:// URL
+//! registry = "file:///srv/fusabi-registry"
+//! # Install registry and git packages from `fpm vendor` output
+//! vendor = "vendor"
+//! # Fail instead of touching the network
+//! offline = true
+//!
+//! [source.replace]
+//! # Clone git repositories from local mirrors
+//! "https://github.com/fusabi-lang/json" = "/srv/git/json"
+//! ```
+//!
+//! Relative paths are relative to the directory of the fusabi.toml.
+
+use crate::manifest::ManifestError;
+use crate::registry::{local_path, Registry};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The `[source]` section of a fusabi.toml.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct SourceConfig {
+    /// Local registry index, replacing fusabi-community.
+    #[serde(default)]
+    pub registry: Option<String>,
+
+    /// Directory of vendored packages, written by `fpm vendor`.
+    #[serde(default)]
+    pub vendor: Option<PathBuf>,
+
+    /// Never fetch from the network.
+    #[serde(default)]
+    pub offline: bool,
+
+    /// Git repository URLs and the locations to clone them from instead.
+    #[serde(default)]
+    pub replace: HashMap<String, String>,
+}
+
+/// A fusabi.toml that may have a `[source]` section.
+#[derive(Deserialize)]
+struct SourceFile {
+    source: Option<SourceConfig>,
+}
+
+impl SourceConfig {
+    /// Loads the `[source]` section of the fusabi.toml in `root`, resolving
+    /// relative paths against `root`. Without a fusabi.toml or section, the
+    /// default sources are used.
+    pub fn load(root: &Path) -> Result<Self, ManifestError> {
+        let manifest_path = root.join("fusabi.toml");
+        if !manifest_path.exists() {
+            return Ok(Self::default());
+        }
+        let file: SourceFile = toml::from_str(&fs::read_to_string(manifest_path)?)?;
+        let mut config = file.source.unwrap_or_default();
+
+        if let Some(registry) = &config.registry {
+            let path = local_path(registry).ok_or_else(|| {
+                ManifestError::InvalidManifest(format!(
+                    "[source] registry must be a local directory or file:// URL, got '{}'",
+                    registry
+                ))
+            })?;
+            config.registry = Some(root.join(path).to_string_lossy().into_owned());
+        }
+        if let Some(vendor) = &config.vendor {
+            config.vendor = Some(root.join(vendor));
+        }
+        for location in config.replace.values_mut() {
+            if let Some(path) = local_path(location) {
+                *location = root.join(path).to_string_lossy().into_owned();
+            }
+        }
+        Ok(config)
+    }
+
+    /// The registry packages are resolved with.
+    pub fn registry(&self) -> Registry {
+        let registry = match &self.registry {
+            Some(dir) => Registry::local(dir),
+            None => Registry::new(),
+        };
+        registry.offline(self.offline)
+    }
+
+    /// The location to clone the git repository at `url` from.
+    pub fn replace<'a>(&'a self, url: &'a str) -> &'a str {
+        self.replace.get(url).map_or(url, String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_source_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(
+            root.join("fusabi.toml"),
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[source]
+registry = "file://registry"
+vendor = "vendor"
+offline = true
+
+[source.replace]
+"https://github.com/test/json" = "mirrors/json"
+"https://github.com/test/http" = "https://mirror.example.com/http"
+"#,
+        )
+        .unwrap();
+
+        let config = SourceConfig::load(root).unwrap();
+        assert_eq!(
+            config.registry.as_deref(),
+            Some(root.join("registry").to_string_lossy().as_ref())
+        );
+        assert_eq!(config.vendor, Some(root.join("vendor")));
+        assert!(config.offline);
+        assert!(config.registry().is_local());
+        assert_eq!(
+            config.replace("https://github.com/test/json"),
+            root.join("mirrors/json").to_string_lossy()
+        );
+        assert_eq!(
+            config.replace("https://github.com/test/http"),
+            "https://mirror.example.com/http"
+        );
+        assert_eq!(
+            config.replace("https://github.com/test/other"),
+            "https://github.com/test/other"
+        );
+    }
+
+    #[test]
+    fn test_load_source_config_defaults() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(
+            SourceConfig::load(temp_dir.path()).unwrap(),
+            SourceConfig::default()
+        );
+
+        fs::write(
+            temp_dir.path().join("fusabi.toml"),
+            "[source]\nregistry = \"https://example.com/index\"\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            SourceConfig::load(temp_dir.path()),
+            Err(ManifestError::InvalidManifest(_))
+        ));
+    }
+}
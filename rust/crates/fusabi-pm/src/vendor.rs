@@ -0,0 +1,95 @@
+//! Vendoring of dependencies for offline builds.
+//!
+//! `fpm vendor` copies every registry and git package in fusabi.lock into a
+//! vendor directory, without git metadata, and records them with their
+//! checksums in `vendor.lock`. With `vendor` set in the `[source]` section,
+//! `fpm install` then copies packages from there instead of fetching them.
+//! Path dependencies are not vendored.
+
+use crate::install::InstallError;
+use crate::lockfile::{checksum_dir, Lockfile, PackageSource};
+use std::fs;
+use std::path::Path;
+
+/// Name of the file listing the vendored packages, in the vendor directory.
+pub const VENDOR_LOCK_NAME: &str = "vendor.lock";
+
+/// Copies the registry and git packages of `lockfile`, checked out in
+/// `packages_dir`, into `vendor_dir`, replacing what was vendored before.
+/// Returns the lockfile of the vendored packages.
+pub fn vendor_packages(
+    lockfile: &Lockfile,
+    packages_dir: &Path,
+    vendor_dir: &Path,
+) -> Result<Lockfile, InstallError> {
+    if vendor_dir.join(VENDOR_LOCK_NAME).exists() {
+        fs::remove_dir_all(vendor_dir)?;
+    } else if vendor_dir.exists() && fs::read_dir(vendor_dir)?.next().is_some() {
+        return Err(InstallError::Other(format!(
+            "{} is not empty and was not created by 'fpm vendor'",
+            vendor_dir.display()
+        )));
+    }
+    fs::create_dir_all(vendor_dir)?;
+
+    let mut vendored = Vec::new();
+    for package in &lockfile.packages {
+        if matches!(package.source, PackageSource::Path(_)) {
+            continue;
+        }
+        let target = vendor_dir.join(&package.name);
+        copy_dir(&packages_dir.join(&package.name), &target)?;
+        verify(&package.name, &target, package.checksum.as_deref())?;
+        println!("Vendored '{}' v{}", package.name, package.version);
+        vendored.push(package.clone());
+    }
+
+    let vendored = Lockfile::new(vendored);
+    vendored.save(vendor_dir.join(VENDOR_LOCK_NAME))?;
+    Ok(vendored)
+}
+
+/// Loads the list of packages vendored in `vendor_dir`.
+pub fn load_vendored(vendor_dir: &Path) -> Result<Lockfile, InstallError> {
+    let path = vendor_dir.join(VENDOR_LOCK_NAME);
+    if !path.exists() {
+        return Err(InstallError::Other(format!(
+            "{} not found. Run 'fpm vendor' to create it.",
+            path.display()
+        )));
+    }
+    Ok(Lockfile::load(path)?)
+}
+
+/// Checks that the package `name` in `dir` has the `expected` checksum.
+pub fn verify(name: &str, dir: &Path, expected: Option<&str>) -> Result<(), InstallError> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let found = checksum_dir(dir)?;
+    if found != expected {
+        return Err(InstallError::ChecksumMismatch {
+            name: name.to_string(),
+            expected: expected.to_string(),
+            found,
+        });
+    }
+    Ok(())
+}
+
+/// Copies the directory `from` to `to`, leaving out `.git`.
+pub fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            if entry.file_name() != ".git" {
+                copy_dir(&entry.path(), &target)?;
+            }
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
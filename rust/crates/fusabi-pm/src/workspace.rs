@@ -0,0 +1,499 @@
+//! Workspace handling for Fusabi packages.
+//!
+//! A workspace is a directory whose fusabi.toml has a `[workspace]` section
+//! listing member packages, by path or glob:
+//!
+//! ```toml
+//! [workspace]
+//! members = ["packages/*", "tools/cli"]
+//! exclude = ["packages/scratch"]
+//!
+//! [workspace.dependencies]
+//! json = "^1.0"
+//! ```
+//!
+//! Members share one fusabi.lock and fusabi_packages directory at the
+//! workspace root. A member dependency written `{ workspace = true }` uses
+//! the version in `[workspace.dependencies]` or, if another member has that
+//! name, that member's directory.
+
+use crate::manifest::{Dependencies, Dependency, DetailedDependency, Manifest, ManifestError};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Errors that can occur when working with workspaces.
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    #[error("Failed to read workspace: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Failed to parse workspace: {0}")]
+    ParseError(#[from] toml::de::Error),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] ManifestError),
+
+    #[error("Workspace member not found: {0}")]
+    MemberNotFound(PathBuf),
+
+    #[error("Workspace has more than one package named '{0}'")]
+    DuplicateMember(String),
+
+    #[error("Package '{0}' is not a member of the workspace")]
+    UnknownPackage(String),
+
+    #[error("'{member}' inherits dependency '{name}', which is not in [workspace.dependencies]")]
+    MissingDependency { member: String, name: String },
+}
+
+/// The `[workspace]` section of a fusabi.toml.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorkspaceConfig {
+    /// Member directories, relative to the workspace root; `*` and `?`
+    /// match within a path component.
+    pub members: Vec<String>,
+
+    /// Directories matched by `members` that are not members.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Dependencies members can inherit with `{ workspace = true }`.
+    #[serde(default)]
+    pub dependencies: Dependencies,
+}
+
+/// A fusabi.toml that may have a `[workspace]` section.
+#[derive(Deserialize)]
+struct WorkspaceFile {
+    workspace: Option<WorkspaceConfig>,
+    package: Option<toml::Value>,
+}
+
+/// A package in a workspace.
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// The package name.
+    pub name: String,
+    /// Directory of the package.
+    pub path: PathBuf,
+    /// The package manifest, with inherited dependencies resolved.
+    pub manifest: Manifest,
+}
+
+/// A workspace and its members.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+    config: WorkspaceConfig,
+    members: Vec<Member>,
+}
+
+impl Workspace {
+    /// Loads the workspace rooted at `root`, or `None` if its fusabi.toml
+    /// has no `[workspace]` section.
+    pub fn load(root: &Path) -> Result<Option<Self>, WorkspaceError> {
+        let manifest_path = root.join("fusabi.toml");
+        let file: WorkspaceFile = toml::from_str(&fs::read_to_string(&manifest_path)?)?;
+        let Some(config) = file.workspace else {
+            return Ok(None);
+        };
+        let root = root.canonicalize()?;
+
+        let mut dirs = Vec::new();
+        if file.package.is_some() {
+            dirs.push(root.clone());
+        }
+        let mut excluded = Vec::new();
+        for pattern in &config.exclude {
+            excluded.extend(expand(&root, pattern)?);
+        }
+        for pattern in &config.members {
+            for dir in expand(&root, pattern)? {
+                let explicit = !pattern.contains(['*', '?']);
+                if !dir.join("fusabi.toml").exists() {
+                    if explicit {
+                        return Err(WorkspaceError::MemberNotFound(dir));
+                    }
+                    continue;
+                }
+                if !excluded.contains(&dir) && !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+        }
+
+        let mut members: Vec<Member> = Vec::new();
+        for dir in dirs {
+            let manifest = Manifest::load(dir.join("fusabi.toml"))?;
+            let name = manifest.package.name.clone();
+            if members.iter().any(|m| m.name == name) {
+                return Err(WorkspaceError::DuplicateMember(name));
+            }
+            members.push(Member {
+                name,
+                path: dir,
+                manifest,
+            });
+        }
+
+        let member_paths: HashMap<String, PathBuf> = members
+            .iter()
+            .map(|m| (m.name.clone(), m.path.clone()))
+            .collect();
+        for member in &mut members {
+            for dependencies in [
+                &mut member.manifest.dependencies,
+                &mut member.manifest.dev_dependencies,
+            ] {
+                for (name, dependency) in dependencies.iter_mut() {
+                    if let Some(inherited) =
+                        inherit(&root, &config, &member_paths, name, dependency)
+                    {
+                        *dependency =
+                            inherited.ok_or_else(|| WorkspaceError::MissingDependency {
+                                member: member.name.clone(),
+                                name: name.clone(),
+                            })?;
+                    }
+                }
+            }
+        }
+
+        Ok(Some(Self {
+            root,
+            config,
+            members,
+        }))
+    }
+
+    /// Finds the workspace `dir` belongs to: the nearest enclosing workspace
+    /// that has `dir` as its root or inside one of its members.
+    pub fn find(dir: &Path) -> Result<Option<Self>, WorkspaceError> {
+        let dir = dir.canonicalize()?;
+        for ancestor in dir.ancestors() {
+            if !ancestor.join("fusabi.toml").exists() {
+                continue;
+            }
+            if let Some(workspace) = Self::load(ancestor)? {
+                let contains = workspace.root == dir
+                    || workspace.members.iter().any(|m| dir.starts_with(&m.path));
+                return Ok(contains.then_some(workspace));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Root directory of the workspace.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The `[workspace]` section.
+    pub fn config(&self) -> &WorkspaceConfig {
+        &self.config
+    }
+
+    /// Members of the workspace, in the order `members` lists them.
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+
+    /// Returns the member with the given name.
+    pub fn member(&self, name: &str) -> Result<&Member, WorkspaceError> {
+        self.members
+            .iter()
+            .find(|m| m.name == name)
+            .ok_or_else(|| WorkspaceError::UnknownPackage(name.to_string()))
+    }
+
+    /// Returns the member whose directory is `dir`.
+    pub fn member_at(&self, dir: &Path) -> Option<&Member> {
+        let dir = dir.canonicalize().ok()?;
+        self.members.iter().find(|m| m.path == dir)
+    }
+
+    /// Selects the members a command run in `dir` applies to: the named
+    /// package, else the member containing `dir`, else every member.
+    pub fn select(
+        &self,
+        package: Option<&str>,
+        dir: &Path,
+    ) -> Result<Vec<&Member>, WorkspaceError> {
+        if let Some(name) = package {
+            return Ok(vec![self.member(name)?]);
+        }
+        let dir = dir.canonicalize()?;
+        if dir != self.root {
+            // The innermost member, as a root package contains the others
+            let member = self
+                .members
+                .iter()
+                .filter(|m| dir.starts_with(&m.path))
+                .max_by_key(|m| m.path.components().count());
+            if let Some(member) = member {
+                return Ok(vec![member]);
+            }
+        }
+        Ok(self.members.iter().collect())
+    }
+}
+
+/// The dependency `{ workspace = true }` stands for, or `None` if it does
+/// not inherit; inner `None` if there is nothing to inherit.
+fn inherit(
+    root: &Path,
+    config: &WorkspaceConfig,
+    member_paths: &HashMap<String, PathBuf>,
+    name: &str,
+    dependency: &Dependency,
+) -> Option<Option<Dependency>> {
+    let Dependency::Detailed(detailed) = dependency else {
+        return None;
+    };
+    if !detailed.workspace {
+        return None;
+    }
+
+    let mut inherited = match config.dependencies.get(name) {
+        Some(Dependency::Simple(version)) => DetailedDependency {
+            version: Some(version.clone()),
+            path: None,
+            git: None,
+            rev: None,
+            optional: false,
+            features: Vec::new(),
+            default_features: true,
+            workspace: false,
+        },
+        Some(Dependency::Detailed(shared)) => {
+            let mut shared = shared.clone();
+            // Paths are relative to the workspace root
+            shared.path = shared
+                .path
+                .map(|path| root.join(path).to_string_lossy().into_owned());
+            shared
+        }
+        None => match member_paths.get(name) {
+            Some(path) => DetailedDependency {
+                version: None,
+                path: Some(path.to_string_lossy().into_owned()),
+                git: None,
+                rev: None,
+                optional: false,
+                features: Vec::new(),
+                default_features: true,
+                workspace: false,
+            },
+            None => return Some(None),
+        },
+    };
+    inherited.optional |= detailed.optional;
+    inherited.features.extend(detailed.features.iter().cloned());
+    Some(Some(Dependency::Detailed(inherited)))
+}
+
+/// Expands a member pattern into the directories it matches, sorted.
+fn expand(root: &Path, pattern: &str) -> std::io::Result<Vec<PathBuf>> {
+    let mut dirs = vec![root.to_path_buf()];
+    for part in pattern.split('/').filter(|p| !p.is_empty() && *p != ".") {
+        let mut next = Vec::new();
+        for dir in dirs {
+            if !part.contains(['*', '?']) {
+                next.push(dir.join(part));
+                continue;
+            }
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut matched = Vec::new();
+            for entry in entries {
+                let entry = entry?;
+                let name = entry.file_name();
+                if entry.file_type()?.is_dir() && wildcard(part, &name.to_string_lossy()) {
+                    matched.push(entry.path());
+                }
+            }
+            matched.sort();
+            next.extend(matched);
+        }
+        dirs = next;
+    }
+    Ok(dirs
+        .into_iter()
+        .map(|dir| dir.canonicalize().unwrap_or(dir))
+        .collect())
+}
+
+/// Matches `name` against a pattern where `*` matches any run of
+/// characters and `?` any one character.
+fn wildcard(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn package(root: &Path, dir: &str, name: &str, dependencies: &str) {
+        write(
+            &root.join(dir).join("fusabi.toml"),
+            &format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+                name, dependencies
+            ),
+        );
+    }
+
+    #[test]
+    fn test_wildcard() {
+        assert!(wildcard("*", "json"));
+        assert!(wildcard("json-*", "json-utils"));
+        assert!(wildcard("j?on", "json"));
+        assert!(wildcard("*-*-x", "a-b-c-x"));
+        assert!(!wildcard("json-*", "json"));
+        assert!(!wildcard("j?on", "jsn"));
+    }
+
+    #[test]
+    fn test_load_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(
+            &root.join("fusabi.toml"),
+            r#"
+[workspace]
+members = ["packages/*", "tools/cli"]
+exclude = ["packages/scratch"]
+
+[workspace.dependencies]
+json = "^1.0"
+shared = { path = "vendor/shared" }
+"#,
+        );
+        package(
+            root,
+            "packages/app",
+            "app",
+            "json = { workspace = true }\nstrings = { workspace = true }\nshared = { workspace = true, optional = true }\n",
+        );
+        package(root, "packages/strings", "strings", "");
+        package(root, "packages/scratch", "scratch", "");
+        fs::create_dir_all(root.join("packages/notes")).unwrap();
+        package(root, "tools/cli", "cli", "");
+
+        let workspace = Workspace::load(root).unwrap().unwrap();
+        let names: Vec<_> = workspace
+            .members()
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(names, ["app", "strings", "cli"]);
+
+        let app = &workspace.member("app").unwrap().manifest;
+        let detailed = |name: &str| match &app.dependencies[name] {
+            Dependency::Detailed(d) => d.clone(),
+            other => panic!("expected a detailed dependency, got {:?}", other),
+        };
+        assert_eq!(detailed("json").version.as_deref(), Some("^1.0"));
+        let strings = workspace.member("strings").unwrap().path.clone();
+        assert_eq!(
+            detailed("strings").path,
+            Some(strings.to_string_lossy().into_owned())
+        );
+        let shared = detailed("shared");
+        assert!(shared.optional);
+        assert_eq!(
+            shared.path,
+            Some(
+                workspace
+                    .root()
+                    .join("vendor/shared")
+                    .to_string_lossy()
+                    .into_owned()
+            )
+        );
+
+        // Members find their workspace; unrelated directories do not
+        let found = Workspace::find(&root.join("tools/cli")).unwrap().unwrap();
+        assert_eq!(found.root(), workspace.root());
+        assert!(Workspace::find(&root.join("packages/scratch"))
+            .unwrap()
+            .is_none());
+
+        let selected = workspace.select(None, &root.join("tools/cli")).unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].name, "cli");
+        assert_eq!(workspace.select(None, root).unwrap().len(), 3);
+        assert!(matches!(
+            workspace.select(Some("nope"), root),
+            Err(WorkspaceError::UnknownPackage(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_workspace_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        package(root, ".", "solo", "");
+        assert!(Workspace::load(root).unwrap().is_none());
+
+        write(
+            &root.join("fusabi.toml"),
+            "[workspace]\nmembers = [\"a\", \"b\"]\n",
+        );
+        package(root, "a", "same", "");
+        package(root, "b", "same", "");
+        assert!(matches!(
+            Workspace::load(root),
+            Err(WorkspaceError::DuplicateMember(_))
+        ));
+
+        package(root, "b", "other", "missing = { workspace = true }\n");
+        assert!(matches!(
+            Workspace::load(root),
+            Err(WorkspaceError::MissingDependency { .. })
+        ));
+
+        write(
+            &root.join("fusabi.toml"),
+            "[workspace]\nmembers = [\"c\"]\n",
+        );
+        assert!(matches!(
+            Workspace::load(root),
+            Err(WorkspaceError::MemberNotFound(_))
+        ));
+    }
+}
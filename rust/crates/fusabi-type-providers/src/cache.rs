@@ -0,0 +1,348 @@
+//! On-disk cache of resolved schemas and generated types
+//!
+//! Entries are JSON files keyed by a hash of the provider, source,
+//! namespace and parameters. An entry is reused until
+//!
+//! - the local file named by the source changes (its content hash is stored),
+//! - or it is older than the TTL, when one is set.
+//!
+//! In offline mode remote (`http://`, `https://`) sources are never fetched:
+//! they resolve from the cache alone, ignoring the TTL, and are an error when
+//! no entry exists. Local sources are still read, since they need no network.
+
+use crate::error::{ProviderError, ProviderResult};
+use crate::generator::GeneratedTypes;
+use crate::provider::{ProviderParams, Schema};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Directory of the cache, when set
+pub const CACHE_DIR_ENV: &str = "FUSABI_PROVIDER_CACHE";
+/// Enables offline mode when set to anything but `0`
+pub const OFFLINE_ENV: &str = "FUSABI_OFFLINE";
+/// Default TTL in seconds for entries resolved without `cache_ttl_secs`
+pub const CACHE_TTL_ENV: &str = "FUSABI_PROVIDER_CACHE_TTL";
+
+/// A resolution stored by the cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub provider: String,
+    pub source: String,
+    /// Hash of the source file's content, for sources naming a local file
+    pub source_hash: Option<String>,
+    /// Seconds since the Unix epoch when the entry was written
+    pub created: u64,
+    pub schema: Schema,
+    pub types: GeneratedTypes,
+}
+
+/// Cache of provider resolutions in a directory
+#[derive(Debug, Clone)]
+pub struct ProviderCache {
+    dir: PathBuf,
+    offline: bool,
+    default_ttl: Option<Duration>,
+}
+
+impl ProviderCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            offline: false,
+            default_ttl: None,
+        }
+    }
+
+    /// Never fetch remote sources, resolving them from the cache alone
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// TTL for resolutions whose params set no `cache_ttl_secs`
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// The cache configured by the environment, if any.
+    ///
+    /// `FUSABI_PROVIDER_CACHE` names the directory; `FUSABI_OFFLINE` alone
+    /// enables the cache in the default directory (see [`default_dir`]).
+    ///
+    /// [`default_dir`]: ProviderCache::default_dir
+    pub fn from_env() -> Option<Self> {
+        let offline = std::env::var(OFFLINE_ENV).is_ok_and(|v| !v.is_empty() && v != "0");
+        let dir = match std::env::var_os(CACHE_DIR_ENV) {
+            Some(dir) => PathBuf::from(dir),
+            None if offline => Self::default_dir(),
+            None => return None,
+        };
+        let mut cache = Self::new(dir).offline(offline);
+        if let Some(secs) = std::env::var(CACHE_TTL_ENV)
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            cache = cache.with_ttl(Duration::from_secs(secs));
+        }
+        Some(cache)
+    }
+
+    /// `$XDG_CACHE_HOME/fusabi/providers`, else `~/.cache/fusabi/providers`
+    pub fn default_dir() -> PathBuf {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .unwrap_or_else(|| PathBuf::from(".cache"));
+        base.join("fusabi").join("providers")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Return the cached resolution, or resolve with `resolve` and store it
+    pub fn get_or_resolve(
+        &self,
+        provider: &str,
+        source: &str,
+        namespace: &str,
+        params: &ProviderParams,
+        resolve: impl FnOnce() -> ProviderResult<(Schema, GeneratedTypes)>,
+    ) -> ProviderResult<GeneratedTypes> {
+        let path = self.entry_path(provider, source, namespace, params);
+        let source_hash = source_file_hash(source);
+        let cached = self
+            .read(&path)
+            .filter(|entry| entry.source_hash == source_hash);
+
+        let remote = is_remote(source);
+        if let Some(entry) = cached {
+            let ttl = params
+                .cache_ttl_secs
+                .map(Duration::from_secs)
+                .or(self.default_ttl);
+            let fresh = ttl.map_or(true, |ttl| {
+                now().saturating_sub(entry.created) <= ttl.as_secs()
+            });
+            if fresh || self.offline && remote {
+                return Ok(entry.types);
+            }
+        } else if self.offline && remote {
+            return Err(ProviderError::CacheError(format!(
+                "{} is not cached and remote sources are not fetched offline",
+                source
+            )));
+        }
+
+        let (schema, types) = resolve()?;
+        let entry = CacheEntry {
+            provider: provider.to_string(),
+            source: source.to_string(),
+            source_hash,
+            created: now(),
+            schema,
+            types,
+        };
+        // A cache that cannot be written only costs the next resolution
+        let _ = self.write(&path, &entry);
+        Ok(entry.types)
+    }
+
+    /// Remove every entry
+    pub fn clear(&self) -> ProviderResult<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(ProviderError::CacheError(
+                format!("{}: {}", self.dir.display(), e),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn entry_path(
+        &self,
+        provider: &str,
+        source: &str,
+        namespace: &str,
+        params: &ProviderParams,
+    ) -> PathBuf {
+        let mut custom: Vec<_> = params.custom.iter().collect();
+        custom.sort();
+        let mut key = format!("{}\0{}\0{}", provider, source, namespace);
+        for (name, value) in custom {
+            key.push_str(&format!("\0{}={}", name, value));
+        }
+        self.dir
+            .join(format!("{}-{}.json", provider, hash(key.as_bytes())))
+    }
+
+    fn read(&self, path: &Path) -> Option<CacheEntry> {
+        let text = std::fs::read_to_string(path).ok()?;
+        // Unreadable entries (e.g. from an older version) are misses
+        serde_json::from_str(&text).ok()
+    }
+
+    fn write(&self, path: &Path, entry: &CacheEntry) -> ProviderResult<()> {
+        let cache_error =
+            |e: std::io::Error| ProviderError::CacheError(format!("{}: {}", path.display(), e));
+        std::fs::create_dir_all(&self.dir).map_err(cache_error)?;
+        let text =
+            serde_json::to_string(entry).map_err(|e| ProviderError::CacheError(e.to_string()))?;
+        std::fs::write(path, text).map_err(cache_error)
+    }
+}
+
+fn is_remote(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Hash of the file named by `source`, if it names one
+fn source_file_hash(source: &str) -> Option<String> {
+    if source.contains('\n') {
+        return None;
+    }
+    std::fs::read(source).ok().map(|bytes| hash(&bytes))
+}
+
+/// 64-bit FNV-1a, stable across builds unlike `DefaultHasher`
+fn hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn resolved(name: &str) -> ProviderResult<(Schema, GeneratedTypes)> {
+        let types =
+            GeneratedTypes::new().with_type(crate::TypeDefinition::Record(crate::RecordDef {
+                name: name.to_string(),
+                fields: vec![],
+            }));
+        Ok((Schema::Custom(name.to_string()), types))
+    }
+
+    fn type_name(types: &GeneratedTypes) -> String {
+        match &types.root_types[0] {
+            crate::TypeDefinition::Record(record) => record.name.clone(),
+            crate::TypeDefinition::Du(du) => du.name.clone(),
+        }
+    }
+
+    #[test]
+    fn test_reuses_until_source_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ProviderCache::new(dir.path().join("cache"));
+        let source = dir.path().join("config.toml");
+        let source = source.to_str().unwrap();
+        std::fs::write(source, "a = 1").unwrap();
+
+        let calls = Cell::new(0);
+        let resolve = |name: &str| {
+            calls.set(calls.get() + 1);
+            resolved(name)
+        };
+        let params = ProviderParams::default();
+        let get = |name: &str| {
+            cache
+                .get_or_resolve("TomlProvider", source, "Config", &params, || resolve(name))
+                .unwrap()
+        };
+
+        assert_eq!(type_name(&get("First")), "First");
+        assert_eq!(type_name(&get("Second")), "First");
+        assert_eq!(calls.get(), 1);
+
+        std::fs::write(source, "a = 2").unwrap();
+        assert_eq!(type_name(&get("Third")), "Third");
+        assert_eq!(calls.get(), 2);
+
+        cache.clear().unwrap();
+        assert_eq!(type_name(&get("Fourth")), "Fourth");
+    }
+
+    #[test]
+    fn test_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ProviderCache::new(dir.path());
+        let params = ProviderParams {
+            cache_ttl_secs: Some(0),
+            ..Default::default()
+        };
+        let path = cache.entry_path("P", "inline", "T", &params);
+
+        cache
+            .get_or_resolve("P", "inline", "T", &params, || resolved("Old"))
+            .unwrap();
+        // Age the entry past the TTL
+        let mut entry = cache.read(&path).unwrap();
+        entry.created -= 10;
+        cache.write(&path, &entry).unwrap();
+
+        let types = cache
+            .get_or_resolve("P", "inline", "T", &params, || resolved("New"))
+            .unwrap();
+        assert_eq!(type_name(&types), "New");
+
+        // Without a TTL, old entries are still used
+        let mut entry = cache.read(&path).unwrap();
+        entry.created -= 10;
+        cache.write(&path, &entry).unwrap();
+        let types = cache
+            .get_or_resolve("P", "inline", "T", &ProviderParams::default(), || {
+                resolved("Ignored")
+            })
+            .unwrap();
+        assert_eq!(type_name(&types), "New");
+    }
+
+    #[test]
+    fn test_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = "https://example.com/schema.json";
+        let params = ProviderParams {
+            cache_ttl_secs: Some(0),
+            ..Default::default()
+        };
+        let offline = ProviderCache::new(dir.path()).offline(true);
+
+        let missing = offline.get_or_resolve("P", url, "T", &params, || resolved("Fetched"));
+        assert!(matches!(missing, Err(ProviderError::CacheError(_))));
+
+        let online = ProviderCache::new(dir.path());
+        online
+            .get_or_resolve("P", url, "T", &params, || resolved("Fetched"))
+            .unwrap();
+
+        // Stale entries of remote sources are used rather than refetched
+        let path = offline.entry_path("P", url, "T", &params);
+        let mut entry = offline.read(&path).unwrap();
+        entry.created -= 10;
+        offline.write(&path, &entry).unwrap();
+        let types = offline
+            .get_or_resolve("P", url, "T", &params, || panic!("fetched offline"))
+            .unwrap();
+        assert_eq!(type_name(&types), "Fetched");
+    }
+
+    #[test]
+    fn test_hash_is_stable() {
+        assert_eq!(hash(b""), "cbf29ce484222325");
+        assert_eq!(hash(b"a"), "af63dc4c8601ec8c");
+    }
+}
@@ -1,14 +1,17 @@
 //! Type generation utilities for type providers
 
-use crate::types::{DuDef, RecordDef, TypeDefinition, TypeExpr, VariantDef};
+use crate::types::{DuDef, ProvidedMember, RecordDef, TypeDefinition, TypeExpr, VariantDef};
+use serde::{Deserialize, Serialize};
 
 /// Generated types from a provider
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeneratedTypes {
     /// Nested modules with types
     pub modules: Vec<GeneratedModule>,
     /// Root-level types
     pub root_types: Vec<TypeDefinition>,
+    /// Value-level members, accessed through the declaration's alias
+    pub members: Vec<ProvidedMember>,
 }
 
 impl GeneratedTypes {
@@ -16,6 +19,7 @@ impl GeneratedTypes {
         Self {
             modules: Vec::new(),
             root_types: Vec::new(),
+            members: Vec::new(),
         }
     }
 
@@ -28,6 +32,11 @@ impl GeneratedTypes {
         self.root_types.push(ty);
         self
     }
+
+    pub fn with_member(mut self, member: ProvidedMember) -> Self {
+        self.members.push(member);
+        self
+    }
 }
 
 impl Default for GeneratedTypes {
@@ -37,7 +46,7 @@ impl Default for GeneratedTypes {
 }
 
 /// A generated module containing types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeneratedModule {
     /// Module path (e.g., ["Core", "V1"])
     pub path: Vec<String>,
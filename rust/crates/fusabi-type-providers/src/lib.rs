@@ -7,8 +7,13 @@
 //!
 //! - `TypeProvider` trait: The main interface that all providers must implement
 //! - `ProviderRegistry`: Manages registered type providers
+//! - `ProviderCache`: On-disk cache of resolutions, with TTL and offline mode
 //! - `GeneratedTypes`: Represents types generated by a provider
 //! - Type definitions: `TypeDefinition`, `RecordDef`, `DuDef`, etc.
+//! - `ProvidedMember`: value-level members erased to stdlib calls
+//! - `TypeShape`: describes generated types for `parse`/`serialize` members
+//! - Built-in providers: `JsonSchemaProvider`, `TomlProvider`, `SqlProvider`,
+//!   `ProtobufProvider`, `GraphqlProvider`, `RegexProvider`
 //!
 //! # Example
 //!
@@ -16,6 +21,7 @@
 //! use fusabi_type_providers::{ProviderRegistry, TypeProvider};
 //! use std::sync::Arc;
 //!
+//! // Starts with the built-in providers
 //! let mut registry = ProviderRegistry::new();
 //! registry.register(Arc::new(MyProvider::new()));
 //!
@@ -27,17 +33,26 @@
 //! )?;
 //! ```
 
+pub mod cache;
 pub mod error;
 pub mod generator;
 pub mod provider;
+pub mod providers;
 pub mod registry;
+pub mod shape;
 pub mod types;
 
 // Re-export main types for convenience
+pub use cache::ProviderCache;
 pub use error::{ProviderError, ProviderResult};
 pub use generator::{GeneratedModule, GeneratedTypes, NamingStrategy, TypeGenerator};
 pub use provider::{ProviderParams, Schema, TypeProvider};
+pub use providers::{
+    GraphqlProvider, JsonSchemaProvider, ProtobufProvider, RegexProvider, SqlProvider, TomlProvider,
+};
 pub use registry::ProviderRegistry;
+pub use shape::TypeShape;
 pub use types::{
-    DuDef, FieldDef, FieldValidationResult, RecordDef, TypeDefinition, TypeExpr, VariantDef,
+    DuDef, FieldDef, FieldValidationResult, ProvidedMember, RecordDef, TypeDefinition, TypeExpr,
+    VariantDef,
 };
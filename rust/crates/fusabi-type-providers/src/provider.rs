@@ -2,17 +2,19 @@
 
 use crate::error::ProviderResult;
 use crate::generator::GeneratedTypes;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// Parameters passed to type providers
 #[derive(Debug, Clone, Default)]
 pub struct ProviderParams {
+    /// How long a cached resolution stays fresh (see `ProviderCache`)
     pub cache_ttl_secs: Option<u64>,
     pub custom: HashMap<String, String>,
 }
 
 /// Result of schema resolution
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Schema {
     JsonSchema(serde_json::Value),
     OpenApi(serde_json::Value),
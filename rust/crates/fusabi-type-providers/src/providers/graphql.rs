@@ -0,0 +1,495 @@
+//! GraphQL schema type provider
+//!
+//! ```fusabi
+//! type Api = GraphqlProvider<"schema.graphql">
+//! ```
+//!
+//! Reads a schema in the GraphQL SDL and generates, in a module named after
+//! the declaration, a record per object, interface and input type (with a
+//! field per field, ignoring arguments), a simple union per enum and a
+//! union with a case per member type per `union`. Nullable fields are
+//! options and list types lists. `ID` is a string, as are custom scalars.
+
+use super::read_source;
+use super::tokens::{Token, Tokens};
+use crate::error::{ProviderError, ProviderResult};
+use crate::generator::{GeneratedModule, GeneratedTypes, NamingStrategy, TypeGenerator};
+use crate::provider::{ProviderParams, Schema, TypeProvider};
+use crate::types::{TypeDefinition, TypeExpr, VariantDef};
+use std::collections::HashSet;
+
+/// Generates records and unions from a GraphQL schema
+pub struct GraphqlProvider {
+    generator: TypeGenerator,
+}
+
+impl GraphqlProvider {
+    pub fn new() -> Self {
+        Self {
+            generator: TypeGenerator::new(NamingStrategy::PreserveOriginal),
+        }
+    }
+}
+
+impl Default for GraphqlProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeProvider for GraphqlProvider {
+    fn name(&self) -> &str {
+        "GraphqlProvider"
+    }
+
+    fn resolve_schema(&self, source: &str, _params: &ProviderParams) -> ProviderResult<Schema> {
+        let text = read_source(source, |s| s.contains('{') || s.contains("union "))?;
+        parse(&text)?;
+        Ok(Schema::Custom(text))
+    }
+
+    fn generate_types(&self, schema: &Schema, namespace: &str) -> ProviderResult<GeneratedTypes> {
+        let document = match schema {
+            Schema::Custom(text) => parse(text)?,
+            _ => {
+                return Err(ProviderError::GenerationError(
+                    "GraphqlProvider expects a GraphQL schema".to_string(),
+                ))
+            }
+        };
+        if document.definitions.is_empty() {
+            return Err(ProviderError::GenerationError(
+                "no types found in the schema".to_string(),
+            ));
+        }
+
+        let mut module = GeneratedModule::new(vec![namespace.to_string()]);
+        for definition in &document.definitions {
+            let ty = match definition {
+                Definition::Object { name, fields } => {
+                    let fields = fields
+                        .iter()
+                        .map(|(field, ty)| (field.clone(), ty.to_type(&document.scalars)))
+                        .collect();
+                    TypeDefinition::Record(self.generator.make_record(name, fields))
+                }
+                Definition::Enum { name, values } => {
+                    let variants = values
+                        .iter()
+                        .map(|value| {
+                            VariantDef::new_simple(super::pascal_case(&value.to_ascii_lowercase()))
+                        })
+                        .collect();
+                    TypeDefinition::Du(self.generator.make_du(name, variants))
+                }
+                Definition::Union { name, members } => {
+                    let variants = members
+                        .iter()
+                        .map(|member| {
+                            VariantDef::new(member.clone(), vec![TypeExpr::Named(member.clone())])
+                        })
+                        .collect();
+                    TypeDefinition::Du(self.generator.make_du(name, variants))
+                }
+            };
+            module = module.with_type(ty);
+        }
+
+        Ok(GeneratedTypes::new().with_module(module))
+    }
+}
+
+struct Document {
+    definitions: Vec<Definition>,
+    /// Custom scalars, represented as strings
+    scalars: HashSet<String>,
+}
+
+enum Definition {
+    /// `type`, `interface` and `input` types
+    Object {
+        name: String,
+        fields: Vec<(String, FieldType)>,
+    },
+    Enum {
+        name: String,
+        values: Vec<String>,
+    },
+    Union {
+        name: String,
+        members: Vec<String>,
+    },
+}
+
+enum FieldType {
+    Named(String, bool),
+    List(Box<FieldType>, bool),
+}
+
+impl FieldType {
+    fn to_type(&self, scalars: &HashSet<String>) -> TypeExpr {
+        let (ty, non_null) = match self {
+            FieldType::Named(name, non_null) => {
+                let ty = match name.as_str() {
+                    "Int" => "int",
+                    "Float" => "float",
+                    "Boolean" => "bool",
+                    "String" | "ID" => "string",
+                    custom if scalars.contains(custom) => "string",
+                    other => other,
+                };
+                (ty.to_string(), *non_null)
+            }
+            FieldType::List(item, non_null) => {
+                (format!("{} list", item.to_type(scalars)), *non_null)
+            }
+        };
+        TypeExpr::Named(if non_null {
+            ty
+        } else {
+            format!("{} option", ty)
+        })
+    }
+}
+
+fn parse(text: &str) -> ProviderResult<Document> {
+    let mut tokens = Tokens::new(text, "#")?;
+    let mut document = Document {
+        definitions: Vec::new(),
+        scalars: HashSet::new(),
+    };
+
+    while !tokens.at_end() {
+        skip_description(&mut tokens);
+        let extend = tokens.eat_word("extend");
+        match tokens.ident()?.as_str() {
+            "schema" => {
+                skip_directives(&mut tokens)?;
+                tokens.expect('{')?;
+                tokens.skip_group('}')?;
+            }
+            "scalar" => {
+                document.scalars.insert(tokens.ident()?);
+                skip_directives(&mut tokens)?;
+            }
+            "type" | "interface" | "input" => {
+                let name = tokens.ident()?;
+                if tokens.eat_word("implements") {
+                    tokens.eat('&');
+                    tokens.ident()?;
+                    while tokens.eat('&') {
+                        tokens.ident()?;
+                    }
+                }
+                skip_directives(&mut tokens)?;
+                let fields = if tokens.eat('{') {
+                    parse_fields(&mut tokens)?
+                } else {
+                    Vec::new()
+                };
+                match existing(&mut document, &name, extend) {
+                    Some(Definition::Object { fields: known, .. }) => known.extend(fields),
+                    _ => document
+                        .definitions
+                        .push(Definition::Object { name, fields }),
+                }
+            }
+            "enum" => {
+                let name = tokens.ident()?;
+                skip_directives(&mut tokens)?;
+                let mut values = Vec::new();
+                if tokens.eat('{') {
+                    while !tokens.eat('}') {
+                        skip_description(&mut tokens);
+                        values.push(tokens.ident()?);
+                        skip_directives(&mut tokens)?;
+                    }
+                }
+                match existing(&mut document, &name, extend) {
+                    Some(Definition::Enum { values: known, .. }) => known.extend(values),
+                    _ => document.definitions.push(Definition::Enum { name, values }),
+                }
+            }
+            "union" => {
+                let name = tokens.ident()?;
+                skip_directives(&mut tokens)?;
+                let mut members = Vec::new();
+                if tokens.eat('=') {
+                    tokens.eat('|');
+                    members.push(tokens.ident()?);
+                    while tokens.eat('|') {
+                        members.push(tokens.ident()?);
+                    }
+                }
+                match existing(&mut document, &name, extend) {
+                    Some(Definition::Union { members: known, .. }) => known.extend(members),
+                    _ => document
+                        .definitions
+                        .push(Definition::Union { name, members }),
+                }
+            }
+            "directive" => {
+                tokens.expect('@')?;
+                tokens.ident()?;
+                if tokens.eat('(') {
+                    tokens.skip_group(')')?;
+                }
+                tokens.eat_word("repeatable");
+                if !tokens.eat_word("on") {
+                    return Err(tokens.unexpected("'on'"));
+                }
+                tokens.eat('|');
+                tokens.ident()?;
+                while tokens.eat('|') {
+                    tokens.ident()?;
+                }
+            }
+            other => {
+                return Err(ProviderError::ParseError(format!(
+                    "unexpected '{}' at top level",
+                    other
+                )))
+            }
+        }
+    }
+    Ok(document)
+}
+
+/// The definition an `extend` adds to
+fn existing<'d>(
+    document: &'d mut Document,
+    name: &str,
+    extend: bool,
+) -> Option<&'d mut Definition> {
+    if !extend {
+        return None;
+    }
+    document
+        .definitions
+        .iter_mut()
+        .find(|definition| match definition {
+            Definition::Object { name: n, .. }
+            | Definition::Enum { name: n, .. }
+            | Definition::Union { name: n, .. } => n == name,
+        })
+}
+
+/// Fields up to the closing `}`
+fn parse_fields(tokens: &mut Tokens) -> ProviderResult<Vec<(String, FieldType)>> {
+    let mut fields = Vec::new();
+    while !tokens.eat('}') {
+        skip_description(tokens);
+        let name = tokens.ident()?;
+        if tokens.eat('(') {
+            tokens.skip_group(')')?;
+        }
+        tokens.expect(':')?;
+        let ty = parse_type(tokens)?;
+        if tokens.eat('=') {
+            skip_value(tokens)?;
+        }
+        skip_directives(tokens)?;
+        fields.push((name, ty));
+    }
+    Ok(fields)
+}
+
+fn parse_type(tokens: &mut Tokens) -> ProviderResult<FieldType> {
+    if tokens.eat('[') {
+        let item = parse_type(tokens)?;
+        tokens.expect(']')?;
+        Ok(FieldType::List(Box::new(item), tokens.eat('!')))
+    } else {
+        let name = tokens.ident()?;
+        Ok(FieldType::Named(name, tokens.eat('!')))
+    }
+}
+
+fn skip_value(tokens: &mut Tokens) -> ProviderResult<()> {
+    match tokens.next() {
+        Some(Token::Punct('[')) => tokens.skip_group(']'),
+        Some(Token::Punct('{')) => tokens.skip_group('}'),
+        Some(Token::Punct(c)) => Err(ProviderError::ParseError(format!(
+            "unexpected '{}' in default value",
+            c
+        ))),
+        Some(_) => Ok(()),
+        None => Err(tokens.unexpected("a value")),
+    }
+}
+
+fn skip_directives(tokens: &mut Tokens) -> ProviderResult<()> {
+    while tokens.eat('@') {
+        tokens.ident()?;
+        if tokens.eat('(') {
+            tokens.skip_group(')')?;
+        }
+    }
+    Ok(())
+}
+
+fn skip_description(tokens: &mut Tokens) {
+    while matches!(tokens.peek(), Some(Token::Str(_))) {
+        tokens.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DuDef, RecordDef};
+
+    const SCHEMA: &str = r#"
+"""
+A blog
+"""
+schema { query: Query }
+
+scalar DateTime
+
+directive @auth(requires: Role = ADMIN) on OBJECT | FIELD_DEFINITION
+
+interface Node { id: ID! }
+
+type User implements Node & Entity @auth {
+  id: ID!
+  "Display name"
+  name: String
+  posts(first: Int = 10, after: String): [Post!]!
+  joined: DateTime!
+}
+
+type Post implements Node {
+  id: ID!
+  title: String!
+  tags: [String]
+  score: Float
+  published: Boolean!
+}
+
+extend type Post {
+  author: User!
+}
+
+enum Role { ADMIN READ_ONLY @deprecated(reason: "no") }
+
+union SearchResult = | User | Post
+
+input PostFilter {
+  tag: String = "news"
+  limit: Int! = 20
+  roles: [Role!] = [ADMIN]
+}
+
+type Query {
+  search(text: String!, filter: PostFilter): [SearchResult!]!
+}
+"#;
+
+    fn generate(source: &str) -> ProviderResult<GeneratedTypes> {
+        let provider = GraphqlProvider::new();
+        let schema = provider.resolve_schema(source, &ProviderParams::default())?;
+        provider.generate_types(&schema, "Api")
+    }
+
+    fn record<'t>(types: &'t GeneratedTypes, name: &str) -> &'t RecordDef {
+        types.modules[0]
+            .types
+            .iter()
+            .find_map(|ty| match ty {
+                TypeDefinition::Record(r) if r.name == name => Some(r),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("no record {}", name))
+    }
+
+    fn union<'t>(types: &'t GeneratedTypes, name: &str) -> &'t DuDef {
+        types.modules[0]
+            .types
+            .iter()
+            .find_map(|ty| match ty {
+                TypeDefinition::Du(du) if du.name == name => Some(du),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("no union {}", name))
+    }
+
+    fn fields(record: &RecordDef) -> Vec<(&str, String)> {
+        record
+            .fields
+            .iter()
+            .map(|(name, ty)| (name.as_str(), ty.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_object_types_become_records() {
+        let types = generate(SCHEMA).unwrap();
+        assert_eq!(types.modules[0].path, vec!["Api"]);
+        assert_eq!(
+            fields(record(&types, "User")),
+            vec![
+                ("id", "string".to_string()),
+                ("name", "string option".to_string()),
+                ("posts", "Post list".to_string()),
+                ("joined", "string".to_string()),
+            ]
+        );
+        // Extensions add fields
+        assert_eq!(
+            fields(record(&types, "Post")),
+            vec![
+                ("id", "string".to_string()),
+                ("title", "string".to_string()),
+                ("tags", "string option list option".to_string()),
+                ("score", "float option".to_string()),
+                ("published", "bool".to_string()),
+                ("author", "User".to_string()),
+            ]
+        );
+        assert_eq!(fields(record(&types, "Node")).len(), 1);
+        assert_eq!(
+            fields(record(&types, "PostFilter")),
+            vec![
+                ("tag", "string option".to_string()),
+                ("limit", "int".to_string()),
+                ("roles", "Role list option".to_string()),
+            ]
+        );
+        assert_eq!(
+            fields(record(&types, "Query")),
+            vec![("search", "SearchResult list".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_enums_and_unions() {
+        let types = generate(SCHEMA).unwrap();
+        let role = union(&types, "Role");
+        assert!(role.is_simple_enum());
+        assert_eq!(role.variant_names(), vec!["Admin", "ReadOnly"]);
+
+        let result = union(&types, "SearchResult");
+        assert_eq!(result.variant_names(), vec!["User", "Post"]);
+        assert_eq!(
+            result.variants[0].fields,
+            vec![TypeExpr::Named("User".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_invalid_schemas() {
+        assert!(matches!(
+            generate("type User { id: }"),
+            Err(ProviderError::ParseError(_))
+        ));
+        assert!(matches!(
+            generate("query { user }"),
+            Err(ProviderError::ParseError(_))
+        ));
+        assert!(matches!(
+            generate("schema.graphql"),
+            Err(ProviderError::InvalidSource(_))
+        ));
+    }
+}
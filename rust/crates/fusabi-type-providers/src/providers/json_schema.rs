@@ -0,0 +1,562 @@
+//! JSON Schema type provider
+//!
+//! ```fusabi
+//! type Person = JsonSchemaProvider<"person.schema.json">
+//! ```
+//!
+//! Object schemas become records named after the declaration, their
+//! properties and their `definitions`/`$defs`. Properties that are not
+//! `required` are options. Local `$ref`s (`#/definitions/Address`) refer to
+//! the type generated for their target, `oneOf`/`anyOf` become discriminated
+//! unions and string `enum`s simple ones. Schemas without a type map to `json`.
+//!
+//! When the root schema defines a type, the alias also gets
+//! `Person.parse : string -> Result<Person, string>`, which checks a JSON
+//! document against the generated types, and `Person.serialize`.
+
+use super::{pascal_case, read_source, TypeNames};
+use crate::error::{ProviderError, ProviderResult};
+use crate::generator::{GeneratedTypes, NamingStrategy, TypeGenerator};
+use crate::provider::{ProviderParams, Schema, TypeProvider};
+use crate::shape::TypeShape;
+use crate::types::{TypeDefinition, TypeExpr, VariantDef};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Generates records and unions from a JSON Schema
+pub struct JsonSchemaProvider {
+    generator: TypeGenerator,
+}
+
+impl JsonSchemaProvider {
+    pub fn new() -> Self {
+        Self {
+            generator: TypeGenerator::new(NamingStrategy::PreserveOriginal),
+        }
+    }
+}
+
+impl Default for JsonSchemaProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeProvider for JsonSchemaProvider {
+    fn name(&self) -> &str {
+        "JsonSchemaProvider"
+    }
+
+    fn resolve_schema(&self, source: &str, _params: &ProviderParams) -> ProviderResult<Schema> {
+        let text = read_source(source, |s| s.trim_start().starts_with('{'))?;
+        serde_json::from_str(&text)
+            .map(Schema::JsonSchema)
+            .map_err(|e| ProviderError::ParseError(e.to_string()))
+    }
+
+    fn generate_types(&self, schema: &Schema, namespace: &str) -> ProviderResult<GeneratedTypes> {
+        let root = match schema {
+            Schema::JsonSchema(root) => root,
+            _ => {
+                return Err(ProviderError::GenerationError(
+                    "JsonSchemaProvider expects a JSON Schema".to_string(),
+                ))
+            }
+        };
+
+        let mut builder = Builder {
+            generator: &self.generator,
+            root,
+            names: TypeNames::default(),
+            refs: HashMap::new(),
+            types: Vec::new(),
+            literals: Vec::new(),
+        };
+        builder.names.reserve(namespace);
+
+        for key in ["definitions", "$defs"] {
+            if let Some(Value::Object(definitions)) = root.get(key) {
+                for name in definitions.keys() {
+                    builder.reference(&format!("#/{}/{}", key, escape_pointer(name)))?;
+                }
+            }
+        }
+
+        let has_root = is_named(root);
+        if has_root {
+            builder.define(namespace, root)?;
+        } else if builder.types.is_empty() {
+            return Err(ProviderError::GenerationError(
+                "schema defines no object, union or enum types".to_string(),
+            ));
+        }
+
+        let mut generated = GeneratedTypes {
+            modules: Vec::new(),
+            root_types: builder.types,
+            members: Vec::new(),
+        };
+        if has_root {
+            let shape = builder.literals.into_iter().fold(
+                TypeShape::new(&generated, namespace),
+                |shape, (union, case, value)| shape.with_literal(&union, &case, value),
+            );
+            generated.members = shape.members("Json");
+        }
+        Ok(generated)
+    }
+}
+
+struct Builder<'a> {
+    generator: &'a TypeGenerator,
+    root: &'a Value,
+    names: TypeNames,
+    /// Types of the `$ref` targets seen so far
+    refs: HashMap<String, TypeExpr>,
+    /// Definitions in dependency order
+    types: Vec<TypeDefinition>,
+    /// Enum cases with the value spelling them: (union, case, value)
+    literals: Vec<(String, String, Value)>,
+}
+
+impl<'a> Builder<'a> {
+    /// The type of a value matching `schema`, defining a type named after
+    /// `hint` if it needs one
+    fn type_of(&mut self, schema: &'a Value, hint: &str, parent: &str) -> ProviderResult<TypeExpr> {
+        if let Some(pointer) = schema.get("$ref").and_then(Value::as_str) {
+            return self.reference(pointer);
+        }
+        if is_named(schema) {
+            let name = self.names.claim(hint, parent);
+            self.define(&name, schema)?;
+            return Ok(TypeExpr::Named(name));
+        }
+
+        let types: Vec<&str> = match schema.get("type") {
+            Some(Value::String(ty)) => vec![ty.as_str()],
+            Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let nullable = types.len() > 1 && types.contains(&"null");
+        let ty = match types.iter().find(|ty| **ty != "null" || types.len() == 1) {
+            Some(&"array") => {
+                let item = match schema.get("items") {
+                    Some(items) => self.type_of(items, hint, parent)?,
+                    None => TypeExpr::Named("json".to_string()),
+                };
+                TypeExpr::Named(format!("{} list", item))
+            }
+            Some(ty) => self.generator.json_type_to_fusabi(ty),
+            None => TypeExpr::Named("json".to_string()),
+        };
+        Ok(if nullable { optional(ty) } else { ty })
+    }
+
+    /// The type of the schema at a local JSON pointer
+    fn reference(&mut self, pointer: &str) -> ProviderResult<TypeExpr> {
+        if let Some(ty) = self.refs.get(pointer) {
+            return Ok(ty.clone());
+        }
+        let target = pointer
+            .strip_prefix('#')
+            .and_then(|path| self.root.pointer(path))
+            .ok_or_else(|| {
+                ProviderError::GenerationError(format!(
+                    "cannot resolve $ref '{}' (only local references are supported)",
+                    pointer
+                ))
+            })?;
+        let key = pointer.rsplit('/').next().unwrap_or_default();
+        let key = key.replace("~1", "/").replace("~0", "~");
+
+        if is_named(target) {
+            // Registered before the definition so recursive references resolve
+            let name = self.names.claim(&key, "");
+            self.refs
+                .insert(pointer.to_string(), TypeExpr::Named(name.clone()));
+            self.define(&name, target)?;
+            Ok(TypeExpr::Named(name))
+        } else {
+            let ty = self.type_of(target, &key, "")?;
+            self.refs.insert(pointer.to_string(), ty.clone());
+            Ok(ty)
+        }
+    }
+
+    /// Define the record or union `name` for `schema`
+    fn define(&mut self, name: &str, schema: &'a Value) -> ProviderResult<()> {
+        if let Some(Value::Array(alternatives)) = schema.get("oneOf").or(schema.get("anyOf")) {
+            return self.define_union(name, alternatives);
+        }
+        if let Some(Value::Array(values)) = schema.get("enum") {
+            let values: Vec<&str> = values.iter().filter_map(Value::as_str).collect();
+            let variants = unique_variants(
+                values
+                    .iter()
+                    .map(|value| VariantDef::new_simple(pascal_case(value)))
+                    .collect(),
+            );
+            let du = self.generator.make_du(name, variants);
+            for (variant, value) in du.variants.iter().zip(values) {
+                self.literals
+                    .push((du.name.clone(), variant.name.clone(), Value::from(value)));
+            }
+            self.types.push(TypeDefinition::Du(du));
+            return Ok(());
+        }
+
+        let mut properties = Vec::new();
+        let mut required = HashSet::new();
+        self.collect_properties(schema, &mut properties, &mut required)?;
+
+        let mut fields = Vec::new();
+        for (property, property_schema) in properties {
+            let ty = self.type_of(property_schema, property, name)?;
+            let ty = if required.contains(property) || is_optional(&ty) {
+                ty
+            } else {
+                optional(ty)
+            };
+            fields.push((property.to_string(), ty));
+        }
+        let record = self.generator.make_record(name, fields);
+        self.types.push(TypeDefinition::Record(record));
+        Ok(())
+    }
+
+    /// Properties of an object schema, including those of its `allOf` parts
+    fn collect_properties(
+        &mut self,
+        schema: &'a Value,
+        properties: &mut Vec<(&'a str, &'a Value)>,
+        required: &mut HashSet<&'a str>,
+    ) -> ProviderResult<()> {
+        if let Some(Value::Array(parts)) = schema.get("allOf") {
+            for part in parts {
+                let part = match part.get("$ref").and_then(Value::as_str) {
+                    Some(pointer) => self.resolve_pointer(pointer)?,
+                    None => part,
+                };
+                self.collect_properties(part, properties, required)?;
+            }
+        }
+        if let Some(Value::Object(own)) = schema.get("properties") {
+            for (name, property) in own {
+                properties.retain(|(existing, _)| *existing != name.as_str());
+                properties.push((name.as_str(), property));
+            }
+        }
+        if let Some(Value::Array(names)) = schema.get("required") {
+            required.extend(names.iter().filter_map(Value::as_str));
+        }
+        Ok(())
+    }
+
+    fn define_union(&mut self, name: &str, alternatives: &'a [Value]) -> ProviderResult<()> {
+        let mut variants = Vec::new();
+        for (i, alternative) in alternatives.iter().enumerate() {
+            let ref_name = alternative
+                .get("$ref")
+                .and_then(Value::as_str)
+                .and_then(|pointer| pointer.rsplit('/').next());
+            let type_name = alternative.get("type").and_then(Value::as_str);
+            let variant = match (alternative.get("title").and_then(Value::as_str), ref_name) {
+                (Some(title), _) => pascal_case(title),
+                (None, Some(target)) => pascal_case(target),
+                (None, None) => match type_name {
+                    Some("null") => "Null".to_string(),
+                    Some(ty) if ty != "object" && !is_named(alternative) => {
+                        pascal_case(&self.generator.json_type_to_fusabi(ty).to_string())
+                    }
+                    _ => format!("Case{}", i + 1),
+                },
+            };
+
+            if type_name == Some("null") {
+                variants.push(VariantDef::new_simple(variant));
+                continue;
+            }
+            let payload = self.type_of(alternative, &format!("{}{}", name, variant), "")?;
+            variants.push(VariantDef::new(variant, vec![payload]));
+        }
+
+        let du = self.generator.make_du(name, unique_variants(variants));
+        self.types.push(TypeDefinition::Du(du));
+        Ok(())
+    }
+
+    fn resolve_pointer(&self, pointer: &str) -> ProviderResult<&'a Value> {
+        pointer
+            .strip_prefix('#')
+            .and_then(|path| self.root.pointer(path))
+            .ok_or_else(|| {
+                ProviderError::GenerationError(format!("cannot resolve $ref '{}'", pointer))
+            })
+    }
+}
+
+/// Whether `schema` needs a named type: objects, unions and string enums
+fn is_named(schema: &Value) -> bool {
+    schema.get("properties").is_some()
+        || schema.get("allOf").is_some()
+        || schema.get("oneOf").is_some()
+        || schema.get("anyOf").is_some()
+        || schema.get("type").and_then(Value::as_str) == Some("object")
+        || schema
+            .get("enum")
+            .and_then(Value::as_array)
+            .is_some_and(|values| !values.is_empty() && values.iter().all(Value::is_string))
+}
+
+fn optional(ty: TypeExpr) -> TypeExpr {
+    TypeExpr::Named(format!("{} option", ty))
+}
+
+fn is_optional(ty: &TypeExpr) -> bool {
+    matches!(ty, TypeExpr::Named(name) if name.ends_with(" option"))
+}
+
+/// Number repeated variant names so each constructor is distinct
+fn unique_variants(variants: Vec<VariantDef>) -> Vec<VariantDef> {
+    let mut seen = HashSet::new();
+    variants
+        .into_iter()
+        .map(|mut variant| {
+            let base = variant.name.clone();
+            let mut n = 2;
+            while !seen.insert(variant.name.clone()) {
+                variant.name = format!("{}{}", base, n);
+                n += 1;
+            }
+            variant
+        })
+        .collect()
+}
+
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DuDef, RecordDef};
+
+    fn generate(schema: &str) -> Vec<TypeDefinition> {
+        let provider = JsonSchemaProvider::new();
+        let schema = provider
+            .resolve_schema(schema, &ProviderParams::default())
+            .unwrap();
+        provider.generate_types(&schema, "Root").unwrap().root_types
+    }
+
+    fn record<'t>(types: &'t [TypeDefinition], name: &str) -> &'t RecordDef {
+        types
+            .iter()
+            .find_map(|ty| match ty {
+                TypeDefinition::Record(r) if r.name == name => Some(r),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("no record {}", name))
+    }
+
+    fn union<'t>(types: &'t [TypeDefinition], name: &str) -> &'t DuDef {
+        types
+            .iter()
+            .find_map(|ty| match ty {
+                TypeDefinition::Du(du) if du.name == name => Some(du),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("no union {}", name))
+    }
+
+    fn named(name: &str) -> TypeExpr {
+        TypeExpr::Named(name.to_string())
+    }
+
+    #[test]
+    fn test_object_properties() {
+        let types = generate(
+            r#"{
+                "type": "object",
+                "required": ["name", "tags"],
+                "properties": {
+                    "name": { "type": "string" },
+                    "age": { "type": "integer" },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "address": {
+                        "type": "object",
+                        "required": ["city"],
+                        "properties": { "city": { "type": "string" } }
+                    },
+                    "nickname": { "type": ["string", "null"] }
+                }
+            }"#,
+        );
+        let root = record(&types, "Root");
+        assert_eq!(
+            root.fields,
+            vec![
+                ("address".to_string(), named("Address option")),
+                ("age".to_string(), named("int option")),
+                ("name".to_string(), named("string")),
+                ("nickname".to_string(), named("string option")),
+                ("tags".to_string(), named("string list")),
+            ]
+        );
+        assert_eq!(
+            record(&types, "Address").fields,
+            vec![("city".to_string(), named("string"))]
+        );
+    }
+
+    #[test]
+    fn test_refs() {
+        let types = generate(
+            r##"{
+                "$defs": {
+                    "node": {
+                        "type": "object",
+                        "required": ["value", "children"],
+                        "properties": {
+                            "value": { "$ref": "#/$defs/id" },
+                            "children": { "type": "array", "items": { "$ref": "#/$defs/node" } }
+                        }
+                    },
+                    "id": { "type": "integer" }
+                },
+                "type": "object",
+                "required": ["tree"],
+                "properties": { "tree": { "$ref": "#/$defs/node" } }
+            }"##,
+        );
+        assert_eq!(
+            record(&types, "Node").fields,
+            vec![
+                ("children".to_string(), named("Node list")),
+                ("value".to_string(), named("int")),
+            ]
+        );
+        assert_eq!(
+            record(&types, "Root").fields,
+            vec![("tree".to_string(), named("Node"))]
+        );
+        // Each definition is generated once
+        assert_eq!(types.len(), 2);
+
+        let provider = JsonSchemaProvider::new();
+        let schema = Schema::JsonSchema(serde_json::json!({
+            "properties": { "x": { "$ref": "other.json#/x" } }
+        }));
+        assert!(provider.generate_types(&schema, "Root").is_err());
+    }
+
+    #[test]
+    fn test_one_of_becomes_union() {
+        let types = generate(
+            r##"{
+                "definitions": {
+                    "circle": {
+                        "type": "object",
+                        "properties": { "radius": { "type": "number" } },
+                        "required": ["radius"]
+                    }
+                },
+                "oneOf": [
+                    { "$ref": "#/definitions/circle" },
+                    {
+                        "title": "rect",
+                        "type": "object",
+                        "properties": { "w": { "type": "number" }, "h": { "type": "number" } },
+                        "required": ["w", "h"]
+                    },
+                    { "type": "string" },
+                    { "type": "null" }
+                ]
+            }"##,
+        );
+        let shape = union(&types, "Root");
+        assert_eq!(
+            shape.variant_names(),
+            vec!["Circle", "Rect", "String", "Null"]
+        );
+        assert_eq!(shape.variants[0].fields, vec![named("Circle")]);
+        assert_eq!(shape.variants[1].fields, vec![named("RootRect")]);
+        assert_eq!(shape.variants[2].fields, vec![named("string")]);
+        assert!(shape.variants[3].is_simple());
+        assert_eq!(record(&types, "RootRect").fields.len(), 2);
+    }
+
+    #[test]
+    fn test_enums_and_all_of() {
+        let types = generate(
+            r##"{
+                "definitions": {
+                    "base": { "properties": { "id": { "type": "integer" } }, "required": ["id"] }
+                },
+                "allOf": [
+                    { "$ref": "#/definitions/base" },
+                    { "properties": { "level": { "enum": ["debug", "info", "info"] } } }
+                ]
+            }"##,
+        );
+        assert_eq!(
+            record(&types, "Root").fields,
+            vec![
+                ("id".to_string(), named("int")),
+                ("level".to_string(), named("Level option")),
+            ]
+        );
+        let level = union(&types, "Level");
+        assert!(level.is_simple_enum());
+        assert_eq!(level.variant_names(), vec!["Debug", "Info", "Info2"]);
+    }
+
+    #[test]
+    fn test_parse_members() {
+        let provider = JsonSchemaProvider::new();
+        let schema = provider
+            .resolve_schema(
+                r#"{ "properties": { "mode": { "enum": ["read-only"] } } }"#,
+                &ProviderParams::default(),
+            )
+            .unwrap();
+        let members = provider.generate_types(&schema, "Root").unwrap().members;
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].name, "parse");
+        assert_eq!(members[0].target, "Json.parseAs");
+        let shape: Value = serde_json::from_str(&members[0].args[0]).unwrap();
+        assert_eq!(shape["root"], "Root");
+        assert_eq!(
+            shape["types"]["Mode"]["union"][0],
+            serde_json::json!({ "name": "ReadOnly", "value": "read-only" })
+        );
+
+        // Without a root type there is nothing to parse into
+        let schema = provider
+            .resolve_schema(
+                r#"{ "$defs": { "Id": { "type": "object" } } }"#,
+                &ProviderParams::default(),
+            )
+            .unwrap();
+        assert!(provider
+            .generate_types(&schema, "Root")
+            .unwrap()
+            .members
+            .is_empty());
+    }
+
+    #[test]
+    fn test_invalid_schemas() {
+        let provider = JsonSchemaProvider::new();
+        let params = ProviderParams::default();
+        assert!(matches!(
+            provider.resolve_schema("{ not json", &params),
+            Err(ProviderError::ParseError(_))
+        ));
+        let schema = provider
+            .resolve_schema(r#"{"type": "string"}"#, &params)
+            .unwrap();
+        assert!(provider.generate_types(&schema, "Root").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! Built-in type providers
+//!
+//! A provider's source is either a path to a file, read relative to the
+//! working directory, or the schema itself written inline. Scripts read from
+//! a file have their paths made relative to the script's directory before
+//! they reach a provider.
+
+pub mod graphql;
+pub mod json_schema;
+pub mod protobuf;
+pub mod regex;
+pub mod sql;
+mod tokens;
+pub mod toml;
+
+pub use graphql::GraphqlProvider;
+pub use json_schema::JsonSchemaProvider;
+pub use protobuf::ProtobufProvider;
+pub use regex::RegexProvider;
+pub use sql::SqlProvider;
+pub use toml::TomlProvider;
+
+use crate::error::{ProviderError, ProviderResult};
+use crate::generator::NamingStrategy;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Read the file named by `source`, or take `source` itself as the content
+/// when no such file exists and `is_inline` accepts it
+pub(crate) fn read_source(
+    source: &str,
+    is_inline: impl Fn(&str) -> bool,
+) -> ProviderResult<String> {
+    if !source.contains('\n') && Path::new(source).is_file() {
+        return std::fs::read_to_string(source)
+            .map_err(|e| ProviderError::IoError(format!("{}: {}", source, e)));
+    }
+    if is_inline(source) {
+        return Ok(source.to_string());
+    }
+    Err(ProviderError::InvalidSource(format!(
+        "{} (no such file)",
+        source
+    )))
+}
+
+/// Names of the types generated so far, so that each one is unique
+#[derive(Default)]
+pub(crate) struct TypeNames {
+    taken: HashSet<String>,
+}
+
+impl TypeNames {
+    /// Claim `name` exactly
+    pub(crate) fn reserve(&mut self, name: &str) {
+        self.taken.insert(name.to_string());
+    }
+
+    /// Claim a PascalCase name for `key`, prefixed with `parent` (and then
+    /// numbered) if it is already in use
+    pub(crate) fn claim(&mut self, key: &str, parent: &str) -> String {
+        let name = pascal_case(key);
+        let candidates = [name.clone(), format!("{}{}", parent, name)];
+        let mut unique = candidates
+            .into_iter()
+            .find(|candidate| !self.taken.contains(candidate));
+        let mut n = 2;
+        while unique.is_none() {
+            let candidate = format!("{}{}", name, n);
+            if !self.taken.contains(&candidate) {
+                unique = Some(candidate);
+            }
+            n += 1;
+        }
+        let name = unique.unwrap();
+        self.taken.insert(name.clone());
+        name
+    }
+}
+
+pub(crate) fn pascal_case(name: &str) -> String {
+    let name = NamingStrategy::PascalCase.apply(name.trim());
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    match name.chars().next() {
+        None => "Type".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("T{}", name),
+        Some(_) => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_source() {
+        assert_eq!(read_source("a = 1", |s| s.contains('=')).unwrap(), "a = 1");
+        assert!(matches!(
+            read_source("missing.toml", |s| s.contains('=')),
+            Err(ProviderError::InvalidSource(_))
+        ));
+    }
+
+    #[test]
+    fn test_unique_names() {
+        let mut names = TypeNames::default();
+        names.reserve("Config");
+        assert_eq!(names.claim("tls", "Server"), "Tls");
+        assert_eq!(names.claim("tls", "Client"), "ClientTls");
+        assert_eq!(names.claim("tls", "Client"), "Tls2");
+        assert_eq!(names.claim("config", "Root"), "RootConfig");
+        assert_eq!(names.claim("2fa-codes", ""), "T2faCodes");
+    }
+}
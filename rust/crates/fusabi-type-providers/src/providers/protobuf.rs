@@ -0,0 +1,551 @@
+//! Protocol Buffers type provider
+//!
+//! ```fusabi
+//! type Proto = ProtobufProvider<"messages.proto">
+//! ```
+//!
+//! Each message becomes a record and each enum a simple union, in a module
+//! named after the declaration; types nested in a message go in a module
+//! named after the message. A `oneof` becomes a union with a case per
+//! field, held in an optional field named after the `oneof`. Repeated
+//! fields are lists, maps lists of key/value pairs, and message-typed and
+//! `optional` fields options. Services, options and extensions are ignored.
+
+use super::read_source;
+use super::tokens::{Token, Tokens};
+use crate::error::{ProviderError, ProviderResult};
+use crate::generator::{GeneratedModule, GeneratedTypes, NamingStrategy, TypeGenerator};
+use crate::provider::{ProviderParams, Schema, TypeProvider};
+use crate::types::{TypeDefinition, TypeExpr, VariantDef};
+use std::collections::HashSet;
+
+/// Generates records and unions from a .proto file
+pub struct ProtobufProvider {
+    generator: TypeGenerator,
+}
+
+impl ProtobufProvider {
+    pub fn new() -> Self {
+        Self {
+            generator: TypeGenerator::new(NamingStrategy::PreserveOriginal),
+        }
+    }
+}
+
+impl Default for ProtobufProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeProvider for ProtobufProvider {
+    fn name(&self) -> &str {
+        "ProtobufProvider"
+    }
+
+    fn resolve_schema(&self, source: &str, _params: &ProviderParams) -> ProviderResult<Schema> {
+        let text = read_source(source, |s| s.contains("message ") || s.contains("enum "))?;
+        parse(&text)?;
+        Ok(Schema::Custom(text))
+    }
+
+    fn generate_types(&self, schema: &Schema, namespace: &str) -> ProviderResult<GeneratedTypes> {
+        let definitions = match schema {
+            Schema::Custom(text) => parse(text)?,
+            _ => {
+                return Err(ProviderError::GenerationError(
+                    "ProtobufProvider expects a .proto file".to_string(),
+                ))
+            }
+        };
+        if definitions.is_empty() {
+            return Err(ProviderError::GenerationError(
+                "no messages or enums found".to_string(),
+            ));
+        }
+
+        let mut enums = HashSet::new();
+        collect_enums(&definitions, &mut enums);
+        let mut builder = Builder {
+            generator: &self.generator,
+            enums,
+            modules: Vec::new(),
+        };
+        builder.generate(&definitions, vec![namespace.to_string()]);
+
+        Ok(GeneratedTypes {
+            modules: builder.modules,
+            root_types: Vec::new(),
+            members: Vec::new(),
+        })
+    }
+}
+
+enum Definition {
+    Message(Message),
+    Enum(Enum),
+}
+
+struct Message {
+    name: String,
+    fields: Vec<Field>,
+    oneofs: Vec<Oneof>,
+    nested: Vec<Definition>,
+}
+
+struct Field {
+    name: String,
+    ty: FieldType,
+    label: Label,
+}
+
+enum FieldType {
+    Named(String),
+    Map(String, String),
+}
+
+#[derive(PartialEq)]
+enum Label {
+    None,
+    Optional,
+    Required,
+    Repeated,
+}
+
+struct Oneof {
+    name: String,
+    /// (field name, type)
+    cases: Vec<(String, String)>,
+}
+
+struct Enum {
+    name: String,
+    values: Vec<String>,
+}
+
+fn parse(text: &str) -> ProviderResult<Vec<Definition>> {
+    let mut tokens = Tokens::new(text, "//")?;
+    let mut definitions = Vec::new();
+    while !tokens.at_end() {
+        if tokens.eat(';') {
+            continue;
+        }
+        match tokens.ident()?.as_str() {
+            "syntax" | "edition" | "package" | "import" | "option" => tokens.skip_past(';')?,
+            "message" => definitions.push(Definition::Message(parse_message(&mut tokens)?)),
+            "enum" => definitions.push(Definition::Enum(parse_enum(&mut tokens)?)),
+            "service" | "extend" => {
+                tokens.skip_past('{')?;
+                tokens.skip_group('}')?;
+            }
+            other => {
+                return Err(ProviderError::ParseError(format!(
+                    "unexpected '{}' at top level",
+                    other
+                )))
+            }
+        }
+    }
+    Ok(definitions)
+}
+
+fn parse_message(tokens: &mut Tokens) -> ProviderResult<Message> {
+    let mut message = Message {
+        name: tokens.ident()?,
+        fields: Vec::new(),
+        oneofs: Vec::new(),
+        nested: Vec::new(),
+    };
+    tokens.expect('{')?;
+    while !tokens.eat('}') {
+        if tokens.at_end() {
+            return Err(tokens.unexpected("'}'"));
+        }
+        if tokens.eat(';') {
+            continue;
+        }
+        let word = tokens.ident()?;
+        match word.as_str() {
+            "message" => message
+                .nested
+                .push(Definition::Message(parse_message(tokens)?)),
+            "enum" => message.nested.push(Definition::Enum(parse_enum(tokens)?)),
+            "oneof" => message.oneofs.push(parse_oneof(tokens)?),
+            "option" | "reserved" | "extensions" => tokens.skip_past(';')?,
+            "extend" => {
+                tokens.skip_past('{')?;
+                tokens.skip_group('}')?;
+            }
+            "map" if tokens.at('<') => {
+                tokens.expect('<')?;
+                let key = tokens.ident()?;
+                tokens.expect(',')?;
+                let value = tokens.ident()?;
+                tokens.expect('>')?;
+                let name = tokens.ident()?;
+                tokens.skip_past(';')?;
+                message.fields.push(Field {
+                    name,
+                    ty: FieldType::Map(key, value),
+                    label: Label::None,
+                });
+            }
+            _ => {
+                let (label, ty) = match word.as_str() {
+                    "optional" => (Label::Optional, tokens.ident()?),
+                    "required" => (Label::Required, tokens.ident()?),
+                    "repeated" => (Label::Repeated, tokens.ident()?),
+                    _ => (Label::None, word),
+                };
+                let name = tokens.ident()?;
+                tokens.skip_past(';')?;
+                message.fields.push(Field {
+                    name,
+                    ty: FieldType::Named(ty),
+                    label,
+                });
+            }
+        }
+    }
+    Ok(message)
+}
+
+fn parse_oneof(tokens: &mut Tokens) -> ProviderResult<Oneof> {
+    let name = tokens.ident()?;
+    let mut cases = Vec::new();
+    tokens.expect('{')?;
+    while !tokens.eat('}') {
+        if tokens.eat(';') {
+            continue;
+        }
+        let ty = tokens.ident()?;
+        if ty == "option" {
+            tokens.skip_past(';')?;
+            continue;
+        }
+        let field = tokens.ident()?;
+        tokens.skip_past(';')?;
+        cases.push((field, ty));
+    }
+    Ok(Oneof { name, cases })
+}
+
+fn parse_enum(tokens: &mut Tokens) -> ProviderResult<Enum> {
+    let name = tokens.ident()?;
+    let mut values = Vec::new();
+    tokens.expect('{')?;
+    while !tokens.eat('}') {
+        match tokens.next() {
+            Some(Token::Punct(';')) => {}
+            Some(Token::Ident(word)) if word == "option" || word == "reserved" => {
+                tokens.skip_past(';')?
+            }
+            Some(Token::Ident(value)) => {
+                values.push(value);
+                tokens.skip_past(';')?;
+            }
+            _ => {
+                return Err(ProviderError::ParseError(format!(
+                    "invalid enum '{}'",
+                    name
+                )))
+            }
+        }
+    }
+    Ok(Enum { name, values })
+}
+
+fn collect_enums(definitions: &[Definition], enums: &mut HashSet<String>) {
+    for definition in definitions {
+        match definition {
+            Definition::Enum(e) => {
+                enums.insert(e.name.clone());
+            }
+            Definition::Message(message) => collect_enums(&message.nested, enums),
+        }
+    }
+}
+
+struct Builder<'a> {
+    generator: &'a TypeGenerator,
+    /// Enum names, whose fields always have a value
+    enums: HashSet<String>,
+    modules: Vec<GeneratedModule>,
+}
+
+impl Builder<'_> {
+    fn generate(&mut self, definitions: &[Definition], path: Vec<String>) {
+        for definition in definitions {
+            match definition {
+                Definition::Enum(e) => {
+                    let variants = enum_cases(&e.name, &e.values)
+                        .into_iter()
+                        .map(VariantDef::new_simple)
+                        .collect();
+                    let du = self.generator.make_du(&e.name, variants);
+                    self.add(&path, TypeDefinition::Du(du));
+                }
+                Definition::Message(message) => self.message(message, &path),
+            }
+        }
+    }
+
+    fn message(&mut self, message: &Message, path: &[String]) {
+        let mut nested_path = path.to_vec();
+        nested_path.push(message.name.clone());
+
+        let mut fields: Vec<(String, TypeExpr)> = message
+            .fields
+            .iter()
+            .map(|field| (field.name.clone(), self.field_type(field)))
+            .collect();
+
+        for oneof in &message.oneofs {
+            let name = super::pascal_case(&oneof.name);
+            let variants = oneof
+                .cases
+                .iter()
+                .map(|(field, ty)| {
+                    VariantDef::new(super::pascal_case(field), vec![scalar_or_named(ty)])
+                })
+                .collect();
+            let du = self.generator.make_du(&name, variants);
+            self.add(&nested_path, TypeDefinition::Du(du));
+            fields.push((
+                oneof.name.clone(),
+                TypeExpr::Named(format!("{} option", name)),
+            ));
+        }
+
+        self.generate(&message.nested, nested_path);
+        let record = self.generator.make_record(&message.name, fields);
+        self.add(path, TypeDefinition::Record(record));
+    }
+
+    fn field_type(&self, field: &Field) -> TypeExpr {
+        let ty = match &field.ty {
+            FieldType::Map(key, value) => {
+                return TypeExpr::Named(format!(
+                    "({} * {}) list",
+                    scalar_or_named(key),
+                    scalar_or_named(value)
+                ))
+            }
+            FieldType::Named(ty) => ty,
+        };
+        let base = scalar_or_named(ty);
+        let is_message = scalar(ty).is_none() && !self.enums.contains(type_name(ty));
+        match field.label {
+            Label::Repeated => TypeExpr::Named(format!("{} list", base)),
+            Label::Optional => TypeExpr::Named(format!("{} option", base)),
+            Label::None if is_message => TypeExpr::Named(format!("{} option", base)),
+            Label::None | Label::Required => base,
+        }
+    }
+
+    fn add(&mut self, path: &[String], ty: TypeDefinition) {
+        match self.modules.iter_mut().find(|module| module.path == path) {
+            Some(module) => module.types.push(ty),
+            None => self
+                .modules
+                .push(GeneratedModule::new(path.to_vec()).with_type(ty)),
+        }
+    }
+}
+
+fn scalar(ty: &str) -> Option<&'static str> {
+    Some(match ty {
+        "double" | "float" => "float",
+        "int32" | "int64" | "uint32" | "uint64" | "sint32" | "sint64" | "fixed32" | "fixed64"
+        | "sfixed32" | "sfixed64" => "int",
+        "bool" => "bool",
+        "string" | "bytes" => "string",
+        _ => return None,
+    })
+}
+
+/// The simple name of a possibly qualified type (`.pkg.Outer.Inner`)
+fn type_name(ty: &str) -> &str {
+    ty.rsplit('.').next().unwrap_or(ty)
+}
+
+fn scalar_or_named(ty: &str) -> TypeExpr {
+    TypeExpr::Named(scalar(ty).unwrap_or_else(|| type_name(ty)).to_string())
+}
+
+/// Union case names for enum values: `STATUS_ACTIVE` in `Status` is `Active`
+fn enum_cases(name: &str, values: &[String]) -> Vec<String> {
+    let mut prefix = String::new();
+    for (i, c) in super::pascal_case(name).chars().enumerate() {
+        if i > 0 && c.is_uppercase() {
+            prefix.push('_');
+        }
+        prefix.push(c.to_ascii_uppercase());
+    }
+    prefix.push('_');
+    let strip = values
+        .iter()
+        .all(|value| value.starts_with(&prefix) && value.len() > prefix.len());
+    values
+        .iter()
+        .map(|value| {
+            let value = if strip { &value[prefix.len()..] } else { value };
+            super::pascal_case(&value.to_ascii_lowercase())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DuDef, RecordDef};
+
+    const PROTO: &str = r#"
+syntax = "proto3";
+package shop.v1;
+
+import "google/protobuf/timestamp.proto";
+
+// A customer order
+message Order {
+  string id = 1;
+  repeated LineItem items = 2 [packed = true];
+  map<string, int64> totals = 3;
+  Status status = 4;
+  google.protobuf.Timestamp placed_at = 5;
+  optional string note = 6;
+
+  message LineItem {
+    string sku = 1;
+    uint32 quantity = 2;
+  }
+
+  oneof payment {
+    string card_token = 7;
+    Voucher voucher = 8;
+  }
+
+  reserved 9, 10;
+}
+
+message Voucher { string code = 1; }
+
+enum Status {
+  STATUS_UNSPECIFIED = 0;
+  STATUS_PLACED = 1;
+  STATUS_SHIPPED = 2 [deprecated = true];
+}
+
+service Orders {
+  rpc Place (Order) returns (Order);
+}
+"#;
+
+    fn generate(source: &str) -> ProviderResult<GeneratedTypes> {
+        let provider = ProtobufProvider::new();
+        let schema = provider.resolve_schema(source, &ProviderParams::default())?;
+        provider.generate_types(&schema, "Proto")
+    }
+
+    fn module<'t>(types: &'t GeneratedTypes, path: &[&str]) -> &'t GeneratedModule {
+        types
+            .modules
+            .iter()
+            .find(|module| module.path == path)
+            .unwrap_or_else(|| panic!("no module {:?}", path))
+    }
+
+    fn record<'t>(module: &'t GeneratedModule, name: &str) -> &'t RecordDef {
+        module
+            .types
+            .iter()
+            .find_map(|ty| match ty {
+                TypeDefinition::Record(r) if r.name == name => Some(r),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("no record {}", name))
+    }
+
+    fn union<'t>(module: &'t GeneratedModule, name: &str) -> &'t DuDef {
+        module
+            .types
+            .iter()
+            .find_map(|ty| match ty {
+                TypeDefinition::Du(du) if du.name == name => Some(du),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("no union {}", name))
+    }
+
+    #[test]
+    fn test_messages_become_records() {
+        let types = generate(PROTO).unwrap();
+        let order = record(module(&types, &["Proto"]), "Order");
+        let fields: Vec<(&str, String)> = order
+            .fields
+            .iter()
+            .map(|(name, ty)| (name.as_str(), ty.to_string()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("id", "string".to_string()),
+                ("items", "LineItem list".to_string()),
+                ("totals", "(string * int) list".to_string()),
+                ("status", "Status".to_string()),
+                ("placed_at", "Timestamp option".to_string()),
+                ("note", "string option".to_string()),
+                ("payment", "Payment option".to_string()),
+            ]
+        );
+        assert_eq!(
+            record(module(&types, &["Proto"]), "Voucher").fields.len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_nested_types_and_oneofs() {
+        let types = generate(PROTO).unwrap();
+        let nested = module(&types, &["Proto", "Order"]);
+        assert_eq!(record(nested, "LineItem").fields.len(), 2);
+
+        let payment = union(nested, "Payment");
+        assert_eq!(payment.variant_names(), vec!["CardToken", "Voucher"]);
+        assert_eq!(
+            payment.variants[1].fields,
+            vec![TypeExpr::Named("Voucher".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_enums_become_unions() {
+        let types = generate(PROTO).unwrap();
+        let status = union(module(&types, &["Proto"]), "Status");
+        assert!(status.is_simple_enum());
+        assert_eq!(
+            status.variant_names(),
+            vec!["Unspecified", "Placed", "Shipped"]
+        );
+
+        let types = generate("enum Color { RED = 0; DARK_BLUE = 1; }").unwrap();
+        let color = union(module(&types, &["Proto"]), "Color");
+        assert_eq!(color.variant_names(), vec!["Red", "DarkBlue"]);
+    }
+
+    #[test]
+    fn test_invalid_protos() {
+        assert!(matches!(
+            generate("message Broken { string id = 1;"),
+            Err(ProviderError::ParseError(_))
+        ));
+        assert!(matches!(
+            generate("syntax = \"proto3\"; message {"),
+            Err(ProviderError::ParseError(_))
+        ));
+        assert!(matches!(
+            generate("messages.proto"),
+            Err(ProviderError::InvalidSource(_))
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+//! Regex type provider
+//!
+//! ```fusabi
+//! type LogEntry = RegexProvider<"(?P<ts>\d+) (?P<level>\w+) (?P<msg>.*)">
+//! ```
+//!
+//! The source is the pattern itself. Each named capture group becomes a
+//! string field of a record named after the declaration, and the alias
+//! gains erased members backed by the `Regex` stdlib module:
+//!
+//! - `LogEntry.tryMatch : string -> LogEntry option`
+//! - `LogEntry.isMatch : string -> bool`
+//!
+//! A group that takes no part in the match is the empty string.
+
+use crate::error::{ProviderError, ProviderResult};
+use crate::generator::{GeneratedTypes, NamingStrategy, TypeGenerator};
+use crate::provider::{ProviderParams, Schema, TypeProvider};
+use crate::types::{ProvidedMember, TypeDefinition, TypeExpr};
+use ::regex::Regex;
+
+/// Generates a record of named capture groups from a regex pattern
+pub struct RegexProvider {
+    generator: TypeGenerator,
+}
+
+impl RegexProvider {
+    pub fn new() -> Self {
+        Self {
+            generator: TypeGenerator::new(NamingStrategy::PreserveOriginal),
+        }
+    }
+}
+
+impl Default for RegexProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeProvider for RegexProvider {
+    fn name(&self) -> &str {
+        "RegexProvider"
+    }
+
+    fn resolve_schema(&self, source: &str, _params: &ProviderParams) -> ProviderResult<Schema> {
+        compile(source)?;
+        Ok(Schema::Custom(source.to_string()))
+    }
+
+    fn generate_types(&self, schema: &Schema, namespace: &str) -> ProviderResult<GeneratedTypes> {
+        let pattern = match schema {
+            Schema::Custom(pattern) => pattern,
+            _ => {
+                return Err(ProviderError::GenerationError(
+                    "RegexProvider expects a regex pattern".to_string(),
+                ))
+            }
+        };
+        let regex = compile(pattern)?;
+
+        let mut fields = Vec::new();
+        for name in regex.capture_names().flatten() {
+            let is_ident = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+            if !is_ident {
+                return Err(ProviderError::GenerationError(format!(
+                    "capture group '{}' is not a valid field name",
+                    name
+                )));
+            }
+            fields.push((name.to_string(), TypeExpr::Named("string".to_string())));
+        }
+        if fields.is_empty() {
+            return Err(ProviderError::GenerationError(
+                "pattern has no named capture groups".to_string(),
+            ));
+        }
+
+        let record = self.generator.make_record(namespace, fields);
+        let member = |name: &str, result: String, target: &str| {
+            ProvidedMember::new(
+                name.to_string(),
+                TypeExpr::Function(
+                    Box::new(TypeExpr::Named("string".to_string())),
+                    Box::new(TypeExpr::Named(result)),
+                ),
+                target.to_string(),
+                vec![pattern.clone()],
+            )
+        };
+
+        Ok(GeneratedTypes::new()
+            .with_type(TypeDefinition::Record(record))
+            .with_member(member(
+                "tryMatch",
+                format!("{} option", namespace),
+                "Regex.namedCaptures",
+            ))
+            .with_member(member("isMatch", "bool".to_string(), "Regex.isMatch")))
+    }
+}
+
+fn compile(pattern: &str) -> ProviderResult<Regex> {
+    Regex::new(pattern).map_err(|e| ProviderError::ParseError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(pattern: &str) -> ProviderResult<GeneratedTypes> {
+        let provider = RegexProvider::new();
+        let schema = provider.resolve_schema(pattern, &ProviderParams::default())?;
+        provider.generate_types(&schema, "LogEntry")
+    }
+
+    #[test]
+    fn test_named_groups() {
+        let types = generate(r"(?P<ts>\d+) (?P<level>\w+) (\S+) (?<msg>.*)").unwrap();
+        assert_eq!(
+            types.root_types[0].to_string(),
+            "type LogEntry = { ts: string; level: string; msg: string }"
+        );
+
+        let try_match = &types.members[0];
+        assert_eq!(try_match.name, "tryMatch");
+        assert_eq!(
+            try_match.member_type.to_string(),
+            "string -> LogEntry option"
+        );
+        assert_eq!(try_match.target, "Regex.namedCaptures");
+        assert_eq!(
+            try_match.args,
+            vec![r"(?P<ts>\d+) (?P<level>\w+) (\S+) (?<msg>.*)"]
+        );
+        assert_eq!(types.members[1].member_type.to_string(), "string -> bool");
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(matches!(
+            generate("(?P<ts>\\d+"),
+            Err(ProviderError::ParseError(_))
+        ));
+        assert!(matches!(
+            generate(r"\d+ \w+"),
+            Err(ProviderError::GenerationError(_))
+        ));
+    }
+}
@@ -0,0 +1,458 @@
+//! SQL type provider
+//!
+//! ```fusabi
+//! type DbSchema = SqlProvider<"schema.sql">
+//! ```
+//!
+//! Reads the `CREATE TABLE` statements of a DDL script and generates a
+//! record per table, in a module named after the declaration, with a field
+//! per column. Nullable columns are options. PostgreSQL
+//! `CREATE TYPE ... AS ENUM` types become simple unions; other statements
+//! are ignored.
+
+use super::{pascal_case, read_source, TypeNames};
+use crate::error::{ProviderError, ProviderResult};
+use crate::generator::{GeneratedModule, GeneratedTypes, NamingStrategy, TypeGenerator};
+use crate::provider::{ProviderParams, Schema, TypeProvider};
+use crate::types::{TypeDefinition, TypeExpr, VariantDef};
+use std::collections::HashMap;
+
+/// Generates table records from SQL DDL
+pub struct SqlProvider {
+    generator: TypeGenerator,
+}
+
+impl SqlProvider {
+    pub fn new() -> Self {
+        Self {
+            generator: TypeGenerator::new(NamingStrategy::PreserveOriginal),
+        }
+    }
+}
+
+impl Default for SqlProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeProvider for SqlProvider {
+    fn name(&self) -> &str {
+        "SqlProvider"
+    }
+
+    fn resolve_schema(&self, source: &str, _params: &ProviderParams) -> ProviderResult<Schema> {
+        let text = read_source(source, |s| s.to_ascii_uppercase().contains("CREATE "))?;
+        Ok(Schema::Custom(text))
+    }
+
+    fn generate_types(&self, schema: &Schema, namespace: &str) -> ProviderResult<GeneratedTypes> {
+        let ddl = match schema {
+            Schema::Custom(ddl) => ddl,
+            _ => {
+                return Err(ProviderError::GenerationError(
+                    "SqlProvider expects SQL DDL".to_string(),
+                ))
+            }
+        };
+
+        let mut names = TypeNames::default();
+        let mut enums = HashMap::new();
+        let mut module = GeneratedModule::new(vec![namespace.to_string()]);
+
+        for statement in statements(&strip_comments(ddl)) {
+            let words: Vec<String> = statement
+                .split_whitespace()
+                .take(3)
+                .map(|w| w.to_ascii_uppercase())
+                .collect();
+            match words.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                ["CREATE", "TYPE", ..] => {
+                    if let Some((name, values)) = parse_enum(&statement)? {
+                        let variants = values
+                            .iter()
+                            .map(|value| VariantDef::new_simple(pascal_case(value)))
+                            .collect();
+                        let du_name = names.claim(&name, namespace);
+                        enums.insert(name.to_ascii_lowercase(), du_name.clone());
+                        let du = self.generator.make_du(&du_name, variants);
+                        module = module.with_type(TypeDefinition::Du(du));
+                    }
+                }
+                ["CREATE", "TABLE", ..] | ["CREATE", _, "TABLE"] => {
+                    let (name, columns) = parse_table(&statement)?;
+                    names.reserve(&name);
+                    let fields = columns
+                        .into_iter()
+                        .map(|column| {
+                            let base = sql_type(&column.sql_type, &enums);
+                            let ty = if column.nullable {
+                                TypeExpr::Named(format!("{} option", base))
+                            } else {
+                                TypeExpr::Named(base)
+                            };
+                            (column.name, ty)
+                        })
+                        .collect();
+                    let record = self.generator.make_record(&name, fields);
+                    module = module.with_type(TypeDefinition::Record(record));
+                }
+                _ => {}
+            }
+        }
+
+        if module.types.is_empty() {
+            return Err(ProviderError::GenerationError(
+                "no CREATE TABLE statements found".to_string(),
+            ));
+        }
+        Ok(GeneratedTypes::new().with_module(module))
+    }
+}
+
+struct Column {
+    name: String,
+    sql_type: String,
+    nullable: bool,
+}
+
+/// Map a column type to a Fusabi type
+fn sql_type(column_type: &str, enums: &HashMap<String, String>) -> String {
+    let lower = column_type.to_ascii_lowercase();
+    let base = lower.split(['(', ' ']).next().unwrap_or_default();
+    if let Some(du) = enums.get(unquote(base).as_str()) {
+        return du.clone();
+    }
+    if lower.ends_with("[]") {
+        let element = sql_type(lower.trim_end_matches("[]"), enums);
+        return format!("{} list", element);
+    }
+    match base {
+        "int" | "integer" | "smallint" | "bigint" | "tinyint" | "mediumint" | "int2" | "int4"
+        | "int8" | "serial" | "smallserial" | "bigserial" => "int",
+        "real" | "float" | "float4" | "float8" | "double" | "numeric" | "decimal" | "money" => {
+            "float"
+        }
+        "bool" | "boolean" | "bit" => "bool",
+        _ => "string",
+    }
+    .to_string()
+}
+
+/// Remove `--` and `/* */` comments
+fn strip_comments(sql: &str) -> String {
+    let mut out = String::new();
+    let mut chars = sql.chars().peekable();
+    let mut quote = None;
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) => {
+                if c == q {
+                    quote = None;
+                }
+                out.push(c);
+            }
+            (None, '\'' | '"' | '`') => {
+                quote = Some(c);
+                out.push(c);
+            }
+            (None, '-') if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            (None, '/') if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+                out.push(' ');
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Split on `separator` outside of quotes and parentheses
+fn split_top_level(text: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, c) if c == separator && depth == 0 => {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    parts.push(current);
+    parts
+        .into_iter()
+        .map(|part| part.trim().to_string())
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+fn statements(sql: &str) -> Vec<String> {
+    split_top_level(sql, ';')
+}
+
+/// The text between the first `(` and its matching `)`, and what precedes it
+fn parenthesized(statement: &str) -> ProviderResult<(&str, &str)> {
+    let open = statement.find('(').ok_or_else(|| syntax_error(statement))?;
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in statement[open..].char_indices() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((&statement[..open], &statement[open + 1..open + i]));
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(syntax_error(statement))
+}
+
+fn syntax_error(statement: &str) -> ProviderError {
+    let start: String = statement.chars().take(40).collect();
+    ProviderError::ParseError(format!("cannot read statement '{}'", start))
+}
+
+/// The unquoted last part of a possibly schema-qualified name
+fn object_name(name: &str) -> String {
+    unquote(name.rsplit('.').next().unwrap_or(name))
+}
+
+fn unquote(name: &str) -> String {
+    name.trim_matches(|c| matches!(c, '"' | '`' | '[' | ']'))
+        .to_string()
+}
+
+/// `CREATE TABLE [IF NOT EXISTS] name (columns and constraints)`
+fn parse_table(statement: &str) -> ProviderResult<(String, Vec<Column>)> {
+    let (head, body) = parenthesized(statement)?;
+    let name = head
+        .split_whitespace()
+        .last()
+        .map(object_name)
+        .ok_or_else(|| syntax_error(statement))?;
+
+    let mut columns: Vec<Column> = Vec::new();
+    let mut primary_key = Vec::new();
+    for definition in split_top_level(body, ',') {
+        let upper = definition.to_ascii_uppercase();
+        let first = upper.split_whitespace().next().unwrap_or_default();
+        if matches!(
+            first,
+            "CONSTRAINT" | "PRIMARY" | "FOREIGN" | "UNIQUE" | "CHECK" | "KEY" | "INDEX" | "EXCLUDE"
+        ) {
+            if let Some(at) = upper.find("PRIMARY KEY") {
+                let (_, keys) = parenthesized(&definition[at..])?;
+                primary_key.extend(split_top_level(keys, ',').iter().map(|k| unquote(k)));
+            }
+            continue;
+        }
+
+        let mut words = definition.split_whitespace();
+        let column = words.next().map(unquote).unwrap_or_default();
+        let mut sql_type = String::new();
+        for word in words {
+            let keyword = word.to_ascii_uppercase();
+            if matches!(
+                keyword.as_str(),
+                "NOT"
+                    | "NULL"
+                    | "PRIMARY"
+                    | "DEFAULT"
+                    | "REFERENCES"
+                    | "UNIQUE"
+                    | "CHECK"
+                    | "CONSTRAINT"
+                    | "GENERATED"
+                    | "COLLATE"
+                    | "AUTO_INCREMENT"
+                    | "AUTOINCREMENT"
+            ) {
+                break;
+            }
+            if !sql_type.is_empty() {
+                sql_type.push(' ');
+            }
+            sql_type.push_str(word);
+        }
+        let nullable = !upper.contains("NOT NULL") && !upper.contains("PRIMARY KEY");
+        columns.push(Column {
+            name: column,
+            sql_type,
+            nullable,
+        });
+    }
+
+    for column in &mut columns {
+        if primary_key
+            .iter()
+            .any(|key| key.eq_ignore_ascii_case(&column.name))
+        {
+            column.nullable = false;
+        }
+    }
+    Ok((name, columns))
+}
+
+/// `CREATE TYPE name AS ENUM ('a', 'b')`; other types are skipped
+fn parse_enum(statement: &str) -> ProviderResult<Option<(String, Vec<String>)>> {
+    let words: Vec<&str> = statement.split_whitespace().collect();
+    let is_enum = words.len() > 4
+        && words[3].eq_ignore_ascii_case("AS")
+        && words[4].to_ascii_uppercase().starts_with("ENUM");
+    if !is_enum {
+        return Ok(None);
+    }
+    let (_, values) = parenthesized(statement)?;
+    let values = split_top_level(values, ',')
+        .iter()
+        .map(|value| value.trim_matches('\'').to_string())
+        .collect();
+    Ok(Some((object_name(words[2]), values)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DuDef, RecordDef};
+
+    const SCHEMA: &str = r#"
+-- Accounts
+CREATE TYPE mood AS ENUM ('happy', 'not-sure');
+
+CREATE TABLE IF NOT EXISTS public.users (
+    id SERIAL PRIMARY KEY,
+    email VARCHAR(255) NOT NULL UNIQUE,
+    name TEXT, /* display name, optional */
+    score NUMERIC(10, 2) DEFAULT 0 NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT true,
+    current_mood mood,
+    tags text[] NOT NULL
+);
+
+CREATE TABLE "order_items" (
+    order_id INTEGER,
+    product_id INTEGER REFERENCES products (id),
+    quantity INT NOT NULL CHECK (quantity > 0),
+    PRIMARY KEY (order_id, product_id)
+);
+
+CREATE INDEX users_email ON users (email);
+"#;
+
+    fn generate(ddl: &str) -> ProviderResult<GeneratedTypes> {
+        let provider = SqlProvider::new();
+        let schema = provider.resolve_schema(ddl, &ProviderParams::default())?;
+        provider.generate_types(&schema, "Db")
+    }
+
+    fn fields(record: &RecordDef) -> Vec<(&str, String)> {
+        record
+            .fields
+            .iter()
+            .map(|(name, ty)| (name.as_str(), ty.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_tables_become_records() {
+        let generated = generate(SCHEMA).unwrap();
+        assert_eq!(generated.modules.len(), 1);
+        let module = &generated.modules[0];
+        assert_eq!(module.path, vec!["Db"]);
+
+        let records: Vec<&RecordDef> = module
+            .types
+            .iter()
+            .filter_map(|ty| match ty {
+                TypeDefinition::Record(r) => Some(r),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].name, "users");
+        assert_eq!(
+            fields(records[0]),
+            vec![
+                ("id", "int".to_string()),
+                ("email", "string".to_string()),
+                ("name", "string option".to_string()),
+                ("score", "float".to_string()),
+                ("active", "bool".to_string()),
+                ("current_mood", "Mood option".to_string()),
+                ("tags", "string list".to_string()),
+            ]
+        );
+
+        assert_eq!(records[1].name, "order_items");
+        assert_eq!(
+            fields(records[1]),
+            vec![
+                ("order_id", "int".to_string()),
+                ("product_id", "int".to_string()),
+                ("quantity", "int".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enum_types() {
+        let generated = generate(SCHEMA).unwrap();
+        let du: &DuDef = generated.modules[0]
+            .types
+            .iter()
+            .find_map(|ty| match ty {
+                TypeDefinition::Du(du) => Some(du),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(du.name, "Mood");
+        assert_eq!(du.variant_names(), vec!["Happy", "NotSure"]);
+    }
+
+    #[test]
+    fn test_invalid_ddl() {
+        assert!(matches!(
+            generate("CREATE INDEX x ON y (z)"),
+            Err(ProviderError::GenerationError(_))
+        ));
+        assert!(matches!(
+            generate("CREATE TABLE broken (id int"),
+            Err(ProviderError::ParseError(_))
+        ));
+        assert!(matches!(
+            generate("schema.sql"),
+            Err(ProviderError::InvalidSource(_))
+        ));
+    }
+}
@@ -0,0 +1,235 @@
+//! Tokenizer shared by the schema language providers
+
+use crate::error::{ProviderError, ProviderResult};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    /// A name, possibly dotted (`google.protobuf.Timestamp`)
+    Ident(String),
+    Number(String),
+    Str(String),
+    Punct(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(s) | Token::Number(s) => write!(f, "{}", s),
+            Token::Str(s) => write!(f, "{:?}", s),
+            Token::Punct(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+/// A token stream with the lookahead helpers the parsers need
+pub(crate) struct Tokens {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Tokens {
+    /// Tokenize `text`, skipping comments introduced by `line_comment`;
+    /// `//` languages also have `/* */` block comments
+    pub(crate) fn new(text: &str, line_comment: &str) -> ProviderResult<Self> {
+        let mut tokens = Vec::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            if c.is_whitespace() || c == ',' && line_comment == "#" {
+                // Commas are insignificant in GraphQL
+                rest = &rest[c.len_utf8()..];
+            } else if rest.starts_with(line_comment) {
+                rest = rest.find('\n').map_or("", |end| &rest[end..]);
+            } else if line_comment == "//" && rest.starts_with("/*") {
+                let end = rest[2..]
+                    .find("*/")
+                    .ok_or_else(|| ProviderError::ParseError("unterminated comment".into()))?;
+                rest = &rest[end + 4..];
+            } else if rest.starts_with("\"\"\"") {
+                let end = rest[3..]
+                    .find("\"\"\"")
+                    .ok_or_else(|| ProviderError::ParseError("unterminated string".into()))?;
+                tokens.push(Token::Str(rest[3..end + 3].to_string()));
+                rest = &rest[end + 6..];
+            } else if c == '"' || c == '\'' {
+                let mut escaped = false;
+                let end = rest[1..]
+                    .char_indices()
+                    .find(|&(_, ch)| {
+                        let end = ch == c && !escaped;
+                        escaped = ch == '\\' && !escaped;
+                        end
+                    })
+                    .map(|(i, _)| i + 1)
+                    .ok_or_else(|| ProviderError::ParseError("unterminated string".into()))?;
+                tokens.push(Token::Str(rest[1..end].to_string()));
+                rest = &rest[end + 1..];
+            } else if c.is_alphabetic() || c == '_' || c == '.' && !rest.starts_with("...") {
+                let end = rest
+                    .find(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '.'))
+                    .unwrap_or(rest.len());
+                tokens.push(Token::Ident(rest[..end].to_string()));
+                rest = &rest[end..];
+            } else if c.is_ascii_digit()
+                || c == '-' && rest[1..].starts_with(|d: char| d.is_ascii_digit())
+            {
+                let end = rest[1..]
+                    .find(|ch: char| !(ch.is_alphanumeric() || matches!(ch, '.' | '-' | '+')))
+                    .map_or(rest.len(), |i| i + 1);
+                tokens.push(Token::Number(rest[..end].to_string()));
+                rest = &rest[end..];
+            } else {
+                tokens.push(Token::Punct(c));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        Ok(Tokens { tokens, pos: 0 })
+    }
+
+    pub(crate) fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    pub(crate) fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    /// Whether the next token is the punctuation `c`
+    pub(crate) fn at(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    /// Whether the next token is the word `word`
+    pub(crate) fn at_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(w)) if w == word)
+    }
+
+    /// Consume the punctuation `c` if it is next
+    pub(crate) fn eat(&mut self, c: char) -> bool {
+        let at = self.at(c);
+        if at {
+            self.pos += 1;
+        }
+        at
+    }
+
+    /// Consume the word `word` if it is next
+    pub(crate) fn eat_word(&mut self, word: &str) -> bool {
+        let at = self.at_word(word);
+        if at {
+            self.pos += 1;
+        }
+        at
+    }
+
+    pub(crate) fn expect(&mut self, c: char) -> ProviderResult<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", c)))
+        }
+    }
+
+    pub(crate) fn ident(&mut self) -> ProviderResult<String> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    /// Skip past the closing bracket matching the one just consumed
+    pub(crate) fn skip_group(&mut self, close: char) -> ProviderResult<()> {
+        let mut depth = 1;
+        while let Some(token) = self.next() {
+            match token {
+                Token::Punct('{' | '(' | '[' | '<') => depth += 1,
+                Token::Punct('}' | ')' | ']' | '>') => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+        Err(ProviderError::ParseError(format!("missing '{}'", close)))
+    }
+
+    /// Skip past the next `c` outside of brackets
+    pub(crate) fn skip_past(&mut self, c: char) -> ProviderResult<()> {
+        while let Some(token) = self.next() {
+            match token {
+                Token::Punct(p) if p == c => return Ok(()),
+                Token::Punct('{') => self.skip_group('}')?,
+                Token::Punct('(') => self.skip_group(')')?,
+                Token::Punct('[') => self.skip_group(']')?,
+                _ => {}
+            }
+        }
+        Err(ProviderError::ParseError(format!("missing '{}'", c)))
+    }
+
+    pub(crate) fn unexpected(&self, expected: &str) -> ProviderError {
+        match self.peek() {
+            Some(token) => {
+                ProviderError::ParseError(format!("expected {}, found '{}'", expected, token))
+            }
+            None => ProviderError::ParseError(format!("expected {}, found end of input", expected)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str, comment: &str) -> Vec<Token> {
+        let mut tokens = Tokens::new(text, comment).unwrap();
+        std::iter::from_fn(|| tokens.next()).collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokens("foo.Bar x = -1; // note\n/* block */ \"s\\\"t\"", "//"),
+            vec![
+                Token::Ident("foo.Bar".into()),
+                Token::Ident("x".into()),
+                Token::Punct('='),
+                Token::Number("-1".into()),
+                Token::Punct(';'),
+                Token::Str("s\\\"t".into()),
+            ]
+        );
+        assert_eq!(
+            tokens("\"\"\"doc\"\"\" a, b! # comment", "#"),
+            vec![
+                Token::Str("doc".into()),
+                Token::Ident("a".into()),
+                Token::Ident("b".into()),
+                Token::Punct('!'),
+            ]
+        );
+        assert!(Tokens::new("\"open", "#").is_err());
+    }
+
+    #[test]
+    fn test_skipping() {
+        let mut tokens = Tokens::new("[deprecated = true, (x) = { a: 1 }]; next", "//").unwrap();
+        tokens.skip_past(';').unwrap();
+        assert!(tokens.at_word("next"));
+
+        let mut tokens = Tokens::new("{ a { b } c } d", "#").unwrap();
+        tokens.expect('{').unwrap();
+        tokens.skip_group('}').unwrap();
+        assert_eq!(tokens.ident().unwrap(), "d");
+        assert!(tokens.at_end());
+    }
+}
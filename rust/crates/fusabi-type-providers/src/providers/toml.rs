@@ -0,0 +1,285 @@
+//! TOML type provider
+//!
+//! ```fusabi
+//! type Config = TomlProvider<"config.toml">
+//! ```
+//!
+//! The source is a sample document: its top-level table becomes a record
+//! named after the declaration and each nested table a record of its own.
+//! Arrays become lists of their element type; a key missing from some
+//! entries of an array of tables is an option. Dates and times are strings.
+//!
+//! The alias also gets `Config.parse : string -> Result<Config, string>`,
+//! which checks a document against the generated records, and
+//! `Config.serialize : Config -> string`.
+
+use super::{read_source, TypeNames};
+use crate::error::{ProviderError, ProviderResult};
+use crate::generator::{GeneratedTypes, NamingStrategy, TypeGenerator};
+use crate::provider::{ProviderParams, Schema, TypeProvider};
+use crate::shape::TypeShape;
+use crate::types::{TypeDefinition, TypeExpr};
+use ::toml::{Table, Value};
+
+/// Generates records from a sample TOML document
+pub struct TomlProvider {
+    generator: TypeGenerator,
+}
+
+impl TomlProvider {
+    pub fn new() -> Self {
+        Self {
+            generator: TypeGenerator::new(NamingStrategy::PreserveOriginal),
+        }
+    }
+}
+
+impl Default for TomlProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeProvider for TomlProvider {
+    fn name(&self) -> &str {
+        "TomlProvider"
+    }
+
+    fn resolve_schema(&self, source: &str, _params: &ProviderParams) -> ProviderResult<Schema> {
+        let text = read_source(source, |s| {
+            s.contains('=') || s.trim_start().starts_with('[')
+        })?;
+        parse(&text)?;
+        Ok(Schema::Custom(text))
+    }
+
+    fn generate_types(&self, schema: &Schema, namespace: &str) -> ProviderResult<GeneratedTypes> {
+        let table = match schema {
+            Schema::Custom(text) => parse(text)?,
+            _ => {
+                return Err(ProviderError::GenerationError(
+                    "TomlProvider expects a TOML document".to_string(),
+                ))
+            }
+        };
+
+        let mut builder = Builder {
+            generator: &self.generator,
+            names: TypeNames::default(),
+            types: Vec::new(),
+        };
+        builder.names.reserve(namespace);
+        builder.define(namespace, &[&table])?;
+
+        let mut generated = GeneratedTypes {
+            modules: Vec::new(),
+            root_types: builder.types,
+            members: Vec::new(),
+        };
+        generated.members = TypeShape::new(&generated, namespace).members("Toml");
+        Ok(generated)
+    }
+}
+
+fn parse(text: &str) -> ProviderResult<Table> {
+    text.parse::<Table>()
+        .map_err(|e| ProviderError::ParseError(e.to_string()))
+}
+
+struct Builder<'a> {
+    generator: &'a TypeGenerator,
+    names: TypeNames,
+    types: Vec<TypeDefinition>,
+}
+
+impl Builder<'_> {
+    /// Define the record `name` covering the keys of all `tables`
+    fn define(&mut self, name: &str, tables: &[&Table]) -> ProviderResult<()> {
+        let mut keys: Vec<&str> = Vec::new();
+        for table in tables {
+            for key in table.keys() {
+                if !keys.contains(&key.as_str()) {
+                    keys.push(key);
+                }
+            }
+        }
+
+        let mut fields = Vec::new();
+        for key in keys {
+            let values: Vec<&Value> = tables.iter().filter_map(|table| table.get(key)).collect();
+            let ty = self.type_of(key, name, &values)?;
+            let ty = if values.len() < tables.len() {
+                TypeExpr::Named(format!("{} option", ty))
+            } else {
+                ty
+            };
+            fields.push((key.to_string(), ty));
+        }
+
+        let record = self.generator.make_record(name, fields);
+        self.types.push(TypeDefinition::Record(record));
+        Ok(())
+    }
+
+    /// The type of `key`, given every value it has in the sample
+    fn type_of(&mut self, key: &str, parent: &str, values: &[&Value]) -> ProviderResult<TypeExpr> {
+        let first = values[0];
+        if values
+            .iter()
+            .any(|value| value.type_str() != first.type_str())
+        {
+            return Err(ProviderError::GenerationError(format!(
+                "'{}' holds values of different types",
+                key
+            )));
+        }
+
+        let named = |name: &str| TypeExpr::Named(name.to_string());
+        Ok(match first {
+            Value::String(_) | Value::Datetime(_) => named("string"),
+            Value::Integer(_) => named("int"),
+            Value::Float(_) => named("float"),
+            Value::Boolean(_) => named("bool"),
+            Value::Table(_) => {
+                let tables: Vec<&Table> = values.iter().filter_map(|v| v.as_table()).collect();
+                let name = self.names.claim(key, parent);
+                self.define(&name, &tables)?;
+                TypeExpr::Named(name)
+            }
+            Value::Array(_) => {
+                let items: Vec<&Value> = values
+                    .iter()
+                    .filter_map(|v| v.as_array())
+                    .flatten()
+                    .collect();
+                if items.is_empty() {
+                    return Err(ProviderError::GenerationError(format!(
+                        "cannot infer the element type of '{}' from an empty array",
+                        key
+                    )));
+                }
+                let item = self.type_of(key, parent, &items)?;
+                TypeExpr::Named(format!("{} list", item))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RecordDef;
+
+    fn generate(source: &str) -> ProviderResult<Vec<TypeDefinition>> {
+        let provider = TomlProvider::new();
+        let schema = provider.resolve_schema(source, &ProviderParams::default())?;
+        Ok(provider.generate_types(&schema, "Config")?.root_types)
+    }
+
+    fn record<'t>(types: &'t [TypeDefinition], name: &str) -> &'t RecordDef {
+        types
+            .iter()
+            .find_map(|ty| match ty {
+                TypeDefinition::Record(r) if r.name == name => Some(r),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("no record {}", name))
+    }
+
+    fn field(record: &RecordDef, name: &str) -> String {
+        record
+            .fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, ty)| ty.to_string())
+            .unwrap_or_else(|| panic!("no field {}", name))
+    }
+
+    #[test]
+    fn test_tables_become_records() {
+        let types = generate(
+            r#"
+title = "example"
+debug = true
+ratio = 0.5
+started = 1979-05-27T07:32:00Z
+ports = [8000, 8001]
+
+[database]
+host = "localhost"
+port = 5432
+
+[database.pool]
+size = 10
+"#,
+        )
+        .unwrap();
+
+        let config = record(&types, "Config");
+        assert_eq!(field(config, "title"), "string");
+        assert_eq!(field(config, "debug"), "bool");
+        assert_eq!(field(config, "ratio"), "float");
+        assert_eq!(field(config, "started"), "string");
+        assert_eq!(field(config, "ports"), "int list");
+        assert_eq!(field(config, "database"), "Database");
+
+        let database = record(&types, "Database");
+        assert_eq!(field(database, "port"), "int");
+        assert_eq!(field(database, "pool"), "Pool");
+        assert_eq!(field(record(&types, "Pool"), "size"), "int");
+    }
+
+    #[test]
+    fn test_arrays_of_tables() {
+        let types = generate(
+            r#"
+[[servers]]
+name = "alpha"
+ip = "10.0.0.1"
+
+[[servers]]
+name = "beta"
+"#,
+        )
+        .unwrap();
+        assert_eq!(field(record(&types, "Config"), "servers"), "Servers list");
+        let servers = record(&types, "Servers");
+        assert_eq!(field(servers, "name"), "string");
+        assert_eq!(field(servers, "ip"), "string option");
+    }
+
+    #[test]
+    fn test_name_clashes() {
+        let types = generate(
+            r#"
+[server.tls]
+cert = "a.pem"
+
+[client.tls]
+verify = true
+"#,
+        )
+        .unwrap();
+        let server = field(record(&types, "Server"), "tls");
+        let client = field(record(&types, "Client"), "tls");
+        assert_ne!(server, client);
+        assert!(server == "Tls" || client == "Tls");
+        assert!(server == "ServerTls" || client == "ClientTls");
+    }
+
+    #[test]
+    fn test_invalid_samples() {
+        assert!(matches!(
+            generate("a = [1, \"two\"]"),
+            Err(ProviderError::GenerationError(_))
+        ));
+        assert!(matches!(
+            generate("a = []"),
+            Err(ProviderError::GenerationError(_))
+        ));
+        assert!(matches!(
+            generate("a = "),
+            Err(ProviderError::ParseError(_))
+        ));
+    }
+}
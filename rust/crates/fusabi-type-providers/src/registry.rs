@@ -2,24 +2,53 @@
 //!
 //! This module provides the ProviderRegistry which manages type providers.
 
+use crate::cache::ProviderCache;
 use crate::error::{ProviderError, ProviderResult};
 use crate::generator::GeneratedTypes;
 use crate::provider::{ProviderParams, TypeProvider};
+use crate::providers::{
+    GraphqlProvider, JsonSchemaProvider, ProtobufProvider, RegexProvider, SqlProvider, TomlProvider,
+};
 use std::collections::HashMap;
 use std::sync::Arc;
 
 /// Registry of available type providers
 pub struct ProviderRegistry {
     providers: HashMap<String, Arc<dyn TypeProvider>>,
+    cache: Option<ProviderCache>,
 }
 
 impl ProviderRegistry {
+    /// A registry with the built-in providers
     pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Arc::new(JsonSchemaProvider::new()));
+        registry.register(Arc::new(TomlProvider::new()));
+        registry.register(Arc::new(SqlProvider::new()));
+        registry.register(Arc::new(ProtobufProvider::new()));
+        registry.register(Arc::new(GraphqlProvider::new()));
+        registry.register(Arc::new(RegexProvider::new()));
+        registry
+    }
+
+    /// A registry without any providers
+    pub fn empty() -> Self {
         Self {
             providers: HashMap::new(),
+            cache: None,
         }
     }
 
+    /// Cache resolutions in `cache`
+    pub fn with_cache(mut self, cache: ProviderCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn cache(&self) -> Option<&ProviderCache> {
+        self.cache.as_ref()
+    }
+
     /// Register a type provider
     pub fn register(&mut self, provider: Arc<dyn TypeProvider>) {
         self.providers.insert(provider.name().to_string(), provider);
@@ -44,10 +73,15 @@ impl ProviderRegistry {
             .ok_or_else(|| ProviderError::UnknownProvider(provider_name.to_string()))?;
 
         // Resolve and generate
-        let schema = provider.resolve_schema(source, params)?;
-        let types = provider.generate_types(&schema, namespace)?;
-
-        Ok(types)
+        let resolve = || {
+            let schema = provider.resolve_schema(source, params)?;
+            let types = provider.generate_types(&schema, namespace)?;
+            Ok((schema, types))
+        };
+        match &self.cache {
+            Some(cache) => cache.get_or_resolve(provider_name, source, namespace, params, resolve),
+            None => resolve().map(|(_, types)| types),
+        }
     }
 
     /// List registered providers
@@ -61,3 +95,70 @@ impl Default for ProviderRegistry {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_providers() {
+        let registry = ProviderRegistry::new();
+        let mut providers = registry.list_providers();
+        providers.sort();
+        assert_eq!(
+            providers,
+            vec![
+                "GraphqlProvider",
+                "JsonSchemaProvider",
+                "ProtobufProvider",
+                "RegexProvider",
+                "SqlProvider",
+                "TomlProvider"
+            ]
+        );
+        assert!(ProviderRegistry::empty().list_providers().is_empty());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let types = ProviderRegistry::new()
+            .resolve(
+                "TomlProvider",
+                "name = \"app\"",
+                "Config",
+                &ProviderParams::default(),
+            )
+            .unwrap();
+        assert_eq!(types.root_types[0].field_names(), vec!["name"]);
+
+        assert!(matches!(
+            ProviderRegistry::new().resolve("Nope", "", "X", &ProviderParams::default()),
+            Err(ProviderError::UnknownProvider(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("config.toml");
+        let source = source.to_str().unwrap();
+        std::fs::write(source, "name = \"app\"").unwrap();
+
+        let registry =
+            ProviderRegistry::new().with_cache(ProviderCache::new(dir.path().join("cache")));
+        let resolve = || {
+            registry
+                .resolve("TomlProvider", source, "Config", &ProviderParams::default())
+                .unwrap()
+        };
+        assert_eq!(resolve().root_types[0].field_names(), vec!["name"]);
+        assert_eq!(
+            std::fs::read_dir(dir.path().join("cache")).unwrap().count(),
+            1
+        );
+
+        // Editing the source invalidates the entry
+        std::fs::write(source, "port = 80").unwrap();
+        assert_eq!(resolve().root_types[0].field_names(), vec!["port"]);
+    }
+}
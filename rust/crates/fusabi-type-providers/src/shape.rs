@@ -0,0 +1,169 @@
+//! Shapes of generated types, for parsing data into them at runtime
+//!
+//! A [`TypeShape`] describes the records and unions a provider generated in
+//! the JSON form the VM's `Json.parseAs` and `Toml.parseAs` read. Providers
+//! bake it into erased members, so `Config.parse text` checks the data
+//! against the generated types and returns `Ok config` or `Error message`.
+
+use crate::generator::GeneratedTypes;
+use crate::types::{ProvidedMember, TypeDefinition, TypeExpr};
+use serde_json::{json, Map, Value};
+
+/// Description of a generated type and the types it refers to
+#[derive(Debug, Clone)]
+pub struct TypeShape {
+    root: String,
+    types: Map<String, Value>,
+}
+
+impl TypeShape {
+    /// The shape of `root`, one of the types in `generated`
+    pub fn new(generated: &GeneratedTypes, root: &str) -> Self {
+        let definitions = generated
+            .root_types
+            .iter()
+            .chain(generated.modules.iter().flat_map(|m| &m.types));
+
+        let mut types = Map::new();
+        for definition in definitions {
+            let (name, shape) = match definition {
+                TypeDefinition::Record(record) => {
+                    let fields: Vec<Value> = record
+                        .fields
+                        .iter()
+                        .map(|(name, ty)| json!([name, ty.to_string()]))
+                        .collect();
+                    (&record.name, json!({ "record": fields }))
+                }
+                TypeDefinition::Du(du) => {
+                    let cases: Vec<Value> = du
+                        .variants
+                        .iter()
+                        .map(|variant| match variant.fields.as_slice() {
+                            [] => json!({ "name": variant.name }),
+                            [payload] => {
+                                json!({ "name": variant.name, "payload": payload.to_string() })
+                            }
+                            payload => json!({
+                                "name": variant.name,
+                                "payload": TypeExpr::Tuple(payload.to_vec()).to_string()
+                            }),
+                        })
+                        .collect();
+                    (&du.name, json!({ "union": cases }))
+                }
+            };
+            types.insert(name.clone(), shape);
+        }
+
+        Self {
+            root: root.to_string(),
+            types,
+        }
+    }
+
+    /// Spell the case `case` of the union `union` as `value` in the data,
+    /// rather than as its name
+    pub fn with_literal(mut self, union: &str, case: &str, value: Value) -> Self {
+        let cases = self
+            .types
+            .get_mut(union)
+            .and_then(|shape| shape.get_mut("union"))
+            .and_then(Value::as_array_mut);
+        for shape in cases.into_iter().flatten() {
+            if shape["name"] == case {
+                shape["value"] = value.clone();
+            }
+        }
+        self
+    }
+
+    /// `parse : string -> Result<Root, string>` and `serialize : Root -> string`
+    /// members backed by the stdlib `module` (`Json` or `Toml`)
+    pub fn members(&self, module: &str) -> Vec<ProvidedMember> {
+        let named = |name: &str| TypeExpr::Named(name.to_string());
+        let function =
+            |from: TypeExpr, to: TypeExpr| TypeExpr::Function(Box::new(from), Box::new(to));
+        let shape = self.to_string();
+        vec![
+            ProvidedMember::new(
+                "parse".to_string(),
+                function(
+                    named("string"),
+                    named(&format!("Result<{}, string>", self.root)),
+                ),
+                format!("{}.parseAs", module),
+                vec![shape.clone()],
+            ),
+            ProvidedMember::new(
+                "serialize".to_string(),
+                function(named(&self.root), named("string")),
+                format!("{}.serializeAs", module),
+                vec![shape],
+            ),
+        ]
+    }
+}
+
+impl std::fmt::Display for TypeShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let shape = json!({ "root": self.root, "types": self.types });
+        write!(f, "{}", shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DuDef, RecordDef, VariantDef};
+
+    #[test]
+    fn test_shape() {
+        let generated = GeneratedTypes::new()
+            .with_type(TypeDefinition::Du(DuDef {
+                name: "Level".to_string(),
+                variants: vec![
+                    VariantDef::new_simple("ReadOnly".to_string()),
+                    VariantDef::new("Code".to_string(), vec![TypeExpr::Named("int".to_string())]),
+                ],
+            }))
+            .with_type(TypeDefinition::Record(RecordDef {
+                name: "Config".to_string(),
+                fields: vec![
+                    ("name".to_string(), TypeExpr::Named("string".to_string())),
+                    (
+                        "level".to_string(),
+                        TypeExpr::Named("Level option".to_string()),
+                    ),
+                ],
+            }));
+        let shape = TypeShape::new(&generated, "Config").with_literal(
+            "Level",
+            "ReadOnly",
+            json!("read-only"),
+        );
+
+        let doc: Value = serde_json::from_str(&shape.to_string()).unwrap();
+        assert_eq!(
+            doc,
+            json!({
+                "root": "Config",
+                "types": {
+                    "Config": {"record": [["name", "string"], ["level", "Level option"]]},
+                    "Level": {"union": [
+                        {"name": "ReadOnly", "value": "read-only"},
+                        {"name": "Code", "payload": "int"}
+                    ]}
+                }
+            })
+        );
+
+        let members = shape.members("Json");
+        assert_eq!(members[0].target, "Json.parseAs");
+        assert_eq!(
+            members[0].member_type.to_string(),
+            "string -> Result<Config, string>"
+        );
+        assert_eq!(members[1].member_type.to_string(), "Config -> string");
+    }
+}
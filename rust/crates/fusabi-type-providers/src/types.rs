@@ -184,6 +184,34 @@ impl FieldDef {
     }
 }
 
+/// A value-level member a provider contributes alongside its types.
+///
+/// Members are erased: `Alias.name` compiles to the stdlib function
+/// `target` applied to the string literals in `args`, so no code is
+/// generated for the member itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvidedMember {
+    /// Member name, accessed as `Alias.name`
+    pub name: String,
+    /// Type of the member after `args` are applied
+    pub member_type: TypeExpr,
+    /// Qualified stdlib function implementing the member (e.g., "Regex.namedCaptures")
+    pub target: String,
+    /// Leading string arguments baked into the member
+    pub args: Vec<String>,
+}
+
+impl ProvidedMember {
+    pub fn new(name: String, member_type: TypeExpr, target: String, args: Vec<String>) -> Self {
+        Self {
+            name,
+            member_type,
+            target,
+            args,
+        }
+    }
+}
+
 /// Result of validating fields against a type definition
 #[derive(Debug, Clone)]
 pub struct FieldValidationResult {
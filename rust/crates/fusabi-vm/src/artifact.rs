@@ -0,0 +1,475 @@
+//! Package artifacts: the compiled modules of a package and its dependencies
+//! in one file.
+//!
+//! An artifact (`.fzp`) has a module table recording, for each module, the
+//! package it was compiled from, the modules it requires, the symbols it
+//! exports and a hash of its bytecode, followed by the bytecode of every
+//! module. It also records a hash of the sources of each dependency it was
+//! built from. Opening an artifact decodes only the table; a module's
+//! bytecode is decoded, and checked against its hash, when it is loaded by
+//! name.
+//!
+//! A library module evaluates to a record of its exports. Linking it runs
+//! it and binds that record to a global named after the module, so modules
+//! requiring it call `Module.member`. The entry module is an ordinary
+//! program, run after the modules it requires are linked.
+//!
+//! # Layout
+//!
+//! `FZP_MAGIC`, `FZP_VERSION`, the length of the table as a little-endian
+//! `u64`, the table, then the module bytecode, all encoded with bincode.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use fusabi_vm::{Artifact, Vm};
+//!
+//! let artifact = Artifact::from_bytes(&std::fs::read("target/app.fzp").unwrap()).unwrap();
+//! let mut vm = Vm::new();
+//! fusabi_vm::stdlib::register_stdlib(&mut vm);
+//!
+//! // Link one library module on its own...
+//! let exports = artifact.link(&mut vm, "Json").unwrap();
+//! // ...or run the entry module with everything it requires
+//! let result = artifact.run(&mut vm).unwrap();
+//! ```
+
+use crate::chunk::Chunk;
+use crate::value::Value;
+use crate::vm::{Vm, VmError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// Magic bytes for Fusabi package artifacts (.fzp)
+pub const FZP_MAGIC: &[u8] = b"FZP\x01";
+/// Version of the artifact format
+pub const FZP_VERSION: u8 = 1;
+
+/// Length of the magic bytes, version and table length.
+const HEADER_LEN: usize = 4 + 1 + 8;
+
+/// Errors reading, loading or linking an artifact.
+#[derive(Debug)]
+pub enum ArtifactError {
+    /// The bytes are not an artifact of a supported version
+    InvalidHeader(String),
+    /// The table or a module could not be encoded or decoded
+    Encoding(bincode::Error),
+    /// No module of this name is in the artifact
+    ModuleNotFound(String),
+    /// A module's bytecode does not match the hash in the table
+    HashMismatch(String),
+    /// Modules require each other
+    CyclicRequire(String),
+    /// Running a module failed
+    Runtime(VmError),
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactError::InvalidHeader(msg) => write!(f, "Invalid artifact: {}", msg),
+            ArtifactError::Encoding(e) => write!(f, "Artifact encoding error: {}", e),
+            ArtifactError::ModuleNotFound(name) => {
+                write!(f, "Module '{}' not found in artifact", name)
+            }
+            ArtifactError::HashMismatch(name) => {
+                write!(f, "Bytecode of module '{}' does not match its hash", name)
+            }
+            ArtifactError::CyclicRequire(name) => {
+                write!(f, "Module '{}' requires itself", name)
+            }
+            ArtifactError::Runtime(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArtifactError {}
+
+impl From<bincode::Error> for ArtifactError {
+    fn from(e: bincode::Error) -> Self {
+        ArtifactError::Encoding(e)
+    }
+}
+
+impl From<VmError> for ArtifactError {
+    fn from(e: VmError) -> Self {
+        ArtifactError::Runtime(e)
+    }
+}
+
+/// A symbol a module exports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Export {
+    /// Name of the binding
+    pub name: String,
+    /// Number of parameters, or 0 for values
+    pub arity: usize,
+}
+
+/// An entry of the module table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleInfo {
+    /// Module name, also the global its exports are bound to
+    pub name: String,
+    /// Package the module was compiled from
+    pub package: String,
+    /// Version of that package
+    pub version: String,
+    /// Modules to link before this one
+    pub requires: Vec<String>,
+    /// Exported symbols
+    pub exports: Vec<Export>,
+    /// Hash of the module bytecode (`sha256:<hex>`)
+    pub hash: String,
+    /// Byte range of the bytecode after the table
+    offset: u64,
+    len: u64,
+}
+
+impl ModuleInfo {
+    /// Creates a table entry for the module `name` of `package`.
+    pub fn new(
+        name: impl Into<String>,
+        package: impl Into<String>,
+        version: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            package: package.into(),
+            version: version.into(),
+            requires: Vec::new(),
+            exports: Vec::new(),
+            hash: String::new(),
+            offset: 0,
+            len: 0,
+        }
+    }
+
+    /// Sets the modules to link before this one.
+    pub fn requires(mut self, requires: Vec<String>) -> Self {
+        self.requires = requires;
+        self
+    }
+
+    /// Sets the exported symbols.
+    pub fn exports(mut self, exports: Vec<Export>) -> Self {
+        self.exports = exports;
+        self
+    }
+}
+
+/// A dependency an artifact was built from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyInfo {
+    /// Package name
+    pub name: String,
+    /// Package version
+    pub version: String,
+    /// Hash of the package sources (`sha256:<hex>`)
+    pub hash: String,
+}
+
+/// The decoded table of an artifact.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Table {
+    package: String,
+    version: String,
+    entry: Option<String>,
+    modules: Vec<ModuleInfo>,
+    dependencies: Vec<DependencyInfo>,
+}
+
+/// Builder for artifacts.
+#[derive(Debug)]
+pub struct ArtifactBuilder {
+    table: Table,
+    chunks: Vec<Chunk>,
+}
+
+impl ArtifactBuilder {
+    /// Creates an empty artifact for `package`.
+    pub fn new(package: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            table: Table {
+                package: package.into(),
+                version: version.into(),
+                entry: None,
+                modules: Vec::new(),
+                dependencies: Vec::new(),
+            },
+            chunks: Vec::new(),
+        }
+    }
+
+    /// Adds a module and its bytecode.
+    pub fn module(mut self, info: ModuleInfo, chunk: Chunk) -> Self {
+        self.table.modules.push(info);
+        self.chunks.push(chunk);
+        self
+    }
+
+    /// Adds a module and makes it the entry module.
+    pub fn entry(mut self, info: ModuleInfo, chunk: Chunk) -> Self {
+        self.table.entry = Some(info.name.clone());
+        self.module(info, chunk)
+    }
+
+    /// Records a dependency the artifact was built from.
+    pub fn dependency(mut self, dependency: DependencyInfo) -> Self {
+        self.table.dependencies.push(dependency);
+        self
+    }
+
+    /// Encodes the artifact.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ArtifactError> {
+        let mut table = self.table.clone();
+        let mut code = Vec::new();
+        for (info, chunk) in table.modules.iter_mut().zip(&self.chunks) {
+            let bytes = bincode::serialize(chunk)?;
+            info.hash = hash(&bytes);
+            info.offset = code.len() as u64;
+            info.len = bytes.len() as u64;
+            code.extend_from_slice(&bytes);
+        }
+
+        let table = bincode::serialize(&table)?;
+        let mut bytes = Vec::with_capacity(HEADER_LEN + table.len() + code.len());
+        bytes.extend_from_slice(FZP_MAGIC);
+        bytes.push(FZP_VERSION);
+        bytes.extend_from_slice(&(table.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&table);
+        bytes.extend_from_slice(&code);
+        Ok(bytes)
+    }
+}
+
+/// An opened artifact.
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    table: Table,
+    /// Module bytecode, still encoded
+    code: Vec<u8>,
+}
+
+impl Artifact {
+    /// Opens an artifact, decoding its table.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArtifactError> {
+        if bytes.len() < HEADER_LEN || &bytes[0..4] != FZP_MAGIC {
+            return Err(ArtifactError::InvalidHeader(
+                "missing magic bytes".to_string(),
+            ));
+        }
+        if bytes[4] != FZP_VERSION {
+            return Err(ArtifactError::InvalidHeader(format!(
+                "unsupported version {}",
+                bytes[4]
+            )));
+        }
+        let mut len = [0; 8];
+        len.copy_from_slice(&bytes[5..HEADER_LEN]);
+        let table_end = usize::try_from(u64::from_le_bytes(len))
+            .ok()
+            .and_then(|len| HEADER_LEN.checked_add(len))
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| ArtifactError::InvalidHeader("truncated table".to_string()))?;
+
+        let table: Table = bincode::deserialize(&bytes[HEADER_LEN..table_end])?;
+        Ok(Self {
+            table,
+            code: bytes[table_end..].to_vec(),
+        })
+    }
+
+    /// Name of the package the artifact was built for.
+    pub fn package(&self) -> &str {
+        &self.table.package
+    }
+
+    /// Version of the package the artifact was built for.
+    pub fn version(&self) -> &str {
+        &self.table.version
+    }
+
+    /// Name of the entry module, if the artifact has one.
+    pub fn entry(&self) -> Option<&str> {
+        self.table.entry.as_deref()
+    }
+
+    /// The module table.
+    pub fn modules(&self) -> &[ModuleInfo] {
+        &self.table.modules
+    }
+
+    /// The table entry of the module `name`.
+    pub fn module(&self, name: &str) -> Option<&ModuleInfo> {
+        self.table.modules.iter().find(|m| m.name == name)
+    }
+
+    /// The dependencies the artifact was built from.
+    pub fn dependencies(&self) -> &[DependencyInfo] {
+        &self.table.dependencies
+    }
+
+    /// Decodes the bytecode of the module `name`, checking it against its
+    /// hash.
+    pub fn load_module(&self, name: &str) -> Result<Chunk, ArtifactError> {
+        let info = self
+            .module(name)
+            .ok_or_else(|| ArtifactError::ModuleNotFound(name.to_string()))?;
+        let bytes = usize::try_from(info.offset)
+            .ok()
+            .zip(usize::try_from(info.len).ok())
+            .and_then(|(offset, len)| self.code.get(offset..offset.checked_add(len)?))
+            .ok_or_else(|| ArtifactError::InvalidHeader(format!("truncated module '{}'", name)))?;
+        if hash(bytes) != info.hash {
+            return Err(ArtifactError::HashMismatch(name.to_string()));
+        }
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    /// Links the library module `name` into `vm`: links the modules it
+    /// requires that are not linked yet, runs it and binds its exports to
+    /// the global `name`. Returns the record of exports.
+    pub fn link(&self, vm: &mut Vm, name: &str) -> Result<Value, ArtifactError> {
+        self.link_with(vm, name, &mut Vec::new())
+    }
+
+    /// Runs the entry module, after linking the modules it requires.
+    pub fn run(&self, vm: &mut Vm) -> Result<Value, ArtifactError> {
+        let entry = self
+            .entry()
+            .ok_or_else(|| ArtifactError::ModuleNotFound("<entry>".to_string()))?;
+        self.link_requires(vm, entry, &mut vec![entry.to_string()])?;
+        Ok(vm.execute(self.load_module(entry)?)?)
+    }
+
+    fn link_with(
+        &self,
+        vm: &mut Vm,
+        name: &str,
+        linking: &mut Vec<String>,
+    ) -> Result<Value, ArtifactError> {
+        if linking.iter().any(|n| n == name) {
+            return Err(ArtifactError::CyclicRequire(name.to_string()));
+        }
+        linking.push(name.to_string());
+        self.link_requires(vm, name, linking)?;
+        let exports = vm.execute(self.load_module(name)?)?;
+        vm.globals.insert(name.to_string(), exports.clone());
+        linking.pop();
+        Ok(exports)
+    }
+
+    fn link_requires(
+        &self,
+        vm: &mut Vm,
+        name: &str,
+        linking: &mut Vec<String>,
+    ) -> Result<(), ArtifactError> {
+        let info = self
+            .module(name)
+            .ok_or_else(|| ArtifactError::ModuleNotFound(name.to_string()))?;
+        for required in &info.requires {
+            if !vm.globals.contains_key(required) {
+                self.link_with(vm, required, linking)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Hashes encoded bytecode as `sha256:<hex>`.
+fn hash(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256:{}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkBuilder;
+    use crate::instruction::Instruction;
+
+    fn constant(value: Value) -> Chunk {
+        ChunkBuilder::new()
+            .constant(value)
+            .instruction(Instruction::LoadConst(0))
+            .instruction(Instruction::Return)
+            .build()
+    }
+
+    fn artifact() -> Vec<u8> {
+        ArtifactBuilder::new("app", "0.1.0")
+            .module(
+                ModuleInfo::new("Strings", "strings", "1.0.0").exports(vec![Export {
+                    name: "greeting".to_string(),
+                    arity: 0,
+                }]),
+                constant(Value::Str("hello".to_string())),
+            )
+            .entry(
+                ModuleInfo::new("App", "app", "0.1.0").requires(vec!["Strings".to_string()]),
+                constant(Value::Int(42)),
+            )
+            .dependency(DependencyInfo {
+                name: "strings".to_string(),
+                version: "1.0.0".to_string(),
+                hash: "sha256:00".to_string(),
+            })
+            .to_bytes()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_artifact_roundtrip() {
+        let bytes = artifact();
+        assert!(bytes.starts_with(FZP_MAGIC));
+
+        let artifact = Artifact::from_bytes(&bytes).unwrap();
+        assert_eq!(artifact.package(), "app");
+        assert_eq!(artifact.version(), "0.1.0");
+        assert_eq!(artifact.entry(), Some("App"));
+        assert_eq!(artifact.modules().len(), 2);
+        assert_eq!(artifact.dependencies()[0].name, "strings");
+
+        let strings = artifact.module("Strings").unwrap();
+        assert_eq!(strings.exports[0].name, "greeting");
+        assert!(strings.hash.starts_with("sha256:"));
+        assert!(artifact.load_module("Strings").is_ok());
+        assert!(matches!(
+            artifact.load_module("Missing"),
+            Err(ArtifactError::ModuleNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_artifact_links_required_modules() {
+        let artifact = Artifact::from_bytes(&artifact()).unwrap();
+        let mut vm = Vm::new();
+        assert_eq!(artifact.run(&mut vm).unwrap(), Value::Int(42));
+        assert_eq!(
+            vm.globals.get("Strings"),
+            Some(&Value::Str("hello".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_artifact_rejects_corruption() {
+        let mut bytes = artifact();
+        let last = bytes.len() - 2;
+        bytes[last] ^= 0xff;
+        let artifact = Artifact::from_bytes(&bytes).unwrap();
+        assert!(matches!(
+            artifact.load_module("App"),
+            Err(ArtifactError::HashMismatch(_))
+        ));
+
+        assert!(matches!(
+            Artifact::from_bytes(b"FZB\x01\x01"),
+            Err(ArtifactError::InvalidHeader(_))
+        ));
+    }
+}
@@ -49,6 +49,20 @@ impl fmt::Display for SourceSpan {
     }
 }
 
+/// Debug info naming a local variable slot over a range of instructions
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LocalVarInfo {
+    /// Variable name
+    pub name: String,
+    /// Slot relative to the frame base
+    pub slot: u8,
+    /// First instruction where the variable is live
+    pub start: usize,
+    /// Instruction after the last one where the variable is live
+    pub end: usize,
+}
+
 /// A chunk of bytecode representing a compiled function
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -65,6 +79,9 @@ pub struct Chunk {
     /// Source file name
     #[cfg_attr(feature = "serde", serde(default))]
     pub source_file: Option<String>,
+    /// Names of local variable slots (debug info)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub local_vars: Vec<LocalVarInfo>,
 }
 
 impl Chunk {
@@ -76,6 +93,7 @@ impl Chunk {
             spans: Vec::new(),
             source: None,
             source_file: None,
+            local_vars: Vec::new(),
         }
     }
 
@@ -87,6 +105,7 @@ impl Chunk {
             spans: Vec::new(),
             source: None,
             source_file: None,
+            local_vars: Vec::new(),
         }
     }
 
@@ -105,6 +124,21 @@ impl Chunk {
         self.spans.get(offset).copied().filter(|s| s.is_known())
     }
 
+    /// Local variables live at the instruction at `offset`, by slot
+    ///
+    /// When a slot is reused, the innermost (most recently declared) name wins.
+    pub fn locals_at(&self, offset: usize) -> Vec<&LocalVarInfo> {
+        let mut live: Vec<&LocalVarInfo> = Vec::new();
+        for var in &self.local_vars {
+            if var.start <= offset && offset < var.end {
+                live.retain(|v| v.slot != var.slot);
+                live.push(var);
+            }
+        }
+        live.sort_by_key(|v| v.slot);
+        live
+    }
+
     pub fn add_constant(&mut self, value: Value) -> u16 {
         self.constants.push(value);
         (self.constants.len() - 1) as u16
@@ -251,4 +285,30 @@ mod tests {
         assert_eq!(chunk.instructions.len(), 2);
         assert_eq!(chunk.constants.len(), 1);
     }
+
+    #[test]
+    fn test_locals_at_innermost_wins() {
+        let mut chunk = Chunk::new();
+        let var = |name: &str, slot, start, end| LocalVarInfo {
+            name: name.to_string(),
+            slot,
+            start,
+            end,
+        };
+        chunk.local_vars.push(var("x", 0, 0, 10));
+        chunk.local_vars.push(var("y", 1, 2, 5));
+        chunk.local_vars.push(var("x2", 0, 6, 8));
+
+        let names = |offset| -> Vec<String> {
+            chunk
+                .locals_at(offset)
+                .into_iter()
+                .map(|v| v.name.clone())
+                .collect()
+        };
+        assert_eq!(names(3), vec!["x", "y"]);
+        assert_eq!(names(5), vec!["x"]);
+        assert_eq!(names(7), vec!["x2"]);
+        assert!(names(10).is_empty());
+    }
 }
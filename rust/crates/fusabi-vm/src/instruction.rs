@@ -199,6 +199,15 @@ pub enum Instruction {
 
     /// Close upvalues on the stack up to a given stack slot
     CloseUpvalue(u8),
+
+    // ===== Global Operations =====
+    // (after the others, so that existing bytecode keeps its encoding)
+    /// Pop stack top into global variable (name is at constants\[`idx`\])
+    StoreGlobal(u16),
+
+    /// Pop a value, then a closure, and make the value the closure's
+    /// upvalues\[`idx`\], for closures that capture themselves
+    SetClosureUpvalue(u8),
 }
 
 impl fmt::Display for Instruction {
@@ -211,6 +220,8 @@ impl fmt::Display for Instruction {
             Instruction::LoadUpvalue(idx) => write!(f, "LOAD_UPVALUE {}", idx),
             Instruction::StoreUpvalue(idx) => write!(f, "STORE_UPVALUE {}", idx),
             Instruction::LoadGlobal(idx) => write!(f, "LOAD_GLOBAL {}", idx),
+            Instruction::StoreGlobal(idx) => write!(f, "STORE_GLOBAL {}", idx),
+            Instruction::SetClosureUpvalue(idx) => write!(f, "SET_CLOSURE_UPVALUE {}", idx),
             Instruction::Pop => write!(f, "POP"),
             Instruction::Dup => write!(f, "DUP"),
 
@@ -447,6 +458,18 @@ mod tests {
         assert_eq!(format!("{}", instr), "LOAD_CONST 42");
     }
 
+    #[test]
+    fn test_display_store_global() {
+        let instr = Instruction::StoreGlobal(3);
+        assert_eq!(format!("{}", instr), "STORE_GLOBAL 3");
+    }
+
+    #[test]
+    fn test_display_set_closure_upvalue() {
+        let instr = Instruction::SetClosureUpvalue(1);
+        assert_eq!(format!("{}", instr), "SET_CLOSURE_UPVALUE 1");
+    }
+
     #[test]
     fn test_display_load_local() {
         let instr = Instruction::LoadLocal(5);
@@ -1,5 +1,7 @@
 // Fusabi VM - Bytecode Virtual Machine Runtime
 
+#[cfg(feature = "serde")]
+pub mod artifact;
 pub mod chunk;
 pub mod closure;
 pub mod conversions;
@@ -7,8 +9,10 @@ pub mod error_reporter;
 pub mod gc;
 pub mod host;
 pub mod instruction;
+pub mod limits;
 pub mod optimized_vm;
 pub mod optimizer;
+pub mod profiler;
 pub mod stdlib;
 pub mod value;
 pub mod vm;
@@ -19,15 +23,21 @@ pub mod async_runtime;
 #[cfg(feature = "async")]
 pub mod async_types;
 
-pub use chunk::{Chunk, ChunkBuilder, SourceSpan};
+#[cfg(feature = "serde")]
+pub use artifact::{
+    Artifact, ArtifactBuilder, ArtifactError, DependencyInfo, Export, ModuleInfo, FZP_MAGIC,
+};
+pub use chunk::{Chunk, ChunkBuilder, LocalVarInfo, SourceSpan};
 pub use closure::{Closure, Upvalue};
 pub use error_reporter::{format_error, RuntimeError};
 pub use gc::{GcHeap, GcStats, Trace, Tracer};
 pub use host::{HostFn, HostRegistry};
 pub use instruction::Instruction;
+pub use limits::{ExecutionLimits, Interrupt};
 pub use optimized_vm::FastVm;
+pub use profiler::{Cost, FunctionProfile, LineProfile, Profile, ProfileMetric, StackProfile};
 pub use value::{HostData, Value};
-pub use vm::{Frame, Vm, VmError};
+pub use vm::{DebugHook, Frame, Vm, VmError};
 
 // Async re-exports (feature-gated)
 #[cfg(feature = "async")]
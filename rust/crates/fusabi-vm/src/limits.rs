@@ -0,0 +1,172 @@
+// Fusabi VM Execution Limits
+// Bounds on instructions, call depth and wall time for a run, and a handle for
+// interrupting a run from another thread.
+//
+// Limits are checked between instructions, so a run blocked inside a host
+// function only stops once that function returns.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::vm::VmError;
+
+/// How often (in instructions) the wall clock is read
+const CLOCK_INTERVAL: u64 = 256;
+
+/// Bounds on a single run of the VM
+///
+/// A run is an outermost [`Vm::execute`](crate::Vm::execute) or
+/// [`Vm::call_value`](crate::Vm::call_value); calls made from host functions
+/// while it is in progress count towards the same run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecutionLimits {
+    /// Instructions a run may execute
+    pub max_instructions: Option<u64>,
+    /// Frames that may be active at once
+    pub max_call_depth: Option<usize>,
+    /// Wall time a run may take
+    pub timeout: Option<Duration>,
+}
+
+/// Stops a running VM from another thread
+///
+/// The run in progress fails with [`VmError::Interrupted`] at its next
+/// instruction; a request made between runs stops the next run as soon as it
+/// starts. Each run clears the request when it ends.
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt(Arc<AtomicBool>);
+
+impl Interrupt {
+    /// Ask the VM to stop
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+
+    fn clear(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Enforces limits while the VM runs
+#[derive(Debug, Default)]
+pub(crate) struct Guard {
+    pub(crate) limits: ExecutionLimits,
+    pub(crate) interrupt: Interrupt,
+    nesting: usize,
+    instructions: u64,
+    deadline: Option<Instant>,
+}
+
+impl Guard {
+    /// A run (or a nested call) starts
+    pub(crate) fn enter(&mut self) {
+        if self.nesting == 0 {
+            self.instructions = 0;
+            self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        }
+        self.nesting += 1;
+    }
+
+    /// A run (or a nested call) ended
+    pub(crate) fn exit(&mut self) {
+        self.nesting = self.nesting.saturating_sub(1);
+        if self.nesting == 0 {
+            self.interrupt.clear();
+        }
+    }
+
+    /// Check the limits before executing an instruction with `depth` frames active
+    pub(crate) fn on_instruction(&mut self, depth: usize) -> Result<(), VmError> {
+        // The first instruction of a run checks too, for requests made before it
+        if self.instructions % CLOCK_INTERVAL == 0 {
+            if self.interrupt.take() {
+                return Err(VmError::Interrupted);
+            }
+            if let (Some(deadline), Some(timeout)) = (self.deadline, self.limits.timeout) {
+                if Instant::now() >= deadline {
+                    return Err(VmError::LimitExceeded(format!(
+                        "time limit of {:?} exceeded",
+                        timeout
+                    )));
+                }
+            }
+        }
+        self.instructions += 1;
+        if let Some(max) = self.limits.max_instructions {
+            if self.instructions > max {
+                return Err(VmError::LimitExceeded(format!(
+                    "instruction limit of {} exceeded",
+                    max
+                )));
+            }
+        }
+        if let Some(max) = self.limits.max_call_depth {
+            if depth > max {
+                return Err(VmError::LimitExceeded(format!(
+                    "call depth limit of {} exceeded",
+                    max
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction_limit() {
+        let mut guard = Guard {
+            limits: ExecutionLimits {
+                max_instructions: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        guard.enter();
+        assert!(guard.on_instruction(1).is_ok());
+        assert!(guard.on_instruction(1).is_ok());
+        assert!(matches!(
+            guard.on_instruction(1),
+            Err(VmError::LimitExceeded(_))
+        ));
+
+        // A new run starts counting again
+        guard.exit();
+        guard.enter();
+        assert!(guard.on_instruction(1).is_ok());
+    }
+
+    #[test]
+    fn test_interrupt_checked_periodically() {
+        let mut guard = Guard::default();
+        guard.enter();
+        guard.interrupt.interrupt();
+        let result = (0..CLOCK_INTERVAL).try_for_each(|_| guard.on_instruction(1));
+        assert!(matches!(result, Err(VmError::Interrupted)));
+    }
+
+    #[test]
+    fn test_interrupt_before_run() {
+        let mut guard = Guard::default();
+        guard.interrupt.interrupt();
+        guard.enter();
+        assert!(matches!(guard.on_instruction(1), Err(VmError::Interrupted)));
+
+        // Ending a run clears the request, even one the run never saw
+        guard.exit();
+        guard.enter();
+        assert!(guard.on_instruction(1).is_ok());
+        guard.interrupt.interrupt();
+        guard.exit();
+        guard.enter();
+        assert!(guard.on_instruction(1).is_ok());
+    }
+}
@@ -197,6 +197,21 @@ impl FastVm {
                     self.push_fast(value);
                 }
 
+                Instruction::StoreGlobal(idx) => {
+                    let name_val = self.get_constant(*idx)?;
+                    let name = match name_val {
+                        Value::Str(s) => s,
+                        _ => {
+                            return Err(VmError::TypeMismatch {
+                                expected: "string (global name)",
+                                got: name_val.type_name(),
+                            })
+                        }
+                    };
+                    let value = self.pop_fast()?;
+                    self.globals.insert(name, value);
+                }
+
                 Instruction::Pop => {
                     self.pop_fast()?;
                 }
@@ -410,8 +425,13 @@ impl FastVm {
                     closure.arity = prototype.arity;
                     closure.name = prototype.name.clone();
 
+                    // The captured values, pushed in upvalue order
+                    let mut captured = Vec::with_capacity(*upvalue_count as usize);
                     for _ in 0..*upvalue_count {
-                        self.pop_fast()?;
+                        captured.push(self.pop_fast()?);
+                    }
+                    for value in captured.into_iter().rev() {
+                        closure.add_upvalue(Arc::new(Mutex::new(Upvalue::new_closed(value))));
                     }
 
                     self.push_fast(Value::Closure(Arc::new(closure)));
@@ -434,6 +454,16 @@ impl FastVm {
                     // Placeholder
                 }
 
+                Instruction::SetClosureUpvalue(idx) => {
+                    let value = self.pop_fast()?;
+                    let closure = self.pop_fast()?;
+                    let upvalue = closure
+                        .as_closure()
+                        .and_then(|closure| closure.get_upvalue(*idx as usize))
+                        .ok_or(VmError::Runtime(format!("Invalid upvalue index: {}", idx)))?;
+                    *upvalue.lock().unwrap() = Upvalue::new_closed(value);
+                }
+
                 Instruction::Return => {
                     self.frames.pop();
                     if self.frames.len() < start_depth {
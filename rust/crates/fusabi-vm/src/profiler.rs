@@ -0,0 +1,422 @@
+// Fusabi VM Profiler
+// Instrumenting profiler that attributes executed instructions, and the wall time
+// until the next one, to functions, source lines and call stacks.
+//
+// Time spent in host functions is charged to the instruction that called them.
+// Line numbers come from the chunk's span table; code compiled without spans is
+// attributed to line 0.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::closure::Closure;
+use crate::vm::Frame;
+
+/// Measurement used to weight a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileMetric {
+    /// Wall time, in microseconds
+    Time,
+    /// Executed instructions
+    Instructions,
+}
+
+/// Instructions executed and wall time spent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cost {
+    pub instructions: u64,
+    pub time: Duration,
+}
+
+impl Cost {
+    fn add(&mut self, other: Cost) {
+        self.instructions += other.instructions;
+        self.time += other.time;
+    }
+
+    /// The cost measured by `metric`
+    pub fn weight(&self, metric: ProfileMetric) -> u64 {
+        match metric {
+            ProfileMetric::Time => self.time.as_micros() as u64,
+            ProfileMetric::Instructions => self.instructions,
+        }
+    }
+}
+
+/// Cost attributed to one function
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionProfile {
+    pub name: String,
+    /// Spent in the function's own instructions
+    pub self_cost: Cost,
+    /// Spent in the function and everything it called
+    pub total_cost: Cost,
+}
+
+/// Cost attributed to one source line of a function
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineProfile {
+    pub function: String,
+    pub line: u32,
+    pub cost: Cost,
+}
+
+/// Self cost of one distinct call stack
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackProfile {
+    /// Function names, outermost first
+    pub frames: Vec<String>,
+    pub cost: Cost,
+}
+
+/// Snapshot of collected profiling data
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    /// Functions, most expensive (by self time) first
+    pub functions: Vec<FunctionProfile>,
+    /// Lines with known positions, most expensive first
+    pub lines: Vec<LineProfile>,
+    /// Call stacks, sorted by frames
+    pub stacks: Vec<StackProfile>,
+    pub total: Cost,
+}
+
+impl Profile {
+    /// Collapsed-stack output ("main;f;g 42" per line), as read by flamegraph tools
+    pub fn folded(&self, metric: ProfileMetric) -> String {
+        let mut out = String::new();
+        for stack in &self.stacks {
+            let weight = stack.cost.weight(metric);
+            if weight > 0 {
+                let _ = writeln!(out, "{} {}", stack.frames.join(";"), weight);
+            }
+        }
+        out
+    }
+
+    /// Table of the `top` most expensive functions and lines
+    pub fn summary(&self, top: usize) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Total: {} instructions in {}",
+            self.total.instructions,
+            format_duration(self.total.time)
+        );
+
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "{:<32} {:>12} {:>7} {:>12} {:>14}",
+            "Function", "Self time", "Self %", "Total time", "Instructions"
+        );
+        for function in self.functions.iter().take(top) {
+            let _ = writeln!(
+                out,
+                "{:<32} {:>12} {:>6.1}% {:>12} {:>14}",
+                function.name,
+                format_duration(function.self_cost.time),
+                self.percent(function.self_cost),
+                format_duration(function.total_cost.time),
+                function.self_cost.instructions
+            );
+        }
+
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "{:<32} {:>12} {:>7} {:>14}",
+            "Line", "Time", "Time %", "Instructions"
+        );
+        for line in self.lines.iter().take(top) {
+            let _ = writeln!(
+                out,
+                "{:<32} {:>12} {:>6.1}% {:>14}",
+                format!("{}:{}", line.function, line.line),
+                format_duration(line.cost.time),
+                self.percent(line.cost),
+                line.cost.instructions
+            );
+        }
+        out
+    }
+
+    /// Share of the total, by time when any was measured
+    fn percent(&self, cost: Cost) -> f64 {
+        if !self.total.time.is_zero() {
+            100.0 * cost.time.as_secs_f64() / self.total.time.as_secs_f64()
+        } else if self.total.instructions > 0 {
+            100.0 * cost.instructions as f64 / self.total.instructions as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Name a frame is reported under
+fn function_name(frame: &Frame, depth: usize) -> &str {
+    let closure = &frame.closure;
+    closure
+        .name
+        .as_deref()
+        .or(closure.chunk.name.as_deref())
+        .unwrap_or(if depth == 0 { "<main>" } else { "<lambda>" })
+}
+
+/// Stack of the frames seen last, so it is only rebuilt on calls and returns
+#[derive(Debug)]
+struct CurrentStack {
+    depth: usize,
+    top: Arc<Closure>,
+    stack: usize,
+    function: u32,
+}
+
+/// Where the cost of the next tick goes
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    stack: usize,
+    line: (u32, u32),
+}
+
+/// Collects costs while the VM runs
+#[derive(Debug, Default)]
+pub(crate) struct Profiler {
+    names: Vec<String>,
+    name_ids: HashMap<String, u32>,
+    stack_ids: HashMap<Vec<u32>, usize>,
+    stacks: Vec<(Vec<u32>, Cost)>,
+    /// Keyed by (function, line)
+    lines: HashMap<(u32, u32), Cost>,
+    current: Option<CurrentStack>,
+    pending: Option<Location>,
+    last_tick: Option<Instant>,
+}
+
+impl Profiler {
+    /// Record the instruction about to execute in the innermost frame
+    pub(crate) fn on_instruction(&mut self, frames: &[Frame]) {
+        let now = Instant::now();
+        self.charge(now);
+        let Some(frame) = frames.last() else {
+            self.pending = None;
+            return;
+        };
+
+        let location = self.locate(frames, frame.ip);
+        self.stacks[location.stack].1.instructions += 1;
+        self.lines.entry(location.line).or_default().instructions += 1;
+        self.pending = Some(location);
+        self.last_tick = Some(now);
+    }
+
+    /// Record that a run loop returned; until the next instruction, time is
+    /// charged to the instruction that called into it
+    pub(crate) fn on_return(&mut self, frames: &[Frame]) {
+        let now = Instant::now();
+        self.charge(now);
+        match frames.last() {
+            Some(frame) => {
+                self.pending = Some(self.locate(frames, frame.ip.saturating_sub(1)));
+                self.last_tick = Some(now);
+            }
+            None => {
+                self.pending = None;
+                self.last_tick = None;
+            }
+        }
+    }
+
+    fn charge(&mut self, now: Instant) {
+        if let (Some(location), Some(last)) = (self.pending, self.last_tick) {
+            let elapsed = now.saturating_duration_since(last);
+            self.stacks[location.stack].1.time += elapsed;
+            self.lines.entry(location.line).or_default().time += elapsed;
+        }
+    }
+
+    fn locate(&mut self, frames: &[Frame], ip: usize) -> Location {
+        let (stack, function) = self.stack_for(frames);
+        let chunk = &frames[frames.len() - 1].closure.chunk;
+        let line = chunk.span_at(ip).map_or(0, |span| span.line);
+        Location {
+            stack,
+            line: (function, line),
+        }
+    }
+
+    fn stack_for(&mut self, frames: &[Frame]) -> (usize, u32) {
+        let depth = frames.len();
+        let top = &frames[depth - 1].closure;
+        if let Some(current) = &self.current {
+            if current.depth == depth && Arc::ptr_eq(&current.top, top) {
+                return (current.stack, current.function);
+            }
+        }
+
+        let ids: Vec<u32> = frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| self.intern(function_name(frame, i)))
+            .collect();
+        let function = ids[depth - 1];
+        let stack = match self.stack_ids.get(&ids) {
+            Some(&stack) => stack,
+            None => {
+                self.stacks.push((ids.clone(), Cost::default()));
+                self.stack_ids.insert(ids, self.stacks.len() - 1);
+                self.stacks.len() - 1
+            }
+        };
+        self.current = Some(CurrentStack {
+            depth,
+            top: top.clone(),
+            stack,
+            function,
+        });
+        (stack, function)
+    }
+
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.name_ids.get(name) {
+            return id;
+        }
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.name_ids.insert(name.to_string(), id);
+        id
+    }
+
+    /// Snapshot of the costs collected so far
+    pub(crate) fn profile(&self) -> Profile {
+        let name = |id: u32| self.names[id as usize].clone();
+
+        let mut total = Cost::default();
+        let mut self_costs = vec![Cost::default(); self.names.len()];
+        let mut total_costs = vec![Cost::default(); self.names.len()];
+        for (ids, cost) in &self.stacks {
+            total.add(*cost);
+            self_costs[ids[ids.len() - 1] as usize].add(*cost);
+            // Count recursive functions once per stack
+            let mut seen = Vec::new();
+            for &id in ids {
+                if !seen.contains(&id) {
+                    seen.push(id);
+                    total_costs[id as usize].add(*cost);
+                }
+            }
+        }
+
+        let mut functions: Vec<FunctionProfile> = (0..self.names.len())
+            .map(|id| FunctionProfile {
+                name: name(id as u32),
+                self_cost: self_costs[id],
+                total_cost: total_costs[id],
+            })
+            .collect();
+        functions.sort_by(|a, b| {
+            (b.self_cost.time, b.self_cost.instructions)
+                .cmp(&(a.self_cost.time, a.self_cost.instructions))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        let mut lines: Vec<LineProfile> = self
+            .lines
+            .iter()
+            .filter(|((_, line), _)| *line > 0)
+            .map(|(&(function, line), &cost)| LineProfile {
+                function: name(function),
+                line,
+                cost,
+            })
+            .collect();
+        lines.sort_by(|a, b| {
+            (b.cost.time, b.cost.instructions)
+                .cmp(&(a.cost.time, a.cost.instructions))
+                .then_with(|| (&a.function, a.line).cmp(&(&b.function, b.line)))
+        });
+
+        let mut stacks: Vec<StackProfile> = self
+            .stacks
+            .iter()
+            .map(|(ids, cost)| StackProfile {
+                frames: ids.iter().map(|&id| name(id)).collect(),
+                cost: *cost,
+            })
+            .collect();
+        stacks.sort_by(|a, b| a.frames.cmp(&b.frames));
+
+        Profile {
+            functions,
+            lines,
+            stacks,
+            total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cost(instructions: u64, micros: u64) -> Cost {
+        Cost {
+            instructions,
+            time: Duration::from_micros(micros),
+        }
+    }
+
+    fn sample_profile() -> Profile {
+        Profile {
+            functions: vec![FunctionProfile {
+                name: "square".to_string(),
+                self_cost: cost(10, 300),
+                total_cost: cost(10, 300),
+            }],
+            lines: vec![LineProfile {
+                function: "square".to_string(),
+                line: 2,
+                cost: cost(10, 300),
+            }],
+            stacks: vec![
+                StackProfile {
+                    frames: vec!["<main>".to_string()],
+                    cost: cost(5, 100),
+                },
+                StackProfile {
+                    frames: vec!["<main>".to_string(), "square".to_string()],
+                    cost: cost(10, 300),
+                },
+            ],
+            total: cost(15, 400),
+        }
+    }
+
+    #[test]
+    fn test_folded_output() {
+        let profile = sample_profile();
+        assert_eq!(
+            profile.folded(ProfileMetric::Instructions),
+            "<main> 5\n<main>;square 10\n"
+        );
+        assert_eq!(
+            profile.folded(ProfileMetric::Time),
+            "<main> 100\n<main>;square 300\n"
+        );
+    }
+
+    #[test]
+    fn test_summary_table() {
+        let summary = sample_profile().summary(10);
+        assert!(summary.starts_with("Total: 15 instructions in 0.400ms"));
+        assert!(summary.contains("square"));
+        assert!(summary.contains("75.0%"));
+        assert!(summary.contains("square:2"));
+    }
+}
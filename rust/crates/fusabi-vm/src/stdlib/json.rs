@@ -30,9 +30,38 @@ pub fn json_parse(arg: &Value) -> Result<Value, VmError> {
     }
 }
 
+#[cfg(feature = "json")]
+/// Json.parseAs : string -> string -> Result<'a, string>
+/// Parses a JSON string into the root type of a type provider's shape,
+/// with an Error describing the first value that does not fit it
+pub fn json_parse_as(shape: &Value, arg: &Value) -> Result<Value, VmError> {
+    let shape = super::shape::Shape::parse(shape)?;
+    let Value::Str(json_str) = arg else {
+        return Err(VmError::TypeMismatch {
+            expected: "string",
+            got: arg.type_name(),
+        });
+    };
+    let decoded = serde_json::from_str(json_str)
+        .map_err(|e| format!("JSON parse error: {}", e))
+        .and_then(|json| shape.decode(&json));
+    Ok(super::shape::result(decoded))
+}
+
+#[cfg(feature = "json")]
+/// Json.serializeAs : string -> 'a -> string
+/// Converts a value of the root type of a type provider's shape to a JSON
+/// string, leaving out fields that are None
+pub fn json_serialize_as(shape: &Value, arg: &Value) -> Result<Value, VmError> {
+    let json_value = super::shape::Shape::parse(shape)?.encode(arg)?;
+    let json_str = serde_json::to_string(&json_value)
+        .map_err(|e| VmError::Runtime(format!("JSON stringify error: {}", e)))?;
+    Ok(Value::Str(json_str))
+}
+
 #[cfg(feature = "json")]
 /// Convert a serde_json::Value to a Fusabi Value
-fn json_value_to_fusabi(json: &serde_json::Value) -> Result<Value, VmError> {
+pub(crate) fn json_value_to_fusabi(json: &serde_json::Value) -> Result<Value, VmError> {
     match json {
         serde_json::Value::Null => Ok(Value::Unit),
         serde_json::Value::Bool(b) => Ok(Value::Bool(*b)),
@@ -75,7 +104,7 @@ pub fn json_stringify(arg: &Value) -> Result<Value, VmError> {
 
 #[cfg(feature = "json")]
 /// Convert a Fusabi Value to a serde_json::Value
-fn fusabi_value_to_json(value: &Value) -> Result<serde_json::Value, VmError> {
+pub(crate) fn fusabi_value_to_json(value: &Value) -> Result<serde_json::Value, VmError> {
     match value {
         Value::Unit => Ok(serde_json::Value::Null),
         Value::Bool(b) => Ok(serde_json::Value::Bool(*b)),
@@ -0,0 +1,398 @@
+// Fusabi MCP Client Standard Library
+// Connects to Model Context Protocol servers over stdio and calls their tools
+// and resources.
+//
+// SECURITY NOTE: Mcp.connect starts the given command as a child process (see
+// the Process module).
+//
+// A reader thread per client forwards the server's output line by line, so a
+// request can give up on a server that stops answering.
+
+use crate::stdlib::json::{fusabi_value_to_json, json_value_to_fusabi};
+use crate::stdlib::process::spawn_piped;
+use crate::value::Value;
+use crate::vm::VmError;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Protocol version sent in the initialize handshake
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Time a server has to answer a request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static::lazy_static! {
+    static ref CLIENTS: Mutex<HashMap<i64, Arc<Mutex<Client>>>> = Mutex::new(HashMap::new());
+    static ref NEXT_CLIENT_ID: Mutex<i64> = Mutex::new(1);
+}
+
+/// A connection to one server process
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines the server writes, from the reader thread
+    lines: Receiver<std::io::Result<String>>,
+    timeout: Duration,
+    next_id: i64,
+}
+
+impl Client {
+    fn spawn(cmd: &Value, args: &Value) -> Result<Self, VmError> {
+        let mut child = spawn_piped(cmd, args)?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, lines) = mpsc::channel();
+        // Ends once the server exits, or the client is dropped
+        thread::Builder::new()
+            .name("fusabi-mcp-client".to_string())
+            .spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            })
+            .map_err(|e| VmError::Runtime(format!("MCP reader thread failed to start: {}", e)))?;
+        Ok(Client {
+            child,
+            stdin,
+            lines,
+            timeout: REQUEST_TIMEOUT,
+            next_id: 1,
+        })
+    }
+
+    /// Send a request and wait for its response, skipping anything else the
+    /// server sends meanwhile (notifications, log messages)
+    ///
+    /// Fails if the response takes longer than the client's timeout.
+    fn request(
+        &mut self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, VmError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let line = match self.lines.recv_timeout(remaining) {
+                Ok(line) => line.map_err(|e| VmError::Runtime(format!("MCP read error: {}", e)))?,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(VmError::Runtime(format!(
+                        "MCP server did not answer '{}' within {} ms",
+                        method,
+                        self.timeout.as_millis()
+                    )))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(VmError::Runtime(format!(
+                        "MCP server closed the connection during '{}'",
+                        method
+                    )))
+                }
+            };
+            let Ok(message) = serde_json::from_str::<serde_json::Value>(line.trim()) else {
+                continue;
+            };
+            if message.get("id") != Some(&json!(id)) {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                return Err(VmError::Runtime(format!(
+                    "MCP error {}: {}",
+                    error["code"],
+                    error["message"].as_str().unwrap_or("unknown error")
+                )));
+            }
+            return Ok(message.get("result").cloned().unwrap_or(json!({})));
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: serde_json::Value) -> Result<(), VmError> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn send(&mut self, message: &serde_json::Value) -> Result<(), VmError> {
+        writeln!(self.stdin, "{}", message)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| VmError::Runtime(format!("MCP write error: {}", e)))
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn client(handle: &Value) -> Result<Arc<Mutex<Client>>, VmError> {
+    let id = match handle {
+        Value::Int(i) => *i,
+        _ => {
+            return Err(VmError::TypeMismatch {
+                expected: "int",
+                got: handle.type_name(),
+            })
+        }
+    };
+    CLIENTS
+        .lock()
+        .unwrap()
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| VmError::Runtime(format!("Invalid MCP client handle: {}", id)))
+}
+
+fn string_arg(value: &Value) -> Result<&str, VmError> {
+    value.as_str().ok_or_else(|| VmError::TypeMismatch {
+        expected: "string",
+        got: value.type_name(),
+    })
+}
+
+fn record(fields: Vec<(&str, Value)>) -> Value {
+    let fields = fields
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+    Value::Record(Arc::new(Mutex::new(fields)))
+}
+
+/// Convert each element of a JSON array; a missing array is empty
+fn json_list(items: Option<&serde_json::Value>) -> Result<Vec<Value>, VmError> {
+    items
+        .and_then(|items| items.as_array())
+        .map(|items| items.iter().map(json_value_to_fusabi).collect())
+        .unwrap_or_else(|| Ok(Vec::new()))
+}
+
+/// Mcp.connect : string -> string list -> int
+/// Starts an MCP server (command and arguments, not run through a shell),
+/// performs the initialize handshake and returns a client handle.
+/// Calls on the handle fail if the server takes over 30 seconds to answer
+pub fn mcp_connect(cmd: &Value, args: &Value) -> Result<Value, VmError> {
+    let mut client = Client::spawn(cmd, args)?;
+    client.request(
+        "initialize",
+        json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "fusabi", "version": env!("CARGO_PKG_VERSION") },
+        }),
+    )?;
+    client.notify("notifications/initialized", json!({}))?;
+
+    let mut next_id = NEXT_CLIENT_ID.lock().unwrap();
+    let id = *next_id;
+    *next_id += 1;
+    CLIENTS
+        .lock()
+        .unwrap()
+        .insert(id, Arc::new(Mutex::new(client)));
+
+    Ok(Value::Int(id))
+}
+
+/// Mcp.listTools : int -> ToolInfo list
+/// Lists the server's tools as { name; description; inputSchema } records
+pub fn mcp_list_tools(handle: &Value) -> Result<Value, VmError> {
+    let client = client(handle)?;
+    let result = client.lock().unwrap().request("tools/list", json!({}))?;
+
+    let tools = result
+        .get("tools")
+        .and_then(|tools| tools.as_array())
+        .map(|tools| tools.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|tool| {
+            Ok(record(vec![
+                (
+                    "name",
+                    Value::Str(tool["name"].as_str().unwrap_or("").into()),
+                ),
+                (
+                    "description",
+                    Value::Str(tool["description"].as_str().unwrap_or("").into()),
+                ),
+                (
+                    "inputSchema",
+                    json_value_to_fusabi(tool.get("inputSchema").unwrap_or(&json!({})))?,
+                ),
+            ]))
+        })
+        .collect::<Result<Vec<_>, VmError>>()?;
+    Ok(Value::vec_to_cons(tools))
+}
+
+/// Mcp.callTool : int -> string -> 'a -> ToolResult
+/// Calls a tool with a record of arguments and returns { isError; text; content },
+/// where text joins the text items of content
+pub fn mcp_call_tool(handle: &Value, name: &Value, arguments: &Value) -> Result<Value, VmError> {
+    let client = client(handle)?;
+    let name = string_arg(name)?;
+    let arguments = match arguments {
+        Value::Unit => json!({}),
+        other => fusabi_value_to_json(other)?,
+    };
+    let result = client.lock().unwrap().request(
+        "tools/call",
+        json!({ "name": name, "arguments": arguments }),
+    )?;
+
+    let text = result
+        .get("content")
+        .and_then(|content| content.as_array())
+        .map(|content| content.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|item| item.get("text").and_then(|text| text.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(record(vec![
+        (
+            "isError",
+            Value::Bool(result["isError"].as_bool().unwrap_or(false)),
+        ),
+        ("text", Value::Str(text)),
+        (
+            "content",
+            Value::vec_to_cons(json_list(result.get("content"))?),
+        ),
+    ]))
+}
+
+/// Mcp.readResource : int -> string -> ResourceContents list
+/// Reads a resource by URI, returning its contents as { uri; mimeType; text } records
+pub fn mcp_read_resource(handle: &Value, uri: &Value) -> Result<Value, VmError> {
+    let client = client(handle)?;
+    let uri = string_arg(uri)?;
+    let result = client
+        .lock()
+        .unwrap()
+        .request("resources/read", json!({ "uri": uri }))?;
+    Ok(Value::vec_to_cons(json_list(result.get("contents"))?))
+}
+
+/// Mcp.close : int -> unit
+/// Stops the server and invalidates the handle
+pub fn mcp_close(handle: &Value) -> Result<Value, VmError> {
+    let client = client(handle)?;
+    if let Value::Int(id) = handle {
+        CLIENTS.lock().unwrap().remove(id);
+    }
+    // The process is stopped once no call is using the client any more
+    drop(client);
+    Ok(Value::Unit)
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use super::*;
+
+    /// A server answering with canned responses, echoing request ids
+    const STUB_SERVER: &str = r##"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9][0-9]*\).*/\1/p')
+  case "$line" in
+    *'"initialize"'*)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"protocolVersion\":\"2024-11-05\",\"capabilities\":{\"tools\":{}},\"serverInfo\":{\"name\":\"stub\",\"version\":\"1\"}}}" ;;
+    *'"tools/list"'*)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"tools\":[{\"name\":\"echo\",\"description\":\"Echo text\",\"inputSchema\":{\"type\":\"object\"}}]}}" ;;
+    *'"tools/call"'*)
+      echo '{"jsonrpc":"2.0","method":"notifications/message","params":{"level":"info","data":"calling"}}'
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"content\":[{\"type\":\"text\",\"text\":\"hello\"},{\"type\":\"text\",\"text\":\"world\"}]}}" ;;
+    *'"resources/read"'*)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"contents\":[{\"uri\":\"docs://readme\",\"mimeType\":\"text/plain\",\"text\":\"# Readme\"}]}}" ;;
+    *'"id"'*)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"error\":{\"code\":-32601,\"message\":\"Method not found\"}}" ;;
+  esac
+done
+"##;
+
+    fn connect() -> Value {
+        let args = Value::vec_to_cons(vec![
+            Value::Str("-c".to_string()),
+            Value::Str(STUB_SERVER.to_string()),
+        ]);
+        mcp_connect(&Value::Str("sh".to_string()), &args).unwrap()
+    }
+
+    #[test]
+    fn test_list_and_call_tools() {
+        let handle = connect();
+
+        let tools = mcp_list_tools(&handle).unwrap().list_to_vec().unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(
+            tools[0].record_get("name").unwrap(),
+            Value::Str("echo".into())
+        );
+        assert!(tools[0].record_get("inputSchema").unwrap().is_record());
+
+        let arguments = record(vec![("text", Value::Str("hi".into()))]);
+        let result = mcp_call_tool(&handle, &Value::Str("echo".into()), &arguments).unwrap();
+        assert_eq!(result.record_get("isError").unwrap(), Value::Bool(false));
+        assert_eq!(
+            result.record_get("text").unwrap(),
+            Value::Str("hello\nworld".into())
+        );
+
+        mcp_close(&handle).unwrap();
+        assert!(mcp_list_tools(&handle).is_err());
+    }
+
+    #[test]
+    fn test_read_resource() {
+        let handle = connect();
+        let contents = mcp_read_resource(&handle, &Value::Str("docs://readme".into()))
+            .unwrap()
+            .list_to_vec()
+            .unwrap();
+        assert_eq!(
+            contents[0].record_get("text").unwrap(),
+            Value::Str("# Readme".into())
+        );
+        mcp_close(&handle).unwrap();
+    }
+
+    #[test]
+    fn test_server_errors() {
+        let handle = connect();
+        let client = client(&handle).unwrap();
+        let error = client
+            .lock()
+            .unwrap()
+            .request("bogus", json!({}))
+            .unwrap_err();
+        assert!(error.to_string().contains("-32601"), "{}", error);
+        mcp_close(&handle).unwrap();
+
+        let missing = Value::Str("/nonexistent/mcp-server".into());
+        assert!(mcp_connect(&missing, &Value::Nil).is_err());
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        // Reads requests and never answers
+        let args = Value::vec_to_cons(vec![
+            Value::Str("-c".to_string()),
+            Value::Str("while IFS= read -r line; do :; done".to_string()),
+        ]);
+        let mut client = Client::spawn(&Value::Str("sh".to_string()), &args).unwrap();
+        client.timeout = Duration::from_millis(100);
+        let error = client.request("tools/list", json!({})).unwrap_err();
+        assert!(error.to_string().contains("did not answer"), "{}", error);
+    }
+}
@@ -20,6 +20,7 @@ pub mod script;
 pub mod string;
 pub mod terminal_control;
 pub mod terminal_info;
+pub mod test;
 pub mod time;
 pub mod ui_formatting;
 pub mod url;
@@ -27,6 +28,15 @@ pub mod url;
 #[cfg(feature = "json")]
 pub mod json;
 
+#[cfg(feature = "json")]
+pub mod mcp;
+
+#[cfg(feature = "json")]
+mod shape;
+
+#[cfg(feature = "toml")]
+pub mod toml;
+
 #[cfg(feature = "osc")]
 pub mod net;
 
@@ -36,6 +46,9 @@ pub mod http;
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
 
+#[cfg(feature = "regex")]
+pub mod regex;
+
 use crate::value::Value;
 use crate::vm::{Vm, VmError};
 use std::collections::HashMap;
@@ -304,6 +317,9 @@ pub fn register_stdlib(vm: &mut Vm) {
         registry.register("Process.setEnv", |_vm, args| {
             wrap_binary(args, process::process_set_env)
         });
+        registry.register("Process.args", |vm, args| {
+            wrap_unary(args, |_| Ok(process::process_args(vm.args())))
+        });
         registry.register("Process.cwd", |_vm, args| {
             wrap_unary(args, process::process_cwd)
         });
@@ -347,6 +363,51 @@ pub fn register_stdlib(vm: &mut Vm) {
         registry.register("Url.encode", |_vm, args| wrap_unary(args, url::url_encode));
         registry.register("Url.decode", |_vm, args| wrap_unary(args, url::url_decode));
 
+        // Test functions
+        registry.register("Test.equal", |_vm, args| {
+            wrap_binary(args, test::test_equal)
+        });
+        registry.register("Test.notEqual", |_vm, args| {
+            wrap_binary(args, test::test_not_equal)
+        });
+        registry.register("Test.isTrue", |_vm, args| {
+            wrap_unary(args, test::test_is_true)
+        });
+        registry.register("Test.fail", |_vm, args| wrap_unary(args, test::test_fail));
+        registry.register("Test.throws", test::test_throws);
+        registry.register("Test.forAll", test::test_for_all);
+        registry.register("Gen.range", |_vm, args| wrap_binary(args, test::gen_range));
+        registry.register("Gen.list", |_vm, args| wrap_unary(args, test::gen_list));
+        registry.register("Gen.oneOf", |_vm, args| wrap_unary(args, test::gen_one_of));
+        registry.register("Gen.pair", |_vm, args| wrap_binary(args, test::gen_pair));
+        registry.register("Gen.map", |_vm, args| wrap_binary(args, test::gen_map));
+
+        // Regex functions (if regex feature is enabled)
+        #[cfg(feature = "regex")]
+        {
+            registry.register("Regex.isMatch", |_vm, args| {
+                wrap_binary(args, regex::regex_is_match)
+            });
+            registry.register("Regex.find", |_vm, args| {
+                wrap_binary(args, regex::regex_find)
+            });
+            registry.register("Regex.findAll", |_vm, args| {
+                wrap_binary(args, regex::regex_find_all)
+            });
+            registry.register("Regex.replace", |_vm, args| {
+                wrap_ternary(args, regex::regex_replace)
+            });
+            registry.register("Regex.split", |_vm, args| {
+                wrap_binary(args, regex::regex_split)
+            });
+            registry.register("Regex.captures", |_vm, args| {
+                wrap_binary(args, regex::regex_captures)
+            });
+            registry.register("Regex.namedCaptures", |_vm, args| {
+                wrap_binary(args, regex::regex_named_captures)
+            });
+        }
+
         // Json functions (if json feature is enabled)
         #[cfg(feature = "json")]
         {
@@ -357,6 +418,45 @@ pub fn register_stdlib(vm: &mut Vm) {
             registry.register("Json.stringifyPretty", |_vm, args| {
                 wrap_unary(args, json::json_stringify_pretty)
             });
+            registry.register("Json.parseAs", |_vm, args| {
+                wrap_binary(args, json::json_parse_as)
+            });
+            registry.register("Json.serializeAs", |_vm, args| {
+                wrap_binary(args, json::json_serialize_as)
+            });
+        }
+
+        // Toml functions (if toml feature is enabled)
+        #[cfg(feature = "toml")]
+        {
+            registry.register("Toml.parse", |_vm, args| wrap_unary(args, toml::toml_parse));
+            registry.register("Toml.stringify", |_vm, args| {
+                wrap_unary(args, toml::toml_stringify)
+            });
+            registry.register("Toml.parseAs", |_vm, args| {
+                wrap_binary(args, toml::toml_parse_as)
+            });
+            registry.register("Toml.serializeAs", |_vm, args| {
+                wrap_binary(args, toml::toml_serialize_as)
+            });
+        }
+
+        // Mcp client functions (if json feature is enabled)
+        #[cfg(feature = "json")]
+        {
+            registry.register("Mcp.connect", |_vm, args| {
+                wrap_binary(args, mcp::mcp_connect)
+            });
+            registry.register("Mcp.listTools", |_vm, args| {
+                wrap_unary(args, mcp::mcp_list_tools)
+            });
+            registry.register("Mcp.callTool", |_vm, args| {
+                wrap_ternary(args, mcp::mcp_call_tool)
+            });
+            registry.register("Mcp.readResource", |_vm, args| {
+                wrap_binary(args, mcp::mcp_read_resource)
+            });
+            registry.register("Mcp.close", |_vm, args| wrap_unary(args, mcp::mcp_close));
         }
 
         // Net.Osc functions (if osc feature is enabled)
@@ -711,6 +811,7 @@ pub fn register_stdlib(vm: &mut Vm) {
     process_fields.insert("runShell".to_string(), native("Process.runShell", 1));
     process_fields.insert("env".to_string(), native("Process.env", 1));
     process_fields.insert("setEnv".to_string(), native("Process.setEnv", 2));
+    process_fields.insert("args".to_string(), native("Process.args", 1));
     process_fields.insert("cwd".to_string(), native("Process.cwd", 1));
     vm.globals.insert(
         "Process".to_string(),
@@ -753,6 +854,55 @@ pub fn register_stdlib(vm: &mut Vm) {
         Value::Record(Arc::new(Mutex::new(url_fields))),
     );
 
+    // Test Module
+    let mut test_fields = HashMap::new();
+    test_fields.insert("equal".to_string(), native("Test.equal", 2));
+    test_fields.insert("notEqual".to_string(), native("Test.notEqual", 2));
+    test_fields.insert("isTrue".to_string(), native("Test.isTrue", 1));
+    test_fields.insert("fail".to_string(), native("Test.fail", 1));
+    test_fields.insert("throws".to_string(), native("Test.throws", 1));
+    test_fields.insert("forAll".to_string(), native("Test.forAll", 2));
+    vm.globals.insert(
+        "Test".to_string(),
+        Value::Record(Arc::new(Mutex::new(test_fields))),
+    );
+
+    // Gen Module (generators for Test.forAll)
+    let mut gen_fields = HashMap::new();
+    gen_fields.insert("int".to_string(), test::gen_int());
+    gen_fields.insert("float".to_string(), test::gen_float());
+    gen_fields.insert("bool".to_string(), test::gen_bool());
+    gen_fields.insert("string".to_string(), test::gen_string());
+    gen_fields.insert("range".to_string(), native("Gen.range", 2));
+    gen_fields.insert("list".to_string(), native("Gen.list", 1));
+    gen_fields.insert("oneOf".to_string(), native("Gen.oneOf", 1));
+    gen_fields.insert("pair".to_string(), native("Gen.pair", 2));
+    gen_fields.insert("map".to_string(), native("Gen.map", 2));
+    vm.globals.insert(
+        "Gen".to_string(),
+        Value::Record(Arc::new(Mutex::new(gen_fields))),
+    );
+
+    // Regex Module (if regex feature is enabled)
+    #[cfg(feature = "regex")]
+    {
+        let mut regex_fields = HashMap::new();
+        regex_fields.insert("isMatch".to_string(), native("Regex.isMatch", 2));
+        regex_fields.insert("find".to_string(), native("Regex.find", 2));
+        regex_fields.insert("findAll".to_string(), native("Regex.findAll", 2));
+        regex_fields.insert("replace".to_string(), native("Regex.replace", 3));
+        regex_fields.insert("split".to_string(), native("Regex.split", 2));
+        regex_fields.insert("captures".to_string(), native("Regex.captures", 2));
+        regex_fields.insert(
+            "namedCaptures".to_string(),
+            native("Regex.namedCaptures", 2),
+        );
+        vm.globals.insert(
+            "Regex".to_string(),
+            Value::Record(Arc::new(Mutex::new(regex_fields))),
+        );
+    }
+
     // Json Module (if json feature is enabled)
     #[cfg(feature = "json")]
     {
@@ -763,12 +913,43 @@ pub fn register_stdlib(vm: &mut Vm) {
             "stringifyPretty".to_string(),
             native("Json.stringifyPretty", 1),
         );
+        json_fields.insert("parseAs".to_string(), native("Json.parseAs", 2));
+        json_fields.insert("serializeAs".to_string(), native("Json.serializeAs", 2));
         vm.globals.insert(
             "Json".to_string(),
             Value::Record(Arc::new(Mutex::new(json_fields))),
         );
     }
 
+    // Toml Module (if toml feature is enabled)
+    #[cfg(feature = "toml")]
+    {
+        let mut toml_fields = HashMap::new();
+        toml_fields.insert("parse".to_string(), native("Toml.parse", 1));
+        toml_fields.insert("stringify".to_string(), native("Toml.stringify", 1));
+        toml_fields.insert("parseAs".to_string(), native("Toml.parseAs", 2));
+        toml_fields.insert("serializeAs".to_string(), native("Toml.serializeAs", 2));
+        vm.globals.insert(
+            "Toml".to_string(),
+            Value::Record(Arc::new(Mutex::new(toml_fields))),
+        );
+    }
+
+    // Mcp Module (if json feature is enabled)
+    #[cfg(feature = "json")]
+    {
+        let mut mcp_fields = HashMap::new();
+        mcp_fields.insert("connect".to_string(), native("Mcp.connect", 2));
+        mcp_fields.insert("listTools".to_string(), native("Mcp.listTools", 1));
+        mcp_fields.insert("callTool".to_string(), native("Mcp.callTool", 3));
+        mcp_fields.insert("readResource".to_string(), native("Mcp.readResource", 2));
+        mcp_fields.insert("close".to_string(), native("Mcp.close", 1));
+        vm.globals.insert(
+            "Mcp".to_string(),
+            Value::Record(Arc::new(Mutex::new(mcp_fields))),
+        );
+    }
+
     // Osc Module (if osc feature is enabled)
     #[cfg(feature = "osc")]
     {
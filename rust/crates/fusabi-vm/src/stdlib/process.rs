@@ -8,7 +8,7 @@
 use crate::value::Value;
 use crate::vm::VmError;
 use std::collections::HashMap;
-use std::process::Command;
+use std::process::{Child, Command, Stdio};
 use std::sync::{Arc, Mutex};
 
 /// Process.run : string -> string list -> ProcessResult
@@ -126,6 +126,18 @@ pub fn process_set_env(var_name: &Value, value: &Value) -> Result<Value, VmError
     Ok(Value::Unit)
 }
 
+/// Process.args : unit -> string list
+/// Gets the command-line arguments passed to the script, without the program
+/// name: those after the script in `fus run script.fsx a b`. A bundled
+/// executable passes on all of its arguments.
+///
+/// Example:
+///   Process.args ()
+///   // Returns ["input.txt"; "--verbose"]
+pub fn process_args(args: &[String]) -> Value {
+    Value::vec_to_cons(args.iter().cloned().map(Value::Str).collect())
+}
+
 /// Process.cwd : unit -> string
 /// Gets the current working directory.
 ///
@@ -143,6 +155,24 @@ pub fn process_cwd(_unit: &Value) -> Result<Value, VmError> {
     Ok(Value::Str(cwd_str.to_string()))
 }
 
+/// Start a command (not through a shell) with piped stdin and stdout, for
+/// modules that talk to a long-running child process
+pub(crate) fn spawn_piped(cmd: &Value, args: &Value) -> Result<Child, VmError> {
+    let cmd_str = cmd.as_str().ok_or_else(|| VmError::TypeMismatch {
+        expected: "string",
+        got: cmd.type_name(),
+    })?;
+    let args_vec = list_to_string_vec(args)?;
+
+    Command::new(cmd_str)
+        .args(&args_vec)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| VmError::Runtime(format!("Failed to execute command '{}': {}", cmd_str, e)))
+}
+
 // Helper functions
 
 /// Convert a Fusabi list to Vec<String>, validating that all elements are strings
@@ -472,6 +502,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_process_args() {
+        let args = vec!["input.txt".to_string(), "--verbose".to_string()];
+        let result = process_args(&args);
+        assert_eq!(list_to_string_vec(&result).unwrap(), args);
+        assert_eq!(process_args(&[]), Value::Nil);
+    }
+
     #[test]
     fn test_list_to_string_vec_empty() {
         let list = Value::Nil;
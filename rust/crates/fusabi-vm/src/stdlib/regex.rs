@@ -0,0 +1,216 @@
+// Fusabi Regex Standard Library
+// Provides regular expression matching, searching, replacing and splitting
+
+use crate::value::Value;
+use crate::vm::VmError;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Compiled patterns are cached so scripts can pass pattern strings in loops
+const CACHE_LIMIT: usize = 64;
+
+lazy_static::lazy_static! {
+    static ref CACHE: Mutex<HashMap<String, Regex>> = Mutex::new(HashMap::new());
+}
+
+fn expect_str(value: &Value) -> Result<&str, VmError> {
+    match value {
+        Value::Str(s) => Ok(s),
+        _ => Err(VmError::TypeMismatch {
+            expected: "string",
+            got: value.type_name(),
+        }),
+    }
+}
+
+/// Look up or compile `pattern`
+fn compile(pattern: &Value) -> Result<Regex, VmError> {
+    let pattern = expect_str(pattern)?;
+    let mut cache = CACHE.lock().unwrap();
+    if let Some(regex) = cache.get(pattern) {
+        return Ok(regex.clone());
+    }
+    let regex = Regex::new(pattern)
+        .map_err(|e| VmError::Runtime(format!("Invalid regex '{}': {}", pattern, e)))?;
+    if cache.len() >= CACHE_LIMIT {
+        cache.clear();
+    }
+    cache.insert(pattern.to_string(), regex.clone());
+    Ok(regex)
+}
+
+fn some(value: Value) -> Value {
+    Value::Variant {
+        type_name: "Option".to_string(),
+        variant_name: "Some".to_string(),
+        fields: vec![value],
+    }
+}
+
+fn none() -> Value {
+    Value::Variant {
+        type_name: "Option".to_string(),
+        variant_name: "None".to_string(),
+        fields: vec![],
+    }
+}
+
+fn str_list<'a>(items: impl Iterator<Item = &'a str>) -> Value {
+    Value::vec_to_cons(items.map(|s| Value::Str(s.to_string())).collect())
+}
+
+/// Regex.isMatch : string -> string -> bool
+/// Check whether the pattern matches anywhere in the string
+pub fn regex_is_match(pattern: &Value, s: &Value) -> Result<Value, VmError> {
+    let regex = compile(pattern)?;
+    Ok(Value::Bool(regex.is_match(expect_str(s)?)))
+}
+
+/// Regex.find : string -> string -> string option
+/// Returns the first match of the pattern, or None
+pub fn regex_find(pattern: &Value, s: &Value) -> Result<Value, VmError> {
+    let regex = compile(pattern)?;
+    Ok(match regex.find(expect_str(s)?) {
+        Some(m) => some(Value::Str(m.as_str().to_string())),
+        None => none(),
+    })
+}
+
+/// Regex.findAll : string -> string -> string list
+/// Returns every non-overlapping match of the pattern
+pub fn regex_find_all(pattern: &Value, s: &Value) -> Result<Value, VmError> {
+    let regex = compile(pattern)?;
+    Ok(str_list(
+        regex.find_iter(expect_str(s)?).map(|m| m.as_str()),
+    ))
+}
+
+/// Regex.replace : string -> string -> string -> string
+/// Replaces every match; `$1` and `$name` in the replacement refer to groups
+pub fn regex_replace(pattern: &Value, replacement: &Value, s: &Value) -> Result<Value, VmError> {
+    let regex = compile(pattern)?;
+    let replacement = expect_str(replacement)?;
+    Ok(Value::Str(
+        regex.replace_all(expect_str(s)?, replacement).into_owned(),
+    ))
+}
+
+/// Regex.split : string -> string -> string list
+/// Splits the string on every match of the pattern
+pub fn regex_split(pattern: &Value, s: &Value) -> Result<Value, VmError> {
+    let regex = compile(pattern)?;
+    Ok(str_list(regex.split(expect_str(s)?)))
+}
+
+/// Regex.captures : string -> string -> string list option
+/// Returns the whole match followed by each group of the first match;
+/// groups that did not participate are empty strings
+pub fn regex_captures(pattern: &Value, s: &Value) -> Result<Value, VmError> {
+    let regex = compile(pattern)?;
+    Ok(match regex.captures(expect_str(s)?) {
+        Some(caps) => some(str_list(caps.iter().map(|m| m.map_or("", |m| m.as_str())))),
+        None => none(),
+    })
+}
+
+/// Regex.namedCaptures : string -> string -> record option
+/// Returns a record with a string field per named group of the first match;
+/// groups that did not participate are empty strings
+pub fn regex_named_captures(pattern: &Value, s: &Value) -> Result<Value, VmError> {
+    let regex = compile(pattern)?;
+    let Some(caps) = regex.captures(expect_str(s)?) else {
+        return Ok(none());
+    };
+    let fields = regex
+        .capture_names()
+        .flatten()
+        .map(|name| {
+            let text = caps.name(name).map_or("", |m| m.as_str());
+            (name.to_string(), Value::Str(text.to_string()))
+        })
+        .collect();
+    Ok(some(Value::Record(Arc::new(Mutex::new(fields)))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(text: &str) -> Value {
+        Value::Str(text.to_string())
+    }
+
+    fn strings(list: Value) -> Vec<String> {
+        list.list_to_vec()
+            .unwrap()
+            .into_iter()
+            .map(|v| v.as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_is_match_and_find() {
+        assert_eq!(
+            regex_is_match(&s(r"\d+"), &s("abc 42")).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            regex_find(&s(r"\d+"), &s("abc 42 7")).unwrap(),
+            some(s("42"))
+        );
+        assert_eq!(regex_find(&s(r"\d+"), &s("abc")).unwrap(), none());
+        assert_eq!(
+            strings(regex_find_all(&s(r"\d+"), &s("1 22 333")).unwrap()),
+            vec!["1", "22", "333"]
+        );
+    }
+
+    #[test]
+    fn test_replace_and_split() {
+        assert_eq!(
+            regex_replace(&s(r"(?P<k>\w+)=(\w+)"), &s("$2:$k"), &s("a=1 b=2")).unwrap(),
+            s("1:a 2:b")
+        );
+        assert_eq!(
+            strings(regex_split(&s(r"\s*,\s*"), &s("a , b,c")).unwrap()),
+            vec!["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_captures() {
+        let result = regex_captures(&s(r"(\d+)-(x)?(\d+)"), &s("10-20")).unwrap();
+        match result {
+            Value::Variant { fields, .. } => {
+                assert_eq!(strings(fields[0].clone()), vec!["10-20", "10", "", "20"])
+            }
+            other => panic!("expected Some, got {:?}", other),
+        }
+        assert_eq!(regex_captures(&s("z"), &s("abc")).unwrap(), none());
+    }
+
+    #[test]
+    fn test_named_captures() {
+        let result =
+            regex_named_captures(&s(r"(?P<ts>\d+) (?P<level>\w+)"), &s("1700 INFO up")).unwrap();
+        match result {
+            Value::Variant { fields, .. } => {
+                let Value::Record(record) = &fields[0] else {
+                    panic!("expected a record");
+                };
+                let record = record.lock().unwrap();
+                assert_eq!(record.get("ts"), Some(&s("1700")));
+                assert_eq!(record.get("level"), Some(&s("INFO")));
+                assert_eq!(record.len(), 2);
+            }
+            other => panic!("expected Some, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let err = regex_is_match(&s("(unclosed"), &s("x")).unwrap_err();
+        assert!(matches!(err, VmError::Runtime(msg) if msg.contains("Invalid regex")));
+    }
+}
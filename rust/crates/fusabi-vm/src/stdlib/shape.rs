@@ -0,0 +1,398 @@
+// Fusabi Typed Decoding
+// Converts JSON data to and from the records and unions a type provider
+// generated, checking it against their shape
+//
+// A shape is the JSON document type providers bake into `Json.parseAs` and
+// friends:
+//
+//   {"root": "Config",
+//    "types": {"Config": {"record": [["name", "string"], ["level", "Level option"]]},
+//              "Level": {"union": [{"name": "Debug", "value": "debug"},
+//                                  {"name": "Custom", "payload": "int"}]}}}
+//
+// Field types use the providers' postfix syntax (`int list`, `Pool option`).
+
+use super::json::{fusabi_value_to_json, json_value_to_fusabi};
+use crate::value::Value;
+use crate::vm::VmError;
+use serde_json::{Map, Value as Json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A parsed shape document
+pub(crate) struct Shape {
+    root: String,
+    types: Map<String, Json>,
+}
+
+impl Shape {
+    pub(crate) fn parse(shape: &Value) -> Result<Self, VmError> {
+        let Value::Str(text) = shape else {
+            return Err(VmError::TypeMismatch {
+                expected: "string",
+                got: shape.type_name(),
+            });
+        };
+        let invalid = || VmError::Runtime("Invalid type shape".to_string());
+        let mut doc: Json = serde_json::from_str(text).map_err(|_| invalid())?;
+        let root = doc
+            .get("root")
+            .and_then(Json::as_str)
+            .ok_or_else(invalid)?
+            .to_string();
+        let types = match doc.get_mut("types").map(Json::take) {
+            Some(Json::Object(types)) => types,
+            _ => return Err(invalid()),
+        };
+        Ok(Self { root, types })
+    }
+
+    /// Convert `json` to a value of the root type
+    pub(crate) fn decode(&self, json: &Json) -> Result<Value, String> {
+        self.decode_as(json, &self.root, "")
+    }
+
+    /// Convert a value of the root type to JSON, leaving out `None` fields
+    pub(crate) fn encode(&self, value: &Value) -> Result<Json, VmError> {
+        self.encode_as(value, &self.root)
+    }
+
+    fn decode_as(&self, json: &Json, ty: &str, path: &str) -> Result<Value, String> {
+        let ty = ty.trim();
+        if let Some(inner) = ty.strip_suffix(" option") {
+            return Ok(match json {
+                Json::Null => option(None),
+                json => option(Some(self.decode_as(json, inner, path)?)),
+            });
+        }
+        if let Some(inner) = ty.strip_suffix(" list") {
+            let items = self.decode_items(json, inner, path)?;
+            return Ok(Value::vec_to_cons(items));
+        }
+        if let Some(inner) = ty.strip_suffix(" array") {
+            let items = self.decode_items(json, inner, path)?;
+            return Ok(Value::Array(Arc::new(Mutex::new(items))));
+        }
+
+        let value = match (ty, json) {
+            ("int", Json::Number(n)) => n.as_i64().map(Value::Int),
+            ("float", Json::Number(n)) => n.as_f64().map(Value::Float),
+            ("bool", Json::Bool(b)) => Some(Value::Bool(*b)),
+            ("string", Json::String(s)) => Some(Value::Str(s.clone())),
+            ("unit", Json::Null) => Some(Value::Unit),
+            ("int" | "float" | "bool" | "string" | "unit", _) => None,
+            _ => match self.types.get(ty) {
+                Some(def) => return self.decode_named(json, ty, def, path),
+                // `json` and anything the shape does not describe pass through
+                None => return json_value_to_fusabi(json).map_err(|e| e.to_string()),
+            },
+        };
+        value.ok_or_else(|| mismatch(path, ty, json))
+    }
+
+    fn decode_items(&self, json: &Json, ty: &str, path: &str) -> Result<Vec<Value>, String> {
+        let Json::Array(items) = json else {
+            return Err(mismatch(path, "array", json));
+        };
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| self.decode_as(item, ty, &format!("{}[{}]", path, i)))
+            .collect()
+    }
+
+    fn decode_named(
+        &self,
+        json: &Json,
+        name: &str,
+        def: &Json,
+        path: &str,
+    ) -> Result<Value, String> {
+        if let Some(fields) = def.get("record").and_then(Json::as_array) {
+            let Json::Object(object) = json else {
+                return Err(mismatch(path, "object", json));
+            };
+            let mut record = HashMap::new();
+            for field in fields {
+                let (Some(field), Some(ty)) = (field[0].as_str(), field[1].as_str()) else {
+                    continue;
+                };
+                let field_path = if path.is_empty() {
+                    field.to_string()
+                } else {
+                    format!("{}.{}", path, field)
+                };
+                let value = match object.get(field) {
+                    Some(value) => self.decode_as(value, ty, &field_path)?,
+                    None if ty.trim().ends_with(" option") => option(None),
+                    None => return Err(format!("{}: missing field", field_path)),
+                };
+                record.insert(field.to_string(), value);
+            }
+            return Ok(Value::Record(Arc::new(Mutex::new(record))));
+        }
+
+        let cases = def.get("union").and_then(Json::as_array);
+        for case in cases.into_iter().flatten() {
+            let Some(case_name) = case.get("name").and_then(Json::as_str) else {
+                continue;
+            };
+            let fields = match case.get("payload").and_then(Json::as_str) {
+                Some(payload) => match self.decode_as(json, payload, path) {
+                    Ok(value) => vec![value],
+                    Err(_) => continue,
+                },
+                None if matches_simple_case(json, case, case_name) => vec![],
+                None => continue,
+            };
+            return Ok(Value::Variant {
+                type_name: name.to_string(),
+                variant_name: case_name.to_string(),
+                fields,
+            });
+        }
+        Err(format!("{}does not match any case of {}", at(path), name))
+    }
+
+    fn encode_as(&self, value: &Value, ty: &str) -> Result<Json, VmError> {
+        let ty = ty.trim();
+        if let Some(inner) = ty.strip_suffix(" option") {
+            return match value {
+                Value::Variant { fields, .. } if fields.len() == 1 => {
+                    self.encode_as(&fields[0], inner)
+                }
+                _ => Ok(Json::Null),
+            };
+        }
+        if let Some(inner) = ty
+            .strip_suffix(" list")
+            .or_else(|| ty.strip_suffix(" array"))
+        {
+            let items = match value {
+                Value::Array(items) => items.lock().unwrap().clone(),
+                list => list.list_to_vec().ok_or(VmError::TypeMismatch {
+                    expected: "list",
+                    got: list.type_name(),
+                })?,
+            };
+            return items
+                .iter()
+                .map(|item| self.encode_as(item, inner))
+                .collect::<Result<_, _>>()
+                .map(Json::Array);
+        }
+
+        let Some(def) = self.types.get(ty) else {
+            return fusabi_value_to_json(value);
+        };
+        if let Some(fields) = def.get("record").and_then(Json::as_array) {
+            let Value::Record(record) = value else {
+                return Err(VmError::TypeMismatch {
+                    expected: "record",
+                    got: value.type_name(),
+                });
+            };
+            let record = record.lock().unwrap();
+            let mut object = Map::new();
+            for field in fields {
+                let (Some(field), Some(ty)) = (field[0].as_str(), field[1].as_str()) else {
+                    continue;
+                };
+                match record
+                    .get(field)
+                    .map(|v| self.encode_as(v, ty))
+                    .transpose()?
+                {
+                    Some(Json::Null) | None => {}
+                    Some(json) => {
+                        object.insert(field.to_string(), json);
+                    }
+                }
+            }
+            return Ok(Json::Object(object));
+        }
+
+        let Value::Variant {
+            variant_name,
+            fields,
+            ..
+        } = value
+        else {
+            return fusabi_value_to_json(value);
+        };
+        let cases = def.get("union").and_then(Json::as_array);
+        let case = cases
+            .into_iter()
+            .flatten()
+            .find(|case| case.get("name").and_then(Json::as_str) == Some(variant_name));
+        match (case, fields.first()) {
+            (Some(case), Some(payload)) => {
+                let ty = case.get("payload").and_then(Json::as_str).unwrap_or("json");
+                self.encode_as(payload, ty)
+            }
+            (Some(case), None) => Ok(case
+                .get("value")
+                .cloned()
+                .unwrap_or_else(|| Json::String(variant_name.clone()))),
+            (None, _) => Err(VmError::Runtime(format!(
+                "{} is not a case of {}",
+                variant_name, ty
+            ))),
+        }
+    }
+}
+
+/// Whether `json` stands for a case without payload: its literal value, or
+/// the case name itself
+fn matches_simple_case(json: &Json, case: &Json, name: &str) -> bool {
+    match case.get("value") {
+        Some(value) => value == json,
+        None => json.as_str() == Some(name) || json.is_null() && name == "Null",
+    }
+}
+
+fn option(value: Option<Value>) -> Value {
+    Value::Variant {
+        type_name: "Option".to_string(),
+        variant_name: if value.is_some() { "Some" } else { "None" }.to_string(),
+        fields: value.into_iter().collect(),
+    }
+}
+
+/// `Ok value` or `Error message`
+pub(crate) fn result(value: Result<Value, String>) -> Value {
+    let (variant_name, field) = match value {
+        Ok(value) => ("Ok", value),
+        Err(message) => ("Error", Value::Str(message)),
+    };
+    Value::Variant {
+        type_name: "Result".to_string(),
+        variant_name: variant_name.to_string(),
+        fields: vec![field],
+    }
+}
+
+fn at(path: &str) -> String {
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("{}: ", path)
+    }
+}
+
+fn mismatch(path: &str, expected: &str, found: &Json) -> String {
+    let found = match found {
+        Json::Null => "null",
+        Json::Bool(_) => "bool",
+        Json::Number(n) if n.is_f64() => "float",
+        Json::Number(_) => "int",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    };
+    format!("{}expected {}, found {}", at(path), expected, found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn shape() -> Shape {
+        let doc = json!({
+            "root": "Config",
+            "types": {
+                "Config": {"record": [
+                    ["name", "string"],
+                    ["ports", "int list"],
+                    ["level", "Level option"],
+                    ["db", "Db"]
+                ]},
+                "Db": {"record": [["host", "string"], ["pool", "int option"]]},
+                "Level": {"union": [
+                    {"name": "Debug", "value": "debug"},
+                    {"name": "Custom", "payload": "int"}
+                ]}
+            }
+        });
+        Shape::parse(&Value::Str(doc.to_string())).unwrap()
+    }
+
+    fn field(record: &Value, name: &str) -> Value {
+        match record {
+            Value::Record(fields) => fields.lock().unwrap()[name].clone(),
+            other => panic!("expected a record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_and_encode() {
+        let data = json!({
+            "name": "app",
+            "ports": [80, 443],
+            "level": "debug",
+            "db": {"host": "localhost"}
+        });
+        let config = shape().decode(&data).unwrap();
+        assert_eq!(field(&config, "name"), Value::Str("app".to_string()));
+        assert_eq!(
+            field(&config, "ports").list_to_vec().unwrap(),
+            vec![Value::Int(80), Value::Int(443)]
+        );
+        assert_eq!(
+            field(&config, "level"),
+            option(Some(Value::Variant {
+                type_name: "Level".to_string(),
+                variant_name: "Debug".to_string(),
+                fields: vec![],
+            }))
+        );
+        assert_eq!(field(&field(&config, "db"), "pool"), option(None));
+
+        assert_eq!(shape().encode(&config).unwrap(), data);
+    }
+
+    #[test]
+    fn test_union_payloads() {
+        let config = shape()
+            .decode(&json!({"name": "", "ports": [], "level": 3, "db": {"host": ""}}))
+            .unwrap();
+        match field(&config, "level") {
+            Value::Variant { fields, .. } => match &fields[0] {
+                Value::Variant {
+                    variant_name,
+                    fields,
+                    ..
+                } => {
+                    assert_eq!(variant_name, "Custom");
+                    assert_eq!(fields, &vec![Value::Int(3)]);
+                }
+                other => panic!("expected a Level, got {:?}", other),
+            },
+            other => panic!("expected Some, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let err = shape()
+            .decode(&json!({"name": "app", "ports": [80, "x"], "db": {"host": "h"}}))
+            .unwrap_err();
+        assert_eq!(err, "ports[1]: expected int, found string");
+
+        let err = shape()
+            .decode(&json!({"name": "app", "ports": [], "db": {}}))
+            .unwrap_err();
+        assert_eq!(err, "db.host: missing field");
+
+        let err = shape()
+            .decode(&json!({"name": "app", "ports": [], "level": true, "db": {"host": "h"}}))
+            .unwrap_err();
+        assert_eq!(err, "level: does not match any case of Level");
+
+        assert_eq!(
+            shape().decode(&json!([])).unwrap_err(),
+            "expected object, found array"
+        );
+    }
+}
@@ -0,0 +1,578 @@
+// Fusabi Test Standard Library
+// Provides assertions and property-based testing for `fusabi test`
+
+use crate::value::Value;
+use crate::vm::{Vm, VmError};
+
+/// Cases `Test.forAll` tries, unless `FUSABI_TEST_CASES` says otherwise
+const DEFAULT_CASES: usize = 100;
+
+/// Seed `Test.forAll` starts from, unless `FUSABI_TEST_SEED` says otherwise
+const DEFAULT_SEED: u64 = 0x5eed_f05a_b100;
+
+/// Most shrinking steps taken for one failure
+const MAX_SHRINKS: usize = 1000;
+
+const STRING_CHARS: &[u8] =
+    b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 .,;:!?-_/'\"";
+
+/// Format a value for a failure message, quoting strings
+fn show(value: &Value) -> String {
+    match value {
+        Value::Str(s) => format!("{:?}", s),
+        other => other.to_string(),
+    }
+}
+
+/// The message of an error raised by a test, without its category
+fn message(error: VmError) -> String {
+    match error {
+        VmError::AssertionFailed(msg) | VmError::Runtime(msg) => msg,
+        other => other.to_string(),
+    }
+}
+
+/// Errors that stop a test run rather than count as a raised error
+fn is_fatal(error: &VmError) -> bool {
+    matches!(error, VmError::Interrupted | VmError::LimitExceeded(_))
+}
+
+/// Test.equal : 'a -> 'a -> unit
+/// Fails unless the actual value (second) equals the expected value (first)
+pub fn test_equal(expected: &Value, actual: &Value) -> Result<Value, VmError> {
+    if expected == actual {
+        Ok(Value::Unit)
+    } else {
+        Err(VmError::AssertionFailed(format!(
+            "expected {}, got {}",
+            show(expected),
+            show(actual)
+        )))
+    }
+}
+
+/// Test.notEqual : 'a -> 'a -> unit
+/// Fails if the two values are equal
+pub fn test_not_equal(unexpected: &Value, actual: &Value) -> Result<Value, VmError> {
+    if unexpected != actual {
+        Ok(Value::Unit)
+    } else {
+        Err(VmError::AssertionFailed(format!(
+            "expected a value other than {}",
+            show(actual)
+        )))
+    }
+}
+
+/// Test.isTrue : bool -> unit
+/// Fails unless the condition is true
+pub fn test_is_true(condition: &Value) -> Result<Value, VmError> {
+    match condition {
+        Value::Bool(true) => Ok(Value::Unit),
+        Value::Bool(false) => Err(VmError::AssertionFailed(
+            "expected true, got false".to_string(),
+        )),
+        _ => Err(VmError::TypeMismatch {
+            expected: "bool",
+            got: condition.type_name(),
+        }),
+    }
+}
+
+/// Test.fail : string -> 'a
+/// Fails the test with the given message
+pub fn test_fail(msg: &Value) -> Result<Value, VmError> {
+    match msg {
+        Value::Str(s) => Err(VmError::AssertionFailed(s.clone())),
+        _ => Err(VmError::TypeMismatch {
+            expected: "string",
+            got: msg.type_name(),
+        }),
+    }
+}
+
+/// Test.throws : (unit -> 'a) -> string
+/// Calls the function, failing unless it raises an error; returns the
+/// error's message
+pub fn test_throws(vm: &mut Vm, args: &[Value]) -> Result<Value, VmError> {
+    if args.len() != 1 {
+        return Err(VmError::Runtime(format!(
+            "Test.throws expects 1 argument, got {}",
+            args.len()
+        )));
+    }
+
+    match vm.call_value(args[0].clone(), &[Value::Unit]) {
+        Ok(value) => Err(VmError::AssertionFailed(format!(
+            "expected an error, but the function returned {}",
+            show(&value)
+        ))),
+        Err(e) if is_fatal(&e) => Err(e),
+        Err(e) => Ok(Value::Str(message(e))),
+    }
+}
+
+/// Test.forAll : 'a gen -> ('a -> bool) -> unit
+/// Checks a property against values drawn from the generator. The property
+/// fails by returning false or raising an error; the failing value is then
+/// shrunk to a smaller one that still fails.
+pub fn test_for_all(vm: &mut Vm, args: &[Value]) -> Result<Value, VmError> {
+    if args.len() != 2 {
+        return Err(VmError::Runtime(format!(
+            "Test.forAll expects 2 arguments, got {}",
+            args.len()
+        )));
+    }
+    let gen = Gen::from_value(&args[0])?;
+    let property = &args[1];
+
+    let cases = setting("FUSABI_TEST_CASES").map_or(DEFAULT_CASES, |n| n as usize);
+    let seed = setting("FUSABI_TEST_SEED").unwrap_or(DEFAULT_SEED);
+    let mut rng = Rng::new(seed);
+
+    for case in 0..cases {
+        // Values grow with each case, from size 1 to 100
+        let size = 1 + (case * 99 / cases.max(1)) as i64;
+        let raw = gen.generate(&mut rng, size);
+        let value = gen.realize(vm, &raw)?;
+        if let Some(reason) = check(vm, property, value)? {
+            let (raw, reason, shrinks) = shrink_failure(vm, &gen, property, raw, reason)?;
+            let value = gen.realize(vm, &raw)?;
+            return Err(VmError::AssertionFailed(format!(
+                "property failed for {} after {} test(s), shrunk {} time(s) (seed {}): {}",
+                show(&value),
+                case + 1,
+                shrinks,
+                seed,
+                reason
+            )));
+        }
+    }
+    Ok(Value::Unit)
+}
+
+fn setting(name: &str) -> Option<u64> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// Runs the property on a value, returning why it failed, if it did
+fn check(vm: &mut Vm, property: &Value, value: Value) -> Result<Option<String>, VmError> {
+    match vm.call_value(property.clone(), &[value]) {
+        Ok(Value::Bool(false)) => Ok(Some("returned false".to_string())),
+        Ok(_) => Ok(None),
+        Err(e) if is_fatal(&e) => Err(e),
+        Err(e) => Ok(Some(message(e))),
+    }
+}
+
+/// Shrinks a failing raw value for as long as a smaller one still fails
+fn shrink_failure(
+    vm: &mut Vm,
+    gen: &Gen,
+    property: &Value,
+    mut raw: Value,
+    mut reason: String,
+) -> Result<(Value, String, usize), VmError> {
+    let mut shrinks = 0;
+    'outer: while shrinks < MAX_SHRINKS {
+        for candidate in gen.shrink(&raw) {
+            let value = gen.realize(vm, &candidate)?;
+            if let Some(candidate_reason) = check(vm, property, value)? {
+                raw = candidate;
+                reason = candidate_reason;
+                shrinks += 1;
+                continue 'outer;
+            }
+        }
+        break;
+    }
+    Ok((raw, reason, shrinks))
+}
+
+/// A generator, decoded from its `Gen` variant
+///
+/// Generators produce raw values, which `realize` turns into the values a
+/// property sees; shrinking works on raw values, so that mapped generators
+/// shrink through the values they map.
+#[derive(Debug, Clone)]
+enum Gen {
+    Int,
+    Range(i64, i64),
+    Float,
+    Bool,
+    String,
+    List(Box<Gen>),
+    OneOf(Vec<Value>),
+    Pair(Box<Gen>, Box<Gen>),
+    Map(Value, Box<Gen>),
+}
+
+fn gen_value(variant: &str, fields: Vec<Value>) -> Value {
+    Value::Variant {
+        type_name: "Gen".to_string(),
+        variant_name: variant.to_string(),
+        fields,
+    }
+}
+
+fn expect_gen(value: &Value) -> Result<(), VmError> {
+    Gen::from_value(value).map(|_| ())
+}
+
+impl Gen {
+    fn from_value(value: &Value) -> Result<Self, VmError> {
+        let mismatch = || VmError::TypeMismatch {
+            expected: "generator",
+            got: value.type_name(),
+        };
+        let Value::Variant {
+            type_name,
+            variant_name,
+            fields,
+        } = value
+        else {
+            return Err(mismatch());
+        };
+        if type_name != "Gen" {
+            return Err(mismatch());
+        }
+        Ok(match (variant_name.as_str(), fields.as_slice()) {
+            ("Int", []) => Gen::Int,
+            ("Range", [Value::Int(lo), Value::Int(hi)]) => Gen::Range(*lo, *hi),
+            ("Float", []) => Gen::Float,
+            ("Bool", []) => Gen::Bool,
+            ("String", []) => Gen::String,
+            ("List", [element]) => Gen::List(Box::new(Gen::from_value(element)?)),
+            ("OneOf", [choices]) => Gen::OneOf(choices.list_to_vec().unwrap_or_default()),
+            ("Pair", [first, second]) => Gen::Pair(
+                Box::new(Gen::from_value(first)?),
+                Box::new(Gen::from_value(second)?),
+            ),
+            ("Map", [f, inner]) => Gen::Map(f.clone(), Box::new(Gen::from_value(inner)?)),
+            _ => return Err(mismatch()),
+        })
+    }
+
+    fn generate(&self, rng: &mut Rng, size: i64) -> Value {
+        match self {
+            Gen::Int => Value::Int(rng.range(-size, size)),
+            Gen::Range(lo, hi) => Value::Int(rng.range(*lo, *hi)),
+            Gen::Float => Value::Float((rng.unit() * 2.0 - 1.0) * size as f64),
+            Gen::Bool => Value::Bool(rng.range(0, 1) == 1),
+            Gen::String => {
+                let len = rng.range(0, size);
+                let s = (0..len)
+                    .map(|_| STRING_CHARS[rng.range(0, STRING_CHARS.len() as i64 - 1) as usize])
+                    .map(char::from)
+                    .collect();
+                Value::Str(s)
+            }
+            Gen::List(element) => {
+                let len = rng.range(0, size);
+                Value::vec_to_cons((0..len).map(|_| element.generate(rng, size)).collect())
+            }
+            Gen::OneOf(choices) => Value::Int(rng.range(0, choices.len() as i64 - 1)),
+            Gen::Pair(first, second) => {
+                Value::Tuple(vec![first.generate(rng, size), second.generate(rng, size)])
+            }
+            Gen::Map(_, inner) => inner.generate(rng, size),
+        }
+    }
+
+    fn realize(&self, vm: &mut Vm, raw: &Value) -> Result<Value, VmError> {
+        match (self, raw) {
+            (Gen::List(element), _) => {
+                let items = raw.list_to_vec().unwrap_or_default();
+                let items = items
+                    .iter()
+                    .map(|item| element.realize(vm, item))
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::vec_to_cons(items))
+            }
+            (Gen::OneOf(choices), Value::Int(index)) => {
+                Ok(choices.get(*index as usize).cloned().unwrap_or(Value::Unit))
+            }
+            (Gen::Pair(first, second), Value::Tuple(items)) => Ok(Value::Tuple(vec![
+                first.realize(vm, &items[0])?,
+                second.realize(vm, &items[1])?,
+            ])),
+            (Gen::Map(f, inner), _) => {
+                let value = inner.realize(vm, raw)?;
+                vm.call_value(f.clone(), &[value])
+            }
+            _ => Ok(raw.clone()),
+        }
+    }
+
+    /// Smaller raw values to try in place of `raw`, most promising first
+    fn shrink(&self, raw: &Value) -> Vec<Value> {
+        match (self, raw) {
+            (Gen::Int, Value::Int(n)) => shrink_int(*n, 0),
+            (Gen::Range(lo, hi), Value::Int(n)) => shrink_int(*n, 0.clamp(*lo, *hi)),
+            (Gen::Float, Value::Float(x)) => {
+                let mut candidates = Vec::new();
+                if *x != 0.0 {
+                    candidates.push(Value::Float(0.0));
+                }
+                if x.trunc() != *x {
+                    candidates.push(Value::Float(x.trunc()));
+                }
+                candidates
+            }
+            (Gen::Bool, Value::Bool(true)) => vec![Value::Bool(false)],
+            (Gen::String, Value::Str(s)) => {
+                let chars: Vec<Value> = s.chars().map(|c| Value::Str(c.to_string())).collect();
+                shrink_items(&chars)
+                    .into_iter()
+                    .map(|items| {
+                        Value::Str(items.iter().map(|c| c.to_string()).collect::<String>())
+                    })
+                    .collect()
+            }
+            (Gen::List(element), _) => {
+                let items = raw.list_to_vec().unwrap_or_default();
+                let mut candidates: Vec<Value> = shrink_items(&items)
+                    .into_iter()
+                    .map(Value::vec_to_cons)
+                    .collect();
+                for (i, item) in items.iter().enumerate() {
+                    for smaller in element.shrink(item) {
+                        let mut shrunk = items.clone();
+                        shrunk[i] = smaller;
+                        candidates.push(Value::vec_to_cons(shrunk));
+                    }
+                }
+                candidates
+            }
+            (Gen::OneOf(_), Value::Int(index)) => (0..*index).map(Value::Int).collect(),
+            (Gen::Pair(first, second), Value::Tuple(items)) => {
+                let mut candidates: Vec<Value> = first
+                    .shrink(&items[0])
+                    .into_iter()
+                    .map(|a| Value::Tuple(vec![a, items[1].clone()]))
+                    .collect();
+                candidates.extend(
+                    second
+                        .shrink(&items[1])
+                        .into_iter()
+                        .map(|b| Value::Tuple(vec![items[0].clone(), b])),
+                );
+                candidates
+            }
+            (Gen::Map(_, inner), _) => inner.shrink(raw),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Integers between `n` and `target`, closest to `target` first
+fn shrink_int(n: i64, target: i64) -> Vec<Value> {
+    if n == target {
+        return Vec::new();
+    }
+    let mut candidates = vec![Value::Int(target)];
+    let mut delta = (n as i128 - target as i128) / 2;
+    while delta != 0 {
+        let candidate = (n as i128 - delta) as i64;
+        if candidate != target {
+            candidates.push(Value::Int(candidate));
+        }
+        delta /= 2;
+    }
+    candidates
+}
+
+/// Shorter sequences: empty, each half, then each with one item removed
+fn shrink_items(items: &[Value]) -> Vec<Vec<Value>> {
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let mut candidates = vec![Vec::new()];
+    if items.len() > 2 {
+        let half = items.len() / 2;
+        candidates.push(items[..half].to_vec());
+        candidates.push(items[half..].to_vec());
+    }
+    if items.len() > 1 {
+        for i in 0..items.len() {
+            let mut removed = items.to_vec();
+            removed.remove(i);
+            candidates.push(removed);
+        }
+    }
+    candidates
+}
+
+/// xorshift64* generator, so runs with the same seed repeat exactly
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniform in `lo..=hi`
+    fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        let span = (hi as i128 - lo as i128 + 1) as u128;
+        (lo as i128 + (self.next() as u128 % span) as i128) as i64
+    }
+
+    /// Uniform in `0.0..1.0`
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Gen.int : int gen
+/// Integers, growing in magnitude as testing proceeds
+pub fn gen_int() -> Value {
+    gen_value("Int", vec![])
+}
+
+/// Gen.float : float gen
+/// Floats, growing in magnitude as testing proceeds
+pub fn gen_float() -> Value {
+    gen_value("Float", vec![])
+}
+
+/// Gen.bool : bool gen
+/// true and false
+pub fn gen_bool() -> Value {
+    gen_value("Bool", vec![])
+}
+
+/// Gen.string : string gen
+/// Strings of printable ASCII characters
+pub fn gen_string() -> Value {
+    gen_value("String", vec![])
+}
+
+/// Gen.range : int -> int -> int gen
+/// Integers from lo to hi, inclusive
+pub fn gen_range(lo: &Value, hi: &Value) -> Result<Value, VmError> {
+    match (lo, hi) {
+        (Value::Int(l), Value::Int(h)) if l <= h => {
+            Ok(gen_value("Range", vec![lo.clone(), hi.clone()]))
+        }
+        (Value::Int(l), Value::Int(h)) => Err(VmError::Runtime(format!(
+            "Gen.range: empty range {}..{}",
+            l, h
+        ))),
+        (Value::Int(_), _) => Err(VmError::TypeMismatch {
+            expected: "int",
+            got: hi.type_name(),
+        }),
+        _ => Err(VmError::TypeMismatch {
+            expected: "int",
+            got: lo.type_name(),
+        }),
+    }
+}
+
+/// Gen.list : 'a gen -> 'a list gen
+/// Lists of values from the generator
+pub fn gen_list(element: &Value) -> Result<Value, VmError> {
+    expect_gen(element)?;
+    Ok(gen_value("List", vec![element.clone()]))
+}
+
+/// Gen.oneOf : 'a list -> 'a gen
+/// Values picked from a non-empty list
+pub fn gen_one_of(choices: &Value) -> Result<Value, VmError> {
+    match choices.list_to_vec() {
+        Some(items) if !items.is_empty() => Ok(gen_value("OneOf", vec![choices.clone()])),
+        Some(_) => Err(VmError::Runtime(
+            "Gen.oneOf: no values to choose from".to_string(),
+        )),
+        None => Err(VmError::TypeMismatch {
+            expected: "list",
+            got: choices.type_name(),
+        }),
+    }
+}
+
+/// Gen.pair : 'a gen -> 'b gen -> ('a * 'b) gen
+/// Tuples of values from two generators
+pub fn gen_pair(first: &Value, second: &Value) -> Result<Value, VmError> {
+    expect_gen(first)?;
+    expect_gen(second)?;
+    Ok(gen_value("Pair", vec![first.clone(), second.clone()]))
+}
+
+/// Gen.map : ('a -> 'b) -> 'a gen -> 'b gen
+/// Values from the generator, transformed by the function
+pub fn gen_map(f: &Value, inner: &Value) -> Result<Value, VmError> {
+    expect_gen(inner)?;
+    Ok(gen_value("Map", vec![f.clone(), inner.clone()]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assertions() {
+        assert!(test_equal(&Value::Int(3), &Value::Int(3)).is_ok());
+        let err = test_equal(&Value::Str("a".into()), &Value::Str("b".into())).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Assertion failed: expected \"a\", got \"b\""
+        );
+
+        assert!(test_not_equal(&Value::Int(3), &Value::Int(4)).is_ok());
+        assert!(test_not_equal(&Value::Int(3), &Value::Int(3)).is_err());
+        assert!(test_is_true(&Value::Bool(true)).is_ok());
+        assert!(matches!(
+            test_is_true(&Value::Bool(false)),
+            Err(VmError::AssertionFailed(_))
+        ));
+        assert!(matches!(
+            test_fail(&Value::Str("nope".into())),
+            Err(VmError::AssertionFailed(msg)) if msg == "nope"
+        ));
+    }
+
+    #[test]
+    fn test_generators_are_deterministic() {
+        let gen = Gen::from_value(
+            &gen_list(&gen_range(&Value::Int(-5), &Value::Int(5)).unwrap()).unwrap(),
+        )
+        .unwrap();
+        let (mut a, mut b) = (Rng::new(7), Rng::new(7));
+        for size in 1..50 {
+            let value = gen.generate(&mut a, size);
+            assert_eq!(value, gen.generate(&mut b, size));
+            for item in value.list_to_vec().unwrap() {
+                let n = item.as_int().unwrap();
+                assert!((-5..=5).contains(&n));
+            }
+        }
+
+        assert!(gen_range(&Value::Int(2), &Value::Int(1)).is_err());
+        assert!(gen_one_of(&Value::Nil).is_err());
+        assert!(gen_list(&Value::Int(1)).is_err());
+    }
+
+    #[test]
+    fn test_shrink() {
+        assert_eq!(
+            shrink_int(100, 0),
+            [0, 50, 75, 88, 94, 97, 99].map(Value::Int).to_vec()
+        );
+        assert_eq!(shrink_int(-3, 0), [0, -2].map(Value::Int).to_vec());
+        assert!(shrink_int(5, 5).is_empty());
+        assert_eq!(Gen::Range(3, 9).shrink(&Value::Int(5))[0], Value::Int(3));
+
+        let list = Value::vec_to_cons(vec![Value::Int(4), Value::Int(0)]);
+        let candidates = Gen::List(Box::new(Gen::Int)).shrink(&list);
+        assert_eq!(candidates[0], Value::Nil);
+        assert!(candidates.contains(&Value::vec_to_cons(vec![Value::Int(0), Value::Int(0)])));
+    }
+}
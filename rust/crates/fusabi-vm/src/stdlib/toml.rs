@@ -0,0 +1,160 @@
+// Fusabi TOML Standard Library
+// Provides functions to convert between TOML documents and Fusabi values
+
+use super::json::{fusabi_value_to_json, json_value_to_fusabi};
+use super::shape::{result, Shape};
+use crate::value::Value;
+use crate::vm::VmError;
+use ::toml::{Table, Value as Toml};
+use serde_json::Value as Json;
+
+fn expect_str(value: &Value) -> Result<&str, VmError> {
+    match value {
+        Value::Str(s) => Ok(s),
+        _ => Err(VmError::TypeMismatch {
+            expected: "string",
+            got: value.type_name(),
+        }),
+    }
+}
+
+/// Parse a document into JSON data; dates and times become strings
+fn parse(text: &str) -> Result<Json, String> {
+    let table = text
+        .parse::<Table>()
+        .map_err(|e| format!("TOML parse error: {}", e))?;
+    Ok(to_json(Toml::Table(table)))
+}
+
+fn to_json(value: Toml) -> Json {
+    match value {
+        Toml::String(s) => Json::String(s),
+        Toml::Integer(i) => Json::from(i),
+        Toml::Float(f) => Json::from(f),
+        Toml::Boolean(b) => Json::Bool(b),
+        Toml::Datetime(dt) => Json::String(dt.to_string()),
+        Toml::Array(items) => Json::Array(items.into_iter().map(to_json).collect()),
+        Toml::Table(table) => Json::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Convert JSON data to TOML; null fields are left out, since TOML has no null
+fn from_json(value: Json) -> Result<Toml, VmError> {
+    Ok(match value {
+        Json::Null => return Err(VmError::Runtime("TOML cannot represent ()".to_string())),
+        Json::Bool(b) => Toml::Boolean(b),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => Toml::Integer(i),
+            None => Toml::Float(n.as_f64().unwrap_or_default()),
+        },
+        Json::String(s) => Toml::String(s),
+        Json::Array(items) => {
+            Toml::Array(items.into_iter().map(from_json).collect::<Result<_, _>>()?)
+        }
+        Json::Object(object) => {
+            let mut table = Table::new();
+            for (key, value) in object {
+                if !value.is_null() {
+                    table.insert(key, from_json(value)?);
+                }
+            }
+            Toml::Table(table)
+        }
+    })
+}
+
+fn stringify(json: Json) -> Result<Value, VmError> {
+    match from_json(json)? {
+        Toml::Table(table) => Ok(Value::Str(table.to_string())),
+        other => Err(VmError::Runtime(format!(
+            "TOML documents are tables, not {}",
+            other.type_str()
+        ))),
+    }
+}
+
+/// Toml.parse : string -> 'a
+/// Parses a TOML document into a record
+pub fn toml_parse(arg: &Value) -> Result<Value, VmError> {
+    let json = parse(expect_str(arg)?).map_err(VmError::Runtime)?;
+    json_value_to_fusabi(&json)
+}
+
+/// Toml.stringify : 'a -> string
+/// Converts a record to a TOML document
+pub fn toml_stringify(arg: &Value) -> Result<Value, VmError> {
+    stringify(fusabi_value_to_json(arg)?)
+}
+
+/// Toml.parseAs : string -> string -> Result<'a, string>
+/// Parses a TOML document into the root type of a type provider's shape,
+/// with an Error describing the first value that does not fit it
+pub fn toml_parse_as(shape: &Value, arg: &Value) -> Result<Value, VmError> {
+    let shape = Shape::parse(shape)?;
+    let decoded = parse(expect_str(arg)?).and_then(|json| shape.decode(&json));
+    Ok(result(decoded))
+}
+
+/// Toml.serializeAs : string -> 'a -> string
+/// Converts a value of the root type of a type provider's shape to a TOML
+/// document, leaving out fields that are None
+pub fn toml_serialize_as(shape: &Value, arg: &Value) -> Result<Value, VmError> {
+    stringify(Shape::parse(shape)?.encode(arg)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(text: &str) -> Value {
+        Value::Str(text.to_string())
+    }
+
+    const SHAPE: &str = r#"{"root": "Config", "types": {
+        "Config": {"record": [["name", "string"], ["port", "int"], ["tags", "string list option"]]}
+    }}"#;
+
+    #[test]
+    fn test_parse_and_stringify() {
+        let value = toml_parse(&s("name = \"app\"\n[server]\nport = 80\n")).unwrap();
+        let Value::Record(record) = &value else {
+            panic!("expected a record, got {:?}", value);
+        };
+        assert_eq!(record.lock().unwrap()["name"], s("app"));
+
+        let text = toml_stringify(&value).unwrap();
+        assert_eq!(toml_parse(&text).unwrap(), value);
+
+        assert!(toml_parse(&s("name = ")).is_err());
+        assert!(toml_stringify(&Value::Int(1)).is_err());
+    }
+
+    #[test]
+    fn test_parse_as() {
+        let parsed = toml_parse_as(&s(SHAPE), &s("name = \"app\"\nport = 80")).unwrap();
+        let Value::Variant {
+            variant_name,
+            fields,
+            ..
+        } = &parsed
+        else {
+            panic!("expected a Result, got {:?}", parsed);
+        };
+        assert_eq!(variant_name, "Ok");
+        assert_eq!(
+            toml_serialize_as(&s(SHAPE), &fields[0]).unwrap(),
+            s("name = \"app\"\nport = 80\n")
+        );
+
+        let invalid = toml_parse_as(&s(SHAPE), &s("name = \"app\"\nport = \"80\"")).unwrap();
+        assert_eq!(
+            invalid,
+            result(Err("port: expected int, found string".to_string()))
+        );
+    }
+}
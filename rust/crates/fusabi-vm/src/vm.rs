@@ -6,6 +6,8 @@ use crate::closure::{Closure, Upvalue};
 use crate::gc::GcHeap;
 use crate::host::HostRegistry;
 use crate::instruction::Instruction;
+use crate::limits::{ExecutionLimits, Guard, Interrupt};
+use crate::profiler::{Profile, Profiler};
 use crate::value::Value;
 use std::collections::HashMap;
 use std::fmt;
@@ -43,6 +45,12 @@ pub enum VmError {
     /// Runtime error with message
     Runtime(String),
     EmptyList,
+    /// Stopped through an [`Interrupt`]
+    Interrupted,
+    /// A run exceeded one of its [`ExecutionLimits`]
+    LimitExceeded(String),
+    /// A `Test` assertion did not hold
+    AssertionFailed(String),
 }
 
 impl fmt::Display for VmError {
@@ -69,6 +77,9 @@ impl fmt::Display for VmError {
             }
             VmError::EmptyList => write!(f, "Cannot access head/tail of empty list"),
             VmError::Runtime(msg) => write!(f, "Runtime error: {}", msg),
+            VmError::Interrupted => write!(f, "Execution interrupted"),
+            VmError::LimitExceeded(msg) => write!(f, "Execution stopped: {}", msg),
+            VmError::AssertionFailed(msg) => write!(f, "Assertion failed: {}", msg),
         }
     }
 }
@@ -82,8 +93,11 @@ pub struct Frame {
     pub closure: Arc<Closure>,
     /// Instruction pointer - index into closure.chunk.instructions
     pub ip: usize,
-    /// Base pointer - index into VM stack where this frame's locals start
+    /// Height of the VM stack when the frame was entered; the frame's
+    /// temporaries are pushed above it
     pub base: usize,
+    /// Local variable slots, starting with the arguments
+    pub locals: Vec<Value>,
 }
 
 impl Frame {
@@ -93,6 +107,7 @@ impl Frame {
             closure,
             ip: 0,
             base,
+            locals: Vec::new(),
         }
     }
 
@@ -116,6 +131,26 @@ impl Frame {
     }
 }
 
+/// Hooks called by [`Vm::run`] while a debugger is attached.
+///
+/// The hook receives the VM between instructions and can inspect (but not
+/// modify) its frames, stack and globals. A hook that blocks, e.g. waiting for
+/// a "continue" from a debugger client, pauses execution.
+pub trait DebugHook: Send + Sync {
+    /// Called before each instruction. The current frame's `ip` points at the
+    /// instruction about to execute. Returning an error stops execution.
+    fn on_instruction(&mut self, vm: &Vm) -> Result<(), VmError>;
+
+    /// Called when execution fails, before the error is returned.
+    fn on_error(&mut self, _vm: &Vm, _error: &VmError) {}
+}
+
+impl fmt::Debug for dyn DebugHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<debug hook>")
+    }
+}
+
 /// Maximum call stack depth
 /// The virtual machine - bytecode interpreter
 #[derive(Debug)]
@@ -133,6 +168,14 @@ pub struct Vm {
     /// Async runtime (Tokio-backed)
     #[cfg(feature = "async")]
     pub async_runtime: Option<Arc<crate::async_runtime::AsyncRuntime>>,
+    /// Attached debugger, if any
+    debug_hook: Option<Box<dyn DebugHook>>,
+    /// Active profiler, if any
+    profiler: Option<Profiler>,
+    /// Execution limits and interrupt flag, once either is used
+    guard: Option<Guard>,
+    /// Command-line arguments of the script, read by `Process.args`
+    args: Vec<String>,
 }
 
 impl Vm {
@@ -149,6 +192,10 @@ impl Vm {
             gc_heap: GcHeap::new(),
             #[cfg(feature = "async")]
             async_runtime: None,
+            debug_hook: None,
+            profiler: None,
+            guard: None,
+            args: Vec::new(),
         }
     }
 
@@ -162,6 +209,10 @@ impl Vm {
             gc_heap: GcHeap::new(),
             #[cfg(feature = "async")]
             async_runtime: None,
+            debug_hook: None,
+            profiler: None,
+            guard: None,
+            args: Vec::new(),
         }
     }
 
@@ -175,6 +226,10 @@ impl Vm {
             gc_heap: GcHeap::with_threshold(threshold),
             #[cfg(feature = "async")]
             async_runtime: None,
+            debug_hook: None,
+            profiler: None,
+            guard: None,
+            args: Vec::new(),
         }
     }
 
@@ -189,10 +244,11 @@ impl Vm {
         // Add globals as roots
         roots.extend(self.globals.values().cloned());
 
-        // Add closure constants and upvalues from frames as roots
+        // Add closure constants, locals and upvalues from frames as roots
         for frame in &self.frames {
             // Add the closure itself as a root
             roots.push(Value::Closure(frame.closure.clone()));
+            roots.extend(frame.locals.iter().cloned());
 
             // Add upvalues
             for upvalue in &frame.closure.upvalues {
@@ -224,11 +280,60 @@ impl Vm {
         // Wrap the top-level chunk in a closure
         let closure = Arc::new(Closure::new(chunk));
 
-        // Push initial frame
-        let frame = Frame::new(closure, 0);
+        // Push initial frame above anything a calling run left on the stack
+        let base = self.stack.len();
+        let depth = self.frames.len();
+        let frame = Frame::new(closure, base);
         self.frames.push(frame);
 
-        self.run()
+        self.run_restoring(depth, base)
+    }
+
+    /// Run, and on failure drop the frames and values the run left behind so
+    /// that the VM can be used again
+    fn run_restoring(&mut self, frames: usize, stack: usize) -> Result<Value, VmError> {
+        let result = self.run();
+        if result.is_err() {
+            self.frames.truncate(frames);
+            self.stack.truncate(stack);
+        }
+        result
+    }
+
+    /// Set the command-line arguments scripts read with `Process.args`
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// The arguments set by [`Vm::set_args`]
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Bound every subsequent run
+    pub fn set_limits(&mut self, limits: ExecutionLimits) {
+        self.guard.get_or_insert_with(Guard::default).limits = limits;
+    }
+
+    /// The limits set by [`Vm::set_limits`]
+    pub fn limits(&self) -> ExecutionLimits {
+        self.guard
+            .as_ref()
+            .map(|guard| guard.limits)
+            .unwrap_or_default()
+    }
+
+    /// Handle for stopping runs of this VM from another thread
+    pub fn interrupt_handle(&mut self) -> Interrupt {
+        self.guard
+            .get_or_insert_with(Guard::default)
+            .interrupt
+            .clone()
+    }
+
+    /// Stop runs of this VM with `interrupt`, which may be shared with other VMs
+    pub fn set_interrupt_handle(&mut self, interrupt: Interrupt) {
+        self.guard.get_or_insert_with(Guard::default).interrupt = interrupt;
     }
 
     /// Create a VM instance from pre-compiled bytecode.
@@ -317,12 +422,89 @@ impl Vm {
         vm.execute(chunk)
     }
 
+    /// Attach a debugger, replacing any previous one
+    pub fn set_debug_hook(&mut self, hook: Box<dyn DebugHook>) {
+        self.debug_hook = Some(hook);
+    }
+
+    /// Detach the debugger, returning it
+    pub fn take_debug_hook(&mut self) -> Option<Box<dyn DebugHook>> {
+        self.debug_hook.take()
+    }
+
+    /// Active call frames, outermost first
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Value in local slot `slot` of the frame at `frame_index`
+    pub fn frame_local(&self, frame_index: usize, slot: u8) -> Option<&Value> {
+        self.frames.get(frame_index)?.locals.get(slot as usize)
+    }
+
+    /// Current value of upvalue `index` of the frame at `frame_index`
+    pub fn frame_upvalue(&self, frame_index: usize, index: usize) -> Option<Value> {
+        let upvalue = self.frames.get(frame_index)?.closure.get_upvalue(index)?;
+        let value = match &*upvalue.lock().unwrap() {
+            Upvalue::Closed(v) => Some(v.clone()),
+            Upvalue::Open(stack_idx) => self.stack.get(*stack_idx).cloned(),
+        };
+        value
+    }
+
+    /// Start collecting a [`Profile`], discarding any collected before
+    pub fn start_profiling(&mut self) {
+        self.profiler = Some(Profiler::default());
+    }
+
+    /// Profile collected so far, if profiling
+    pub fn profile(&self) -> Option<Profile> {
+        self.profiler.as_ref().map(Profiler::profile)
+    }
+
+    /// Stop profiling, returning the collected profile
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profiler.take().map(|profiler| profiler.profile())
+    }
+
     /// Run the interpreter loop
     pub fn run(&mut self) -> Result<Value, VmError> {
+        if let Some(guard) = &mut self.guard {
+            guard.enter();
+        }
+        let result = self.run_loop();
+        if let Some(guard) = &mut self.guard {
+            guard.exit();
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.on_return(&self.frames);
+        }
+        if let Err(error) = &result {
+            if let Some(mut hook) = self.debug_hook.take() {
+                hook.on_error(self, error);
+                self.debug_hook = Some(hook);
+            }
+        }
+        result
+    }
+
+    fn run_loop(&mut self) -> Result<Value, VmError> {
         let start_depth = self.frames.len();
 
         // Main interpreter loop
         loop {
+            if let Some(profiler) = &mut self.profiler {
+                profiler.on_instruction(&self.frames);
+            }
+            if let Some(mut hook) = self.debug_hook.take() {
+                let result = hook.on_instruction(self);
+                self.debug_hook = Some(hook);
+                result?;
+            }
+            if let Some(guard) = &mut self.guard {
+                guard.on_instruction(self.frames.len())?;
+            }
+
             // Fetch next instruction in a separate scope to release mutable borrow on self
             let instruction = {
                 let frame = self.current_frame_mut()?;
@@ -406,6 +588,21 @@ impl Vm {
                     }
                 }
 
+                Instruction::StoreGlobal(idx) => {
+                    let name_val = self.current_frame()?.get_constant(idx)?;
+                    let name = match name_val {
+                        Value::Str(s) => s,
+                        _ => {
+                            return Err(VmError::TypeMismatch {
+                                expected: "string (global name)",
+                                got: name_val.type_name(),
+                            })
+                        }
+                    };
+                    let value = self.pop()?;
+                    self.globals.insert(name, value);
+                }
+
                 Instruction::Pop => {
                     self.pop()?;
                 }
@@ -707,9 +904,13 @@ impl Vm {
                     closure.arity = prototype.arity;
                     closure.name = prototype.name.clone();
 
-                    // Pop upvalues (placeholder logic for now)
+                    // The captured values, pushed in upvalue order
+                    let mut captured = Vec::with_capacity(upvalue_count as usize);
                     for _ in 0..upvalue_count {
-                        self.pop()?;
+                        captured.push(self.pop()?);
+                    }
+                    for value in captured.into_iter().rev() {
+                        closure.add_upvalue(Arc::new(Mutex::new(Upvalue::new_closed(value))));
                     }
 
                     self.push(Value::Closure(Arc::new(closure)));
@@ -732,9 +933,11 @@ impl Vm {
                                 )));
                             }
 
-                            // Locals start at first argument
-                            let base = func_idx + 1;
-                            let frame = Frame::new(closure.clone(), base);
+                            // The arguments become the first locals
+                            let args = self.stack.split_off(func_idx + 1);
+                            self.stack.truncate(func_idx);
+                            let mut frame = Frame::new(closure.clone(), func_idx);
+                            frame.locals = args;
                             self.frames.push(frame);
                         }
                         Value::NativeFn {
@@ -748,6 +951,7 @@ impl Vm {
                                 new_args.push(self.pop()?);
                             }
                             new_args.reverse(); // Arguments are pushed left-to-right, so stack has last arg on top.
+                            self.pop()?; // The callee
 
                             // Combine with already applied arguments
                             let mut all_args = applied_args.clone();
@@ -880,14 +1084,9 @@ impl Vm {
                             // Now call the method value based on its type
                             match method_value {
                                 Value::Closure(closure) => {
-                                    // Push arguments onto stack for the call
-                                    for arg in args {
-                                        self.push(arg);
-                                    }
-
                                     // Create new frame for closure
-                                    let base = self.stack.len() - closure.arity as usize;
-                                    let frame = Frame::new(closure, base);
+                                    let mut frame = Frame::new(closure, self.stack.len());
+                                    frame.locals = args;
 
                                     if self.frames.len() >= 1000 {
                                         return Err(VmError::CallStackOverflow);
@@ -948,11 +1147,23 @@ impl Vm {
                     // Placeholder
                 }
 
+                Instruction::SetClosureUpvalue(idx) => {
+                    let value = self.pop()?;
+                    let closure = self.pop()?;
+                    let upvalue = closure
+                        .as_closure()
+                        .and_then(|closure| closure.get_upvalue(idx as usize))
+                        .ok_or(VmError::Runtime(format!("Invalid upvalue index: {}", idx)))?;
+                    *upvalue.lock().unwrap() = Upvalue::new_closed(value);
+                }
+
                 Instruction::Return => {
                     let returned_value = self.pop().unwrap_or(Value::Unit);
 
-                    // Pop the frame
-                    self.frames.pop();
+                    // Pop the frame with whatever it left on the stack
+                    if let Some(frame) = self.frames.pop() {
+                        self.stack.truncate(frame.base);
+                    }
 
                     // If we've dropped below the starting depth, we're done with this run() call
                     if self.frames.len() < start_depth {
@@ -1361,9 +1572,9 @@ impl Vm {
     #[inline(always)]
     fn get_local(&self, idx: u8) -> Result<Value, VmError> {
         let frame = self.frames.last().ok_or(VmError::NoActiveFrame)?;
-        let local_idx = frame.base + idx as usize;
-        self.stack
-            .get(local_idx)
+        frame
+            .locals
+            .get(idx as usize)
             .cloned()
             .ok_or(VmError::InvalidLocalIndex(idx))
     }
@@ -1371,15 +1582,15 @@ impl Vm {
     /// Set a local variable
     #[inline(always)]
     fn set_local(&mut self, idx: u8, value: Value) -> Result<(), VmError> {
-        let frame = self.frames.last().ok_or(VmError::NoActiveFrame)?;
-        let local_idx = frame.base + idx as usize;
+        let frame = self.frames.last_mut().ok_or(VmError::NoActiveFrame)?;
+        let slot = idx as usize;
 
-        // Extend stack if necessary
-        while self.stack.len() <= local_idx {
-            self.stack.push(Value::Unit);
+        // Extend locals if necessary
+        if frame.locals.len() <= slot {
+            frame.locals.resize(slot + 1, Value::Unit);
         }
 
-        self.stack[local_idx] = value;
+        frame.locals[slot] = value;
         Ok(())
     }
 
@@ -1419,20 +1630,15 @@ impl Vm {
             )));
         }
 
-        // Push args to stack
-        for arg in args {
-            self.push(arg.clone());
-        }
-
-        // Calculate base pointer (locals start at first argument)
-        let base = self.stack.len() - args.len();
-
-        // Push frame
-        let frame = Frame::new(closure, base);
+        // Push frame, with the arguments as its first locals
+        let base = self.stack.len();
+        let mut frame = Frame::new(closure, base);
+        frame.locals = args.to_vec();
+        let depth = self.frames.len();
         self.frames.push(frame);
 
         // Run the VM loop until this frame returns
-        self.run()
+        self.run_restoring(depth, base)
     }
 
     /// Call any callable value (Closure or NativeFn) from Rust code
@@ -2880,6 +3086,93 @@ mod tests {
         assert!(vm.gc_stats().collections > 0);
     }
 
+    // ========== Call Frame Tests ==========
+
+    #[test]
+    fn test_vm_call_in_call_argument() {
+        // inc = fun x -> x + 1
+        let inc = ChunkBuilder::new()
+            .constant(Value::Int(1))
+            .instruction(Instruction::LoadLocal(0))
+            .instruction(Instruction::LoadConst(0))
+            .instruction(Instruction::Add)
+            .instruction(Instruction::Return)
+            .build();
+        // double_plus_one = fun x -> let y = x * 2 in y + 1
+        let double_plus_one = ChunkBuilder::new()
+            .constant(Value::Int(2))
+            .constant(Value::Int(1))
+            .instruction(Instruction::LoadLocal(0))
+            .instruction(Instruction::LoadConst(0))
+            .instruction(Instruction::Mul)
+            .instruction(Instruction::StoreLocal(1))
+            .instruction(Instruction::LoadLocal(1))
+            .instruction(Instruction::LoadConst(1))
+            .instruction(Instruction::Add)
+            .instruction(Instruction::Return)
+            .build();
+
+        // inc (double_plus_one 2)
+        let chunk = ChunkBuilder::new()
+            .constant(Value::Closure(Arc::new(Closure::with_arity(inc, 1))))
+            .constant(Value::Closure(Arc::new(Closure::with_arity(
+                double_plus_one,
+                1,
+            ))))
+            .constant(Value::Int(2))
+            .instruction(Instruction::MakeClosure(0, 0))
+            .instruction(Instruction::MakeClosure(1, 0))
+            .instruction(Instruction::LoadConst(2))
+            .instruction(Instruction::Call(1))
+            .instruction(Instruction::Call(1))
+            .instruction(Instruction::Return)
+            .build();
+
+        let mut vm = Vm::new();
+        assert_eq!(vm.execute(chunk).unwrap(), Value::Int(6));
+        assert!(vm.stack.is_empty());
+        assert!(vm.frames().is_empty());
+    }
+
+    #[test]
+    fn test_vm_make_closure_captures_values() {
+        let captured = ChunkBuilder::new()
+            .instruction(Instruction::LoadUpvalue(1))
+            .instruction(Instruction::LoadUpvalue(0))
+            .instruction(Instruction::Sub)
+            .instruction(Instruction::Return)
+            .build();
+        let chunk = ChunkBuilder::new()
+            .constant(Value::Closure(Arc::new(Closure::with_arity(captured, 0))))
+            .constant(Value::Int(2))
+            .constant(Value::Int(44))
+            .instruction(Instruction::LoadConst(1))
+            .instruction(Instruction::LoadConst(2))
+            .instruction(Instruction::MakeClosure(0, 2))
+            .instruction(Instruction::Call(0))
+            .instruction(Instruction::Return)
+            .build();
+
+        let mut vm = Vm::new();
+        assert_eq!(vm.execute(chunk).unwrap(), Value::Int(42));
+    }
+
+    #[test]
+    fn test_vm_store_global() {
+        let chunk = ChunkBuilder::new()
+            .constant(Value::Int(42))
+            .constant(Value::Str("answer".to_string()))
+            .instruction(Instruction::LoadConst(0))
+            .instruction(Instruction::StoreGlobal(1))
+            .instruction(Instruction::LoadGlobal(1))
+            .instruction(Instruction::Return)
+            .build();
+
+        let mut vm = Vm::new();
+        assert_eq!(vm.execute(chunk).unwrap(), Value::Int(42));
+        assert_eq!(vm.globals.get("answer"), Some(&Value::Int(42)));
+    }
+
     // ========== CallMethod on Record Tests ==========
 
     #[test]
@@ -2938,4 +3231,131 @@ mod tests {
         let result = vm.execute(chunk);
         assert!(matches!(result, Err(VmError::Runtime(msg)) if msg.contains("Method not found")));
     }
+
+    struct CountingHook {
+        instructions: Arc<Mutex<usize>>,
+        errors: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl DebugHook for CountingHook {
+        fn on_instruction(&mut self, vm: &Vm) -> Result<(), VmError> {
+            assert!(!vm.frames().is_empty());
+            *self.instructions.lock().unwrap() += 1;
+            Ok(())
+        }
+
+        fn on_error(&mut self, vm: &Vm, error: &VmError) {
+            assert_eq!(vm.frames().len(), 1);
+            self.errors.lock().unwrap().push(error.to_string());
+        }
+    }
+
+    #[test]
+    fn test_debug_hook_sees_every_instruction_and_error() {
+        let instructions = Arc::new(Mutex::new(0));
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let mut vm = Vm::new();
+        vm.set_debug_hook(Box::new(CountingHook {
+            instructions: instructions.clone(),
+            errors: errors.clone(),
+        }));
+
+        let chunk = ChunkBuilder::new()
+            .constant(Value::Int(1))
+            .constant(Value::Str("a".to_string()))
+            .instruction(Instruction::LoadConst(0))
+            .instruction(Instruction::LoadConst(1))
+            .instruction(Instruction::Add)
+            .instruction(Instruction::Return)
+            .build();
+        assert!(vm.execute(chunk).is_err());
+        assert_eq!(*instructions.lock().unwrap(), 3);
+        assert_eq!(errors.lock().unwrap().len(), 1);
+        assert!(vm.take_debug_hook().is_some());
+    }
+
+    struct StopHook;
+
+    impl DebugHook for StopHook {
+        fn on_instruction(&mut self, _vm: &Vm) -> Result<(), VmError> {
+            Err(VmError::Runtime("stopped".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_debug_hook_can_stop_execution() {
+        let mut vm = Vm::new();
+        vm.set_debug_hook(Box::new(StopHook));
+        let chunk = ChunkBuilder::new()
+            .constant(Value::Int(42))
+            .instruction(Instruction::LoadConst(0))
+            .instruction(Instruction::Return)
+            .build();
+        assert_eq!(
+            vm.execute(chunk),
+            Err(VmError::Runtime("stopped".to_string()))
+        );
+    }
+
+    fn infinite_loop() -> Chunk {
+        ChunkBuilder::new()
+            .instruction(Instruction::Jump(-1))
+            .build()
+    }
+
+    #[test]
+    fn test_limits_stop_runaway_code() {
+        let mut vm = Vm::new();
+        vm.set_limits(ExecutionLimits {
+            max_instructions: Some(1000),
+            ..Default::default()
+        });
+        let result = vm.execute(infinite_loop());
+        assert!(matches!(result, Err(VmError::LimitExceeded(msg)) if msg.contains("instruction")));
+
+        vm.set_limits(ExecutionLimits {
+            timeout: Some(std::time::Duration::from_millis(20)),
+            ..Default::default()
+        });
+        let result = vm.execute(infinite_loop());
+        assert!(matches!(result, Err(VmError::LimitExceeded(msg)) if msg.contains("time")));
+
+        // The VM is usable after a failed run
+        let chunk = ChunkBuilder::new()
+            .constant(Value::Int(7))
+            .instruction(Instruction::LoadConst(0))
+            .instruction(Instruction::Return)
+            .build();
+        assert_eq!(vm.execute(chunk).unwrap(), Value::Int(7));
+        assert!(vm.frames().is_empty());
+    }
+
+    #[test]
+    fn test_interrupt_from_another_thread() {
+        let mut vm = Vm::new();
+        let interrupt = vm.interrupt_handle();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            interrupt.interrupt();
+        });
+        assert!(matches!(
+            vm.execute(infinite_loop()),
+            Err(VmError::Interrupted)
+        ));
+        stopper.join().unwrap();
+    }
+
+    #[test]
+    fn test_shared_interrupt_handle() {
+        let mut first = Vm::new();
+        let interrupt = first.interrupt_handle();
+        let mut second = Vm::new();
+        second.set_interrupt_handle(interrupt.clone());
+
+        interrupt.interrupt();
+        assert!(matches!(
+            second.execute(infinite_loop()),
+            Err(VmError::Interrupted)
+        ));
+    }
 }
@@ -0,0 +1,237 @@
+//! Self-contained executables
+//!
+//! [`Bundler`] compiles a script, together with the files it `#load`s, to
+//! bytecode and appends the bytecode to a copy of a runtime executable, by
+//! default the running `fus`. At startup `fus` looks for such a payload in
+//! its own executable with [`embedded_payload`] and, if there is one, runs it
+//! with [`run_payload`] instead of parsing its command line, so the script
+//! gets every argument as `Process.args ()`. `fus bundle` is built on it.
+//!
+//! A bundled executable is laid out as
+//!
+//! ```text
+//! runtime executable | .fzb payload | payload length (u64, little-endian) | BUNDLE_MAGIC
+//! ```
+//!
+//! ```no_run
+//! use fusabi::bundle::Bundler;
+//!
+//! let size = Bundler::new().bundle("app.fsx", "app").unwrap();
+//! println!("wrote {} bytes", size);
+//! ```
+
+use crate::check;
+use crate::{parse_recovering, register_script_eval_override, script_dir, FusabiError, Value};
+use fusabi_frontend::provider_resolver::resolve_source_paths;
+use fusabi_frontend::{Compiler, Lexer, Parser};
+use fusabi_vm::{deserialize_chunk, serialize_chunk, Vm};
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Magic bytes ending a bundled executable
+pub const BUNDLE_MAGIC: &[u8; 8] = b"FZBUNDLE";
+
+/// Length of the payload length and magic that end a bundled executable
+const TRAILER_LEN: u64 = 16;
+
+/// Builds self-contained executables from scripts
+#[derive(Debug, Clone, Default)]
+pub struct Bundler {
+    runtime: Option<PathBuf>,
+}
+
+impl Bundler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The executable to copy (default: the running executable). A payload
+    /// it already carries is replaced.
+    pub fn runtime(mut self, path: impl Into<PathBuf>) -> Self {
+        self.runtime = Some(path.into());
+        self
+    }
+
+    /// Compile the script at `script` and the files it loads to `.fzb`
+    /// bytecode
+    pub fn compile(&self, script: impl AsRef<Path>) -> Result<Vec<u8>, FusabiError> {
+        let script = script.as_ref();
+        let source = fs::read_to_string(script)?;
+        let tokens = Lexer::new(&source).tokenize()?;
+        let mut program = parse_recovering(Parser::new(tokens))?;
+        resolve_source_paths(&mut program, script_dir(script));
+
+        let mut failure = None;
+        let loaded = check::load_each(script, &program.directives, |_, error| {
+            failure.get_or_insert(error);
+        });
+        if let Some(error) = failure {
+            return Err(error.into());
+        }
+        let chunk = Compiler::compile_program(&check::link(&loaded, &program))?;
+        Ok(serialize_chunk(&chunk)?)
+    }
+
+    /// Write an executable running the script at `script` to `output`,
+    /// returning its size in bytes
+    pub fn bundle(
+        &self,
+        script: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+    ) -> Result<u64, FusabiError> {
+        let payload = self.compile(script)?;
+        let runtime = match &self.runtime {
+            Some(runtime) => runtime.clone(),
+            None => env::current_exe()?,
+        };
+        let runtime = fs::read(runtime)?;
+
+        let mut bytes = strip_payload(&runtime).to_vec();
+        bytes.extend_from_slice(&payload);
+        bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(BUNDLE_MAGIC);
+        let output = output.as_ref();
+        fs::write(output, &bytes)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(output, fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(bytes.len() as u64)
+    }
+}
+
+/// The payload bundled into the executable at `exe`, if any
+pub fn embedded_payload(exe: impl AsRef<Path>) -> Result<Option<Vec<u8>>, FusabiError> {
+    let mut file = File::open(exe)?;
+    let len = file.metadata()?.len();
+    if len < TRAILER_LEN {
+        return Ok(None);
+    }
+    let mut trailer = [0; TRAILER_LEN as usize];
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    file.read_exact(&mut trailer)?;
+    let Some(payload_len) = payload_len(&trailer, len) else {
+        return Ok(None);
+    };
+
+    let mut payload = vec![0; payload_len as usize];
+    file.seek(SeekFrom::Start(len - TRAILER_LEN - payload_len))?;
+    file.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+/// Run a bundled payload in a fresh VM, with `args` as the script's
+/// `Process.args ()`
+pub fn run_payload(payload: &[u8], args: Vec<String>) -> Result<Value, FusabiError> {
+    let chunk = deserialize_chunk(payload)?;
+
+    let mut vm = Vm::new();
+    fusabi_vm::stdlib::register_stdlib(&mut vm);
+    register_script_eval_override(&mut vm);
+    vm.set_args(args);
+    Ok(vm.execute(chunk)?)
+}
+
+/// Length of the payload of a file of `len` bytes ending with `trailer`, if
+/// the trailer is that of a bundle
+fn payload_len(trailer: &[u8], len: u64) -> Option<u64> {
+    let (payload_len, magic) = trailer.split_at(8);
+    if magic != BUNDLE_MAGIC {
+        return None;
+    }
+    let payload_len = u64::from_le_bytes(payload_len.try_into().ok()?);
+    (payload_len <= len - TRAILER_LEN).then_some(payload_len)
+}
+
+/// The executable of `bytes`, without the payload bundled into it, if any
+fn strip_payload(bytes: &[u8]) -> &[u8] {
+    let len = bytes.len() as u64;
+    if len < TRAILER_LEN {
+        return bytes;
+    }
+    let trailer = &bytes[(len - TRAILER_LEN) as usize..];
+    match payload_len(trailer, len) {
+        Some(payload_len) => &bytes[..(len - TRAILER_LEN - payload_len) as usize],
+        None => bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bundle_and_run_payload() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        fs::write(
+            dir.join("lib.fsx"),
+            "module Lib =\n    let double x = x * 2\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("app.fsx"),
+            "#load \"lib.fsx\"\n\nlet args = Process.args ()\nLib.double (List.length args)\n",
+        )
+        .unwrap();
+        let runtime = dir.join("runtime");
+        fs::write(&runtime, b"#!runtime").unwrap();
+
+        let bundler = Bundler::new().runtime(&runtime);
+        let output = dir.join("app");
+        let size = bundler.bundle(dir.join("app.fsx"), &output).unwrap();
+        let bytes = fs::read(&output).unwrap();
+        assert_eq!(size, bytes.len() as u64);
+        assert!(bytes.starts_with(b"#!runtime"));
+        assert!(bytes.ends_with(BUNDLE_MAGIC));
+
+        let payload = embedded_payload(&output).unwrap().unwrap();
+        assert_eq!(payload, bundler.compile(dir.join("app.fsx")).unwrap());
+        let args = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(run_payload(&payload, args).unwrap(), Value::Int(4));
+        assert_eq!(run_payload(&payload, Vec::new()).unwrap(), Value::Int(0));
+
+        // Bundling with a bundle as the runtime replaces its payload
+        let rebundled = dir.join("rebundled");
+        Bundler::new()
+            .runtime(&output)
+            .bundle(dir.join("app.fsx"), &rebundled)
+            .unwrap();
+        assert_eq!(fs::read(&rebundled).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_no_embedded_payload() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        fs::write(dir.join("short"), b"exe").unwrap();
+        fs::write(dir.join("plain"), b"an executable without a payload").unwrap();
+        assert_eq!(embedded_payload(dir.join("short")).unwrap(), None);
+        assert_eq!(embedded_payload(dir.join("plain")).unwrap(), None);
+
+        // A length past the start of the file is not a payload
+        let mut bytes = u64::MAX.to_le_bytes().to_vec();
+        bytes.extend_from_slice(BUNDLE_MAGIC);
+        fs::write(dir.join("corrupt"), &bytes).unwrap();
+        assert_eq!(embedded_payload(dir.join("corrupt")).unwrap(), None);
+        assert_eq!(strip_payload(&bytes), &bytes[..]);
+    }
+
+    #[test]
+    fn test_bundle_missing_load() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        fs::write(dir.join("app.fsx"), "#load \"missing.fsx\"\n1\n").unwrap();
+        let error = Bundler::new()
+            .runtime(dir.join("app.fsx"))
+            .bundle(dir.join("app.fsx"), dir.join("app"))
+            .unwrap_err();
+        assert!(matches!(error, FusabiError::Load(_)), "{}", error);
+        assert!(!dir.join("app").exists());
+    }
+}
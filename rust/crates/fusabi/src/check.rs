@@ -0,0 +1,809 @@
+//! Static checking of scripts
+//!
+//! [`Checker`] lexes, parses, resolves `#load` directives and type checks
+//! `.fsx` files without running them. It keeps going after an error: every
+//! file is checked, the parser recovers from syntax errors, and a top-level
+//! binding that fails to parse or type check is reported and then treated as
+//! having any type, so the bindings after it are still checked. `fus check`
+//! is built on it.
+//!
+//! ```no_run
+//! use fusabi::check::Checker;
+//!
+//! let report = Checker::new().check("src").unwrap();
+//! for diagnostic in &report.diagnostics {
+//!     eprint!("{}", diagnostic.report);
+//! }
+//! std::fs::write("check.json", report.to_json()).unwrap();
+//! ```
+
+use crate::{script_dir, FusabiError};
+use fusabi_frontend::ast::{TypeDefinition, TypeProviderDecl};
+use fusabi_frontend::provider_resolver::{resolve_source_paths, ProviderResolver, ResolverError};
+use fusabi_frontend::span::{Position, Span};
+use fusabi_frontend::{
+    format_source_highlight, Expr, FileLoader, Lexer, LoadDirective, LoadError, ModuleItem,
+    ModuleRegistry, Parser, Program, TokenWithPos, Type, TypeEnv, TypeError, TypeInference,
+    TypeScheme, TypeVar,
+};
+use fusabi_vm::Vm;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The stage of checking that found a problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    Lex,
+    Parse,
+    /// A `#load` directive could not be loaded
+    Load,
+    /// A type provider could not resolve its source
+    Provider,
+    Type,
+}
+
+impl DiagnosticKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticKind::Lex => "lex",
+            DiagnosticKind::Parse => "parse",
+            DiagnosticKind::Load => "load",
+            DiagnosticKind::Provider => "provider",
+            DiagnosticKind::Type => "type",
+        }
+    }
+}
+
+/// An error found in a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// The file the error is in
+    pub path: PathBuf,
+    pub kind: DiagnosticKind,
+    /// The error message
+    pub message: String,
+    /// Where the error is, if known
+    pub span: Option<Span>,
+    /// The message with its location and a source snippet
+    pub report: String,
+}
+
+/// Results of checking a set of files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CheckReport {
+    /// The files that were checked, in order
+    pub files: Vec<PathBuf>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CheckReport {
+    pub fn is_success(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    /// The diagnostics as a JSON array, one object per error with `file`,
+    /// `line`, `column` (1-based, 0 if unknown), `endLine`, `endColumn`
+    /// (left out if unknown), `severity`, `kind` and `message` fields, as
+    /// read by CI annotation tools
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[");
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            let (line, column) = diagnostic
+                .span
+                .map_or((0, 0), |span| (span.start.line, span.start.column));
+            let end = diagnostic.span.map_or(String::new(), |span| {
+                format!(
+                    " \"endLine\": {}, \"endColumn\": {},",
+                    span.end.line, span.end.column
+                )
+            });
+            let _ = write!(
+                json,
+                "{}\n  {{\"file\": \"{}\", \"line\": {}, \"column\": {},{} \"severity\": \"error\", \
+                 \"kind\": \"{}\", \"message\": \"{}\"}}",
+                if i == 0 { "" } else { "," },
+                escape_json(&diagnostic.path.display().to_string()),
+                line,
+                column,
+                end,
+                diagnostic.kind.as_str(),
+                escape_json(&diagnostic.message),
+            );
+        }
+        if !self.diagnostics.is_empty() {
+            json.push('\n');
+        }
+        json.push_str("]\n");
+        json
+    }
+}
+
+/// Checks `.fsx` files without running them
+#[derive(Debug, Clone, Default)]
+pub struct Checker {}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The `.fsx` files to check at `path`: the file itself, or every
+    /// `.fsx` file under the directory, sorted
+    pub fn discover(path: impl AsRef<Path>) -> Result<Vec<PathBuf>, FusabiError> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Ok(vec![path.to_path_buf()]);
+        }
+        let mut files = Vec::new();
+        let mut dirs = vec![path.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                let hidden = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                if path.is_dir() && !hidden {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "fsx") {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Check every file at each of `paths`
+    pub fn check_all<P: AsRef<Path>>(&self, paths: &[P]) -> Result<CheckReport, FusabiError> {
+        let mut report = CheckReport::default();
+        for path in paths {
+            for file in Self::discover(path)? {
+                report.diagnostics.extend(self.check_file(&file)?);
+                report.files.push(file);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Check the file at `path`, or every `.fsx` file under the directory
+    pub fn check(&self, path: impl AsRef<Path>) -> Result<CheckReport, FusabiError> {
+        self.check_all(&[path])
+    }
+
+    /// Check one file, returning the errors found in it and in the files
+    /// it loads
+    pub fn check_file(&self, path: impl AsRef<Path>) -> Result<Vec<Diagnostic>, FusabiError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        let mut diagnostics = Vec::new();
+
+        let tokens = match Lexer::new(&source).tokenize() {
+            Ok(tokens) => tokens,
+            Err(error) => {
+                let span = point_span(error.position());
+                diagnostics.push(diagnostic(
+                    path,
+                    &source,
+                    DiagnosticKind::Lex,
+                    error.to_string(),
+                    Some(span),
+                ));
+                return Ok(diagnostics);
+            }
+        };
+        let (mut program, errors) = Parser::new(tokens.clone())
+            .with_spans()
+            .parse_program_recovering();
+        resolve_source_paths(&mut program, script_dir(path));
+        for error in errors {
+            // Errors at the end of input have no position
+            let span = error
+                .position()
+                .filter(|pos| pos.line > 0)
+                .map(|pos| token_span(&tokens, pos));
+            diagnostics.push(diagnostic(
+                path,
+                &source,
+                DiagnosticKind::Parse,
+                error.to_string(),
+                span,
+            ));
+        }
+
+        let loaded = load(path, &source, &program.directives, &mut diagnostics);
+        diagnostics.extend(type_check(path, &source, &program, &loaded));
+        Ok(diagnostics)
+    }
+}
+
+/// Load the files `directives` load, reporting those that cannot be loaded.
+/// Returns the programs of the loaded files, dependencies first.
+pub(crate) fn load(
+    path: &Path,
+    source: &str,
+    directives: &[LoadDirective],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<Program> {
+    load_each(path, directives, |directive, error| {
+        let line = source
+            .lines()
+            .position(|line| line.contains(&format!("\"{}\"", directive.path)))
+            .map(|index| index + 1);
+        let span = line.map(|line| line_span(source, line));
+        diagnostics.push(diagnostic(
+            path,
+            source,
+            DiagnosticKind::Load,
+            error.to_string(),
+            span,
+        ));
+    })
+}
+
+/// Load the files `directives` of the file at `path` load, passing those that
+/// cannot be loaded to `on_error`. Returns the programs of the loaded files,
+/// dependencies first.
+pub(crate) fn load_each(
+    path: &Path,
+    directives: &[LoadDirective],
+    mut on_error: impl FnMut(&LoadDirective, LoadError),
+) -> Vec<Program> {
+    let base_dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let mut loader = FileLoader::new(base_dir);
+    let mut programs: Vec<(PathBuf, Program)> = Vec::new();
+    for directive in directives {
+        match loader
+            .load(&directive.path, path)
+            .map(|loaded| loaded.path.clone())
+        {
+            Ok(loaded) => collect(&loader, &loaded, &mut programs),
+            Err(error) => on_error(directive, error),
+        }
+    }
+    programs.into_iter().map(|(_, program)| program).collect()
+}
+
+/// Add the cached file at `path` and what it loads to `programs`,
+/// dependencies first
+fn collect(loader: &FileLoader, path: &Path, programs: &mut Vec<(PathBuf, Program)>) {
+    if programs.iter().any(|(loaded, _)| loaded == path) {
+        return;
+    }
+    if let Some(file) = loader.get_cached(path) {
+        for dependency in &file.dependencies {
+            collect(loader, dependency, programs);
+        }
+        programs.push((path.to_path_buf(), file.program.clone()));
+    }
+}
+
+/// One program of the files `program` loads, dependencies first, followed by
+/// `program`, to compile them together
+pub(crate) fn link(loaded: &[Program], program: &Program) -> Program {
+    let mut linked = Program {
+        directives: Vec::new(),
+        modules: Vec::new(),
+        imports: Vec::new(),
+        items: Vec::new(),
+        main_expr: program.main_expr.clone(),
+        docs: HashMap::new(),
+    };
+    for program in loaded.iter().chain([program]) {
+        linked.modules.extend(program.modules.iter().cloned());
+        linked.imports.extend(program.imports.iter().cloned());
+        linked.items.extend(program.items.iter().cloned());
+    }
+    linked
+}
+
+/// Type check the top-level bindings and main expression of `program`
+fn type_check(path: &Path, source: &str, program: &Program, loaded: &[Program]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let (mut inference, mut env) = environment(program, loaded, |decl, error| {
+        let line = source
+            .lines()
+            .position(|line| line.contains(&format!("type {}", decl.name)))
+            .map(|index| index + 1);
+        let span = line.map(|line| line_span(source, line));
+        diagnostics.push(diagnostic(
+            path,
+            source,
+            DiagnosticKind::Provider,
+            error.to_string(),
+            span,
+        ));
+    });
+
+    let mut report = |error: TypeError| {
+        let mut report = error.format(source);
+        if let Some(span) = error.span {
+            report = report.replacen(
+                &format!("  --> {}", span.format_location()),
+                &format!(
+                    "  --> {}:{}:{}",
+                    path.display(),
+                    span.start.line,
+                    span.start.column
+                ),
+                1,
+            );
+        }
+        diagnostics.push(Diagnostic {
+            path: path.to_path_buf(),
+            kind: DiagnosticKind::Type,
+            message: error.to_string(),
+            span: error.span,
+            report,
+        });
+    };
+    for (name, value) in bindings(&program.items) {
+        let scheme = match inference.infer_and_solve(&value, &env) {
+            Ok(ty) => env.generalize(&ty),
+            Err(error) => {
+                report(error);
+                any()
+            }
+        };
+        if let Some(name) = name {
+            env.insert(name, scheme);
+        }
+    }
+    if let Some(expr) = &program.main_expr {
+        if let Err(error) = inference.infer_and_solve(expr, &env) {
+            report(error);
+        }
+    }
+    diagnostics
+}
+
+/// Type inference and the environment to check the bindings of `program`
+/// in: the standard library, the modules it defines and loads, the
+/// bindings of the modules it opens, the type providers it and the files it
+/// loads declare and the bindings of those files. Providers of `program`
+/// that fail to resolve are passed to `on_provider_error`.
+pub(crate) fn environment(
+    program: &Program,
+    loaded: &[Program],
+    mut on_provider_error: impl FnMut(&TypeProviderDecl, ResolverError),
+) -> (TypeInference, TypeEnv) {
+    // Members of modules, like those of the standard library, are not typed
+    let mut registry = ModuleRegistry::with_stdlib();
+    let mut env = TypeEnv::new();
+    for name in stdlib_globals() {
+        env.insert(name, any());
+    }
+    let modules = loaded.iter().chain([program]).flat_map(|p| &p.modules);
+    for module in modules {
+        registry.register_module_def(module);
+        env.insert(module.name.clone(), any());
+    }
+    for import in &program.imports {
+        let bindings = import
+            .module_path
+            .first()
+            .and_then(|name| registry.get_module_bindings(name));
+        for name in bindings.into_iter().flat_map(|bindings| bindings.keys()) {
+            env.insert(name.clone(), any());
+        }
+    }
+    let mut inference = TypeInference::with_module_registry(registry);
+
+    // A provider alias is a record of its members, which reads
+    // `Alias.member` as the compiler does
+    let decls = |program: &Program| -> Vec<TypeProviderDecl> {
+        program
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ModuleItem::TypeDef(TypeDefinition::Provider(decl)) => Some(decl.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+    let resolver = ProviderResolver::from_env();
+    for (i, program) in loaded.iter().chain([program]).enumerate() {
+        for decl in decls(program) {
+            let scheme = match resolver.resolve(&decl) {
+                Ok(resolved) => {
+                    for type_def in &resolved.types {
+                        if let TypeDefinition::Du(union) = type_def {
+                            inference.register_union(union);
+                        }
+                    }
+                    resolved.members_scheme()
+                }
+                // Providers of loaded files are errors of those files
+                Err(error) => {
+                    if i == loaded.len() {
+                        on_provider_error(&decl, error);
+                    }
+                    any()
+                }
+            };
+            env.insert(decl.name, scheme);
+        }
+    }
+
+    // Bindings of loaded files are errors of those files, so a binding
+    // that fails to check just has any type here
+    for program in loaded {
+        for (name, value) in bindings(&program.items) {
+            let scheme = match inference.infer_and_solve(&value, &env) {
+                Ok(ty) => env.generalize(&ty),
+                Err(_) => any(),
+            };
+            if let Some(name) = name {
+                env.insert(name, scheme);
+            }
+        }
+    }
+    (inference, env)
+}
+
+/// The bindings of `items` in order, as expressions to check. Recursive
+/// bindings are checked as `let rec ... in name` so that they can refer to
+/// themselves and each other.
+pub(crate) fn bindings(items: &[ModuleItem]) -> Vec<(Option<String>, Expr)> {
+    let mut bindings = Vec::new();
+    for item in items {
+        match item {
+            ModuleItem::Let(name, value) => bindings.push((name.clone(), value.clone())),
+            ModuleItem::LetRec(group) if group.len() == 1 => {
+                let (name, value) = &group[0];
+                bindings.push((
+                    Some(name.clone()),
+                    Expr::LetRec {
+                        name: name.clone(),
+                        value: Box::new(value.clone()),
+                        body: Box::new(Expr::Var(name.clone())),
+                    },
+                ));
+            }
+            ModuleItem::LetRec(group) => bindings.extend(group.iter().map(|(name, _)| {
+                (
+                    Some(name.clone()),
+                    Expr::LetRecMutual {
+                        bindings: group.clone(),
+                        body: Box::new(Expr::Var(name.clone())),
+                    },
+                )
+            })),
+            _ => {}
+        }
+    }
+    bindings
+}
+
+/// Names the standard library defines, as globals and host functions
+fn stdlib_globals() -> Vec<String> {
+    let mut vm = Vm::new();
+    fusabi_vm::stdlib::register_stdlib(&mut vm);
+    let mut names: Vec<String> = vm.globals.keys().cloned().collect();
+    names.extend(vm.host_registry.lock().unwrap().function_names());
+    names
+}
+
+/// A type scheme that can be used at any type
+pub(crate) fn any() -> TypeScheme {
+    let var = TypeVar::new(0, "a");
+    TypeScheme::poly(vec![var.clone()], Type::Var(var))
+}
+
+fn diagnostic(
+    path: &Path,
+    source: &str,
+    kind: DiagnosticKind,
+    message: String,
+    span: Option<Span>,
+) -> Diagnostic {
+    let mut report = format!("Error: {}\n", message);
+    if let Some(span) = &span {
+        let _ = writeln!(
+            report,
+            "  --> {}:{}:{}",
+            path.display(),
+            span.start.line,
+            span.start.column
+        );
+        // Underline at least the character a point diagnostic is at
+        let mut highlight = *span;
+        if highlight.is_empty() {
+            highlight.end = Position::new(
+                span.start.line,
+                span.start.column + 1,
+                span.start.offset + 1,
+            );
+        }
+        if let Some(highlight) = format_source_highlight(source, &highlight) {
+            report.push_str(&highlight);
+        }
+    } else {
+        let _ = writeln!(report, "  --> {}", path.display());
+    }
+    Diagnostic {
+        path: path.to_path_buf(),
+        kind,
+        message,
+        span,
+        report,
+    }
+}
+
+/// The empty span at `pos`, for errors known only by their position
+fn point_span(pos: Position) -> Span {
+    Span::new(pos, pos)
+}
+
+/// The span of the token at `pos`, or the empty span there if no token
+/// starts at `pos`
+fn token_span(tokens: &[TokenWithPos], pos: Position) -> Span {
+    match tokens.iter().find(|token| token.pos.offset == pos.offset) {
+        Some(token) => Span::new(token.pos, token.end),
+        None => point_span(pos),
+    }
+}
+
+/// The span of the whole of line `line`
+fn line_span(source: &str, line: usize) -> Span {
+    let offset: usize = source.lines().take(line - 1).map(|l| l.len() + 1).sum();
+    let len = source.lines().nth(line - 1).map_or(0, str::len);
+    Span::new(
+        Position::new(line, 1, offset),
+        Position::new(line, len + 1, offset + len),
+    )
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_reports_every_type_error() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        let path = dir.join("main.fsx");
+        fs::write(
+            &path,
+            "let a = 1 + true\nlet b = a + 1\nlet c = if b then 1 else 2\nlet d = List.map\n",
+        )
+        .unwrap();
+
+        let report = Checker::new().check(&path).unwrap();
+        assert_eq!(report.files, vec![path.clone()]);
+        let lines: Vec<_> = report
+            .diagnostics
+            .iter()
+            .map(|d| (d.kind, d.span.map(|s| s.start.line)))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (DiagnosticKind::Type, Some(1)),
+                (DiagnosticKind::Type, Some(3))
+            ]
+        );
+        let report = &report.diagnostics[1].report;
+        assert!(report.contains("main.fsx:3:9"));
+        assert!(report.contains("let c = if b then 1 else 2"));
+    }
+
+    #[test]
+    fn test_check_resolves_providers() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        fs::write(
+            dir.join("settings.toml"),
+            "name = \"app\"\n[db]\nport = 5432\n",
+        )
+        .unwrap();
+        let path = dir.join("main.fsx");
+        fs::write(
+            &path,
+            r#"type Settings = TomlProvider<"settings.toml">
+type Line = RegexProvider<"(?P<n>\d+)-(?P<w>\w+)">
+type Person = JsonSchemaProvider<"{ \"properties\": { \"name\": { \"type\": \"string\" }, \"role\": { \"enum\": [\"read-only\", \"admin\"] } }, \"required\": [\"name\"] }">
+
+let port text =
+    match Settings.parse text with
+    | Ok(settings) -> settings.db.port
+    | Error(message) -> 0
+let word text =
+    match Line.tryMatch text with
+    | Some(m) -> m.w
+    | None -> ""
+let role text =
+    match Person.parse text with
+    | Ok(person) ->
+        (match person.role with
+         | Some(ReadOnly) -> person.name ++ " reads"
+         | Some(Admin) -> person.name ++ " administers"
+         | None -> person.name)
+    | Error(message) -> message
+"#,
+        )
+        .unwrap();
+
+        let report = Checker::new().check(&path).unwrap();
+        assert!(report.is_success(), "{:?}", report.diagnostics);
+    }
+
+    #[test]
+    fn test_check_reports_unknown_provider_fields() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        let path = dir.join("main.fsx");
+        fs::write(
+            &path,
+            r#"type Settings = TomlProvider<"name = \"app\"\n[db]\nport = 5432">
+type Line = RegexProvider<"(?P<n>\d+)">
+type Missing = TomlProvider<"missing.toml">
+
+let port text =
+    match Settings.parse text with
+    | Ok(settings) -> settings.db.nope
+    | Error(message) -> 0
+let word text =
+    match Line.tryMatch text with
+    | Some(m) -> m.nope
+    | None -> ""
+"#,
+        )
+        .unwrap();
+
+        let report = Checker::new().check(&path).unwrap();
+        let found: Vec<_> = report
+            .diagnostics
+            .iter()
+            .map(|d| (d.kind, d.span.map(|s| s.start.line)))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (DiagnosticKind::Provider, Some(3)),
+                (DiagnosticKind::Type, Some(7)),
+                (DiagnosticKind::Type, Some(11))
+            ]
+        );
+        assert!(report.diagnostics[1]
+            .message
+            .starts_with("Field 'nope' not found in record type {port: int}"));
+    }
+
+    #[test]
+    fn test_check_directory() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        fs::write(
+            dir.join("lib/utils.fsx"),
+            "module Utils =\n    let add x y = x + y\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("main.fsx"),
+            "#load \"lib/utils.fsx\"\n#load \"missing.fsx\"\n\nlet sum = Utils.add 1 2\n",
+        )
+        .unwrap();
+        fs::write(dir.join("lex.fsx"), "let x = 1 $ 2\n").unwrap();
+        fs::write(
+            dir.join("parse.fsx"),
+            "let x = (1 +\nlet y = [1; 2 *]\nlet z = if true then y else [\"a\"]\n",
+        )
+        .unwrap();
+        fs::write(dir.join(".hidden/bad.fsx"), "let x = (\n").unwrap();
+
+        let report = Checker::new().check(dir).unwrap();
+        assert_eq!(report.files.len(), 4);
+        let kinds: Vec<_> = report
+            .diagnostics
+            .iter()
+            .map(|d| (d.path.file_name().unwrap().to_str().unwrap(), d.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("lex.fsx", DiagnosticKind::Lex),
+                ("main.fsx", DiagnosticKind::Load),
+                ("parse.fsx", DiagnosticKind::Parse),
+                ("parse.fsx", DiagnosticKind::Parse),
+                ("parse.fsx", DiagnosticKind::Type)
+            ]
+        );
+        let lines: Vec<_> = report.diagnostics[1..]
+            .iter()
+            .map(|d| d.span.map(|s| s.start.line))
+            .collect();
+        assert_eq!(lines, [Some(2), Some(2), Some(2), Some(3)]);
+        assert!(!report.is_success());
+    }
+
+    #[test]
+    fn test_parse_error_spans_token() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        let path = dir.join("main.fsx");
+        fs::write(&path, "let x = if true 1 else 2\n").unwrap();
+
+        let report = Checker::new().check(&path).unwrap();
+        let diagnostic = &report.diagnostics[0];
+        assert_eq!(diagnostic.kind, DiagnosticKind::Parse);
+        let span = diagnostic.span.unwrap();
+        assert_eq!((span.start.column, span.end.column), (19, 23));
+        assert!(diagnostic.report.contains("^^^^"), "{}", diagnostic.report);
+    }
+
+    #[test]
+    fn test_report_to_json() {
+        let report = CheckReport {
+            files: vec![PathBuf::from("a.fsx")],
+            diagnostics: vec![diagnostic(
+                Path::new("a.fsx"),
+                "let x = \"a\"\n",
+                DiagnosticKind::Type,
+                "Expected \"int\"\n".to_string(),
+                Some(point_span(Position::new(1, 9, 8))),
+            )],
+        };
+        assert_eq!(
+            report.to_json(),
+            "[\n  {\"file\": \"a.fsx\", \"line\": 1, \"column\": 9, \"endLine\": 1, \
+             \"endColumn\": 9, \"severity\": \"error\", \"kind\": \"type\", \
+             \"message\": \"Expected \\\"int\\\"\\n\"}\n]\n"
+        );
+        assert_eq!(CheckReport::default().to_json(), "[]\n");
+    }
+
+    #[test]
+    fn test_report_to_json_spans() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        let path = dir.join("a.fsx");
+        fs::write(&path, "let a = 1 + \"x\"\n").unwrap();
+        let mut report = Checker::new().check(&path).unwrap();
+        report.diagnostics[0].path = PathBuf::from("a.fsx");
+        report.diagnostics.push(diagnostic(
+            Path::new("a.fsx"),
+            "",
+            DiagnosticKind::Parse,
+            "Unexpected end of input".to_string(),
+            None,
+        ));
+
+        // The span of `1 + "x"` ends on its line, not at the next one
+        let json = report.to_json();
+        assert!(
+            json.starts_with(
+                "[\n  {\"file\": \"a.fsx\", \"line\": 1, \"column\": 9, \"endLine\": 1, \
+                 \"endColumn\": 16, \"severity\": \"error\", \"kind\": \"type\","
+            ),
+            "{}",
+            json
+        );
+        assert!(json.ends_with(
+            "\n  {\"file\": \"a.fsx\", \"line\": 0, \"column\": 0, \"severity\": \"error\", \
+             \"kind\": \"parse\", \"message\": \"Unexpected end of input\"}\n]\n"
+        ));
+    }
+}
@@ -0,0 +1,916 @@
+//! API documentation
+//!
+//! [`DocGenerator`] documents the modules, types and bindings of `.fsx`
+//! files: each declaration with its `///` comment, the fields or cases of
+//! types, and the inferred type of bindings. [`Documentation`] renders the
+//! result as Markdown or HTML pages, one per file plus an index.
+//!
+//! Fenced code blocks in comments are examples. They are run as doctests
+//! after the declarations of their file and pass if they run without error,
+//! so `Test.equal` can check what they compute. `fus doc` and `fpm doc` are
+//! built on it.
+//!
+//! ```no_run
+//! use fusabi::doc::{DocFormat, DocGenerator};
+//!
+//! let docs = DocGenerator::new().title("geometry").document("src").unwrap();
+//! assert!(docs.doctests.iter().all(|doctest| doctest.passed()));
+//! docs.write("target/doc", DocFormat::Html).unwrap();
+//! ```
+
+use crate::check::{self, Checker};
+use crate::{parse_recovering, register_script_eval_override, script_dir, FusabiError};
+use fusabi_frontend::ast::TypeDefinition;
+use fusabi_frontend::provider_resolver::resolve_source_paths;
+use fusabi_frontend::{Compiler, Expr, Lexer, ModuleItem, Parser, Program, Type, TypeVar};
+use fusabi_vm::Vm;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Languages of the fenced code blocks that are run as doctests; a block
+/// without a language is also run
+const EXAMPLE_LANGUAGES: &[&str] = &["fusabi", "fsharp", "fsx"];
+
+/// Language tag of rendered signatures and of examples written without one,
+/// so that renderers highlight them as F#
+const CODE_LANGUAGE: &str = "fsharp";
+
+/// The format documentation is rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+impl DocFormat {
+    /// Extension of the rendered pages
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DocFormat::Markdown => "md",
+            DocFormat::Html => "html",
+        }
+    }
+}
+
+/// What a documented declaration declares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Module,
+    Type,
+    Function,
+    Value,
+}
+
+/// A declaration and its documentation
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocItem {
+    pub kind: ItemKind,
+    /// Name, qualified by the module declaring it
+    pub name: String,
+    /// `type Shape` or `module Math`, or for bindings the name and inferred
+    /// type, as in `area : float -> float`
+    pub signature: String,
+    /// Fields of a record type or cases of a union type
+    pub members: Vec<String>,
+    /// The `///` comment
+    pub doc: Option<String>,
+}
+
+/// The documentation of one file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocPage {
+    pub path: PathBuf,
+    /// Name of the page: the file's path below the documented directory,
+    /// without extension and with `.` between directories
+    pub name: String,
+    /// Modules, each followed by its members, then top-level declarations,
+    /// in source order
+    pub items: Vec<DocItem>,
+}
+
+/// An example that was run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Doctest {
+    /// The file of the documented declaration
+    pub path: PathBuf,
+    /// Name of the documented declaration
+    pub item: String,
+    pub source: String,
+    /// Why the example did not run, if it failed
+    pub error: Option<String>,
+}
+
+impl Doctest {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Documentation of a set of files
+#[derive(Debug, Clone, PartialEq)]
+pub struct Documentation {
+    pub title: String,
+    pub pages: Vec<DocPage>,
+    pub doctests: Vec<Doctest>,
+}
+
+impl Documentation {
+    /// The index page, linking to the page of each file
+    pub fn render_index(&self, format: DocFormat) -> String {
+        let mut renderer = Renderer::new(format);
+        renderer.heading(1, &self.title, "");
+        let links: Vec<(String, String)> = self
+            .pages
+            .iter()
+            .map(|page| {
+                let target = format!("{}.{}", page.name, format.extension());
+                (page.name.clone(), target)
+            })
+            .collect();
+        renderer.links(&links);
+        renderer.finish(&self.title)
+    }
+
+    /// The page documenting one file
+    pub fn render_page(&self, page: &DocPage, format: DocFormat) -> String {
+        let mut renderer = Renderer::new(format);
+        renderer.heading(1, "", &page.name);
+
+        let top_level: Vec<&DocItem> = page
+            .items
+            .iter()
+            .filter(|item| item.kind != ItemKind::Module && !item.name.contains('.'))
+            .collect();
+        render_sections(&mut renderer, 2, &top_level);
+
+        for module in page.items.iter().filter(|i| i.kind == ItemKind::Module) {
+            renderer.heading(2, "Module ", &module.name);
+            if let Some(doc) = &module.doc {
+                renderer.text(doc);
+            }
+            let prefix = format!("{}.", module.name);
+            let members: Vec<&DocItem> = page
+                .items
+                .iter()
+                .filter(|item| item.name.starts_with(&prefix))
+                .collect();
+            render_sections(&mut renderer, 3, &members);
+        }
+        renderer.finish(&page.name)
+    }
+
+    /// Write the index and the pages to `dir`, returning the files written
+    pub fn write(
+        &self,
+        dir: impl AsRef<Path>,
+        format: DocFormat,
+    ) -> Result<Vec<PathBuf>, FusabiError> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let index = dir.join(format!("index.{}", format.extension()));
+        fs::write(&index, self.render_index(format))?;
+        let mut written = vec![index];
+        for page in &self.pages {
+            let path = dir.join(format!("{}.{}", page.name, format.extension()));
+            fs::write(&path, self.render_page(page, format))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+/// Documents `.fsx` files
+#[derive(Debug, Clone)]
+pub struct DocGenerator {
+    title: Option<String>,
+    doctests: bool,
+}
+
+impl Default for DocGenerator {
+    fn default() -> Self {
+        Self {
+            title: None,
+            doctests: true,
+        }
+    }
+}
+
+impl DocGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Title of the index page (default: "API documentation")
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Whether to run the examples in comments (default: true)
+    pub fn doctests(mut self, doctests: bool) -> Self {
+        self.doctests = doctests;
+        self
+    }
+
+    /// Document every file at each of `paths`
+    pub fn document_all<P: AsRef<Path>>(&self, paths: &[P]) -> Result<Documentation, FusabiError> {
+        let mut docs = Documentation {
+            title: self
+                .title
+                .clone()
+                .unwrap_or_else(|| "API documentation".to_string()),
+            pages: Vec::new(),
+            doctests: Vec::new(),
+        };
+        for path in paths {
+            let root = path.as_ref();
+            for file in Checker::discover(root)? {
+                let name = page_name(root, &file);
+                self.document_file(&file, name, &mut docs)?;
+            }
+        }
+        Ok(docs)
+    }
+
+    /// Document the file at `path`, or every `.fsx` file under the directory
+    pub fn document(&self, path: impl AsRef<Path>) -> Result<Documentation, FusabiError> {
+        self.document_all(&[path])
+    }
+
+    /// Add the page of one file, and the doctests of its examples, to `docs`
+    fn document_file(
+        &self,
+        path: &Path,
+        name: String,
+        docs: &mut Documentation,
+    ) -> Result<(), FusabiError> {
+        let source = fs::read_to_string(path)?;
+        let tokens = Lexer::new(&source).tokenize()?;
+        // Document what parses; `fus check` reports the errors
+        let (mut program, _) = Parser::new(tokens).parse_program_recovering();
+        resolve_source_paths(&mut program, script_dir(path));
+        let loaded = check::load(path, &source, &program.directives, &mut Vec::new());
+        let types = signatures(&program, &loaded);
+
+        let mut items = Vec::new();
+        for module in &program.modules {
+            items.push(DocItem {
+                kind: ItemKind::Module,
+                name: module.name.clone(),
+                signature: format!("module {}", module.name),
+                members: Vec::new(),
+                doc: program.docs.get(&module.name).cloned(),
+            });
+            let prefix = format!("{}.", module.name);
+            declarations(&module.items, &prefix, &program, &types, &mut items);
+        }
+        declarations(&program.items, "", &program, &types, &mut items);
+
+        if self.doctests {
+            let linked = check::link(&loaded, &program);
+            for item in &items {
+                for example in item.doc.as_deref().map(examples).unwrap_or_default() {
+                    docs.doctests.push(Doctest {
+                        path: path.to_path_buf(),
+                        item: item.name.clone(),
+                        error: run_example(&linked, &example).err(),
+                        source: example,
+                    });
+                }
+            }
+        }
+        docs.pages.push(DocPage {
+            path: path.to_path_buf(),
+            name,
+            items,
+        });
+        Ok(())
+    }
+}
+
+/// Name of the page of `file`, found under `root`
+fn page_name(root: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(root).unwrap_or(file).with_extension("");
+    let parts: Vec<String> = relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    match parts.join(".") {
+        name if name.is_empty() => file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        name => name,
+    }
+}
+
+/// Add the declarations of `items`, qualified with `prefix`, to `docs`.
+/// Names starting with `_` are private and left out, and a later binding
+/// replaces an earlier one of the same name.
+fn declarations(
+    items: &[ModuleItem],
+    prefix: &str,
+    program: &Program,
+    types: &HashMap<String, Type>,
+    docs: &mut Vec<DocItem>,
+) {
+    let mut add = |item: DocItem| {
+        if !item.name[prefix.len()..].starts_with('_') {
+            docs.retain(|other| other.name != item.name);
+            docs.push(item);
+        }
+    };
+    let binding = |name: &str, value: &Expr| {
+        let name = format!("{}{}", prefix, name);
+        let kind = match types.get(&name) {
+            Some(Type::Function(..)) => ItemKind::Function,
+            Some(_) => ItemKind::Value,
+            None if value.unspanned().is_lambda() => ItemKind::Function,
+            None => ItemKind::Value,
+        };
+        DocItem {
+            kind,
+            signature: match types.get(&name) {
+                Some(ty) => format!("{} : {}", name, type_signature(ty)),
+                None => name.clone(),
+            },
+            members: Vec::new(),
+            doc: program.docs.get(&name).cloned(),
+            name,
+        }
+    };
+
+    for item in items {
+        match item {
+            ModuleItem::Let(Some(name), value) => add(binding(name, value)),
+            ModuleItem::LetRec(group) => {
+                for (name, value) in group {
+                    add(binding(name, value));
+                }
+            }
+            ModuleItem::TypeDef(definition) => {
+                let (type_name, signature, members) = match definition {
+                    TypeDefinition::Record(record) => (
+                        &record.name,
+                        format!("type {}", record.name),
+                        record
+                            .fields
+                            .iter()
+                            .map(|(field, ty)| format!("{}: {}", field, ty))
+                            .collect(),
+                    ),
+                    TypeDefinition::Du(du) => (
+                        &du.name,
+                        format!("type {}", du.name),
+                        du.variants.iter().map(ToString::to_string).collect(),
+                    ),
+                    TypeDefinition::Provider(provider) => {
+                        (&provider.name, provider.to_string(), Vec::new())
+                    }
+                };
+                let name = format!("{}{}", prefix, type_name);
+                add(DocItem {
+                    kind: ItemKind::Type,
+                    signature,
+                    members,
+                    doc: program.docs.get(&name).cloned(),
+                    name,
+                });
+            }
+            ModuleItem::Let(None, _) | ModuleItem::Module(_) => {}
+        }
+    }
+}
+
+/// The inferred types of the bindings of `program` and of its modules, by
+/// qualified name. Bindings that do not type check are left out.
+fn signatures(program: &Program, loaded: &[Program]) -> HashMap<String, Type> {
+    let (mut inference, env) = check::environment(program, loaded, |_, _| {});
+    let mut types = HashMap::new();
+    let scopes = program
+        .modules
+        .iter()
+        .map(|module| (format!("{}.", module.name), &module.items))
+        .chain([(String::new(), &program.items)]);
+    for (prefix, items) in scopes {
+        // Members of a module see the members before them unqualified
+        let mut env = env.clone();
+        for (name, value) in check::bindings(items) {
+            let scheme = match inference.infer_and_solve(&value, &env) {
+                Ok(ty) => {
+                    let scheme = env.generalize(&ty);
+                    if let Some(name) = &name {
+                        types.insert(format!("{}{}", prefix, name), ty);
+                    }
+                    scheme
+                }
+                Err(_) => check::any(),
+            };
+            if let Some(name) = name {
+                env.insert(name, scheme);
+            }
+        }
+    }
+    types
+}
+
+/// `ty` as written in a signature: type variables are named `'a`, `'b`, ...
+/// in order of appearance and record fields are sorted
+fn type_signature(ty: &Type) -> String {
+    fn write_type(ty: &Type, vars: &mut Vec<TypeVar>, out: &mut String) {
+        match ty {
+            Type::Var(var) => {
+                let index = vars.iter().position(|v| v == var).unwrap_or_else(|| {
+                    vars.push(var.clone());
+                    vars.len() - 1
+                });
+                match index {
+                    0..=25 => {
+                        let _ = write!(out, "'{}", (b'a' + index as u8) as char);
+                    }
+                    _ => {
+                        let _ = write!(out, "'t{}", index);
+                    }
+                }
+            }
+            Type::Tuple(elems) => {
+                out.push('(');
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(" * ");
+                    }
+                    write_type(elem, vars, out);
+                }
+                out.push(')');
+            }
+            Type::List(elem) => {
+                write_type(elem, vars, out);
+                out.push_str(" list");
+            }
+            Type::Array(elem) => {
+                write_type(elem, vars, out);
+                out.push_str("[]");
+            }
+            Type::Function(param, ret) => {
+                let nested = matches!(param.as_ref(), Type::Function(..));
+                if nested {
+                    out.push('(');
+                }
+                write_type(param, vars, out);
+                if nested {
+                    out.push(')');
+                }
+                out.push_str(" -> ");
+                write_type(ret, vars, out);
+            }
+            Type::Record(fields) => {
+                let mut fields: Vec<_> = fields.iter().collect();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                out.push('{');
+                for (i, (name, ty)) in fields.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push_str("; ");
+                    }
+                    let _ = write!(out, "{}: ", name);
+                    write_type(ty, vars, out);
+                }
+                out.push('}');
+            }
+            Type::Variant(name, params) if !params.is_empty() => {
+                let _ = write!(out, "{}<", name);
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    write_type(param, vars, out);
+                }
+                out.push('>');
+            }
+            _ => {
+                let _ = write!(out, "{}", ty);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    write_type(ty, &mut Vec::new(), &mut out);
+    out
+}
+
+/// The runnable fenced code blocks of a comment
+fn examples(doc: &str) -> Vec<String> {
+    let mut examples = Vec::new();
+    let mut example: Option<(bool, String)> = None;
+    for line in doc.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        match (fence, example.take()) {
+            (Some(language), None) => {
+                let language = language.trim();
+                let runnable = language.is_empty() || EXAMPLE_LANGUAGES.contains(&language);
+                example = Some((runnable, String::new()));
+            }
+            (Some(_), Some((runnable, code))) => {
+                if runnable {
+                    examples.push(code);
+                }
+            }
+            (None, Some((runnable, mut code))) => {
+                code.push_str(line);
+                code.push('\n');
+                example = Some((runnable, code));
+            }
+            (None, None) => {}
+        }
+    }
+    examples
+}
+
+/// Run `example` after the declarations of `program` in a fresh VM
+fn run_example(program: &Program, example: &str) -> Result<(), String> {
+    let tokens = Lexer::new(example)
+        .tokenize()
+        .map_err(|error| error.to_string())?;
+    let example = parse_recovering(Parser::new(tokens)).map_err(|error| error.to_string())?;
+
+    let mut program = program.clone();
+    program.modules.extend(example.modules);
+    program.imports.extend(example.imports);
+    program.items.extend(example.items);
+    program.main_expr = example.main_expr;
+    let chunk = Compiler::compile_program(&program).map_err(|error| error.to_string())?;
+
+    let mut vm = Vm::new();
+    fusabi_vm::stdlib::register_stdlib(&mut vm);
+    register_script_eval_override(&mut vm);
+    vm.execute(chunk).map_err(|error| error.to_string())?;
+    Ok(())
+}
+
+/// Render `items` in sections of functions, values and types, under
+/// headings of `level`
+fn render_sections(renderer: &mut Renderer, level: usize, items: &[&DocItem]) {
+    let sections = [
+        (ItemKind::Type, "Types"),
+        (ItemKind::Function, "Functions"),
+        (ItemKind::Value, "Values"),
+    ];
+    for (kind, title) in sections {
+        let items: Vec<&&DocItem> = items.iter().filter(|item| item.kind == kind).collect();
+        if items.is_empty() {
+            continue;
+        }
+        renderer.heading(level, title, "");
+        for item in items {
+            renderer.heading(level + 1, "", &item.name);
+            renderer.code_block(&item.signature, CODE_LANGUAGE);
+            renderer.list(&item.members);
+            if let Some(doc) = &item.doc {
+                renderer.text(doc);
+            }
+        }
+    }
+}
+
+/// Writes the blocks of a page in a [`DocFormat`]
+struct Renderer {
+    format: DocFormat,
+    out: String,
+}
+
+impl Renderer {
+    fn new(format: DocFormat) -> Self {
+        Self {
+            format,
+            out: String::new(),
+        }
+    }
+
+    /// A heading of `text` followed by `name` as code, if not empty
+    fn heading(&mut self, level: usize, text: &str, name: &str) {
+        let out = &mut self.out;
+        match self.format {
+            DocFormat::Markdown => {
+                let name = match name {
+                    "" => String::new(),
+                    name => format!("`{}`", name),
+                };
+                let _ = write!(out, "{} {}{}\n\n", "#".repeat(level), text, name);
+            }
+            DocFormat::Html => {
+                let _ = write!(out, "<h{}", level);
+                if !name.is_empty() {
+                    let _ = write!(out, " id=\"{}\"", escape_html(name));
+                }
+                let _ = write!(out, ">{}", escape_html(text));
+                if !name.is_empty() {
+                    let _ = write!(out, "<code>{}</code>", escape_html(name));
+                }
+                let _ = writeln!(out, "</h{}>", level);
+            }
+        }
+    }
+
+    fn code_block(&mut self, code: &str, language: &str) {
+        let code = code.trim_end();
+        let _ = match self.format {
+            DocFormat::Markdown => write!(self.out, "```{}\n{}\n```\n\n", language, code),
+            DocFormat::Html => writeln!(
+                self.out,
+                "<pre><code class=\"language-{}\">{}</code></pre>",
+                escape_html(language),
+                escape_html(code)
+            ),
+        };
+    }
+
+    /// A bulleted list of code
+    fn list(&mut self, items: &[String]) {
+        if items.is_empty() {
+            return;
+        }
+        let out = &mut self.out;
+        match self.format {
+            DocFormat::Markdown => {
+                for item in items {
+                    let _ = writeln!(out, "- `{}`", item);
+                }
+                out.push('\n');
+            }
+            DocFormat::Html => {
+                out.push_str("<ul>\n");
+                for item in items {
+                    let _ = writeln!(out, "<li><code>{}</code></li>", escape_html(item));
+                }
+                out.push_str("</ul>\n");
+            }
+        }
+    }
+
+    /// A bulleted list of `(text, target)` links
+    fn links(&mut self, links: &[(String, String)]) {
+        let out = &mut self.out;
+        match self.format {
+            DocFormat::Markdown => {
+                for (text, target) in links {
+                    let _ = writeln!(out, "- [{}]({})", text, target);
+                }
+                out.push('\n');
+            }
+            DocFormat::Html => {
+                out.push_str("<ul>\n");
+                for (text, target) in links {
+                    let _ = writeln!(
+                        out,
+                        "<li><a href=\"{}\">{}</a></li>",
+                        escape_html(target),
+                        escape_html(text)
+                    );
+                }
+                out.push_str("</ul>\n");
+            }
+        }
+    }
+
+    /// A comment, which is Markdown. In HTML, paragraphs, fenced code
+    /// blocks and `code` spans are kept. Code blocks without a language are
+    /// examples, tagged [`CODE_LANGUAGE`].
+    fn text(&mut self, doc: &str) {
+        let mut paragraph: Vec<&str> = Vec::new();
+        let mut code: Option<(&str, Vec<&str>)> = None;
+        for line in doc.trim_end().lines().chain([""]) {
+            let fence = line.trim_start().strip_prefix("```").map(str::trim);
+            match (fence, code.as_mut()) {
+                (None, Some((_, lines))) => lines.push(line),
+                (Some(_), Some(_)) => {
+                    let (language, lines) = code.take().unwrap();
+                    self.code_block(&lines.join("\n"), language);
+                }
+                (_, None) if fence.is_some() || line.trim().is_empty() => {
+                    self.paragraph(&paragraph);
+                    paragraph.clear();
+                    if let Some(language) = fence {
+                        let language = match language {
+                            "" => CODE_LANGUAGE,
+                            language => language,
+                        };
+                        code = Some((language, Vec::new()));
+                    }
+                }
+                (_, None) => paragraph.push(line),
+            }
+        }
+        if let Some((language, lines)) = code {
+            self.code_block(&lines.join("\n"), language);
+        }
+    }
+
+    /// The lines of a paragraph, if any
+    fn paragraph(&mut self, lines: &[&str]) {
+        if lines.is_empty() {
+            return;
+        }
+        let text = lines.join("\n");
+        let _ = match self.format {
+            DocFormat::Markdown => write!(self.out, "{}\n\n", text),
+            DocFormat::Html => writeln!(self.out, "<p>{}</p>", inline_html(&text)),
+        };
+    }
+
+    /// The page, titled `title`
+    fn finish(self, title: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => self.out,
+            DocFormat::Html => format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                 <title>{}</title>\n<style>\n\
+                 body {{ font-family: sans-serif; max-width: 50em; margin: 2em auto; }}\n\
+                 pre {{ background: #f4f4f4; padding: 0.5em; }}\n\
+                 </style>\n</head>\n<body>\n{}</body>\n</html>\n",
+                escape_html(title),
+                self.out
+            ),
+        }
+    }
+}
+
+/// `text` as HTML, with `code` spans
+fn inline_html(text: &str) -> String {
+    let mut html = String::new();
+    for (i, part) in text.split('`').enumerate() {
+        if i % 2 == 1 {
+            let _ = write!(html, "<code>{}</code>", escape_html(part));
+        } else {
+            html.push_str(&escape_html(part));
+        }
+    }
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const GEOMETRY: &str = "\
+/// Shapes to measure.
+type Shape = Circle of int | Square of int
+
+/// A point in the plane.
+type Point = { x: float; y: float }
+
+/// The area of a square.
+///
+/// ```
+/// Test.equal 9 (area 3)
+/// ```
+let area side = side * side
+
+/// Gives back its argument.
+///
+/// ```fusabi
+/// Test.equal 2 (identity 1)
+/// ```
+///
+/// ```text
+/// not run
+/// ```
+let identity x = x
+
+let unit = 1.5
+let _private = 0
+";
+
+    #[test]
+    fn test_document_declarations() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("shapes")).unwrap();
+        fs::write(dir.join("shapes/geometry.fsx"), GEOMETRY).unwrap();
+        fs::write(
+            dir.join("math.fsx"),
+            "/// Arithmetic.\nmodule Math =\n    /// Squares a number.\n    ///\n    /// ```\n    /// Test.equal 9 (Math.square 3)\n    /// ```\n    let square x = x * x\n    let cube x = x * square x\n",
+        )
+        .unwrap();
+
+        let docs = DocGenerator::new().title("demo").document(dir).unwrap();
+        assert_eq!(docs.title, "demo");
+        let names: Vec<&str> = docs.pages.iter().map(|page| page.name.as_str()).collect();
+        assert_eq!(names, ["math", "shapes.geometry"]);
+
+        let math = &docs.pages[0];
+        let items: Vec<(ItemKind, &str, &str)> = math
+            .items
+            .iter()
+            .map(|item| (item.kind, item.name.as_str(), item.signature.as_str()))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                (ItemKind::Module, "Math", "module Math"),
+                (
+                    ItemKind::Function,
+                    "Math.square",
+                    "Math.square : int -> int"
+                ),
+                (ItemKind::Function, "Math.cube", "Math.cube : int -> int"),
+            ]
+        );
+        assert_eq!(math.items[0].doc.as_deref(), Some("Arithmetic."));
+        assert_eq!(math.items[2].doc, None);
+
+        let geometry = &docs.pages[1];
+        let items: Vec<(ItemKind, &str)> = geometry
+            .items
+            .iter()
+            .map(|item| (item.kind, item.signature.as_str()))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                (ItemKind::Type, "type Shape"),
+                (ItemKind::Type, "type Point"),
+                (ItemKind::Function, "area : int -> int"),
+                (ItemKind::Function, "identity : 'a -> 'a"),
+                (ItemKind::Value, "unit : float"),
+            ]
+        );
+        assert_eq!(
+            geometry.items[0].members,
+            ["Circle of int", "Square of int"]
+        );
+        assert_eq!(geometry.items[1].members, ["x: float", "y: float"]);
+
+        let doctests: Vec<(&str, bool)> = docs
+            .doctests
+            .iter()
+            .map(|doctest| (doctest.item.as_str(), doctest.passed()))
+            .collect();
+        assert_eq!(
+            doctests,
+            [("Math.square", true), ("area", true), ("identity", false)]
+        );
+        assert_eq!(docs.doctests[2].source, "Test.equal 2 (identity 1)\n");
+
+        let docs = DocGenerator::new().doctests(false).document(dir).unwrap();
+        assert!(docs.doctests.is_empty());
+    }
+
+    #[test]
+    fn test_render_and_write() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        fs::write(dir.join("geometry.fsx"), GEOMETRY).unwrap();
+        let docs = DocGenerator::new()
+            .doctests(false)
+            .document(dir.join("geometry.fsx"))
+            .unwrap();
+
+        let markdown = docs.render_page(&docs.pages[0], DocFormat::Markdown);
+        assert!(markdown.starts_with("# `geometry`\n\n## Types\n\n### `Shape`\n\n"));
+        assert!(markdown.contains("```fsharp\ntype Shape\n```\n\n- `Circle of int`\n"));
+        assert!(markdown.contains(
+            "### `area`\n\n```fsharp\narea : int -> int\n```\n\nThe area of a square.\n\n\
+             ```fsharp\nTest.equal 9 (area 3)\n```\n"
+        ));
+        assert!(markdown.contains("```fusabi\nTest.equal 2 (identity 1)\n```\n"));
+        assert!(markdown.contains("```text\nnot run\n```\n"));
+        assert!(!markdown.contains("_private"));
+
+        let html = docs.render_page(&docs.pages[0], DocFormat::Html);
+        assert!(html.contains("<h3 id=\"area\"><code>area</code></h3>"));
+        assert!(
+            html.contains("<pre><code class=\"language-fsharp\">area : int -&gt; int</code></pre>")
+        );
+        assert!(html.contains("<p>The area of a square.</p>"));
+        assert!(html
+            .contains("<pre><code class=\"language-fsharp\">Test.equal 9 (area 3)</code></pre>"));
+
+        let index = docs.render_index(DocFormat::Markdown);
+        assert_eq!(
+            index,
+            "# API documentation\n\n- [geometry](geometry.md)\n\n"
+        );
+
+        let out = dir.join("doc");
+        let written = docs.write(&out, DocFormat::Html).unwrap();
+        assert_eq!(written, [out.join("index.html"), out.join("geometry.html")]);
+        let index = fs::read_to_string(out.join("index.html")).unwrap();
+        assert!(index.contains("<a href=\"geometry.html\">geometry</a>"));
+    }
+}
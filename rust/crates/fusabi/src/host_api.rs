@@ -2,8 +2,8 @@
 // Provides ergonomic embedding API for Rust applications
 
 use fusabi_frontend::compiler::CompileOptions;
-use fusabi_frontend::{Compiler, Lexer, Parser};
-use fusabi_vm::{HostData, HostRegistry, Value, Vm, VmError};
+use fusabi_frontend::{Compiler, Expr, Lexer, Parser, Program};
+use fusabi_vm::{ExecutionLimits, HostData, HostRegistry, Interrupt, Value, Vm, VmError};
 use std::any::Any;
 use std::collections::HashMap;
 use std::convert::TryInto;
@@ -225,6 +225,7 @@ impl FusabiEngine {
     ///     enable_type_checking: true,
     ///     verbose: false,
     ///     strict_mode: true,
+    ///     args: Vec::new(),
     /// };
     /// let result = engine.eval_with_options("let x = 42 in x * 2", options).unwrap();
     /// assert_eq!(result.as_int(), Some(84));
@@ -234,11 +235,6 @@ impl FusabiEngine {
         source: &str,
         options: crate::RunOptions,
     ) -> Result<Value, crate::FusabiError> {
-        // Sync global_bindings to vm.globals before execution
-        for (name, value) in self.global_bindings.iter() {
-            self.vm.globals.insert(name.clone(), value.clone());
-        }
-
         // Stage 1: Lexical Analysis
         let mut lexer = Lexer::new(source);
         let tokens = lexer.tokenize()?;
@@ -247,6 +243,43 @@ impl FusabiEngine {
         let mut parser = Parser::new(tokens);
         let ast = parser.parse()?;
 
+        self.execute_expr(&ast, options)
+    }
+
+    /// Compile and run an already parsed program
+    ///
+    /// Useful for hosts that inspect or rewrite the AST before running it,
+    /// e.g. to evaluate a script's top-level bindings without its main expression.
+    pub fn eval_program(
+        &mut self,
+        program: &Program,
+        options: crate::RunOptions,
+    ) -> Result<Value, crate::FusabiError> {
+        self.sync_globals();
+        let compile_options = CompileOptions {
+            enable_type_checking: options.enable_type_checking,
+            strict_mode: options.strict_mode,
+            allow_warnings: !options.strict_mode,
+            provider_resolver: None,
+        };
+        let chunk = Compiler::compile_program_with_options(program, compile_options)?;
+        Ok(self.vm.execute(chunk)?)
+    }
+
+    /// Sync global_bindings to vm.globals before execution
+    fn sync_globals(&mut self) {
+        for (name, value) in self.global_bindings.iter() {
+            self.vm.globals.insert(name.clone(), value.clone());
+        }
+    }
+
+    fn execute_expr(
+        &mut self,
+        ast: &Expr,
+        options: crate::RunOptions,
+    ) -> Result<Value, crate::FusabiError> {
+        self.sync_globals();
+
         // Stage 3: Compilation (with optional type checking)
         let compile_options = CompileOptions {
             enable_type_checking: options.enable_type_checking,
@@ -254,7 +287,7 @@ impl FusabiEngine {
             allow_warnings: !options.strict_mode,
             provider_resolver: None, // Type providers can be configured separately
         };
-        let chunk = Compiler::compile_with_options(&ast, compile_options)?;
+        let chunk = Compiler::compile_with_options(ast, compile_options)?;
 
         // Stage 4: Execution
         let result = self.vm.execute(chunk)?;
@@ -369,6 +402,39 @@ impl FusabiEngine {
         self.vm.call_value(func, args)
     }
 
+    /// Bound every subsequent evaluation (instructions, call depth, wall time)
+    pub fn set_limits(&mut self, limits: ExecutionLimits) {
+        self.vm.set_limits(limits);
+    }
+
+    /// The limits set by [`FusabiEngine::set_limits`]
+    pub fn limits(&self) -> ExecutionLimits {
+        self.vm.limits()
+    }
+
+    /// Handle for stopping an evaluation from another thread
+    ///
+    /// # Example
+    /// ```no_run
+    /// use fusabi::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// let interrupt = engine.interrupt_handle();
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(std::time::Duration::from_secs(1));
+    ///     interrupt.interrupt();
+    /// });
+    /// assert!(engine.eval("let rec loop n = loop n in loop 0").is_err());
+    /// ```
+    pub fn interrupt_handle(&mut self) -> Interrupt {
+        self.vm.interrupt_handle()
+    }
+
+    /// Stop evaluations with `interrupt`, which may be shared with other engines
+    pub fn set_interrupt_handle(&mut self, interrupt: Interrupt) {
+        self.vm.set_interrupt_handle(interrupt);
+    }
+
     /// Check if a host function is registered
     pub fn has_host_function(&self, name: &str) -> bool {
         self.host_registry.lock().unwrap().has_function(name)
@@ -542,6 +608,21 @@ mod tests {
         assert_eq!(engine.get_global("y"), None);
     }
 
+    #[test]
+    fn test_eval_program() {
+        let tokens = Lexer::new("let x = 20\nlet y = x + 1\ny")
+            .tokenize()
+            .unwrap();
+        let mut program = Parser::new(tokens).parse_program().unwrap();
+        program.main_expr = Some(Expr::Var("x".to_string()));
+
+        let mut engine = FusabiEngine::new();
+        let result = engine
+            .eval_program(&program, crate::RunOptions::default())
+            .unwrap();
+        assert_eq!(result, Value::Int(20));
+    }
+
     #[test]
     fn test_set_global_visible_in_eval() {
         // Test for issue #244: set_global() values should be visible in eval()
@@ -78,17 +78,26 @@
 //! assert_eq!(result.as_int(), Some(42));
 //! ```
 
-use fusabi_frontend::{Compiler, Lexer, Parser};
-use fusabi_vm::{deserialize_chunk, serialize_chunk, Chunk, Vm, VmError, FZB_MAGIC};
+use fusabi_frontend::provider_resolver::resolve_source_paths;
+use fusabi_frontend::{Compiler, Expr, Lexer, ModuleItem, Parser, Program};
+use fusabi_vm::{
+    deserialize_chunk, serialize_chunk, Artifact, ArtifactError, Chunk, Export, Vm, VmError,
+    FZB_MAGIC, FZP_MAGIC,
+};
 use std::error::Error;
 use std::fmt;
 use std::fs;
+use std::path::Path;
 use std::string::FromUtf8Error;
 
+pub mod bundle;
+pub mod check;
+pub mod doc;
 pub mod host_api;
+pub mod testing;
 
 // Re-export the primary API at the crate root for easy access
-pub use fusabi_vm::{HostData, Value};
+pub use fusabi_vm::{ExecutionLimits, HostData, Interrupt, Profile, ProfileMetric, Value};
 pub use host_api::{FusabiEngine as Engine, Module};
 // Re-export CompileOptions for advanced compilation control
 pub use fusabi_frontend::CompileOptions;
@@ -124,11 +133,14 @@ fn script_eval_impl(vm: &mut Vm, args: &[fusabi_vm::Value]) -> Result<fusabi_vm:
         Err(e) => return Err(VmError::Runtime(format!("Lex error: {}", e))),
     };
 
-    let mut parser = Parser::new(tokens);
-    let program = match parser.parse_program() {
-        Ok(p) => p,
-        Err(e) => return Err(VmError::Runtime(format!("Parse error: {}", e))),
-    };
+    let (program, errors) = Parser::new(tokens).parse_program_recovering();
+    if !errors.is_empty() {
+        let errors: Vec<String> = errors
+            .iter()
+            .map(|e| format!("Parse error: {}", e))
+            .collect();
+        return Err(VmError::Runtime(errors.join("\n")));
+    }
 
     let chunk = match Compiler::compile_program(&program) {
         Ok(c) => c,
@@ -173,10 +185,16 @@ pub enum FusabiError {
     Lex(fusabi_frontend::LexError),
     /// Parser error during parsing
     Parse(fusabi_frontend::ParseError),
+    /// Several parser errors, in source order
+    ParseErrors(Vec<fusabi_frontend::ParseError>),
+    /// A file named by a `#load` directive could not be loaded
+    Load(fusabi_frontend::LoadError),
     /// Compiler error during bytecode generation
     Compile(fusabi_frontend::CompileError),
     /// VM runtime error during execution
     Runtime(fusabi_vm::VmError),
+    /// A package artifact is malformed or lacks a module it needs
+    Artifact(ArtifactError),
     /// Error during bytecode serialization/deserialization
     Serde(Box<dyn std::error::Error + Send + Sync + 'static>),
     /// UTF-8 error when reading source file
@@ -189,8 +207,19 @@ impl fmt::Display for FusabiError {
             FusabiError::Io(e) => write!(f, "IO Error: {}", e),
             FusabiError::Lex(e) => write!(f, "Lexer Error: {}", e),
             FusabiError::Parse(e) => write!(f, "Parser Error: {}", e),
+            FusabiError::ParseErrors(errors) => {
+                for (i, e) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "Parser Error: {}", e)?;
+                }
+                Ok(())
+            }
+            FusabiError::Load(e) => write!(f, "Load Error: {}", e),
             FusabiError::Compile(e) => write!(f, "Compiler Error: {}", e),
             FusabiError::Runtime(e) => write!(f, "Runtime Error: {}", e),
+            FusabiError::Artifact(e) => write!(f, "Artifact Error: {}", e),
             FusabiError::Serde(e) => write!(f, "Serialization Error: {}", e),
             FusabiError::Utf8(e) => write!(f, "UTF-8 Error: {}", e),
         }
@@ -203,8 +232,11 @@ impl Error for FusabiError {
             FusabiError::Io(e) => Some(e),
             FusabiError::Lex(e) => Some(e),
             FusabiError::Parse(e) => Some(e),
+            FusabiError::ParseErrors(errors) => errors.first().map(|e| e as &(dyn Error + 'static)),
+            FusabiError::Load(e) => Some(e),
             FusabiError::Compile(e) => Some(e),
             FusabiError::Runtime(e) => Some(e),
+            FusabiError::Artifact(e) => Some(e),
             FusabiError::Serde(e) => Some(e.as_ref()),
             FusabiError::Utf8(e) => Some(e),
         }
@@ -229,6 +261,22 @@ impl From<fusabi_frontend::ParseError> for FusabiError {
     }
 }
 
+/// Parse a program, reporting every syntax error rather than only the first
+pub(crate) fn parse_recovering(mut parser: Parser) -> Result<Program, FusabiError> {
+    let (program, mut errors) = parser.parse_program_recovering();
+    match errors.len() {
+        0 => Ok(program),
+        1 => Err(FusabiError::Parse(errors.remove(0))),
+        _ => Err(FusabiError::ParseErrors(errors)),
+    }
+}
+
+impl From<fusabi_frontend::LoadError> for FusabiError {
+    fn from(err: fusabi_frontend::LoadError) -> Self {
+        FusabiError::Load(err)
+    }
+}
+
 impl From<fusabi_frontend::CompileError> for FusabiError {
     fn from(err: fusabi_frontend::CompileError) -> Self {
         FusabiError::Compile(err)
@@ -253,6 +301,15 @@ impl From<FromUtf8Error> for FusabiError {
     }
 }
 
+impl From<ArtifactError> for FusabiError {
+    fn from(err: ArtifactError) -> Self {
+        match err {
+            ArtifactError::Runtime(e) => FusabiError::Runtime(e),
+            other => FusabiError::Artifact(other),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct RunOptions {
     /// Enable type checking before compilation
@@ -261,6 +318,8 @@ pub struct RunOptions {
     pub verbose: bool,
     /// Strict mode - treat warnings as errors
     pub strict_mode: bool,
+    /// Command-line arguments the script reads with `Process.args`
+    pub args: Vec<String>,
 }
 
 /// Execute Mini-F# source code from a string (backward compatible)
@@ -279,6 +338,16 @@ pub fn run_source_checked(source: &str) -> Result<Value, FusabiError> {
 
 /// Execute Mini-F# source code with custom run options
 pub fn run_source_with_options(source: &str, options: RunOptions) -> Result<Value, FusabiError> {
+    run_source_in(source, None, options)
+}
+
+/// Execute source code, read from a file in `dir` if given, so that the
+/// files its type providers name are found next to it
+fn run_source_in(
+    source: &str,
+    dir: Option<&Path>,
+    options: RunOptions,
+) -> Result<Value, FusabiError> {
     if options.verbose {
         println!("=== Fusabi Execution Pipeline ===");
         println!("Type checking: {}", options.enable_type_checking);
@@ -300,8 +369,10 @@ pub fn run_source_with_options(source: &str, options: RunOptions) -> Result<Valu
     if options.verbose {
         println!("Stage 2: Parsing");
     }
-    let mut parser = Parser::new(tokens);
-    let program = parser.parse_program()?;
+    let mut program = parse_recovering(Parser::new(tokens))?;
+    if let Some(dir) = dir {
+        resolve_source_paths(&mut program, dir);
+    }
     if options.verbose {
         println!("  Parsed AST successfully");
     }
@@ -326,6 +397,7 @@ pub fn run_source_with_options(source: &str, options: RunOptions) -> Result<Valu
     fusabi_vm::stdlib::register_stdlib(&mut vm);
     // Override Script.eval with real implementation that has compiler access
     register_script_eval_override(&mut vm);
+    vm.set_args(options.args);
 
     let result = vm.execute(chunk)?;
     if options.verbose {
@@ -338,9 +410,27 @@ pub fn run_source_with_options(source: &str, options: RunOptions) -> Result<Valu
 
 /// Execute a Mini-F# script from a file (backward compatible)
 pub fn run_file(path: &str) -> Result<Value, FusabiError> {
+    run_file_with_options(path, RunOptions::default())
+}
+
+/// Execute a Mini-F# script from a file with type checking enabled
+pub fn run_file_checked(path: &str) -> Result<Value, FusabiError> {
+    let source = fs::read_to_string(path)?;
+    let options = RunOptions {
+        enable_type_checking: true,
+        ..Default::default()
+    };
+    run_source_in(&source, Some(script_dir(Path::new(path))), options)
+}
+
+/// Execute a script, bytecode or package artifact file with custom options
+pub fn run_file_with_options(path: &str, options: RunOptions) -> Result<Value, FusabiError> {
     let bytes = fs::read(path)?;
 
-    if bytes.starts_with(FZB_MAGIC) {
+    if bytes.starts_with(FZP_MAGIC) {
+        // It's a package artifact (.fzp)
+        run_artifact(&bytes, options.args)
+    } else if bytes.starts_with(FZB_MAGIC) {
         // It's a pre-compiled bytecode file (.fzb)
         let chunk = deserialize_chunk(&bytes)?;
 
@@ -348,25 +438,20 @@ pub fn run_file(path: &str) -> Result<Value, FusabiError> {
         fusabi_vm::stdlib::register_stdlib(&mut vm);
         // Override Script.eval with real implementation that has compiler access
         register_script_eval_override(&mut vm);
+        vm.set_args(options.args);
         let result = vm.execute(chunk)?;
         Ok(result)
     } else {
         // It's a source file (.fsx), compile it
         let source = String::from_utf8(bytes)?;
-        run_source(&source)
+        run_source_in(&source, Some(script_dir(Path::new(path))), options)
     }
 }
 
-/// Execute a Mini-F# script from a file with type checking enabled
-pub fn run_file_checked(path: &str) -> Result<Value, FusabiError> {
-    let source = fs::read_to_string(path)?;
-    run_source_checked(&source)
-}
-
-/// Execute a Mini-F# script from a file with custom options
-pub fn run_file_with_options(path: &str, options: RunOptions) -> Result<Value, FusabiError> {
-    let source = fs::read_to_string(path)?;
-    run_source_with_options(&source, options)
+/// The directory of the script at `path`, which the files its type
+/// providers name are relative to, as `#load` paths are
+pub(crate) fn script_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
 }
 
 /// Execute source with optional disassembly output (backward compatible)
@@ -376,8 +461,7 @@ pub fn run_source_with_disasm(source: &str, name: &str) -> Result<Value, FusabiE
     let tokens = lexer.tokenize()?;
 
     // Stage 2: Parsing
-    let mut parser = Parser::new(tokens);
-    let program = parser.parse_program()?;
+    let program = parse_recovering(Parser::new(tokens))?;
 
     // Stage 3: Compilation
     let chunk = Compiler::compile_program(&program)?;
@@ -399,8 +483,9 @@ pub fn run_source_with_disasm(source: &str, name: &str) -> Result<Value, FusabiE
     Ok(result)
 }
 
-/// Execute file with optional disassembly output
-pub fn run_file_with_disasm(path: &str) -> Result<Value, FusabiError> {
+/// Execute file with optional disassembly output, with `args` as the
+/// script's `Process.args ()`
+pub fn run_file_with_disasm(path: &str, args: Vec<String>) -> Result<Value, FusabiError> {
     let bytes = fs::read(path)?;
 
     let chunk = if bytes.starts_with(FZB_MAGIC) {
@@ -415,8 +500,8 @@ pub fn run_file_with_disasm(path: &str) -> Result<Value, FusabiError> {
         let tokens = lexer.tokenize()?;
 
         // Stage 2: Parsing
-        let mut parser = Parser::new(tokens);
-        let program = parser.parse_program()?;
+        let mut program = parse_recovering(Parser::new(tokens))?;
+        resolve_source_paths(&mut program, script_dir(Path::new(path)));
 
         // Stage 3: Compilation
         Compiler::compile_program(&program)?
@@ -432,11 +517,66 @@ pub fn run_file_with_disasm(path: &str) -> Result<Value, FusabiError> {
     fusabi_vm::stdlib::register_stdlib(&mut vm);
     // Override Script.eval with real implementation that has compiler access
     register_script_eval_override(&mut vm);
+    vm.set_args(args);
     let result = vm.execute(chunk)?;
 
     Ok(result)
 }
 
+/// Execute source with the profiler enabled, returning the result and the profile
+///
+/// The source is compiled with source spans so costs can be attributed to lines.
+pub fn run_source_with_profile(source: &str) -> Result<(Value, Profile), FusabiError> {
+    run_chunk_with_profile(compile_with_spans(source, None)?, Vec::new())
+}
+
+/// Execute a script or bytecode file with the profiler enabled, with `args`
+/// as the script's `Process.args ()`
+///
+/// Bytecode files are attributed to lines only if they were compiled with spans.
+pub fn run_file_with_profile(
+    path: &str,
+    args: Vec<String>,
+) -> Result<(Value, Profile), FusabiError> {
+    let bytes = fs::read(path)?;
+
+    let chunk = if bytes.starts_with(FZB_MAGIC) {
+        deserialize_chunk(&bytes)?
+    } else {
+        compile_with_spans(
+            &String::from_utf8(bytes)?,
+            Some(script_dir(Path::new(path))),
+        )?
+    };
+    run_chunk_with_profile(chunk, args)
+}
+
+fn compile_with_spans(source: &str, dir: Option<&Path>) -> Result<Chunk, FusabiError> {
+    let tokens = Lexer::new(source).tokenize()?;
+    let mut program = parse_recovering(Parser::new(tokens).with_spans())?;
+    if let Some(dir) = dir {
+        resolve_source_paths(&mut program, dir);
+    }
+    Ok(Compiler::compile_program(&program)?)
+}
+
+fn run_chunk_with_profile(
+    chunk: Chunk,
+    args: Vec<String>,
+) -> Result<(Value, Profile), FusabiError> {
+    let mut vm = Vm::new();
+    fusabi_vm::stdlib::register_stdlib(&mut vm);
+    // Override Script.eval with real implementation that has compiler access
+    register_script_eval_override(&mut vm);
+    vm.set_args(args);
+
+    vm.start_profiling();
+    let result = vm.execute(chunk)?;
+    let profile = vm.stop_profiling().unwrap_or_default();
+
+    Ok((result, profile))
+}
+
 // ============================================================================
 // Bytecode Compilation API
 // ============================================================================
@@ -503,8 +643,7 @@ pub fn compile_to_bytecode(source: &str) -> Result<Vec<u8>, FusabiError> {
     let tokens = lexer.tokenize()?;
 
     // Stage 2: Parsing
-    let mut parser = Parser::new(tokens);
-    let program = parser.parse_program()?;
+    let program = parse_recovering(Parser::new(tokens))?;
 
     // Stage 3: Compilation
     let chunk = Compiler::compile_program(&program)?;
@@ -519,7 +658,8 @@ pub fn compile_to_bytecode(source: &str) -> Result<Vec<u8>, FusabiError> {
 ///
 /// This is a convenience function that reads a .fsx file, compiles it to bytecode,
 /// and returns the serialized result. It's equivalent to reading the file and
-/// calling [`compile_to_bytecode`].
+/// calling [`compile_to_bytecode`], except that the files its type providers
+/// name are read relative to the file's directory.
 ///
 /// # Arguments
 ///
@@ -577,7 +717,11 @@ pub fn compile_to_bytecode(source: &str) -> Result<Vec<u8>, FusabiError> {
 /// ```
 pub fn compile_file_to_bytecode(path: &str) -> Result<Vec<u8>, FusabiError> {
     let source = fs::read_to_string(path)?;
-    compile_to_bytecode(&source)
+    let tokens = Lexer::new(&source).tokenize()?;
+    let mut program = parse_recovering(Parser::new(tokens))?;
+    resolve_source_paths(&mut program, script_dir(Path::new(path)));
+    let chunk = Compiler::compile_program(&program)?;
+    Ok(serialize_chunk(&chunk)?)
 }
 
 /// Compile source code to a Chunk (internal representation)
@@ -619,13 +763,32 @@ pub fn compile_file_to_bytecode(path: &str) -> Result<Vec<u8>, FusabiError> {
 /// let result = vm.execute(chunk).unwrap();
 /// ```
 pub fn compile_to_chunk(source: &str) -> Result<Chunk, FusabiError> {
+    compile_to_chunk_with_features(source, &[])
+}
+
+/// Compile Fusabi source code to a Chunk with features enabled
+///
+/// Code in `#if FEATURE` blocks is compiled only for the given features;
+/// otherwise this is the same as [`compile_to_chunk`].
+///
+/// # Example
+///
+/// ```
+/// use fusabi::compile_to_chunk_with_features;
+///
+/// let source = "#if terminal\nlet width = 80\n#else\nlet width = 0\n#endif\nwidth";
+/// let chunk = compile_to_chunk_with_features(source, &["terminal".to_string()]).unwrap();
+/// ```
+pub fn compile_to_chunk_with_features(
+    source: &str,
+    features: &[String],
+) -> Result<Chunk, FusabiError> {
     // Stage 1: Lexical Analysis
-    let mut lexer = Lexer::new(source);
+    let mut lexer = Lexer::new(source).with_features(features.iter().cloned());
     let tokens = lexer.tokenize()?;
 
     // Stage 2: Parsing
-    let mut parser = Parser::new(tokens);
-    let program = parser.parse_program()?;
+    let program = parse_recovering(Parser::new(tokens))?;
 
     // Stage 3: Compilation
     let chunk = Compiler::compile_program(&program)?;
@@ -633,6 +796,74 @@ pub fn compile_to_chunk(source: &str) -> Result<Chunk, FusabiError> {
     Ok(chunk)
 }
 
+/// Compile Fusabi source code to a library module of a package artifact
+///
+/// The module evaluates to a record of its top-level `let` bindings, except
+/// those whose names start with `_`, and any final expression is dropped.
+/// Returns the chunk and the exports it makes, for the artifact's module
+/// table. Code in `#if FEATURE` blocks is compiled only for the given
+/// features.
+///
+/// # Example
+///
+/// ```
+/// use fusabi::compile_module;
+///
+/// let (chunk, exports) = compile_module("let double x = x * 2\nlet answer = 42", &[]).unwrap();
+/// assert_eq!(exports[0].name, "double");
+/// assert_eq!(exports[0].arity, 1);
+/// assert_eq!(exports[1].arity, 0);
+/// ```
+pub fn compile_module(
+    source: &str,
+    features: &[String],
+) -> Result<(Chunk, Vec<Export>), FusabiError> {
+    let tokens = Lexer::new(source)
+        .with_features(features.iter().cloned())
+        .tokenize()?;
+    let mut program = parse_recovering(Parser::new(tokens))?;
+
+    let mut exports: Vec<Export> = Vec::new();
+    let bindings = program.items.iter().flat_map(|item| match item {
+        ModuleItem::Let(Some(name), value) => vec![(name, value)],
+        ModuleItem::LetRec(bindings) => bindings.iter().map(|(n, v)| (n, v)).collect(),
+        _ => Vec::new(),
+    });
+    for (name, value) in bindings {
+        if name.starts_with('_') {
+            continue;
+        }
+        // A later binding shadows an earlier one
+        exports.retain(|export| &export.name != name);
+        let mut arity = 0;
+        let mut body = value.unspanned();
+        while let Expr::Lambda { body: inner, .. } = body {
+            arity += 1;
+            body = inner.unspanned();
+        }
+        exports.push(Export {
+            name: name.clone(),
+            arity,
+        });
+    }
+
+    program.main_expr = Some(Expr::RecordLiteral {
+        type_name: String::new(),
+        fields: exports
+            .iter()
+            .map(|export| {
+                (
+                    export.name.clone(),
+                    Box::new(Expr::Var(export.name.clone())),
+                )
+            })
+            .collect(),
+    });
+    let chunk = Compiler::compile_program(&program)?;
+
+    Ok((chunk, exports))
+}
+
 /// Execute bytecode directly without saving to file
 ///
 /// This is a convenience function that deserializes and executes bytecode
@@ -677,6 +908,36 @@ pub fn execute_bytecode(bytecode: &[u8]) -> Result<Value, FusabiError> {
     Ok(result)
 }
 
+/// Execute the entry module of a package artifact
+///
+/// The modules the entry module requires are linked first; see
+/// [`fusabi_vm::Artifact`] to load modules individually.
+///
+/// # Example
+///
+/// ```no_run
+/// use fusabi::execute_artifact;
+///
+/// let bytes = std::fs::read("target/app.fzp").unwrap();
+/// let result = execute_artifact(&bytes).unwrap();
+/// ```
+pub fn execute_artifact(bytes: &[u8]) -> Result<Value, FusabiError> {
+    run_artifact(bytes, Vec::new())
+}
+
+fn run_artifact(bytes: &[u8], args: Vec<String>) -> Result<Value, FusabiError> {
+    let artifact = Artifact::from_bytes(bytes)?;
+
+    let mut vm = Vm::new();
+    fusabi_vm::stdlib::register_stdlib(&mut vm);
+    // Override Script.eval with real implementation that has compiler access
+    register_script_eval_override(&mut vm);
+    vm.set_args(args);
+    let result = artifact.run(&mut vm)?;
+
+    Ok(result)
+}
+
 /// Compile and execute source in one step, returning both bytecode and result
 ///
 /// This is useful for development workflows where you want to cache the bytecode
@@ -793,6 +1054,36 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_run_source_reports_every_parse_error() {
+        let error = run_source("let a = 1 +\nlet b = [1; 2 *; 3]\nlet c = 3\nc").unwrap_err();
+        let FusabiError::ParseErrors(errors) = &error else {
+            panic!("expected parse errors, got {}", error);
+        };
+        assert_eq!(errors.len(), 2);
+        assert_eq!(error.to_string().lines().count(), 2);
+        assert!(error
+            .to_string()
+            .lines()
+            .all(|line| line.starts_with("Parser Error: ")));
+
+        let error = run_source("let a = 1 +\nlet b = 2\nb").unwrap_err();
+        assert!(matches!(error, FusabiError::Parse(_)), "{}", error);
+    }
+
+    #[test]
+    fn test_invalid_artifact() {
+        let error = execute_artifact(b"NOT_FZP").unwrap_err();
+        assert!(matches!(
+            error,
+            FusabiError::Artifact(ArtifactError::InvalidHeader(_))
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Artifact Error: Invalid artifact: missing magic bytes"
+        );
+    }
+
     #[test]
     fn test_compile_file_to_bytecode() {
         use std::io::Write;
@@ -819,4 +1110,56 @@ mod tests {
         // Cleanup
         std::fs::remove_file(&temp_path).unwrap();
     }
+
+    #[test]
+    fn test_run_source_with_profile() {
+        let source =
+            "let square x =\n    x * x\nlet xs = List.map square [1; 2; 3; 4]\nList.length xs\n";
+        let (result, profile) = run_source_with_profile(source).unwrap();
+        assert_eq!(result.as_int(), Some(4));
+
+        let square = profile
+            .functions
+            .iter()
+            .find(|f| f.name == "square")
+            .unwrap();
+        assert!(square.self_cost.instructions > 0);
+        let main = profile
+            .functions
+            .iter()
+            .find(|f| f.name == "<main>")
+            .unwrap();
+        assert_eq!(main.total_cost, profile.total);
+        assert!(profile
+            .lines
+            .iter()
+            .any(|l| l.function == "square" && l.line == 2));
+
+        let folded = profile.folded(ProfileMetric::Instructions);
+        assert!(folded.lines().any(|l| l.starts_with("<main>;square ")));
+        let total: u64 = folded
+            .lines()
+            .map(|l| l.rsplit(' ').next().unwrap().parse::<u64>().unwrap())
+            .sum();
+        assert_eq!(total, profile.total.instructions);
+    }
+
+    #[test]
+    fn test_profile_names_nested_calls() {
+        let source = "let step x = x + 1\nlet go x = step (step x)\ngo 1\n";
+        let (result, profile) = run_source_with_profile(source).unwrap();
+        assert_eq!(result.as_int(), Some(3));
+        let folded = profile.folded(ProfileMetric::Instructions);
+        assert!(folded.lines().any(|l| l.starts_with("<main>;go;step ")));
+        assert!(!folded.contains("<lambda>"));
+
+        let source = "module Steps =\n    let step x = x + 1\n    let go x = Steps.step (Steps.step x)\n\nSteps.go 1\n";
+        let (result, profile) = run_source_with_profile(source).unwrap();
+        assert_eq!(result.as_int(), Some(3));
+        let folded = profile.folded(ProfileMetric::Instructions);
+        assert!(folded
+            .lines()
+            .any(|l| l.starts_with("<main>;Steps.go;Steps.step ")));
+        assert!(!folded.contains("<lambda>"));
+    }
 }
@@ -14,9 +14,26 @@
 //! # Run with disassembly output
 //! fus run --disasm examples/arithmetic.fsx
 //!
+//! # Profile a script, writing collapsed stacks for flamegraph tools
+//! fus run --profile out.folded examples/arithmetic.fsx
+//!
 //! # Evaluate an expression directly
 //! fus run -e "let x = 42 in x + 1"
 //!
+//! # Run the test functions in tests/*.fsx
+//! fus test
+//! fus test tests/math.fsx --filter parse --junit junit.xml
+//!
+//! # Check scripts for errors without running them
+//! fus check src
+//! fus check main.fsx lib.fsx --format json
+//!
+//! # Generate API documentation from /// comments, running their examples
+//! fus doc src --format html -o target/doc
+//!
+//! # Build a self-contained executable that runs the script with its arguments
+//! fus bundle app.fsx -o app
+//!
 //! # Package manager commands (delegates to fpm)
 //! fus pm init              # Initialize a new package
 //! fus pm build             # Build the package
@@ -28,14 +45,26 @@
 //! ```
 
 use colored::*;
-use fusabi::{run_file, run_file_with_disasm, run_source, run_source_with_disasm};
+use fusabi::bundle::{embedded_payload, run_payload, Bundler};
+use fusabi::check::{CheckReport, Checker};
+use fusabi::doc::{DocFormat, DocGenerator, Documentation};
+use fusabi::testing::{TestOutcome, TestReport, TestRunner};
+use fusabi::{
+    run_file_with_disasm, run_file_with_options, run_file_with_profile, run_source,
+    run_source_with_disasm, run_source_with_profile, Profile, ProfileMetric, RunOptions,
+};
+use fusabi_frontend::provider_resolver::resolve_source_paths;
 use fusabi_frontend::{Compiler, Lexer, Parser};
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::process;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Rows in each table of the `--profile` summary
+const PROFILE_SUMMARY_ROWS: usize = 10;
+
 const BANNER: &str = r#"
    ___                _     _
   / __\   _ ___  __ _| |__ (_)
@@ -54,8 +83,12 @@ fn print_help() {
     );
     println!();
     println!("{}", "USAGE:".bold());
-    println!("    fus <COMMAND> [OPTIONS] [FILE]");
+    println!("    fus <COMMAND> [OPTIONS] [FILE] [ARGS...]");
     println!("    fus run -e <EXPRESSION>");
+    println!("    fus test [PATH] [--filter <TEXT>] [--junit <FILE>]");
+    println!("    fus check [PATH...] [--format <human|json>]");
+    println!("    fus doc [PATH...] [--format <markdown|html>] [-o <DIR>] [--no-doctests]");
+    println!("    fus bundle <FILE> [-o <OUTPUT>] [--runtime <EXE>]");
     println!();
     println!("{}", "COMMANDS:".bold());
     println!(
@@ -66,6 +99,22 @@ fn print_help() {
         "    {}               Compile script to .fzb bytecode",
         "grind".truecolor(153, 204, 51)
     );
+    println!(
+        "    {}                Run test functions in tests/*.fsx (or PATH)",
+        "test".truecolor(153, 204, 51)
+    );
+    println!(
+        "    {}               Lex, parse, load and type check without running (default: .)",
+        "check".truecolor(153, 204, 51)
+    );
+    println!(
+        "    {}                 Generate API docs from /// comments and run their examples",
+        "doc".truecolor(153, 204, 51)
+    );
+    println!(
+        "    {}              Build a self-contained executable from a script and its #loads",
+        "bundle".truecolor(153, 204, 51)
+    );
     println!(
         "    {}                  Package manager (delegates to fpm)",
         "pm".truecolor(153, 204, 51)
@@ -76,9 +125,33 @@ fn print_help() {
     println!("    -v, --version       Show version information");
     println!("    -e, --eval <EXPR>   Evaluate an expression directly (run mode only)");
     println!("    -d, --disasm        Show bytecode disassembly before execution");
+    println!("    --profile <FILE>    Profile execution, writing collapsed stacks to FILE");
+    println!("    --profile-metric <time|instructions>");
+    println!("                        Weight collapsed stacks by wall time (us, default) or instructions");
+    println!("    --offline           Resolve remote type provider sources from the cache only");
+    println!(
+        "    --filter <TEXT>     Run only tests whose name (file::test) contains TEXT (test mode)"
+    );
+    println!("    --junit <FILE>      Write test results as JUnit XML (test mode)");
+    println!("    --format <human|json>");
+    println!(
+        "                        Print check errors for people (default) or as JSON (check mode)"
+    );
+    println!("    --format <markdown|html>");
+    println!("                        Write docs as Markdown (default) or HTML (doc mode)");
+    println!("    -o, --output <PATH> Directory to write docs to (doc mode, default: doc),");
+    println!(
+        "                        or executable to write (bundle mode, default: FILE without .fsx)"
+    );
+    println!("    --title <TEXT>      Title of the docs index page (doc mode)");
+    println!("    --no-doctests       Do not run the examples in comments (doc mode)");
+    println!(
+        "    --runtime <EXE>     Executable to bundle the script into (bundle mode, default: fus)"
+    );
     println!();
     println!("{}", "ARGUMENTS:".bold());
     println!("    FILE                Path to .fsx script file");
+    println!("    ARGS                Arguments the script reads with Process.args ()");
     println!();
     println!("{}", "EXAMPLES:".bold());
     println!(
@@ -107,6 +180,46 @@ fn print_help() {
     );
     println!("    fus run --disasm examples/conditionals.fsx");
     println!();
+    println!(
+        "    {}",
+        "# Profile a script (flamegraph.pl out.folded > out.svg)"
+            .italic()
+            .truecolor(128, 128, 128)
+    );
+    println!("    fus run --profile out.folded examples/conditionals.fsx");
+    println!();
+    println!(
+        "    {}",
+        "# Run tests, writing a JUnit report"
+            .italic()
+            .truecolor(128, 128, 128)
+    );
+    println!("    fus test --junit junit.xml");
+    println!();
+    println!(
+        "    {}",
+        "# Check every script under src/ for CI annotations"
+            .italic()
+            .truecolor(128, 128, 128)
+    );
+    println!("    fus check src --format json");
+    println!();
+    println!(
+        "    {}",
+        "# Write HTML docs for the scripts under src/"
+            .italic()
+            .truecolor(128, 128, 128)
+    );
+    println!("    fus doc src --format html -o target/doc");
+    println!();
+    println!(
+        "    {}",
+        "# Ship a script as a single executable (./app <args>)"
+            .italic()
+            .truecolor(128, 128, 128)
+    );
+    println!("    fus bundle app.fsx -o app");
+    println!();
     println!(
         "    {}",
         "# Package manager (init, build, run, add)"
@@ -126,12 +239,41 @@ fn print_help() {
 struct Config {
     mode: Mode,
     disasm: bool,
+    profile: Option<ProfileOutput>,
+    offline: bool,
+}
+
+struct ProfileOutput {
+    path: String,
+    metric: ProfileMetric,
 }
 
 enum Mode {
-    RunFile(String),
+    /// A script and the arguments after it
+    RunFile(String, Vec<String>),
     Eval(String),
     Grind(String),
+    Test {
+        path: String,
+        filter: Option<String>,
+        junit: Option<String>,
+    },
+    Check {
+        paths: Vec<String>,
+        json: bool,
+    },
+    Bundle {
+        script: String,
+        output: Option<String>,
+        runtime: Option<String>,
+    },
+    Doc {
+        paths: Vec<String>,
+        format: DocFormat,
+        output: String,
+        title: Option<String>,
+        doctests: bool,
+    },
     Pm(Vec<String>),
     Help,
     Version,
@@ -142,6 +284,9 @@ fn parse_args() -> Result<Config, String> {
 
     let mut mode = None;
     let mut disasm = false;
+    let mut offline = false;
+    let mut profile_path = None;
+    let mut profile_metric = ProfileMetric::Time;
     let mut i = 1;
 
     // Check for global flags first
@@ -170,6 +315,28 @@ fn parse_args() -> Result<Config, String> {
                             disasm = true;
                             i += 1;
                         }
+                        "--offline" => {
+                            offline = true;
+                            i += 1;
+                        }
+                        "--profile" => {
+                            if i + 1 >= args.len() {
+                                return Err("--profile requires an output file".to_string());
+                            }
+                            profile_path = Some(args[i + 1].clone());
+                            i += 2;
+                        }
+                        "--profile-metric" => {
+                            profile_metric = match args.get(i + 1).map(String::as_str) {
+                                Some("time") => ProfileMetric::Time,
+                                Some("instructions") => ProfileMetric::Instructions,
+                                _ => {
+                                    return Err("--profile-metric must be 'time' or 'instructions'"
+                                        .to_string())
+                                }
+                            };
+                            i += 2;
+                        }
                         "-e" | "--eval" => {
                             if i + 1 >= args.len() {
                                 return Err("--eval requires an expression argument".to_string());
@@ -181,14 +348,15 @@ fn parse_args() -> Result<Config, String> {
                             return Err(format!("Unknown option: {}", arg));
                         }
                         file => {
-                            mode = Some(Mode::RunFile(file.to_string()));
+                            let script_args = args[i + 1..].to_vec();
+                            mode = Some(Mode::RunFile(file.to_string(), script_args));
                             break;
                         }
                     }
                 }
                 // Default to hello.fus if no file specified
                 if mode.is_none() {
-                    mode = Some(Mode::RunFile("examples/hello.fus".to_string()));
+                    mode = Some(Mode::RunFile("examples/hello.fus".to_string(), Vec::new()));
                 }
             }
             "grind" => {
@@ -198,6 +366,162 @@ fn parse_args() -> Result<Config, String> {
                 }
                 mode = Some(Mode::Grind(args[i].clone()));
             }
+            "test" => {
+                i += 1;
+                let mut path = None;
+                let mut filter = None;
+                let mut junit = None;
+                while i < args.len() {
+                    match args[i].as_str() {
+                        "--filter" | "--junit" if i + 1 >= args.len() => {
+                            return Err(format!("{} requires an argument", args[i]));
+                        }
+                        "--filter" => {
+                            filter = Some(args[i + 1].clone());
+                            i += 2;
+                        }
+                        "--junit" => {
+                            junit = Some(args[i + 1].clone());
+                            i += 2;
+                        }
+                        arg if arg.starts_with('-') => {
+                            return Err(format!("Unknown option: {}", arg));
+                        }
+                        arg => {
+                            path = Some(arg.to_string());
+                            i += 1;
+                        }
+                    }
+                }
+                mode = Some(Mode::Test {
+                    path: path.unwrap_or_else(|| "tests".to_string()),
+                    filter,
+                    junit,
+                });
+            }
+            "check" => {
+                i += 1;
+                let mut paths = Vec::new();
+                let mut json = false;
+                while i < args.len() {
+                    match args[i].as_str() {
+                        "--format" => {
+                            json = match args.get(i + 1).map(String::as_str) {
+                                Some("human") => false,
+                                Some("json") => true,
+                                _ => return Err("--format expects human or json".to_string()),
+                            };
+                            i += 2;
+                        }
+                        arg if arg.starts_with('-') => {
+                            return Err(format!("Unknown option: {}", arg));
+                        }
+                        arg => {
+                            paths.push(arg.to_string());
+                            i += 1;
+                        }
+                    }
+                }
+                if paths.is_empty() {
+                    paths.push(".".to_string());
+                }
+                mode = Some(Mode::Check { paths, json });
+            }
+            "bundle" => {
+                i += 1;
+                let mut script = None;
+                let mut output = None;
+                let mut runtime = None;
+                while i < args.len() {
+                    match args[i].as_str() {
+                        "-o" | "--output" => {
+                            output = Some(
+                                args.get(i + 1)
+                                    .ok_or("--output requires a file argument")?
+                                    .clone(),
+                            );
+                            i += 2;
+                        }
+                        "--runtime" => {
+                            runtime = Some(
+                                args.get(i + 1)
+                                    .ok_or("--runtime requires an executable argument")?
+                                    .clone(),
+                            );
+                            i += 2;
+                        }
+                        arg if arg.starts_with('-') => {
+                            return Err(format!("Unknown option: {}", arg));
+                        }
+                        arg if script.is_none() => {
+                            script = Some(arg.to_string());
+                            i += 1;
+                        }
+                        arg => return Err(format!("Unexpected argument: {}", arg)),
+                    }
+                }
+                mode = Some(Mode::Bundle {
+                    script: script.ok_or("bundle requires a script file")?,
+                    output,
+                    runtime,
+                });
+            }
+            "doc" => {
+                i += 1;
+                let mut paths = Vec::new();
+                let mut format = DocFormat::Markdown;
+                let mut output = "doc".to_string();
+                let mut title = None;
+                let mut doctests = true;
+                while i < args.len() {
+                    match args[i].as_str() {
+                        "--format" => {
+                            format = match args.get(i + 1).map(String::as_str) {
+                                Some("markdown") => DocFormat::Markdown,
+                                Some("html") => DocFormat::Html,
+                                _ => return Err("--format expects markdown or html".to_string()),
+                            };
+                            i += 2;
+                        }
+                        "-o" | "--output" => {
+                            output = args
+                                .get(i + 1)
+                                .ok_or("--output requires a directory argument")?
+                                .clone();
+                            i += 2;
+                        }
+                        "--title" => {
+                            title = Some(
+                                args.get(i + 1)
+                                    .ok_or("--title requires an argument")?
+                                    .clone(),
+                            );
+                            i += 2;
+                        }
+                        "--no-doctests" => {
+                            doctests = false;
+                            i += 1;
+                        }
+                        arg if arg.starts_with('-') => {
+                            return Err(format!("Unknown option: {}", arg));
+                        }
+                        arg => {
+                            paths.push(arg.to_string());
+                            i += 1;
+                        }
+                    }
+                }
+                if paths.is_empty() {
+                    paths.push(".".to_string());
+                }
+                mode = Some(Mode::Doc {
+                    paths,
+                    format,
+                    output,
+                    title,
+                    doctests,
+                });
+            }
             "pm" => {
                 i += 1;
                 let subcommands: Vec<String> = args[i..].to_vec();
@@ -208,14 +532,27 @@ fn parse_args() -> Result<Config, String> {
             }
             // If no command specified, treat first arg as file for run mode
             file => {
-                mode = Some(Mode::RunFile(file.to_string()));
+                mode = Some(Mode::RunFile(file.to_string(), args[i + 1..].to_vec()));
             }
         }
     }
 
-    let mode = mode.unwrap_or_else(|| Mode::RunFile("examples/hello.fus".to_string()));
+    let mode = mode.unwrap_or_else(|| Mode::RunFile("examples/hello.fus".to_string(), Vec::new()));
 
-    Ok(Config { mode, disasm })
+    if profile_path.is_some() && disasm {
+        return Err("--profile cannot be combined with --disasm".to_string());
+    }
+    let profile = profile_path.map(|path| ProfileOutput {
+        path,
+        metric: profile_metric,
+    });
+
+    Ok(Config {
+        mode,
+        disasm,
+        profile,
+        offline,
+    })
 }
 
 fn run(config: Config) -> Result<(), Box<dyn std::error::Error>> {
@@ -233,7 +570,11 @@ fn run(config: Config) -> Result<(), Box<dyn std::error::Error>> {
             Ok(())
         }
         Mode::Eval(expr) => {
-            let result = if config.disasm {
+            let result = if let Some(output) = &config.profile {
+                let (result, profile) = run_source_with_profile(&expr)?;
+                write_profile(&profile, output)?;
+                result
+            } else if config.disasm {
                 run_source_with_disasm(&expr, "<eval>")?
             } else {
                 run_source(&expr)?
@@ -241,11 +582,19 @@ fn run(config: Config) -> Result<(), Box<dyn std::error::Error>> {
             println!("{}", result);
             Ok(())
         }
-        Mode::RunFile(path) => {
-            let result = if config.disasm {
-                run_file_with_disasm(&path)?
+        Mode::RunFile(path, args) => {
+            let result = if let Some(output) = &config.profile {
+                let (result, profile) = run_file_with_profile(&path, args)?;
+                write_profile(&profile, output)?;
+                result
+            } else if config.disasm {
+                run_file_with_disasm(&path, args)?
             } else {
-                run_file(&path)?
+                let options = RunOptions {
+                    args,
+                    ..Default::default()
+                };
+                run_file_with_options(&path, options)?
             };
             println!("{}", result);
             Ok(())
@@ -254,10 +603,244 @@ fn run(config: Config) -> Result<(), Box<dyn std::error::Error>> {
             grind_command(&path);
             Ok(())
         }
+        Mode::Test {
+            path,
+            filter,
+            junit,
+        } => test_command(&path, filter, junit.as_deref()),
+        Mode::Check { paths, json } => check_command(&paths, json),
+        Mode::Bundle {
+            script,
+            output,
+            runtime,
+        } => bundle_command(&script, output, runtime),
+        Mode::Doc {
+            paths,
+            format,
+            output,
+            title,
+            doctests,
+        } => doc_command(&paths, format, &output, title, doctests),
         Mode::Pm(subcommands) => pm_command(subcommands),
     }
 }
 
+/// Run the tests at `path`, exiting with status 1 if any fails
+fn test_command(
+    path: &str,
+    filter: Option<String>,
+    junit: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut runner = TestRunner::new();
+    if let Some(filter) = filter {
+        runner = runner.filter(filter);
+    }
+    let report = runner.run(path)?;
+    print_test_report(&report);
+
+    if let Some(junit) = junit {
+        fs::write(junit, report.to_junit_xml())?;
+    }
+    if !report.is_success() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Check the scripts at `paths`, exiting with status 1 if any has errors
+fn check_command(paths: &[String], json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let report = Checker::new().check_all(paths)?;
+    if json {
+        print!("{}", report.to_json());
+    } else {
+        print_check_report(&report);
+    }
+    if !report.is_success() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Bundle the script at `script` into an executable, by default next to the
+/// script and named after it
+fn bundle_command(
+    script: &str,
+    output: Option<String>,
+    runtime: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(script).with_extension(env::consts::EXE_EXTENSION));
+    let mut bundler = Bundler::new();
+    if let Some(runtime) = runtime {
+        bundler = bundler.runtime(runtime);
+    }
+    let size = bundler.bundle(script, &output)?;
+    println!(
+        "{} {} into {} ({} bytes)",
+        "Bundled".truecolor(153, 204, 51).bold(),
+        script,
+        output.display(),
+        size
+    );
+    Ok(())
+}
+
+/// Run the script bundled into this executable with the command-line
+/// arguments, and exit
+fn run_bundled(payload: &[u8]) -> ! {
+    match run_payload(payload, env::args().skip(1).collect()) {
+        Ok(_) => process::exit(0),
+        Err(err) => {
+            eprintln!("{} {}", "Error:".truecolor(183, 65, 14).bold(), err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Write the docs of the scripts at `paths` to `output`, exiting with
+/// status 1 if an example fails
+fn doc_command(
+    paths: &[String],
+    format: DocFormat,
+    output: &str,
+    title: Option<String>,
+    doctests: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut generator = DocGenerator::new().doctests(doctests);
+    if let Some(title) = title {
+        generator = generator.title(title);
+    }
+    let docs = generator.document_all(paths)?;
+    docs.write(output, format)?;
+    print_doc_report(&docs, output);
+    if !docs.doctests.iter().all(|doctest| doctest.passed()) {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn print_doc_report(docs: &Documentation, output: &str) {
+    for doctest in &docs.doctests {
+        let status = if doctest.passed() {
+            "ok".truecolor(153, 204, 51)
+        } else {
+            "FAILED".truecolor(183, 65, 14).bold()
+        };
+        println!(
+            "doctest {}::{} ... {}",
+            doctest.path.display(),
+            doctest.item,
+            status
+        );
+    }
+    for doctest in &docs.doctests {
+        if let Some(error) = &doctest.error {
+            println!("\n---- {}::{} ----", doctest.path.display(), doctest.item);
+            print!("{}", doctest.source);
+            println!("Error: {}", error);
+        }
+    }
+
+    let failed = docs.doctests.iter().filter(|d| !d.passed()).count();
+    let result = if failed == 0 {
+        "ok".truecolor(153, 204, 51).bold()
+    } else {
+        "FAILED".truecolor(183, 65, 14).bold()
+    };
+    println!(
+        "\ndoc result: {}. {} written to {}; {} passed; {} failed",
+        result,
+        plural(docs.pages.len(), "page"),
+        output,
+        plural(docs.doctests.len() - failed, "doctest"),
+        failed
+    );
+}
+
+/// `count` followed by `noun`, in the plural unless `count` is 1
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+fn print_check_report(report: &CheckReport) {
+    for diagnostic in &report.diagnostics {
+        print!("{}", diagnostic.report);
+        println!();
+    }
+
+    let result = if report.is_success() {
+        "ok".truecolor(153, 204, 51).bold()
+    } else {
+        "FAILED".truecolor(183, 65, 14).bold()
+    };
+    println!(
+        "check result: {}. {} checked; {}",
+        result,
+        plural(report.files.len(), "file"),
+        plural(report.diagnostics.len(), "error")
+    );
+}
+
+fn print_test_report(report: &TestReport) {
+    for suite in &report.suites {
+        for case in &suite.cases {
+            let status = match case.outcome {
+                TestOutcome::Passed => "ok".truecolor(153, 204, 51),
+                TestOutcome::Failed(_) => "FAILED".truecolor(183, 65, 14).bold(),
+                TestOutcome::Errored(_) => "ERROR".truecolor(183, 65, 14).bold(),
+            };
+            println!("test {}::{} ... {}", suite.name(), case.name, status);
+        }
+    }
+
+    let failed: Vec<_> = report
+        .suites
+        .iter()
+        .flat_map(|suite| suite.cases.iter().map(move |case| (suite, case)))
+        .filter_map(|(suite, case)| case.failure().map(|failure| (suite, case, failure)))
+        .collect();
+    if !failed.is_empty() {
+        println!();
+        println!("{}", "failures:".bold());
+        for (suite, case, failure) in failed {
+            println!();
+            println!("---- {}::{} ----", suite.name(), case.name);
+            print!("{}", failure.report);
+        }
+    }
+
+    let result = if report.is_success() {
+        "ok".truecolor(153, 204, 51).bold()
+    } else {
+        "FAILED".truecolor(183, 65, 14).bold()
+    };
+    println!();
+    println!(
+        "test result: {}. {} passed; {} failed; {} errors; finished in {:.2}s",
+        result,
+        report.passed(),
+        report.failures(),
+        report.errors(),
+        report.duration().as_secs_f64()
+    );
+}
+
+/// Write collapsed stacks to the output file and a summary to stderr
+fn write_profile(profile: &Profile, output: &ProfileOutput) -> std::io::Result<()> {
+    fs::write(&output.path, profile.folded(output.metric))?;
+    eprintln!("{}", profile.summary(PROFILE_SUMMARY_ROWS));
+    eprintln!(
+        "{} {}",
+        "Profile written to".truecolor(153, 204, 51).bold(),
+        output.path
+    );
+    Ok(())
+}
+
 fn grind_command(file_path: &str) {
     let source = match fs::read_to_string(file_path) {
         Ok(s) => s,
@@ -282,13 +865,16 @@ fn grind_command(file_path: &str) {
     };
 
     let mut parser = Parser::new(tokens);
-    let program = match parser.parse_program() {
-        Ok(p) => p,
-        Err(e) => {
+    let (mut program, errors) = parser.parse_program_recovering();
+    if !errors.is_empty() {
+        for e in &errors {
             eprintln!("{} {}", "Parse error:".truecolor(183, 65, 14).bold(), e);
-            process::exit(1);
         }
-    };
+        process::exit(1);
+    }
+    if let Some(dir) = Path::new(file_path).parent() {
+        resolve_source_paths(&mut program, dir);
+    }
 
     let chunk = match Compiler::compile_program(&program) {
         Ok(c) => c,
@@ -428,6 +1014,13 @@ fn pm_command(subcommands: Vec<String>) -> Result<(), Box<dyn std::error::Error>
 }
 
 fn main() {
+    let payload = env::current_exe()
+        .ok()
+        .and_then(|exe| embedded_payload(exe).ok().flatten());
+    if let Some(payload) = payload {
+        run_bundled(&payload);
+    }
+
     let config = match parse_args() {
         Ok(config) => config,
         Err(err) => {
@@ -440,6 +1033,11 @@ fn main() {
         }
     };
 
+    // Type providers read offline mode from the environment
+    if config.offline {
+        env::set_var("FUSABI_OFFLINE", "1");
+    }
+
     if let Err(err) = run(config) {
         eprintln!("{} {}", "Error:".truecolor(183, 65, 14).bold(), err);
         process::exit(1);
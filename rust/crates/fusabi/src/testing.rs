@@ -0,0 +1,546 @@
+//! Script test runner
+//!
+//! A test is a top-level function of a `.fsx` file whose name starts with
+//! `test`:
+//!
+//! ```fsharp
+//! let testAddition () =
+//!     Test.equal 4 (2 + 2)
+//!
+//! let testReverseTwice () =
+//!     Test.forAll (Gen.list Gen.int) (fun xs -> List.reverse (List.reverse xs) = xs)
+//! ```
+//!
+//! [`TestRunner`] calls each test with `()` in a fresh VM, so tests do not
+//! share state. A test passes if it returns, fails if an assertion of the
+//! `Test` module fails, and errors on any other runtime error. `fus test` and
+//! `fpm test` are built on it.
+//!
+//! ```no_run
+//! use fusabi::testing::TestRunner;
+//!
+//! let report = TestRunner::new().filter("parser").run("tests").unwrap();
+//! std::fs::write("junit.xml", report.to_junit_xml()).unwrap();
+//! assert!(report.is_success());
+//! ```
+
+use crate::{parse_recovering, register_script_eval_override, script_dir, FusabiError};
+use fusabi_frontend::provider_resolver::resolve_source_paths;
+use fusabi_frontend::{Compiler, Expr, Lexer, Literal, ModuleItem, Parser, Program};
+use fusabi_vm::{DebugHook, RuntimeError, SourceSpan, Vm, VmError};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Prefix of the names of test functions
+pub const TEST_PREFIX: &str = "test";
+
+/// How a test ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestOutcome {
+    Passed,
+    /// An assertion failed
+    Failed(TestFailure),
+    /// Another error was raised, or the file did not compile
+    Errored(TestFailure),
+}
+
+/// Why a test did not pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestFailure {
+    /// The error message
+    pub message: String,
+    /// Where the error was raised in the test file, if known
+    pub span: Option<SourceSpan>,
+    /// The message with its location and a source snippet
+    pub report: String,
+}
+
+/// A test that was run
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    /// Name of the test function
+    pub name: String,
+    pub outcome: TestOutcome,
+    pub duration: Duration,
+}
+
+impl TestCase {
+    pub fn passed(&self) -> bool {
+        self.outcome == TestOutcome::Passed
+    }
+
+    /// The failure, if the test failed or errored
+    pub fn failure(&self) -> Option<&TestFailure> {
+        match &self.outcome {
+            TestOutcome::Passed => None,
+            TestOutcome::Failed(failure) | TestOutcome::Errored(failure) => Some(failure),
+        }
+    }
+}
+
+/// The tests of one file
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestSuite {
+    pub path: PathBuf,
+    pub cases: Vec<TestCase>,
+}
+
+impl TestSuite {
+    /// File stem of the test file, used to qualify test names
+    pub fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.count(|outcome| matches!(outcome, TestOutcome::Failed(_)))
+    }
+
+    pub fn errors(&self) -> usize {
+        self.count(|outcome| matches!(outcome, TestOutcome::Errored(_)))
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.cases.iter().map(|case| case.duration).sum()
+    }
+
+    fn count(&self, predicate: impl Fn(&TestOutcome) -> bool) -> usize {
+        self.cases
+            .iter()
+            .filter(|case| predicate(&case.outcome))
+            .count()
+    }
+}
+
+/// The results of a test run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TestReport {
+    pub suites: Vec<TestSuite>,
+}
+
+impl TestReport {
+    pub fn tests(&self) -> usize {
+        self.suites.iter().map(|suite| suite.cases.len()).sum()
+    }
+
+    pub fn passed(&self) -> usize {
+        self.tests() - self.failures() - self.errors()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.suites.iter().map(TestSuite::failures).sum()
+    }
+
+    pub fn errors(&self) -> usize {
+        self.suites.iter().map(TestSuite::errors).sum()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.suites.iter().map(TestSuite::duration).sum()
+    }
+
+    /// Whether no test failed or errored
+    pub fn is_success(&self) -> bool {
+        self.failures() == 0 && self.errors() == 0
+    }
+
+    /// The report in the JUnit XML format read by CI systems
+    pub fn to_junit_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuites name=\"fusabi\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+            self.tests(),
+            self.failures(),
+            self.errors(),
+            self.duration().as_secs_f64()
+        );
+        for suite in &self.suites {
+            let name = escape_xml(&suite.name());
+            let _ = writeln!(
+                xml,
+                "  <testsuite name=\"{}\" file=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+                name,
+                escape_xml(&suite.path.display().to_string()),
+                suite.cases.len(),
+                suite.failures(),
+                suite.errors(),
+                suite.duration().as_secs_f64()
+            );
+            for case in &suite.cases {
+                let _ = write!(
+                    xml,
+                    "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                    escape_xml(&case.name),
+                    name,
+                    case.duration.as_secs_f64()
+                );
+                let (tag, failure) = match &case.outcome {
+                    TestOutcome::Passed => {
+                        xml.push_str("/>\n");
+                        continue;
+                    }
+                    TestOutcome::Failed(failure) => ("failure", failure),
+                    TestOutcome::Errored(failure) => ("error", failure),
+                };
+                let _ = writeln!(
+                    xml,
+                    ">\n      <{} message=\"{}\">{}</{}>\n    </testcase>",
+                    tag,
+                    escape_xml(&failure.message),
+                    escape_xml(&failure.report),
+                    tag
+                );
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+/// Discovers and runs script tests
+#[derive(Debug, Clone, Default)]
+pub struct TestRunner {
+    filter: Option<String>,
+}
+
+impl TestRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only run tests whose qualified name (`file::test`) contains `filter`
+    pub fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.filter = Some(filter.into());
+        self
+    }
+
+    /// The test files at `path`: the file itself, or the `.fsx` files of a
+    /// directory in name order
+    pub fn discover(path: impl AsRef<Path>) -> Result<Vec<PathBuf>, FusabiError> {
+        let path = path.as_ref();
+        if path.is_file() {
+            return Ok(vec![path.to_path_buf()]);
+        }
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let file = entry?.path();
+            if file.is_file() && file.extension().is_some_and(|ext| ext == "fsx") {
+                files.push(file);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Run the tests of the file or directory at `path`
+    ///
+    /// Files without selected tests are left out of the report.
+    pub fn run(&self, path: impl AsRef<Path>) -> Result<TestReport, FusabiError> {
+        let mut report = TestReport::default();
+        for file in Self::discover(path)? {
+            let suite = self.run_file(&file)?;
+            if !suite.cases.is_empty() {
+                report.suites.push(suite);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Run the tests of one file
+    ///
+    /// A file that does not parse is reported as a single errored test named
+    /// after the file.
+    pub fn run_file(&self, path: impl AsRef<Path>) -> Result<TestSuite, FusabiError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        let mut suite = TestSuite {
+            path: path.to_path_buf(),
+            cases: Vec::new(),
+        };
+        let file = path.display().to_string();
+
+        let program = match parse(&source, path) {
+            Ok(program) => program,
+            Err(error) => {
+                let name = suite.name();
+                if self.selects(&name, &name) {
+                    suite.cases.push(TestCase {
+                        name,
+                        outcome: TestOutcome::Errored(TestFailure {
+                            report: format!("Error: {}\n  --> {}\n", error, file),
+                            message: error.to_string(),
+                            span: None,
+                        }),
+                        duration: Duration::ZERO,
+                    });
+                }
+                return Ok(suite);
+            }
+        };
+
+        let suite_name = suite.name();
+        for name in test_names(&program) {
+            if !self.selects(&suite_name, &name) {
+                continue;
+            }
+            let start = Instant::now();
+            let outcome = run_test(&program, &name, &source, &file);
+            suite.cases.push(TestCase {
+                name,
+                outcome,
+                duration: start.elapsed(),
+            });
+        }
+        Ok(suite)
+    }
+
+    fn selects(&self, suite: &str, test: &str) -> bool {
+        self.filter.as_ref().map_or(true, |filter| {
+            format!("{}::{}", suite, test).contains(filter)
+        })
+    }
+}
+
+/// Parse `source`, read from `path`
+fn parse(source: &str, path: &Path) -> Result<Program, FusabiError> {
+    let tokens = Lexer::new(source).tokenize()?;
+    let mut program = parse_recovering(Parser::new(tokens).with_spans())?;
+    resolve_source_paths(&mut program, script_dir(path));
+    Ok(program)
+}
+
+/// Names of the top-level functions that are tests, in source order
+fn test_names(program: &Program) -> Vec<String> {
+    let is_test =
+        |name: &str, value: &Expr| name.starts_with(TEST_PREFIX) && value.unspanned().is_lambda();
+    let mut names = Vec::new();
+    for item in &program.items {
+        match item {
+            ModuleItem::Let(Some(name), value) if is_test(name, value) => names.push(name.clone()),
+            ModuleItem::LetRec(bindings) => names.extend(
+                bindings
+                    .iter()
+                    .filter(|(name, value)| is_test(name, value))
+                    .map(|(name, _)| name.clone()),
+            ),
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Run the program with `name ()` as its main expression in a fresh VM
+fn run_test(program: &Program, name: &str, source: &str, file: &str) -> TestOutcome {
+    let mut program = program.clone();
+    program.main_expr = Some(Expr::App {
+        func: Box::new(Expr::Var(name.to_string())),
+        arg: Box::new(Expr::Lit(Literal::Unit)),
+    });
+
+    let chunk = match Compiler::compile_program(&program) {
+        Ok(chunk) => chunk,
+        Err(error) => {
+            return TestOutcome::Errored(TestFailure {
+                report: format!("Error: {}\n  --> {}\n", error, file),
+                message: error.to_string(),
+                span: None,
+            })
+        }
+    };
+
+    let mut vm = Vm::new();
+    fusabi_vm::stdlib::register_stdlib(&mut vm);
+    register_script_eval_override(&mut vm);
+    let location = Arc::new(Mutex::new(None));
+    vm.set_debug_hook(Box::new(ErrorLocation(location.clone())));
+
+    let error = match vm.execute(chunk) {
+        Ok(_) => return TestOutcome::Passed,
+        Err(error) => error,
+    };
+    let span = *location.lock().unwrap();
+    let mut runtime_error = RuntimeError::new(error.clone()).with_file(file);
+    if let Some(span) = span {
+        runtime_error = runtime_error.with_span(span);
+    }
+    let failure = TestFailure {
+        message: error.to_string(),
+        span,
+        report: runtime_error.format(Some(source)),
+    };
+    match error {
+        VmError::AssertionFailed(_) => TestOutcome::Failed(failure),
+        _ => TestOutcome::Errored(failure),
+    }
+}
+
+/// Records where the last runtime error was raised: the innermost frame with
+/// a known span at its faulting instruction
+struct ErrorLocation(Arc<Mutex<Option<SourceSpan>>>);
+
+impl DebugHook for ErrorLocation {
+    fn on_instruction(&mut self, _vm: &Vm) -> Result<(), VmError> {
+        Ok(())
+    }
+
+    fn on_error(&mut self, vm: &Vm, _error: &VmError) {
+        let span = vm.frames().iter().rev().find_map(|frame| {
+            frame
+                .closure
+                .chunk
+                .span_at(frame.ip.saturating_sub(1))
+                .filter(SourceSpan::is_known)
+        });
+        *self.0.lock().unwrap() = span;
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_test_file(dir: &Path, name: &str, source: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, source).unwrap();
+        path
+    }
+
+    const MATH: &str = r#"let double x = x * 2
+
+let testDouble () =
+    Test.equal 4 (double 2)
+
+let testWrong () =
+    Test.equal 5 (double 2)
+
+let testCrash () =
+    1 / 0
+
+let helper () = 1
+"#;
+
+    #[test]
+    fn test_run_file() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        let path = write_test_file(dir, "math.fsx", MATH);
+
+        let suite = TestRunner::new().run_file(&path).unwrap();
+        let names: Vec<&str> = suite.cases.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["testDouble", "testWrong", "testCrash"]);
+
+        assert!(suite.cases[0].passed());
+        match &suite.cases[1].outcome {
+            TestOutcome::Failed(failure) => {
+                assert_eq!(failure.message, "Assertion failed: expected 5, got 4");
+                assert_eq!(failure.span.map(|s| s.line), Some(7));
+                assert!(failure.report.contains("math.fsx:7:"));
+                assert!(failure.report.contains("Test.equal 5 (double 2)"));
+            }
+            other => panic!("expected a failure, got {:?}", other),
+        }
+        assert!(matches!(suite.cases[2].outcome, TestOutcome::Errored(_)));
+    }
+
+    #[test]
+    fn test_run_directory_with_filter() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        write_test_file(dir, "math.fsx", MATH);
+        write_test_file(
+            dir,
+            "strings.fsx",
+            "let testConcat () = Test.equal \"ab\" (\"a\" ++ \"b\")\n",
+        );
+        write_test_file(
+            dir,
+            "notes.txt",
+            "let testIgnored () = Test.fail \"not a test file\"\n",
+        );
+
+        let report = TestRunner::new().run(dir).unwrap();
+        assert_eq!(report.suites.len(), 2);
+        assert_eq!(report.tests(), 4);
+        assert_eq!(report.passed(), 2);
+        assert_eq!(report.failures(), 1);
+        assert_eq!(report.errors(), 1);
+        assert!(!report.is_success());
+
+        let report = TestRunner::new().filter("strings::").run(dir).unwrap();
+        assert_eq!(report.suites.len(), 1);
+        assert_eq!(report.suites[0].name(), "strings");
+        assert!(report.is_success());
+
+        let report = TestRunner::new().filter("Double").run(dir).unwrap();
+        assert_eq!(report.tests(), 1);
+    }
+
+    #[test]
+    fn test_parse_error_is_reported() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        let path = write_test_file(dir, "broken.fsx", "let testBroken () = (1 +\n");
+
+        let suite = TestRunner::new().run_file(&path).unwrap();
+        assert_eq!(suite.cases.len(), 1);
+        assert_eq!(suite.cases[0].name, "broken");
+        assert!(matches!(suite.cases[0].outcome, TestOutcome::Errored(_)));
+    }
+
+    #[test]
+    fn test_junit_xml() {
+        let report = TestReport {
+            suites: vec![TestSuite {
+                path: PathBuf::from("tests/math.fsx"),
+                cases: vec![
+                    TestCase {
+                        name: "testOk".to_string(),
+                        outcome: TestOutcome::Passed,
+                        duration: Duration::from_millis(2),
+                    },
+                    TestCase {
+                        name: "testBad".to_string(),
+                        outcome: TestOutcome::Failed(TestFailure {
+                            message: "expected \"a\", got <b>".to_string(),
+                            span: None,
+                            report: "Error: expected \"a\", got <b>\n".to_string(),
+                        }),
+                        duration: Duration::ZERO,
+                    },
+                ],
+            }],
+        };
+
+        let xml = report.to_junit_xml();
+        assert!(xml.contains(
+            "<testsuites name=\"fusabi\" tests=\"2\" failures=\"1\" errors=\"0\" time=\"0.002\">"
+        ));
+        assert!(xml.contains("<testsuite name=\"math\" file=\"tests/math.fsx\" tests=\"2\""));
+        assert!(xml.contains("<testcase name=\"testOk\" classname=\"math\" time=\"0.002\"/>"));
+        assert!(xml.contains("<failure message=\"expected &quot;a&quot;, got &lt;b&gt;\">"));
+        assert!(xml.ends_with("</testsuites>\n"));
+    }
+}
@@ -0,0 +1,46 @@
+//! Tests for `fus bundle`: the bundled executable runs the script, and the
+//! files it loads, with its command-line arguments.
+
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn test_bundled_executable_runs_script_with_args() {
+    let temp = TempDir::new().unwrap();
+    let dir = temp.path();
+    fs::write(
+        dir.join("lib.fsx"),
+        "module Greeting =\n    let greet name = String.concat [\"Hello, \"; name]\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("app.fsx"),
+        "#load \"lib.fsx\"\n\nlet args = Process.args ()\nlet _ = List.map (fun a -> printfn (Greeting.greet a)) args\n",
+    )
+    .unwrap();
+
+    let app = dir
+        .join("app")
+        .with_extension(std::env::consts::EXE_EXTENSION);
+    let status = Command::new(env!("CARGO_BIN_EXE_fus"))
+        .arg("bundle")
+        .arg(dir.join("app.fsx"))
+        .arg("-o")
+        .arg(&app)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Options of fus are arguments of the script
+    let output = Command::new(&app)
+        .args(["Ann", "--help"])
+        .current_dir(std::env::temp_dir())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Hello, Ann\nHello, --help\n"
+    );
+}
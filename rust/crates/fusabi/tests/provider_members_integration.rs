@@ -0,0 +1,70 @@
+//! Integration tests for the `parse` and `serialize` members of providers
+//!
+//! Runs Fusabi scripts that parse documents into the records a
+//! `TomlProvider` or `JsonSchemaProvider` declaration generates.
+
+use fusabi::run_source;
+use fusabi_vm::Value;
+
+fn str_value(s: &str) -> Value {
+    Value::Str(s.to_string())
+}
+
+#[test]
+fn test_toml_parse_validates() {
+    let source = r#"
+        type Config = TomlProvider<"name = \"app\"\nport = 8080">
+
+        let describe text =
+            match Config.parse text with
+            | Ok(config) -> config.name
+            | Error(message) -> message
+        in
+        let good = describe "name = \"web\"\nport = 80" in
+        let bad = describe "name = \"web\"\nport = \"80\"" in
+        good ++ " / " ++ bad
+    "#;
+    assert_eq!(
+        run_source(source).unwrap(),
+        str_value("web / port: expected int, found string")
+    );
+}
+
+#[test]
+fn test_toml_serialize() {
+    let source = r#"
+        type Config = TomlProvider<"name = \"app\"\nport = 8080">
+
+        match Config.parse "port = 80\nname = \"web\"" with
+        | Ok(config) -> Config.serialize config
+        | Error(message) -> message
+    "#;
+    assert_eq!(
+        run_source(source).unwrap(),
+        str_value("name = \"web\"\nport = 80\n")
+    );
+}
+
+#[test]
+fn test_json_schema_parse() {
+    let source = r#"
+        type Person = JsonSchemaProvider<"{ \"properties\": { \"name\": { \"type\": \"string\" }, \"role\": { \"enum\": [\"read-only\", \"admin\"] } }, \"required\": [\"name\"] }">
+
+        let role text =
+            match Person.parse text with
+            | Ok(person) ->
+                (match person.role with
+                 | Some(ReadOnly) -> person.name ++ " reads"
+                 | Some(Admin) -> person.name ++ " administers"
+                 | None -> person.name ++ " has no role")
+            | Error(message) -> message
+        in
+        let first = role "{ \"name\": \"ada\", \"role\": \"read-only\" }" in
+        let second = role "{ \"role\": \"admin\" }" in
+        first ++ " / " ++ second
+    "#;
+    assert_eq!(
+        run_source(source).unwrap(),
+        str_value("ada reads / name: missing field")
+    );
+}
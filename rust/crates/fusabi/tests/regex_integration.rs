@@ -0,0 +1,77 @@
+//! Integration tests for the Regex stdlib module and RegexProvider
+//!
+//! Runs Fusabi scripts that match log lines through the stdlib functions
+//! and through the members a `RegexProvider` declaration generates.
+
+use fusabi::run_source;
+use fusabi_vm::Value;
+
+fn str_value(s: &str) -> Value {
+    Value::Str(s.to_string())
+}
+
+#[test]
+fn test_regex_stdlib() {
+    let source = r#"
+        let line = "1700000000 WARN disk 91% full" in
+        if Regex.isMatch "^\d+ " line then
+            Regex.replace "(\d+)%" "$1 percent" line
+        else
+            "no match"
+    "#;
+    assert_eq!(
+        run_source(source).unwrap(),
+        str_value("1700000000 WARN disk 91 percent full")
+    );
+
+    let source = r#"
+        let fields = Regex.split "\s*,\s*" "a, b ,c" in
+        List.length fields
+    "#;
+    assert_eq!(run_source(source).unwrap(), Value::Int(3));
+
+    let source = r#"
+        match Regex.find "\d+" "no digits" with
+        | Some(n) -> n
+        | None -> "none"
+    "#;
+    assert_eq!(run_source(source).unwrap(), str_value("none"));
+}
+
+#[test]
+fn test_regex_provider_try_match() {
+    let source = r#"
+        type LogEntry = RegexProvider<"(?P<ts>\d+) (?P<level>\w+) (?P<msg>.*)">
+
+        let describe line =
+            match LogEntry.tryMatch line with
+            | Some(entry) -> entry.level ++ ": " ++ entry.msg
+            | None -> "unparsed"
+        in
+        let parsed = describe "1700000000 ERROR disk full" in
+        let unparsed = describe "garbage" in
+        parsed ++ " / " ++ unparsed
+    "#;
+    assert_eq!(
+        run_source(source).unwrap(),
+        str_value("ERROR: disk full / unparsed")
+    );
+}
+
+#[test]
+fn test_regex_provider_is_match() {
+    let source = r#"
+        type Version = RegexProvider<"^v(?P<major>\d+)\.(?P<minor>\d+)$">
+        Version.isMatch "v1.2"
+    "#;
+    assert_eq!(run_source(source).unwrap(), Value::Bool(true));
+}
+
+#[test]
+fn test_regex_provider_invalid_pattern() {
+    let result = run_source(
+        r#"type Broken = RegexProvider<"(?P<x>\d+">
+42"#,
+    );
+    assert!(result.is_err());
+}
@@ -0,0 +1,73 @@
+//! Tests for `fus run`: the arguments after the script are the script's
+//! command-line arguments.
+
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn test_run_passes_trailing_args_to_script() {
+    let temp = TempDir::new().unwrap();
+    let dir = temp.path();
+    let script = dir.join("app.fsx");
+    fs::write(
+        &script,
+        "let _ = List.map (fun a -> printfn a) (Process.args ())\nList.length (Process.args ())\n",
+    )
+    .unwrap();
+
+    // Options after the script are arguments of the script
+    for args in [vec!["run"], vec!["run", "--disasm"], vec![]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_fus"))
+            .args(&args)
+            .arg(&script)
+            .args(["a.txt", "--verbose"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "fus {:?}", args);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.ends_with("a.txt\n--verbose\n2\n"), "{}", stdout);
+    }
+
+    let folded = dir.join("out.folded");
+    let output = Command::new(env!("CARGO_BIN_EXE_fus"))
+        .arg("run")
+        .arg("--profile")
+        .arg(&folded)
+        .arg(&script)
+        .arg("a.txt")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with("a.txt\n1\n"));
+}
+
+#[test]
+fn test_run_reads_provider_sources_next_to_script() {
+    let temp = TempDir::new().unwrap();
+    let dir = temp.path();
+    fs::create_dir_all(dir.join("rv")).unwrap();
+    fs::write(dir.join("rv/config.toml"), "name = \"app\"\nport = 8080\n").unwrap();
+    fs::write(
+        dir.join("rv/tp.fsx"),
+        "type Config = TomlProvider<\"config.toml\">\n\
+         match Config.parse \"name = \\\"web\\\"\\nport = 80\" with\n\
+         | Ok(config) -> config.port\n\
+         | Error(message) -> 0\n",
+    )
+    .unwrap();
+
+    // The source is found from another working directory
+    for args in [vec!["run"], vec!["run", "--disasm"], vec!["check"]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_fus"))
+            .current_dir(dir)
+            .args(&args)
+            .arg("rv/tp.fsx")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "fus {:?}: {}", args, stderr);
+    }
+}
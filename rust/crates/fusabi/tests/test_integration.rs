@@ -331,3 +331,96 @@ mod complex_integration_tests {
         assert_eq!(result, Value::Int(1));
     }
 }
+
+#[cfg(test)]
+mod function_call_tests {
+    use super::*;
+
+    #[test]
+    fn test_call_in_argument() {
+        let source = "let g = fun x -> x + 1 in let f = fun x -> x * 2 in g (f 2)";
+        let result = run_source(source).expect("Failed to execute nested call");
+        assert_eq!(result, Value::Int(5));
+    }
+
+    #[test]
+    fn test_call_in_host_function_argument() {
+        let source = r#"String.length ((fun s -> s ++ "c") "ab")"#;
+        let result = run_source(source).expect("Failed to execute nested host call");
+        assert_eq!(result, Value::Int(3));
+    }
+
+    #[test]
+    fn test_match_in_expression() {
+        let source = "1 + (match Some 2 with | Some(x) -> x | None -> 0)";
+        let result = run_source(source).expect("Failed to execute match in expression");
+        assert_eq!(result, Value::Int(3));
+    }
+
+    #[test]
+    fn test_matches_falling_through_in_operands() {
+        let source = "(match 1 with | 0 -> 0 | n -> n) + (match 2 with | 0 -> 0 | 1 -> 1 | n -> n)";
+        let result = run_source(source).expect("Failed to execute matches");
+        assert_eq!(result, Value::Int(3));
+    }
+
+    #[test]
+    fn test_match_binding_in_last_arm() {
+        let source = r#"let f r = match r with | Ok(v) -> v | Error(m) -> m in f (Error "bad")"#;
+        let result = run_source(source).expect("Failed to execute match");
+        assert_eq!(result, Value::Str("bad".to_string()));
+    }
+
+    #[test]
+    fn test_top_level_functions_call_each_other() {
+        let source = "let double x = x * 2\nlet quad x = double (double x)\nlet offset = 1\nlet f () = quad 3 + offset\nf ()";
+        let result = run_source(source).expect("Failed to execute top-level functions");
+        assert_eq!(result, Value::Int(13));
+    }
+
+    #[test]
+    fn test_function_uses_computed_top_level_value() {
+        let source = "let cfg = List.length [1; 2]\nlet f x = x + cfg\nf 1";
+        let result = run_source(source).expect("Failed to execute computed top-level value");
+        assert_eq!(result, Value::Int(3));
+    }
+
+    #[test]
+    fn test_top_level_recursive_functions() {
+        let source = "let rec fact n = if n <= 1 then 1 else n * fact (n - 1)\nfact 5";
+        let result = run_source(source).expect("Failed to execute recursive function");
+        assert_eq!(result, Value::Int(120));
+
+        let source = "let rec even n = if n = 0 then true else odd (n - 1)\nand odd n = if n = 0 then false else even (n - 1)\neven 10";
+        let result = run_source(source).expect("Failed to execute mutually recursive functions");
+        assert_eq!(result, Value::Bool(true));
+    }
+
+    #[test]
+    fn test_local_recursive_functions() {
+        let source = "let f x = (let rec go k = if k > 3 then k else go (k + 1) in go x) in f 0";
+        let result = run_source(source).expect("Failed to execute local recursive function");
+        assert_eq!(result, Value::Int(4));
+
+        let source = "let f x =\n    let rec even n = if n = 0 then true else odd (n - 1)\n    and odd n = if n = 0 then false else even (n - 1)\n    in\n    let rec sum acc k = if k = 0 then acc else sum (acc + k) (k - 1) in\n    (even x, sum 0 x)\nin f 10";
+        let result = run_source(source).expect("Failed to execute local recursive functions");
+        assert_eq!(
+            result,
+            Value::Tuple(vec![Value::Bool(true), Value::Int(55)])
+        );
+    }
+
+    #[test]
+    fn test_function_captures_parameters_and_locals() {
+        let source = "let add x y = x + y\nlet n = 5 in (fun z -> n + z) (add 1 2)";
+        let result = run_source(source).expect("Failed to execute closures");
+        assert_eq!(result, Value::Int(8));
+    }
+
+    #[test]
+    fn test_function_keeps_binding_it_was_defined_with() {
+        let source = "let x = 1\nlet f () = x\nlet x = 2\nf () + x";
+        let result = run_source(source).expect("Failed to execute shadowed binding");
+        assert_eq!(result, Value::Int(3));
+    }
+}
@@ -145,6 +145,7 @@ fn test_run_with_options_type_checking_disabled() {
         enable_type_checking: false,
         verbose: false,
         strict_mode: false,
+        args: Vec::new(),
     };
     let result = run_source_with_options("5 + 10", options);
     assert!(result.is_ok());
@@ -157,6 +158,7 @@ fn test_run_with_options_type_checking_enabled() {
         enable_type_checking: true,
         verbose: false,
         strict_mode: false,
+        args: Vec::new(),
     };
     let result = run_source_with_options("5 + 10", options);
     assert!(result.is_ok());
@@ -169,6 +171,7 @@ fn test_run_with_options_verbose() {
         enable_type_checking: true,
         verbose: true, // This will print to stdout during test
         strict_mode: false,
+        args: Vec::new(),
     };
     let result = run_source_with_options("42", options);
     assert!(result.is_ok());